@@ -0,0 +1,36 @@
+//! Grinder throughput against the old way of drawing keys: the real grind
+//! loop (batched seeds from one `thread_rng` per worker) next to a loop that
+//! calls `Keypair::new()`, and so `getrandom(2)`, once per key. The baseline
+//! skips the base58 tail filter, so the measured gain errs on the low side.
+//!
+//! `cargo bench --bench grind [seconds]`, one thread, 5 seconds per side by default.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use pinpet_suffix_generator::pet::benchmark;
+use solana_sdk::signature::{Keypair, Signer};
+
+fn per_key_keypairs(duration: Duration) -> f64 {
+    let start = Instant::now();
+    let mut keys = 0u64;
+    while start.elapsed() < duration {
+        for _ in 0..1024 {
+            black_box(Keypair::new().pubkey().to_bytes());
+        }
+        keys += 1024;
+    }
+    keys as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`; the first number is the duration
+    let seconds = std::env::args().skip(1).find_map(|arg| arg.parse().ok()).unwrap_or(5);
+    let duration = Duration::from_secs(seconds);
+
+    let baseline = per_key_keypairs(duration);
+    let batched = benchmark(1, duration).keys_per_second();
+    println!("Keypair::new() per key  {:>10.0} keys/s", baseline);
+    println!("batched seeds           {:>10.0} keys/s", batched);
+    println!("speedup                 {:>10.2}x", batched / baseline);
+}
@@ -0,0 +1,95 @@
+//! Store/pop throughput while the cold path scans the database: `WORKERS`
+//! threads store and pop addresses while two tasks read diagnostics and the
+//! dispensed archive in a loop.
+//!
+//! `cargo bench --bench store_pop`
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use pinpet_suffix_generator::pet::{DispensedFilter, DispensedRecord, PetAddress, PetStorage, Requester, StoreError, DEFAULT_POOL};
+
+const WORKERS: usize = 4;
+const PER_WORKER: usize = 25_000;
+
+fn address(worker: usize, i: usize) -> PetAddress {
+    let address = format!("BenchAddress{}x{}", worker, i);
+    PetAddress {
+        public_key: address.clone(),
+        private_key: format!("secret-{}-{}", worker, i).into(),
+        address,
+    }
+}
+
+#[tokio::main(flavor = "multi_thread", worker_threads = 4)]
+async fn main() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join(format!("pinpet-store-pop-bench-{}", std::process::id()));
+    let storage = Arc::new(PetStorage::new(&dir)?);
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let readers: Vec<_> = (0..2)
+        .map(|_| {
+            let (storage, stop) = (Arc::clone(&storage), Arc::clone(&stop));
+            tokio::spawn(async move {
+                let filter = DispensedFilter { requester: None, since: None, limit: 200 };
+                let mut scans = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    storage.diagnostics().await?;
+                    storage.dispensed(&filter)?;
+                    scans += 1;
+                }
+                anyhow::Ok(scans)
+            })
+        })
+        .collect();
+
+    let started = Instant::now();
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|worker| {
+            let storage = Arc::clone(&storage);
+            tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+                for i in 0..PER_WORKER {
+                    // Refused stores (`Busy`) are retried and show up in the refused count
+                    while let Err(e) = storage.store_address(address(worker, i)) {
+                        if !matches!(e.downcast_ref(), Some(StoreError::Busy { .. })) {
+                            return Err(e);
+                        }
+                        storage.wait_for_writer();
+                    }
+                    match storage.get_next_address() {
+                        Ok(Some(info)) => storage.record_dispensed(vec![DispensedRecord::new(DEFAULT_POOL, &info, &Requester::default())]),
+                        Ok(None) => {}
+                        Err(e) if matches!(e.downcast_ref(), Some(StoreError::Busy { .. })) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                Ok(())
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.await??;
+    }
+    storage.flush().await?;
+    let elapsed = started.elapsed();
+
+    stop.store(true, Ordering::Relaxed);
+    let mut scans = 0;
+    for reader in readers {
+        scans += reader.await??;
+    }
+    let refused = storage.diagnostics().await?.refused_operations;
+    println!(
+        "{} stores and pops in {:.2?} ({:.0} ops/s), {} cold scans, {} refused",
+        WORKERS * PER_WORKER,
+        elapsed,
+        (2 * WORKERS * PER_WORKER) as f64 / elapsed.as_secs_f64(),
+        scans,
+        refused
+    );
+
+    drop(storage);
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
@@ -0,0 +1,12 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        // protox parses the proto in Rust, so no protoc install is needed
+        let descriptors = protox::compile(["proto/pinpet.proto"], ["proto"]).expect("Failed to parse proto/pinpet.proto");
+        tonic_build::configure()
+            .build_client(cfg!(test))
+            .compile_fds(descriptors)
+            .expect("Failed to generate gRPC code");
+        println!("cargo:rerun-if-changed=proto/pinpet.proto");
+    }
+}
@@ -0,0 +1,281 @@
+//! Command-line interface: `pinpet-suffix-generator [COMMAND] [FLAGS]`.
+//! Without a command the server is started.
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::config::ConfigSources;
+use crate::pet::{DumpFormat, KeyFileFormat, DEFAULT_POOL};
+
+/// Default number of addresses printed by `generate`
+pub const DEFAULT_GENERATE_COUNT: usize = 10;
+
+/// Default duration of `benchmark` in seconds
+pub const DEFAULT_BENCHMARK_SECONDS: u64 = 10;
+
+pub const USAGE: &str = "\
+Usage: pinpet-suffix-generator [COMMAND] [FLAGS]
+
+Commands:
+  serve              Run the HTTP server (default)
+                       --force                     quarantine unreadable records instead of refusing to start
+                       --restore-snapshot <KEY>    refill the pools from a snapshot (or `latest`) first
+  generate           Grind addresses locally, print them and the rate; no server, no database
+                       --count <N>                 addresses to generate (default 10)
+                       --pattern <SUFFIX>          grind [a-z]{SUFFIX} instead of the configured pattern
+                       --out <PATH>                write the keys to a new file (mode 0600) in
+                                                   the dump format `import` reads
+                       --format <FORMAT>           format of --out: jsonl (default), csv or keypair
+  export             Write every stored address of a pool, private keys included
+                       --pool <NAME>               pool name or tenant:{name} (default `default`)
+                       --format <FORMAT>           jsonl (default), csv or keypair (Solana CLI keyfile)
+                       --output <PATH>             file to write (default stdout)
+  import             Load a dump produced by `export` into a pool
+                       --pool, --format            as for export
+                       --input <PATH>              file to read (default stdin)
+  keyfile            Write the secret key of one stored address, found in any pool
+                       --address <PUBKEY>          public key of the address (required)
+                       --format <FORMAT>           json (default, Solana CLI keyfile), base58 or hex
+                       --output <PATH>             new file to write (mode 0600; default stdout)
+  verify             Check and repair the database, print a report
+                       --force                     quarantine unreadable records
+  benchmark          Measure keys/sec and store the result for /api/v1/estimate
+                       --seconds <N>               duration (default 10)
+  migrate-to-redb    Copy the sled database into storage.redb_path
+
+Global flags:
+  --config <PATH>    config file to read instead of config.toml (TOML, YAML or JSON)
+  --set <KEY=VALUE>  override a setting, e.g. --set server.port=8080 (repeatable)
+  -h, --help         print this help
+
+Every command except serve, generate and benchmark opens the database, so
+run it while the server is stopped.";
+
+/// Flags that take a value
+const VALUE_FLAGS: &[&str] = &[
+    "--config",
+    "--set",
+    "--count",
+    "--pattern",
+    "--out",
+    "--seconds",
+    "--restore-snapshot",
+    "--pool",
+    "--format",
+    "--output",
+    "--input",
+    "--address",
+];
+
+/// Flags from before there were commands, still accepted in place of one
+const LEGACY_COMMANDS: &[(&str, &str)] = &[
+    ("--dry-run", "generate"),
+    ("--bench", "benchmark"),
+    ("--verify", "verify"),
+    ("--migrate-to-redb", "migrate-to-redb"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Serve { force: bool, restore_snapshot: Option<String> },
+    Generate { count: usize, pattern: Option<String>, format: DumpFormat, output: Option<String> },
+    Export { pool: String, format: DumpFormat, output: Option<String> },
+    Import { pool: String, format: DumpFormat, input: Option<String> },
+    Keyfile { address: String, format: KeyFileFormat, output: Option<String> },
+    Verify { force: bool },
+    Benchmark { seconds: u64 },
+    MigrateToRedb,
+    Help,
+}
+
+/// Parsed command line
+#[derive(Debug, Clone)]
+pub struct Cli {
+    pub sources: ConfigSources,
+    pub command: Command,
+}
+
+impl Cli {
+    /// Parse the arguments after the program name
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut command = None;
+        let mut flags: Vec<(&str, Option<&str>)> = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if let Some((_, legacy)) = LEGACY_COMMANDS.iter().find(|(flag, _)| flag == arg) {
+                command.get_or_insert(*legacy);
+            } else if VALUE_FLAGS.contains(&arg.as_str()) {
+                let value = args.next().ok_or_else(|| anyhow!("{} requires a value", arg))?;
+                flags.push((arg, Some(value)));
+            } else if arg.starts_with('-') {
+                flags.push((arg, None));
+            } else if command.is_none() {
+                command = Some(arg);
+            } else {
+                bail!("Unexpected argument '{}'\n\n{}", arg, USAGE);
+            }
+        }
+
+        let mut parsed = Flags { flags };
+        if parsed.present("--help") || parsed.present("-h") {
+            return Ok(Self {
+                sources: ConfigSources::default(),
+                command: Command::Help,
+            });
+        }
+
+        let mut sources = ConfigSources {
+            file: parsed.take("--config"),
+            ..Default::default()
+        };
+        while let Some(assignment) = parsed.take("--set") {
+            sources.add_override(&assignment)?;
+        }
+
+        let command = match command.unwrap_or("serve") {
+            "serve" => Command::Serve {
+                force: parsed.switch("--force"),
+                restore_snapshot: parsed.take("--restore-snapshot"),
+            },
+            "generate" => Command::Generate {
+                count: parsed.parse("--count")?.unwrap_or(DEFAULT_GENERATE_COUNT),
+                pattern: parsed.take("--pattern"),
+                format: parsed.format()?,
+                output: parsed.take("--out").or_else(|| parsed.take("--output")),
+            },
+            "export" => Command::Export {
+                pool: parsed.take("--pool").unwrap_or_else(|| DEFAULT_POOL.to_string()),
+                format: parsed.format()?,
+                output: parsed.take("--output"),
+            },
+            "import" => Command::Import {
+                pool: parsed.take("--pool").unwrap_or_else(|| DEFAULT_POOL.to_string()),
+                format: parsed.format()?,
+                input: parsed.take("--input"),
+            },
+            "keyfile" => Command::Keyfile {
+                address: parsed.take("--address").ok_or_else(|| anyhow!("keyfile requires --address"))?,
+                format: match parsed.take("--format") {
+                    Some(format) => KeyFileFormat::parse(&format).ok_or_else(|| anyhow!("--format must be json, base58 or hex"))?,
+                    None => KeyFileFormat::default(),
+                },
+                output: parsed.take("--output"),
+            },
+            "verify" => Command::Verify {
+                force: parsed.switch("--force"),
+            },
+            "benchmark" => Command::Benchmark {
+                seconds: parsed.parse("--seconds")?.unwrap_or(DEFAULT_BENCHMARK_SECONDS),
+            },
+            "migrate-to-redb" => Command::MigrateToRedb,
+            other => bail!("Unknown command '{}'\n\n{}", other, USAGE),
+        };
+
+        // Anything left over does not apply to this command
+        if let Some((flag, _)) = parsed.flags.first() {
+            bail!("Unknown flag '{}' for this command\n\n{}", flag, USAGE);
+        }
+        Ok(Self { sources, command })
+    }
+}
+
+/// Flags not consumed yet
+struct Flags<'a> {
+    flags: Vec<(&'a str, Option<&'a str>)>,
+}
+
+impl Flags<'_> {
+    fn present(&self, flag: &str) -> bool {
+        self.flags.iter().any(|(name, _)| *name == flag)
+    }
+
+    fn switch(&mut self, flag: &str) -> bool {
+        let before = self.flags.len();
+        self.flags.retain(|(name, _)| *name != flag);
+        self.flags.len() < before
+    }
+
+    /// Value of the first occurrence of `flag`
+    fn take(&mut self, flag: &str) -> Option<String> {
+        let index = self.flags.iter().position(|(name, _)| *name == flag)?;
+        self.flags.remove(index).1.map(String::from)
+    }
+
+    fn parse<T>(&mut self, flag: &str) -> Result<Option<T>>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.take(flag)
+            .map(|value| value.parse().map_err(|e| anyhow!("Invalid {} value: {}", flag, e)))
+            .transpose()
+    }
+
+    fn format(&mut self) -> Result<DumpFormat> {
+        match self.take("--format") {
+            Some(format) => DumpFormat::parse(&format).ok_or_else(|| anyhow!("--format must be jsonl, csv or keypair")),
+            None => Ok(DumpFormat::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Cli> {
+        Cli::parse(&args.split_whitespace().map(String::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_commands_and_flags() {
+        let cli = parse("").unwrap();
+        assert_eq!(
+            cli.command,
+            Command::Serve {
+                force: false,
+                restore_snapshot: None
+            }
+        );
+
+        let cli = parse("--config /etc/pinpet.yaml export --pool tenant:staging --format csv --set server.port=1").unwrap();
+        assert_eq!(cli.sources.file.as_deref(), Some("/etc/pinpet.yaml"));
+        assert_eq!(cli.sources.overrides, vec![("server.port".to_string(), "1".to_string())]);
+        assert_eq!(
+            cli.command,
+            Command::Export {
+                pool: "tenant:staging".to_string(),
+                format: DumpFormat::Csv,
+                output: None
+            }
+        );
+
+        // Flags from before the commands still work
+        let generate = |count, pattern: Option<&str>, output: Option<&str>| Command::Generate {
+            count,
+            pattern: pattern.map(String::from),
+            format: DumpFormat::JsonLines,
+            output: output.map(String::from),
+        };
+        assert_eq!(parse("--dry-run --count 3").unwrap().command, generate(3, None, None));
+        assert_eq!(
+            parse("generate --pattern Pet --count 10 --out keys.jsonl").unwrap().command,
+            generate(10, Some("Pet"), Some("keys.jsonl"))
+        );
+        assert_eq!(parse("--verify --force").unwrap().command, Command::Verify { force: true });
+        assert_eq!(
+            parse("keyfile --address AbcPet --format hex").unwrap().command,
+            Command::Keyfile {
+                address: "AbcPet".to_string(),
+                format: KeyFileFormat::Hex,
+                output: None
+            }
+        );
+        assert!(parse("keyfile --format json").is_err());
+
+        assert!(parse("generate --seconds 3").is_err());
+        assert!(parse("benchmark --seconds").is_err());
+        assert!(parse("export --format xml").is_err());
+        assert!(parse("frobnicate").is_err());
+        assert_eq!(parse("verify --help").unwrap().command, Command::Help);
+    }
+}
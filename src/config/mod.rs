@@ -1,7 +1,16 @@
 use anyhow::Result;
-use config::{Config, ConfigError, Environment, File};
+use config::{Config, ConfigError, Environment, File, FileFormat};
 use serde::{Deserialize, Serialize};
 
+mod profile;
+mod secrets;
+mod validate;
+
+pub use profile::{Profile, PROFILE_ENV};
+pub use secrets::{Secrets, VAULT_TOKEN_ENV};
+pub use validate::InvalidConfig;
+
+/// Settings of the server; every section is documented in `config.toml`
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct AppConfig {
     pub server: ServerConfig,
@@ -9,24 +18,127 @@ pub struct AppConfig {
     pub logging: LoggingConfig,
     pub swagger: SwaggerConfig,
     pub pet_generator: PetGeneratorConfig,
+    #[serde(default)]
     pub rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub idempotency: IdempotencyConfig,
+    #[serde(default)]
+    pub reconciliation: ReconciliationConfig,
+    #[serde(default)]
+    pub jobs: JobsConfig,
+    #[serde(default)]
+    pub leases: LeaseConfig,
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub alerts: AlertConfig,
+    /// Separate pools for other environments sharing this server
+    #[serde(default)]
+    pub tenants: Vec<TenantConfig>,
+    #[serde(default)]
+    pub expiry: ExpiryConfig,
+    #[serde(default)]
+    pub backup: BackupConfig,
+    #[serde(default)]
+    pub startup: StartupConfig,
+    #[serde(default)]
+    pub preflight: PreflightConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub grpc: GrpcConfig,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub jwt: JwtConfig,
+    #[serde(default)]
+    pub ip_filter: IpFilterConfig,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub signing: SigningConfig,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    #[serde(default)]
+    pub probes: ProbeConfig,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    #[serde(default)]
+    pub webhooks: WebhookConfig,
+    #[serde(default)]
+    pub workers: WorkersConfig,
+    #[serde(default)]
+    pub sharding: ShardingConfig,
+    #[serde(default)]
+    pub vault: VaultConfig,
+    /// Where this configuration was read from, read again on reload
+    #[serde(skip)]
+    pub sources: ConfigSources,
+    /// Filled by `load_secrets`
+    #[serde(skip)]
+    pub secrets: Secrets,
+    /// Selected by `APP_ENV`
+    #[serde(skip)]
+    pub profile: Option<Profile>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// How long shutdown waits for open connections (e.g. event streams) to finish
+    #[serde(default = "default_shutdown_timeout_seconds")]
+    pub shutdown_timeout_seconds: u64,
+    /// Listen on `host:port`; turn off together with `unix_socket` to have no network listener
+    #[serde(default = "default_tcp")]
+    pub tcp: bool,
+    /// Also serve the HTTP API on a Unix domain socket at this path (Unix only)
+    #[serde(default)]
+    pub unix_socket: Option<String>,
+    /// Permissions of the socket file, e.g. 0o660 to admit the file's group
+    #[serde(default = "default_unix_socket_mode")]
+    pub unix_socket_mode: u32,
+}
+
+fn default_shutdown_timeout_seconds() -> u64 {
+    10
+}
+
+fn default_tcp() -> bool {
+    true
+}
+
+fn default_unix_socket_mode() -> u32 {
+    0o600
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ApiConfig {
     pub base_path: String,
     pub version: String,
+    /// Also serve the current version without the version segment
+    /// (`/api/pet/address`), marked deprecated, for clients that do not pin one
+    #[serde(default = "default_unversioned_paths")]
+    pub unversioned_paths: bool,
+}
+
+fn default_unversioned_paths() -> bool {
+    true
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LoggingConfig {
     pub level: String,
+    /// `json` for one JSON object per line (with the request span fields)
+    /// or `text`. Overridden by the `LOG_FORMAT` environment variable.
     pub format: String,
 }
 
@@ -44,34 +156,982 @@ pub struct PetGeneratorConfig {
     pub pool_size: usize,
     pub batch_size: usize,
     pub db_path: String,
+    /// Addresses must end with a lowercase letter followed by this suffix
+    #[serde(default = "default_target_suffix")]
+    pub target_suffix: String,
+    /// Optional regex (e.g. "^Pin", "[a-z]Pet$"); overrides `target_suffix` when set
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// When false, "pet", "PET" and "Pet" all satisfy the suffix (or regex)
+    #[serde(default = "default_case_sensitive")]
+    pub case_sensitive: bool,
+    /// Optional combined prefix + suffix constraint; overrides `target_suffix` when set
+    #[serde(default)]
+    pub vanity: Option<crate::pet::PatternSpec>,
+    /// Additional `[a-z]{suffix}` pools ground in the same loop, each with its own queue
+    #[serde(default)]
+    pub extra_suffixes: Vec<String>,
+    /// Grinder worker threads per address; 0 uses every available core.
+    /// Overridden by the `GENERATOR_THREADS` environment variable.
+    #[serde(default)]
+    pub threads: usize,
+    /// Hard limit on queued addresses per pool (at least `pool_size`); stores
+    /// beyond it are refused. Unlimited when unset.
+    #[serde(default)]
+    pub max_queue_size: Option<usize>,
+    /// Queued addresses per pool kept in memory; later ones stay in sled and
+    /// are paged in as the queue drains. Everything is in memory when unset.
+    #[serde(default)]
+    pub max_in_memory: Option<usize>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+fn default_target_suffix() -> String {
+    crate::pet::DEFAULT_TARGET_SUFFIX.to_string()
+}
+
+fn default_case_sensitive() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
 pub struct RateLimitConfig {
-    pub max_requests_per_minute: u32,
-    pub window_seconds: u64,
+    /// Token-bucket limits per client: the tenant when a known `X-API-Key`
+    /// is sent, otherwise the IP address
+    pub enabled: bool,
+    /// Address fetches, pops and acks; 0 disables the limit for the class
+    pub pop_per_minute: u32,
+    /// Requests a client may make at once after being idle
+    pub pop_burst: u32,
+    /// Status, peek, estimate, events and job routes
+    pub read_per_minute: u32,
+    pub read_burst: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            pop_per_minute: 60,
+            pop_burst: 10,
+            read_per_minute: 600,
+            read_burst: 100,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct IdempotencyConfig {
+    /// How long a key keeps returning the same address
+    pub ttl_seconds: u64,
+    /// Upper bound on cached keys; oldest entries are evicted first
+    pub max_entries: usize,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            ttl_seconds: 300,
+            max_entries: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReconciliationConfig {
+    /// Periodically repair drift between the in-memory queue and sled
+    pub enabled: bool,
+    pub interval_seconds: u64,
+}
+
+impl Default for ReconciliationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_seconds: 300,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct JobsConfig {
+    /// Custom-pattern jobs ground at the same time
+    pub max_concurrent: usize,
+    /// Queued + running jobs accepted before new submissions are rejected
+    pub max_pending: usize,
+    /// Reject patterns expected to need more keys than this per address
+    pub max_expected_attempts: f64,
+    /// How long finished jobs (and their keys) stay retrievable
+    pub retention_seconds: u64,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 1,
+            max_pending: 100,
+            max_expected_attempts: 1e9,
+            retention_seconds: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct WorkersConfig {
+    /// Accept keypairs ground on other machines at `POST /workers/submit`,
+    /// authenticated with the WORKER_API_KEY secret
+    pub enabled: bool,
+    /// Most keypairs in one submission
+    pub max_batch: usize,
+    /// How often registered workers are asked to send a heartbeat
+    pub heartbeat_interval_seconds: u64,
+    /// Workers without a heartbeat for this long are shown as dead and get
+    /// no share of the pools
+    pub timeout_seconds: u64,
+    /// Keys/sec cap handed to each worker as a throttle hint; unset = none
+    pub max_keys_per_second: Option<u64>,
+}
+
+impl Default for WorkersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_batch: 1000,
+            heartbeat_interval_seconds: 30,
+            timeout_seconds: 90,
+            max_keys_per_second: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct ShardingConfig {
+    /// Grind and serve only the addresses whose letter before the suffix is
+    /// in `letters`; letter-specific pops for other letters go to `peers`
+    pub enabled: bool,
+    /// This node's letters, e.g. "a-m" or "a-c,x"
+    pub letters: String,
+    /// Timeout of calls to peers
+    pub timeout_ms: u64,
+    pub peers: Vec<ShardPeerConfig>,
+}
+
+impl Default for ShardingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            letters: "a-z".to_string(),
+            timeout_ms: 2000,
+            peers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ShardPeerConfig {
+    /// Base URL of the node, e.g. "http://pinpet-b:5057"
+    pub url: String,
+    /// Letters it serves
+    pub letters: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct LeaseConfig {
+    /// Pops check addresses out until `POST .../address/{id}/ack` instead of deleting them
+    pub enabled: bool,
+    /// Unacknowledged addresses are requeued after this long
+    pub ttl_seconds: u64,
+    /// How often expired leases are swept back into the queue
+    pub sweep_interval_seconds: u64,
+}
+
+impl Default for LeaseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_seconds: 300,
+            sweep_interval_seconds: 30,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct ExpiryConfig {
+    /// Prune queued addresses older than `max_age_days` from the queue and sled
+    pub enabled: bool,
+    pub max_age_days: u64,
+    /// How often the queue is scanned for stale addresses
+    pub sweep_interval_seconds: u64,
+}
+
+impl Default for ExpiryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_age_days: 30,
+            sweep_interval_seconds: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct StartupConfig {
+    /// Check every sled pool against its records before serving, repairing
+    /// orphans, missing records and duplicate keys
+    pub verify: bool,
+    /// Start even when records are unreadable; they are moved under
+    /// `quarantine:` instead. Usually set with `--force`.
+    pub force: bool,
+}
+
+impl Default for StartupConfig {
+    fn default() -> Self {
+        Self {
+            verify: true,
+            force: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct PreflightConfig {
+    /// Check free disk space, a database write, the clock and every pool's
+    /// pattern before the port is bound; a failed check stops the server
+    pub enabled: bool,
+    /// Refuse to start with less free space on the database volume
+    pub min_free_disk_mb: u64,
+}
+
+impl Default for PreflightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_free_disk_mb: 100,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Serve Prometheus metrics at `/metrics` and record HTTP latencies
+    pub enabled: bool,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct ProbeConfig {
+    /// `/readyz` fails while the default pool holds fewer addresses than this
+    pub min_ready_queue_depth: usize,
+    /// Storage calls slower than this fail `/readyz`, and `/livez` once they hang
+    pub timeout_seconds: u64,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            min_ready_queue_depth: 1,
+            timeout_seconds: 5,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// Switch to degraded mode (reported by `/readyz`, `/metrics` and the
+    /// diagnostics) once sled writes keep failing, e.g. on a full disk
+    pub enabled: bool,
+    /// Failed write batches or flushes in a row that open the breaker
+    pub failure_threshold: u32,
+    /// Refuse new addresses with 503 while degraded; pops keep serving the
+    /// in-memory queue either way
+    pub refuse_stores: bool,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            failure_threshold: crate::pet::persistence::DEFAULT_FAILURE_THRESHOLD,
+            refuse_stores: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct GrpcConfig {
+    /// Serve the gRPC API (requires building with `--features grpc`)
+    pub enabled: bool,
+    /// Listens on `server.host` at this port
+    pub port: u16,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 50051,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct RedactionConfig {
+    /// Pops return only the public fields; private keys are held until revealed
+    /// with the key in the PRIVATE_KEY_REVEAL_API_KEY env var (never from this
+    /// file), or are encrypted to the caller's `X-Recipient-Key`
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Destroy private keys held for reveal once they are older than
+    /// `destroy_after_hours`, keeping a tombstone with the address and times
+    pub enabled: bool,
+    /// Counted from the pop that dispensed the address
+    pub destroy_after_hours: u64,
+    pub sweep_interval_seconds: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            destroy_after_hours: 24,
+            sweep_interval_seconds: 300,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Hash-chained log of every store, pop, ack, export and admin request,
+    /// read at `/admin/audit` and checked at `/admin/audit/verify`
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct SigningConfig {
+    /// Add an `X-Signature` HMAC-SHA256 of the body to API responses, keyed
+    /// with the secret in the RESPONSE_SIGNING_KEY env var (never from this file)
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// gzip / deflate responses for clients that send a matching `Accept-Encoding`
+    pub enabled: bool,
+    /// Bodies smaller than this many bytes are sent as they are; streamed
+    /// bodies of unknown length (exports) are always compressed
+    pub min_size: usize,
+    /// 1 (fastest) to 9 (smallest)
+    pub level: u32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_size: 1024,
+            level: 6,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct CorsConfig {
+    /// Origins browsers may call the API from, e.g. "https://dashboard.example.com";
+    /// "*" allows any origin
+    pub allowed_origins: Vec<String>,
+    /// "*" allows any method
+    pub allowed_methods: Vec<String>,
+    /// Request headers a browser may send; "*" allows any header
+    pub allowed_headers: Vec<String>,
+    /// Response headers scripts may read
+    pub exposed_headers: Vec<String>,
+    /// Allow cookies and `Authorization`; needs explicit origins, methods and headers
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight response
+    pub max_age_seconds: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+        Self {
+            allowed_origins: strings(&["*"]),
+            allowed_methods: strings(&["GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH"]),
+            allowed_headers: strings(&[
+                "authorization",
+                "accept",
+                "accept-language",
+                "content-type",
+                "content-length",
+                "origin",
+                "user-agent",
+                "x-requested-with",
+                "x-request-id",
+                "x-api-key",
+                "idempotency-key",
+                "x-reveal-key",
+                "x-recipient-key",
+            ]),
+            exposed_headers: strings(&["content-length", "content-type", "x-signature", "x-request-id"]),
+            allow_credentials: false,
+            max_age_seconds: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// Serve HTTPS instead of plain HTTP (requires building with `--features tls`)
+    pub enabled: bool,
+    /// PEM certificate chain, leaf first
+    pub cert_path: String,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key_path: String,
+    /// Check both files this often and reload them when they change, so a
+    /// renewed certificate applies without a restart; 0 disables
+    pub reload_interval_seconds: u64,
+    /// PEM bundle of the CA(s) client certificates must be signed by. When
+    /// set, clients may present a certificate; it is verified in the
+    /// handshake, and connections without one are still accepted.
+    pub client_ca_path: String,
+    /// Route groups only served to clients with a verified certificate
+    pub require_client_cert: Vec<ClientCertRoutes>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert_path: "certs/cert.pem".to_string(),
+            key_path: "certs/key.pem".to_string(),
+            reload_interval_seconds: 0,
+            client_ca_path: String::new(),
+            require_client_cert: Vec::new(),
+        }
+    }
+}
+
+impl TlsConfig {
+    pub fn requires_client_cert(&self, routes: ClientCertRoutes) -> bool {
+        self.require_client_cert.contains(&routes)
+    }
+}
+
+/// Route groups that can demand a client certificate (mutual TLS)
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientCertRoutes {
+    /// `/workers/…`: registration, heartbeats and keypair submissions
+    Workers,
+    /// `/replicas/pools/…`: pools served to replicas
+    Replicas,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct JwtConfig {
+    /// Accept `Authorization: Bearer <JWT>` from an identity provider, and
+    /// require it (or a tenant API key) on the address routes
+    pub enabled: bool,
+    /// HS256 uses the shared secret in the JWT_HS256_SECRET env var (never
+    /// from this file); RS256 the keys in `public_key_path`
+    pub algorithm: JwtAlgorithm,
+    /// PEM public key, or a JWKS document whose keys are picked by `kid`
+    pub public_key_path: String,
+    /// Required `iss`; empty accepts any
+    pub issuer: String,
+    /// Required entry of `aud`; empty accepts any
+    pub audience: String,
+    /// Clock skew allowed on `exp` and `nbf`
+    pub leeway_seconds: u64,
+    /// Scope (in `scope` or `scp`) needed for pops, acks and reveals
+    pub pop_scope: String,
+    /// Scope needed for status, peek and the other read-only routes
+    pub read_scope: String,
+    /// Scope accepted on the admin routes in place of the admin bearer token
+    pub admin_scope: String,
+    /// Claim naming the tenant a token may use `/tenants/{name}/…` for
+    pub tenant_claim: String,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            algorithm: JwtAlgorithm::Rs256,
+            public_key_path: String::new(),
+            issuer: String::new(),
+            audience: String::new(),
+            leeway_seconds: 60,
+            pop_scope: "pinpet:pop".to_string(),
+            read_scope: "pinpet:read".to_string(),
+            admin_scope: "pinpet:admin".to_string(),
+            tenant_claim: "tenant".to_string(),
+        }
+    }
+}
+
+/// Addresses allowed to reach each route group, checked before any credentials.
+/// Entries are CIDRs (`10.20.0.0/24`, `fd00::/8`) or single addresses; a deny
+/// match always wins, and an empty allow list lets every other address in.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct IpFilterConfig {
+    pub enabled: bool,
+    /// Only these networks may reach `/admin/…`
+    pub admin_allow: Vec<String>,
+    pub admin_deny: Vec<String>,
+    /// Only these networks may reach the pop, status and `/ws` routes
+    pub consumer_allow: Vec<String>,
+    pub consumer_deny: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    #[serde(rename = "HS256")]
+    Hs256,
+    #[serde(rename = "RS256")]
+    Rs256,
+}
+
+impl JwtAlgorithm {
+    /// Value of the token header's `alg`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Hs256 => "HS256",
+            Self::Rs256 => "RS256",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Upload a snapshot of every sled pool to S3-compatible storage on a
+    /// schedule. Credentials come from AWS_ACCESS_KEY_ID and
+    /// AWS_SECRET_ACCESS_KEY, never from this file.
+    pub enabled: bool,
+    /// e.g. `https://s3.us-east-1.amazonaws.com` or `http://minio:9000`
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    /// Key prefix for snapshot objects
+    pub prefix: String,
+    /// `/{bucket}/{key}` URLs (MinIO); false uses `{bucket}.{host}`
+    pub path_style: bool,
+    pub interval_seconds: u64,
+    /// Number of snapshots kept; older ones are deleted after each upload
+    pub retain: usize,
+    pub timeout_ms: u64,
+    /// Snapshot key (or `latest`) to load into the pools at startup; usually
+    /// set with `serve --restore-snapshot`
+    pub restore_from: Option<String>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            bucket: String::new(),
+            region: "us-east-1".to_string(),
+            prefix: "pinpet/".to_string(),
+            path_style: true,
+            interval_seconds: 3600,
+            retain: 24,
+            timeout_ms: 30_000,
+            restore_from: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// Encrypt private keys at rest with AES-256-GCM. Key material comes from
+    /// the PRIVATE_KEY_ENCRYPTION_KEYS env var (`id:hex,id:hex`), never from this file.
+    pub enabled: bool,
+    /// Key used for new records; other keys in the env var only decrypt
+    pub active_key_id: String,
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            active_key_id: "primary".to_string(),
+        }
+    }
+}
+
+/// Where address queues live
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// Embedded sled database at `pet_generator.db_path`
+    #[default]
+    Sled,
+    /// Shared Redis server (requires the `redis` cargo feature)
+    Redis,
+    /// Shared PostgreSQL database (requires the `postgres` cargo feature)
+    Postgres,
+    /// Embedded redb file at `storage.redb_path` (requires the `redb` cargo feature)
+    Redb,
+    /// Process memory only; nothing is written to disk and everything is lost on restart
+    Memory,
+    /// No local queue: every pool lives on the server at `storage.primary_url`
+    Primary,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    /// `redis://[[user]:password@]host[:port][/db]`
+    pub redis_url: String,
+    /// Namespace for every Redis key, so replicas sharing a pool must agree on it
+    pub redis_prefix: String,
+    /// Idle connections kept open per server
+    pub redis_pool_size: usize,
+    /// Connect, read and write timeout
+    pub redis_timeout_ms: u64,
+    /// `postgres://[user[:password]@]host[:port][/database]`
+    pub postgres_url: String,
+    /// Idle connections kept open to the database
+    pub postgres_pool_size: usize,
+    /// Connect, read and write timeout
+    pub postgres_timeout_ms: u64,
+    /// Database file of the redb backend; also the target of `migrate-to-redb`
+    pub redb_path: String,
+    /// Server holding the pools when this one is a replica (`backend = "primary"`)
+    pub primary_url: String,
+    /// Connect, read and write timeout of calls to the primary
+    pub primary_timeout_ms: u64,
+    /// Client certificate and key (PEM) a replica presents to an `https://`
+    /// primary that requires one; empty sends none
+    pub primary_client_cert_path: String,
+    pub primary_client_key_path: String,
+    /// PEM CA bundle the primary's certificate is checked against instead
+    /// of the system roots, for a private CA; empty uses the system roots
+    pub primary_ca_path: String,
+    /// Serve this server's pools to replicas at `/replicas/pools/{pool}/…`,
+    /// authenticated with the REPLICA_API_KEY secret
+    pub serve_replicas: bool,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            backend: StorageBackend::Sled,
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            redis_prefix: "pinpet".to_string(),
+            redis_pool_size: 8,
+            redis_timeout_ms: 2000,
+            postgres_url: "postgres://postgres@127.0.0.1:5432/pinpet".to_string(),
+            postgres_pool_size: 8,
+            postgres_timeout_ms: 2000,
+            redb_path: "./data/pet_addresses.redb".to_string(),
+            primary_url: "http://127.0.0.1:5057".to_string(),
+            primary_timeout_ms: 2000,
+            primary_client_cert_path: String::new(),
+            primary_client_key_path: String::new(),
+            primary_ca_path: String::new(),
+            serve_replicas: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TenantConfig {
+    /// Served at `/tenants/{name}`; letters, digits, '-' and '_'
+    pub name: String,
+    /// Required in the `X-API-Key` header when set. Better left out and
+    /// supplied as the `TENANT_{NAME}_API_KEY` secret.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Most addresses dispensed per UTC day; unset is unlimited
+    #[serde(default)]
+    pub daily_quota: Option<u64>,
+    /// Most addresses dispensed per UTC calendar month; unset is unlimited
+    #[serde(default)]
+    pub monthly_quota: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct AlertConfig {
+    /// POST to `webhook_url` when a pool drops below `low_watermark`
+    pub enabled: bool,
+    /// `http://` or `https://` endpoint receiving the JSON alerts
+    pub webhook_url: String,
+    /// Alert once a pool holds fewer addresses than this
+    pub low_watermark: usize,
+    /// Depth at which a low pool counts as recovered; defaults to `low_watermark`
+    pub recovery_watermark: Option<usize>,
+    /// How often queue depths are checked
+    pub check_interval_seconds: u64,
+    /// Connect, read and write timeout for the webhook
+    pub timeout_ms: u64,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            webhook_url: String::new(),
+            low_watermark: 10,
+            recovery_watermark: None,
+            check_interval_seconds: 10,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Let consumers register callback URLs that addresses are pushed to
+    pub enabled: bool,
+    /// Most addresses per POST
+    pub batch_size: usize,
+    /// Largest `count` one subscription may ask for
+    pub max_count: usize,
+    /// Active subscriptions accepted at once
+    pub max_active: usize,
+    /// Attempts per batch before the subscription fails
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubles with every further attempt
+    pub retry_backoff_ms: u64,
+    /// Connect, read and write timeout of each attempt
+    pub timeout_ms: u64,
+    /// How often an empty pool is checked again
+    pub poll_interval_ms: u64,
+    /// Hosts callbacks may point at; empty allows any
+    pub allowed_hosts: Vec<String>,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            batch_size: 10,
+            max_count: 10_000,
+            max_active: 100,
+            max_attempts: 5,
+            retry_backoff_ms: 1000,
+            timeout_ms: 5000,
+            poll_interval_ms: 1000,
+            allowed_hosts: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct VaultConfig {
+    /// Read secrets from a HashiCorp Vault KV version 2 secret at startup. The
+    /// token comes from VAULT_TOKEN or the file named by VAULT_TOKEN_FILE.
+    pub enabled: bool,
+    /// e.g. `https://vault.internal:8200`
+    pub address: String,
+    /// Mount path of the KV engine
+    pub mount: String,
+    /// Secret whose fields are named like the variables they replace,
+    /// e.g. `ADMIN_API_KEY` or `PRIVATE_KEY_ENCRYPTION_KEYS`
+    pub path: String,
+    /// Vault Enterprise namespace
+    pub namespace: Option<String>,
+    pub timeout_ms: u64,
+}
+
+impl Default for VaultConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "http://127.0.0.1:8200".to_string(),
+            mount: "secret".to_string(),
+            path: "pinpet".to_string(),
+            namespace: None,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+/// Secret holding the API key of tenant `name`: `TENANT_STAGING_API_KEY` for `staging`
+pub fn tenant_key_name(name: &str) -> String {
+    format!("TENANT_{}_API_KEY", name.to_ascii_uppercase().replace('-', "_"))
+}
+
+/// Where settings come from besides the defaults. Layers apply in order, each
+/// over the previous: config file, `APP_*` environment variables, then
+/// command-line overrides.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    /// File to read instead of `config.*` and `config.{APP_ENV}.*`; the
+    /// format follows the extension (`.toml`, `.yaml`/`.yml` or `.json`)
+    pub file: Option<String>,
+    /// Dotted keys and values, e.g. `("server.port", "8080")`
+    pub overrides: Vec<(String, String)>,
+}
+
+impl ConfigSources {
+    /// Add a `key=value` override as given on the command line
+    pub fn add_override(&mut self, assignment: &str) -> Result<(), ConfigError> {
+        let (key, value) = assignment
+            .split_once('=')
+            .filter(|(key, _)| !key.trim().is_empty())
+            .ok_or_else(|| ConfigError::Message(format!("Expected key=value, got '{}'", assignment)))?;
+        self.overrides.push((key.trim().to_string(), value.to_string()));
+        Ok(())
+    }
 }
 
 impl AppConfig {
     pub fn load() -> Result<Self, ConfigError> {
-        let env = std::env::var("RUST_ENV").unwrap_or_else(|_| "development".into());
-        
-        let builder = Config::builder()
-            // Default configuration file
-            .add_source(File::with_name("config").required(false))
-            // Environment-specific configuration file
-            .add_source(File::with_name(&format!("config.{}", env)).required(false))
-            // Environment variable overrides with underscore separator
-            .add_source(Environment::with_prefix("APP").separator("_"));
+        Self::load_from(&ConfigSources::default())
+    }
+
+    pub fn load_from(sources: &ConfigSources) -> Result<Self, ConfigError> {
+        let profile = Profile::from_env()?;
+        let profile_settings = File::from_str(profile.map_or("", Profile::settings), FileFormat::Toml);
+        let mut builder = Config::builder();
+        builder = match &sources.file {
+            // An explicit file must exist, and beats the profile
+            Some(path) => builder
+                .add_source(profile_settings)
+                .add_source(File::with_name(path).required(true)),
+            None => {
+                let env = std::env::var(PROFILE_ENV)
+                    .or_else(|_| std::env::var("RUST_ENV"))
+                    .unwrap_or_else(|_| "development".into());
+                builder
+                    // Default configuration file
+                    .add_source(File::with_name("config").required(false))
+                    .add_source(profile_settings)
+                    // Environment-specific configuration file
+                    .add_source(File::with_name(&format!("config.{}", env)).required(false))
+            }
+        };
+        // Environment variable overrides: APP_{SECTION}__{KEY}, e.g. APP_PET_GENERATOR__POOL_SIZE
+        builder = builder.add_source(Environment::with_prefix("APP").prefix_separator("_").separator("__"));
+        for (key, value) in &sources.overrides {
+            builder = builder.set_override(key.as_str(), value.as_str())?;
+        }
+
+        let mut config: Self = builder.build()?.try_deserialize()?;
+
+        // Command-line overrides beat the shorthand variables
+        let overridden = |setting: &str| sources.overrides.iter().any(|(key, _)| key == setting);
+        if let Some(threads) = std::env::var("GENERATOR_THREADS").ok().filter(|_| !overridden("pet_generator.threads")) {
+            config.pet_generator.threads = threads.trim().parse().map_err(|_| {
+                ConfigError::Message(format!("GENERATOR_THREADS must be a non-negative integer, got '{}'", threads))
+            })?;
+        }
+        if let Some(format) = std::env::var("LOG_FORMAT").ok().filter(|_| !overridden("logging.format")) {
+            config.logging.format = format;
+        }
 
-        builder.build()?.try_deserialize()
+        config.sources = sources.clone();
+        config.profile = profile;
+        Ok(config)
+    }
+
+    /// Fetch the Vault secret, if enabled, and fill in tenant API keys that
+    /// are not in the file from `TENANT_{NAME}_API_KEY`. Blocks on Vault.
+    pub fn load_secrets(&mut self) -> Result<()> {
+        self.secrets = Secrets::load(&self.vault)?;
+        for tenant in self.tenants.iter_mut().filter(|tenant| tenant.api_key.is_none()) {
+            tenant.api_key = self.secrets.get(&tenant_key_name(&tenant.name))?;
+        }
+        Ok(())
     }
 
     pub fn server_address(&self) -> String {
         format!("{}:{}", self.server.host, self.server.port)
     }
 
+    /// `https` when TLS is enabled, otherwise `http`
+    pub fn scheme(&self) -> &'static str {
+        if self.tls.enabled { "https" } else { "http" }
+    }
+
+    pub fn grpc_address(&self) -> String {
+        format!("{}:{}", self.server.host, self.grpc.port)
+    }
+
+    /// Prefix of routes served without the version segment, if enabled.
+    /// None with an empty `base_path`, as routes cannot be nested at the root.
+    pub fn unversioned_base_url(&self) -> Option<&str> {
+        let base_path = self.api.base_path.trim_end_matches('/');
+        (self.api.unversioned_paths && !base_path.is_empty()).then_some(base_path)
+    }
+
     pub fn api_base_url(&self) -> String {
         format!("{}/{}", self.api.base_path, self.api.version)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line_overrides_beat_the_config_file() {
+        let path = std::env::temp_dir().join(format!("pinpet-config-test-{}.yaml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+server: { host: "127.0.0.1", port: 5057 }
+api: { base_path: "/api", version: "v1" }
+logging: { level: "info", format: "text" }
+swagger: { enabled: false, path: "/swagger-ui", title: "t", description: "d", version: "1" }
+pet_generator: { pool_size: 10, batch_size: 2, db_path: "./data/test.db" }
+"#,
+        )
+        .unwrap();
+
+        let mut sources = ConfigSources {
+            file: Some(path.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        sources.add_override("server.port=6060").unwrap();
+        let config = AppConfig::load_from(&sources).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.server.port, 6060);
+        assert_eq!(config.pet_generator.pool_size, 10);
+        // Kept so a reload reads the same layers
+        assert_eq!(config.sources.file, sources.file);
+        assert_eq!(config.sources.overrides.len(), 1);
+
+        assert!(sources.add_override("no-equals-sign").is_err());
+        sources.file = Some("/nonexistent/pinpet.toml".to_string());
+        assert!(AppConfig::load_from(&sources).is_err());
+    }
+}
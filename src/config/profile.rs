@@ -0,0 +1,121 @@
+//! Named deployment profiles selected by `APP_ENV`. A profile's settings
+//! apply over `config.toml` and under everything else: `config.{APP_ENV}.*`,
+//! an explicit `--config` file, `APP_*` variables and `--set` flags.
+
+use config::ConfigError;
+
+/// Variable naming the profile
+pub const PROFILE_ENV: &str = "APP_ENV";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// Nothing on disk, debug text logs, Swagger UI on
+    Dev,
+    /// sled verified at startup, JSON logs
+    Staging,
+    /// Encrypted sled verified at startup, JSON logs, rate limits on, Swagger UI off
+    Prod,
+}
+
+impl Profile {
+    /// The profile named by `APP_ENV`, if set
+    pub fn from_env() -> Result<Option<Self>, ConfigError> {
+        match std::env::var(PROFILE_ENV).ok().filter(|name| !name.is_empty()) {
+            Some(name) => Self::parse(&name)
+                .map(Some)
+                .ok_or_else(|| ConfigError::Message(format!("{} must be dev, staging or prod, got '{}'", PROFILE_ENV, name))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "dev" | "development" => Some(Self::Dev),
+            "staging" | "stage" => Some(Self::Staging),
+            "prod" | "production" => Some(Self::Prod),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dev => "dev",
+            Self::Staging => "staging",
+            Self::Prod => "prod",
+        }
+    }
+
+    /// Settings of the profile, as TOML
+    pub fn settings(self) -> &'static str {
+        match self {
+            Self::Dev => {
+                r#"
+                logging = { level = "debug", format = "text" }
+                storage = { backend = "memory" }
+                swagger = { enabled = true }
+                startup = { verify = false }
+                "#
+            }
+            Self::Staging => {
+                r#"
+                logging = { level = "info", format = "json" }
+                storage = { backend = "sled" }
+                startup = { verify = true }
+                "#
+            }
+            Self::Prod => {
+                r#"
+                logging = { level = "info", format = "json" }
+                storage = { backend = "sled" }
+                encryption = { enabled = true }
+                startup = { verify = true }
+                rate_limit = { enabled = true }
+                swagger = { enabled = false }
+                "#
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AppConfig, StorageBackend};
+    use config::{Config, File, FileFormat};
+
+    #[test]
+    fn test_profiles_sit_between_the_base_file_and_later_layers() {
+        let base = r#"
+            server = { host = "127.0.0.1", port = 5057 }
+            api = { base_path = "/api", version = "v1" }
+            logging = { level = "info", format = "text" }
+            swagger = { enabled = true, path = "/swagger-ui", title = "t", description = "d", version = "1" }
+            pet_generator = { pool_size = 10, batch_size = 2, db_path = "./data/test.db" }
+        "#;
+        let load = |profile: Profile, later: &str| -> AppConfig {
+            Config::builder()
+                .add_source(File::from_str(base, FileFormat::Toml))
+                .add_source(File::from_str(profile.settings(), FileFormat::Toml))
+                .add_source(File::from_str(later, FileFormat::Toml))
+                .build()
+                .unwrap()
+                .try_deserialize()
+                .unwrap()
+        };
+
+        let dev = load(Profile::Dev, "");
+        assert_eq!(dev.storage.backend, StorageBackend::Memory);
+        assert_eq!(dev.logging.level, "debug");
+
+        let prod = load(Profile::Prod, "rate_limit = { enabled = false }");
+        assert_eq!(prod.storage.backend, StorageBackend::Sled);
+        assert!(prod.encryption.enabled);
+        assert!(!prod.swagger.enabled);
+        assert_eq!(prod.swagger.path, "/swagger-ui");
+        assert_eq!(prod.logging.format, "json");
+        assert!(!prod.rate_limit.enabled);
+
+        assert_eq!(Profile::parse("Production"), Some(Profile::Prod));
+        assert_eq!(Profile::parse("qa"), None);
+    }
+}
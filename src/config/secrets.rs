@@ -0,0 +1,135 @@
+//! Secrets (API keys, encryption keys, credentials) are never read from the
+//! config file. Each is looked up by its variable name, in order: the
+//! variable itself, the file named by `{NAME}_FILE` (Docker and Kubernetes
+//! secrets), then the `[vault]` secret when Vault is enabled.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::VaultConfig;
+use crate::pet::http::{self, Url};
+
+/// Token for Vault; like the other secrets it may come from `VAULT_TOKEN_FILE`
+pub const VAULT_TOKEN_ENV: &str = "VAULT_TOKEN";
+
+/// Values fetched from Vault at startup
+#[derive(Clone, Default)]
+pub struct Secrets {
+    vault: HashMap<String, String>,
+}
+
+/// Only the names, so a logged config never shows a secret
+impl std::fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.vault.keys()).finish()
+    }
+}
+
+impl Secrets {
+    /// Fetch the Vault secret when Vault is enabled; blocks on the request
+    pub fn load(config: &VaultConfig) -> Result<Self> {
+        if !config.enabled {
+            return Ok(Self::default());
+        }
+        let token = read_local(VAULT_TOKEN_ENV)?
+            .with_context(|| format!("Vault is enabled but neither {0} nor {0}_FILE is set", VAULT_TOKEN_ENV))?;
+        let vault = fetch_kv(config, &token).with_context(|| format!("Failed to read Vault secret '{}/{}'", config.mount, config.path))?;
+        Ok(Self { vault })
+    }
+
+    /// The secret `name`, or `None` when no source has it
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        Ok(read_local(name)?.or_else(|| self.vault.get(name).cloned().filter(|value| !value.is_empty())))
+    }
+
+    /// Number of values fetched from Vault
+    pub fn vault_len(&self) -> usize {
+        self.vault.len()
+    }
+}
+
+/// `name` from the environment, or the contents of the file named by `{name}_FILE`
+/// without the trailing newline. Setting both is an error.
+fn read_local(name: &str) -> Result<Option<String>> {
+    let value = std::env::var(name).ok().filter(|value| !value.is_empty());
+    let file_var = format!("{}_FILE", name);
+    let path = std::env::var(&file_var).ok().filter(|path| !path.is_empty());
+    match (value, path) {
+        (Some(_), Some(_)) => bail!("Both {} and {} are set; use one", name, file_var),
+        (Some(value), None) => Ok(Some(value)),
+        (None, Some(path)) => {
+            let contents = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {} '{}'", file_var, path))?;
+            Ok(Some(contents.trim_end_matches(['\r', '\n']).to_string()).filter(|value| !value.is_empty()))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+/// Read a KV version 2 secret: `GET /v1/{mount}/data/{path}`
+fn fetch_kv(config: &VaultConfig, token: &str) -> Result<HashMap<String, String>> {
+    let url = Url::parse(&config.address)?;
+    let path = format!(
+        "{}/v1/{}/data/{}",
+        url.path.trim_end_matches('/'),
+        config.mount.trim_matches('/'),
+        config.path.trim_matches('/')
+    );
+    let mut headers = vec![("X-Vault-Token", token.to_string())];
+    if let Some(namespace) = &config.namespace {
+        headers.push(("X-Vault-Namespace", namespace.clone()));
+    }
+    let response = http::send(&url, "GET", &path, &headers, b"", Duration::from_millis(config.timeout_ms))?;
+    if !response.is_success() {
+        bail!("Vault answered {}", response.describe());
+    }
+    parse_kv(&response.body)
+}
+
+/// The fields of a KV v2 read response; every value must be a string
+fn parse_kv(body: &[u8]) -> Result<HashMap<String, String>> {
+    let response: serde_json::Value = serde_json::from_slice(body).context("Vault response is not JSON")?;
+    let fields = response
+        .pointer("/data/data")
+        .and_then(|data| data.as_object())
+        .ok_or_else(|| anyhow!("Vault response has no data.data object; is the mount a KV version 2 engine?"))?;
+    fields
+        .iter()
+        .map(|(name, value)| match value.as_str() {
+            Some(value) => Ok((name.clone(), value.to_string())),
+            None => Err(anyhow!("Vault field '{}' is not a string", name)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secrets_come_from_variables_files_and_vault() {
+        let name = format!("PINPET_TEST_SECRET_{}", std::process::id());
+        let path = std::env::temp_dir().join(&name);
+        std::fs::write(&path, "from-file\n").unwrap();
+
+        let secrets = Secrets {
+            vault: HashMap::from([(name.clone(), "from-vault".to_string())]),
+        };
+        assert_eq!(secrets.get(&name).unwrap().as_deref(), Some("from-vault"));
+
+        std::env::set_var(format!("{}_FILE", name), &path);
+        assert_eq!(secrets.get(&name).unwrap().as_deref(), Some("from-file"));
+
+        std::env::set_var(&name, "from-env");
+        assert!(secrets.get(&name).is_err());
+        std::env::remove_var(format!("{}_FILE", name));
+        assert_eq!(secrets.get(&name).unwrap().as_deref(), Some("from-env"));
+        std::env::remove_var(&name);
+        std::fs::remove_file(&path).unwrap();
+
+        let body = br#"{"data": {"data": {"ADMIN_API_KEY": "s3cret"}, "metadata": {"version": 3}}}"#;
+        assert_eq!(parse_kv(body).unwrap()["ADMIN_API_KEY"], "s3cret");
+        assert!(parse_kv(br#"{"data": {"ADMIN_API_KEY": "s3cret"}}"#).is_err());
+        assert!(parse_kv(br#"{"data": {"data": {"PORT": 1}}}"#).is_err());
+    }
+}
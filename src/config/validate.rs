@@ -0,0 +1,273 @@
+//! Checks run once at startup, so a bad setting stops the server with every
+//! problem listed instead of failing later, one at a time.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::{AppConfig, JwtAlgorithm, StorageBackend};
+use crate::middleware::IpFilter;
+use crate::pet::AddressMatcher;
+
+/// Every problem found in a configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidConfig {
+    pub problems: Vec<String>,
+}
+
+impl std::fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid configuration ({} problems):", self.problems.len())?;
+        for problem in &self.problems {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidConfig {}
+
+impl AppConfig {
+    /// Check the settings the server needs before anything is opened
+    pub fn validate(&self) -> Result<(), InvalidConfig> {
+        let mut problems = Vec::new();
+        self.check_listeners(&mut problems);
+        self.check_logging(&mut problems);
+        self.check_patterns(&mut problems);
+        self.check_generator(&mut problems);
+        self.check_storage(&mut problems);
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(InvalidConfig { problems }),
+        }
+    }
+
+    fn check_listeners(&self, problems: &mut Vec<String>) {
+        if !self.server.tcp && self.server.unix_socket.is_none() {
+            problems.push("server.tcp is off and server.unix_socket is unset: nothing to listen on".to_string());
+        }
+        if self.server.tcp && self.server.port == 0 {
+            problems.push("server.port must be between 1 and 65535".to_string());
+        }
+        if self.grpc.enabled {
+            if self.grpc.port == 0 {
+                problems.push("grpc.port must be between 1 and 65535".to_string());
+            } else if self.server.tcp && self.grpc.port == self.server.port {
+                problems.push(format!("grpc.port and server.port are both {}; give gRPC its own port", self.grpc.port));
+            }
+        }
+        if self.tls.enabled {
+            for (key, path) in [("tls.cert_path", &self.tls.cert_path), ("tls.key_path", &self.tls.key_path)] {
+                if !Path::new(path).is_file() {
+                    problems.push(format!("{} '{}' is not a readable file", key, path));
+                }
+            }
+            if !self.tls.client_ca_path.is_empty() && !Path::new(&self.tls.client_ca_path).is_file() {
+                problems.push(format!("tls.client_ca_path '{}' is not a readable file", self.tls.client_ca_path));
+            }
+        }
+        if !self.tls.require_client_cert.is_empty() && (!self.tls.enabled || self.tls.client_ca_path.is_empty()) {
+            problems.push("tls.require_client_cert needs tls.enabled and tls.client_ca_path".to_string());
+        }
+        if self.jwt.enabled && self.jwt.algorithm == JwtAlgorithm::Rs256 && !Path::new(&self.jwt.public_key_path).is_file() {
+            problems.push(format!("jwt.public_key_path '{}' is not a readable file (needed for RS256)", self.jwt.public_key_path));
+        }
+        if self.ip_filter.enabled {
+            for (key, allow, deny) in [
+                ("admin", &self.ip_filter.admin_allow, &self.ip_filter.admin_deny),
+                ("consumer", &self.ip_filter.consumer_allow, &self.ip_filter.consumer_deny),
+            ] {
+                if let Err(entries) = IpFilter::new(allow, deny) {
+                    problems.extend(entries.into_iter().map(|entry| format!("ip_filter.{}: {}", key, entry)));
+                }
+            }
+        }
+    }
+
+    fn check_logging(&self, problems: &mut Vec<String>) {
+        let logging = &self.logging;
+        if !["json", "text"].iter().any(|format| logging.format.eq_ignore_ascii_case(format)) {
+            problems.push(format!("logging.format (or LOG_FORMAT) must be json or text, got '{}'", logging.format));
+        }
+        if !["trace", "debug", "info", "warn", "error"].iter().any(|level| logging.level.eq_ignore_ascii_case(level)) {
+            problems.push(format!("logging.level must be trace, debug, info, warn or error, got '{}'", logging.level));
+        }
+    }
+
+    fn check_patterns(&self, problems: &mut Vec<String>) {
+        let generator = &self.pet_generator;
+        if let Err(e) = AddressMatcher::from_config(generator) {
+            problems.push(format!("pet_generator pattern: {:#}", e));
+        }
+
+        let mut suffixes = HashSet::new();
+        for suffix in &generator.extra_suffixes {
+            if !suffixes.insert(suffix) {
+                problems.push(format!("pet_generator.extra_suffixes lists '{}' twice", suffix));
+            } else if let Err(e) = AddressMatcher::suffix(suffix, generator.case_sensitive) {
+                problems.push(format!("pet_generator.extra_suffixes '{}': {:#}", suffix, e));
+            }
+        }
+
+        let mut tenants = HashSet::new();
+        for tenant in &self.tenants {
+            let name = &tenant.name;
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                problems.push(format!("Tenant name '{}' may only use letters, digits, '-' and '_'", name));
+            } else if !tenants.insert(name) {
+                problems.push(format!("Tenant '{}' is configured twice", name));
+            }
+        }
+    }
+
+    fn check_generator(&self, problems: &mut Vec<String>) {
+        let generator = &self.pet_generator;
+        if generator.pool_size == 0 {
+            problems.push("pet_generator.pool_size must be above zero".to_string());
+        }
+        if generator.batch_size == 0 {
+            problems.push("pet_generator.batch_size must be above zero".to_string());
+        }
+        if let Some(max_queue_size) = generator.max_queue_size.filter(|max| *max < generator.pool_size) {
+            problems.push(format!(
+                "pet_generator.max_queue_size ({}) must be at least pool_size ({})",
+                max_queue_size, generator.pool_size
+            ));
+        }
+        if generator.max_in_memory == Some(0) {
+            problems.push("pet_generator.max_in_memory must be above zero".to_string());
+        }
+        if self.circuit_breaker.enabled && self.circuit_breaker.failure_threshold == 0 {
+            problems.push("circuit_breaker.failure_threshold must be above zero".to_string());
+        }
+        if self.retention.enabled && self.retention.destroy_after_hours == 0 {
+            problems.push("retention.destroy_after_hours must be above zero".to_string());
+        }
+        if self.workers.enabled && self.workers.max_batch == 0 {
+            problems.push("workers.max_batch must be above zero".to_string());
+        }
+        if self.sharding.enabled {
+            if let Err(e) = crate::pet::ShardRouter::new(&self.sharding) {
+                problems.push(format!("sharding: {:#}", e));
+            }
+        }
+        if self.workers.enabled && self.workers.timeout_seconds <= self.workers.heartbeat_interval_seconds {
+            problems.push("workers.timeout_seconds must be longer than workers.heartbeat_interval_seconds".to_string());
+        }
+        let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        if generator.threads > cores {
+            problems.push(format!(
+                "pet_generator.threads ({}) exceeds the available cores ({}); use at most {}, or 0 for all of them",
+                generator.threads, cores, cores
+            ));
+        }
+    }
+
+    fn check_storage(&self, problems: &mut Vec<String>) {
+        let (key, path) = match self.storage.backend {
+            // The Redis and PostgreSQL backends keep usage and overrides in sled
+            StorageBackend::Sled | StorageBackend::Redis | StorageBackend::Postgres => ("pet_generator.db_path", &self.pet_generator.db_path),
+            StorageBackend::Redb => ("storage.redb_path", &self.storage.redb_path),
+            StorageBackend::Memory => return,
+            StorageBackend::Primary => {
+                if let Err(e) = crate::pet::http::Url::parse(&self.storage.primary_url) {
+                    problems.push(format!("storage.primary_url '{}': {}", self.storage.primary_url, e));
+                }
+                let storage = &self.storage;
+                if storage.primary_client_cert_path.is_empty() != storage.primary_client_key_path.is_empty() {
+                    problems.push("storage.primary_client_cert_path and primary_client_key_path must be set together".to_string());
+                }
+                for (key, path) in [
+                    ("storage.primary_client_cert_path", &storage.primary_client_cert_path),
+                    ("storage.primary_client_key_path", &storage.primary_client_key_path),
+                    ("storage.primary_ca_path", &storage.primary_ca_path),
+                ] {
+                    if !path.is_empty() && !Path::new(path).is_file() {
+                        problems.push(format!("{} '{}' is not a readable file", key, path));
+                    }
+                }
+                return;
+            }
+        };
+        if let Err(e) = check_writable(Path::new(path)) {
+            problems.push(format!("{} '{}' {}", key, path, e));
+        }
+    }
+}
+
+/// Whether `path` can be written, or created in its nearest existing directory
+fn check_writable(path: &Path) -> Result<(), String> {
+    let mut existing = path;
+    while !existing.exists() {
+        existing = match existing.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+    }
+    if existing.is_file() && existing != path {
+        return Err(format!("cannot be created: '{}' is a file", existing.display()));
+    }
+    if existing.is_file() {
+        return std::fs::OpenOptions::new()
+            .append(true)
+            .open(existing)
+            .map(|_| ())
+            .map_err(|e| format!("is not writable: {}", e));
+    }
+
+    let probe = existing.join(format!(".pinpet-write-check-{}", std::process::id()));
+    std::fs::write(&probe, b"")
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|e| format!("is not writable: {} ('{}')", e, existing.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{Config, File, FileFormat};
+
+    use super::*;
+
+    fn config(toml: &str) -> AppConfig {
+        let base = r#"
+            server = { host = "127.0.0.1", port = 5057 }
+            api = { base_path = "/api", version = "v1" }
+            logging = { level = "info", format = "text" }
+            swagger = { enabled = false, path = "/swagger-ui", title = "t", description = "d", version = "1" }
+        "#;
+        Config::builder()
+            .add_source(File::from_str(base, FileFormat::Toml))
+            .add_source(File::from_str(toml, FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_every_problem_is_reported() {
+        let db_path = std::env::temp_dir().join(format!("pinpet-validate-{}", std::process::id())).join("pet.db");
+        let valid = config(&format!(
+            "pet_generator = {{ pool_size = 10, batch_size = 2, db_path = '{}' }}",
+            db_path.display()
+        ));
+        assert_eq!(valid.validate(), Ok(()));
+
+        let invalid = config(
+            r#"
+            grpc = { enabled = true, port = 5057 }
+            logging = { format = "xml" }
+            pet_generator = { pool_size = 10, batch_size = 0, db_path = "/proc/pinpet/pet.db", extra_suffixes = ["Cat", "Cat", "0x"], threads = 100000, max_queue_size = 5, max_in_memory = 0 }
+            tenants = [{ name = "dev" }, { name = "dev" }, { name = "bad name" }]
+            tls = { require_client_cert = ["workers", "replicas"] }
+            retention = { enabled = true, destroy_after_hours = 0 }
+            jwt = { enabled = true }
+            ip_filter = { enabled = true, admin_allow = ["10.20.0.0/33"] }
+            circuit_breaker = { failure_threshold = 0 }
+            "#,
+        );
+        let problems = invalid.validate().unwrap_err().problems;
+        for expected in ["grpc.port", "LOG_FORMAT", "batch_size", "db_path", "'Cat' twice", "'0x'", "threads", "max_queue_size", "max_in_memory", "'dev' is configured twice", "'bad name'", "tls.require_client_cert", "destroy_after_hours", "jwt.public_key_path", "ip_filter.admin", "circuit_breaker"] {
+            assert!(problems.iter().any(|problem| problem.contains(expected)), "no problem mentions {}: {:?}", expected, problems);
+        }
+        assert_eq!(problems.len(), 16);
+    }
+}
@@ -0,0 +1,280 @@
+//! API errors rendered as RFC 7807 `application/problem+json`. `code` is the
+//! stable, machine-readable part clients branch on; `detail` is for humans
+//! and may change between releases.
+
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Media type of error bodies
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// `type` of a problem is this prefix followed by its code
+pub const PROBLEM_TYPE_PREFIX: &str = "urn:pinpet:problem:";
+
+/// Machine-readable error codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Malformed body, query or header, or a value out of range
+    InvalidRequest,
+    /// The pattern does not compile or can never match a base58 address
+    InvalidPattern,
+    /// `X-Recipient-Key` or a registered key is not a usable public key
+    InvalidRecipientKey,
+    /// Missing or wrong API key, reveal key or admin token
+    Unauthorized,
+    /// The operation is disabled on this server
+    Forbidden,
+    NotFound,
+    /// No pattern pool with this name
+    UnknownPool,
+    /// No tenant with this name
+    UnknownTenant,
+    /// The pool has no address to hand out right now
+    PoolEmpty,
+    MethodNotAllowed,
+    Conflict,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    /// The tenant's daily or monthly quota is used up
+    QuotaExceeded,
+    /// Too many requests from this client; see `Retry-After`
+    RateLimited,
+    /// An `Idempotency-Key` was replayed with a different private key delivery
+    IdempotencyMismatch,
+    /// Too many active webhook subscriptions
+    TooManySubscriptions,
+    /// The storage backend does not support this operation
+    NotImplemented,
+    /// A dependency (storage, Redis) is unavailable
+    Unavailable,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidRequest => "invalid_request",
+            Self::InvalidPattern => "invalid_pattern",
+            Self::InvalidRecipientKey => "invalid_recipient_key",
+            Self::Unauthorized => "unauthorized",
+            Self::Forbidden => "forbidden",
+            Self::NotFound => "not_found",
+            Self::UnknownPool => "unknown_pool",
+            Self::UnknownTenant => "unknown_tenant",
+            Self::PoolEmpty => "pool_empty",
+            Self::MethodNotAllowed => "method_not_allowed",
+            Self::Conflict => "conflict",
+            Self::PayloadTooLarge => "payload_too_large",
+            Self::UnsupportedMediaType => "unsupported_media_type",
+            Self::QuotaExceeded => "quota_exceeded",
+            Self::RateLimited => "rate_limited",
+            Self::IdempotencyMismatch => "idempotency_mismatch",
+            Self::TooManySubscriptions => "too_many_subscriptions",
+            Self::NotImplemented => "not_implemented",
+            Self::Unavailable => "unavailable",
+            Self::Internal => "internal",
+        }
+    }
+
+    pub fn status(self) -> StatusCode {
+        match self {
+            Self::InvalidRequest | Self::InvalidPattern | Self::InvalidRecipientKey => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::NotFound | Self::UnknownPool | Self::UnknownTenant | Self::PoolEmpty => StatusCode::NOT_FOUND,
+            Self::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            Self::Conflict => StatusCode::CONFLICT,
+            Self::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::IdempotencyMismatch => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::QuotaExceeded | Self::RateLimited | Self::TooManySubscriptions => StatusCode::TOO_MANY_REQUESTS,
+            Self::NotImplemented => StatusCode::NOT_IMPLEMENTED,
+            Self::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Short summary; the same for every occurrence of the code
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::InvalidRequest => "Invalid request",
+            Self::InvalidPattern => "Invalid pattern",
+            Self::InvalidRecipientKey => "Invalid recipient key",
+            Self::Unauthorized => "Unauthorized",
+            Self::Forbidden => "Forbidden",
+            Self::NotFound => "Not found",
+            Self::UnknownPool => "Unknown pool",
+            Self::UnknownTenant => "Unknown tenant",
+            Self::PoolEmpty => "Pool is empty",
+            Self::MethodNotAllowed => "Method not allowed",
+            Self::Conflict => "Conflict",
+            Self::PayloadTooLarge => "Payload too large",
+            Self::UnsupportedMediaType => "Unsupported media type",
+            Self::QuotaExceeded => "Quota exceeded",
+            Self::RateLimited => "Rate limited",
+            Self::IdempotencyMismatch => "Idempotency key mismatch",
+            Self::TooManySubscriptions => "Too many subscriptions",
+            Self::NotImplemented => "Not implemented",
+            Self::Unavailable => "Service unavailable",
+            Self::Internal => "Internal server error",
+        }
+    }
+
+    /// Generic code of a bare status, for errors raised outside the handlers
+    pub fn for_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => Self::InvalidRequest,
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            StatusCode::FORBIDDEN => Self::Forbidden,
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::METHOD_NOT_ALLOWED => Self::MethodNotAllowed,
+            StatusCode::CONFLICT => Self::Conflict,
+            StatusCode::PAYLOAD_TOO_LARGE => Self::PayloadTooLarge,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => Self::UnsupportedMediaType,
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited,
+            StatusCode::NOT_IMPLEMENTED => Self::NotImplemented,
+            StatusCode::SERVICE_UNAVAILABLE => Self::Unavailable,
+            _ => Self::Internal,
+        }
+    }
+}
+
+/// RFC 7807 problem details, with the error code and request ID as extension members
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProblemDetails {
+    /// `urn:pinpet:problem:{code}`
+    #[serde(rename = "type")]
+    #[schema(example = "urn:pinpet:problem:pool_empty")]
+    pub problem_type: String,
+    #[schema(example = "Pool is empty")]
+    pub title: String,
+    #[schema(example = 404)]
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "Pool 'default' has no addresses right now")]
+    pub detail: Option<String>,
+    pub code: ErrorCode,
+    /// `X-Request-Id` of the failed request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ProblemDetails {
+    pub fn new(status: StatusCode, code: ErrorCode, detail: Option<String>) -> Self {
+        Self {
+            problem_type: format!("{}{}", PROBLEM_TYPE_PREFIX, code.as_str()),
+            title: code.title().to_string(),
+            status: status.as_u16(),
+            detail,
+            code,
+            request_id: None,
+        }
+    }
+
+    /// The response carrying this problem. The problem is also kept in the
+    /// response extensions, where `problem_details_middleware` adds the request ID.
+    pub fn into_response_with_status(self, status: StatusCode) -> Response {
+        let mut response = (status, Json(&self)).into_response();
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_CONTENT_TYPE));
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// Error returned by handlers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: ErrorCode,
+    pub detail: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode) -> Self {
+        Self {
+            status: code.status(),
+            code,
+            detail: None,
+        }
+    }
+
+    pub fn with_detail(code: ErrorCode, detail: impl Into<String>) -> Self {
+        Self {
+            detail: Some(detail.into()),
+            ..Self::new(code)
+        }
+    }
+
+    pub fn internal() -> Self {
+        Self::new(ErrorCode::Internal)
+    }
+
+    pub fn unknown_pool(pool: &str) -> Self {
+        Self::with_detail(ErrorCode::UnknownPool, format!("No pattern pool named '{}'", pool))
+    }
+
+    pub fn pool_empty(pool: &str) -> Self {
+        Self::with_detail(ErrorCode::PoolEmpty, format!("Pool '{}' has no addresses right now", pool))
+    }
+}
+
+/// Bare statuses become the generic code of the status
+impl From<StatusCode> for ApiError {
+    fn from(status: StatusCode) -> Self {
+        Self {
+            status,
+            code: ErrorCode::for_status(status),
+            detail: None,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.detail {
+            Some(detail) => write!(f, "{}: {}", self.code.as_str(), detail),
+            None => f.write_str(self.code.as_str()),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        ProblemDetails::new(self.status, self.code, self.detail).into_response_with_status(self.status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+
+    #[tokio::test]
+    async fn test_errors_render_as_problem_json() {
+        let response = ApiError::with_detail(ErrorCode::PoolEmpty, "Pool 'Cat' has no addresses right now").into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_CONTENT_TYPE);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["type"], "urn:pinpet:problem:pool_empty");
+        assert_eq!(problem["title"], "Pool is empty");
+        assert_eq!(problem["status"], 404);
+        assert_eq!(problem["code"], "pool_empty");
+        assert_eq!(problem["detail"], "Pool 'Cat' has no addresses right now");
+
+        // Every code serializes to its `as_str` form
+        let error = ApiError::from(StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(serde_json::to_value(error.code).unwrap(), error.code.as_str());
+        assert_eq!(error.code, ErrorCode::RateLimited);
+        assert_eq!(ApiError::from(StatusCode::IM_A_TEAPOT).code, ErrorCode::Internal);
+    }
+}
@@ -0,0 +1,336 @@
+//! gRPC service (`proto/pinpet.proto`) on its own port next to the HTTP API.
+//! It serves the same pools and applies the same tenant keys and quotas.
+
+// Every RPC returns the (large) `tonic::Status` anyway
+#![allow(clippy::result_large_err)]
+
+use futures_util::{stream, Stream};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status};
+
+use crate::handlers::{PetAppState, API_KEY_HEADER};
+use crate::pet::generator::TENANT_POOL_PREFIX;
+use crate::pet::{audit, metrics, DispensedRecord, DumpRecord, GeneratorEvent, PatternPool, Requester, StoreError, UsageError, DEFAULT_POOL};
+
+pub mod proto {
+    tonic::include_proto!("pinpet.v1");
+}
+
+use proto::pet_addresses_server::{PetAddresses, PetAddressesServer};
+
+pub struct PetAddressService {
+    state: Arc<PetAppState>,
+}
+
+impl PetAddressService {
+    pub fn new(state: Arc<PetAppState>) -> Self {
+        Self { state }
+    }
+
+    pub fn into_server(self) -> PetAddressesServer<Self> {
+        PetAddressesServer::new(self)
+    }
+
+    /// Pool `name` (empty is the default pool); tenant pools need their API key
+    fn pool(&self, name: &str, metadata: &MetadataMap) -> Result<PatternPool, Status> {
+        let name = if name.is_empty() { DEFAULT_POOL } else { name };
+        let pool = self
+            .state
+            .generator
+            .any_pool(name)
+            .ok_or_else(|| Status::not_found(format!("Unknown pool '{}'", name)))?;
+
+        if let Some(tenant) = name.strip_prefix(TENANT_POOL_PREFIX) {
+            let provided = metadata.get(API_KEY_HEADER).map(|value| value.as_bytes()).unwrap_or_default();
+            if !self.state.is_authorized(tenant, provided) {
+                return Err(Status::unauthenticated("Missing or wrong API key"));
+            }
+        }
+        Ok(pool)
+    }
+}
+
+fn requester(remote_addr: Option<SocketAddr>, metadata: &MetadataMap) -> Requester {
+    Requester {
+        ip: remote_addr.map(|addr| addr.ip().to_string()),
+        user_agent: metadata
+            .get("user-agent")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+    }
+}
+
+/// Audit actor of a call: the tenant of a tenant pool, otherwise `grpc`, at the peer's IP
+fn actor(pool: &PatternPool, requester: &Requester) -> String {
+    let role = match pool.name.strip_prefix(TENANT_POOL_PREFIX) {
+        Some(tenant) => format!("tenant:{}", tenant),
+        None => "grpc".to_string(),
+    };
+    match &requester.ip {
+        Some(ip) => format!("{}@{}", role, ip),
+        None => role,
+    }
+}
+
+fn internal(context: &str, e: anyhow::Error) -> Status {
+    tracing::error!("{}: {}", context, e);
+    Status::internal(context)
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<proto::GeneratorEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl PetAddresses for PetAddressService {
+    async fn get_next_address(
+        &self,
+        request: Request<proto::GetNextAddressRequest>,
+    ) -> Result<Response<proto::PetAddress>, Status> {
+        let requester = requester(request.remote_addr(), request.metadata());
+        let pool = self.pool(&request.get_ref().pool, request.metadata())?;
+        let storage = pool.storage.as_ref();
+
+        // Same quota accounting as GET /tenants/{name}/address/next
+        let tenant = pool.name.strip_prefix(TENANT_POOL_PREFIX);
+        // A tenant's registered recipient key wins over redaction, as over HTTP
+        let recipient = match tenant {
+            Some(tenant) => {
+                self.state.recipients.get(tenant).map_err(|e| internal("Failed to read the recipient key", e))?
+            }
+            None => None,
+        };
+        let now = chrono::Utc::now();
+        if let Some(tenant) = tenant {
+            self.state.usage.charge(tenant, now).map_err(|e| match e.downcast_ref::<UsageError>() {
+                Some(quota) => Status::resource_exhausted(quota.to_string()),
+                None => internal("Failed to record usage", e),
+            })?;
+        }
+
+        let address_info = match storage.get_next_address() {
+            Ok(Some(address_info)) => address_info,
+            popped => {
+                if let Some(tenant) = tenant {
+                    self.state.usage.refund(tenant, now);
+                }
+                return match popped {
+                    Err(e) if matches!(e.downcast_ref(), Some(StoreError::Busy { .. })) => Err(Status::unavailable(e.to_string())),
+                    Err(e) => Err(internal("Failed to get an address", e)),
+                    _ => Err(Status::not_found("No addresses available")),
+                };
+            }
+        };
+        // Redacted pops hold the key for POST .../address/{id}/reveal
+        let redaction = self.state.redaction.as_ref().filter(|_| recipient.is_none());
+        if let Some(redaction) = redaction {
+            redaction
+                .vault
+                .hold(&pool.name, &address_info)
+                .map_err(|e| internal("Failed to hold the private key", e))?;
+        }
+        metrics::record_dispensed(&pool.name, 1);
+        audit::with_actor_sync(actor(&pool, &requester), || {
+            storage.record_dispensed(vec![DispensedRecord::new(&pool.name, &address_info, &requester)])
+        });
+        if let Ok(depth) = storage.count_addresses() {
+            self.state.generator.events().publish(GeneratorEvent::QueueDepth {
+                pool: pool.name.clone(),
+                depth,
+                target: self.state.generator.target_pool_size(),
+            });
+        }
+
+        let lease_expires_at = storage.lease_expires_at(address_info.id).map(|at| at.to_rfc3339());
+        let address = address_info.address;
+        let encrypted_private_key = match &recipient {
+            Some(recipient) => Some(
+                recipient
+                    .seal(&address.private_key)
+                    .map_err(|e| internal("Failed to encrypt the private key", e))?,
+            ),
+            None => None,
+        };
+        Ok(Response::new(proto::PetAddress {
+            id: address_info.id,
+            public_key: address.public_key,
+            private_key: match request.get_ref().omit_private_key || redaction.is_some() || recipient.is_some() {
+                true => String::new(),
+                // prost messages hold plain strings; this copy is not wiped
+                false => address.private_key.expose().to_string(),
+            },
+            encrypted_private_key,
+            address: address.address,
+            created_at: address_info.created_at.to_rfc3339(),
+            lease_expires_at,
+        }))
+    }
+
+    async fn store_address(
+        &self,
+        request: Request<proto::StoreAddressRequest>,
+    ) -> Result<Response<proto::StoreAddressResponse>, Status> {
+        let requester = requester(request.remote_addr(), request.metadata());
+        let pool = self.pool(&request.get_ref().pool, request.metadata())?;
+        let request = request.get_ref();
+        let record = DumpRecord {
+            public_key: request.public_key.clone(),
+            private_key: request.private_key.as_str().into(),
+            address: request.address.clone(),
+            created_at: chrono::Utc::now(),
+        };
+        record.validate(&pool.matcher).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        match audit::with_actor_sync(actor(&pool, &requester), || pool.storage.store_address(record.to_address())) {
+            Ok(id) => Ok(Response::new(proto::StoreAddressResponse { id })),
+            Err(e) => Err(match e.downcast_ref::<StoreError>() {
+                Some(StoreError::Duplicate { .. }) => Status::already_exists(e.to_string()),
+                Some(StoreError::QueueFull { .. }) => Status::resource_exhausted(e.to_string()),
+                Some(StoreError::Busy { .. } | StoreError::Degraded { .. }) => Status::unavailable(e.to_string()),
+                None => internal("Failed to store the address", e),
+            }),
+        }
+    }
+
+    async fn count(&self, request: Request<proto::CountRequest>) -> Result<Response<proto::CountResponse>, Status> {
+        let pool = self.pool(&request.get_ref().pool, request.metadata())?;
+        let count = pool
+            .storage
+            .count_addresses()
+            .map_err(|e| internal("Failed to count addresses", e))?;
+
+        Ok(Response::new(proto::CountResponse {
+            count: count as u64,
+            target: self.state.generator.target_pool_size() as u64,
+            capacity: pool.storage.capacity().map(|capacity| capacity as u64),
+        }))
+    }
+
+    type SubscribeStream = EventStream;
+
+    async fn subscribe(
+        &self,
+        _request: Request<proto::SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let receiver = self.state.generator.events().subscribe();
+
+        let events = stream::unfold(receiver, |mut receiver| async move {
+            let event = match receiver.recv().await {
+                Ok(event) => proto::generator_event::Event::from(event),
+                Err(RecvError::Lagged(skipped)) => proto::generator_event::Event::Lagged(skipped),
+                Err(RecvError::Closed) => return None,
+            };
+            Some((Ok(proto::GeneratorEvent { event: Some(event) }), receiver))
+        });
+
+        Ok(Response::new(Box::pin(events)))
+    }
+}
+
+impl From<GeneratorEvent> for proto::generator_event::Event {
+    fn from(event: GeneratorEvent) -> Self {
+        match event {
+            GeneratorEvent::Progress {
+                keys_per_second,
+                total_keys,
+            } => Self::Progress(proto::Progress {
+                keys_per_second,
+                total_keys,
+            }),
+            GeneratorEvent::AddressFound { pool, id, address } => {
+                Self::AddressFound(proto::AddressFound { pool, id, address })
+            }
+            GeneratorEvent::QueueDepth { pool, depth, target } => Self::QueueDepth(proto::QueueDepth {
+                pool,
+                depth: depth as u64,
+                target: target as u64,
+            }),
+            GeneratorEvent::GenerationActive { active } => {
+                Self::GenerationActive(proto::GenerationActive { active })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::{PetAddress, RecipientKey};
+    use tonic::Code;
+
+    async fn service() -> PetAddressService {
+        PetAddressService::new(Arc::new(PetAppState::temporary().await))
+    }
+
+    fn store_request(pool: &str, address: &PetAddress) -> Request<proto::StoreAddressRequest> {
+        let mut request = Request::new(proto::StoreAddressRequest {
+            pool: pool.to_string(),
+            public_key: address.public_key.clone(),
+            private_key: address.private_key.expose().to_string(),
+            address: address.address.clone(),
+        });
+        request.metadata_mut().insert(API_KEY_HEADER, "secret".parse().unwrap());
+        request
+    }
+
+    #[tokio::test]
+    async fn test_store_count_and_pop() {
+        let service = service().await;
+        let matcher = Arc::clone(&service.state.generator.pools()[0].matcher);
+        let address = PetAddress::generate(&matcher).unwrap();
+
+        let mut forged = address.clone();
+        forged.public_key = PetAddress::generate(&matcher).unwrap().public_key;
+        let error = service.store_address(store_request("", &forged)).await.unwrap_err();
+        assert_eq!(error.code(), Code::InvalidArgument);
+
+        service.store_address(store_request("", &address)).await.unwrap();
+        let error = service.store_address(store_request("", &address)).await.unwrap_err();
+        assert_eq!(error.code(), Code::AlreadyExists);
+        let count = service.count(Request::new(proto::CountRequest::default())).await.unwrap().into_inner();
+        assert_eq!((count.count, count.target), (1, 2));
+
+        let request = proto::GetNextAddressRequest {
+            pool: "default".to_string(),
+            omit_private_key: true,
+        };
+        let popped = service.get_next_address(Request::new(request.clone())).await.unwrap().into_inner();
+        assert_eq!((popped.public_key, popped.private_key), (address.public_key, String::new()));
+        let error = service.get_next_address(Request::new(request)).await.unwrap_err();
+        assert_eq!(error.code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_pools_need_key_and_respect_quota() {
+        let service = service().await;
+        let matcher = Arc::clone(&service.state.generator.pools()[0].matcher);
+        for _ in 0..2 {
+            let address = PetAddress::generate(&matcher).unwrap();
+            service.store_address(store_request("tenant:dev", &address)).await.unwrap();
+        }
+
+        let request = proto::GetNextAddressRequest {
+            pool: "tenant:dev".to_string(),
+            omit_private_key: false,
+        };
+        let error = service.get_next_address(Request::new(request.clone())).await.unwrap_err();
+        assert_eq!(error.code(), Code::Unauthenticated);
+
+        let with_key = || {
+            let mut request = Request::new(request.clone());
+            request.metadata_mut().insert(API_KEY_HEADER, "secret".parse().unwrap());
+            request
+        };
+        // A registered recipient key means the private key only leaves encrypted
+        let recipient = openssl::pkey::PKey::generate_x25519().unwrap().raw_public_key().unwrap();
+        let recipient = RecipientKey::parse(&openssl::base64::encode_block(&recipient)).unwrap();
+        service.state.recipients.register("dev", &recipient).unwrap();
+        let popped = service.get_next_address(with_key()).await.unwrap().into_inner();
+        assert!(popped.private_key.is_empty());
+        assert!(popped.encrypted_private_key.is_some());
+        let error = service.get_next_address(with_key()).await.unwrap_err();
+        assert_eq!(error.code(), Code::ResourceExhausted);
+    }
+}
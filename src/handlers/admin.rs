@@ -0,0 +1,959 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::error::{ApiError, ErrorCode, ProblemDetails};
+use crate::handlers::PetAppState;
+use crate::models::{
+    ApiResponse, AuditListResponse, AuditQuery, AuditVerifyResponse, BackupQuery, DispensedListResponse, DispensedQuery, GeneratorControlResponse, ImportResponse, KeyCheckQuery, KeyCheckResponse, KeyFileQuery,
+    DailyUsageResponse, QueueDiagnosticsResponse, RuntimeConfigPatch, RuntimeConfigResponse, TenantUsageResponse, ThrottleRequest,
+    UsageQuery, UsageResponse, WorkerListResponse, WorkerStatusResponse,
+    DEFAULT_AUDIT_LIMIT, DEFAULT_DISPENSED_LIMIT, MAX_AUDIT_LIMIT, MAX_DISPENSED_LIMIT,
+};
+use crate::pet::{parse_dump, AuditAction, AuditEvent, AuditLog, DispensedFilter, DumpFormat, DumpRecord, KeyFileFormat, PatternPool, PetGenerator, PetStorage, RuntimeOverrides, StoreError, DEFAULT_POOL};
+
+/// Queue health diagnostics
+///
+/// Compares the atomic queue size, the real queue length and the sled record count
+/// so operators can spot accounting drift
+#[utoipa::path(
+    get,
+    path = "/admin/diagnostics",
+    responses(
+        (status = 200, description = "Queue diagnostics", body = ApiResponse<QueueDiagnosticsResponse>),
+        (status = 501, description = "The storage backend has no diagnostics", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn get_queue_diagnostics(
+    State(app_state): State<Arc<PetAppState>>,
+) -> Result<Json<ApiResponse<QueueDiagnosticsResponse>>, ApiError> {
+    let storage = app_state.storage.sled().ok_or_else(not_on_sled)?;
+    match storage.diagnostics().await {
+        Ok(diagnostics) => {
+            let response = QueueDiagnosticsResponse {
+                reported_size: diagnostics.reported_size,
+                actual_queue_len: diagnostics.actual_queue_len,
+                spilled: diagnostics.spilled,
+                db_record_count: diagnostics.db_record_count,
+                leased: diagnostics.leased,
+                counter: diagnostics.counter,
+                size_delta: diagnostics.size_delta(),
+                db_delta: diagnostics.db_delta(),
+                consistent: diagnostics.is_consistent(),
+                pending_writes: diagnostics.pending_writes,
+                persist_lag_ms: diagnostics.persist_lag_ms,
+                failed_writes: diagnostics.failed_writes,
+                retrying_writes: diagnostics.retrying_writes,
+                dead_letters: diagnostics.dead_letters,
+                refused_operations: diagnostics.refused_operations,
+                degraded: diagnostics.degraded,
+                consecutive_write_failures: diagnostics.consecutive_write_failures,
+                expired: diagnostics.expired,
+            };
+
+            Ok(Json(ApiResponse::success(response)))
+        }
+        Err(e) => {
+            tracing::error!("Failed to collect queue diagnostics: {}", e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// Query the archive of dispensed addresses
+///
+/// Each entry records when and to whom an address was handed out, plus lease
+/// and acknowledgement times; private keys are never archived
+#[utoipa::path(
+    get,
+    path = "/admin/dispensed",
+    params(
+        ("pool" = Option<String>, Query, description = "Pattern pool name, or `tenant:{name}` for a tenant pool (default: `default`)", example = "default"),
+        ("requester" = Option<String>, Query, description = "Only entries requested from this client IP", example = "203.0.113.7"),
+        ("since" = Option<String>, Query, description = "Only entries dispensed at or after this RFC 3339 time", example = "2026-01-01T00:00:00Z"),
+        ("limit" = Option<usize>, Query, description = "Entries to return, 1..=1000 (default: 100)", example = 100)
+    ),
+    responses(
+        (status = 200, description = "Archive entries, newest address ID first", body = ApiResponse<DispensedListResponse>),
+        (status = 400, description = "limit is 0 or above 1000, or since is not RFC 3339", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pattern", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend has no archive", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn get_dispensed_addresses(
+    State(app_state): State<Arc<PetAppState>>,
+    Query(query): Query<DispensedQuery>,
+) -> Result<Json<ApiResponse<DispensedListResponse>>, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_DISPENSED_LIMIT);
+    if limit == 0 || limit > MAX_DISPENSED_LIMIT {
+        return Err(ApiError::with_detail(
+            ErrorCode::InvalidRequest,
+            format!("limit must be between 1 and {}", MAX_DISPENSED_LIMIT),
+        ));
+    }
+    let since = match &query.since {
+        Some(since) => Some(
+            chrono::DateTime::parse_from_rfc3339(since)
+                .map_err(|_| ApiError::with_detail(ErrorCode::InvalidRequest, "since must be an RFC 3339 time"))?
+                .with_timezone(&chrono::Utc),
+        ),
+        None => None,
+    };
+
+    let pool_name = query.pool.as_deref().unwrap_or(DEFAULT_POOL);
+    let pool = app_state.generator.any_pool(pool_name).ok_or_else(|| ApiError::unknown_pool(pool_name))?;
+    let storage = pool.storage.sled().ok_or_else(not_on_sled)?;
+
+    let filter = DispensedFilter {
+        requester: query.requester,
+        since,
+        limit,
+    };
+    match storage.dispensed(&filter) {
+        Ok(records) => Ok(Json(ApiResponse::success(DispensedListResponse {
+            pool: pool.name.clone(),
+            records: records.into_iter().map(Into::into).collect(),
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to query the dispensed archive of pool '{}': {}", pool.name, e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// Read the audit log
+///
+/// Stores, pops, acks, exports and admin requests with the actor behind
+/// each, newest first. Page back with `before` set to the lowest `seq` seen.
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    params(
+        ("before" = Option<u64>, Query, description = "Only entries with a lower sequence number", example = 1200),
+        ("limit" = Option<usize>, Query, description = "Entries to return, 1..=1000 (default: 100)", example = 100)
+    ),
+    responses(
+        (status = 200, description = "Audit entries, newest first", body = ApiResponse<AuditListResponse>),
+        (status = 400, description = "limit is 0 or above 1000", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "The audit log is not enabled", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn get_audit_log(
+    State(app_state): State<Arc<PetAppState>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<ApiResponse<AuditListResponse>>, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT);
+    if limit == 0 || limit > MAX_AUDIT_LIMIT {
+        return Err(ApiError::with_detail(
+            ErrorCode::InvalidRequest,
+            format!("limit must be between 1 and {}", MAX_AUDIT_LIMIT),
+        ));
+    }
+
+    match audit_log(&app_state)?.entries(query.before, limit) {
+        Ok(entries) => Ok(Json(ApiResponse::success(AuditListResponse {
+            entries: entries.into_iter().map(Into::into).collect(),
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to read the audit log: {}", e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// Verify the audit log's hash chain
+///
+/// Recomputes every entry's hash and checks it against the next entry's
+/// `prev_hash`. Entries cut off the end of the log leave no gap; compare
+/// `head_hash` with a copy taken earlier to detect that.
+#[utoipa::path(
+    get,
+    path = "/admin/audit/verify",
+    responses(
+        (status = 200, description = "Whether the chain is intact, and where it breaks if not", body = ApiResponse<AuditVerifyResponse>),
+        (status = 404, description = "The audit log is not enabled", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn verify_audit_log(
+    State(app_state): State<Arc<PetAppState>>,
+) -> Result<Json<ApiResponse<AuditVerifyResponse>>, ApiError> {
+    let audit = audit_log(&app_state)?.clone();
+    let report = tokio::task::spawn_blocking(move || audit.verify()).await.map_err(|e| {
+        tracing::error!("Audit log verification panicked: {}", e);
+        ApiError::internal()
+    })?;
+    match report {
+        Ok(report) => {
+            if let (Some(broken_at), Some(problem)) = (report.broken_at, &report.problem) {
+                tracing::warn!("Audit log chain is broken at entry {}: {}", broken_at, problem);
+            }
+            Ok(Json(ApiResponse::success(report.into())))
+        }
+        Err(e) => {
+            tracing::error!("Failed to verify the audit log: {}", e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+fn audit_log(app_state: &PetAppState) -> Result<&AuditLog, ApiError> {
+    app_state
+        .audit
+        .as_ref()
+        .ok_or_else(|| ApiError::with_detail(ErrorCode::NotFound, "The audit log is not enabled on this server"))
+}
+
+/// Addresses dispensed per tenant, for billing
+///
+/// Reports each tenant's total for a UTC calendar month with a per-day
+/// breakdown, next to its quotas and today's count
+#[utoipa::path(
+    get,
+    path = "/usage",
+    params(
+        ("tenant" = Option<String>, Query, description = "Only this tenant (default: every tenant)", example = "staging"),
+        ("month" = Option<String>, Query, description = "Billing month as `YYYY-MM`, UTC (default: the current month)", example = "2026-10")
+    ),
+    responses(
+        (status = 200, description = "Usage per tenant, sorted by name", body = ApiResponse<UsageResponse>),
+        (status = 400, description = "month is not `YYYY-MM`", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown tenant", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend does not record usage", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn get_usage(
+    State(app_state): State<Arc<PetAppState>>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<ApiResponse<UsageResponse>>, ApiError> {
+    let usage = &app_state.usage;
+    if !usage.is_persistent() {
+        return Err(ApiError::with_detail(ErrorCode::NotImplemented, "The storage backend does not record usage"));
+    }
+
+    let now = chrono::Utc::now();
+    let month = match query.month {
+        Some(month) => {
+            chrono::NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").map_err(|_| ApiError::with_detail(ErrorCode::InvalidRequest, "month must be YYYY-MM"))?;
+            month
+        }
+        None => now.format("%Y-%m").to_string(),
+    };
+    let today = now.format("%Y-%m-%d").to_string();
+    let tenants = match query.tenant {
+        Some(tenant) if usage.tenants().contains(&tenant) => vec![tenant],
+        Some(tenant) => {
+            return Err(ApiError::with_detail(ErrorCode::UnknownTenant, format!("No tenant named '{}'", tenant)))
+        }
+        None => usage.tenants(),
+    };
+
+    let report = tenants
+        .into_iter()
+        .map(|tenant| {
+            let quota = usage.quota(&tenant);
+            Ok(TenantUsageResponse {
+                dispensed: usage.monthly(&tenant, &month)?,
+                monthly_quota: quota.monthly,
+                dispensed_today: usage.on_day(&tenant, &today)?,
+                daily_quota: quota.daily,
+                days: usage
+                    .daily(&tenant, &month)?
+                    .into_iter()
+                    .map(|(date, dispensed)| DailyUsageResponse { date, dispensed })
+                    .collect(),
+                tenant,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>();
+
+    match report {
+        Ok(tenants) => Ok(Json(ApiResponse::success(UsageResponse { month, tenants }))),
+        Err(e) => {
+            tracing::error!("Failed to read usage: {}", e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// Export every stored address of a pool, private keys included
+///
+/// Streams queued and leased addresses in ID order as JSON Lines or CSV, for
+/// `POST /admin/import` on another host
+#[utoipa::path(
+    get,
+    path = "/admin/export",
+    params(
+        ("pool" = Option<String>, Query, description = "Pattern pool name, or `tenant:{name}` for a tenant pool (default: `default`)", example = "default"),
+        ("format" = Option<String>, Query, description = "`jsonl` (default), `csv` or `keypair` (Solana CLI keyfile arrays)", example = "csv")
+    ),
+    responses(
+        (status = 200, description = "Dump of the pool: one address per line (CSV has a header row)", content_type = "application/x-ndjson", body = String),
+        (status = 400, description = "Unknown format", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Neither ADMIN_API_KEY nor [jwt] is set", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pattern", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend cannot export", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn export_addresses(
+    State(app_state): State<Arc<PetAppState>>,
+    Query(query): Query<BackupQuery>,
+) -> Result<Response, ApiError> {
+    // Private keys never leave through an unauthenticated route
+    if !app_state.admin_is_protected() {
+        return Err(admin_key_unset("Exports"));
+    }
+    let format = dump_format(&query)?;
+    let pool = backup_pool(&app_state, &query)?;
+    let storage = sled_storage(&pool)?;
+
+    let records = storage.records();
+    tracing::info!("Exporting {} addresses from pool '{}'", records.len(), pool.name);
+
+    let header = format.header().map(|header| Ok(format!("{}\n", header)));
+    let lines = header
+        .into_iter()
+        .chain(records.into_iter().map(move |record| DumpRecord::from_info(&record).encode(format)));
+    let filename = format!(
+        "pinpet-{}-{}.{}",
+        pool.name.replace(':', "-"),
+        chrono::Utc::now().format("%Y%m%dT%H%M%SZ"),
+        format.extension()
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        Body::from_stream(futures_util::stream::iter(lines)),
+    )
+        .into_response())
+}
+
+/// Load a dump produced by `GET /admin/export` into a pool
+///
+/// Every line is validated first (the private key must derive the public key
+/// and the address must match the pool's pattern); if any line is invalid
+/// nothing is imported. Addresses whose public key was already enqueued on
+/// this host are skipped, so an import can safely be retried.
+#[utoipa::path(
+    post,
+    path = "/admin/import",
+    params(
+        ("pool" = Option<String>, Query, description = "Pattern pool name, or `tenant:{name}` for a tenant pool (default: `default`)", example = "default"),
+        ("format" = Option<String>, Query, description = "`jsonl` (default), `csv` or `keypair` (Solana CLI keyfile arrays)", example = "csv")
+    ),
+    request_body(content = String, description = "Dump in the chosen format, at most 64 MiB", content_type = "application/x-ndjson"),
+    responses(
+        (status = 200, description = "Addresses imported", body = ApiResponse<ImportResponse>),
+        (status = 400, description = "Unknown format or invalid lines (listed in the message)", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pattern", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "The pool reached max_queue_size part way through", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend cannot import", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn import_addresses(
+    State(app_state): State<Arc<PetAppState>>,
+    Query(query): Query<BackupQuery>,
+    body: String,
+) -> Result<Json<ApiResponse<ImportResponse>>, ApiError> {
+    let format = dump_format(&query)?;
+    let pool = backup_pool(&app_state, &query)?;
+    let storage = sled_storage(&pool)?;
+
+    let records = parse_dump(&body, format, &pool.matcher).map_err(|errors| {
+        ApiError::with_detail(
+            ErrorCode::InvalidRequest,
+            format!("{} invalid lines: {}", errors.len(), errors.join("; ")),
+        )
+    })?;
+
+    let mut response = ImportResponse {
+        pool: pool.name.clone(),
+        imported: 0,
+        duplicates: 0,
+    };
+    for record in records {
+        match storage.store_address_at(record.to_address(), record.created_at) {
+            Ok(id) => {
+                response.imported += 1;
+                app_state.audit(
+                    AuditEvent::new(AuditAction::Store)
+                        .pool(&pool.name)
+                        .subject(record.public_key.clone())
+                        .detail(format!("id {}, imported", id)),
+                );
+            }
+            Err(e) => match e.downcast_ref::<StoreError>() {
+                Some(StoreError::Duplicate { .. }) => response.duplicates += 1,
+                Some(StoreError::QueueFull { .. }) => {
+                    return Err(ApiError::with_detail(
+                        ErrorCode::Conflict,
+                        format!("{} after importing {} addresses", e, response.imported),
+                    ));
+                }
+                Some(StoreError::Busy { .. } | StoreError::Degraded { .. }) => {
+                    return Err(ApiError::with_detail(
+                        ErrorCode::Unavailable,
+                        format!("{} after importing {} addresses", e, response.imported),
+                    ));
+                }
+                None => {
+                    tracing::error!("Import into pool '{}' failed: {}", pool.name, e);
+                    return Err(ApiError::internal());
+                }
+            },
+        }
+    }
+
+    tracing::info!(
+        "Imported {} addresses into pool '{}' ({} duplicates skipped)",
+        response.imported,
+        pool.name,
+        response.duplicates
+    );
+    Ok(Json(ApiResponse::success(response)))
+}
+
+/// Check every stored key of a pool
+///
+/// Reads each sled record of the pool and confirms that it decodes, that its
+/// private key derives its public key and address, and that the address
+/// matches the pool's pattern. Nothing is repaired; stop the server and run
+/// `verify --force` to quarantine bad records.
+#[utoipa::path(
+    get,
+    path = "/admin/verify",
+    params(
+        ("pool" = Option<String>, Query, description = "Pattern pool name, or `tenant:{name}` for a tenant pool (default: `default`)", example = "default")
+    ),
+    responses(
+        (status = 200, description = "Key check of the pool", body = ApiResponse<KeyCheckResponse>),
+        (status = 404, description = "Unknown pattern", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend cannot be checked", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn verify_keys(
+    State(app_state): State<Arc<PetAppState>>,
+    Query(query): Query<KeyCheckQuery>,
+) -> Result<Json<ApiResponse<KeyCheckResponse>>, ApiError> {
+    let pool_name = query.pool.as_deref().unwrap_or(DEFAULT_POOL);
+    let pool = app_state.generator.any_pool(pool_name).ok_or_else(|| ApiError::unknown_pool(pool_name))?;
+    let storage = sled_storage(&pool)?;
+
+    match storage.check_keys(&pool.matcher).await {
+        Ok(report) => {
+            if report.unusable() > 0 {
+                tracing::warn!(
+                    "Pool '{}' has {} corrupt records and {} mismatched keys",
+                    pool.name,
+                    report.corrupt.len(),
+                    report.mismatched.len()
+                );
+            }
+            Ok(Json(ApiResponse::success(KeyCheckResponse::new(pool.name.clone(), report))))
+        }
+        Err(e) => {
+            tracing::error!("Failed to check the keys of pool '{}': {}", pool.name, e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// Download the secret key of one stored address
+///
+/// Looks the public key up among the queued and leased addresses of every
+/// pool and returns its secret key as a file, by default the JSON array that
+/// `solana-keygen` reads. The address stays queued.
+#[utoipa::path(
+    get,
+    path = "/admin/address/{pubkey}/keyfile",
+    params(
+        ("pubkey" = String, Path, description = "Public key of a stored address", example = "AGm9DpEaQYHxLKy98WGGoqErJEML9Pf5HySA1o4skPet"),
+        ("format" = Option<String>, Query, description = "`json` (default, Solana CLI keyfile), `base58` or `hex`", example = "json")
+    ),
+    responses(
+        (status = 200, description = "The secret key in the chosen format", content_type = "application/json", body = String),
+        (status = 400, description = "Unknown format", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Neither ADMIN_API_KEY nor [jwt] is set", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No pool holds this address", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend cannot look addresses up", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn get_address_keyfile(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(public_key): Path<String>,
+    Query(query): Query<KeyFileQuery>,
+) -> Result<Response, ApiError> {
+    if !app_state.admin_is_protected() {
+        return Err(admin_key_unset("Key files"));
+    }
+    let format = query
+        .format
+        .as_deref()
+        .map_or(Some(KeyFileFormat::default()), KeyFileFormat::parse)
+        .ok_or_else(|| ApiError::with_detail(ErrorCode::InvalidRequest, "format must be json, base58 or hex"))?;
+    app_state.storage.sled().ok_or_else(not_on_sled)?;
+
+    let pools = app_state.generator.pools();
+    let (pool, address_info) = pools
+        .iter()
+        .find_map(|pool| Some((pool, pool.storage.sled()?.find_by_public_key(&public_key)?)))
+        .ok_or_else(|| ApiError::with_detail(ErrorCode::NotFound, format!("No stored address {}", public_key)))?;
+    let encoded = format.encode(&address_info.address).map_err(|e| {
+        tracing::error!("Failed to encode the key of {}: {}", public_key, e);
+        ApiError::internal()
+    })?;
+
+    tracing::info!("Key file of {} in pool '{}' retrieved", public_key, pool.name);
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.{}\"", address_info.address.address, format.extension()),
+            ),
+        ],
+        encoded,
+    )
+        .into_response())
+}
+
+fn dump_format(query: &BackupQuery) -> Result<DumpFormat, ApiError> {
+    query
+        .format
+        .as_deref()
+        .map_or(Some(DumpFormat::default()), DumpFormat::parse)
+        .ok_or_else(|| ApiError::with_detail(ErrorCode::InvalidRequest, "format must be jsonl, csv or keypair"))
+}
+
+/// Pool named in the query
+fn backup_pool(app_state: &PetAppState, query: &BackupQuery) -> Result<PatternPool, ApiError> {
+    let pool_name = query.pool.as_deref().unwrap_or(DEFAULT_POOL);
+    app_state.generator.any_pool(pool_name).ok_or_else(|| ApiError::unknown_pool(pool_name))
+}
+
+/// Sled storage of `pool`; backups are sled-only
+fn sled_storage(pool: &PatternPool) -> Result<&PetStorage, ApiError> {
+    pool.storage.sled().ok_or_else(not_on_sled)
+}
+
+fn not_on_sled() -> ApiError {
+    ApiError::with_detail(ErrorCode::NotImplemented, "The storage backend does not support this operation")
+}
+
+/// Remote grind workers
+///
+/// Every registered worker with its liveness, reported throughput, keypairs
+/// submitted and the pool it grinds for
+#[utoipa::path(
+    get,
+    path = "/admin/workers",
+    responses(
+        (status = 200, description = "Registered workers", body = ApiResponse<WorkerListResponse>)
+    ),
+    tag = "Admin"
+)]
+pub async fn list_workers(State(app_state): State<Arc<PetAppState>>) -> Json<ApiResponse<WorkerListResponse>> {
+    let now = chrono::Utc::now();
+    let workers: Vec<WorkerStatusResponse> = app_state
+        .workers
+        .list()
+        .into_iter()
+        .map(|worker| {
+            let alive = app_state.workers.is_alive(&worker, now);
+            WorkerStatusResponse::new(worker, alive)
+        })
+        .collect();
+    let live = || workers.iter().filter(|worker| worker.alive);
+    Json(ApiResponse::success(WorkerListResponse {
+        alive: live().count(),
+        total_keys_per_second: live().map(|worker| worker.keys_per_second).sum(),
+        workers,
+    }))
+}
+
+/// Current pause / throttle state of the background generator
+#[utoipa::path(
+    get,
+    path = "/admin/generator",
+    responses(
+        (status = 200, description = "Generator control state", body = ApiResponse<GeneratorControlResponse>)
+    ),
+    tag = "Admin"
+)]
+pub async fn get_generator_control(
+    State(app_state): State<Arc<PetAppState>>,
+) -> Json<ApiResponse<GeneratorControlResponse>> {
+    Json(ApiResponse::success(control_response(&app_state.generator)))
+}
+
+/// Pause background generation
+///
+/// In-flight searches stop at their next chunk boundary, freeing the CPU
+/// without restarting the server
+#[utoipa::path(
+    post,
+    path = "/admin/generator/pause",
+    responses(
+        (status = 200, description = "Generator paused", body = ApiResponse<GeneratorControlResponse>)
+    ),
+    tag = "Admin"
+)]
+pub async fn pause_generator(
+    State(app_state): State<Arc<PetAppState>>,
+) -> Json<ApiResponse<GeneratorControlResponse>> {
+    app_state.generator.grinder().limits().pause();
+    tracing::info!("Generator paused by admin request");
+    Json(ApiResponse::success(control_response(&app_state.generator)))
+}
+
+/// Resume background generation
+#[utoipa::path(
+    post,
+    path = "/admin/generator/resume",
+    responses(
+        (status = 200, description = "Generator resumed", body = ApiResponse<GeneratorControlResponse>)
+    ),
+    tag = "Admin"
+)]
+pub async fn resume_generator(
+    State(app_state): State<Arc<PetAppState>>,
+) -> Json<ApiResponse<GeneratorControlResponse>> {
+    app_state.generator.grinder().limits().resume();
+    tracing::info!("Generator resumed by admin request");
+    Json(ApiResponse::success(control_response(&app_state.generator)))
+}
+
+/// Throttle background generation
+///
+/// Caps worker threads and/or combined keys/sec; omitted fields remove that cap
+#[utoipa::path(
+    post,
+    path = "/admin/generator/throttle",
+    request_body = ThrottleRequest,
+    responses(
+        (status = 200, description = "Limits applied", body = ApiResponse<GeneratorControlResponse>),
+        (status = 400, description = "A limit of zero was requested (use pause instead)", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn throttle_generator(
+    State(app_state): State<Arc<PetAppState>>,
+    Json(request): Json<ThrottleRequest>,
+) -> Result<Json<ApiResponse<GeneratorControlResponse>>, ApiError> {
+    if request.max_threads == Some(0) || request.max_keys_per_second == Some(0) {
+        return Err(ApiError::with_detail(ErrorCode::InvalidRequest, "Limits must be above zero; pause the generator instead"));
+    }
+
+    let limits = app_state.generator.grinder().limits();
+    limits.set_max_threads(request.max_threads);
+    limits.set_max_keys_per_second(request.max_keys_per_second);
+    tracing::info!(
+        "Generator throttled by admin request: max_threads={:?}, max_keys_per_second={:?}",
+        request.max_threads,
+        request.max_keys_per_second
+    );
+
+    Ok(Json(ApiResponse::success(control_response(&app_state.generator))))
+}
+
+fn control_response(generator: &PetGenerator) -> GeneratorControlResponse {
+    let grinder = generator.grinder();
+    GeneratorControlResponse {
+        paused: grinder.limits().is_paused(),
+        generation_active: generator.is_generating(),
+        threads: grinder.threads(),
+        effective_threads: grinder.effective_threads(),
+        max_threads: grinder.limits().max_threads(),
+        max_keys_per_second: grinder.limits().max_keys_per_second(),
+    }
+}
+
+/// Runtime settings
+///
+/// The pool target, extra suffix pools, grinder threads and rate limits in
+/// effect, and which of them are overridden at runtime
+#[utoipa::path(
+    get,
+    path = "/admin/config",
+    params(
+        ("Authorization" = Option<String>, Header, description = "`Bearer {ADMIN_API_KEY}`, required when the admin key is set")
+    ),
+    responses(
+        (status = 200, description = "Settings in effect", body = ApiResponse<RuntimeConfigResponse>),
+        (status = 401, description = "Missing or wrong admin bearer token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn get_runtime_config(
+    State(app_state): State<Arc<PetAppState>>,
+) -> Result<Json<ApiResponse<RuntimeConfigResponse>>, ApiError> {
+    runtime_config_response(&app_state)
+}
+
+/// Change settings at runtime
+///
+/// Applies immediately and is persisted, so the change survives a restart and
+/// beats the config file until reset. Needs `ADMIN_API_KEY` to be set.
+#[utoipa::path(
+    patch,
+    path = "/admin/config",
+    params(
+        ("Authorization" = Option<String>, Header, description = "`Bearer {ADMIN_API_KEY}`, required when the admin key is set")
+    ),
+    request_body = RuntimeConfigPatch,
+    responses(
+        (status = 200, description = "Settings applied", body = ApiResponse<RuntimeConfigResponse>),
+        (status = 400, description = "pool_size is 0 or above max_queue_size, or a suffix is invalid", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or wrong admin bearer token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Neither ADMIN_API_KEY nor [jwt] is set", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn update_runtime_config(
+    State(app_state): State<Arc<PetAppState>>,
+    Json(patch): Json<RuntimeConfigPatch>,
+) -> Result<Json<ApiResponse<RuntimeConfigResponse>>, ApiError> {
+    if !app_state.admin_is_protected() {
+        return Err(admin_key_unset("Runtime changes"));
+    }
+    check_settings(&app_state, patch.pool_size, patch.extra_suffixes.as_deref())?;
+
+    let mut changes = RuntimeOverrides::default();
+    if let Some(pool_size) = patch.pool_size {
+        app_state.generator.set_target_pool_size(pool_size);
+        changes.pool_size = Some(pool_size);
+    }
+    if let Some(threads) = patch.threads {
+        app_state.generator.grinder().set_threads(threads);
+        changes.threads = Some(threads);
+    }
+    if let Some(rate_limit) = &patch.rate_limit {
+        let rate_limit = rate_limit.apply(app_state.rate_limiter.config());
+        app_state.rate_limiter.set_rates(&rate_limit);
+        changes.rate_limit = Some(rate_limit);
+    }
+    if let Some(suffixes) = patch.extra_suffixes {
+        sync_extra_suffixes(&app_state, &suffixes, "admin request").await?;
+        changes.extra_suffixes = Some(app_state.generator.extra_suffixes());
+    }
+
+    let persisted = app_state.overrides.load().and_then(|mut overrides| {
+        overrides.merge(changes);
+        app_state.overrides.save(&overrides)
+    });
+    if let Err(e) = persisted {
+        tracing::error!("Runtime config changes were applied but not persisted: {}", e);
+        return Err(ApiError::internal());
+    }
+    tracing::info!("Runtime config changed by admin request");
+    runtime_config_response(&app_state)
+}
+
+/// Reload the configuration
+///
+/// Reads the config file, `APP_*` environment and `--set` flags again and
+/// applies the pool target, extra suffix pools, grinder threads and rate
+/// limits without dropping queued addresses or stopping the grinder. Settings
+/// overridden through `PATCH /admin/config` stay overridden; everything else
+/// needs a restart. `SIGHUP` does the same.
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    params(
+        ("Authorization" = Option<String>, Header, description = "`Bearer {ADMIN_API_KEY}`, required when the admin key is set")
+    ),
+    responses(
+        (status = 200, description = "Configuration reloaded", body = ApiResponse<RuntimeConfigResponse>),
+        (status = 400, description = "The configuration does not load, or a reloaded setting is invalid; nothing was changed", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or wrong admin bearer token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn reload_runtime_config(
+    State(app_state): State<Arc<PetAppState>>,
+) -> Result<Json<ApiResponse<RuntimeConfigResponse>>, ApiError> {
+    reload_config(&app_state, "admin request").await?;
+    runtime_config_response(&app_state)
+}
+
+/// Read the configuration again and apply what can change at runtime.
+/// Nothing is changed when the configuration fails to load or validate.
+pub async fn reload_config(app_state: &PetAppState, reason: &str) -> Result<(), ApiError> {
+    let mut config = AppConfig::load_from(&app_state.config_sources)
+        .map_err(|e| ApiError::with_detail(ErrorCode::InvalidRequest, format!("Configuration does not load: {}", e)))?;
+    let overrides = app_state.overrides.load().map_err(|e| {
+        tracing::error!("Failed to load runtime overrides: {}", e);
+        ApiError::internal()
+    })?;
+    overrides.apply(&mut config);
+
+    let settings = &config.pet_generator;
+    check_settings(app_state, Some(settings.pool_size), Some(&settings.extra_suffixes))?;
+    app_state.generator.set_target_pool_size(settings.pool_size);
+    app_state.generator.grinder().set_threads(settings.threads);
+    app_state.rate_limiter.set_rates(&config.rate_limit);
+    sync_extra_suffixes(app_state, &settings.extra_suffixes, reason).await?;
+
+    tracing::info!(
+        "Configuration reloaded by {}: pool_size={}, extra_suffixes={:?}, threads={}, rate limits {}",
+        reason,
+        settings.pool_size,
+        settings.extra_suffixes,
+        app_state.generator.grinder().threads(),
+        if config.rate_limit.enabled { "on" } else { "off" },
+    );
+    if !overrides.is_empty() {
+        tracing::info!("Still overridden at runtime: {}", overrides.overridden().join(", "));
+    }
+    Ok(())
+}
+
+/// Refuse a pool target the storage cannot hold and suffixes that can never match
+fn check_settings(app_state: &PetAppState, pool_size: Option<usize>, suffixes: Option<&[String]>) -> Result<(), ApiError> {
+    if let Some(pool_size) = pool_size {
+        let capacity = app_state.storage.capacity().unwrap_or(usize::MAX);
+        if pool_size == 0 || pool_size > capacity {
+            return Err(ApiError::with_detail(
+                ErrorCode::InvalidRequest,
+                "pool_size must be above zero and at most max_queue_size",
+            ));
+        }
+    }
+    for suffix in suffixes.unwrap_or_default() {
+        if let Err(e) = app_state.generator.check_suffix(suffix) {
+            tracing::info!("Rejected extra suffix '{}': {:#}", suffix, e);
+            return Err(ApiError::with_detail(ErrorCode::InvalidPattern, format!("Suffix '{}': {:#}", suffix, e)));
+        }
+    }
+    Ok(())
+}
+
+/// Open pools for new suffixes and stop serving those no longer listed;
+/// pools that stay keep their queue
+async fn sync_extra_suffixes(app_state: &PetAppState, suffixes: &[String], reason: &str) -> Result<(), ApiError> {
+    let generator = &app_state.generator;
+    for removed in generator.extra_suffixes().iter().filter(|current| !suffixes.contains(current)) {
+        generator.remove_suffix(removed);
+        tracing::info!("Stopped serving suffix pool '{}' by {}", removed, reason);
+    }
+    for added in suffixes {
+        if generator.pool(added).is_some() {
+            continue;
+        }
+        match generator.add_suffix(added).await {
+            Ok(pool) => {
+                if let Some(storage) = pool.storage.sled() {
+                    app_state.maintenance.start(storage);
+                }
+                tracing::info!("Opened suffix pool '{}' by {}", added, reason);
+            }
+            Err(e) => {
+                tracing::error!("Failed to open suffix pool '{}': {}", added, e);
+                return Err(ApiError::internal());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reset runtime settings
+///
+/// Forgets the persisted overrides; the config file applies again from the
+/// next restart. Settings in effect are not changed until then.
+#[utoipa::path(
+    delete,
+    path = "/admin/config",
+    params(
+        ("Authorization" = Option<String>, Header, description = "`Bearer {ADMIN_API_KEY}`, required when the admin key is set")
+    ),
+    responses(
+        (status = 200, description = "Overrides cleared", body = ApiResponse<RuntimeConfigResponse>),
+        (status = 401, description = "Missing or wrong admin bearer token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "Neither ADMIN_API_KEY nor [jwt] is set", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Admin"
+)]
+pub async fn reset_runtime_config(
+    State(app_state): State<Arc<PetAppState>>,
+) -> Result<Json<ApiResponse<RuntimeConfigResponse>>, ApiError> {
+    if !app_state.admin_is_protected() {
+        return Err(admin_key_unset("Runtime changes"));
+    }
+    if let Err(e) = app_state.overrides.clear() {
+        tracing::error!("Failed to clear runtime overrides: {}", e);
+        return Err(ApiError::internal());
+    }
+    tracing::info!("Runtime overrides cleared by admin request");
+    runtime_config_response(&app_state)
+}
+
+fn admin_key_unset(what: &str) -> ApiError {
+    ApiError::with_detail(ErrorCode::Forbidden, format!("{} need ADMIN_API_KEY or [jwt] to be set", what))
+}
+
+fn runtime_config_response(app_state: &PetAppState) -> Result<Json<ApiResponse<RuntimeConfigResponse>>, ApiError> {
+    let overrides = app_state.overrides.load().map_err(|e| {
+        tracing::error!("Failed to load runtime overrides: {}", e);
+        ApiError::internal()
+    })?;
+    let generator = &app_state.generator;
+    Ok(Json(ApiResponse::success(RuntimeConfigResponse {
+        pool_size: generator.target_pool_size(),
+        extra_suffixes: generator.extra_suffixes(),
+        threads: generator.grinder().threads(),
+        rate_limit: app_state.rate_limiter.config().into(),
+        overridden: overrides.overridden(),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_keyfile_is_refused_without_admin_credentials() {
+        let keyfile = |app_state: PetAppState| {
+            get_address_keyfile(
+                State(Arc::new(app_state)),
+                Path("AGm9DpEaQYHxLKy98WGGoqErJEML9Pf5HySA1o4skPet".to_string()),
+                Query(KeyFileQuery { format: None }),
+            )
+        };
+
+        let error = keyfile(PetAppState::temporary().await).await.unwrap_err();
+        assert_eq!(error.status, StatusCode::FORBIDDEN);
+
+        // With a credential the request gets past the check (to the sled-only lookup)
+        let mut app_state = PetAppState::temporary().await;
+        app_state.admin_key = Some("admin".to_string());
+        let error = keyfile(app_state).await.unwrap_err();
+        assert_eq!(error.status, StatusCode::NOT_IMPLEMENTED);
+    }
+}
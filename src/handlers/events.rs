@@ -0,0 +1,115 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Response,
+    },
+};
+use futures_util::{stream, Stream};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::error::{ApiError, ProblemDetails};
+use crate::handlers::PetAppState;
+use crate::models::{AddressStreamMessage, AddressStreamQuery};
+use crate::pet::GeneratorEvent;
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/events",
+    responses(
+        (status = 200, description = "Server-Sent Events stream of generator activity: `progress` (keys/sec), `address_found`, `queue_depth` and `generation_active`. A `lagged` event reports how many events a slow client missed.", content_type = "text/event-stream", body = String)
+    ),
+    tag = "Events"
+)]
+pub async fn stream_generator_events(
+    State(app_state): State<Arc<PetAppState>>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let receiver = app_state.generator.events().subscribe();
+
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        let event = match receiver.recv().await {
+            Ok(event) => Event::default().event(event.name()).json_data(&event),
+            Err(RecvError::Lagged(skipped)) => Ok(Event::default().event("lagged").data(skipped.to_string())),
+            Err(RecvError::Closed) => return None,
+        };
+        Some((event, receiver))
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// Push every newly generated address over a WebSocket
+///
+/// Each address the generator stores is sent as an `address_found` JSON text
+/// message with its public key and the pool's queue depth, so allocators can
+/// react without polling. Private keys are never sent.
+#[utoipa::path(
+    get,
+    path = "/ws",
+    params(
+        ("pool" = Option<String>, Query, description = "Only addresses stored in this pool: `default`, an extra suffix or `tenant:{name}` (default: all pools)", example = "default")
+    ),
+    responses(
+        (status = 101, description = "WebSocket upgrade; then one `AddressStreamMessage` per text frame", body = AddressStreamMessage),
+        (status = 404, description = "Unknown pool", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Events"
+)]
+pub async fn subscribe_addresses(
+    ws: WebSocketUpgrade,
+    State(app_state): State<Arc<PetAppState>>,
+    Query(query): Query<AddressStreamQuery>,
+) -> Result<Response, ApiError> {
+    if let Some(pool) = &query.pool {
+        app_state.generator.any_pool(pool).ok_or_else(|| ApiError::unknown_pool(pool))?;
+    }
+    Ok(ws.on_upgrade(move |socket| stream_addresses(socket, app_state, query.pool)))
+}
+
+async fn stream_addresses(mut socket: WebSocket, app_state: Arc<PetAppState>, pool_filter: Option<String>) {
+    let mut receiver = app_state.generator.events().subscribe();
+
+    loop {
+        let message = tokio::select! {
+            event = receiver.recv() => match event {
+                Ok(GeneratorEvent::AddressFound { pool, id, address }) => {
+                    if pool_filter.as_ref().is_some_and(|filter| *filter != pool) {
+                        continue;
+                    }
+                    let queue_depth = app_state
+                        .generator
+                        .any_pool(&pool)
+                        .and_then(|pool| pool.storage.count_addresses().ok())
+                        .unwrap_or_default();
+                    AddressStreamMessage::AddressFound {
+                        pool,
+                        id,
+                        // Addresses are their public keys
+                        public_key: address,
+                        queue_depth,
+                        target: app_state.generator.target_pool_size(),
+                    }
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => AddressStreamMessage::Lagged { skipped },
+                Err(RecvError::Closed) => break,
+            },
+            // Only watch for the client going away; pings are answered by axum
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+
+        let Ok(text) = serde_json::to_string(&message) else {
+            continue;
+        };
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            break;
+        }
+    }
+}
@@ -1,8 +1,13 @@
-use axum::{response::Json, http::StatusCode};
-use crate::models::{ApiResponse, HealthResponse};
+use axum::{extract::State, response::Json, http::StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{ApiError, ErrorCode, ProblemDetails};
+use crate::handlers::PetAppState;
+use crate::models::{ApiResponse, HealthResponse, ReadinessResponse};
 
 static START_TIME: std::sync::LazyLock<chrono::DateTime<chrono::Utc>> = 
-    std::sync::LazyLock::new(|| chrono::Utc::now());
+    std::sync::LazyLock::new(chrono::Utc::now);
 
 /// Health check endpoint
 ///
@@ -12,11 +17,11 @@ static START_TIME: std::sync::LazyLock<chrono::DateTime<chrono::Utc>> =
     path = "/health",
     responses(
         (status = 200, description = "Service is healthy", body = ApiResponse<HealthResponse>),
-        (status = 500, description = "Service error")
+        (status = 500, description = "Service error", body = ProblemDetails, content_type = "application/problem+json")
     ),
     tag = "Health Check"
 )]
-pub async fn health_check() -> Result<Json<ApiResponse<HealthResponse>>, StatusCode> {
+pub async fn health_check() -> Result<Json<ApiResponse<HealthResponse>>, ApiError> {
     let uptime_duration = chrono::Utc::now().signed_duration_since(*START_TIME);
     let uptime = format!("{} days {} hours {} minutes", 
         uptime_duration.num_days(),
@@ -63,6 +68,96 @@ pub async fn detailed_health_check() -> Json<ApiResponse<serde_json::Value>> {
     Json(ApiResponse::success(detailed_info))
 }
 
+/// Process check: answers as long as the server accepts requests
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    responses(
+        (status = 200, description = "The process is up", body = ApiResponse<String>)
+    ),
+    tag = "Health Check"
+)]
+pub async fn healthz() -> Json<ApiResponse<String>> {
+    Json(ApiResponse::success("ok".to_string()))
+}
+
+/// Liveness probe: fails only when storage hangs (e.g. a stuck lock), which
+/// a restart fixes. Storage errors such as an unreachable Redis are left to
+/// `/readyz`, since restarting would not help.
+#[utoipa::path(
+    get,
+    path = "/livez",
+    responses(
+        (status = 200, description = "The server and its storage respond", body = ApiResponse<String>),
+        (status = 503, description = "Storage did not answer within `[probes].timeout_seconds`", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Health Check"
+)]
+pub async fn livez(State(app_state): State<Arc<PetAppState>>) -> Result<Json<ApiResponse<String>>, ApiError> {
+    match queue_depth(&app_state).await {
+        Some(_) => Ok(Json(ApiResponse::success("ok".to_string()))),
+        None => Err(ApiError::with_detail(ErrorCode::Unavailable, "Storage is not responding")),
+    }
+}
+
+/// Readiness probe: the storage answers and the default pool holds at least
+/// `[probes].min_ready_queue_depth` addresses, so load balancers only route
+/// to replicas that can actually dispense. Degraded mode is reported but does
+/// not fail the probe, since the in-memory queue still dispenses.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    responses(
+        (status = 200, description = "Ready to serve addresses, possibly degraded", body = ApiResponse<ReadinessResponse>),
+        (status = 503, description = "Storage failing or too few addresses queued", body = ApiResponse<ReadinessResponse>)
+    ),
+    tag = "Health Check"
+)]
+pub async fn readyz(State(app_state): State<Arc<PetAppState>>) -> (StatusCode, Json<ApiResponse<ReadinessResponse>>) {
+    let min_queue_depth = app_state.probes.min_ready_queue_depth;
+    let (queue_depth, reason) = match queue_depth(&app_state).await {
+        Some(Ok(depth)) if depth < min_queue_depth => {
+            (Some(depth), Some(format!("Queue depth {} is below {}", depth, min_queue_depth)))
+        }
+        Some(Ok(depth)) => (Some(depth), None),
+        Some(Err(e)) => (None, Some(format!("Storage error: {}", e))),
+        None => (None, Some("Storage is not responding".to_string())),
+    };
+
+    let ready = reason.is_none();
+    let degraded = app_state.storage.sled().is_some_and(|storage| storage.is_degraded());
+    let (status, message) = match &reason {
+        None if degraded => (StatusCode::OK, "degraded: storage writes are failing".to_string()),
+        None => (StatusCode::OK, "success".to_string()),
+        Some(reason) => (StatusCode::SERVICE_UNAVAILABLE, reason.clone()),
+    };
+    let response = ApiResponse {
+        code: status.as_u16() as i32,
+        message,
+        data: Some(ReadinessResponse {
+            ready,
+            queue_depth,
+            min_queue_depth,
+            reason,
+            degraded,
+        }),
+        timestamp: chrono::Utc::now().timestamp(),
+    };
+    (status, Json(response))
+}
+
+/// Default pool depth, counted off the async runtime (backends may block);
+/// None if it takes longer than the probe timeout
+async fn queue_depth(app_state: &PetAppState) -> Option<anyhow::Result<usize>> {
+    let storage = Arc::clone(&app_state.storage);
+    let count = tokio::task::spawn_blocking(move || storage.count_addresses());
+    match tokio::time::timeout(Duration::from_secs(app_state.probes.timeout_seconds), count).await {
+        Ok(Ok(depth)) => Some(depth),
+        Ok(Err(e)) => Some(Err(e.into())),
+        Err(_) => None,
+    }
+}
+
 fn get_memory_usage() -> u64 {
     // Simple memory usage statistics, can use more professional libraries in production
     #[cfg(target_os = "linux")]
@@ -0,0 +1,75 @@
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::error::{ApiError, ErrorCode, ProblemDetails};
+use crate::handlers::pet::{caller, requester};
+use crate::handlers::PetAppState;
+use crate::models::{ApiResponse, CreateJobRequest, CreateJobResponse, JobResponse};
+use crate::pet::AddressMatcher;
+
+/// Submit a custom-pattern job
+///
+/// The address is ground in the background; poll `GET /api/v1/jobs/{id}` for the result.
+/// Only the same caller (JWT subject, tenant key or client IP) can read it.
+#[utoipa::path(
+    post,
+    path = "/api/v1/jobs",
+    request_body = CreateJobRequest,
+    responses(
+        (status = 202, description = "Job accepted", body = ApiResponse<CreateJobResponse>),
+        (status = 400, description = "Invalid or impossible pattern", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Pattern too difficult or too many pending jobs", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Jobs"
+)]
+pub async fn create_job(
+    State(app_state): State<Arc<PetAppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<CreateJobRequest>,
+) -> Result<(StatusCode, Json<ApiResponse<CreateJobResponse>>), ApiError> {
+    let matcher = AddressMatcher::regex(&request.pattern, request.case_sensitive.unwrap_or(true))
+        .map_err(|e| ApiError::with_detail(ErrorCode::InvalidPattern, e.to_string()))?;
+
+    let owner = caller(&app_state, &headers, &requester(client, &headers));
+    match app_state.jobs.submit(matcher, owner) {
+        Ok(id) => Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(CreateJobResponse { id })))),
+        Err(e) => {
+            tracing::warn!("Rejected job for '{}': {}", request.pattern, e);
+            Err(ApiError::with_detail(ErrorCode::RateLimited, e.to_string()))
+        }
+    }
+}
+
+/// Status and, once completed, result of a custom-pattern job
+#[utoipa::path(
+    get,
+    path = "/api/v1/jobs/{id}",
+    params(
+        ("id" = String, Path, description = "Job ID returned by POST /api/v1/jobs")
+    ),
+    responses(
+        (status = 200, description = "Job status", body = ApiResponse<JobResponse>),
+        (status = 404, description = "Unknown or expired job, or one submitted by another caller", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Jobs"
+)]
+pub async fn get_job(
+    State(app_state): State<Arc<PetAppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<JobResponse>>, ApiError> {
+    // Someone else's job reads as unknown, so IDs cannot be probed
+    let owner = caller(&app_state, &headers, &requester(client, &headers));
+    let job = app_state
+        .jobs
+        .get(&id, &owner)
+        .ok_or_else(|| ApiError::with_detail(ErrorCode::NotFound, format!("No job with id '{}'", id)))?;
+    Ok(Json(ApiResponse::success(JobResponse::from(job))))
+}
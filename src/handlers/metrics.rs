@@ -0,0 +1,140 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Json, Response},
+};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::error::{ApiError, ErrorCode, ProblemDetails};
+use crate::handlers::PetAppState;
+use crate::models::{ApiResponse, GeneratorStatsResponse, PoolSuffixStats, QueueAgeResponse, SuffixStatsResponse};
+use crate::pet::{metrics, SHARD_FORWARDED_HEADER};
+
+/// Prometheus metrics
+///
+/// Queue depth, store and dispense counters, generator throughput,
+/// persistence backlog, database size and HTTP latency histograms
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text format", content_type = "text/plain", body = String),
+        (status = 404, description = "Metrics are disabled", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Health Check"
+)]
+pub async fn get_metrics(State(app_state): State<Arc<PetAppState>>) -> Result<Response, ApiError> {
+    let handle = app_state
+        .metrics
+        .as_ref()
+        .ok_or_else(|| ApiError::with_detail(ErrorCode::NotFound, "Metrics are disabled"))?;
+    let body = metrics::render(handle, &app_state.generator).await;
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response())
+}
+
+/// Generator statistics
+///
+/// Attempts per found address, per-thread keys/sec and a rolling histogram of
+/// time-to-find, for deciding whether more cores would pay off, plus the age
+/// of each pool's oldest and newest queued address
+#[utoipa::path(
+    get,
+    path = "/stats/generator",
+    responses(
+        (status = 200, description = "Statistics since startup", body = ApiResponse<GeneratorStatsResponse>)
+    ),
+    tag = "Health Check"
+)]
+pub async fn get_generator_stats(State(app_state): State<Arc<PetAppState>>) -> Json<ApiResponse<GeneratorStatsResponse>> {
+    let grinder = app_state.generator.grinder();
+    let queues = app_state
+        .generator
+        .pools()
+        .iter()
+        .map(|pool| {
+            let (oldest, newest) = metrics::queue_ages(pool).unzip();
+            QueueAgeResponse {
+                pool: pool.name.clone(),
+                depth: pool.storage.count_addresses().unwrap_or_default(),
+                oldest_age_seconds: oldest.map(|age| age.as_secs_f64()),
+                newest_age_seconds: newest.map(|age| age.as_secs_f64()),
+            }
+        })
+        .collect();
+    Json(ApiResponse::success(GeneratorStatsResponse::new(grinder.stats(), grinder.effective_threads(), queues)))
+}
+
+/// Suffix letter distribution
+///
+/// How many queued addresses end with each letter before the suffix (`aPet`,
+/// `bPet`, ...), per pool, to show which variants are scarce. Counted from
+/// the queue on each call. With sharding on, the counts of every peer are
+/// added in, so any node answers for the whole cluster.
+#[utoipa::path(
+    get,
+    path = "/stats/suffixes",
+    responses(
+        (status = 200, description = "Distribution per pool", body = ApiResponse<SuffixStatsResponse>),
+        (status = 501, description = "The storage backend cannot scan its queue", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Health Check"
+)]
+pub async fn get_suffix_stats(
+    State(app_state): State<Arc<PetAppState>>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<SuffixStatsResponse>>, ApiError> {
+    let mut counts = Vec::new();
+    for pool in app_state.generator.pools() {
+        let Some(suffix) = pool.matcher.fixed_suffix() else {
+            continue;
+        };
+        let storage = pool.storage.sled().ok_or_else(|| {
+            ApiError::with_detail(ErrorCode::NotImplemented, "The storage backend does not support this operation")
+        })?;
+        counts.push((pool.name.clone(), suffix.to_string(), storage.ending_letter_counts(&pool.matcher)));
+    }
+
+    let mut unreachable_shards = Vec::new();
+    let shards = app_state.shards.clone().filter(|_| !headers.contains_key(SHARD_FORWARDED_HEADER));
+    if let Some(shards) = shards {
+        let answers = tokio::task::spawn_blocking(move || {
+            shards
+                .peers()
+                .iter()
+                .map(|peer| (peer.url.host_header(), shards.suffix_stats(peer)))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|_| ApiError::internal())?;
+        for (host, answer) in answers {
+            match answer {
+                Ok(remote) => merge_suffix_counts(&mut counts, remote),
+                Err(e) => {
+                    tracing::warn!("Shard {} did not send its suffix stats: {:#}", host, e);
+                    unreachable_shards.push(host);
+                }
+            }
+        }
+    }
+
+    let pools = counts
+        .into_iter()
+        .map(|(pool, suffix, counts)| PoolSuffixStats::new(&pool, &suffix, counts))
+        .collect();
+    Ok(Json(ApiResponse::success(SuffixStatsResponse { pools, unreachable_shards })))
+}
+
+/// Add a peer's per-letter counts to the pools this node also has
+fn merge_suffix_counts(counts: &mut [(String, String, BTreeMap<char, usize>)], remote: SuffixStatsResponse) {
+    for remote_pool in remote.pools {
+        let Some((_, _, pool_counts)) = counts.iter_mut().find(|(pool, _, _)| *pool == remote_pool.pool) else {
+            continue;
+        };
+        for letter in remote_pool.letters {
+            if let Some(character) = letter.letter.chars().next() {
+                *pool_counts.entry(character).or_default() += letter.count;
+            }
+        }
+    }
+}
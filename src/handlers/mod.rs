@@ -1,7 +1,21 @@
 pub mod health;
 pub mod time;
 pub mod pet;
+pub mod admin;
+pub mod events;
+pub mod jobs;
+pub mod metrics;
+pub mod replicas;
+pub mod webhooks;
+pub mod workers;
 
 pub use health::*;
 pub use time::*;
-pub use pet::*;
\ No newline at end of file
+pub use pet::*;
+pub use admin::*;
+pub use events::*;
+pub use jobs::*;
+pub use metrics::*;
+pub use replicas::*;
+pub use webhooks::*;
+pub use workers::*;
\ No newline at end of file
@@ -1,51 +1,1309 @@
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{ConnectInfo, Path, Query, RawQuery, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use metrics_exporter_prometheus::PrometheusHandle;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
 use std::sync::Arc;
 
-use crate::models::{ApiResponse, GetPetAddressResponse, PetGeneratorStatusResponse};
-use crate::pet::{PetGenerator, PetStorage};
+use crate::error::{ApiError, ErrorCode, ProblemDetails};
+use crate::config::{ConfigSources, ProbeConfig};
+use crate::middleware::{bearer_token, JwtValidator, RateLimiter};
+use crate::models::{
+    AcknowledgeResponse, ApiResponse, BatchPopRequest, BatchPopResponse, DifficultyEstimateQuery, PeekQuery, PeekResponse, MAX_PEEK, DifficultyEstimateResponse, GetPetAddressResponse, LetterQuery,
+    PetAddressQuery, PetGeneratorStatusResponse, RecipientKeyResponse, RegisterRecipientRequest, RevealResponse, ValidateAddressRequest,
+    ValidateAddressResponse, ProofRequest, ProofResponse, MAX_BATCH_POP, MAX_PROOF_NONCE,
+};
+use crate::pet::generator::TENANT_POOL_PREFIX;
+use crate::pet::{
+    estimate, metrics, AddressMatcher, AuditEvent, AuditLog, DispensedRecord, GeneratorEvent, IdempotencyCache, JobManager, KeyVault, OverrideStore, PatternPool, PetAddressInfo, ReplayMismatch,
+    PetGenerator, PoolMaintenance, RecipientKey, RecipientRegistry, Requester, Storage, StoreError, UsageError, UsageStore, WebhookDispatcher, WorkerRegistry, ShardRouter, DEFAULT_POOL, SHARD_FORWARDED_HEADER,
+};
+use crate::utils::validate_solana_address;
+
+/// Header clients set so retried fetches return the same address
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Header carrying a tenant's API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Header carrying the key that reveals redacted private keys
+pub const REVEAL_KEY_HEADER: &str = "x-reveal-key";
+
+/// Header carrying an X25519 or RSA public key (base64) to encrypt private keys to
+pub const RECIPIENT_KEY_HEADER: &str = "x-recipient-key";
 
 pub struct PetAppState {
     pub generator: Arc<PetGenerator>,
-    pub storage: Arc<PetStorage>,
+    pub storage: Arc<dyn Storage>,
+    pub idempotency: IdempotencyCache,
+    pub jobs: JobManager,
+    /// API key per tenant; tenants without an entry are open
+    pub tenant_keys: HashMap<String, String>,
+    /// Addresses dispensed per tenant, checked against their quotas
+    pub usage: UsageStore,
+    /// Prometheus recorder, when metrics are enabled
+    pub metrics: Option<PrometheusHandle>,
+    /// Set when pops are redacted
+    pub redaction: Option<Redaction>,
+    /// Keys that tenants' private keys are encrypted to
+    pub recipients: RecipientRegistry,
+    /// Thresholds of `/readyz` and `/livez`
+    pub probes: ProbeConfig,
+    /// Per-client limits, adjustable through `/admin/config`
+    pub rate_limiter: RateLimiter,
+    /// Settings changed through `/admin/config`, applied again on restart
+    pub overrides: OverrideStore,
+    /// Upkeep started for suffix pools opened at runtime
+    pub maintenance: PoolMaintenance,
+    /// Bearer token of the admin routes; runtime changes are refused without one
+    pub admin_key: Option<String>,
+    /// Push delivery to registered callbacks, when webhooks are enabled
+    pub webhooks: Option<WebhookDispatcher>,
+    /// Where the configuration came from, read again by `/admin/reload` and SIGHUP
+    pub config_sources: ConfigSources,
+    /// Remote grind workers and the `[workers]` settings
+    pub workers: WorkerRegistry,
+    /// Which node serves which suffix letters, when sharding is on
+    pub shards: Option<Arc<ShardRouter>>,
+    /// Hash-chained record of stores, pops, acks and admin requests, when enabled
+    pub audit: Option<AuditLog>,
+    /// Checks bearer JWTs, when `[jwt]` is enabled
+    pub jwt: Option<Arc<JwtValidator>>,
+}
+
+/// Redacted dispensing: where private keys wait, and the key that reveals them
+pub struct Redaction {
+    pub vault: KeyVault,
+    pub reveal_key: String,
+}
+
+impl PetAppState {
+    /// Whether `provided` is the API key of `tenant` (always true for tenants without one)
+    pub fn is_authorized(&self, tenant: &str, provided: &[u8]) -> bool {
+        match self.tenant_keys.get(tenant) {
+            Some(expected) => keys_match(provided, expected),
+            None => true,
+        }
+    }
+
+    /// Whether a request may use `tenant`'s pool: it carries the tenant's API
+    /// key (any request, for tenants without one), or a JWT whose tenant
+    /// claim names the tenant
+    pub fn tenant_authorized(&self, tenant: &str, headers: &HeaderMap) -> bool {
+        let provided = headers.get(API_KEY_HEADER).map(|value| value.as_bytes()).unwrap_or_default();
+        if self.is_authorized(tenant, provided) {
+            return true;
+        }
+        let Some(jwt) = &self.jwt else {
+            return false;
+        };
+        bearer_token(headers)
+            .and_then(|token| jwt.validate(token).ok())
+            .is_some_and(|claims| claims.tenant.as_deref() == Some(tenant))
+    }
+
+    /// Whether the admin routes need credentials (the admin key or a JWT);
+    /// runtime changes are refused otherwise
+    pub fn admin_is_protected(&self) -> bool {
+        self.admin_key.is_some() || self.jwt.is_some()
+    }
+
+    /// Chain `event` to the audit log, when it is enabled
+    pub fn audit(&self, event: AuditEvent) {
+        if let Some(audit) = &self.audit {
+            audit.record(event);
+        }
+    }
+}
+
+#[cfg(test)]
+impl PetAppState {
+    /// In-memory state with a `.` pattern, no admin credential and a tenant
+    /// `dev` (API key `secret`, one address a day) (used by tests)
+    pub(crate) async fn temporary() -> Self {
+        use crate::config::{JobsConfig, PetGeneratorConfig, RateLimitConfig, WorkersConfig};
+        use crate::pet::{MemoryStorage, Quota};
+
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let config: PetGeneratorConfig = serde_json::from_value(serde_json::json!({
+            "pool_size": 2,
+            "batch_size": 1,
+            "db_path": "",
+            "pattern": "."
+        }))
+        .unwrap();
+        let mut generator = PetGenerator::new(Arc::clone(&storage), config).await.unwrap();
+        generator.add_tenant("dev").await.unwrap();
+
+        let usage_db = sled::Config::new().temporary(true).open().unwrap();
+        let quota = Quota {
+            daily: Some(1),
+            monthly: None,
+        };
+        Self {
+            jobs: JobManager::new(generator.grinder().clone(), JobsConfig::default()),
+            generator: Arc::new(generator),
+            storage,
+            idempotency: IdempotencyCache::new(60, 10),
+            tenant_keys: HashMap::from([("dev".to_string(), "secret".to_string())]),
+            usage: UsageStore::new(Some(usage_db), HashMap::from([("dev".to_string(), quota)])),
+            metrics: None,
+            redaction: None,
+            recipients: RecipientRegistry::new(None),
+            probes: ProbeConfig::default(),
+            rate_limiter: RateLimiter::new(&RateLimitConfig::default(), Arc::new(Vec::new())),
+            overrides: OverrideStore::new(None),
+            maintenance: PoolMaintenance::default(),
+            admin_key: None,
+            webhooks: None,
+            config_sources: ConfigSources::default(),
+            workers: WorkerRegistry::new(WorkersConfig::default()),
+            shards: None,
+            audit: None,
+            jwt: None,
+        }
+    }
+}
+
+/// Constant-time comparison so a key cannot be guessed byte by byte
+fn keys_match(provided: &[u8], expected: &str) -> bool {
+    provided.len() == expected.len() && openssl::memcmp::eq(provided, expected.as_bytes())
+}
+
+/// How the private keys of popped addresses reach the client
+enum Delivery<'a> {
+    /// In the response, unless the client opted out
+    Plain { include_private_key: bool },
+    /// Held in the vault until revealed
+    Redacted(&'a KeyVault),
+    /// Encrypted to the client's `X-Recipient-Key` or the tenant's registered key
+    Encrypted(RecipientKey),
+}
+
+impl<'a> Delivery<'a> {
+    fn for_request(
+        app_state: &'a PetAppState,
+        pool: &str,
+        headers: &HeaderMap,
+        include_private_key: bool,
+    ) -> Result<Self, ApiError> {
+        if let Some(recipient) = recipient_key(app_state, pool, headers)? {
+            return Ok(Delivery::Encrypted(recipient));
+        }
+        Ok(match &app_state.redaction {
+            Some(redaction) => Delivery::Redacted(&redaction.vault),
+            None => Delivery::Plain { include_private_key },
+        })
+    }
+
+    /// How private keys are delivered; an idempotent replay must match it
+    fn fingerprint(&self) -> String {
+        match self {
+            Delivery::Plain { include_private_key } => format!("plain:{}", include_private_key),
+            Delivery::Redacted(_) => "redacted".to_string(),
+            Delivery::Encrypted(recipient) => format!("sealed:{}", recipient.to_base64().unwrap_or_default()),
+        }
+    }
+
+    /// Runs once per address, when it leaves `pool`
+    fn dispensed(&self, pool: &str, address_info: &PetAddressInfo) -> anyhow::Result<()> {
+        match self {
+            Delivery::Redacted(vault) => vault.hold(pool, address_info),
+            _ => Ok(()),
+        }
+    }
+
+    fn response(&self, address_info: PetAddressInfo) -> Result<GetPetAddressResponse, ApiError> {
+        match self {
+            Delivery::Plain { include_private_key } => Ok(GetPetAddressResponse::from_info(address_info, *include_private_key)),
+            Delivery::Redacted(_) => Ok(GetPetAddressResponse::from_info(address_info, false)),
+            Delivery::Encrypted(recipient) => {
+                let sealed = seal_for(recipient, &address_info.address.private_key)?;
+                let mut response = GetPetAddressResponse::from_info(address_info, false);
+                response.encrypted_private_key = Some(sealed);
+                Ok(response)
+            }
+        }
+    }
+}
+
+/// Key to encrypt private keys from `pool` to: the request's `X-Recipient-Key`,
+/// else the key registered for the pool's tenant. 400 when the header is not
+/// a usable key.
+fn recipient_key(app_state: &PetAppState, pool: &str, headers: &HeaderMap) -> Result<Option<RecipientKey>, ApiError> {
+    let Some(value) = headers.get(RECIPIENT_KEY_HEADER) else {
+        let Some(tenant) = pool.strip_prefix(TENANT_POOL_PREFIX) else {
+            return Ok(None);
+        };
+        return app_state.recipients.get(tenant).map_err(|e| {
+            tracing::error!("Failed to read the recipient key of tenant '{}': {}", tenant, e);
+            ApiError::internal()
+        });
+    };
+    let value = value.to_str().map_err(|_| {
+        ApiError::with_detail(ErrorCode::InvalidRecipientKey, format!("{} is not valid text", RECIPIENT_KEY_HEADER))
+    })?;
+    match RecipientKey::parse(value) {
+        Ok(recipient) => Ok(Some(recipient)),
+        Err(e) => {
+            tracing::info!("Rejected {}: {:#}", RECIPIENT_KEY_HEADER, e);
+            Err(ApiError::with_detail(ErrorCode::InvalidRecipientKey, format!("{:#}", e)))
+        }
+    }
+}
+
+fn seal_for(recipient: &RecipientKey, private_key: &str) -> Result<String, ApiError> {
+    recipient.seal(private_key).map_err(|e| {
+        tracing::error!("Failed to encrypt a private key for the recipient: {}", e);
+        ApiError::internal()
+    })
 }
 
 #[utoipa::path(
     get,
     path = "/api/v1/pet/address",
+    params(
+        ("include_private_key" = Option<bool>, Query, description = "Set to false to omit the private key from the response (default: true)", example = false),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key return the same address instead of consuming another")
+    ),
     responses(
         (status = 200, description = "Successfully retrieved Pet address", body = ApiResponse<GetPetAddressResponse>),
-        (status = 404, description = "No Pet addresses available", body = ApiResponse<String>),
-        (status = 500, description = "Internal server error", body = ApiResponse<String>)
+        (status = 404, description = "No Pet addresses available", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Idempotency-Key replayed with another include_private_key or X-Recipient-Key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Storage is busy writing to disk; retry shortly", body = ProblemDetails, content_type = "application/problem+json")
     ),
     tag = "Pet Address"
 )]
 pub async fn get_pet_address(
     State(app_state): State<Arc<PetAppState>>,
-) -> Result<Json<ApiResponse<GetPetAddressResponse>>, StatusCode> {
-    match app_state.storage.get_next_address() {
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Query(query): Query<PetAddressQuery>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<GetPetAddressResponse>>, ApiError> {
+    let storage = app_state.storage.as_ref();
+    let requester = requester(client, &headers);
+    dispense_address(&app_state, storage, DEFAULT_POOL, "address", || storage.get_next_address(), &query, &headers, &requester)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/pet/{pattern}/address",
+    params(
+        ("pattern" = String, Path, description = "Pattern pool name: `default` or one of the configured extra suffixes", example = "Cat"),
+        ("include_private_key" = Option<bool>, Query, description = "Set to false to omit the private key from the response (default: true)", example = false),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key return the same address instead of consuming another")
+    ),
+    responses(
+        (status = 200, description = "Successfully retrieved address from the pattern pool", body = ApiResponse<GetPetAddressResponse>),
+        (status = 404, description = "Unknown pattern or no addresses available", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Idempotency-Key replayed with another include_private_key or X-Recipient-Key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Storage is busy writing to disk; retry shortly", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn get_pattern_address(
+    State(app_state): State<Arc<PetAppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(pattern): Path<String>,
+    Query(query): Query<PetAddressQuery>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<GetPetAddressResponse>>, ApiError> {
+    let pool = app_state.generator.pool(&pattern).ok_or_else(|| ApiError::unknown_pool(&pattern))?;
+    let requester = requester(client, &headers);
+    dispense_address(
+        &app_state,
+        pool.storage.as_ref(),
+        &pool.name,
+        "address",
+        || pool.storage.get_next_address(),
+        &query,
+        &headers,
+        &requester,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/pet/address/peek",
+    params(
+        ("count" = Option<usize>, Query, description = "Addresses to show, 1..=100 (default: 1)", example = 5)
+    ),
+    responses(
+        (status = 200, description = "Next addresses without consuming them (public fields only)", body = ApiResponse<PeekResponse>),
+        (status = 400, description = "count is 0 or above 100", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend cannot peek", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn peek_pet_addresses(
+    State(app_state): State<Arc<PetAppState>>,
+    Query(query): Query<PeekQuery>,
+) -> Result<Json<ApiResponse<PeekResponse>>, ApiError> {
+    peek(app_state.storage.as_ref(), &query)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/pet/{pattern}/address/peek",
+    params(
+        ("pattern" = String, Path, description = "Pattern pool name: `default` or one of the configured extra suffixes", example = "Cat"),
+        ("count" = Option<usize>, Query, description = "Addresses to show, 1..=100 (default: 1)", example = 5)
+    ),
+    responses(
+        (status = 200, description = "Next addresses of the pattern pool without consuming them (public fields only)", body = ApiResponse<PeekResponse>),
+        (status = 400, description = "count is 0 or above 100", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pattern", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend cannot peek", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn peek_pattern_addresses(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(pattern): Path<String>,
+    Query(query): Query<PeekQuery>,
+) -> Result<Json<ApiResponse<PeekResponse>>, ApiError> {
+    let pool = app_state.generator.pool(&pattern).ok_or_else(|| ApiError::unknown_pool(&pattern))?;
+    peek(pool.storage.as_ref(), &query)
+}
+
+fn peek(storage: &dyn Storage, query: &PeekQuery) -> Result<Json<ApiResponse<PeekResponse>>, ApiError> {
+    let storage = storage.sled().ok_or_else(not_on_sled)?;
+    let count = query.count.unwrap_or(1);
+    if count == 0 || count > MAX_PEEK {
+        return Err(ApiError::with_detail(ErrorCode::InvalidRequest, format!("count must be between 1 and {}", MAX_PEEK)));
+    }
+
+    let addresses = storage
+        .peek_addresses(count)
+        .into_iter()
+        .map(|info| GetPetAddressResponse::from_info(info, false))
+        .collect();
+
+    Ok(Json(ApiResponse::success(PeekResponse { addresses })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/pet/addresses/pop",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key return the same batch instead of consuming more addresses")
+    ),
+    request_body = BatchPopRequest,
+    responses(
+        (status = 200, description = "Up to `count` addresses in FIFO order", body = ApiResponse<BatchPopResponse>),
+        (status = 400, description = "count is 0 or above 1000", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No Pet addresses available", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Idempotency-Key replayed with another include_private_key or X-Recipient-Key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Storage is busy writing to disk; retry shortly", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn pop_pet_addresses(
+    State(app_state): State<Arc<PetAppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<BatchPopRequest>,
+) -> Result<Json<ApiResponse<BatchPopResponse>>, ApiError> {
+    pop_batch(&app_state, app_state.storage.as_ref(), DEFAULT_POOL, &request, &headers, &requester(client, &headers))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/pet/{pattern}/addresses/pop",
+    params(
+        ("pattern" = String, Path, description = "Pattern pool name: `default` or one of the configured extra suffixes", example = "Cat"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key return the same batch instead of consuming more addresses")
+    ),
+    request_body = BatchPopRequest,
+    responses(
+        (status = 200, description = "Up to `count` addresses from the pattern pool in FIFO order", body = ApiResponse<BatchPopResponse>),
+        (status = 400, description = "count is 0 or above 1000", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pattern or no addresses available", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Idempotency-Key replayed with another include_private_key or X-Recipient-Key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Storage is busy writing to disk; retry shortly", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn pop_pattern_addresses(
+    State(app_state): State<Arc<PetAppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(pattern): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<BatchPopRequest>,
+) -> Result<Json<ApiResponse<BatchPopResponse>>, ApiError> {
+    let pool = app_state.generator.pool(&pattern).ok_or_else(|| ApiError::unknown_pool(&pattern))?;
+    pop_batch(&app_state, pool.storage.as_ref(), &pool.name, &request, &headers, &requester(client, &headers))
+}
+
+fn pop_batch(
+    app_state: &PetAppState,
+    storage: &dyn Storage,
+    pool_name: &str,
+    request: &BatchPopRequest,
+    headers: &HeaderMap,
+    requester: &Requester,
+) -> Result<Json<ApiResponse<BatchPopResponse>>, ApiError> {
+    if request.count == 0 || request.count > MAX_BATCH_POP {
+        return Err(ApiError::with_detail(
+            ErrorCode::InvalidRequest,
+            format!("count must be between 1 and {}", MAX_BATCH_POP),
+        ));
+    }
+    let delivery = Delivery::for_request(app_state, pool_name, headers, request.include_private_key.unwrap_or(true))?;
+    let fetch = || {
+        let popped = storage.get_next_addresses(request.count)?;
+        for info in &popped {
+            delivery.dispensed(pool_name, info)?;
+        }
+        if !popped.is_empty() {
+            metrics::record_dispensed(pool_name, popped.len());
+            storage.record_dispensed(
+                popped
+                    .iter()
+                    .map(|info| DispensedRecord::new(pool_name, info, requester))
+                    .collect(),
+            );
+        }
+        Ok(popped)
+    };
+
+    // Batches get their own key space per pool, apart from single pops
+    let result = match idempotency_key(headers) {
+        Some(key) => {
+            let key = format!("batch:{}:{}:{}", pool_name, caller(app_state, headers, requester), key);
+            app_state.idempotency.get_or_insert_batch(&key, &delivery.fingerprint(), fetch)
+        }
+        None => fetch(),
+    };
+
+    match result {
+        Ok(popped) if popped.is_empty() => Err(ApiError::pool_empty(pool_name)),
+        Ok(popped) => {
+            if let Ok(depth) = storage.count_addresses() {
+                app_state.generator.events().publish(GeneratorEvent::QueueDepth {
+                    pool: pool_name.to_string(),
+                    depth,
+                    target: app_state.generator.target_pool_size(),
+                });
+            }
+
+            let addresses = popped
+                .into_iter()
+                .map(|info| {
+                    let lease = storage.lease_expires_at(info.id);
+                    Ok(delivery.response(info)?.with_lease(lease))
+                })
+                .collect::<Result<Vec<_>, ApiError>>()?;
+
+            Ok(Json(ApiResponse::success(BatchPopResponse {
+                requested: request.count,
+                returned: addresses.len(),
+                addresses,
+            })))
+        }
+        Err(e) if e.downcast_ref::<ReplayMismatch>().is_some() => Err(ApiError::with_detail(ErrorCode::IdempotencyMismatch, e.to_string())),
+        Err(e) if matches!(e.downcast_ref(), Some(StoreError::Busy { .. })) => {
+            tracing::warn!("Refused a batch pop from pool '{}': {}", pool_name, e);
+            Err(ApiError::with_detail(ErrorCode::Unavailable, e.to_string()))
+        }
+        Err(e) => {
+            tracing::error!("Failed to pop addresses from pool '{}': {}", pool_name, e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/pet/address/next",
+    params(
+        ("letter" = String, Query, description = "Lowercase letter the address must have right before the suffix", example = "k"),
+        ("include_private_key" = Option<bool>, Query, description = "Set to false to omit the private key from the response (default: true)", example = false),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key return the same address instead of consuming another")
+    ),
+    responses(
+        (status = 200, description = "Oldest queued address with the requested ending letter", body = ApiResponse<GetPetAddressResponse>),
+        (status = 400, description = "Not a single lowercase base58 letter", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No address with that letter is available", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Idempotency-Key replayed with another include_private_key or X-Recipient-Key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend cannot filter pops", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Sharding is on and the node serving the letter is unreachable, or storage is busy", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn get_pet_address_by_letter(
+    State(app_state): State<Arc<PetAppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Query(letter): Query<LetterQuery>,
+    Query(query): Query<PetAddressQuery>,
+    RawQuery(raw_query): RawQuery,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let pool = app_state.generator.pool(DEFAULT_POOL).ok_or(ApiError::internal())?;
+    let path = format!("/api/v1/pet/address/next?{}", raw_query.unwrap_or_default());
+    if let Some(response) = forward_to_shard(&app_state, &pool, &letter, path, &headers).await? {
+        return Ok(response);
+    }
+    dispense_by_letter(&app_state, &pool, &letter, &query, &headers, &requester(client, &headers)).map(IntoResponse::into_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/pet/{pattern}/address/next",
+    params(
+        ("pattern" = String, Path, description = "Pattern pool name: `default` or one of the configured extra suffixes", example = "Cat"),
+        ("letter" = String, Query, description = "Lowercase letter the address must have right before the suffix", example = "k"),
+        ("include_private_key" = Option<bool>, Query, description = "Set to false to omit the private key from the response (default: true)", example = false),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key return the same address instead of consuming another")
+    ),
+    responses(
+        (status = 200, description = "Oldest queued address with the requested ending letter", body = ApiResponse<GetPetAddressResponse>),
+        (status = 400, description = "Not a single lowercase base58 letter, or the pool's pattern has no fixed suffix", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pattern or no address with that letter is available", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Idempotency-Key replayed with another include_private_key or X-Recipient-Key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend cannot filter pops", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Sharding is on and the node serving the letter is unreachable, or storage is busy", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn get_pattern_address_by_letter(
+    State(app_state): State<Arc<PetAppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(pattern): Path<String>,
+    Query(letter): Query<LetterQuery>,
+    Query(query): Query<PetAddressQuery>,
+    RawQuery(raw_query): RawQuery,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let pool = app_state.generator.pool(&pattern).ok_or_else(|| ApiError::unknown_pool(&pattern))?;
+    let path = format!("/api/v1/pet/{}/address/next?{}", pattern, raw_query.unwrap_or_default());
+    if let Some(response) = forward_to_shard(&app_state, &pool, &letter, path, &headers).await? {
+        return Ok(response);
+    }
+    dispense_by_letter(&app_state, &pool, &letter, &query, &headers, &requester(client, &headers)).map(IntoResponse::into_response)
+}
+
+/// With sharding on, a letter-specific pop for a letter another node serves
+/// is sent to that node and its answer relayed as is. None when the pool is
+/// not sharded, the letter is this node's, unserved or invalid, or the request
+/// was already forwarded.
+async fn forward_to_shard(
+    app_state: &PetAppState,
+    pool: &PatternPool,
+    letter: &LetterQuery,
+    path: String,
+    headers: &HeaderMap,
+) -> Result<Option<Response>, ApiError> {
+    let Some(shards) = &app_state.shards else {
+        return Ok(None);
+    };
+    if headers.contains_key(SHARD_FORWARDED_HEADER) || !matches!(*pool.matcher, AddressMatcher::Sharded { .. }) {
+        return Ok(None);
+    }
+    let Some((letter, peer)) = letter.letter().and_then(|letter| Some((letter, shards.owner(letter)?.clone()))) else {
+        return Ok(None);
+    };
+
+    // Pass on what the peer needs to authenticate, dedupe and encrypt the same way
+    let passed: Vec<(&str, String)> = [
+        API_KEY_HEADER,
+        "authorization",
+        IDEMPOTENCY_KEY_HEADER,
+        RECIPIENT_KEY_HEADER,
+        "user-agent",
+    ]
+    .into_iter()
+    .filter_map(|name| Some((name, headers.get(name)?.to_str().ok()?.to_string())))
+    .collect();
+
+    let shards = Arc::clone(shards);
+    let host = peer.url.host_header();
+    let sent = tokio::task::spawn_blocking(move || shards.forward(&peer, "GET", &path, &passed)).await;
+    let response = match sent {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => {
+            tracing::warn!("Shard {} serving '{}' did not answer: {:#}", host, letter, e);
+            return Err(ApiError::with_detail(
+                ErrorCode::Unavailable,
+                format!("The node serving '{}' is unreachable", letter),
+            ));
+        }
+        Err(e) => {
+            tracing::error!("Forwarding to shard {} panicked: {}", host, e);
+            return Err(ApiError::internal());
+        }
+    };
+
+    let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::BAD_GATEWAY);
+    let content_type = response.content_type.unwrap_or_else(|| "application/json".to_string());
+    Ok(Some((status, [(header::CONTENT_TYPE, content_type)], response.body).into_response()))
+}
+
+/// Audit identity of the caller: connection IP and User-Agent
+pub(crate) fn requester(client: SocketAddr, headers: &HeaderMap) -> Requester {
+    Requester {
+        ip: Some(client.ip().to_string()),
+        user_agent: headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+    }
+}
+
+/// Filtered pop: the oldest address in `pool` whose ending letter is `letter`
+fn dispense_by_letter(
+    app_state: &PetAppState,
+    pool: &PatternPool,
+    letter: &LetterQuery,
+    query: &PetAddressQuery,
+    headers: &HeaderMap,
+    requester: &Requester,
+) -> Result<Json<ApiResponse<GetPetAddressResponse>>, ApiError> {
+    let letter = letter
+        .letter()
+        .ok_or_else(|| ApiError::with_detail(ErrorCode::InvalidRequest, "letter must be a single lowercase base58 letter"))?;
+    // Regex pools have no well-defined "letter before the suffix"
+    if !pool.matcher.has_fixed_suffix() {
+        return Err(ApiError::with_detail(
+            ErrorCode::InvalidRequest,
+            format!("Pool '{}' has no fixed suffix to pick a letter before", pool.name),
+        ));
+    }
+    let storage = pool.storage.sled().ok_or_else(not_on_sled)?;
+
+    dispense_address(
+        app_state,
+        storage,
+        &pool.name,
+        &format!("next:{}", letter),
+        || {
+            storage
+                .get_next_address_where(|address| pool.matcher.ending_letter(&address.address) == Some(letter))
+        },
+        query,
+        headers,
+        requester,
+    )
+}
+
+fn idempotency_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|key| !key.is_empty())
+}
+
+/// Who an idempotency key belongs to: the JWT subject, else the tenant of a
+/// known API key, else the client IP. Two callers never share a cached pop.
+pub(crate) fn caller(app_state: &PetAppState, headers: &HeaderMap, requester: &Requester) -> String {
+    let subject = app_state
+        .jwt
+        .as_ref()
+        .and_then(|jwt| jwt.validate(bearer_token(headers)?).ok())
+        .and_then(|claims| claims.subject);
+    if let Some(subject) = subject {
+        return format!("sub={}", subject);
+    }
+    let provided = headers.get(API_KEY_HEADER).map(|value| value.as_bytes()).unwrap_or_default();
+    let tenant = app_state
+        .tenant_keys
+        .iter()
+        .find(|(_, expected)| keys_match(provided, expected))
+        .map(|(tenant, _)| tenant);
+    match (tenant, &requester.ip) {
+        (Some(tenant), _) => format!("tenant={}", tenant),
+        (None, Some(ip)) => format!("ip={}", ip),
+        (None, None) => "anonymous".to_string(),
+    }
+}
+
+/// Pop an address with `fetch`, honouring the Idempotency-Key header. Keys are
+/// cached as `{route}:{pool}:{caller}:{key}` so the same key can be used on
+/// different routes, pools or filters, and by different callers. Only the
+/// first delivery is archived; idempotent replays are not.
+#[allow(clippy::too_many_arguments)]
+fn dispense_address<F>(
+    app_state: &PetAppState,
+    storage: &dyn Storage,
+    pool_name: &str,
+    route: &str,
+    fetch: F,
+    query: &PetAddressQuery,
+    headers: &HeaderMap,
+    requester: &Requester,
+) -> Result<Json<ApiResponse<GetPetAddressResponse>>, ApiError>
+where
+    F: FnOnce() -> anyhow::Result<Option<PetAddressInfo>>,
+{
+    let delivery = Delivery::for_request(app_state, pool_name, headers, query.include_private_key())?;
+    let fetch = || {
+        let popped = fetch()?;
+        if let Some(address_info) = &popped {
+            delivery.dispensed(pool_name, address_info)?;
+            metrics::record_dispensed(pool_name, 1);
+            storage.record_dispensed(vec![DispensedRecord::new(pool_name, address_info, requester)]);
+        }
+        Ok(popped)
+    };
+
+    let result = match idempotency_key(headers) {
+        Some(key) => {
+            let key = format!("{}:{}:{}:{}", route, pool_name, caller(app_state, headers, requester), key);
+            app_state.idempotency.get_or_insert_with(&key, &delivery.fingerprint(), fetch)
+        }
+        None => fetch(),
+    };
+
+    match result {
         Ok(Some(address_info)) => {
-            let response = GetPetAddressResponse {
-                id: address_info.id,
-                public_key: address_info.address.public_key,
-                private_key: address_info.address.private_key,
-                address: address_info.address.address,
-                created_at: address_info.created_at.to_rfc3339(),
-            };
-            
+            if let Ok(depth) = storage.count_addresses() {
+                app_state.generator.events().publish(GeneratorEvent::QueueDepth {
+                    pool: pool_name.to_string(),
+                    depth,
+                    target: app_state.generator.target_pool_size(),
+                });
+            }
+
+            let lease = storage.lease_expires_at(address_info.id);
+            let response = delivery.response(address_info)?.with_lease(lease);
+
             Ok(Json(ApiResponse::success(response)))
         }
+        Ok(None) => Err(ApiError::pool_empty(pool_name)),
+        Err(e) if e.downcast_ref::<ReplayMismatch>().is_some() => Err(ApiError::with_detail(ErrorCode::IdempotencyMismatch, e.to_string())),
+        Err(e) if e.downcast_ref::<UsageError>().is_some() => {
+            tracing::info!("{}", e);
+            Err(ApiError::with_detail(ErrorCode::QuotaExceeded, e.to_string()))
+        }
+        Err(e) if matches!(e.downcast_ref(), Some(StoreError::Busy { .. })) => {
+            tracing::warn!("Refused a pop from pool '{}': {}", pool_name, e);
+            Err(ApiError::with_detail(ErrorCode::Unavailable, e.to_string()))
+        }
+        Err(e) => {
+            tracing::error!("Failed to get address from pool '{}': {}", pool_name, e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/pet/address/{id}/ack",
+    params(
+        ("id" = u64, Path, description = "ID of the leased address", example = 42)
+    ),
+    responses(
+        (status = 200, description = "Lease acknowledged, address deleted", body = ApiResponse<AcknowledgeResponse>),
+        (status = 404, description = "No live lease for this ID (never leased, already acknowledged or expired)", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn acknowledge_pet_address(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(id): Path<u64>,
+) -> Result<Json<ApiResponse<AcknowledgeResponse>>, ApiError> {
+    acknowledge(app_state.storage.as_ref(), id)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/pet/{pattern}/address/{id}/ack",
+    params(
+        ("pattern" = String, Path, description = "Pattern pool name: `default` or one of the configured extra suffixes", example = "Cat"),
+        ("id" = u64, Path, description = "ID of the leased address", example = 42)
+    ),
+    responses(
+        (status = 200, description = "Lease acknowledged, address deleted", body = ApiResponse<AcknowledgeResponse>),
+        (status = 404, description = "Unknown pattern or no live lease for this ID", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn acknowledge_pattern_address(
+    State(app_state): State<Arc<PetAppState>>,
+    Path((pattern, id)): Path<(String, u64)>,
+) -> Result<Json<ApiResponse<AcknowledgeResponse>>, ApiError> {
+    let pool = app_state.generator.pool(&pattern).ok_or_else(|| ApiError::unknown_pool(&pattern))?;
+    acknowledge(pool.storage.as_ref(), id)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/pet/address/{id}/reveal",
+    params(
+        ("id" = u64, Path, description = "ID of an address dispensed in redacted form", example = 42),
+        ("X-Reveal-Key" = String, Header, description = "Key from PRIVATE_KEY_REVEAL_API_KEY"),
+        ("X-Recipient-Key" = Option<String>, Header, description = "RSA public key (base64 DER, at least 2048 bits) to encrypt the private key to")
+    ),
+    responses(
+        (status = 200, description = "The held private key; it is deleted from the server", body = ApiResponse<RevealResponse>),
+        (status = 400, description = "Unusable X-Recipient-Key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or wrong reveal key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Redaction is off, or no key is held for this ID (never dispensed redacted, or already revealed)", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn reveal_pet_private_key(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<RevealResponse>>, ApiError> {
+    reveal(&app_state, DEFAULT_POOL, id, &headers)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/pet/{pattern}/address/{id}/reveal",
+    params(
+        ("pattern" = String, Path, description = "Pattern pool name: `default` or one of the configured extra suffixes", example = "Cat"),
+        ("id" = u64, Path, description = "ID of an address dispensed in redacted form", example = 42),
+        ("X-Reveal-Key" = String, Header, description = "Key from PRIVATE_KEY_REVEAL_API_KEY"),
+        ("X-Recipient-Key" = Option<String>, Header, description = "RSA public key (base64 DER, at least 2048 bits) to encrypt the private key to")
+    ),
+    responses(
+        (status = 200, description = "The held private key; it is deleted from the server", body = ApiResponse<RevealResponse>),
+        (status = 400, description = "Unusable X-Recipient-Key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or wrong reveal key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pattern, redaction is off, or no key is held for this ID", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn reveal_pattern_private_key(
+    State(app_state): State<Arc<PetAppState>>,
+    Path((pattern, id)): Path<(String, u64)>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<RevealResponse>>, ApiError> {
+    let pool = app_state.generator.pool(&pattern).ok_or_else(|| ApiError::unknown_pool(&pattern))?;
+    reveal(&app_state, &pool.name, id, &headers)
+}
+
+/// Prove the server holds the key of a queued address
+///
+/// Signs `pinpet-proof:{address}:{nonce}` with the private key of an address
+/// that is still queued, so a buyer can check possession before taking it
+#[utoipa::path(
+    post,
+    path = "/api/v1/pet/address/{id}/prove",
+    params(
+        ("id" = u64, Path, description = "ID of a queued address, e.g. from the peek endpoint", example = 42)
+    ),
+    request_body = ProofRequest,
+    responses(
+        (status = 200, description = "Signature of the proof message by the address's key", body = ApiResponse<ProofResponse>),
+        (status = 400, description = "nonce is empty or longer than 256 bytes", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "No queued address with this ID (never existed or already dispensed)", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend cannot look up queued addresses", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn prove_pet_address(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(id): Path<u64>,
+    Json(request): Json<ProofRequest>,
+) -> Result<Json<ApiResponse<ProofResponse>>, ApiError> {
+    prove(app_state.storage.as_ref(), id, request)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/pet/{pattern}/address/{id}/prove",
+    params(
+        ("pattern" = String, Path, description = "Pattern pool name: `default` or one of the configured extra suffixes", example = "Cat"),
+        ("id" = u64, Path, description = "ID of a queued address, e.g. from the peek endpoint", example = 42)
+    ),
+    request_body = ProofRequest,
+    responses(
+        (status = 200, description = "Signature of the proof message by the address's key", body = ApiResponse<ProofResponse>),
+        (status = 400, description = "nonce is empty or longer than 256 bytes", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pattern, or no queued address with this ID", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend cannot look up queued addresses", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn prove_pattern_address(
+    State(app_state): State<Arc<PetAppState>>,
+    Path((pattern, id)): Path<(String, u64)>,
+    Json(request): Json<ProofRequest>,
+) -> Result<Json<ApiResponse<ProofResponse>>, ApiError> {
+    let pool = app_state.generator.pool(&pattern).ok_or_else(|| ApiError::unknown_pool(&pattern))?;
+    prove(pool.storage.as_ref(), id, request)
+}
+
+fn prove(storage: &dyn Storage, id: u64, request: ProofRequest) -> Result<Json<ApiResponse<ProofResponse>>, ApiError> {
+    let storage = storage.sled().ok_or_else(not_on_sled)?;
+    if request.nonce.is_empty() || request.nonce.len() > MAX_PROOF_NONCE {
+        return Err(ApiError::with_detail(
+            ErrorCode::InvalidRequest,
+            format!("nonce must be 1 to {} bytes long", MAX_PROOF_NONCE),
+        ));
+    }
+
+    // Leased addresses are already dispensed; only queued ones can be proven
+    let address_info = storage
+        .queued_address(id)
+        .ok_or_else(|| ApiError::with_detail(ErrorCode::NotFound, format!("No queued address with id {}", id)))?;
+    let signature = address_info.address.prove_possession(&request.nonce).map_err(|e| {
+        tracing::error!("Failed to sign proof for address {}: {}", id, e);
+        ApiError::internal()
+    })?;
+
+    Ok(Json(ApiResponse::success(ProofResponse {
+        id,
+        message: address_info.address.proof_message(&request.nonce),
+        address: address_info.address.address,
+        nonce: request.nonce,
+        signature,
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/tenants/{tenant}/address/next",
+    params(
+        ("tenant" = String, Path, description = "Tenant name from `[[tenants]]`", example = "staging"),
+        ("include_private_key" = Option<bool>, Query, description = "Set to false to omit the private key from the response (default: true)", example = false),
+        ("X-API-Key" = Option<String>, Header, description = "The tenant's API key, if it has one"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key return the same address instead of consuming another")
+    ),
+    responses(
+        (status = 200, description = "Next address from the tenant's pool", body = ApiResponse<GetPetAddressResponse>),
+        (status = 401, description = "Missing or wrong API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown tenant or no addresses available", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Idempotency-Key replayed with another include_private_key or X-Recipient-Key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "The tenant's daily or monthly quota is used up", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Storage is busy writing to disk; retry shortly", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Tenants"
+)]
+pub async fn get_tenant_address(
+    State(app_state): State<Arc<PetAppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(tenant): Path<String>,
+    Query(query): Query<PetAddressQuery>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<GetPetAddressResponse>>, ApiError> {
+    let pool = tenant_pool(&app_state, &tenant, &headers)?;
+    let requester = requester(client, &headers);
+    let now = chrono::Utc::now();
+    dispense_address(
+        &app_state,
+        pool.storage.as_ref(),
+        &pool.name,
+        "address",
+        || {
+            // Reserve quota first so concurrent requests cannot overshoot it
+            app_state.usage.charge(&tenant, now)?;
+            let popped = pool.storage.get_next_address();
+            if !matches!(popped, Ok(Some(_))) {
+                app_state.usage.refund(&tenant, now);
+            }
+            popped
+        },
+        &query,
+        &headers,
+        &requester,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/tenants/{tenant}/status",
+    params(
+        ("tenant" = String, Path, description = "Tenant name from `[[tenants]]`", example = "staging"),
+        ("X-API-Key" = Option<String>, Header, description = "The tenant's API key, if it has one")
+    ),
+    responses(
+        (status = 200, description = "Tenant pool status", body = ApiResponse<PetGeneratorStatusResponse>),
+        (status = 401, description = "Missing or wrong API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown tenant", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Tenants"
+)]
+pub async fn get_tenant_status(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(tenant): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<PetGeneratorStatusResponse>>, ApiError> {
+    let pool = tenant_pool(&app_state, &tenant, &headers)?;
+
+    match pool.storage.count_addresses() {
+        Ok(count) => Ok(Json(ApiResponse::success(PetGeneratorStatusResponse {
+            total_addresses: count,
+            pool_size: app_state.generator.target_pool_size(),
+            generation_active: app_state.generator.is_generating(),
+            max_queue_size: pool.storage.capacity(),
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to get status for tenant '{}': {}", tenant, e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/tenants/{tenant}/address/{id}/reveal",
+    params(
+        ("tenant" = String, Path, description = "Tenant name from `[[tenants]]`", example = "staging"),
+        ("id" = u64, Path, description = "ID of an address dispensed in redacted form", example = 42),
+        ("X-API-Key" = Option<String>, Header, description = "The tenant's API key, if it has one"),
+        ("X-Reveal-Key" = String, Header, description = "Key from PRIVATE_KEY_REVEAL_API_KEY"),
+        ("X-Recipient-Key" = Option<String>, Header, description = "RSA public key (base64 DER, at least 2048 bits) to encrypt the private key to")
+    ),
+    responses(
+        (status = 200, description = "The held private key; it is deleted from the server", body = ApiResponse<RevealResponse>),
+        (status = 400, description = "Unusable X-Recipient-Key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or wrong API key or reveal key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown tenant, redaction is off, or no key is held for this ID", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Tenants"
+)]
+pub async fn reveal_tenant_private_key(
+    State(app_state): State<Arc<PetAppState>>,
+    Path((tenant, id)): Path<(String, u64)>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<RevealResponse>>, ApiError> {
+    let pool = tenant_pool(&app_state, &tenant, &headers)?;
+    reveal(&app_state, &pool.name, id, &headers)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/tenants/{tenant}/address/{id}/prove",
+    params(
+        ("tenant" = String, Path, description = "Tenant name from `[[tenants]]`", example = "staging"),
+        ("id" = u64, Path, description = "ID of a queued address in the tenant's pool", example = 42),
+        ("X-API-Key" = Option<String>, Header, description = "The tenant's API key, if it has one")
+    ),
+    request_body = ProofRequest,
+    responses(
+        (status = 200, description = "Signature of the proof message by the address's key", body = ApiResponse<ProofResponse>),
+        (status = 400, description = "nonce is empty or longer than 256 bytes", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or wrong API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown tenant, or no queued address with this ID", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 501, description = "The storage backend cannot look up queued addresses", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Tenants"
+)]
+pub async fn prove_tenant_address(
+    State(app_state): State<Arc<PetAppState>>,
+    Path((tenant, id)): Path<(String, u64)>,
+    headers: HeaderMap,
+    Json(request): Json<ProofRequest>,
+) -> Result<Json<ApiResponse<ProofResponse>>, ApiError> {
+    let pool = tenant_pool(&app_state, &tenant, &headers)?;
+    prove(pool.storage.as_ref(), id, request)
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/tenants/{tenant}/recipient-key",
+    params(
+        ("tenant" = String, Path, description = "Tenant name from `[[tenants]]`", example = "staging"),
+        ("X-API-Key" = Option<String>, Header, description = "The tenant's API key, if it has one")
+    ),
+    request_body = RegisterRecipientRequest,
+    responses(
+        (status = 200, description = "Registered; from now on the tenant's private keys are only sent encrypted to this key", body = ApiResponse<RecipientKeyResponse>),
+        (status = 400, description = "Not an X25519 or RSA (2048+ bits) public key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or wrong API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown tenant", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Tenants"
+)]
+pub async fn register_recipient_key(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(tenant): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<RegisterRecipientRequest>,
+) -> Result<Json<ApiResponse<RecipientKeyResponse>>, ApiError> {
+    tenant_pool(&app_state, &tenant, &headers)?;
+    let recipient = RecipientKey::parse(&request.public_key).map_err(|e| {
+        tracing::info!("Rejected recipient key of tenant '{}': {:#}", tenant, e);
+        ApiError::with_detail(ErrorCode::InvalidRecipientKey, format!("{:#}", e))
+    })?;
+
+    if let Err(e) = app_state.recipients.register(&tenant, &recipient) {
+        tracing::error!("Failed to register the recipient key of tenant '{}': {}", tenant, e);
+        return Err(ApiError::internal());
+    }
+    tracing::info!("Registered a {} recipient key for tenant '{}'", recipient.algorithm(), tenant);
+    recipient_key_response(tenant, &recipient)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/tenants/{tenant}/recipient-key",
+    params(
+        ("tenant" = String, Path, description = "Tenant name from `[[tenants]]`", example = "staging"),
+        ("X-API-Key" = Option<String>, Header, description = "The tenant's API key, if it has one")
+    ),
+    responses(
+        (status = 200, description = "The tenant's registered recipient key", body = ApiResponse<RecipientKeyResponse>),
+        (status = 401, description = "Missing or wrong API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown tenant or no key registered", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Tenants"
+)]
+pub async fn get_recipient_key(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(tenant): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<RecipientKeyResponse>>, ApiError> {
+    tenant_pool(&app_state, &tenant, &headers)?;
+    match app_state.recipients.get(&tenant) {
+        Ok(Some(recipient)) => recipient_key_response(tenant, &recipient),
+        Ok(None) => Err(no_recipient_key(&tenant)),
+        Err(e) => {
+            tracing::error!("Failed to read the recipient key of tenant '{}': {}", tenant, e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/tenants/{tenant}/recipient-key",
+    params(
+        ("tenant" = String, Path, description = "Tenant name from `[[tenants]]`", example = "staging"),
+        ("X-API-Key" = Option<String>, Header, description = "The tenant's API key, if it has one")
+    ),
+    responses(
+        (status = 204, description = "Unregistered; private keys are sent as without a key again"),
+        (status = 401, description = "Missing or wrong API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown tenant or no key registered", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Tenants"
+)]
+pub async fn delete_recipient_key(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(tenant): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    tenant_pool(&app_state, &tenant, &headers)?;
+    match app_state.recipients.remove(&tenant) {
+        Ok(true) => {
+            tracing::info!("Unregistered the recipient key of tenant '{}'", tenant);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(false) => Err(no_recipient_key(&tenant)),
+        Err(e) => {
+            tracing::error!("Failed to remove the recipient key of tenant '{}': {}", tenant, e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+fn recipient_key_response(tenant: String, recipient: &RecipientKey) -> Result<Json<ApiResponse<RecipientKeyResponse>>, ApiError> {
+    let public_key = recipient.to_base64().map_err(|e| {
+        tracing::error!("Failed to encode a recipient key: {}", e);
+        ApiError::internal()
+    })?;
+    Ok(Json(ApiResponse::success(RecipientKeyResponse {
+        tenant,
+        algorithm: recipient.algorithm().to_string(),
+        public_key,
+    })))
+}
+
+/// Hand out (once) the private key held for address `id` of `pool_name`
+fn reveal(
+    app_state: &PetAppState,
+    pool_name: &str,
+    id: u64,
+    headers: &HeaderMap,
+) -> Result<Json<ApiResponse<RevealResponse>>, ApiError> {
+    let redaction = app_state
+        .redaction
+        .as_ref()
+        .ok_or_else(|| ApiError::with_detail(ErrorCode::NotFound, "Private keys are not held back on this server"))?;
+    let provided = headers.get(REVEAL_KEY_HEADER).map(|value| value.as_bytes()).unwrap_or_default();
+    if !keys_match(provided, &redaction.reveal_key) {
+        return Err(ApiError::with_detail(ErrorCode::Unauthorized, format!("Missing or wrong {}", REVEAL_KEY_HEADER)));
+    }
+    let recipient = recipient_key(app_state, pool_name, headers)?;
+
+    let held = match redaction.vault.reveal(pool_name, id) {
+        Ok(Some(held)) if held.is_destroyed() => {
+            let destroyed_at = held.destroyed_at.map(|at| at.to_rfc3339()).unwrap_or_default();
+            return Err(ApiError::with_detail(
+                ErrorCode::NotFound,
+                format!("The private key of address {} was destroyed at {} by the retention policy", id, destroyed_at),
+            ));
+        }
+        Ok(Some(held)) => held,
         Ok(None) => {
-            Err(StatusCode::NOT_FOUND)
+            return Err(ApiError::with_detail(
+                ErrorCode::NotFound,
+                format!("No private key held for address {}, or it was already revealed", id),
+            ))
         }
         Err(e) => {
-            tracing::error!("Failed to get Pet address: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            tracing::error!("Failed to reveal the private key of address {} in pool '{}': {}", id, pool_name, e);
+            return Err(ApiError::internal());
         }
+    };
+    tracing::info!("Revealed the private key of address {} in pool '{}'", id, pool_name);
+
+    let (private_key, encrypted_private_key) = match recipient {
+        Some(recipient) => (None, Some(seal_for(&recipient, &held.private_key)?)),
+        None => (Some(held.private_key), None),
+    };
+    Ok(Json(ApiResponse::success(RevealResponse {
+        id,
+        address: held.address,
+        private_key,
+        encrypted_private_key,
+    })))
+}
+
+fn not_on_sled() -> ApiError {
+    ApiError::with_detail(ErrorCode::NotImplemented, "The storage backend does not support this operation")
+}
+
+fn invalid_pattern(e: impl std::fmt::Display) -> ApiError {
+    ApiError::with_detail(ErrorCode::InvalidPattern, e.to_string())
+}
+
+fn no_recipient_key(tenant: &str) -> ApiError {
+    ApiError::with_detail(ErrorCode::NotFound, format!("Tenant '{}' has no recipient key registered", tenant))
+}
+
+/// Pool of `tenant`, once the request carries its API key (if it has one)
+fn tenant_pool(app_state: &PetAppState, tenant: &str, headers: &HeaderMap) -> Result<PatternPool, ApiError> {
+    let pool = app_state
+        .generator
+        .tenant(tenant)
+        .ok_or_else(|| ApiError::with_detail(ErrorCode::UnknownTenant, format!("No tenant named '{}'", tenant)))?;
+
+    if !app_state.tenant_authorized(tenant, headers) {
+        return Err(ApiError::with_detail(ErrorCode::Unauthorized, format!("Missing or wrong {}", API_KEY_HEADER)));
     }
+    Ok(pool)
+}
+
+fn acknowledge(storage: &dyn Storage, id: u64) -> Result<Json<ApiResponse<AcknowledgeResponse>>, ApiError> {
+    if !storage.acknowledge(id) {
+        return Err(ApiError::with_detail(ErrorCode::NotFound, format!("No leased address with id {}", id)));
+    }
+
+    Ok(Json(ApiResponse::success(AcknowledgeResponse { id, acknowledged: true })))
 }
 
 #[utoipa::path(
@@ -53,26 +1311,209 @@ pub async fn get_pet_address(
     path = "/api/v1/pet/status",
     responses(
         (status = 200, description = "Pet generator status", body = ApiResponse<PetGeneratorStatusResponse>),
-        (status = 500, description = "Internal server error", body = ApiResponse<String>)
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
     ),
     tag = "Pet Address"
 )]
 pub async fn get_pet_status(
     State(app_state): State<Arc<PetAppState>>,
-) -> Result<Json<ApiResponse<PetGeneratorStatusResponse>>, StatusCode> {
+) -> Result<Json<ApiResponse<PetGeneratorStatusResponse>>, ApiError> {
     match app_state.generator.get_current_count().await {
         Ok(count) => {
             let response = PetGeneratorStatusResponse {
                 total_addresses: count,
-                pool_size: 100, // TODO: Get from config
-                generation_active: true, // TODO: Get actual status
+                pool_size: app_state.generator.target_pool_size(),
+                generation_active: app_state.generator.is_generating(),
+                max_queue_size: app_state.storage.capacity(),
             };
             
             Ok(Json(ApiResponse::success(response)))
         }
         Err(e) => {
             tracing::error!("Failed to get Pet generator status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal())
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/pet/{pattern}/status",
+    params(
+        ("pattern" = String, Path, description = "Pattern pool name: `default` or one of the configured extra suffixes", example = "Cat")
+    ),
+    responses(
+        (status = 200, description = "Pattern pool status", body = ApiResponse<PetGeneratorStatusResponse>),
+        (status = 404, description = "Unknown pattern", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn get_pattern_status(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(pattern): Path<String>,
+) -> Result<Json<ApiResponse<PetGeneratorStatusResponse>>, ApiError> {
+    let pool = app_state.generator.pool(&pattern).ok_or_else(|| ApiError::unknown_pool(&pattern))?;
+
+    match pool.storage.count_addresses() {
+        Ok(count) => {
+            let response = PetGeneratorStatusResponse {
+                total_addresses: count,
+                pool_size: app_state.generator.target_pool_size(),
+                generation_active: app_state.generator.is_generating(),
+                max_queue_size: pool.storage.capacity(),
+            };
+
+            Ok(Json(ApiResponse::success(response)))
+        }
+        Err(e) => {
+            tracing::error!("Failed to get status for pool '{}': {}", pool.name, e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/estimate",
+    params(
+        ("pattern" = Option<String>, Query, description = "Regex to estimate (default: the configured pattern)", example = "^Pin.*Pet$"),
+        ("case_sensitive" = Option<bool>, Query, description = "Set to false to match the pattern case-insensitively (default: true)")
+    ),
+    responses(
+        (status = 200, description = "Expected attempts and time per address for the pattern", body = ApiResponse<DifficultyEstimateResponse>),
+        (status = 400, description = "Invalid, impossible or unsupported pattern", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn get_difficulty_estimate(
+    State(app_state): State<Arc<PetAppState>>,
+    Query(query): Query<DifficultyEstimateQuery>,
+) -> Result<Json<ApiResponse<DifficultyEstimateResponse>>, ApiError> {
+    let matcher = match query.pattern.as_deref().filter(|p| !p.is_empty()) {
+        Some(pattern) => Arc::new(
+            AddressMatcher::regex(pattern, query.case_sensitive.unwrap_or(true)).map_err(invalid_pattern)?,
+        ),
+        None => app_state
+            .generator
+            .pool(DEFAULT_POOL)
+            .map(|pool| pool.matcher)
+            .ok_or(ApiError::internal())?,
+    };
+
+    // Prefer the live rate; fall back to the last `benchmark` run
+    let keys_per_second = match app_state.generator.grinder().keys_per_second() {
+        Some(rate) => Some(rate),
+        None => match app_state.storage.sled() {
+            Some(storage) => storage
+                .load_benchmark()
+                .ok()
+                .flatten()
+                .map(|report| report.keys_per_second()),
+            None => None,
+        },
+    };
+    match estimate(&matcher, keys_per_second) {
+        Ok(difficulty) => Ok(Json(ApiResponse::success(DifficultyEstimateResponse {
+            pattern: matcher.describe(),
+            probability: difficulty.probability,
+            expected_attempts: difficulty.expected_attempts,
+            keys_per_second: difficulty.keys_per_second,
+            estimated_seconds: difficulty.estimated_seconds,
+        }))),
+        Err(e) => {
+            tracing::debug!("Cannot estimate pattern: {}", e);
+            Err(invalid_pattern(e))
+        }
+    }
+}
+
+/// Check a third-party address against a pattern
+///
+/// Reports whether the address is a valid Solana public key and whether it
+/// matches the pattern, using the generator's own matcher
+#[utoipa::path(
+    post,
+    path = "/api/v1/validate",
+    request_body = ValidateAddressRequest,
+    responses(
+        (status = 200, description = "Validity of the address and whether it matches", body = ApiResponse<ValidateAddressResponse>),
+        (status = 400, description = "Invalid or impossible pattern", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pool", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Pet Address"
+)]
+pub async fn validate_address(
+    State(app_state): State<Arc<PetAppState>>,
+    Json(request): Json<ValidateAddressRequest>,
+) -> Result<Json<ApiResponse<ValidateAddressResponse>>, ApiError> {
+    let pool_name = request.pool.as_deref().unwrap_or(DEFAULT_POOL);
+    let matcher = match request.pattern.as_deref().filter(|p| !p.is_empty()) {
+        Some(pattern) => Arc::new(
+            AddressMatcher::regex(pattern, request.case_sensitive.unwrap_or(true)).map_err(invalid_pattern)?,
+        ),
+        None => app_state
+            .generator
+            .any_pool(pool_name)
+            .map(|pool| pool.matcher)
+            .ok_or_else(|| ApiError::unknown_pool(pool_name))?,
+    };
+
+    let (valid_address, on_curve, error) = match validate_solana_address(&request.address) {
+        Ok(()) => {
+            let on_curve = Pubkey::from_str(&request.address).is_ok_and(|key| key.is_on_curve());
+            (true, on_curve, None)
+        }
+        Err(e) => (false, false, Some(e)),
+    };
+
+    Ok(Json(ApiResponse::success(ValidateAddressResponse {
+        matches: valid_address && matcher.is_match(&request.address),
+        pattern: matcher.describe(),
+        address: request.address,
+        valid_address,
+        on_curve,
+        error,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::PetAddress;
+    use axum::http::{HeaderValue, StatusCode};
+
+    #[tokio::test]
+    async fn test_idempotency_keys_are_scoped_per_caller_and_delivery() {
+        let app_state = PetAppState::temporary().await;
+        let storage = app_state.storage.as_ref();
+        let matcher = Arc::clone(&app_state.generator.pools()[0].matcher);
+        for _ in 0..3 {
+            storage.store_address(PetAddress::generate(&matcher).unwrap()).unwrap();
         }
+
+        let mut headers = HeaderMap::new();
+        headers.insert(IDEMPOTENCY_KEY_HEADER, HeaderValue::from_static("order-42"));
+        let client = |ip: &str| Requester {
+            ip: Some(ip.to_string()),
+            user_agent: None,
+        };
+        let pop = |include_private_key: bool, requester: &Requester| {
+            let request = BatchPopRequest {
+                count: 1,
+                include_private_key: Some(include_private_key),
+            };
+            pop_batch(&app_state, storage, DEFAULT_POOL, &request, &headers, requester)
+                .map(|response| response.0.data.unwrap().addresses[0].public_key.clone())
+        };
+
+        let first = pop(true, &client("203.0.113.7")).unwrap();
+        assert_eq!(pop(true, &client("203.0.113.7")).unwrap(), first);
+        // Another client with the same key gets its own address
+        assert_ne!(pop(true, &client("198.51.100.2")).unwrap(), first);
+        assert_eq!(storage.count_addresses().unwrap(), 1);
+
+        let error = pop(false, &client("203.0.113.7")).unwrap_err();
+        assert_eq!(error.status, StatusCode::UNPROCESSABLE_ENTITY);
     }
-}
\ No newline at end of file
+}
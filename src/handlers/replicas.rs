@@ -0,0 +1,139 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use std::sync::Arc;
+
+use crate::error::{ApiError, ErrorCode, ProblemDetails};
+use crate::handlers::PetAppState;
+use crate::models::{ApiResponse, ReplicaAddress, ReplicaPoolStatus, ReplicaPopRequest, ReplicaStoreRequest, ReplicaStoreResponse, MAX_BATCH_POP};
+use crate::pet::{AuditAction, AuditEvent, PatternPool, PetAddress, StoreError};
+
+/// Store an address ground by a replica
+///
+/// Replicas using the `primary` storage backend send every address they
+/// grind here instead of keeping a queue of their own
+#[utoipa::path(
+    post,
+    path = "/replicas/pools/{pool}/addresses",
+    params(
+        ("pool" = String, Path, description = "Pool name, e.g. `default`, `Pin` or `tenant:staging`")
+    ),
+    request_body = ReplicaStoreRequest,
+    responses(
+        (status = 200, description = "Address queued", body = ApiResponse<ReplicaStoreResponse>),
+        (status = 401, description = "Missing or wrong replica bearer token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pool", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "The address was enqueued before", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "The pool is at max_queue_size", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Replicas"
+)]
+pub async fn replica_store_address(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(pool_name): Path<String>,
+    Json(request): Json<ReplicaStoreRequest>,
+) -> Result<Json<ApiResponse<ReplicaStoreResponse>>, ApiError> {
+    let pool = replica_pool(&app_state, &pool_name)?;
+    let address = PetAddress {
+        public_key: request.public_key,
+        private_key: request.private_key,
+        address: request.address,
+    };
+    match pool.storage.store_address(address) {
+        Ok(id) => Ok(Json(ApiResponse::success(ReplicaStoreResponse { id }))),
+        Err(e) => Err(match e.downcast_ref::<StoreError>() {
+            Some(StoreError::Duplicate { .. }) => ApiError::with_detail(ErrorCode::Conflict, e.to_string()),
+            Some(StoreError::QueueFull { .. } | StoreError::Busy { .. } | StoreError::Degraded { .. }) => {
+                ApiError::with_detail(ErrorCode::Unavailable, e.to_string())
+            }
+            None => {
+                tracing::error!("Failed to store an address from a replica in pool '{}': {}", pool.name, e);
+                ApiError::internal()
+            }
+        }),
+    }
+}
+
+/// Pop addresses for a replica
+///
+/// Pops are final: with leases enabled on the primary they are acknowledged
+/// at once, since the replica has already handed the addresses out
+#[utoipa::path(
+    post,
+    path = "/replicas/pools/{pool}/pop",
+    params(
+        ("pool" = String, Path, description = "Pool name, e.g. `default`, `Pin` or `tenant:staging`")
+    ),
+    request_body = ReplicaPopRequest,
+    responses(
+        (status = 200, description = "Popped addresses, oldest first", body = ApiResponse<Vec<ReplicaAddress>>),
+        (status = 400, description = "count outside 1..=1000", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or wrong replica bearer token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pool", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Replicas"
+)]
+pub async fn replica_pop_addresses(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(pool_name): Path<String>,
+    Json(request): Json<ReplicaPopRequest>,
+) -> Result<Json<ApiResponse<Vec<ReplicaAddress>>>, ApiError> {
+    if request.count == 0 || request.count > MAX_BATCH_POP {
+        return Err(ApiError::with_detail(
+            ErrorCode::InvalidRequest,
+            format!("count must be between 1 and {}", MAX_BATCH_POP),
+        ));
+    }
+    let pool = replica_pool(&app_state, &pool_name)?;
+    let popped = pool.storage.get_next_addresses(request.count).map_err(|e| match e.downcast_ref::<StoreError>() {
+        Some(StoreError::Busy { .. }) => ApiError::with_detail(ErrorCode::Unavailable, e.to_string()),
+        _ => {
+            tracing::error!("Failed to pop addresses for a replica from pool '{}': {}", pool.name, e);
+            ApiError::internal()
+        }
+    })?;
+    for info in &popped {
+        pool.storage.acknowledge(info.id);
+        app_state.audit(
+            AuditEvent::new(AuditAction::Pop)
+                .pool(&pool.name)
+                .subject(info.address.public_key.clone())
+                .detail(format!("id {}, handed to a replica", info.id)),
+        );
+    }
+    Ok(Json(ApiResponse::success(popped.into_iter().map(ReplicaAddress::from).collect())))
+}
+
+/// Queue size of a pool, for replicas
+#[utoipa::path(
+    get,
+    path = "/replicas/pools/{pool}/status",
+    params(
+        ("pool" = String, Path, description = "Pool name, e.g. `default`, `Pin` or `tenant:staging`")
+    ),
+    responses(
+        (status = 200, description = "Pool size", body = ApiResponse<ReplicaPoolStatus>),
+        (status = 401, description = "Missing or wrong replica bearer token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pool", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Replicas"
+)]
+pub async fn replica_pool_status(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(pool_name): Path<String>,
+) -> Result<Json<ApiResponse<ReplicaPoolStatus>>, ApiError> {
+    let pool = replica_pool(&app_state, &pool_name)?;
+    let count = pool.storage.count_addresses().map_err(|e| {
+        tracing::error!("Failed to count addresses of pool '{}' for a replica: {}", pool.name, e);
+        ApiError::internal()
+    })?;
+    Ok(Json(ApiResponse::success(ReplicaPoolStatus {
+        count,
+        capacity: pool.storage.capacity(),
+    })))
+}
+
+fn replica_pool(app_state: &PetAppState, pool_name: &str) -> Result<PatternPool, ApiError> {
+    app_state.generator.any_pool(pool_name).ok_or_else(|| ApiError::unknown_pool(pool_name))
+}
@@ -1,8 +1,8 @@
 use axum::{
     extract::Query,
     response::Json,
-    http::StatusCode,
 };
+use crate::error::{ApiError, ErrorCode, ProblemDetails};
 use crate::models::{ApiResponse, ServerTimeResponse, TimeQuery};
 
 /// Get current server time
@@ -17,27 +17,24 @@ use crate::models::{ApiResponse, ServerTimeResponse, TimeQuery};
     ),
     responses(
         (status = 200, description = "Successfully returned server time", body = ApiResponse<ServerTimeResponse>),
-        (status = 400, description = "Request parameter error"),
-        (status = 500, description = "Internal server error")
+        (status = 400, description = "Request parameter error", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
     ),
     tag = "Time Service"
 )]
 pub async fn get_server_time(
     Query(query): Query<TimeQuery>
-) -> Result<Json<ApiResponse<ServerTimeResponse>>, StatusCode> {
+) -> Result<Json<ApiResponse<ServerTimeResponse>>, ApiError> {
     
     // Validate time format parameter
     if let Some(ref format) = query.format {
         match format.as_str() {
             "iso8601" | "timestamp" | "formatted" => {},
             _ => {
-                let error_response: ApiResponse<ServerTimeResponse> = ApiResponse {
-                    code: 400,
-                    message: "Invalid time format, supported formats: iso8601, timestamp, formatted".to_string(),
-                    data: None,
-                    timestamp: chrono::Utc::now().timestamp(),
-                };
-                return Ok(Json(error_response));
+                return Err(ApiError::with_detail(
+                    ErrorCode::InvalidRequest,
+                    "Invalid time format, supported formats: iso8601, timestamp, formatted",
+                ));
             }
         }
     }
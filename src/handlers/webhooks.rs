@@ -0,0 +1,143 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use std::sync::Arc;
+
+use crate::error::{ApiError, ErrorCode, ProblemDetails};
+use crate::handlers::{PetAppState, API_KEY_HEADER};
+use crate::models::{ApiResponse, CreateSubscriptionRequest, SubscriptionResponse};
+use crate::pet::generator::TENANT_POOL_PREFIX;
+use crate::pet::{SubscribeError, WebhookDispatcher, DEFAULT_POOL};
+
+/// Register a webhook subscription
+///
+/// `count` addresses of the pool are POSTed to `callback_url` in batches as
+/// they become available, each signed with the returned `secret`
+#[utoipa::path(
+    post,
+    path = "/api/v1/subscriptions",
+    request_body = CreateSubscriptionRequest,
+    params(
+        ("X-API-Key" = Option<String>, Header, description = "The tenant's API key, for a tenant pool with one")
+    ),
+    responses(
+        (status = 201, description = "Subscription registered; delivery has started", body = ApiResponse<SubscriptionResponse>),
+        (status = 400, description = "Invalid callback URL, count or recipient key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or wrong API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pool, or webhooks are disabled", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 429, description = "Too many active subscriptions", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Webhooks"
+)]
+pub async fn create_subscription(
+    State(app_state): State<Arc<PetAppState>>,
+    headers: HeaderMap,
+    Json(request): Json<CreateSubscriptionRequest>,
+) -> Result<(StatusCode, Json<ApiResponse<SubscriptionResponse>>), ApiError> {
+    let webhooks = dispatcher(&app_state)?;
+    let pool = request.pool.as_deref().unwrap_or(DEFAULT_POOL);
+    if app_state.generator.any_pool(pool).is_none() {
+        return Err(ApiError::unknown_pool(pool));
+    }
+    authorize(&app_state, pool, &headers)?;
+
+    match webhooks.subscribe(pool, &request.callback_url, request.count, request.recipient_key) {
+        Ok(subscription) => Ok((
+            StatusCode::CREATED,
+            Json(ApiResponse::success(SubscriptionResponse::with_secret(subscription))),
+        )),
+        Err(e) => match e.downcast_ref::<SubscribeError>() {
+            Some(SubscribeError::Invalid(reason)) => {
+                tracing::info!("Rejected subscription: {}", reason);
+                Err(ApiError::with_detail(ErrorCode::InvalidRequest, reason.clone()))
+            }
+            Some(SubscribeError::TooMany { .. }) => {
+                tracing::warn!("Rejected subscription: {}", e);
+                Err(ApiError::with_detail(ErrorCode::TooManySubscriptions, e.to_string()))
+            }
+            None => {
+                tracing::error!("Failed to register subscription: {}", e);
+                Err(ApiError::internal())
+            }
+        },
+    }
+}
+
+/// Delivery progress of a webhook subscription
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscriptions/{id}",
+    params(
+        ("id" = String, Path, description = "Subscription ID returned by POST /api/v1/subscriptions"),
+        ("X-API-Key" = Option<String>, Header, description = "The tenant's API key, for a tenant pool with one")
+    ),
+    responses(
+        (status = 200, description = "Subscription status", body = ApiResponse<SubscriptionResponse>),
+        (status = 401, description = "Missing or wrong API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown subscription, or webhooks are disabled", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Webhooks"
+)]
+pub async fn get_subscription(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<SubscriptionResponse>>, ApiError> {
+    let subscription = dispatcher(&app_state)?.get(&id).ok_or_else(|| unknown_subscription(&id))?;
+    authorize(&app_state, &subscription.pool, &headers)?;
+    Ok(Json(ApiResponse::success(SubscriptionResponse::new(subscription))))
+}
+
+/// Cancel a webhook subscription
+///
+/// No further batches are sent; a batch being retried is given up
+#[utoipa::path(
+    delete,
+    path = "/api/v1/subscriptions/{id}",
+    params(
+        ("id" = String, Path, description = "Subscription ID returned by POST /api/v1/subscriptions"),
+        ("X-API-Key" = Option<String>, Header, description = "The tenant's API key, for a tenant pool with one")
+    ),
+    responses(
+        (status = 200, description = "Subscription cancelled (or already finished)", body = ApiResponse<SubscriptionResponse>),
+        (status = 401, description = "Missing or wrong API key", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown subscription, or webhooks are disabled", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Webhooks"
+)]
+pub async fn cancel_subscription(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<SubscriptionResponse>>, ApiError> {
+    let webhooks = dispatcher(&app_state)?;
+    let subscription = webhooks.get(&id).ok_or_else(|| unknown_subscription(&id))?;
+    authorize(&app_state, &subscription.pool, &headers)?;
+    let subscription = webhooks.cancel(&id).ok_or_else(|| unknown_subscription(&id))?;
+    Ok(Json(ApiResponse::success(SubscriptionResponse::new(subscription))))
+}
+
+fn dispatcher(app_state: &PetAppState) -> Result<&WebhookDispatcher, ApiError> {
+    app_state
+        .webhooks
+        .as_ref()
+        .ok_or_else(|| ApiError::with_detail(ErrorCode::NotFound, "Webhooks are disabled"))
+}
+
+fn unknown_subscription(id: &str) -> ApiError {
+    ApiError::with_detail(ErrorCode::NotFound, format!("No subscription with id '{}'", id))
+}
+
+/// Tenant pools need the tenant's API key; other pools are open like their fetch routes
+fn authorize(app_state: &PetAppState, pool: &str, headers: &HeaderMap) -> Result<(), ApiError> {
+    let Some(tenant) = pool.strip_prefix(TENANT_POOL_PREFIX) else {
+        return Ok(());
+    };
+    if !app_state.tenant_authorized(tenant, headers) {
+        return Err(ApiError::with_detail(ErrorCode::Unauthorized, format!("Missing or wrong {}", API_KEY_HEADER)));
+    }
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use std::sync::Arc;
+
+use crate::error::{ApiError, ErrorCode, ProblemDetails};
+use crate::handlers::PetAppState;
+use crate::models::{
+    ApiResponse, RejectedKeypair, ThrottleHint, WorkerAssignmentResponse, WorkerHeartbeatRequest, WorkerInstructions, WorkerRegisterRequest,
+    WorkerSubmitRequest, WorkerSubmitResponse,
+};
+use crate::pet::{metrics, GeneratorEvent, PetAddress, StoreError, Worker, DEFAULT_POOL};
+
+/// Register a remote grind worker
+///
+/// Returns the worker's ID, the pool to grind for and throttle hints. The
+/// worker then sends a heartbeat every `heartbeat_interval_seconds` to stay
+/// alive and pick up a new assignment.
+#[utoipa::path(
+    post,
+    path = "/workers/register",
+    request_body = WorkerRegisterRequest,
+    responses(
+        (status = 200, description = "Worker registered", body = ApiResponse<WorkerInstructions>),
+        (status = 400, description = "Empty worker name", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or wrong worker bearer token", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Workers"
+)]
+pub async fn register_worker(
+    State(app_state): State<Arc<PetAppState>>,
+    Json(request): Json<WorkerRegisterRequest>,
+) -> Result<Json<ApiResponse<WorkerInstructions>>, ApiError> {
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::with_detail(ErrorCode::InvalidRequest, "name must not be empty"));
+    }
+    let worker = app_state.workers.register(name, request.threads);
+    tracing::info!("👷 Worker '{}' registered as {}", worker.name, worker.id);
+    Ok(Json(ApiResponse::success(instructions(&app_state, &worker))))
+}
+
+/// Worker heartbeat
+///
+/// Reports the worker's throughput and returns its current assignment and
+/// throttle hints. `404` means the server has forgotten the worker (after a
+/// restart or a long silence); it should register again.
+#[utoipa::path(
+    post,
+    path = "/workers/{id}/heartbeat",
+    params(
+        ("id" = String, Path, description = "Worker ID returned by POST /workers/register")
+    ),
+    request_body = WorkerHeartbeatRequest,
+    responses(
+        (status = 200, description = "Current assignment", body = ApiResponse<WorkerInstructions>),
+        (status = 401, description = "Missing or wrong worker bearer token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown worker; register again", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Workers"
+)]
+pub async fn worker_heartbeat(
+    State(app_state): State<Arc<PetAppState>>,
+    Path(id): Path<String>,
+    Json(request): Json<WorkerHeartbeatRequest>,
+) -> Result<Json<ApiResponse<WorkerInstructions>>, ApiError> {
+    let worker = app_state
+        .workers
+        .heartbeat(&id, request.keys_per_second, request.keys_tried)
+        .ok_or_else(|| ApiError::with_detail(ErrorCode::NotFound, format!("No worker with id '{}'", id)))?;
+    Ok(Json(ApiResponse::success(instructions(&app_state, &worker))))
+}
+
+/// Assign a pool to `worker` and tell it how fast to go
+fn instructions(app_state: &PetAppState, worker: &Worker) -> WorkerInstructions {
+    let assignment = app_state.workers.assign(&worker.id, &app_state.generator);
+    let config = app_state.workers.config();
+    WorkerInstructions {
+        worker_id: worker.id.clone(),
+        heartbeat_interval_seconds: config.heartbeat_interval_seconds,
+        throttle: ThrottleHint {
+            pause: assignment.is_none() || app_state.generator.grinder().limits().is_paused(),
+            max_keys_per_second: config.max_keys_per_second,
+        },
+        assignment: assignment.map(WorkerAssignmentResponse::from),
+    }
+}
+
+/// Submit keypairs found by a remote grind worker
+///
+/// Each keypair is checked before it is queued: the private key must derive
+/// the public key, and the address must match the pool's pattern. Invalid
+/// keypairs are listed in `rejected`. Once the pool is full the remaining
+/// keypairs are rejected too, so the worker can hold on to them.
+#[utoipa::path(
+    post,
+    path = "/workers/submit",
+    request_body = WorkerSubmitRequest,
+    responses(
+        (status = 200, description = "Keypairs checked; valid ones are queued", body = ApiResponse<WorkerSubmitResponse>),
+        (status = 400, description = "More keypairs than workers.max_batch", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or wrong worker bearer token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown pool", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ProblemDetails, content_type = "application/problem+json")
+    ),
+    tag = "Workers"
+)]
+pub async fn submit_keypairs(
+    State(app_state): State<Arc<PetAppState>>,
+    Json(request): Json<WorkerSubmitRequest>,
+) -> Result<Json<ApiResponse<WorkerSubmitResponse>>, ApiError> {
+    let pool_name = request.pool.as_deref().unwrap_or(DEFAULT_POOL);
+    let pool = app_state.generator.any_pool(pool_name).ok_or_else(|| ApiError::unknown_pool(pool_name))?;
+    let max_batch = app_state.workers.config().max_batch;
+    if request.keypairs.len() > max_batch {
+        return Err(ApiError::with_detail(
+            ErrorCode::InvalidRequest,
+            format!("at most {} keypairs per submission", max_batch),
+        ));
+    }
+    let worker = request.worker.as_deref().unwrap_or("unnamed");
+
+    let mut response = WorkerSubmitResponse {
+        pool: pool.name.clone(),
+        accepted: 0,
+        duplicates: 0,
+        rejected: Vec::new(),
+    };
+    let mut invalid = 0;
+    let mut remaining_reason = "pool is full";
+    let mut keypairs = request.keypairs.into_iter();
+    for submitted in keypairs.by_ref() {
+        let address = PetAddress {
+            address: submitted.public_key.clone(),
+            public_key: submitted.public_key,
+            private_key: submitted.private_key,
+        };
+        let checked = address.check_keypair().and_then(|_| match pool.matcher.is_match(&address.address) {
+            true => Ok(()),
+            false => Err(anyhow::anyhow!("does not match {}", pool.matcher.describe())),
+        });
+        if let Err(e) = checked {
+            invalid += 1;
+            response.rejected.push(RejectedKeypair {
+                public_key: address.public_key,
+                reason: e.to_string(),
+            });
+            continue;
+        }
+
+        let public_key = address.public_key.clone();
+        match pool.storage.store_address(address) {
+            Ok(id) => {
+                response.accepted += 1;
+                app_state.generator.events().publish(GeneratorEvent::AddressFound {
+                    pool: pool.name.clone(),
+                    id,
+                    address: public_key,
+                });
+            }
+            Err(e) => match e.downcast_ref::<StoreError>() {
+                Some(StoreError::Duplicate { .. }) => response.duplicates += 1,
+                Some(error @ (StoreError::QueueFull { .. } | StoreError::Busy { .. } | StoreError::Degraded { .. })) => {
+                    match error {
+                        StoreError::Busy { .. } => remaining_reason = "storage is busy",
+                        StoreError::Degraded { .. } => remaining_reason = "storage is degraded",
+                        _ => {}
+                    }
+                    response.rejected.push(RejectedKeypair {
+                        public_key,
+                        reason: e.to_string(),
+                    });
+                    break;
+                }
+                None => {
+                    tracing::error!("Failed to store a keypair from worker '{}' in pool '{}': {}", worker, pool.name, e);
+                    return Err(ApiError::internal());
+                }
+            },
+        }
+    }
+    // Nothing more fits once the pool is full or the writer is behind
+    for submitted in keypairs {
+        response.rejected.push(RejectedKeypair {
+            public_key: submitted.public_key,
+            reason: remaining_reason.to_string(),
+        });
+    }
+
+    metrics::record_worker_keypairs(&pool.name, &response);
+    app_state.workers.record_submission(worker, response.accepted, response.duplicates, response.rejected.len());
+    if invalid > 0 {
+        tracing::warn!("Worker '{}' submitted {} invalid keypairs for pool '{}'", worker, invalid, pool.name);
+    }
+    tracing::info!(
+        "Worker '{}' submitted {} keypairs to pool '{}' ({} duplicates, {} rejected)",
+        worker,
+        response.accepted,
+        pool.name,
+        response.duplicates,
+        response.rejected.len()
+    );
+    Ok(Json(ApiResponse::success(response)))
+}
@@ -1,49 +1,232 @@
+pub mod cli;
 pub mod config;
+pub mod error;
 pub mod handlers;
 pub mod middleware;
 pub mod models;
 pub mod routes;
 pub mod utils;
 pub mod pet;
+pub mod preflight;
+pub mod telemetry;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(unix)]
+pub mod unix_socket;
 
-use axum::Router;
+pub use crate::pet::{Generator, GeneratorBuilder};
+
+use anyhow::Context;
+use axum::{middleware::{from_fn, from_fn_with_state}, Router};
 use tower::ServiceBuilder;
 use utoipa::OpenApi;
-use utoipa_swagger_ui::SwaggerUi;
+use utoipa_swagger_ui::{Config as SwaggerUiConfig, SwaggerUi};
+use std::collections::HashMap;
+use std::future::{Future, IntoFuture};
+use std::io::Write;
+use std::pin::Pin;
 use std::sync::Arc;
 
-use crate::config::AppConfig;
-use crate::middleware::{cors_layer, logging_layer, RateLimiter};
-use crate::routes::create_routes;
-use crate::handlers::PetAppState;
-use crate::pet::{PetGenerator, PetStorage};
+use crate::config::{AppConfig, ClientCertRoutes, JwtAlgorithm, LoggingConfig, StorageBackend, SwaggerConfig};
+use crate::middleware::{access_log_middleware, admin_auth_middleware, admin_unconfigured_middleware, audit_actor_middleware, audit_admin_middleware, ip_filter_middleware, jwt_auth_middleware, IpFilter, JwtGuard, JwtValidator, StaticCredential, JWT_SECRET_ENV, client_cert_middleware, compression_middleware, content_negotiation_middleware, cors_layer, http_metrics_middleware, logging_layer, problem_details_middleware, propagate_request_id_layer, rate_limit_middleware, request_id_layer, response_signing_middleware, sensitive_headers_layer, worker_auth_middleware, replica_auth_middleware, RateLimiter, ResponseSigner, RouteClass, ADMIN_KEY_ENV, REPLICA_KEY_ENV, SIGNING_KEY_ENV, WORKER_KEY_ENV};
+use crate::routes::{admin_routes, create_routes, metrics_routes, openapi_routes, probe_routes, websocket_routes, worker_routes, replica_routes, OPENAPI_PATH};
+use crate::handlers::{PetAppState, Redaction};
+use crate::pet::vault::REVEAL_KEY_ENV;
+use crate::pet::generator::TENANT_POOL_PREFIX;
+use crate::pet::{start_snapshots, AuditLog, AuditedStorage, start_watermark_watcher, IdempotencyCache, JobManager, KeyRing, KeyVault, OverrideStore, RecipientRegistry, PetGenerator, PetStorage, PoolMaintenance, Quota, SnapshotStore, Storage, SubscriptionStore, UsageStore, WebhookDispatcher, WorkerRegistry, ShardRouter, AddressMatcher, DumpFormat, DumpRecord, KeyFileFormat, StoreError, DEFAULT_POOL, parse_dump};
+use crate::preflight::Preflight;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         crate::handlers::health::health_check,
         crate::handlers::health::detailed_health_check,
+        crate::handlers::health::healthz,
+        crate::handlers::health::livez,
+        crate::handlers::health::readyz,
+        crate::handlers::metrics::get_metrics,
+        crate::handlers::metrics::get_generator_stats,
+        crate::handlers::metrics::get_suffix_stats,
         crate::handlers::time::get_server_time,
         crate::handlers::time::get_multi_timezone,
         crate::handlers::pet::get_pet_address,
         crate::handlers::pet::get_pet_status,
+        crate::handlers::pet::get_pattern_address,
+        crate::handlers::pet::get_pet_address_by_letter,
+        crate::handlers::pet::get_pattern_address_by_letter,
+        crate::handlers::pet::pop_pet_addresses,
+        crate::handlers::pet::pop_pattern_addresses,
+        crate::handlers::pet::acknowledge_pet_address,
+        crate::handlers::pet::acknowledge_pattern_address,
+        crate::handlers::pet::reveal_pet_private_key,
+        crate::handlers::pet::reveal_pattern_private_key,
+        crate::handlers::pet::prove_pet_address,
+        crate::handlers::pet::prove_pattern_address,
+        crate::handlers::pet::peek_pet_addresses,
+        crate::handlers::pet::peek_pattern_addresses,
+        crate::handlers::pet::get_pattern_status,
+        crate::handlers::pet::get_tenant_address,
+        crate::handlers::pet::get_tenant_status,
+        crate::handlers::pet::reveal_tenant_private_key,
+        crate::handlers::pet::prove_tenant_address,
+        crate::handlers::pet::register_recipient_key,
+        crate::handlers::pet::get_recipient_key,
+        crate::handlers::pet::delete_recipient_key,
+        crate::handlers::pet::get_difficulty_estimate,
+        crate::handlers::pet::validate_address,
+        crate::handlers::admin::get_queue_diagnostics,
+        crate::handlers::admin::get_dispensed_addresses,
+        crate::handlers::admin::get_audit_log,
+        crate::handlers::admin::verify_audit_log,
+        crate::handlers::admin::get_usage,
+        crate::handlers::admin::export_addresses,
+        crate::handlers::admin::import_addresses,
+        crate::handlers::admin::verify_keys,
+        crate::handlers::admin::get_address_keyfile,
+        crate::handlers::admin::list_workers,
+        crate::handlers::workers::register_worker,
+        crate::handlers::workers::worker_heartbeat,
+        crate::handlers::workers::submit_keypairs,
+        crate::handlers::replicas::replica_store_address,
+        crate::handlers::replicas::replica_pop_addresses,
+        crate::handlers::replicas::replica_pool_status,
+        crate::handlers::admin::get_generator_control,
+        crate::handlers::admin::pause_generator,
+        crate::handlers::admin::resume_generator,
+        crate::handlers::admin::throttle_generator,
+        crate::handlers::admin::get_runtime_config,
+        crate::handlers::admin::update_runtime_config,
+        crate::handlers::admin::reset_runtime_config,
+        crate::handlers::admin::reload_runtime_config,
+        crate::handlers::events::stream_generator_events,
+        crate::handlers::events::subscribe_addresses,
+        crate::handlers::jobs::create_job,
+        crate::handlers::jobs::get_job,
+        crate::handlers::webhooks::create_subscription,
+        crate::handlers::webhooks::get_subscription,
+        crate::handlers::webhooks::cancel_subscription,
     ),
     components(schemas(
         crate::models::ApiResponse<crate::models::HealthResponse>,
+        crate::models::ApiResponse<crate::models::ReadinessResponse>,
         crate::models::ApiResponse<crate::models::ServerTimeResponse>,
         crate::models::ApiResponse<crate::models::GetPetAddressResponse>,
         crate::models::ApiResponse<crate::models::PetGeneratorStatusResponse>,
+        crate::models::ApiResponse<crate::models::QueueDiagnosticsResponse>,
+        crate::models::ApiResponse<crate::models::DispensedListResponse>,
+        crate::models::ApiResponse<crate::models::AuditListResponse>,
+        crate::models::ApiResponse<crate::models::AuditVerifyResponse>,
+        crate::models::ApiResponse<crate::models::UsageResponse>,
+        crate::models::ApiResponse<crate::models::ImportResponse>,
+        crate::models::ApiResponse<crate::models::KeyCheckResponse>,
+        crate::models::ApiResponse<crate::models::WorkerSubmitResponse>,
+        crate::models::ApiResponse<crate::models::WorkerInstructions>,
+        crate::models::ApiResponse<crate::models::WorkerListResponse>,
+        crate::models::ApiResponse<crate::models::ReplicaStoreResponse>,
+        crate::models::ApiResponse<Vec<crate::models::ReplicaAddress>>,
+        crate::models::ApiResponse<crate::models::ReplicaPoolStatus>,
+        crate::models::ApiResponse<crate::models::DifficultyEstimateResponse>,
+        crate::models::ApiResponse<crate::models::ValidateAddressResponse>,
+        crate::models::ApiResponse<crate::models::GeneratorControlResponse>,
+        crate::models::ApiResponse<crate::models::GeneratorStatsResponse>,
+        crate::models::ApiResponse<crate::models::SuffixStatsResponse>,
+        crate::models::ApiResponse<crate::models::RuntimeConfigResponse>,
+        crate::models::ApiResponse<crate::models::CreateJobResponse>,
+        crate::models::ApiResponse<crate::models::BatchPopResponse>,
+        crate::models::ApiResponse<crate::models::AcknowledgeResponse>,
+        crate::models::ApiResponse<crate::models::PeekResponse>,
+        crate::models::ApiResponse<crate::models::JobResponse>,
+        crate::models::ApiResponse<crate::models::SubscriptionResponse>,
         crate::models::ApiResponse<serde_json::Value>,
         crate::models::HealthResponse,
+        crate::models::ReadinessResponse,
         crate::models::ServerTimeResponse,
         crate::models::GetPetAddressResponse,
+        crate::models::RevealResponse,
+        crate::models::ProofRequest,
+        crate::models::ProofResponse,
+        crate::models::RegisterRecipientRequest,
+        crate::models::RecipientKeyResponse,
         crate::models::PetGeneratorStatusResponse,
+        crate::models::QueueDiagnosticsResponse,
+        crate::models::DispensedQuery,
+        crate::models::DispensedRecordResponse,
+        crate::models::DispensedListResponse,
+        crate::models::AuditEntryResponse,
+        crate::models::AuditListResponse,
+        crate::models::AuditVerifyResponse,
+        crate::models::UsageQuery,
+        crate::models::AddressStreamQuery,
+        crate::models::AddressStreamMessage,
+        crate::models::DailyUsageResponse,
+        crate::models::TenantUsageResponse,
+        crate::models::UsageResponse,
+        crate::models::ImportResponse,
+        crate::models::KeyCheckResponse,
+        crate::models::WorkerSubmitRequest,
+        crate::models::SubmittedKeypair,
+        crate::models::WorkerSubmitResponse,
+        crate::models::RejectedKeypair,
+        crate::models::WorkerRegisterRequest,
+        crate::models::WorkerHeartbeatRequest,
+        crate::models::WorkerInstructions,
+        crate::models::WorkerAssignmentResponse,
+        crate::models::ThrottleHint,
+        crate::models::WorkerListResponse,
+        crate::models::WorkerStatusResponse,
+        crate::models::ReplicaStoreRequest,
+        crate::models::ReplicaStoreResponse,
+        crate::models::ReplicaPopRequest,
+        crate::models::ReplicaAddress,
+        crate::models::ReplicaPoolStatus,
+        crate::models::DifficultyEstimateResponse,
+        crate::models::ValidateAddressRequest,
+        crate::models::ValidateAddressResponse,
+        crate::models::DifficultyEstimateQuery,
+        crate::models::GeneratorControlResponse,
+        crate::models::GeneratorStatsResponse,
+        crate::models::ThreadStatsResponse,
+        crate::models::TimeToFindResponse,
+        crate::models::TimeToFindBucket,
+        crate::models::QueueAgeResponse,
+        crate::models::SuffixStatsResponse,
+        crate::models::PoolSuffixStats,
+        crate::models::LetterCount,
+        crate::models::ThrottleRequest,
+        crate::models::RateLimitSettings,
+        crate::models::RateLimitPatch,
+        crate::models::RuntimeConfigPatch,
+        crate::models::RuntimeConfigResponse,
+        crate::models::CreateJobRequest,
+        crate::models::CreateJobResponse,
+        crate::models::JobAddressResponse,
+        crate::models::JobResponse,
+        crate::models::CreateSubscriptionRequest,
+        crate::models::SubscriptionResponse,
+        crate::models::PetAddressQuery,
+        crate::models::LetterQuery,
+        crate::models::BatchPopRequest,
+        crate::models::BatchPopResponse,
+        crate::models::AcknowledgeResponse,
+        crate::models::PeekQuery,
+        crate::models::PeekResponse,
         crate::models::TimeQuery,
+        crate::error::ProblemDetails,
+        crate::error::ErrorCode,
     )),
     tags(
         (name = "Time Service", description = "APIs for getting server time"),
         (name = "Health Check", description = "Service health status check"),
-        (name = "Pet Address", description = "APIs for Pet address generation and management")
+        (name = "Pet Address", description = "APIs for Pet address generation and management"),
+        (name = "Jobs", description = "Ad-hoc custom-pattern grinding jobs"),
+        (name = "Tenants", description = "Per-tenant address pools"),
+        (name = "Events", description = "Live generator activity streams"),
+        (name = "Webhooks", description = "Push delivery of addresses to registered callbacks"),
+        (name = "Admin", description = "Operator diagnostics and maintenance APIs"),
+        (name = "Workers", description = "Registration, heartbeats and keypair submission for remote grind workers"),
+        (name = "Replicas", description = "Pools of a primary server, used by replicas with the primary storage backend")
     ),
     info(
         title = "PetAddr Server API",
@@ -53,106 +236,1322 @@ use crate::pet::{PetGenerator, PetStorage};
 )]
 pub struct ApiDoc;
 
-pub async fn create_app(config: AppConfig) -> anyhow::Result<(Router, Arc<PetGenerator>)> {
+/// The generated OpenAPI document, titled and versioned from `[swagger]`
+pub fn openapi_spec(config: &SwaggerConfig) -> utoipa::openapi::OpenApi {
+    let mut spec = ApiDoc::openapi();
+    spec.info.title = config.title.clone();
+    spec.info.description = Some(config.description.clone());
+    spec.info.version = config.version.clone();
+    spec
+}
+
+pub async fn create_app(mut config: AppConfig) -> anyhow::Result<(Router, Arc<PetAppState>)> {
     // Initialize Pet storage
-    let storage = Arc::new(PetStorage::new(&config.pet_generator.db_path)?);
+    let mut storage = open_storage(&config)?;
 
-    // Start background counter persistence (non-blocking)
-    storage.start_counter_persistence();
+    // Hash-chained audit log; every pool opened from here on records into it
+    let audit = if config.audit.enabled {
+        let audit = match storage.sled() {
+            Some(sled) => sled.audit_log()?,
+            None => {
+                tracing::warn!("The audit log is kept in memory with the {} backend and lost on restart", storage.backend_name());
+                AuditLog::new(None)?
+            }
+        };
+        storage = Arc::new(AuditedStorage::new(storage, DEFAULT_POOL, audit.clone()));
+        tracing::info!("📜 Stores, pops, acks and admin requests are written to the audit log");
+        Some(audit)
+    } else {
+        None
+    };
 
-    // Initialize Pet generator
-    let generator = Arc::new(PetGenerator::new(
+    // Settings changed through the admin API win over the file and environment
+    let admin_key = config.secrets.get(ADMIN_KEY_ENV)?;
+    let overrides = match storage.sled() {
+        Some(sled) => sled.override_store(),
+        None => {
+            if admin_key.is_some() {
+                tracing::warn!("Runtime config changes are kept in memory with the {} backend and lost on restart", storage.backend_name());
+            }
+            OverrideStore::new(None)
+        }
+    };
+    let persisted = overrides.load()?;
+    if !persisted.is_empty() {
+        tracing::info!("Applying runtime overrides of {}", persisted.overridden().join(", "));
+        persisted.apply(&mut config);
+        if let Some(capacity) = storage.capacity().filter(|capacity| *capacity < config.pet_generator.pool_size) {
+            tracing::warn!("Overridden pool_size {} exceeds max_queue_size, using {}", config.pet_generator.pool_size, capacity);
+            config.pet_generator.pool_size = capacity;
+        }
+    }
+
+    // Initialize Pet generator (opens one pool per configured pattern and tenant)
+    let mut generator = PetGenerator::new(
         Arc::clone(&storage),
         config.pet_generator.clone(),
-    ));
+    ).await?;
+    let shards = match config.sharding.enabled {
+        true => {
+            let router = ShardRouter::new(&config.sharding)?;
+            generator.set_shard(router.letters().clone());
+            tracing::info!(
+                "🧩 Sharded by suffix letter: this node serves [{}], {} peers serve the rest",
+                router.letters(),
+                router.peers().len()
+            );
+            Some(Arc::new(router))
+        }
+        false => None,
+    };
+    for tenant in &config.tenants {
+        generator.add_tenant(&tenant.name).await?;
+    }
+    let generator = Arc::new(generator);
+
+    // Refuse to serve from a corrupted database
+    if config.startup.verify {
+        let pools = generator
+            .pools()
+            .iter()
+            .filter_map(|pool| Some((pool.name.clone(), pool.storage.sled()?.clone(), Some((*pool.matcher).clone()))))
+            .collect();
+        verify_pools(pools, config.startup.force).await?;
+    }
+
+    // Load a snapshot before anything is served or generated
+    if let Some(name) = &config.backup.restore_from {
+        let store = SnapshotStore::from_config(&config.backup, &config.secrets)?;
+        let pools = generator.pools();
+        let name = name.clone();
+        let (key, report) = tokio::task::spawn_blocking(move || store.restore(&name, &pools)).await??;
+        tracing::info!(
+            "Restored snapshot '{}': {} addresses, {} already queued, {} skipped",
+            key,
+            report.restored,
+            report.duplicates,
+            report.skipped
+        );
+    }
+
+    // Reconciliation, lease and expiry sweeps per sled pool
+    let maintenance = PoolMaintenance {
+        reconciliation: config.reconciliation.clone(),
+        leases: config.leases.clone(),
+        expiry: config.expiry.clone(),
+    };
+    for pool in generator.pools() {
+        if let Some(storage) = pool.storage.sled() {
+            maintenance.start(storage);
+        }
+    }
+
+    // Page on-call before a pool runs dry
+    if config.alerts.enabled {
+        let pools = generator
+            .pools()
+            .iter()
+            .map(|pool| (pool.name.clone(), Arc::clone(&pool.storage)))
+            .collect();
+        start_watermark_watcher(pools, &config.alerts)?;
+    }
+
+    // Ship snapshots off-host
+    if config.backup.enabled {
+        let store = SnapshotStore::from_config(&config.backup, &config.secrets)?;
+        start_snapshots(store, generator.pools(), config.backup.interval_seconds);
+    }
+
+    // Per-tenant usage accounting, kept in sled next to the pools
+    let quotas: HashMap<String, Quota> = config
+        .tenants
+        .iter()
+        .map(|tenant| {
+            let quota = Quota {
+                daily: tenant.daily_quota,
+                monthly: tenant.monthly_quota,
+            };
+            (tenant.name.clone(), quota)
+        })
+        .collect();
+    let usage = match storage.sled() {
+        Some(sled) => sled.usage_store(quotas),
+        None => {
+            if quotas.values().any(|quota| *quota != Quota::default()) {
+                tracing::warn!("Tenant quotas are sled-only and are not enforced with the {} backend", storage.backend_name());
+            }
+            UsageStore::new(None, quotas)
+        }
+    };
+
+    // Redacted pops: private keys wait in the vault until revealed
+    let redaction = if config.redaction.enabled {
+        let reveal_key = config
+            .secrets
+            .get(REVEAL_KEY_ENV)?
+            .with_context(|| format!("Redaction is enabled but {} is not set", REVEAL_KEY_ENV))?;
+        let vault = match storage.sled() {
+            Some(sled) => sled.key_vault(),
+            None => {
+                tracing::warn!("Held private keys are kept in memory with the {} backend and lost on restart", storage.backend_name());
+                KeyVault::new(None, None)
+            }
+        };
+        tracing::info!("🙈 Pops are redacted; private keys are revealed separately");
+        if config.retention.enabled {
+            vault.start_retention_sweeper(
+                std::time::Duration::from_secs(config.retention.destroy_after_hours * 3600),
+                std::time::Duration::from_secs(config.retention.sweep_interval_seconds),
+            );
+            tracing::info!("🔥 Held private keys are destroyed {} hours after dispensing", config.retention.destroy_after_hours);
+        }
+        Some(Redaction { vault, reveal_key })
+    } else {
+        if config.retention.enabled {
+            tracing::warn!("Key retention is enabled but redaction is off; no private keys are held after dispensing");
+        }
+        None
+    };
+
+    // Keys tenants registered to receive their private keys encrypted
+    let recipients = match storage.sled() {
+        Some(sled) => sled.recipient_registry(),
+        None => RecipientRegistry::new(None),
+    };
+
+    // Push delivery to consumers' callbacks, resumed after a restart
+    let webhooks = if config.webhooks.enabled {
+        let store = match storage.sled() {
+            Some(sled) => sled.subscription_store(),
+            None => {
+                tracing::warn!("Webhook subscriptions are kept in memory with the {} backend and lost on restart", storage.backend_name());
+                SubscriptionStore::new(None)
+            }
+        };
+        let webhooks = WebhookDispatcher::new(
+            store,
+            Arc::clone(&generator),
+            usage.clone(),
+            redaction.as_ref().map(|redaction| redaction.vault.clone()),
+            recipients.clone(),
+            config.webhooks.clone(),
+        );
+        let resumed = webhooks.resume()?;
+        if resumed > 0 {
+            tracing::info!("Resumed {} webhook subscriptions", resumed);
+        }
+        Some(webhooks)
+    } else {
+        None
+    };
+
+    // (key, tenant) pairs, to name the caller in rate limits and access logs
+    let api_keys: Arc<Vec<(String, String)>> = Arc::new(
+        config
+            .tenants
+            .iter()
+            .filter_map(|tenant| Some((tenant.api_key.clone()?, tenant.name.clone())))
+            .collect(),
+    );
+
+    // Bearer JWTs from an identity provider, accepted next to the static keys
+    let jwt = if config.jwt.enabled {
+        let secret = match config.jwt.algorithm {
+            JwtAlgorithm::Hs256 => Some(
+                config
+                    .secrets
+                    .get(JWT_SECRET_ENV)?
+                    .with_context(|| format!("HS256 JWTs are enabled but {} is not set", JWT_SECRET_ENV))?,
+            ),
+            JwtAlgorithm::Rs256 => None,
+        };
+        let validator = JwtValidator::new(&config.jwt, secret.as_deref())?;
+        tracing::info!("🎫 Accepting {} bearer JWTs on the address and admin routes", config.jwt.algorithm.as_str());
+        Some(Arc::new(validator))
+    } else {
+        None
+    };
 
     // Create Pet app state
     let pet_state = Arc::new(PetAppState {
         generator: Arc::clone(&generator),
         storage,
+        idempotency: IdempotencyCache::new(
+            config.idempotency.ttl_seconds,
+            config.idempotency.max_entries,
+        ),
+        jobs: JobManager::new(generator.grinder().clone(), config.jobs.clone()),
+        tenant_keys: config
+            .tenants
+            .iter()
+            .filter_map(|tenant| Some((tenant.name.clone(), tenant.api_key.clone()?)))
+            .collect(),
+        usage,
+        redaction,
+        recipients,
+        probes: config.probes.clone(),
+        metrics: match config.metrics.enabled {
+            true => Some(pet::metrics::install_recorder()?),
+            false => None,
+        },
+        rate_limiter: RateLimiter::new(&config.rate_limit, Arc::clone(&api_keys)),
+        overrides,
+        maintenance,
+        admin_key,
+        webhooks,
+        config_sources: config.sources.clone(),
+        workers: WorkerRegistry::new(config.workers.clone()),
+        shards,
+        audit,
+        jwt,
     });
     
-    // Create rate limiter (currently unused, reserved for future use)
-    let _rate_limiter = RateLimiter::new(
-        config.rate_limit.max_requests_per_minute,
-        config.rate_limit.window_seconds,
-    );
-    
-    let (base_routes, pet_routes, pet_status_routes) = create_routes(&config);
+    let (base_routes, mut pet_routes, mut pet_status_routes) = create_routes(&config);
+
+    // Per-client token buckets, separate for routes that drain the pool and read-only ones.
+    // Always installed so limits can be switched on at runtime.
+    let limiter = pet_state.rate_limiter.clone();
+    pet_routes = pet_routes.route_layer(from_fn_with_state((limiter.clone(), RouteClass::Pop), rate_limit_middleware));
+    pet_status_routes = pet_status_routes.route_layer(from_fn_with_state((limiter, RouteClass::Read), rate_limit_middleware));
+    let mut websockets = websocket_routes();
+
+    // With JWTs on, the address routes need a token with their scope or a tenant API key
+    if let Some(jwt) = &pet_state.jwt {
+        let guard = |scope: &str| JwtGuard::new(jwt, scope, StaticCredential::TenantKeys(Arc::clone(&api_keys)));
+        pet_routes = pet_routes.route_layer(from_fn_with_state(guard(&config.jwt.pop_scope), jwt_auth_middleware));
+        websockets = websockets.route_layer(from_fn_with_state(guard(&config.jwt.pop_scope), jwt_auth_middleware));
+        pet_status_routes = pet_status_routes.route_layer(from_fn_with_state(guard(&config.jwt.read_scope), jwt_auth_middleware));
+    }
+
+    let mut admin = admin_routes();
+    // Inside the auth layer, so only authenticated requests are recorded as admin actions
+    if let Some(audit) = &pet_state.audit {
+        admin = admin.route_layer(from_fn_with_state(audit.clone(), audit_admin_middleware));
+    }
+    match (&pet_state.jwt, &pet_state.admin_key) {
+        (Some(jwt), admin_key) => {
+            let fallback = match admin_key {
+                Some(admin_key) => StaticCredential::Bearer(Arc::from(admin_key.as_str())),
+                None => StaticCredential::None,
+            };
+            let guard = JwtGuard::new(jwt, &config.jwt.admin_scope, fallback);
+            admin = admin.route_layer(from_fn_with_state(guard, jwt_auth_middleware));
+        }
+        (None, Some(admin_key)) => {
+            admin = admin.route_layer(from_fn_with_state(Arc::<str>::from(admin_key.as_str()), admin_auth_middleware));
+        }
+        (None, None) => {
+            admin = admin.route_layer(from_fn(admin_unconfigured_middleware));
+            tracing::warn!("Neither {} nor [jwt] is set: admin routes and /usage are refused with 403", ADMIN_KEY_ENV);
+        }
+    }
+
+    // Outermost on each group, so a refused network never reaches auth or the rate limiter
+    if config.ip_filter.enabled {
+        let filter = |allow: &[String], deny: &[String]| {
+            IpFilter::new(allow, deny).map(Arc::new).map_err(|problems| anyhow::anyhow!("Invalid [ip_filter] entries: {}", problems.join("; ")))
+        };
+        let admin_filter = filter(&config.ip_filter.admin_allow, &config.ip_filter.admin_deny)?;
+        let consumer_filter = filter(&config.ip_filter.consumer_allow, &config.ip_filter.consumer_deny)?;
+        admin = admin.route_layer(from_fn_with_state(admin_filter, ip_filter_middleware));
+        pet_routes = pet_routes.route_layer(from_fn_with_state(Arc::clone(&consumer_filter), ip_filter_middleware));
+        pet_status_routes = pet_status_routes.route_layer(from_fn_with_state(Arc::clone(&consumer_filter), ip_filter_middleware));
+        websockets = websockets.route_layer(from_fn_with_state(consumer_filter, ip_filter_middleware));
+        tracing::info!("🧱 IP filter on admin and consumer routes");
+    }
     
     let mut app = Router::new()
         .merge(base_routes)
+        .merge(admin.with_state(Arc::clone(&pet_state)))
+        .merge(probe_routes().with_state(Arc::clone(&pet_state)))
+        .merge(metrics_routes().with_state(Arc::clone(&pet_state)))
+        .merge(websockets.with_state(Arc::clone(&pet_state)))
         .merge(pet_status_routes.with_state(Arc::clone(&pet_state)))
-        .merge(pet_routes.with_state(pet_state));
+        .merge(pet_routes.with_state(Arc::clone(&pet_state)));
 
-    // Add Swagger UI if enabled
+    // Keypairs from remote grind workers, only with their own token
+    if config.workers.enabled {
+        let worker_key = config
+            .secrets
+            .get(WORKER_KEY_ENV)?
+            .with_context(|| format!("Worker submissions are enabled but {} is not set", WORKER_KEY_ENV))?;
+        let mut workers = worker_routes().route_layer(from_fn_with_state(Arc::<str>::from(worker_key.as_str()), worker_auth_middleware));
+        if config.tls.requires_client_cert(ClientCertRoutes::Workers) {
+            workers = workers.route_layer(from_fn(client_cert_middleware));
+        }
+        app = app.merge(workers.with_state(Arc::clone(&pet_state)));
+        tracing::info!("👷 Accepting remote workers at /workers/register, /workers/{{id}}/heartbeat and /workers/submit");
+    }
+
+    // Pools of this server, for replicas using the primary storage backend
+    if config.storage.serve_replicas {
+        let replica_key = config
+            .secrets
+            .get(REPLICA_KEY_ENV)?
+            .with_context(|| format!("Serving replicas is enabled but {} is not set", REPLICA_KEY_ENV))?;
+        let mut replicas = replica_routes().route_layer(from_fn_with_state(Arc::<str>::from(replica_key.as_str()), replica_auth_middleware));
+        if config.tls.requires_client_cert(ClientCertRoutes::Replicas) {
+            replicas = replicas.route_layer(from_fn(client_cert_middleware));
+        }
+        app = app.merge(replicas.with_state(Arc::clone(&pet_state)));
+        tracing::info!("🔗 Serving pools to replicas at /replicas/pools");
+    }
+
+    // The spec is always served, for SDK generators; the UI only if enabled
+    let spec = openapi_spec(&config.swagger).to_json()?;
+    app = app.merge(openapi_routes(spec));
     if config.swagger.enabled {
-        let swagger_path = config.swagger.path.clone();
-        let swagger_url = format!("{}/openapi.json", swagger_path.trim_end_matches('/'));
         app = app.merge(
-            SwaggerUi::new(swagger_path)
-                .url(swagger_url, ApiDoc::openapi())
+            SwaggerUi::new(config.swagger.path.clone())
+                .config(SwaggerUiConfig::from(OPENAPI_PATH))
         );
     }
 
+    // Name the caller of every request in the audit entries it causes
+    if pet_state.audit.is_some() {
+        app = app.layer(from_fn_with_state(Arc::clone(&api_keys), audit_actor_middleware));
+    }
+
+    // Request counts and latency histograms, labelled by route
+    if config.metrics.enabled {
+        app = app.layer(from_fn(http_metrics_middleware));
+    }
+
+    // Every error as application/problem+json, with the request ID
+    app = app.layer(from_fn(problem_details_middleware));
+
+    // MessagePack / CBOR for clients that send a matching Accept header
+    app = app.layer(from_fn(content_negotiation_middleware));
+
+    // Signed after encoding, so the signature covers the bytes on the wire
+    if config.signing.enabled {
+        let secret = config
+            .secrets
+            .get(SIGNING_KEY_ENV)?
+            .with_context(|| format!("Response signing is enabled but {} is not set", SIGNING_KEY_ENV))?;
+        app = app.layer(from_fn_with_state(ResponseSigner::new(secret.as_bytes())?, response_signing_middleware));
+        tracing::info!("✍️ API responses are signed (X-Signature)");
+    }
+
+    // Outside signing, so X-Signature covers the decoded body
+    if config.compression.enabled {
+        app = app.layer(from_fn_with_state(config.compression.clone(), compression_middleware));
+    }
+
     // Add middleware layers
     app = app.layer(
         ServiceBuilder::new()
+            .layer(request_id_layer())
+            .layer(propagate_request_id_layer())
+            .layer(sensitive_headers_layer())
             .layer(logging_layer())
-            .layer(cors_layer())
+            .layer(from_fn_with_state(api_keys, access_log_middleware))
+            .layer(cors_layer(&config.cors)?)
     );
 
-    Ok((app, generator))
+    Ok((app, pet_state))
 }
 
 pub async fn run_server(config: AppConfig) -> anyhow::Result<()> {
-    // Initialize logging
-    init_logging(&config.logging.level);
+    // Initialize logging, and span export when OTEL_* variables ask for it
+    let exporter = telemetry::OtlpLayer::from_env().context("Invalid OpenTelemetry settings")?;
+    let traces_url = exporter.as_ref().map(|exporter| exporter.traces_url().to_string());
+    init_tracing(&config.logging, std::io::stdout, exporter);
+    if let Some(traces_url) = traces_url {
+        tracing::info!("📡 Exporting traces to {}", traces_url);
+    }
 
-    // Create database directory if it doesn't exist
-    if let Some(parent) = std::path::Path::new(&config.pet_generator.db_path).parent() {
+    // Report every bad setting now rather than failing on the first one later
+    config.validate()?;
+    if let Some(profile) = config.profile {
+        tracing::info!("Using the {} profile", profile.as_str());
+    }
+    if config.vault.enabled {
+        tracing::info!("🔑 Read {} secrets from Vault at {}", config.secrets.vault_len(), config.vault.address);
+    }
+
+    // Create database directory if it doesn't exist (the memory backend needs no disk)
+    let db_path = match config.storage.backend {
+        StorageBackend::Sled | StorageBackend::Redis | StorageBackend::Postgres => Some(&config.pet_generator.db_path),
+        StorageBackend::Redb => Some(&config.storage.redb_path),
+        StorageBackend::Memory | StorageBackend::Primary => None,
+    };
+    if let Some(parent) = db_path.and_then(|path| std::path::Path::new(path).parent()) {
         std::fs::create_dir_all(parent)?;
     }
 
-    let (app, generator) = create_app(config.clone()).await?;
+    // A full disk fails here rather than as a mysterious error while opening the database
+    if let Some(db_path) = db_path.filter(|_| config.preflight.enabled) {
+        let mut preflight = Preflight::default();
+        preflight.check_disk(std::path::Path::new(db_path), config.preflight.min_free_disk_mb * 1024 * 1024);
+        preflight.finish()?;
+    }
+
+    let (app, pet_state) = create_app(config.clone()).await?;
+    let generator = Arc::clone(&pet_state.generator);
+
+    // The rest of the preflight needs the opened database and pools, but
+    // still runs before anything is generated or served
+    if config.preflight.enabled {
+        let pools = generator.pools();
+        let newest_record = pools
+            .iter()
+            .filter_map(|pool| pool.storage.created_range().ok().flatten())
+            .map(|(_, newest)| newest)
+            .max();
+        let mut preflight = Preflight::default();
+        preflight.check_clock(chrono::Utc::now(), newest_record);
+        if let Some(storage) = pet_state.storage.sled() {
+            preflight.check_database(storage).await;
+        }
+        preflight.check_patterns(&pools);
+        preflight.finish()?;
+    }
     
     // Start Pet address generator
     generator.start().await?;
     
     let addr = config.server_address();
     
-    tracing::info!("🚀 Server started successfully!");
-    tracing::info!("📡 Listening on: http://{}", addr);
+    let scheme = config.scheme();
     
-    if config.swagger.enabled {
-        tracing::info!("📊 API Documentation: http://{}{}", addr, config.swagger.path);
+    tracing::info!("🚀 Server started successfully!");
+    if config.server.tcp {
+        tracing::info!("📡 Listening on: {}://{}", scheme, addr);
+        
+        if config.swagger.enabled {
+            tracing::info!("📊 API Documentation: {}://{}{}", scheme, addr, config.swagger.path);
+        }
+        
+        tracing::info!("⏰ Time API: {}://{}{}/time", scheme, addr, config.api_base_url());
+        tracing::info!("🐕 Pet Address API: {}://{}{}/pet/address", scheme, addr, config.api_base_url());
+        tracing::info!("📊 Pet Status API: {}://{}{}/pet/status", scheme, addr, config.api_base_url());
+        tracing::info!("❤️  Health Check: {}://{}/health", scheme, addr);
+    }
+    if let Some(path) = &config.server.unix_socket {
+        tracing::info!("🧦 Unix socket: {} (mode {:o})", path, config.server.unix_socket_mode);
     }
-    
-    tracing::info!("⏰ Time API: http://{}{}/time", addr, config.api_base_url());
-    tracing::info!("🐕 Pet Address API: http://{}{}/pet/address", addr, config.api_base_url());
-    tracing::info!("📊 Pet Status API: http://{}{}/pet/status", addr, config.api_base_url());
-    tracing::info!("❤️  Health Check: http://{}/health", addr);
 
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await?;
-    
+    #[cfg(unix)]
+    reload_on_hangup(Arc::clone(&pet_state))?;
+
+    let stop_grpc = start_grpc(&config, pet_state)?;
+
+    // One stop handle per listener: `notify_one` wakes exactly one waiter
+    let stop_accepting = Arc::new(tokio::sync::Notify::new());
+    let stop_unix = Arc::new(tokio::sync::Notify::new());
+    let mut servers: Vec<Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>>> = Vec::new();
+    if let Some(path) = &config.server.unix_socket {
+        servers.push(Box::pin(serve_unix(path, config.server.unix_socket_mode, app.clone(), Arc::clone(&stop_unix))?));
+    }
+    if config.server.tcp && config.tls.enabled {
+        servers.push(Box::pin(serve_tls(&config, &addr, app, Arc::clone(&stop_accepting)).await?));
+    } else if config.server.tcp {
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        servers.push(Box::pin(
+            axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+                .with_graceful_shutdown({
+                    let stop_accepting = Arc::clone(&stop_accepting);
+                    async move { stop_accepting.notified().await }
+                })
+                .into_future(),
+        ));
+    }
+    let server = async move { futures_util::future::try_join_all(servers).await.map(|_| ()) };
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => result?,
+        _ = shutdown_signal() => {
+            tracing::info!("Shutdown signal received, draining connections");
+            stop_accepting.notify_one();
+            stop_unix.notify_one();
+            stop_grpc.notify_one();
+            let drain = std::time::Duration::from_secs(config.server.shutdown_timeout_seconds);
+            match tokio::time::timeout(drain, &mut server).await {
+                Ok(result) => result?,
+                Err(_) => tracing::warn!("Connections still open after {:?}, closing them", drain),
+            }
+        }
+    }
+
+    shutdown(&generator).await;
     Ok(())
 }
 
-fn init_logging(level: &str) {
-    let log_level = match level.to_lowercase().as_str() {
-        "trace" => tracing::Level::TRACE,
-        "debug" => tracing::Level::DEBUG,
-        "info" => tracing::Level::INFO,
-        "warn" => tracing::Level::WARN,
-        "error" => tracing::Level::ERROR,
-        _ => tracing::Level::INFO,
+/// Serve the gRPC API on `[grpc].port` until the returned handle is notified
+#[cfg(feature = "grpc")]
+fn start_grpc(config: &AppConfig, pet_state: Arc<PetAppState>) -> anyhow::Result<Arc<tokio::sync::Notify>> {
+    let stop = Arc::new(tokio::sync::Notify::new());
+    if !config.grpc.enabled {
+        return Ok(stop);
+    }
+
+    let addr: std::net::SocketAddr = config.grpc_address().parse()?;
+    let service = grpc::PetAddressService::new(pet_state).into_server();
+    let shutdown = {
+        let stop = Arc::clone(&stop);
+        async move { stop.notified().await }
     };
+    tokio::spawn(async move {
+        let server = tonic::transport::Server::builder().add_service(service);
+        if let Err(e) = server.serve_with_shutdown(addr, shutdown).await {
+            tracing::error!("gRPC server failed: {}", e);
+        }
+    });
+    tracing::info!("🔌 gRPC API: {}", addr);
+    Ok(stop)
+}
+
+#[cfg(not(feature = "grpc"))]
+fn start_grpc(config: &AppConfig, _pet_state: Arc<PetAppState>) -> anyhow::Result<Arc<tokio::sync::Notify>> {
+    if config.grpc.enabled {
+        anyhow::bail!("The gRPC API requires building with `--features grpc`");
+    }
+    Ok(Arc::new(tokio::sync::Notify::new()))
+}
+
+#[cfg(feature = "tls")]
+async fn serve_tls(
+    config: &AppConfig,
+    addr: &str,
+    app: Router,
+    stop_accepting: Arc<tokio::sync::Notify>,
+) -> anyhow::Result<impl Future<Output = std::io::Result<()>> + Send> {
+    tls::serve(&config.tls, addr, app, stop_accepting).await
+}
+
+#[cfg(not(feature = "tls"))]
+async fn serve_tls(
+    _config: &AppConfig,
+    _addr: &str,
+    _app: Router,
+    _stop_accepting: Arc<tokio::sync::Notify>,
+) -> anyhow::Result<std::future::Ready<std::io::Result<()>>> {
+    anyhow::bail!("TLS requires building with `--features tls`")
+}
+
+#[cfg(unix)]
+fn serve_unix(
+    path: &str,
+    mode: u32,
+    app: Router,
+    stop_accepting: Arc<tokio::sync::Notify>,
+) -> anyhow::Result<impl Future<Output = std::io::Result<()>> + Send> {
+    unix_socket::serve(path, mode, app, stop_accepting)
+}
+
+#[cfg(not(unix))]
+fn serve_unix(
+    _path: &str,
+    _mode: u32,
+    _app: Router,
+    _stop_accepting: Arc<tokio::sync::Notify>,
+) -> anyhow::Result<std::future::Ready<std::io::Result<()>>> {
+    anyhow::bail!("[server].unix_socket is only supported on Unix")
+}
+
+/// Reload the configuration on every SIGHUP, like `POST /admin/reload`
+#[cfg(unix)]
+fn reload_on_hangup(pet_state: Arc<PetAppState>) -> anyhow::Result<()> {
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            if let Err(e) = handlers::reload_config(&pet_state, "SIGHUP").await {
+                tracing::warn!("Configuration reload on SIGHUP failed, keeping the current settings: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Resolves on SIGINT (Ctrl+C) or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Stop generating and make every stored address (and the ID counters) durable
+async fn shutdown(generator: &PetGenerator) {
+    generator.stop().await;
+    // In-flight searches stop at their next chunk boundary
+    generator.grinder().limits().pause();
+
+    for pool in generator.pools() {
+        if let Err(e) = pool.storage.flush().await {
+            tracing::error!("Failed to flush pool '{}' on shutdown: {}", pool.name, e);
+        }
+    }
+    tokio::task::block_in_place(|| telemetry::flush(std::time::Duration::from_secs(5)));
+    tracing::info!("Shutdown complete");
+}
+
+/// Generate `count` addresses in memory and exit, printing them or, with
+/// `output`, writing them in the dump format `import` reads. Neither the
+/// database nor the server is touched, so this works on an air-gapped machine.
+pub fn run_dry_run(config: AppConfig, count: usize, format: DumpFormat, output: Option<&str>) -> anyhow::Result<()> {
+    init_logging(&config.logging);
+
+    let matcher = crate::pet::AddressMatcher::from_config(&config.pet_generator)?;
+    // Created first so a bad path fails before any grinding
+    let file = output.map(create_key_file).transpose()?;
+
+    let grinder = crate::pet::Grinder::new(config.pet_generator.threads);
 
-    tracing_subscriber::fmt()
-        .with_max_level(log_level)
-        .with_target(false)
+    tracing::info!(
+        "🧪 Dry run: generating {} {} addresses in memory on {} threads",
+        count,
+        matcher.describe(),
+        grinder.threads()
+    );
+
+    let report = crate::pet::dry_run(&grinder, &matcher, count);
+
+    match (file, output) {
+        (Some(file), Some(path)) => {
+            write_keys(file, &report.addresses, format).with_context(|| format!("Failed to write {}", path))?;
+            println!("Wrote {} keys to {}", report.addresses.len(), path);
+        }
+        _ => {
+            for address in &report.addresses {
+                println!("{}", address.address);
+            }
+        }
+    }
+
+    println!(
+        "Generated {} addresses in {:.2}s ({:.3} addresses/sec)",
+        report.addresses.len(),
+        report.elapsed.as_secs_f64(),
+        report.rate()
+    );
+
+    Ok(())
+}
+
+fn write_keys(file: std::fs::File, addresses: &[crate::pet::PetAddress], format: DumpFormat) -> anyhow::Result<()> {
+    let created_at = chrono::Utc::now();
+    let mut writer = std::io::BufWriter::new(file);
+    if let Some(header) = format.header() {
+        writeln!(writer, "{}", header)?;
+    }
+    for address in addresses {
+        let record = DumpRecord {
+            public_key: address.public_key.clone(),
+            private_key: address.private_key.clone(),
+            address: address.address.clone(),
+            created_at,
+        };
+        writer.write_all(record.encode(format)?.as_bytes())?;
+    }
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    Ok(())
+}
+
+/// A new file for private keys, readable by the owner only. An existing
+/// file is never overwritten, so keys from an earlier run cannot be lost.
+fn create_key_file(path: &str) -> anyhow::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path).with_context(|| format!("Failed to create {}", path))
+}
+
+/// Check every sled pool of the database at `pet_generator.db_path`, repair
+/// what can be repaired and print a report (`verify`). Fails on unreadable
+/// records unless `startup.force` is set, in which case they are quarantined.
+pub async fn run_verify(config: AppConfig) -> anyhow::Result<()> {
+    init_logging(&config.logging);
+
+    let storage = open_sled_storage(&config, load_key_ring(&config)?)?;
+    let default_matcher = AddressMatcher::from_config(&config.pet_generator)?;
+    let mut pools = vec![(crate::pet::DEFAULT_POOL.to_string(), storage.clone(), Some(default_matcher.clone()))];
+    for name in storage.pool_names()? {
+        let pool = storage.open_pool(&name).await?;
+        // Tenant pools grind the default pattern, the others are named after their suffix
+        let matcher = match name.starts_with(TENANT_POOL_PREFIX) {
+            true => Some(default_matcher.clone()),
+            false => AddressMatcher::suffix(&name, config.pet_generator.case_sensitive).ok(),
+        };
+        pools.push((name, pool, matcher));
+    }
+
+    for (name, report) in verify_pools(pools, config.startup.force).await? {
+        println!(
+            "{}: {} records, {} orphans removed, {} re-persisted, {} duplicates removed, {} corrupt, {} with mismatched keys ({} quarantined), {} off-pattern",
+            name,
+            report.checked,
+            report.orphans_removed,
+            report.repersisted,
+            report.duplicates_removed,
+            report.corrupt.len(),
+            report.mismatched.len(),
+            report.quarantined,
+            report.off_pattern.len()
+        );
+    }
+    storage.flush().await
+}
+
+/// Run `PetStorage::verify` over `pools` in order, so a key queued in two
+/// pools is kept in the first. Bails on corrupt records or keys that do not
+/// derive their address unless `force`.
+async fn verify_pools(
+    pools: Vec<(String, PetStorage, Option<AddressMatcher>)>,
+    force: bool,
+) -> anyhow::Result<Vec<(String, crate::pet::VerifyReport)>> {
+    let mut seen = std::collections::HashSet::new();
+    let mut reports = Vec::with_capacity(pools.len());
+    for (name, storage, matcher) in pools {
+        let report = storage.verify(&mut seen, matcher.as_ref(), force).await?;
+        if report.unusable() > 0 && !force {
+            anyhow::bail!(
+                "Pool '{}' has {} unreadable records or mismatched keys (first: {}); start with --force to quarantine them (and --restore-snapshot to refill from a backup)",
+                name,
+                report.unusable(),
+                report.corrupt.first().or(report.mismatched.first()).map_or("", String::as_str)
+            );
+        }
+        if !report.off_pattern.is_empty() {
+            tracing::warn!(
+                "Pool '{}' holds {} addresses that do not match its pattern (first: {})",
+                name,
+                report.off_pattern.len(),
+                report.off_pattern[0]
+            );
+        }
+        if report.is_clean() {
+            tracing::info!("✅ Pool '{}' verified: {} records consistent", name, report.checked);
+        } else {
+            tracing::warn!(
+                "Pool '{}' repaired: {} orphans removed, {} re-persisted, {} duplicates removed, {} unusable records quarantined",
+                name,
+                report.orphans_removed,
+                report.repersisted,
+                report.duplicates_removed,
+                report.quarantined
+            );
+        }
+        reports.push((name, report));
+    }
+    Ok(reports)
+}
+
+/// Copy every pool of the sled database at `pet_generator.db_path` into a
+/// new redb database at `storage.redb_path`, keeping IDs, creation times and
+/// the set of already-dispensed keys. The target must not hold any addresses.
+#[cfg(feature = "redb")]
+pub async fn run_redb_migration(config: AppConfig) -> anyhow::Result<()> {
+    init_logging(&config.logging);
+
+    let key_ring = load_key_ring(&config)?;
+    let source = open_sled_storage(&config, key_ring.clone())?;
+    if let Some(parent) = std::path::Path::new(&config.storage.redb_path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut target = crate::pet::RedbStorage::open(&config.storage.redb_path)?;
+    if let Some(key_ring) = key_ring {
+        target = target.with_key_ring(key_ring);
+    }
+    if !target.is_empty()? {
+        anyhow::bail!("{} already holds addresses; migrate into a new file", config.storage.redb_path);
+    }
+
+    let mut pools = vec![(None, source.clone())];
+    for name in source.pool_names()? {
+        let pool = source.open_pool(&name).await?;
+        pools.push((Some(name), pool));
+    }
+
+    let mut total = 0;
+    for (name, pool) in &pools {
+        let records = pool.records();
+        match name {
+            Some(name) => target.named_pool(name).import(&records)?,
+            None => target.import(&records)?,
+        }
+        println!("{}: {} addresses", name.as_deref().unwrap_or(crate::pet::DEFAULT_POOL), records.len());
+        total += records.len();
+    }
+
+    let seen = source.seen_keys()?;
+    target.mark_seen(seen.iter().map(String::as_str))?;
+    println!(
+        "Migrated {} addresses and {} known keys into {}",
+        total,
+        seen.len(),
+        config.storage.redb_path
+    );
+
+    Ok(())
+}
+
+#[cfg(not(feature = "redb"))]
+pub async fn run_redb_migration(_config: AppConfig) -> anyhow::Result<()> {
+    anyhow::bail!("migrate-to-redb requires building with `--features redb`")
+}
+
+/// Grind for `seconds` on the configured number of threads, print keys/sec per
+/// thread and in total, and store the result for the difficulty estimator.
+/// Opens the database, so run it while the server is stopped.
+pub async fn run_benchmark(config: AppConfig, seconds: u64) -> anyhow::Result<()> {
+    init_logging(&config.logging);
+
+    if let Some(parent) = std::path::Path::new(&config.pet_generator.db_path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let storage = open_sled_storage(&config, load_key_ring(&config)?)?;
+    let threads = crate::pet::Grinder::new(config.pet_generator.threads).threads();
+
+    tracing::info!("⏱️  Benchmarking grinder on {} threads for {}s", threads, seconds);
+
+    let report = tokio::task::spawn_blocking(move || {
+        crate::pet::benchmark(threads, std::time::Duration::from_secs(seconds))
+    })
+    .await?;
+
+    for (thread, rate) in report.per_thread_keys_per_second().iter().enumerate() {
+        println!("thread {:>3}: {:.0} keys/sec", thread, rate);
+    }
+    println!(
+        "total: {} keys in {:.2}s ({:.0} keys/sec on {} threads)",
+        report.total_keys(),
+        report.elapsed_seconds,
+        report.keys_per_second(),
+        report.threads
+    );
+
+    storage.store_benchmark(&report).await?;
+
+    Ok(())
+}
+
+/// Write every stored address of `pool` to `output` (stdout if None) in the
+/// format `POST /admin/import` and `run_import` read. Logs go to stderr.
+pub async fn run_export(config: AppConfig, pool: &str, format: DumpFormat, output: Option<&str>) -> anyhow::Result<()> {
+    init_logging_to(&config.logging, std::io::stderr);
+
+    let (storage, _) = open_offline_pool(&config, pool).await?;
+    let records = storage.records();
+    let mut writer: Box<dyn std::io::Write> = match output {
+        Some(path) => Box::new(std::fs::File::create(path).with_context(|| format!("Failed to create {}", path))?),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut writer = std::io::BufWriter::new(writer.as_mut());
+    if let Some(header) = format.header() {
+        writeln!(writer, "{}", header)?;
+    }
+    for record in &records {
+        writer.write_all(DumpRecord::from_info(record).encode(format)?.as_bytes())?;
+    }
+    writer.flush()?;
+
+    tracing::info!("Exported {} addresses from pool '{}'", records.len(), pool);
+    Ok(())
+}
+
+/// Load a dump from `input` (stdin if None) into `pool`. Nothing is imported
+/// if any line is invalid; addresses already enqueued here are skipped.
+pub async fn run_import(config: AppConfig, pool: &str, format: DumpFormat, input: Option<&str>) -> anyhow::Result<()> {
+    init_logging_to(&config.logging, std::io::stderr);
+
+    let body = match input {
+        Some(path) => std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?,
+        None => std::io::read_to_string(std::io::stdin())?,
+    };
+    let (storage, matcher) = open_offline_pool(&config, pool).await?;
+    let records = parse_dump(&body, format, &matcher)
+        .map_err(|errors| anyhow::anyhow!("{} invalid lines: {}", errors.len(), errors.join("; ")))?;
+
+    let (mut imported, mut duplicates) = (0, 0);
+    for record in records {
+        storage.wait_for_writer();
+        match storage.store_address_at(record.to_address(), record.created_at) {
+            Ok(_) => imported += 1,
+            Err(e) if matches!(e.downcast_ref::<StoreError>(), Some(StoreError::Duplicate { .. })) => duplicates += 1,
+            Err(e) => {
+                storage.flush().await?;
+                return Err(e.context(format!("Import stopped after {} addresses", imported)));
+            }
+        }
+    }
+    storage.flush().await?;
+
+    println!("Imported {} addresses into pool '{}' ({} duplicates skipped)", imported, pool, duplicates);
+    Ok(())
+}
+
+/// Write the secret key of the stored address `public_key` to a new file at
+/// `output` (stdout if None), looking in every pool of the sled database
+pub async fn run_keyfile(config: AppConfig, public_key: &str, format: KeyFileFormat, output: Option<&str>) -> anyhow::Result<()> {
+    init_logging_to(&config.logging, std::io::stderr);
+
+    if config.storage.backend != StorageBackend::Sled {
+        anyhow::bail!("keyfile works on the sled database only");
+    }
+    let storage = open_sled_storage(&config, load_key_ring(&config)?)?;
+    let mut pools = vec![(DEFAULT_POOL.to_string(), storage.clone())];
+    for name in storage.pool_names()? {
+        let pool = storage.open_pool(&name).await?;
+        pools.push((name, pool));
+    }
+    let (pool, address_info) = pools
+        .iter()
+        .find_map(|(name, pool)| Some((name, pool.find_by_public_key(public_key)?)))
+        .ok_or_else(|| anyhow::anyhow!("No stored address {}", public_key))?;
+
+    let encoded = format.encode(&address_info.address)?;
+    match output {
+        Some(path) => create_key_file(path)?
+            .write_all(encoded.as_bytes())
+            .with_context(|| format!("Failed to write {}", path))?,
+        None => std::io::stdout().write_all(encoded.as_bytes())?,
+    }
+
+    tracing::info!("Wrote the key of {} from pool '{}'", public_key, pool);
+    Ok(())
+}
+
+/// Sled storage and pattern of `name` for the offline commands: the default
+/// pool, a configured extra suffix or tenant, or a pool already in the database
+async fn open_offline_pool(config: &AppConfig, name: &str) -> anyhow::Result<(PetStorage, AddressMatcher)> {
+    if config.storage.backend != StorageBackend::Sled {
+        anyhow::bail!("export and import work on the sled database only");
+    }
+    let storage = open_sled_storage(config, load_key_ring(config)?)?;
+    if name == DEFAULT_POOL {
+        return Ok((storage, AddressMatcher::from_config(&config.pet_generator)?));
+    }
+
+    let tenant = name.strip_prefix(TENANT_POOL_PREFIX);
+    let configured = match tenant {
+        Some(tenant) => config.tenants.iter().any(|configured| configured.name == tenant),
+        None => config.pet_generator.extra_suffixes.iter().any(|suffix| suffix == name),
+    };
+    if !configured && !storage.pool_names()?.iter().any(|known| known == name) {
+        anyhow::bail!("Unknown pool '{}'", name);
+    }
+    let matcher = match tenant {
+        Some(_) => AddressMatcher::from_config(&config.pet_generator)?,
+        None => AddressMatcher::suffix(name, config.pet_generator.case_sensitive)?,
+    };
+    Ok((storage.open_pool(name).await?, matcher))
+}
+
+/// Open the configured storage backend, with private-key encryption and
+/// leases when enabled
+fn open_storage(config: &AppConfig) -> anyhow::Result<Arc<dyn Storage>> {
+    let key_ring = load_key_ring(config)?;
+
+    let storage = match config.storage.backend {
+        StorageBackend::Sled => {
+            let mut storage = open_sled_storage(config, key_ring)?;
+            let breaker = &config.circuit_breaker;
+            storage = match breaker.enabled {
+                true => storage.with_circuit_breaker(breaker.failure_threshold, breaker.refuse_stores),
+                false => storage.with_circuit_breaker(0, false),
+            };
+            if config.leases.enabled {
+                storage = storage.with_lease_ttl(std::time::Duration::from_secs(config.leases.ttl_seconds));
+            }
+            if let Some(max_queue_size) = config.pet_generator.max_queue_size {
+                if max_queue_size < config.pet_generator.pool_size {
+                    anyhow::bail!(
+                        "max_queue_size ({}) must be at least pool_size ({})",
+                        max_queue_size,
+                        config.pet_generator.pool_size
+                    );
+                }
+                storage = storage.with_capacity(max_queue_size);
+            }
+            if let Some(max_in_memory) = config.pet_generator.max_in_memory {
+                storage = storage.with_memory_limit(max_in_memory);
+                tracing::info!("📦 Keeping at most {} queued addresses per pool in memory", max_in_memory);
+            }
+            Arc::new(storage)
+        }
+        StorageBackend::Redis => open_redis_storage(config, key_ring)?,
+        StorageBackend::Postgres => open_postgres_storage(config, key_ring)?,
+        StorageBackend::Redb => open_redb_storage(config, key_ring)?,
+        StorageBackend::Memory => open_memory_storage(config)?,
+        StorageBackend::Primary => open_primary_storage(config)?,
+    };
+    match telemetry::is_enabled() {
+        true => Ok(Arc::new(crate::pet::TracedStorage::new(storage))),
+        false => Ok(storage),
+    }
+}
+
+fn load_key_ring(config: &AppConfig) -> anyhow::Result<Option<Arc<KeyRing>>> {
+    if !config.encryption.enabled {
+        return Ok(None);
+    }
+
+    let key_ring = KeyRing::from_secrets(&config.secrets, &config.encryption.active_key_id)?;
+    tracing::info!("🔐 Private keys encrypted at rest with key '{}'", key_ring.active_key_id());
+    Ok(Some(Arc::new(key_ring)))
+}
+
+fn open_sled_storage(config: &AppConfig, key_ring: Option<Arc<KeyRing>>) -> anyhow::Result<PetStorage> {
+    match key_ring {
+        Some(key_ring) => PetStorage::new_encrypted(&config.pet_generator.db_path, key_ring),
+        None => PetStorage::new(&config.pet_generator.db_path),
+    }
+}
+
+fn open_memory_storage(config: &AppConfig) -> anyhow::Result<Arc<dyn Storage>> {
+    if config.encryption.enabled {
+        tracing::warn!("Encryption at rest has no effect with the memory storage backend");
+    }
+    if config.leases.enabled {
+        tracing::warn!("Leases are not supported by the memory storage backend; pops are destructive");
+    }
+    if config.expiry.enabled {
+        tracing::warn!("Address expiry is not supported by the memory storage backend");
+    }
+    if config.backup.enabled || config.backup.restore_from.is_some() {
+        tracing::warn!("Snapshots only cover sled pools; the memory backend is not backed up or restored");
+    }
+
+    let mut storage = crate::pet::MemoryStorage::new();
+    if let Some(max_queue_size) = config.pet_generator.max_queue_size {
+        storage = storage.with_capacity(max_queue_size);
+    }
+    tracing::info!("Using in-memory storage; addresses are lost on restart");
+    Ok(Arc::new(storage))
+}
+
+fn open_primary_storage(config: &AppConfig) -> anyhow::Result<Arc<dyn Storage>> {
+    if config.encryption.enabled {
+        tracing::warn!("Encryption at rest has no effect on a replica; configure it on the primary");
+    }
+    if config.leases.enabled {
+        tracing::warn!("Leases are not supported by the primary storage backend; pops are destructive");
+    }
+    if config.expiry.enabled {
+        tracing::warn!("Address expiry is not supported by the primary storage backend");
+    }
+    if config.backup.enabled || config.backup.restore_from.is_some() {
+        tracing::warn!("Snapshots only cover sled pools; back up the primary instead");
+    }
+    if config.pet_generator.max_queue_size.is_some() {
+        tracing::warn!("max_queue_size is enforced by the primary; the replica's setting is ignored");
+    }
+
+    let replica_key = config
+        .secrets
+        .get(REPLICA_KEY_ENV)?
+        .with_context(|| format!("The primary storage backend needs {}", REPLICA_KEY_ENV))?;
+    let storage_config = &config.storage;
+    let identity = (!storage_config.primary_client_cert_path.is_empty())
+        .then_some((storage_config.primary_client_cert_path.as_str(), storage_config.primary_client_key_path.as_str()));
+    let ca_path = Some(storage_config.primary_ca_path.as_str()).filter(|path| !path.is_empty());
+    let tls = match (identity, ca_path) {
+        (None, None) => None,
+        _ => Some(crate::pet::http::ClientTls::load(identity, ca_path)?),
+    };
+    let storage = crate::pet::PrimaryStorage::connect(
+        &storage_config.primary_url,
+        &replica_key,
+        std::time::Duration::from_millis(storage_config.primary_timeout_ms),
+        tls,
+    )?;
+    Ok(Arc::new(storage))
+}
+
+#[cfg(feature = "redb")]
+fn open_redb_storage(config: &AppConfig, key_ring: Option<Arc<KeyRing>>) -> anyhow::Result<Arc<dyn Storage>> {
+    if config.leases.enabled {
+        tracing::warn!("Leases are not supported by the redb storage backend; pops are destructive");
+    }
+    if config.expiry.enabled {
+        tracing::warn!("Address expiry is not supported by the redb storage backend");
+    }
+    if config.backup.enabled || config.backup.restore_from.is_some() {
+        tracing::warn!("Snapshots only cover sled pools; redb data is not backed up or restored");
+    }
+
+    let mut storage = crate::pet::RedbStorage::open(&config.storage.redb_path)?;
+    if let Some(key_ring) = key_ring {
+        storage = storage.with_key_ring(key_ring);
+    }
+    if let Some(max_queue_size) = config.pet_generator.max_queue_size {
+        storage = storage.with_capacity(max_queue_size);
+    }
+    tracing::info!("Opened redb database {}", config.storage.redb_path);
+    Ok(Arc::new(storage))
+}
+
+#[cfg(not(feature = "redb"))]
+fn open_redb_storage(_config: &AppConfig, _key_ring: Option<Arc<KeyRing>>) -> anyhow::Result<Arc<dyn Storage>> {
+    anyhow::bail!("The redb storage backend requires building with `--features redb`")
+}
+
+#[cfg(feature = "redis")]
+fn open_redis_storage(config: &AppConfig, key_ring: Option<Arc<KeyRing>>) -> anyhow::Result<Arc<dyn Storage>> {
+    if config.leases.enabled {
+        tracing::warn!("Leases are not supported by the redis storage backend; pops are destructive");
+    }
+    if config.pet_generator.max_queue_size.is_some() {
+        tracing::warn!("max_queue_size is not enforced by the redis storage backend");
+    }
+    if config.expiry.enabled {
+        tracing::warn!("Address expiry is not supported by the redis storage backend");
+    }
+    if config.backup.enabled || config.backup.restore_from.is_some() {
+        tracing::warn!("Snapshots only cover sled pools; redis data is not backed up or restored");
+    }
+
+    let mut storage = crate::pet::RedisStorage::connect(
+        &config.storage.redis_url,
+        &config.storage.redis_prefix,
+        config.storage.redis_pool_size,
+        std::time::Duration::from_millis(config.storage.redis_timeout_ms),
+    )?;
+    if let Some(key_ring) = key_ring {
+        storage = storage.with_key_ring(key_ring);
+    }
+    Ok(Arc::new(storage))
+}
+
+#[cfg(not(feature = "redis"))]
+fn open_redis_storage(_config: &AppConfig, _key_ring: Option<Arc<KeyRing>>) -> anyhow::Result<Arc<dyn Storage>> {
+    anyhow::bail!("The redis storage backend requires building with `--features redis`")
+}
+
+#[cfg(feature = "postgres")]
+fn open_postgres_storage(config: &AppConfig, key_ring: Option<Arc<KeyRing>>) -> anyhow::Result<Arc<dyn Storage>> {
+    if config.leases.enabled {
+        tracing::warn!("Leases are not supported by the postgres storage backend; pops are destructive");
+    }
+    if config.pet_generator.max_queue_size.is_some() {
+        tracing::warn!("max_queue_size is not enforced by the postgres storage backend");
+    }
+    if config.expiry.enabled {
+        tracing::warn!("Address expiry is not supported by the postgres storage backend");
+    }
+    if config.backup.enabled || config.backup.restore_from.is_some() {
+        tracing::warn!("Snapshots only cover sled pools; back up the PostgreSQL database with pg_dump");
+    }
+
+    let mut storage = crate::pet::PostgresStorage::connect(
+        &config.storage.postgres_url,
+        config.storage.postgres_pool_size,
+        std::time::Duration::from_millis(config.storage.postgres_timeout_ms),
+    )?;
+    if let Some(key_ring) = key_ring {
+        storage = storage.with_key_ring(key_ring);
+    }
+    Ok(Arc::new(storage))
+}
+
+#[cfg(not(feature = "postgres"))]
+fn open_postgres_storage(_config: &AppConfig, _key_ring: Option<Arc<KeyRing>>) -> anyhow::Result<Arc<dyn Storage>> {
+    anyhow::bail!("The postgres storage backend requires building with `--features postgres`")
+}
+
+fn init_logging(logging: &LoggingConfig) {
+    init_logging_to(logging, std::io::stdout);
+}
+
+/// Logging to `writer`; commands that print data to stdout log to stderr
+fn init_logging_to<W>(logging: &LoggingConfig, writer: W)
+where
+    W: for<'writer> tracing_subscriber::fmt::MakeWriter<'writer> + Send + Sync + 'static,
+{
+    init_tracing(logging, writer, None);
+}
+
+/// Logging to `writer`, plus span export when `exporter` is given. Spans under
+/// `telemetry::TRACE_TARGET` are only exported, never logged.
+fn init_tracing<W>(logging: &LoggingConfig, writer: W, exporter: Option<telemetry::OtlpLayer>)
+where
+    W: for<'writer> tracing_subscriber::fmt::MakeWriter<'writer> + Send + Sync + 'static,
+{
+    use tracing_subscriber::filter::{LevelFilter, Targets};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    use tracing_subscriber::Layer;
+
+    let log_level = match logging.level.to_lowercase().as_str() {
+        "trace" => LevelFilter::TRACE,
+        "debug" => LevelFilter::DEBUG,
+        "info" => LevelFilter::INFO,
+        "warn" => LevelFilter::WARN,
+        "error" => LevelFilter::ERROR,
+        _ => LevelFilter::INFO,
+    };
+
+    let fmt = tracing_subscriber::fmt::layer()
         .with_thread_ids(true)
         .with_line_number(true)
+        .with_writer(writer);
+    let fmt = if logging.format.eq_ignore_ascii_case("json") {
+        // Keep targets so access logs can be told apart (`"target":"access_log"`)
+        fmt.json().with_current_span(true).with_span_list(false).boxed()
+    } else {
+        fmt.with_target(false).boxed()
+    };
+    let logged = Targets::new().with_target(telemetry::TRACE_TARGET, LevelFilter::OFF).with_default(log_level);
+
+    tracing_subscriber::registry()
+        .with(fmt.with_filter(logged))
+        // Traced spans are info level, so export keeps them under a quieter log level
+        .with(exporter.map(|exporter| exporter.with_filter(log_level.max(LevelFilter::INFO))))
         .init();
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_spec_is_complete() {
+        let config = SwaggerConfig {
+            enabled: true,
+            path: "/swagger-ui".to_string(),
+            title: "Pet API".to_string(),
+            description: "Addresses".to_string(),
+            version: "2.1.0".to_string(),
+        };
+        let spec = openapi_spec(&config);
+        assert_eq!((spec.info.title.as_str(), spec.info.version.as_str()), ("Pet API", "2.1.0"));
+
+        // Generated SDKs need every referenced schema to be declared
+        let json = spec.to_json().unwrap();
+        let schemas = &spec.components.as_ref().unwrap().schemas;
+        for reference in json.split("\"$ref\":\"#/components/schemas/").skip(1) {
+            let name = &reference[..reference.find('"').unwrap()];
+            assert!(schemas.contains_key(name), "schema {} is referenced but not declared", name);
+        }
+        for path in ["/api/v1/pet/address", "/api/v1/tenants/{tenant}/address/next", "/usage", "/metrics", "/readyz"] {
+            assert!(spec.paths.paths.contains_key(path), "{} is not documented", path);
+        }
+    }
+}
@@ -1,12 +1,56 @@
 use anyhow::Result;
-use pinpet_suffix_generator::{config::AppConfig, run_server};
+use pinpet_suffix_generator::cli::{Cli, Command, USAGE};
+use pinpet_suffix_generator::{
+    config::AppConfig, run_benchmark, run_dry_run, run_export, run_import, run_keyfile, run_redb_migration, run_server, run_verify,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Load configuration
-    let config = AppConfig::load()
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cli = Cli::parse(&args)?;
+    if cli.command == Command::Help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    // Load configuration: --config file (or config.*), APP_* variables, then --set overrides
+    let mut config = AppConfig::load_from(&cli.sources)
         .map_err(|e| anyhow::anyhow!("Failed to load configuration: {}", e))?;
+    // Secrets from {NAME}_FILE and Vault, before anything needs them
+    tokio::task::block_in_place(|| config.load_secrets())
+        .map_err(|e| anyhow::anyhow!("Failed to load secrets: {:#}", e))?;
 
-    // Run server
-    run_server(config).await
+    match cli.command {
+        Command::Serve { force, restore_snapshot } => {
+            // Start even if the database has unreadable records (they are quarantined)
+            config.startup.force |= force;
+            // Restore a snapshot (a key or `latest`) into the pools before serving
+            if restore_snapshot.is_some() {
+                config.backup.restore_from = restore_snapshot;
+            }
+            run_server(config).await
+        }
+        // Grind locally, print or write the keys and exit; nothing is stored
+        Command::Generate { count, pattern, format, output } => {
+            if let Some(suffix) = pattern {
+                config.pet_generator.target_suffix = suffix;
+                config.pet_generator.pattern = None;
+                config.pet_generator.vanity = None;
+            }
+            run_dry_run(config, count, format, output.as_deref())
+        }
+        Command::Export { pool, format, output } => run_export(config, &pool, format, output.as_deref()).await,
+        Command::Import { pool, format, input } => run_import(config, &pool, format, input.as_deref()).await,
+        Command::Keyfile { address, format, output } => run_keyfile(config, &address, format, output.as_deref()).await,
+        // Check and repair the database, report and exit
+        Command::Verify { force } => {
+            config.startup.force |= force;
+            run_verify(config).await
+        }
+        // Measure keys/sec, store the result and exit
+        Command::Benchmark { seconds } => run_benchmark(config, seconds).await,
+        // Copy the sled database into storage.redb_path and exit
+        Command::MigrateToRedb => run_redb_migration(config).await,
+        Command::Help => Ok(()),
+    }
 }
@@ -0,0 +1,147 @@
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+use crate::error::{ApiError, ErrorCode};
+
+/// Environment variable holding the admin API bearer token
+pub const ADMIN_KEY_ENV: &str = "ADMIN_API_KEY";
+
+/// Environment variable holding the bearer token of remote grind workers
+pub const WORKER_KEY_ENV: &str = "WORKER_API_KEY";
+
+/// Environment variable holding the bearer token replicas use to reach the primary
+pub const REPLICA_KEY_ENV: &str = "REPLICA_API_KEY";
+
+/// Client certificate of a TLS connection, verified against
+/// `[tls].client_ca_path`. Requests on the TLS listener carry an
+/// `Option<ClientCertificate>` extension.
+#[derive(Debug, Clone)]
+pub struct ClientCertificate {
+    /// SHA-256 of the leaf certificate (DER), hex
+    pub fingerprint: String,
+}
+
+impl ClientCertificate {
+    pub fn from_der(der: &[u8]) -> Self {
+        Self {
+            fingerprint: openssl::sha::sha256(der).iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+/// Require `Authorization: Bearer <admin key>` on the admin routes
+pub async fn admin_auth_middleware(
+    State(admin_key): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !bearer_matches(&request, &admin_key) {
+        let error = ApiError::with_detail(ErrorCode::Unauthorized, "Missing or wrong admin bearer token");
+        return ([(header::WWW_AUTHENTICATE, "Bearer")], error).into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Refuse every admin request when neither `ADMIN_API_KEY` nor `[jwt]` is
+/// set, so the admin routes are never open by default
+pub async fn admin_unconfigured_middleware(_request: Request, _next: Next) -> Response {
+    ApiError::with_detail(ErrorCode::Forbidden, "Admin routes need ADMIN_API_KEY or [jwt] to be set").into_response()
+}
+
+/// Require `Authorization: Bearer <worker key>` on the worker routes
+pub async fn worker_auth_middleware(
+    State(worker_key): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !bearer_matches(&request, &worker_key) {
+        let error = ApiError::with_detail(ErrorCode::Unauthorized, "Missing or wrong worker bearer token");
+        return ([(header::WWW_AUTHENTICATE, "Bearer")], error).into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Require `Authorization: Bearer <replica key>` on the routes replicas call
+pub async fn replica_auth_middleware(
+    State(replica_key): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !bearer_matches(&request, &replica_key) {
+        let error = ApiError::with_detail(ErrorCode::Unauthorized, "Missing or wrong replica bearer token");
+        return ([(header::WWW_AUTHENTICATE, "Bearer")], error).into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Refuse requests that did not arrive with a verified client certificate
+/// (mutual TLS); plain HTTP and the Unix socket never carry one
+pub async fn client_cert_middleware(request: Request, next: Next) -> Response {
+    match request.extensions().get::<Option<ClientCertificate>>().and_then(Option::as_ref) {
+        Some(certificate) => tracing::debug!("Client certificate {} on {}", certificate.fingerprint, request.uri().path()),
+        None => {
+            return ApiError::with_detail(ErrorCode::Forbidden, "These routes need a client certificate signed by the configured CA")
+                .into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+pub(super) fn bearer_matches(request: &Request, key: &str) -> bool {
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.as_bytes().strip_prefix(b"Bearer "))
+        .unwrap_or_default();
+    // Constant-time comparison so the key cannot be guessed byte by byte
+    provided.len() == key.len() && openssl::memcmp::eq(provided, key.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, middleware::from_fn_with_state, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_admin_routes_need_the_bearer_token() {
+        let app = Router::new()
+            .route("/admin/config", get(|| async { "ok" }))
+            .layer(from_fn_with_state(Arc::<str>::from("s3cret"), admin_auth_middleware));
+        let get = |authorization: Option<&str>| {
+            let mut request = Request::builder().uri("/admin/config");
+            if let Some(authorization) = authorization {
+                request = request.header(header::AUTHORIZATION, authorization);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        assert_eq!(get(Some("Bearer s3cret")).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(get(Some("Bearer guess")).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(get(Some("s3cret")).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(get(None).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_are_closed_without_a_credential() {
+        let app = Router::new()
+            .route("/admin/config", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn(admin_unconfigured_middleware));
+        let request = Request::builder()
+            .uri("/admin/config")
+            .header(header::AUTHORIZATION, "Bearer anything")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,116 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::{net::SocketAddr, sync::Arc};
+
+use super::admin_auth::ClientCertificate;
+use super::rate_limit::{tenant_for_key, API_KEY_HEADER};
+use crate::pet::audit::with_actor;
+use crate::pet::{AuditAction, AuditEvent, AuditLog};
+
+/// Hex digits of a client certificate fingerprint kept in the actor
+const FINGERPRINT_PREFIX_LEN: usize = 16;
+
+/// Name the caller of each request as the actor of whatever it audits:
+/// its role (`admin`, `worker`, `replica`, `tenant:{name}` or `client`),
+/// its IP and, on mutual TLS, its certificate, e.g. `worker@10.0.0.5 cert:3f2a…`
+pub async fn audit_actor_middleware(
+    State(api_keys): State<Arc<Vec<(String, String)>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let actor = actor_for(&api_keys, &request);
+    with_actor(actor, next.run(request)).await
+}
+
+/// Record each admin request that got past authentication, with its status;
+/// `/admin/export` is recorded as an export
+pub async fn audit_admin_middleware(State(log): State<AuditLog>, request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let target = request.uri().path_and_query().map(|target| target.to_string()).unwrap_or_default();
+    let action = match request.uri().path().ends_with("/admin/export") {
+        true => AuditAction::Export,
+        false => AuditAction::Admin,
+    };
+
+    let response = next.run(request).await;
+    log.record(
+        AuditEvent::new(action)
+            .subject(format!("{} {}", method, target))
+            .detail(format!("status {}", response.status().as_u16())),
+    );
+    response
+}
+
+fn actor_for(api_keys: &[(String, String)], request: &Request) -> String {
+    let path = request.uri().path();
+    let tenant = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|provided| tenant_for_key(api_keys, provided.as_bytes()));
+    let mut actor = match tenant {
+        _ if path.starts_with("/admin/") => "admin".to_string(),
+        _ if path.starts_with("/workers/") => "worker".to_string(),
+        _ if path.starts_with("/replicas/") => "replica".to_string(),
+        Some(tenant) => format!("tenant:{}", tenant),
+        None => "client".to_string(),
+    };
+
+    if let Some(ConnectInfo(addr)) = request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        actor.push_str(&format!("@{}", addr.ip()));
+    }
+    if let Some(certificate) = request.extensions().get::<Option<ClientCertificate>>().and_then(Option::as_ref) {
+        let prefix = &certificate.fingerprint[..FINGERPRINT_PREFIX_LEN.min(certificate.fingerprint.len())];
+        actor.push_str(&format!(" cert:{}", prefix));
+    }
+    actor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware::from_fn_with_state, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_requests_are_audited_under_their_caller() {
+        let log = AuditLog::new(None).unwrap();
+        let api_keys = Arc::new(vec![("tenant-key".to_string(), "staging".to_string())]);
+        let audited = log.clone();
+        let pop = move || {
+            let audited = audited.clone();
+            async move {
+                audited.record(AuditEvent::new(AuditAction::Pop).pool("default"));
+                "ok"
+            }
+        };
+        let admin = Router::new()
+            .route("/admin/export", get(|| async { "keys" }))
+            .route_layer(from_fn_with_state(log.clone(), audit_admin_middleware));
+        let app = Router::new()
+            .route("/api/v1/pet/address", get(pop))
+            .merge(admin)
+            .layer(from_fn_with_state(api_keys, audit_actor_middleware));
+
+        let request = |uri: &str, api_key: Option<&str>| {
+            let mut request = Request::builder().uri(uri);
+            if let Some(api_key) = api_key {
+                request = request.header(API_KEY_HEADER, api_key);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        app.clone().oneshot(request("/api/v1/pet/address", Some("tenant-key"))).await.unwrap();
+        app.clone().oneshot(request("/api/v1/pet/address", Some("guess"))).await.unwrap();
+        app.oneshot(request("/admin/export?format=json", None)).await.unwrap();
+
+        let entries = log.entries(None, 10).unwrap();
+        let actors: Vec<_> = entries.iter().rev().map(|entry| entry.actor.as_str()).collect();
+        assert_eq!(actors, vec!["tenant:staging", "client", "admin"]);
+        assert_eq!(entries[0].action, AuditAction::Export);
+        assert_eq!(entries[0].subject.as_deref(), Some("GET /admin/export?format=json"));
+        assert_eq!(entries[0].detail.as_deref(), Some("status 200"));
+        assert!(log.verify().unwrap().is_valid());
+    }
+}
@@ -0,0 +1,218 @@
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderValue, Method},
+    middleware::Next,
+    response::Response,
+};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use futures_util::{stream, StreamExt};
+use std::io::Write;
+
+use crate::config::CompressionConfig;
+
+/// Content codings the server can apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Gzip,
+    Deflate,
+}
+
+impl ContentEncoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+
+    /// The preferred coding of an `Accept-Encoding` header: the first listed
+    /// one (with a non-zero q) that we support; `*` means gzip
+    pub fn from_accept_encoding(accept_encoding: &str) -> Option<Self> {
+        for coding in accept_encoding.split(',') {
+            let mut parts = coding.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default().to_ascii_lowercase();
+            let refused = parts.any(|param| matches!(param.strip_prefix("q="), Some(q) if q.parse::<f32>() == Ok(0.0)));
+            if refused {
+                continue;
+            }
+            match name.as_str() {
+                "gzip" | "x-gzip" | "*" => return Some(Self::Gzip),
+                "deflate" => return Some(Self::Deflate),
+                "identity" => return None,
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Incremental encoder; output is taken after every chunk so streamed
+/// bodies are compressed as they are produced
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(encoding: ContentEncoding, level: u32) -> Self {
+        let level = Compression::new(level.clamp(1, 9));
+        match encoding {
+            ContentEncoding::Gzip => Self::Gzip(GzEncoder::new(Vec::new(), level)),
+            ContentEncoding::Deflate => Self::Deflate(ZlibEncoder::new(Vec::new(), level)),
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<Bytes> {
+        let output = match self {
+            Self::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                encoder.get_mut()
+            }
+            Self::Deflate(encoder) => {
+                encoder.write_all(data)?;
+                encoder.get_mut()
+            }
+        };
+        Ok(Bytes::from(std::mem::take(output)))
+    }
+
+    fn finish(self) -> std::io::Result<Bytes> {
+        match self {
+            Self::Gzip(encoder) => encoder.finish().map(Bytes::from),
+            Self::Deflate(encoder) => encoder.finish().map(Bytes::from),
+        }
+    }
+}
+
+/// Whether a content type is worth compressing. Event streams are left alone
+/// so events are not held back in the encoder.
+fn is_compressible(content_type: &[u8]) -> bool {
+    if content_type.starts_with(b"text/event-stream") {
+        return false;
+    }
+    [
+        "text/",
+        "application/json",
+        "application/x-ndjson",
+        "application/msgpack",
+        "application/cbor",
+        "application/javascript",
+        "application/openmetrics-text",
+    ]
+    .iter()
+    .any(|compressible| content_type.starts_with(compressible.as_bytes()))
+}
+
+/// Compress responses for clients that accept gzip or deflate. Bodies below
+/// `min_size` and already-encoded or non-text responses pass through.
+pub async fn compression_middleware(State(config): State<CompressionConfig>, request: Request, next: Next) -> Response {
+    let encoding = request
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|accept_encoding| accept_encoding.to_str().ok())
+        .and_then(ContentEncoding::from_accept_encoding)
+        .filter(|_| request.method() != Method::HEAD);
+
+    let mut response = next.run(request).await;
+    let compressible = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| is_compressible(content_type.as_bytes()))
+        && !response.headers().contains_key(header::CONTENT_ENCODING)
+        && !response.headers().contains_key(header::CONTENT_RANGE);
+    if !compressible {
+        return response;
+    }
+    response.headers_mut().append(header::VARY, HeaderValue::from_static("accept-encoding"));
+
+    let Some(encoding) = encoding else {
+        return response;
+    };
+    if response.body().size_hint().exact().is_some_and(|size| size < config.min_size as u64) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding.name()));
+
+    let mut encoder = Some(Encoder::new(encoding, config.level));
+    let chunks = body
+        .into_data_stream()
+        .map(Some)
+        .chain(stream::once(async { None }))
+        .map(move |chunk| match (chunk, &mut encoder) {
+            (Some(Ok(data)), Some(encoder)) => encoder.write(&data).map_err(axum::Error::new),
+            (Some(Err(e)), _) => Err(e),
+            (None, encoder) => match encoder.take() {
+                Some(encoder) => encoder.finish().map_err(axum::Error::new),
+                None => Ok(Bytes::new()),
+            },
+            (Some(Ok(_)), None) => Ok(Bytes::new()),
+        });
+    Response::from_parts(parts, Body::from_stream(chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::to_bytes, middleware::from_fn_with_state, routing::get, Router};
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use std::io::Read;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_accept_encoding_negotiation() {
+        assert_eq!(ContentEncoding::from_accept_encoding("gzip, deflate, br"), Some(ContentEncoding::Gzip));
+        assert_eq!(ContentEncoding::from_accept_encoding("br, deflate"), Some(ContentEncoding::Deflate));
+        assert_eq!(ContentEncoding::from_accept_encoding("gzip;q=0, deflate"), Some(ContentEncoding::Deflate));
+        assert_eq!(ContentEncoding::from_accept_encoding("*"), Some(ContentEncoding::Gzip));
+        assert_eq!(ContentEncoding::from_accept_encoding("identity"), None);
+        assert_eq!(ContentEncoding::from_accept_encoding("br"), None);
+    }
+
+    #[tokio::test]
+    async fn test_large_responses_are_compressed() {
+        let large = "Pet".repeat(1000);
+        let streamed = large.clone();
+        let app = Router::new()
+            .route("/large", get(move || async move { large }))
+            .route("/small", get(|| async { "Pet" }))
+            .route(
+                "/stream",
+                get(move || async move {
+                    let chunks: Vec<_> = streamed.into_bytes().chunks(100).map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec())).collect();
+                    ([(header::CONTENT_TYPE, "text/csv")], Body::from_stream(stream::iter(chunks)))
+                }),
+            )
+            .layer(from_fn_with_state(CompressionConfig::default(), compression_middleware));
+        let get = |uri: &'static str, accept_encoding: &'static str| {
+            let request = Request::builder().uri(uri).header(header::ACCEPT_ENCODING, accept_encoding);
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = get("/large", "gzip").await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(response.headers()[header::VARY], "accept-encoding");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(body.len() < 3000);
+        let mut decoded = String::new();
+        GzDecoder::new(&body[..]).read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, "Pet".repeat(1000));
+
+        let response = get("/large", "identity").await.unwrap();
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+        let response = get("/small", "gzip").await.unwrap();
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+
+        // Streamed exports have no known length and are compressed chunk by chunk
+        let response = get("/stream", "deflate").await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "deflate");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut decoded = String::new();
+        ZlibDecoder::new(&body[..]).read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, "Pet".repeat(1000));
+    }
+}
@@ -1,69 +1,112 @@
-use tower_http::cors::{CorsLayer, AllowOrigin, AllowMethods, AllowHeaders};
-use axum::http::{Method, HeaderName};
+use anyhow::{bail, Context, Result};
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
 
-#[cfg(feature = "production")]
-use axum::http::HeaderValue;
+use crate::config::CorsConfig;
 
-pub fn cors_layer() -> CorsLayer {
-    CorsLayer::new()
-        // Allow all origins - development environment configuration
-        .allow_origin(AllowOrigin::any())
-        // Allowed HTTP methods
-        .allow_methods(AllowMethods::list([
-            Method::GET,
-            Method::POST,
-            Method::PUT,
-            Method::DELETE,
-            Method::HEAD,
-            Method::OPTIONS,
-            Method::PATCH,
-        ]))
-        // Allowed request headers
-        .allow_headers(AllowHeaders::list([
-            axum::http::header::AUTHORIZATION,
-            axum::http::header::ACCEPT,
-            axum::http::header::ACCEPT_LANGUAGE,
-            axum::http::header::CONTENT_TYPE,
-            axum::http::header::CONTENT_LENGTH,
-            axum::http::header::ORIGIN,
-            axum::http::header::USER_AGENT,
-            HeaderName::from_static("x-requested-with"),
-            // Custom headers
-            HeaderName::from_static("x-request-id"),
-            HeaderName::from_static("x-api-key"),
-        ]))
-        // Exposed response headers
-        .expose_headers([
-            axum::http::header::CONTENT_LENGTH,
-            axum::http::header::CONTENT_TYPE,
-        ])
-        // No credentials needed for development environment
+/// CORS policy from `[cors]`. A `"*"` entry allows any origin, method or
+/// header; malformed entries fail startup rather than being skipped.
+pub fn cors_layer(config: &CorsConfig) -> Result<CorsLayer> {
+    let wildcard = |values: &[String]| values.iter().any(|value| value == "*");
+
+    if config.allow_credentials
+        && (wildcard(&config.allowed_origins) || wildcard(&config.allowed_methods) || wildcard(&config.allowed_headers))
+    {
+        bail!("cors.allow_credentials needs explicit origins, methods and headers, not \"*\"");
+    }
+
+    let allow_origin = if wildcard(&config.allowed_origins) {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(parse_all(&config.allowed_origins, "origin", |origin| {
+            HeaderValue::from_str(origin.trim_end_matches('/')).ok()
+        })?)
+    };
+    let allow_methods = if wildcard(&config.allowed_methods) {
+        AllowMethods::any()
+    } else {
+        AllowMethods::list(parse_all(&config.allowed_methods, "method", |method| {
+            Method::from_bytes(method.to_ascii_uppercase().as_bytes()).ok()
+        })?)
+    };
+    let allow_headers = if wildcard(&config.allowed_headers) {
+        AllowHeaders::any()
+    } else {
+        AllowHeaders::list(parse_all(&config.allowed_headers, "header", |header| {
+            HeaderName::from_bytes(header.as_bytes()).ok()
+        })?)
+    };
+    let expose_headers = parse_all(&config.exposed_headers, "header", |header| {
+        HeaderName::from_bytes(header.as_bytes()).ok()
+    })?;
+
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(allow_methods)
+        .allow_headers(allow_headers)
+        .expose_headers(expose_headers)
+        .allow_credentials(config.allow_credentials)
         // Preflight request cache time
-        .max_age(std::time::Duration::from_secs(3600))
+        .max_age(std::time::Duration::from_secs(config.max_age_seconds)))
 }
 
-#[cfg(feature = "production")]
-pub fn production_cors_layer(allowed_origins: Vec<String>) -> CorsLayer {
-    let origins: Vec<HeaderValue> = allowed_origins
-        .into_iter()
-        .filter_map(|origin| origin.parse().ok())
-        .collect();
-    
-    CorsLayer::new()
-        .allow_origin(AllowOrigin::list(origins))
-        .allow_methods(AllowMethods::list([
-            Method::GET,
-            Method::POST,
-            Method::PUT,
-            Method::DELETE,
-            Method::OPTIONS,
-        ]))
-        .allow_headers(AllowHeaders::list([
-            axum::http::header::AUTHORIZATION,
-            axum::http::header::ACCEPT,
-            axum::http::header::CONTENT_TYPE,
-            HeaderName::from_static("x-api-key"),
-        ]))
-        .allow_credentials(true)
-        .max_age(std::time::Duration::from_secs(86400))
-}
\ No newline at end of file
+fn parse_all<T>(values: &[String], kind: &str, parse: impl Fn(&str) -> Option<T>) -> Result<Vec<T>> {
+    values
+        .iter()
+        .map(|value| parse(value).with_context(|| format!("Invalid CORS {} '{}'", kind, value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn preflight(layer: CorsLayer, origin: &str) -> axum::http::HeaderMap {
+        let app = Router::new().route("/", get(|| async { "ok" })).layer(layer);
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/")
+            .header("origin", origin)
+            .header("access-control-request-method", "POST")
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap().headers().clone()
+    }
+
+    #[tokio::test]
+    async fn test_only_configured_origins_are_allowed() {
+        let config = CorsConfig {
+            allowed_origins: vec!["https://dashboard.internal/".to_string()],
+            allowed_methods: vec!["get".to_string(), "POST".to_string()],
+            allow_credentials: true,
+            ..CorsConfig::default()
+        };
+        let layer = cors_layer(&config).unwrap();
+
+        let allowed = preflight(layer.clone(), "https://dashboard.internal").await;
+        assert_eq!(allowed["access-control-allow-origin"], "https://dashboard.internal");
+        assert_eq!(allowed["access-control-allow-credentials"], "true");
+        assert_eq!(allowed["access-control-allow-methods"], "GET,POST");
+
+        let other = preflight(layer, "https://evil.example").await;
+        assert!(other.get("access-control-allow-origin").is_none());
+    }
+
+    #[test]
+    fn test_invalid_policies_are_rejected() {
+        let credentials_with_wildcard = CorsConfig {
+            allow_credentials: true,
+            ..CorsConfig::default()
+        };
+        assert!(cors_layer(&credentials_with_wildcard).is_err());
+
+        let bad_header = CorsConfig {
+            allowed_headers: vec!["x api key".to_string()],
+            ..CorsConfig::default()
+        };
+        assert!(cors_layer(&bad_header).unwrap_err().to_string().contains("x api key"));
+        assert!(cors_layer(&CorsConfig::default()).is_ok());
+    }
+}
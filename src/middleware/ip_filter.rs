@@ -0,0 +1,157 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+};
+
+use crate::error::{ApiError, ErrorCode};
+
+/// An IPv4 or IPv6 network, `address/prefix`; a bare address is a single host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl IpNetwork {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, canonical(ip)) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                prefix_matches(u32::from(network) as u128, u32::from(ip) as u128, self.prefix, 32)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => prefix_matches(u128::from(network), u128::from(ip), self.prefix, 128),
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match s.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let address = IpAddr::from_str(address).map_err(|_| format!("'{}' is not an IP address or CIDR", s))?;
+        let address = canonical(address);
+        let max = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max)
+                .ok_or_else(|| format!("'{}' has a prefix length outside 0-{}", s, max))?,
+            None => max,
+        };
+        Ok(Self { address, prefix })
+    }
+}
+
+/// IPv4-mapped IPv6 addresses (`::ffff:10.0.0.1`, from dual-stack listeners) as IPv4
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    }
+}
+
+fn prefix_matches(network: u128, ip: u128, prefix: u8, bits: u32) -> bool {
+    let shift = bits - prefix as u32;
+    shift == bits || network >> shift == ip >> shift
+}
+
+/// Allow and deny lists of one route group
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    allow: Vec<IpNetwork>,
+    deny: Vec<IpNetwork>,
+}
+
+impl IpFilter {
+    /// Parse both lists, naming every entry that is not a network
+    pub fn new(allow: &[String], deny: &[String]) -> Result<Self, Vec<String>> {
+        let mut problems = Vec::new();
+        let mut parse = |entries: &[String]| {
+            entries
+                .iter()
+                .filter_map(|entry| entry.parse().map_err(|problem| problems.push(problem)).ok())
+                .collect::<Vec<IpNetwork>>()
+        };
+        let filter = Self { allow: parse(allow), deny: parse(deny) };
+        match problems.is_empty() {
+            true => Ok(filter),
+            false => Err(problems),
+        }
+    }
+
+    /// True when neither list has an entry, so the filter lets everything through
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Denied networks always lose; otherwise an empty allow list admits everyone
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        if self.deny.iter().any(|network| network.contains(ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|network| network.contains(ip))
+    }
+}
+
+/// Refuse requests whose peer address the route group's lists do not admit,
+/// before any credentials are looked at
+pub async fn ip_filter_middleware(
+    State(filter): State<Arc<IpFilter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip());
+    match peer {
+        Some(ip) if filter.permits(ip) => next.run(request).await,
+        Some(ip) => {
+            tracing::debug!("Refused {} {} from {} by the IP filter", request.method(), request.uri().path(), ip);
+            ApiError::with_detail(ErrorCode::Forbidden, format!("Requests from {} are not allowed here", canonical(ip)))
+                .into_response()
+        }
+        // Without a peer address the lists cannot be checked, so only an empty filter lets it through
+        None if filter.is_empty() => next.run(request).await,
+        None => ApiError::with_detail(ErrorCode::Forbidden, "Client address unknown").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(allow: &[&str], deny: &[&str]) -> IpFilter {
+        let strings = |entries: &[&str]| entries.iter().map(|entry| entry.to_string()).collect::<Vec<_>>();
+        IpFilter::new(&strings(allow), &strings(deny)).unwrap()
+    }
+
+    #[test]
+    fn test_allow_and_deny_lists() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        let admin = filter(&["10.20.0.0/24", "fd00::/8"], &["10.20.0.13"]);
+        assert!(admin.permits(ip("10.20.0.7")));
+        assert!(admin.permits(ip("::ffff:10.20.0.7")));
+        assert!(admin.permits(ip("fd12::1")));
+        assert!(!admin.permits(ip("10.20.0.13")));
+        assert!(!admin.permits(ip("10.20.1.7")));
+        assert!(!admin.permits(ip("2001:db8::1")));
+
+        let consumer = filter(&[], &["203.0.113.0/24"]);
+        assert!(consumer.permits(ip("198.51.100.1")));
+        assert!(!consumer.permits(ip("203.0.113.9")));
+        assert!(filter(&["0.0.0.0/0"], &[]).permits(ip("8.8.8.8")));
+
+        let problems = IpFilter::new(&["10.0.0.0/33".to_string(), "intranet".to_string()], &[]).unwrap_err();
+        assert_eq!(problems.len(), 2);
+    }
+}
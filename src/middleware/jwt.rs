@@ -0,0 +1,458 @@
+//! Bearer JWTs issued by an OpenID Connect provider, checked in addition to
+//! the static API keys and admin token. Only the signature, `exp`, `nbf`,
+//! `iss` and `aud` are checked; the token is never sent anywhere.
+
+use anyhow::{bail, Context, Result};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use openssl::bn::BigNum;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private, Public};
+use openssl::rsa::Rsa;
+use openssl::sign::{Signer, Verifier};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+
+use super::admin_auth::bearer_matches;
+use super::rate_limit::{tenant_for_key, API_KEY_HEADER};
+use crate::config::{JwtAlgorithm, JwtConfig};
+use crate::error::{ApiError, ErrorCode};
+
+/// Environment variable holding the HS256 shared secret
+pub const JWT_SECRET_ENV: &str = "JWT_HS256_SECRET";
+
+/// Keys token signatures are checked against
+enum VerifyingKeys {
+    Hmac(PKey<Private>),
+    /// RS256 keys with their JWKS `kid`; a PEM key has none and is tried for every token
+    Rsa(Vec<(Option<String>, PKey<Public>)>),
+}
+
+/// Claims of a valid token that decide what it may do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JwtClaims {
+    pub subject: Option<String>,
+    /// From `scope` (space separated) or `scp` (string or array)
+    pub scopes: Vec<String>,
+    /// Value of the configured tenant claim
+    pub tenant: Option<String>,
+}
+
+impl JwtClaims {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|granted| granted == scope)
+    }
+}
+
+/// Why a token was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JwtError {
+    Malformed(&'static str),
+    Algorithm(String),
+    UnknownKey,
+    Signature,
+    Expired,
+    NotYetValid,
+    Issuer,
+    Audience,
+}
+
+impl fmt::Display for JwtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(what) => write!(f, "Malformed token: {}", what),
+            Self::Algorithm(alg) => write!(f, "Token algorithm '{}' is not accepted", alg),
+            Self::UnknownKey => write!(f, "Token was signed with an unknown key"),
+            Self::Signature => write!(f, "Token signature is invalid"),
+            Self::Expired => write!(f, "Token has expired"),
+            Self::NotYetValid => write!(f, "Token is not valid yet"),
+            Self::Issuer => write!(f, "Token was issued by someone else"),
+            Self::Audience => write!(f, "Token is meant for another audience"),
+        }
+    }
+}
+
+impl std::error::Error for JwtError {}
+
+#[derive(Deserialize)]
+struct TokenHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+/// Checks tokens against the `[jwt]` settings
+pub struct JwtValidator {
+    algorithm: JwtAlgorithm,
+    keys: VerifyingKeys,
+    issuer: Option<String>,
+    audience: Option<String>,
+    leeway: i64,
+    tenant_claim: String,
+}
+
+impl JwtValidator {
+    /// `secret` is the HS256 shared secret; RS256 reads `public_key_path`
+    pub fn new(config: &JwtConfig, secret: Option<&str>) -> Result<Self> {
+        let keys = match config.algorithm {
+            JwtAlgorithm::Hs256 => {
+                let secret = secret.with_context(|| format!("HS256 tokens need {}", JWT_SECRET_ENV))?;
+                VerifyingKeys::Hmac(PKey::hmac(secret.as_bytes())?)
+            }
+            JwtAlgorithm::Rs256 => {
+                let contents = std::fs::read(&config.public_key_path)
+                    .with_context(|| format!("Failed to read JWT key file {}", config.public_key_path))?;
+                VerifyingKeys::Rsa(parse_keys(&contents)?)
+            }
+        };
+        let non_empty = |value: &str| Some(value.to_string()).filter(|value| !value.is_empty());
+        Ok(Self {
+            algorithm: config.algorithm,
+            keys,
+            issuer: non_empty(&config.issuer),
+            audience: non_empty(&config.audience),
+            leeway: config.leeway_seconds as i64,
+            tenant_claim: config.tenant_claim.clone(),
+        })
+    }
+
+    pub fn validate(&self, token: &str) -> Result<JwtClaims, JwtError> {
+        self.validate_at(token, chrono::Utc::now().timestamp())
+    }
+
+    fn validate_at(&self, token: &str, now: i64) -> Result<JwtClaims, JwtError> {
+        let mut segments = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (segments.next(), segments.next(), segments.next(), segments.next())
+        else {
+            return Err(JwtError::Malformed("expected three segments"));
+        };
+        let header: TokenHeader = serde_json::from_slice(&decode_segment(header)?)
+            .map_err(|_| JwtError::Malformed("header is not a JOSE header"))?;
+        // The configured algorithm only: never `none`, never HS256 keyed with an RSA public key
+        if header.alg != self.algorithm.as_str() {
+            return Err(JwtError::Algorithm(header.alg));
+        }
+        let signed = &token[..token.len() - signature.len() - 1];
+        self.verify_signature(signed.as_bytes(), &decode_segment(signature)?, header.kid.as_deref())?;
+
+        let claims: Value =
+            serde_json::from_slice(&decode_segment(payload)?).map_err(|_| JwtError::Malformed("payload is not JSON"))?;
+        let exp = claims.get("exp").and_then(Value::as_i64).ok_or(JwtError::Malformed("exp is missing"))?;
+        if now > exp + self.leeway {
+            return Err(JwtError::Expired);
+        }
+        if claims.get("nbf").and_then(Value::as_i64).is_some_and(|nbf| now + self.leeway < nbf) {
+            return Err(JwtError::NotYetValid);
+        }
+        if let Some(issuer) = &self.issuer {
+            if claims.get("iss").and_then(Value::as_str) != Some(issuer.as_str()) {
+                return Err(JwtError::Issuer);
+            }
+        }
+        if let Some(audience) = &self.audience {
+            let matches = match claims.get("aud") {
+                Some(Value::String(aud)) => aud == audience,
+                Some(Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience.as_str())),
+                _ => false,
+            };
+            if !matches {
+                return Err(JwtError::Audience);
+            }
+        }
+
+        let mut scopes: Vec<String> = Vec::new();
+        for claim in ["scope", "scp"] {
+            match claims.get(claim) {
+                Some(Value::String(granted)) => scopes.extend(granted.split_whitespace().map(str::to_string)),
+                Some(Value::Array(granted)) => scopes.extend(granted.iter().filter_map(Value::as_str).map(str::to_string)),
+                _ => {}
+            }
+        }
+        Ok(JwtClaims {
+            subject: claims.get("sub").and_then(Value::as_str).map(str::to_string),
+            scopes,
+            tenant: claims.get(&self.tenant_claim).and_then(Value::as_str).map(str::to_string),
+        })
+    }
+
+    fn verify_signature(&self, signed: &[u8], signature: &[u8], kid: Option<&str>) -> Result<(), JwtError> {
+        match &self.keys {
+            VerifyingKeys::Hmac(key) => {
+                let expected = Signer::new(MessageDigest::sha256(), key)
+                    .and_then(|mut signer| signer.sign_oneshot_to_vec(signed))
+                    .map_err(|_| JwtError::Signature)?;
+                match expected.len() == signature.len() && openssl::memcmp::eq(&expected, signature) {
+                    true => Ok(()),
+                    false => Err(JwtError::Signature),
+                }
+            }
+            VerifyingKeys::Rsa(keys) => {
+                // A `kid` names one JWKS key; tokens without one are tried against all
+                let candidates: Vec<_> = keys
+                    .iter()
+                    .filter(|(key_id, _)| kid.is_none() || key_id.is_none() || key_id.as_deref() == kid)
+                    .collect();
+                if candidates.is_empty() {
+                    return Err(JwtError::UnknownKey);
+                }
+                let verified = candidates.iter().any(|(_, key)| {
+                    Verifier::new(MessageDigest::sha256(), key)
+                        .and_then(|mut verifier| verifier.verify_oneshot(signature, signed))
+                        .unwrap_or(false)
+                });
+                match verified {
+                    true => Ok(()),
+                    false => Err(JwtError::Signature),
+                }
+            }
+        }
+    }
+}
+
+/// RSA keys from a PEM public key or a JWKS document
+fn parse_keys(contents: &[u8]) -> Result<Vec<(Option<String>, PKey<Public>)>> {
+    if !contents.trim_ascii_start().starts_with(b"{") {
+        return Ok(vec![(None, PKey::public_key_from_pem(contents).context("JWT key is not a PEM public key")?)]);
+    }
+
+    #[derive(Deserialize)]
+    struct Jwks {
+        keys: Vec<Jwk>,
+    }
+    #[derive(Deserialize)]
+    struct Jwk {
+        kty: String,
+        kid: Option<String>,
+        #[serde(rename = "use")]
+        usage: Option<String>,
+        n: Option<String>,
+        e: Option<String>,
+    }
+
+    let jwks: Jwks = serde_json::from_slice(contents).context("JWT key file is neither PEM nor a JWKS document")?;
+    let mut keys = Vec::new();
+    for jwk in jwks.keys {
+        if jwk.kty != "RSA" || jwk.usage.as_deref().is_some_and(|usage| usage != "sig") {
+            continue;
+        }
+        let component = |value: Option<&String>| -> Result<BigNum> {
+            let bytes = value.map(|value| decode_segment(value)).transpose().ok().flatten();
+            Ok(BigNum::from_slice(&bytes.context("JWKS RSA key without a valid n or e")?)?)
+        };
+        let rsa = Rsa::from_public_components(component(jwk.n.as_ref())?, component(jwk.e.as_ref())?)?;
+        keys.push((jwk.kid, PKey::from_rsa(rsa)?));
+    }
+    if keys.is_empty() {
+        bail!("JWKS document has no RSA signing keys");
+    }
+    Ok(keys)
+}
+
+/// Unpadded base64url, as used by every JWT segment
+fn decode_segment(segment: &str) -> Result<Vec<u8>, JwtError> {
+    if segment.contains(['+', '/', '=']) {
+        return Err(JwtError::Malformed("segment is not base64url"));
+    }
+    let mut standard: String = segment
+        .chars()
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+    while !standard.len().is_multiple_of(4) {
+        standard.push('=');
+    }
+    openssl::base64::decode_block(&standard).map_err(|_| JwtError::Malformed("segment is not base64url"))
+}
+
+/// Bearer token of `Authorization`, if any
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// What a route group accepts instead of a token with its scope
+#[derive(Clone)]
+pub enum StaticCredential {
+    None,
+    /// A known tenant key in `X-API-Key`
+    TenantKeys(Arc<Vec<(String, String)>>),
+    /// This bearer token (the admin key)
+    Bearer(Arc<str>),
+}
+
+/// Middleware state: the validator, the scope a route group needs and its fallback credential
+#[derive(Clone)]
+pub struct JwtGuard {
+    validator: Arc<JwtValidator>,
+    scope: Arc<str>,
+    fallback: StaticCredential,
+}
+
+impl JwtGuard {
+    pub fn new(validator: &Arc<JwtValidator>, scope: &str, fallback: StaticCredential) -> Self {
+        Self {
+            validator: Arc::clone(validator),
+            scope: Arc::from(scope),
+            fallback,
+        }
+    }
+}
+
+/// Require a valid bearer JWT granting the guard's scope, or the guard's
+/// static credential. The claims of an accepted token are added to the
+/// request's extensions.
+pub async fn jwt_auth_middleware(State(guard): State<JwtGuard>, mut request: Request, next: Next) -> Response {
+    let fallback_accepted = match &guard.fallback {
+        StaticCredential::None => false,
+        StaticCredential::TenantKeys(api_keys) => request
+            .headers()
+            .get(API_KEY_HEADER)
+            .is_some_and(|provided| tenant_for_key(api_keys, provided.as_bytes()).is_some()),
+        StaticCredential::Bearer(key) => bearer_matches(&request, key),
+    };
+    if fallback_accepted {
+        return next.run(request).await;
+    }
+
+    let Some(token) = bearer_token(request.headers()) else {
+        let error = ApiError::with_detail(ErrorCode::Unauthorized, "Missing bearer token");
+        return ([(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"))], error).into_response();
+    };
+    match guard.validator.validate(token) {
+        Ok(claims) if claims.has_scope(&guard.scope) => {
+            request.extensions_mut().insert(claims);
+            next.run(request).await
+        }
+        Ok(_) => {
+            let challenge = format!("Bearer error=\"insufficient_scope\", scope=\"{}\"", guard.scope);
+            let error = ApiError::with_detail(ErrorCode::Forbidden, format!("Token lacks the '{}' scope", guard.scope));
+            (
+                [(header::WWW_AUTHENTICATE, HeaderValue::from_str(&challenge).unwrap_or(HeaderValue::from_static("Bearer")))],
+                error,
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::debug!("Refused a bearer token on {}: {}", request.uri().path(), e);
+            let error = ApiError::with_detail(ErrorCode::Unauthorized, e.to_string());
+            ([(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer error=\"invalid_token\""))], error).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_segment(bytes: &[u8]) -> String {
+        openssl::base64::encode_block(bytes).replace('+', "-").replace('/', "_").replace('=', "")
+    }
+
+    fn sign(header: &Value, claims: &Value, sign: impl Fn(&[u8]) -> Vec<u8>) -> String {
+        let signed = format!(
+            "{}.{}",
+            encode_segment(header.to_string().as_bytes()),
+            encode_segment(claims.to_string().as_bytes())
+        );
+        let signature = sign(signed.as_bytes());
+        format!("{}.{}", signed, encode_segment(&signature))
+    }
+
+    fn config(algorithm: JwtAlgorithm) -> JwtConfig {
+        JwtConfig {
+            enabled: true,
+            algorithm,
+            issuer: "https://id.example.com".to_string(),
+            audience: "pinpet".to_string(),
+            ..JwtConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_hs256_tokens_are_checked() {
+        let validator = JwtValidator::new(&config(JwtAlgorithm::Hs256), Some("shared-secret")).unwrap();
+        let key = PKey::hmac(b"shared-secret").unwrap();
+        let hs256 = |claims: &Value| {
+            sign(&serde_json::json!({"alg": "HS256", "typ": "JWT"}), claims, |signed| {
+                Signer::new(MessageDigest::sha256(), &key).unwrap().sign_oneshot_to_vec(signed).unwrap()
+            })
+        };
+        let now = 1_800_000_000;
+        let claims = serde_json::json!({
+            "sub": "svc-checkout", "iss": "https://id.example.com", "aud": ["pinpet", "other"],
+            "exp": now + 300, "scope": "pinpet:pop pinpet:read", "tenant": "staging"
+        });
+
+        let accepted = validator.validate_at(&hs256(&claims), now).unwrap();
+        assert_eq!(accepted.subject.as_deref(), Some("svc-checkout"));
+        assert!(accepted.has_scope("pinpet:pop") && !accepted.has_scope("pinpet:admin"));
+        assert_eq!(accepted.tenant.as_deref(), Some("staging"));
+
+        assert_eq!(validator.validate_at(&hs256(&claims), now + 361), Err(JwtError::Expired));
+        let mut other = claims.clone();
+        other["iss"] = "https://evil.example.com".into();
+        assert_eq!(validator.validate_at(&hs256(&other), now), Err(JwtError::Issuer));
+        other = claims.clone();
+        other["aud"] = "other".into();
+        assert_eq!(validator.validate_at(&hs256(&other), now), Err(JwtError::Audience));
+
+        // Tampered payloads and unsigned tokens are refused
+        let token = hs256(&claims);
+        let mut parts: Vec<&str> = token.split('.').collect();
+        let forged = encode_segment(serde_json::json!({"exp": now + 300, "scope": "pinpet:admin"}).to_string().as_bytes());
+        parts[1] = &forged;
+        assert_eq!(validator.validate_at(&parts.join("."), now), Err(JwtError::Signature));
+        let unsigned = sign(&serde_json::json!({"alg": "none"}), &claims, |_| Vec::new());
+        assert_eq!(validator.validate_at(&unsigned, now), Err(JwtError::Algorithm("none".to_string())));
+    }
+
+    #[test]
+    fn test_rs256_tokens_are_checked_against_jwks_keys() {
+        let signing = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let rsa = signing.rsa().unwrap();
+        let jwks = serde_json::json!({"keys": [
+            {"kty": "EC", "kid": "ec-1"},
+            {"kty": "RSA", "kid": "rsa-1", "use": "sig", "n": encode_segment(&rsa.n().to_vec()), "e": encode_segment(&rsa.e().to_vec())}
+        ]});
+        let path = std::env::temp_dir().join(format!("pinpet-jwks-{}.json", std::process::id()));
+        std::fs::write(&path, jwks.to_string()).unwrap();
+        let validator = JwtValidator::new(
+            &JwtConfig {
+                public_key_path: path.display().to_string(),
+                ..config(JwtAlgorithm::Rs256)
+            },
+            None,
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let now = 1_800_000_000;
+        let claims = serde_json::json!({"iss": "https://id.example.com", "aud": "pinpet", "exp": now + 60, "scp": ["pinpet:admin"]});
+        let rs256 = |kid: &str| {
+            sign(&serde_json::json!({"alg": "RS256", "kid": kid}), &claims, |signed| {
+                Signer::new(MessageDigest::sha256(), &signing).unwrap().sign_oneshot_to_vec(signed).unwrap()
+            })
+        };
+        assert!(validator.validate_at(&rs256("rsa-1"), now).unwrap().has_scope("pinpet:admin"));
+        assert_eq!(validator.validate_at(&rs256("rotated-away"), now), Err(JwtError::UnknownKey));
+
+        // An HS256 token keyed with the public key must not pass as RS256
+        let public_pem = signing.public_key_to_pem().unwrap();
+        let confused = sign(&serde_json::json!({"alg": "HS256", "kid": "rsa-1"}), &claims, |signed| {
+            let key = PKey::hmac(&public_pem).unwrap();
+            Signer::new(MessageDigest::sha256(), &key).unwrap().sign_oneshot_to_vec(signed).unwrap()
+        });
+        assert_eq!(validator.validate_at(&confused, now), Err(JwtError::Algorithm("HS256".to_string())));
+    }
+}
@@ -1,12 +1,237 @@
-use tower_http::trace::{TraceLayer, DefaultMakeSpan, DefaultOnResponse};
-use tracing::Level;
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header, HeaderName},
+    middleware::Next,
+    response::Response,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::sensitive_headers::SetSensitiveRequestHeadersLayer;
+use tower_http::trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer};
+use tracing::{field, Level, Span};
 
-pub fn logging_layer() -> TraceLayer<tower_http::classify::SharedClassifier<tower_http::classify::ServerErrorsAsFailures>> {
+use crate::handlers::{API_KEY_HEADER, REVEAL_KEY_HEADER};
+use crate::middleware::tenant_for_key;
+
+/// Header carrying the request ID, taken from the client or generated
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// W3C trace context header
+const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Mark credential headers sensitive so the request log prints them as `Sensitive`
+pub fn sensitive_headers_layer() -> SetSensitiveRequestHeadersLayer {
+    SetSensitiveRequestHeadersLayer::new([
+        header::AUTHORIZATION,
+        HeaderName::from_static(API_KEY_HEADER),
+        HeaderName::from_static(REVEAL_KEY_HEADER),
+    ])
+}
+
+/// Give requests without an `X-Request-Id` a UUID
+pub fn request_id_layer() -> SetRequestIdLayer<MakeRequestUuid> {
+    SetRequestIdLayer::new(HeaderName::from_static(REQUEST_ID_HEADER), MakeRequestUuid)
+}
+
+/// Echo the request ID on the response
+pub fn propagate_request_id_layer() -> PropagateRequestIdLayer {
+    PropagateRequestIdLayer::new(HeaderName::from_static(REQUEST_ID_HEADER))
+}
+
+type MakeSpan = fn(&Request<Body>) -> Span;
+
+/// One span per request carrying its ID, route template and tenant, so every
+/// event logged while handling it can be correlated and grouped. The tenant
+/// and status are filled in by `access_log_middleware`. Full headers are logged at debug level.
+pub fn logging_layer() -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>, MakeSpan> {
     TraceLayer::new_for_http()
-        .make_span_with(DefaultMakeSpan::new()
-            .level(Level::INFO)
-            .include_headers(true))
-        .on_response(DefaultOnResponse::new()
-            .level(Level::INFO)
-            .include_headers(true))
-}
\ No newline at end of file
+        .make_span_with(request_span as MakeSpan)
+        .on_request(DefaultOnRequest::new().level(Level::DEBUG))
+        .on_response(DefaultOnResponse::new().level(Level::DEBUG).include_headers(true))
+}
+
+fn request_span(request: &Request<Body>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    // Set once the router matched the request; unknown paths have no route
+    let route = request.extensions().get::<MatchedPath>().map(MatchedPath::as_str);
+    // W3C trace context of the caller, continued when traces are exported
+    let traceparent = request.headers().get(TRACEPARENT_HEADER).and_then(|value| value.to_str().ok());
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id = %request_id,
+        route = route,
+        tenant = field::Empty,
+        status = field::Empty,
+        traceparent = traceparent,
+    )
+}
+
+/// Tenant a request acts for: the owner of a known API key, otherwise the
+/// `{tenant}` segment of a tenant route
+fn tenant(api_keys: &[(String, String)], request: &Request) -> Option<String> {
+    let provided = request.headers().get(API_KEY_HEADER);
+    if let Some(tenant) = provided.and_then(|provided| tenant_for_key(api_keys, provided.as_bytes())) {
+        return Some(tenant.to_string());
+    }
+    let route = request.extensions().get::<MatchedPath>()?.as_str();
+    let position = route.split('/').position(|segment| segment == "{tenant}")?;
+    request.uri().path().split('/').nth(position).map(String::from)
+}
+
+/// Who made a request, for the access log: `tenant:{name}` for a known API
+/// key, `unknown-key` for any other key, otherwise `anonymous`
+fn identity(api_keys: &[(String, String)], request: &Request) -> String {
+    match request.headers().get(API_KEY_HEADER) {
+        Some(provided) => match tenant_for_key(api_keys, provided.as_bytes()) {
+            Some(tenant) => format!("tenant:{}", tenant),
+            None => "unknown-key".to_string(),
+        },
+        None => "anonymous".to_string(),
+    }
+}
+
+/// One structured `access_log` event per request with status, latency and
+/// caller identity (never the key itself). `api_keys` are (key, tenant) pairs.
+pub async fn access_log_middleware(
+    State(api_keys): State<Arc<Vec<(String, String)>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let identity = identity(&api_keys, &request);
+    if let Some(tenant) = tenant(&api_keys, &request) {
+        Span::current().record("tenant", tenant.as_str());
+    }
+    let client_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_default();
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+    Span::current().record("status", response.status().as_u16());
+
+    tracing::info!(
+        target: "access_log",
+        method = %method,
+        path = %path,
+        status = response.status().as_u16(),
+        latency_ms = start.elapsed().as_secs_f64() * 1000.0,
+        identity = %identity,
+        client_ip = %client_ip,
+        "{} {} {}",
+        method,
+        path,
+        response.status().as_u16()
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware::from_fn_with_state, routing::get, Router};
+    use tower::{ServiceBuilder, ServiceExt};
+
+    #[tokio::test]
+    async fn test_request_ids_are_generated_or_propagated() {
+        let api_keys = Arc::new(vec![("secret".to_string(), "staging".to_string())]);
+        let app = Router::new().route("/", get(|| async { "ok" })).layer(
+            ServiceBuilder::new()
+                .layer(request_id_layer())
+                .layer(propagate_request_id_layer())
+                .layer(logging_layer())
+                .layer(from_fn_with_state(api_keys, access_log_middleware)),
+        );
+
+        let response = app.clone().oneshot(Request::new(Body::empty())).await.unwrap();
+        let generated = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert_eq!(generated.len(), 36);
+
+        let request = Request::builder().header(REQUEST_ID_HEADER, "abc-123").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "abc-123");
+    }
+
+    /// Log lines written while a test runs
+    #[derive(Clone, Default)]
+    struct Captured(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_logs_carry_request_id_route_and_tenant() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let api_keys = Arc::new(vec![("secret".to_string(), "staging".to_string())]);
+        let app = Router::new()
+            .route("/tenants/{tenant}/status", get(|| async { "ok" }))
+            .route("/pet/status", get(|| async { "ok" }))
+            .layer(
+                ServiceBuilder::new()
+                    .layer(logging_layer())
+                    .layer(from_fn_with_state(api_keys, access_log_middleware)),
+            );
+        let request = |uri: &str, key: Option<&str>| {
+            let mut request = Request::builder().uri(uri).header(REQUEST_ID_HEADER, "req-7");
+            if let Some(key) = key {
+                request = request.header(API_KEY_HEADER, key);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        app.clone().oneshot(request("/tenants/dev/status", None)).await.unwrap();
+        app.oneshot(request("/pet/status", Some("secret"))).await.unwrap();
+
+        let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let spans: Vec<serde_json::Value> = logs
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .filter(|event| event["target"] == "access_log")
+            .map(|event| event["span"].clone())
+            .collect();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0]["request_id"], "req-7");
+        assert_eq!(spans[0]["route"], "/tenants/{tenant}/status");
+        assert_eq!(spans[0]["tenant"], "dev");
+        assert_eq!(spans[1]["route"], "/pet/status");
+        assert_eq!(spans[1]["tenant"], "staging");
+    }
+
+    #[test]
+    fn test_identity_names_the_tenant_not_the_key() {
+        let api_keys = vec![("secret".to_string(), "staging".to_string())];
+        let with_key = |key: &str| Request::builder().header(API_KEY_HEADER, key).body(Body::empty()).unwrap();
+        assert_eq!(identity(&api_keys, &with_key("secret")), "tenant:staging");
+        assert_eq!(identity(&api_keys, &with_key("guess")), "unknown-key");
+        assert_eq!(identity(&api_keys, &Request::new(Body::empty())), "anonymous");
+    }
+}
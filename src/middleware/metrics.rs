@@ -0,0 +1,32 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+
+use crate::pet::metrics::{HTTP_DURATION, HTTP_REQUESTS};
+
+/// Count requests and record their latency, labelled by route template
+/// (`/api/v1/pet/{pattern}/address`, not the concrete path) to keep the
+/// number of series bounded
+pub async fn http_metrics_middleware(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let path = match request.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None => "unmatched".to_string(),
+    };
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+
+    let labels = [
+        ("method", method),
+        ("path", path),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    metrics::counter!(HTTP_REQUESTS, &labels).increment(1);
+    metrics::histogram!(HTTP_DURATION, &labels).record(start.elapsed().as_secs_f64());
+
+    response
+}
@@ -1,7 +1,27 @@
+pub mod admin_auth;
+pub mod audit;
+pub mod compression;
 pub mod cors;
+pub mod ip_filter;
+pub mod jwt;
 pub mod logging;
+pub mod metrics;
+pub mod negotiation;
+pub mod problem;
 pub mod rate_limit;
+pub mod signing;
+pub mod versioning;
 
+pub use admin_auth::*;
+pub use audit::*;
+pub use compression::*;
 pub use cors::*;
+pub use ip_filter::*;
+pub use jwt::*;
 pub use logging::*;
-pub use rate_limit::*;
\ No newline at end of file
+pub use metrics::*;
+pub use negotiation::*;
+pub use problem::*;
+pub use rate_limit::*;
+pub use signing::*;
+pub use versioning::*;
\ No newline at end of file
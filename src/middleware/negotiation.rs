@@ -0,0 +1,139 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Compact encodings a client can ask for instead of JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryFormat {
+    MessagePack,
+    Cbor,
+}
+
+impl BinaryFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::MessagePack => "application/msgpack",
+            Self::Cbor => "application/cbor",
+        }
+    }
+
+    /// The preferred format of an `Accept` header: the first media range
+    /// (with a non-zero q) that is JSON or one of ours, in listed order
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        for range in accept.split(',') {
+            let mut parts = range.split(';').map(str::trim);
+            let media_type = parts.next().unwrap_or_default().to_ascii_lowercase();
+            let refused = parts.any(|param| matches!(param.strip_prefix("q="), Some(q) if q.parse::<f32>() == Ok(0.0)));
+            if refused {
+                continue;
+            }
+            match media_type.as_str() {
+                "application/json" => return None,
+                "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                    return Some(Self::MessagePack)
+                }
+                "application/cbor" => return Some(Self::Cbor),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn transcode(self, json: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let value: serde_json::Value = serde_json::from_slice(json)?;
+        Ok(match self {
+            Self::MessagePack => rmp_serde::to_vec_named(&value)?,
+            Self::Cbor => {
+                let mut encoded = Vec::new();
+                ciborium::into_writer(&value, &mut encoded)?;
+                encoded
+            }
+        })
+    }
+}
+
+/// Re-encode JSON responses as MessagePack or CBOR when the `Accept` header
+/// asks for it. Other responses (streams, CSV, metrics) pass through.
+pub async fn content_negotiation_middleware(request: Request, next: Next) -> Response {
+    let format = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .and_then(BinaryFormat::from_accept);
+
+    let mut response = next.run(request).await;
+    response.headers_mut().append(header::VARY, HeaderValue::from_static("accept"));
+    let Some(format) = format else {
+        return response;
+    };
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| content_type.as_bytes().starts_with(b"application/json"));
+    if !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let transcoded = match to_bytes(body, usize::MAX).await {
+        Ok(json) => format.transcode(&json),
+        Err(e) => Err(e.into()),
+    };
+    match transcoded {
+        Ok(encoded) => {
+            parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(format.content_type()));
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(encoded))
+        }
+        Err(e) => {
+            tracing::error!("Failed to encode response as {}: {}", format.content_type(), e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ApiResponse;
+    use axum::{middleware::from_fn, routing::get, Json, Router};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_accept_header_picks_first_supported_format() {
+        assert_eq!(BinaryFormat::from_accept("application/msgpack"), Some(BinaryFormat::MessagePack));
+        assert_eq!(BinaryFormat::from_accept("text/html, application/cbor;q=0.9"), Some(BinaryFormat::Cbor));
+        assert_eq!(BinaryFormat::from_accept("application/json, application/cbor"), None);
+        assert_eq!(BinaryFormat::from_accept("application/msgpack;q=0, application/cbor"), Some(BinaryFormat::Cbor));
+        assert_eq!(BinaryFormat::from_accept("*/*"), None);
+    }
+
+    #[tokio::test]
+    async fn test_json_responses_are_transcoded() {
+        let app = Router::new()
+            .route("/", get(|| async { Json(ApiResponse::success(vec![1u64, 2])) }))
+            .layer(from_fn(content_negotiation_middleware));
+        let get = |accept: &'static str| {
+            app.clone()
+                .oneshot(Request::builder().uri("/").header(header::ACCEPT, accept).body(Body::empty()).unwrap())
+        };
+
+        let response = get("application/msgpack").await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/msgpack");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let decoded: serde_json::Value = rmp_serde::from_slice(&body).unwrap();
+        assert_eq!(decoded["data"], serde_json::json!([1, 2]));
+
+        let response = get("application/cbor").await.unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let decoded: serde_json::Value = ciborium::from_reader(&body[..]).unwrap();
+        assert_eq!(decoded["code"], 200);
+
+        let response = get("application/json").await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    }
+}
@@ -0,0 +1,121 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::error::{ErrorCode, ProblemDetails, PROBLEM_CONTENT_TYPE};
+use crate::middleware::REQUEST_ID_HEADER;
+
+/// Longest plain-text error body kept as a problem's `detail`
+const MAX_DETAIL_BYTES: usize = 4096;
+
+/// Put the request ID into problem responses, and turn errors raised outside
+/// the handlers (body rejections, unknown routes, rate limits, admin auth)
+/// into problems as well. Error responses with any other body, like the
+/// `/readyz` report, pass through.
+pub async fn problem_details_middleware(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+
+    let response = next.run(request).await;
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let mut problem = match parts.extensions.remove::<ProblemDetails>() {
+        Some(problem) => problem,
+        None => {
+            let content_type = parts.headers.get(header::CONTENT_TYPE).map(|value| value.as_bytes());
+            let detail = match content_type {
+                None => None,
+                Some(content_type) if content_type.starts_with(b"text/plain") => to_bytes(body, MAX_DETAIL_BYTES)
+                    .await
+                    .ok()
+                    .map(|text| String::from_utf8_lossy(&text).into_owned())
+                    .filter(|text| !text.is_empty()),
+                Some(_) => return Response::from_parts(parts, body),
+            };
+            ProblemDetails::new(status, ErrorCode::for_status(status), detail)
+        }
+    };
+    problem.request_id = request_id;
+
+    match serde_json::to_vec(&problem) {
+        Ok(json) => {
+            parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_CONTENT_TYPE));
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(json))
+        }
+        Err(e) => {
+            tracing::error!("Failed to encode problem details: {}", e);
+            Response::from_parts(parts, Body::empty())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ApiError;
+    use axum::{
+        http::StatusCode,
+        middleware::from_fn,
+        routing::{get, post},
+        Json, Router,
+    };
+    use tower::ServiceExt;
+
+    async fn problem(app: &Router, request: Request) -> (StatusCode, serde_json::Value) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_CONTENT_TYPE);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_every_error_becomes_a_problem_with_the_request_id() {
+        let app = Router::new()
+            .route("/empty", get(|| async { ApiError::with_detail(ErrorCode::PoolEmpty, "Pool 'default' has no addresses right now") }))
+            .route("/bare", get(|| async { StatusCode::SERVICE_UNAVAILABLE }))
+            .route("/json", post(|Json(value): Json<serde_json::Value>| async move { Json(value) }))
+            .route("/report", get(|| async { (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({"ready": false}))) }))
+            .layer(from_fn(problem_details_middleware));
+        let get = |uri: &str| Request::builder().uri(uri).header(REQUEST_ID_HEADER, "req-1").body(Body::empty()).unwrap();
+
+        let (status, body) = problem(&app, get("/empty")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "pool_empty");
+        assert_eq!(body["request_id"], "req-1");
+
+        let (status, body) = problem(&app, get("/bare")).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["code"], "unavailable");
+
+        let (_, body) = problem(&app, get("/missing")).await;
+        assert_eq!(body["code"], "not_found");
+
+        // Extractor rejections keep their explanation as the detail
+        let request = Request::builder()
+            .method("POST")
+            .uri("/json")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{"))
+            .unwrap();
+        let (status, body) = problem(&app, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_request");
+        assert!(body["detail"].as_str().unwrap().contains("JSON"));
+
+        let response = app.clone().oneshot(get("/report")).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    }
+}
@@ -1,93 +1,265 @@
 use axum::{
-    extract::{Request, ConnectInfo},
-    http::StatusCode,
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue},
     middleware::Next,
     response::{IntoResponse, Response},
 };
 use dashmap::DashMap;
 use std::{
     net::SocketAddr,
-    sync::Arc,
+    sync::{Arc, RwLock},
     time::{Duration, Instant},
 };
 use tokio::time::sleep;
 
+use crate::config::RateLimitConfig;
+use crate::error::{ApiError, ErrorCode};
+
+/// Header carrying a client's API key (the tenant keys)
+pub(crate) const API_KEY_HEADER: &str = "x-api-key";
+/// Buckets untouched for this long are full again and can be dropped
+const IDLE_BUCKET_TTL: Duration = Duration::from_secs(600);
+
+/// Tenant owning the API key `provided`, from (key, tenant) pairs
+pub fn tenant_for_key<'a>(api_keys: &'a [(String, String)], provided: &[u8]) -> Option<&'a str> {
+    api_keys
+        .iter()
+        .find(|(key, _)| {
+            // Constant-time comparison so the key cannot be guessed byte by byte
+            key.len() == provided.len() && openssl::memcmp::eq(key.as_bytes(), provided)
+        })
+        .map(|(_, tenant)| tenant.as_str())
+}
+
+/// Routes are limited separately: pops drain the pool, reads do not
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteClass {
+    Pop,
+    Read,
+}
+
+/// Steady rate plus burst size of one route class
+#[derive(Debug, Clone, Copy)]
+struct Rate {
+    per_second: f64,
+    burst: f64,
+}
+
+impl Rate {
+    fn new(per_minute: u32, burst: u32) -> Option<Self> {
+        (per_minute > 0).then(|| Self {
+            per_second: per_minute as f64 / 60.0,
+            burst: burst.max(1) as f64,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per-class rates; `None` leaves the class unlimited
+#[derive(Debug, Clone)]
+struct Rates {
+    config: RateLimitConfig,
+    pop: Option<Rate>,
+    read: Option<Rate>,
+}
+
+impl Rates {
+    fn from_config(config: &RateLimitConfig) -> Self {
+        let enabled = config.enabled;
+        Self {
+            config: config.clone(),
+            pop: Rate::new(config.pop_per_minute, config.pop_burst).filter(|_| enabled),
+            read: Rate::new(config.read_per_minute, config.read_burst).filter(|_| enabled),
+        }
+    }
+}
+
+/// Token-bucket limiter keyed by client and route class. A client is its
+/// API key when it sends a known one, otherwise its IP address.
 #[derive(Clone)]
 pub struct RateLimiter {
-    requests: Arc<DashMap<String, Vec<Instant>>>,
-    max_requests: u32,
-    window_duration: Duration,
+    buckets: Arc<DashMap<(RouteClass, String), TokenBucket>>,
+    // Swapped by the admin API; a disabled limiter has no rates
+    rates: Arc<RwLock<Rates>>,
+    // (key, tenant) pairs; only known keys get their own bucket, so rotating
+    // made-up keys does not escape the per-IP limit
+    api_keys: Arc<Vec<(String, String)>>,
 }
 
 impl RateLimiter {
-    pub fn new(max_requests: u32, window_seconds: u64) -> Self {
+    pub fn new(config: &RateLimitConfig, api_keys: Arc<Vec<(String, String)>>) -> Self {
         let limiter = Self {
-            requests: Arc::new(DashMap::new()),
-            max_requests,
-            window_duration: Duration::from_secs(window_seconds),
+            buckets: Arc::new(DashMap::new()),
+            rates: Arc::new(RwLock::new(Rates::from_config(config))),
+            api_keys,
         };
-        
+
         // Start cleanup task
         let limiter_clone = limiter.clone();
         tokio::spawn(async move {
             limiter_clone.cleanup_task().await;
         });
-        
+
         limiter
     }
-    
-    pub fn check_rate_limit(&self, ip: &str) -> bool {
-        let now = Instant::now();
-        let cutoff = now - self.window_duration;
-        
-        let mut entry = self.requests.entry(ip.to_string()).or_insert_with(Vec::new);
-        
-        // Remove old requests
-        entry.retain(|&time| time > cutoff);
-        
-        // Check if under limit
-        if entry.len() >= self.max_requests as usize {
-            false
+
+    /// Apply new limits. Existing buckets keep their tokens, capped at the new burst on next use.
+    pub fn set_rates(&self, config: &RateLimitConfig) {
+        *self.rates.write().unwrap_or_else(|e| e.into_inner()) = Rates::from_config(config);
+    }
+
+    /// The limits currently applied
+    pub fn config(&self) -> RateLimitConfig {
+        self.rates.read().unwrap_or_else(|e| e.into_inner()).config.clone()
+    }
+
+    /// Take one token for `client`; when none is left, the wait until the next one
+    pub fn check(&self, class: RouteClass, client: &str) -> Result<(), Duration> {
+        self.check_at(class, client, Instant::now())
+    }
+
+    fn check_at(&self, class: RouteClass, client: &str, now: Instant) -> Result<(), Duration> {
+        let rate = {
+            let rates = self.rates.read().unwrap_or_else(|e| e.into_inner());
+            match class {
+                RouteClass::Pop => rates.pop,
+                RouteClass::Read => rates.read,
+            }
+        };
+        let Some(rate) = rate else {
+            return Ok(());
+        };
+
+        let mut bucket = self
+            .buckets
+            .entry((class, client.to_string()))
+            .or_insert(TokenBucket {
+                tokens: rate.burst,
+                updated: now,
+            });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate.per_second).min(rate.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
         } else {
-            // Add current request
-            entry.push(now);
-            true
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate.per_second))
+        }
+    }
+
+    /// `key:{tenant}` for a known API key, otherwise `ip:{address}`
+    fn client_id(&self, request: &Request) -> String {
+        if let Some(provided) = request.headers().get(API_KEY_HEADER).map(HeaderValue::as_bytes) {
+            if let Some(tenant) = tenant_for_key(&self.api_keys, provided) {
+                return format!("key:{}", tenant);
+            }
+        }
+
+        match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+            Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+            None => "ip:unknown".to_string(),
         }
     }
-    
+
     async fn cleanup_task(&self) {
         loop {
             sleep(Duration::from_secs(60)).await; // Cleanup every minute
-            
+
             let now = Instant::now();
-            let cutoff = now - self.window_duration;
-            
-            self.requests.retain(|_ip, times| {
-                times.retain(|&time| time > cutoff);
-                !times.is_empty()
-            });
+            self.buckets
+                .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < IDLE_BUCKET_TTL);
         }
     }
 }
 
+/// Reject requests over the client's budget for `class` with 429 and `Retry-After`
 pub async fn rate_limit_middleware(
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((limiter, class)): State<(RateLimiter, RouteClass)>,
     request: Request,
     next: Next,
 ) -> Response {
-    let limiter = request
-        .extensions()
-        .get::<RateLimiter>()
-        .expect("RateLimiter not found in request extensions");
-    
-    let ip = addr.ip().to_string();
-    
-    if !limiter.check_rate_limit(&ip) {
+    let client = limiter.client_id(&request);
+
+    if let Err(retry_after) = limiter.check(class, &client) {
+        let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        tracing::debug!("Rate limited {} on {:?} routes for {}s", client, class, seconds);
         return (
-            StatusCode::TOO_MANY_REQUESTS,
-            "Rate limit exceeded. Maximum 10 requests per minute allowed.",
-        ).into_response();
+            [(header::RETRY_AFTER, seconds.to_string())],
+            ApiError::with_detail(ErrorCode::RateLimited, format!("Rate limit exceeded. Retry in {} seconds.", seconds)),
+        )
+            .into_response();
     }
-    
+
     next.run(request).await
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(pop_per_minute: u32, pop_burst: u32) -> RateLimiter {
+        let config = RateLimitConfig {
+            enabled: true,
+            pop_per_minute,
+            pop_burst,
+            read_per_minute: 0,
+            read_burst: 0,
+        };
+        RateLimiter::new(&config, Arc::new(vec![("secret".to_string(), "dev".to_string())]))
+    }
+
+    #[tokio::test]
+    async fn test_token_bucket_allows_burst_then_refills() {
+        let limiter = limiter(60, 2);
+        let start = Instant::now();
+
+        assert!(limiter.check_at(RouteClass::Pop, "ip:a", start).is_ok());
+        assert!(limiter.check_at(RouteClass::Pop, "ip:a", start).is_ok());
+        let retry_after = limiter.check_at(RouteClass::Pop, "ip:a", start).unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(1));
+
+        // Other clients and unlimited classes are unaffected
+        assert!(limiter.check_at(RouteClass::Pop, "ip:b", start).is_ok());
+        assert!(limiter.check_at(RouteClass::Read, "ip:a", start).is_ok());
+
+        // One token per second at 60/minute, never more than the burst
+        let later = start + Duration::from_millis(1500);
+        assert!(limiter.check_at(RouteClass::Pop, "ip:a", later).is_ok());
+        assert!(limiter.check_at(RouteClass::Pop, "ip:a", later).is_err());
+        let much_later = start + Duration::from_secs(3600);
+        assert!(limiter.check_at(RouteClass::Pop, "ip:a", much_later).is_ok());
+        assert!(limiter.check_at(RouteClass::Pop, "ip:a", much_later).is_ok());
+        assert!(limiter.check_at(RouteClass::Pop, "ip:a", much_later).is_err());
+
+        // Disabling at runtime lifts every limit
+        limiter.set_rates(&RateLimitConfig { enabled: false, ..limiter.config() });
+        assert!(limiter.check_at(RouteClass::Pop, "ip:a", much_later).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_clients_are_keyed_by_known_api_key() {
+        let limiter = limiter(60, 1);
+        let request = |key: Option<&str>| {
+            let mut builder = Request::builder().uri("/");
+            if let Some(key) = key {
+                builder = builder.header(API_KEY_HEADER, key);
+            }
+            let mut request = builder.body(axum::body::Body::empty()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 4000))));
+            request
+        };
+
+        assert_eq!(limiter.client_id(&request(Some("secret"))), "key:dev");
+        assert_eq!(limiter.client_id(&request(Some("made-up"))), "ip:10.0.0.1");
+        assert_eq!(limiter.client_id(&request(None)), "ip:10.0.0.1");
+    }
+}
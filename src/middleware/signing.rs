@@ -0,0 +1,111 @@
+use anyhow::Result;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::Signer;
+
+/// Environment variable holding the shared response signing secret
+pub const SIGNING_KEY_ENV: &str = "RESPONSE_SIGNING_KEY";
+/// Header carrying `sha256=<hex HMAC-SHA256 of the body>`
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+/// Signs response bodies with HMAC-SHA256 under a shared secret
+#[derive(Clone)]
+pub struct ResponseSigner {
+    key: PKey<Private>,
+}
+
+impl ResponseSigner {
+    pub fn new(secret: &[u8]) -> Result<Self> {
+        Ok(Self {
+            key: PKey::hmac(secret)?,
+        })
+    }
+
+    /// `X-Signature` value for `body`
+    pub fn sign(&self, body: &[u8]) -> Result<String> {
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.key)?;
+        signer.update(body)?;
+        let mac = signer.sign_to_vec()?;
+        Ok(format!("sha256={}", mac.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()))
+    }
+}
+
+/// Whether a response is an API body worth signing; event streams and
+/// exports are streamed and stay unsigned
+fn is_signed_content(content_type: &[u8]) -> bool {
+    ["application/json", "application/msgpack", "application/cbor"]
+        .iter()
+        .any(|signed| content_type.starts_with(signed.as_bytes()))
+}
+
+/// Add `X-Signature` to API responses, computed over the exact bytes sent
+/// (after any MessagePack / CBOR encoding)
+pub async fn response_signing_middleware(
+    State(signer): State<ResponseSigner>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    let signed = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| is_signed_content(content_type.as_bytes()));
+    if !signed {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let signature = match to_bytes(body, usize::MAX).await {
+        Ok(body) => signer.sign(&body).map(|signature| (signature, body)),
+        Err(e) => Err(e.into()),
+    };
+    match signature {
+        Ok((signature, body)) => {
+            if let Ok(value) = HeaderValue::from_str(&signature) {
+                parts.headers.insert(SIGNATURE_HEADER, value);
+            }
+            Response::from_parts(parts, Body::from(body))
+        }
+        Err(e) => {
+            tracing::error!("Failed to sign response: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware::from_fn_with_state, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_json_responses_carry_body_signature() {
+        // RFC 4231 test case 2
+        let signer = ResponseSigner::new(b"Jefe").unwrap();
+        assert_eq!(
+            signer.sign(b"what do ya want for nothing?").unwrap(),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        let app = Router::new()
+            .route("/json", get(|| async { axum::Json(serde_json::json!({"address": "x"})) }))
+            .route("/text", get(|| async { "plain" }))
+            .layer(from_fn_with_state(signer.clone(), response_signing_middleware));
+        let get = |uri: &'static str| app.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap());
+
+        let response = get("/json").await.unwrap();
+        let signature = response.headers()[SIGNATURE_HEADER].to_str().unwrap().to_string();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(signature, signer.sign(&body).unwrap());
+
+        assert!(get("/text").await.unwrap().headers().get(SIGNATURE_HEADER).is_none());
+    }
+}
@@ -0,0 +1,47 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Mark responses of unversioned routes deprecated and link the versioned
+/// path clients should move to. Runs inside the nested router, so the
+/// request path no longer carries the unversioned prefix.
+pub async fn unversioned_path_middleware(
+    State(versioned_prefix): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let successor = format!("{}{}", versioned_prefix, request.uri().path());
+    let mut response = next.run(request).await;
+
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    if let Ok(link) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor)) {
+        headers.append(header::LINK, link);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware::from_fn_with_state, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_unversioned_routes_link_their_successor() {
+        let routes = Router::new().route("/pet/{pattern}/status", get(|| async { "ok" }));
+        let app = Router::new().nest(
+            "/api",
+            routes.layer(from_fn_with_state(Arc::<str>::from("/api/v1"), unversioned_path_middleware)),
+        );
+
+        let request = Request::builder().uri("/api/pet/Cat/status?x=1").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.headers()["deprecation"], "true");
+        assert_eq!(response.headers()[header::LINK], "</api/v1/pet/Cat/status>; rel=\"successor-version\"");
+    }
+}
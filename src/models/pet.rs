@@ -1,13 +1,196 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use utoipa::ToSchema;
 
+use crate::config::RateLimitConfig;
+use crate::pet::stats::GeneratorStats;
+use crate::pet::{Assignment, AuditEntry, AuditVerifyReport, DispensedRecord, Job, PetAddressInfo, SecretKey, Subscription, VerifyReport, Worker};
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct GetPetAddressResponse {
     pub id: u64,
     pub public_key: String,
-    pub private_key: String,
+    /// Omitted when the request sets `include_private_key=false`, when
+    /// redaction is on, or when it is sent as `encrypted_private_key`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub private_key: Option<SecretKey>,
+    /// Private key encrypted to the request's `X-Recipient-Key` or the tenant's registered key (base64)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypted_private_key: Option<String>,
     pub address: String,
     pub created_at: String,
+    /// Set when leases are enabled: acknowledge before this time or the address is requeued
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lease_expires_at: Option<String>,
+}
+
+impl GetPetAddressResponse {
+    /// Project a stored address into the response shape, optionally dropping the private key
+    pub fn from_info(info: PetAddressInfo, include_private_key: bool) -> Self {
+        Self {
+            id: info.id,
+            public_key: info.address.public_key,
+            private_key: include_private_key.then_some(info.address.private_key),
+            encrypted_private_key: None,
+            address: info.address.address,
+            created_at: info.created_at.to_rfc3339(),
+            lease_expires_at: None,
+        }
+    }
+
+    pub fn with_lease(mut self, expires_at: Option<chrono::DateTime<chrono::Utc>>) -> Self {
+        self.lease_expires_at = expires_at.map(|at| at.to_rfc3339());
+        self
+    }
+}
+
+/// Private key of an address dispensed in redacted form
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RevealResponse {
+    pub id: u64,
+    pub address: String,
+    /// Omitted when sent as `encrypted_private_key`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub private_key: Option<SecretKey>,
+    /// Private key encrypted to the request's `X-Recipient-Key` or the tenant's registered key (base64)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypted_private_key: Option<String>,
+}
+
+/// Longest nonce accepted for a proof of possession, in bytes
+pub const MAX_PROOF_NONCE: usize = 256;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ProofRequest {
+    /// Caller-chosen challenge, 1..=256 bytes; use a fresh random value per proof
+    #[schema(example = "c8f2d5e1-buyer-challenge")]
+    pub nonce: String,
+}
+
+/// Signature proving the server holds the private key of a queued address
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ProofResponse {
+    pub id: u64,
+    pub address: String,
+    pub nonce: String,
+    /// Exact UTF-8 message that was signed: `pinpet-proof:{address}:{nonce}`
+    pub message: String,
+    /// Ed25519 signature of `message` (base58); verify it with `address` as the public key
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RegisterRecipientRequest {
+    /// Base64 of a raw 32-byte X25519 public key, or of a DER X25519 or RSA (2048+ bits) public key
+    #[schema(example = "hSDwCYkwp1R0i33ctD73Wg2/Og0mOBr066SpjqqbTmo=")]
+    pub public_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RecipientKeyResponse {
+    pub tenant: String,
+    /// `x25519-hkdf-sha256-aes-256-gcm` or `rsa-oaep-sha256`
+    pub algorithm: String,
+    /// The registered key, normalized to raw X25519 or DER RSA, base64
+    pub public_key: String,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct AddressStreamQuery {
+    /// Only addresses stored in this pool: `default`, an extra suffix or `tenant:{name}` (default: all pools)
+    #[schema(example = "default")]
+    pub pool: Option<String>,
+}
+
+/// Message pushed over `/ws`, as JSON text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AddressStreamMessage {
+    /// The generator stored a new address (never the private key)
+    AddressFound {
+        pool: String,
+        id: u64,
+        public_key: String,
+        /// Addresses queued in the pool right after the store
+        queue_depth: usize,
+        target: usize,
+    },
+    /// The connection fell behind and missed this many generator events
+    Lagged { skipped: u64 },
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AcknowledgeResponse {
+    pub id: u64,
+    pub acknowledged: bool,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct PetAddressQuery {
+    /// Set to false to receive only the public address fields (default: true)
+    #[schema(example = false)]
+    pub include_private_key: Option<bool>,
+}
+
+impl PetAddressQuery {
+    pub fn include_private_key(&self) -> bool {
+        self.include_private_key.unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct LetterQuery {
+    /// Lowercase letter right before the suffix, e.g. "k" for "…kPet"
+    #[schema(example = "k")]
+    pub letter: String,
+}
+
+impl LetterQuery {
+    /// The requested letter, if it is a single lowercase letter that can appear in base58
+    pub fn letter(&self) -> Option<char> {
+        let mut chars = self.letter.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_lowercase() && crate::utils::is_base58(&self.letter) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+/// Upper bound on addresses returned by one peek
+pub const MAX_PEEK: usize = 100;
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct PeekQuery {
+    /// Addresses to show, 1..=100 (default: 1)
+    #[schema(example = 5)]
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PeekResponse {
+    /// Next addresses in pop order; private keys are never included
+    pub addresses: Vec<GetPetAddressResponse>,
+}
+
+/// Upper bound on addresses returned by one batch pop
+pub const MAX_BATCH_POP: usize = 1000;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BatchPopRequest {
+    /// Addresses to pop, 1..=1000; fewer are returned if the pool runs short
+    #[schema(example = 50)]
+    pub count: usize,
+    /// Set to false to receive only the public address fields (default: true)
+    pub include_private_key: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchPopResponse {
+    pub requested: usize,
+    pub returned: usize,
+    pub addresses: Vec<GetPetAddressResponse>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -15,4 +198,984 @@ pub struct PetGeneratorStatusResponse {
     pub total_addresses: usize,
     pub pool_size: usize,
     pub generation_active: bool,
-}
\ No newline at end of file
+    /// Hard limit on queued addresses; null when unlimited
+    pub max_queue_size: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct DifficultyEstimateQuery {
+    /// Regex to estimate; defaults to the configured pattern
+    #[schema(example = "^Pin.*Pet$")]
+    pub pattern: Option<String>,
+    /// Match `pattern` case-insensitively when false (default: true)
+    pub case_sensitive: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DifficultyEstimateResponse {
+    /// Pattern the estimate is for
+    pub pattern: String,
+    /// Chance that a single random key matches
+    pub probability: f64,
+    /// Keys to try on average per address
+    pub expected_attempts: f64,
+    /// Live grinder throughput, else the last stored benchmark; null when neither exists
+    pub keys_per_second: Option<f64>,
+    /// Expected seconds per address at `keys_per_second`
+    pub estimated_seconds: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ValidateAddressRequest {
+    /// Solana address to check
+    #[schema(example = "7EcDhSYGxXyscszYEp35KHN8vvw3svAuLKTzXwCFaPet")]
+    pub address: String,
+    /// Regex the address should match; defaults to `pool`'s pattern
+    #[schema(example = "[a-z]Pet$")]
+    pub pattern: Option<String>,
+    /// Match `pattern` case-insensitively when false (default: true)
+    pub case_sensitive: Option<bool>,
+    /// Pattern pool, or `tenant:{name}`, whose pattern to check when no `pattern` is given (default: `default`)
+    #[schema(example = "default")]
+    pub pool: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ValidateAddressResponse {
+    pub address: String,
+    /// Pattern the address was checked against
+    pub pattern: String,
+    /// Base58 that decodes to a 32-byte public key
+    pub valid_address: bool,
+    /// The key is an ed25519 curve point, so a private key for it can exist.
+    /// Program-derived addresses are off the curve.
+    pub on_curve: bool,
+    /// The address matches `pattern`; always false for an invalid address
+    pub matches: bool,
+    /// Why the address is invalid
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct ThrottleRequest {
+    /// Cap on worker threads per address; omit or null to remove the cap
+    #[schema(example = 2)]
+    pub max_threads: Option<usize>,
+    /// Cap on combined keys/sec; omit or null to remove the cap
+    #[schema(example = 20000)]
+    pub max_keys_per_second: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GeneratorControlResponse {
+    pub paused: bool,
+    /// True while a batch is actually being ground
+    pub generation_active: bool,
+    /// Configured worker threads per address
+    pub threads: usize,
+    /// Threads used after the admin cap
+    pub effective_threads: usize,
+    pub max_threads: Option<usize>,
+    pub max_keys_per_second: Option<u64>,
+}
+
+/// Throughput of one grinder thread slot
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ThreadStatsResponse {
+    pub thread: usize,
+    pub keys: u64,
+    pub keys_per_second: Option<f64>,
+}
+
+/// Finds at or under `le_seconds` among the recent ones
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TimeToFindBucket {
+    pub le_seconds: f64,
+    pub count: usize,
+}
+
+/// Time to find each of the most recent addresses (up to 1000)
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TimeToFindResponse {
+    pub samples: usize,
+    pub mean_seconds: Option<f64>,
+    pub p50_seconds: Option<f64>,
+    pub p90_seconds: Option<f64>,
+    pub p99_seconds: Option<f64>,
+    /// Cumulative, like a Prometheus histogram
+    pub buckets: Vec<TimeToFindBucket>,
+}
+
+/// Depth and age of one pool's queue
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct QueueAgeResponse {
+    pub pool: String,
+    pub depth: usize,
+    /// Seconds since the oldest queued address was created; a growing value
+    /// means nothing is being dispensed from this pool
+    pub oldest_age_seconds: Option<f64>,
+    /// Seconds since the newest queued address was created; a growing value
+    /// while the pool is below target means generation has stalled
+    pub newest_age_seconds: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GeneratorStatsResponse {
+    /// Addresses found since startup
+    pub addresses_found: u64,
+    /// Keys tried since startup, including searches that found nothing
+    pub keys_tried: u64,
+    /// Mean keys tried per address found
+    pub attempts_per_address: Option<f64>,
+    /// Measured throughput across all threads
+    pub keys_per_second: Option<f64>,
+    /// Worker threads per address, after the admin cap
+    pub effective_threads: usize,
+    pub threads: Vec<ThreadStatsResponse>,
+    pub time_to_find: TimeToFindResponse,
+    /// Empty queues, and backends that cannot tell, have no ages
+    pub queues: Vec<QueueAgeResponse>,
+}
+
+impl GeneratorStatsResponse {
+    pub fn new(stats: GeneratorStats, effective_threads: usize, queues: Vec<QueueAgeResponse>) -> Self {
+        let time_to_find = stats.time_to_find;
+        Self {
+            addresses_found: stats.addresses_found,
+            keys_tried: stats.keys_tried,
+            attempts_per_address: stats.attempts_per_address,
+            keys_per_second: stats.keys_per_second,
+            effective_threads,
+            threads: stats
+                .workers
+                .iter()
+                .enumerate()
+                .map(|(thread, worker)| ThreadStatsResponse {
+                    thread,
+                    keys: worker.keys,
+                    keys_per_second: worker.keys_per_second(),
+                })
+                .collect(),
+            time_to_find: TimeToFindResponse {
+                samples: time_to_find.samples,
+                mean_seconds: time_to_find.mean_seconds,
+                p50_seconds: time_to_find.p50_seconds,
+                p90_seconds: time_to_find.p90_seconds,
+                p99_seconds: time_to_find.p99_seconds,
+                buckets: time_to_find
+                    .buckets
+                    .into_iter()
+                    .map(|(le_seconds, count)| TimeToFindBucket { le_seconds, count })
+                    .collect(),
+            },
+            queues,
+        }
+    }
+}
+
+/// Queued addresses ending with one letter before the suffix
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LetterCount {
+    pub letter: String,
+    /// Letter and suffix, e.g. `kPet`
+    pub variant: String,
+    pub count: usize,
+    /// Fraction of the pool's queue
+    pub share: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PoolSuffixStats {
+    pub pool: String,
+    pub suffix: String,
+    /// Queued addresses
+    pub total: usize,
+    /// Every lowercase letter (none for `l`, which base58 lacks), plus any other
+    /// character that occurs, alphabetically
+    pub letters: Vec<LetterCount>,
+    /// Letters with the fewest queued addresses
+    pub scarcest: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SuffixStatsResponse {
+    /// Pools with a literal suffix; regex pools have no ending letter
+    pub pools: Vec<PoolSuffixStats>,
+    /// Shard peers that did not answer; their letters are missing from the counts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unreachable_shards: Vec<String>,
+}
+
+impl PoolSuffixStats {
+    pub fn new(pool: &str, suffix: &str, mut counts: BTreeMap<char, usize>) -> Self {
+        // Base58 has no lowercase 'l'
+        for letter in ('a'..='z').filter(|letter| *letter != 'l') {
+            counts.entry(letter).or_default();
+        }
+        let total: usize = counts.values().sum();
+        let fewest = counts.values().copied().min().unwrap_or_default();
+        Self {
+            pool: pool.to_string(),
+            suffix: suffix.to_string(),
+            total,
+            scarcest: counts
+                .iter()
+                .filter(|(_, count)| **count == fewest)
+                .map(|(letter, _)| letter.to_string())
+                .collect(),
+            letters: counts
+                .into_iter()
+                .map(|(letter, count)| LetterCount {
+                    letter: letter.to_string(),
+                    variant: format!("{}{}", letter, suffix),
+                    count,
+                    share: if total == 0 { 0.0 } else { count as f64 / total as f64 },
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Rate limits per client, as in `[rate_limit]`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RateLimitSettings {
+    pub enabled: bool,
+    #[schema(example = 60)]
+    pub pop_per_minute: u32,
+    #[schema(example = 10)]
+    pub pop_burst: u32,
+    #[schema(example = 600)]
+    pub read_per_minute: u32,
+    #[schema(example = 100)]
+    pub read_burst: u32,
+}
+
+impl From<RateLimitConfig> for RateLimitSettings {
+    fn from(config: RateLimitConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            pop_per_minute: config.pop_per_minute,
+            pop_burst: config.pop_burst,
+            read_per_minute: config.read_per_minute,
+            read_burst: config.read_burst,
+        }
+    }
+}
+
+/// Rate limit changes; omitted fields keep their current value
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct RateLimitPatch {
+    pub enabled: Option<bool>,
+    pub pop_per_minute: Option<u32>,
+    pub pop_burst: Option<u32>,
+    pub read_per_minute: Option<u32>,
+    pub read_burst: Option<u32>,
+}
+
+impl RateLimitPatch {
+    /// `current` with the set fields replaced
+    pub fn apply(&self, current: RateLimitConfig) -> RateLimitConfig {
+        RateLimitConfig {
+            enabled: self.enabled.unwrap_or(current.enabled),
+            pop_per_minute: self.pop_per_minute.unwrap_or(current.pop_per_minute),
+            pop_burst: self.pop_burst.unwrap_or(current.pop_burst),
+            read_per_minute: self.read_per_minute.unwrap_or(current.read_per_minute),
+            read_burst: self.read_burst.unwrap_or(current.read_burst),
+        }
+    }
+}
+
+/// Settings to change at runtime; omitted fields are left alone
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct RuntimeConfigPatch {
+    /// Target queue depth of every pool
+    #[schema(example = 2000)]
+    pub pool_size: Option<usize>,
+    /// The complete set of extra suffix pools: missing ones are opened,
+    /// unlisted ones stop being served (their addresses stay stored)
+    #[schema(example = json!(["Cat", "Dog"]))]
+    pub extra_suffixes: Option<Vec<String>>,
+    /// Grinder worker threads per address; 0 uses every core
+    #[schema(example = 4)]
+    pub threads: Option<usize>,
+    pub rate_limit: Option<RateLimitPatch>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RuntimeConfigResponse {
+    pub pool_size: usize,
+    pub extra_suffixes: Vec<String>,
+    /// Worker threads per address, with 0 resolved to the core count
+    pub threads: usize,
+    pub rate_limit: RateLimitSettings,
+    /// Settings whose runtime value is persisted and overrides the config file
+    #[schema(example = json!(["pool_size"]))]
+    pub overridden: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateJobRequest {
+    /// Regex the address must match, e.g. "^Pin" or "[a-z]Cafe$"
+    #[schema(example = "^Pin")]
+    pub pattern: String,
+    /// Set to false to match case-insensitively (default: true)
+    pub case_sensitive: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateJobResponse {
+    /// Poll `GET /api/v1/jobs/{id}` with this ID
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateSubscriptionRequest {
+    /// `http://` or `https://` URL the addresses are POSTed to
+    #[schema(example = "https://consumer.example.com/pet-addresses")]
+    pub callback_url: String,
+    /// Addresses to deliver in total
+    #[schema(example = 100)]
+    pub count: usize,
+    /// Pattern pool name, or `tenant:{name}` for a tenant pool (default: `default`)
+    #[schema(example = "default")]
+    pub pool: Option<String>,
+    /// X25519 or RSA public key (base64) to encrypt private keys to
+    pub recipient_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SubscriptionResponse {
+    pub id: String,
+    pub pool: String,
+    pub callback_url: String,
+    pub count: usize,
+    pub delivered: usize,
+    /// active, completed, failed or cancelled
+    #[schema(example = "active")]
+    pub status: String,
+    /// Key of the `X-Signature` HMAC; only returned when the subscription is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub last_error: Option<String>,
+}
+
+impl SubscriptionResponse {
+    /// `subscription` without its secret
+    pub fn new(subscription: Subscription) -> Self {
+        Self {
+            id: subscription.id,
+            pool: subscription.pool,
+            callback_url: subscription.callback_url,
+            count: subscription.count,
+            delivered: subscription.delivered,
+            status: subscription.status.as_str().to_string(),
+            secret: None,
+            created_at: subscription.created_at.to_rfc3339(),
+            updated_at: subscription.updated_at.to_rfc3339(),
+            last_error: subscription.last_error,
+        }
+    }
+
+    /// `subscription` including its secret, for the consumer that created it
+    pub fn with_secret(subscription: Subscription) -> Self {
+        let secret = subscription.secret.clone();
+        Self {
+            secret: Some(secret),
+            ..Self::new(subscription)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct JobAddressResponse {
+    pub public_key: String,
+    #[schema(value_type = String)]
+    pub private_key: SecretKey,
+    pub address: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct JobResponse {
+    pub id: String,
+    pub pattern: String,
+    /// queued, running, completed or failed
+    #[schema(example = "running")]
+    pub status: String,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+    /// Present once the job has completed
+    pub result: Option<JobAddressResponse>,
+    pub error: Option<String>,
+}
+
+impl From<Job> for JobResponse {
+    fn from(job: Job) -> Self {
+        Self {
+            id: job.id,
+            pattern: job.pattern,
+            status: job.status.as_str().to_string(),
+            created_at: job.created_at.to_rfc3339(),
+            finished_at: job.finished_at.map(|at| at.to_rfc3339()),
+            result: job.result.map(|address| JobAddressResponse {
+                public_key: address.public_key,
+                private_key: address.private_key,
+                address: address.address,
+            }),
+            error: job.error,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct QueueDiagnosticsResponse {
+    /// Value of the atomic queue size counter
+    pub reported_size: usize,
+    /// Authoritative number of queued entries, in memory and spilled
+    pub actual_queue_len: usize,
+    /// Queued addresses held only in sled beyond `max_in_memory`
+    pub spilled: usize,
+    /// Number of address records persisted in sled
+    pub db_record_count: usize,
+    /// Next address ID
+    pub counter: u64,
+    /// reported_size - actual_queue_len
+    pub size_delta: i64,
+    /// Checked-out addresses awaiting acknowledgement
+    pub leased: usize,
+    /// db_record_count - actual_queue_len - leased
+    pub db_delta: i64,
+    /// True when all three counts agree
+    pub consistent: bool,
+    /// DB writes queued for the background writer but not yet applied
+    pub pending_writes: usize,
+    /// Milliseconds the last applied write batch waited in the queue
+    pub persist_lag_ms: u64,
+    /// DB writes that failed every retry since startup
+    pub failed_writes: u64,
+    /// DB writes of the batch waiting to be retried
+    pub retrying_writes: usize,
+    /// Failed writes kept in the `persist_dead_letters` tree
+    pub dead_letters: u64,
+    /// Stores and pops refused with 503 since startup because the writer was
+    /// behind or degraded mode refused stores
+    pub refused_operations: u64,
+    /// Sled keeps failing; queued addresses may not be on disk
+    pub degraded: bool,
+    /// Failed write batches and flushes since the last success
+    pub consecutive_write_failures: u32,
+    /// Addresses pruned by the expiry sweeper since startup
+    pub expired: u64,
+}
+
+/// Default and upper bound on archive entries returned by one query
+pub const DEFAULT_DISPENSED_LIMIT: usize = 100;
+pub const MAX_DISPENSED_LIMIT: usize = 1000;
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct DispensedQuery {
+    /// Pattern pool name (default: `default`)
+    #[schema(example = "default")]
+    pub pool: Option<String>,
+    /// Only entries requested from this client IP
+    #[schema(example = "203.0.113.7")]
+    pub requester: Option<String>,
+    /// Only entries dispensed at or after this RFC 3339 time
+    #[schema(example = "2026-01-01T00:00:00Z")]
+    pub since: Option<String>,
+    /// Entries to return, 1..=1000 (default: 100)
+    #[schema(example = 100)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DispensedRecordResponse {
+    pub id: u64,
+    pub pool: String,
+    pub public_key: String,
+    pub address: String,
+    pub created_at: String,
+    pub dispensed_at: String,
+    /// Client IP of the request that received the address
+    pub requester: Option<String>,
+    pub user_agent: Option<String>,
+    pub lease_expires_at: Option<String>,
+    pub acknowledged_at: Option<String>,
+}
+
+impl From<DispensedRecord> for DispensedRecordResponse {
+    fn from(record: DispensedRecord) -> Self {
+        Self {
+            id: record.id,
+            pool: record.pool,
+            public_key: record.public_key,
+            address: record.address,
+            created_at: record.created_at.to_rfc3339(),
+            dispensed_at: record.dispensed_at.to_rfc3339(),
+            requester: record.requester,
+            user_agent: record.user_agent,
+            lease_expires_at: record.lease_expires_at.map(|at| at.to_rfc3339()),
+            acknowledged_at: record.acknowledged_at.map(|at| at.to_rfc3339()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DispensedListResponse {
+    pub pool: String,
+    /// Newest address ID first
+    pub records: Vec<DispensedRecordResponse>,
+}
+
+/// Default and upper bound on audit entries returned by one query
+pub const DEFAULT_AUDIT_LIMIT: usize = 100;
+pub const MAX_AUDIT_LIMIT: usize = 1000;
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct AuditQuery {
+    /// Only entries with a lower sequence number, to page back through the log
+    #[schema(example = 1200)]
+    pub before: Option<u64>,
+    /// Entries to return, 1..=1000 (default: 100)
+    #[schema(example = 100)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntryResponse {
+    pub seq: u64,
+    pub at: String,
+    /// `store`, `pop`, `ack`, `export` or `admin`
+    #[schema(example = "pop")]
+    pub action: String,
+    /// Role, IP and client certificate of the caller, or `generator` / `system`
+    #[schema(example = "tenant:staging@203.0.113.7")]
+    pub actor: String,
+    pub pool: Option<String>,
+    /// Public key, address ID or admin request the action applied to
+    pub subject: Option<String>,
+    pub detail: Option<String>,
+    pub prev_hash: String,
+    /// SHA-256 of the entry, `prev_hash` included
+    pub hash: String,
+}
+
+impl From<AuditEntry> for AuditEntryResponse {
+    fn from(entry: AuditEntry) -> Self {
+        Self {
+            seq: entry.seq,
+            at: entry.at.to_rfc3339(),
+            action: entry.action.as_str().to_string(),
+            actor: entry.actor,
+            pool: entry.pool,
+            subject: entry.subject,
+            detail: entry.detail,
+            prev_hash: entry.prev_hash,
+            hash: entry.hash,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuditListResponse {
+    /// Newest first
+    pub entries: Vec<AuditEntryResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuditVerifyResponse {
+    /// Every entry follows from the one before it
+    pub valid: bool,
+    /// Entries checked before the first problem
+    pub verified: u64,
+    /// Hash of the newest entry. The chain cannot show entries cut off its
+    /// end, so compare this with a copy kept elsewhere.
+    pub head_hash: Option<String>,
+    /// Sequence number of the first entry that breaks the chain
+    pub broken_at: Option<u64>,
+    pub problem: Option<String>,
+}
+
+impl From<AuditVerifyReport> for AuditVerifyResponse {
+    fn from(report: AuditVerifyReport) -> Self {
+        Self {
+            valid: report.is_valid(),
+            verified: report.verified,
+            head_hash: report.head_hash,
+            broken_at: report.broken_at,
+            problem: report.problem,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct UsageQuery {
+    /// Only this tenant (default: every tenant)
+    #[schema(example = "staging")]
+    pub tenant: Option<String>,
+    /// Billing month as `YYYY-MM`, UTC (default: the current month)
+    #[schema(example = "2026-10")]
+    pub month: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DailyUsageResponse {
+    /// UTC date, `YYYY-MM-DD`
+    pub date: String,
+    pub dispensed: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TenantUsageResponse {
+    pub tenant: String,
+    /// Addresses dispensed in the requested month
+    pub dispensed: u64,
+    pub monthly_quota: Option<u64>,
+    /// Addresses dispensed so far today (UTC), whatever month was requested
+    pub dispensed_today: u64,
+    pub daily_quota: Option<u64>,
+    /// Days of the month with any usage, oldest first
+    pub days: Vec<DailyUsageResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UsageResponse {
+    pub month: String,
+    pub tenants: Vec<TenantUsageResponse>,
+}
+
+/// Largest import body accepted (about 250k addresses)
+pub const MAX_IMPORT_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct BackupQuery {
+    /// Pattern pool name, or `tenant:{name}` (default: `default`)
+    #[schema(example = "default")]
+    pub pool: Option<String>,
+    /// `jsonl` (default), `csv` or `keypair`
+    #[schema(example = "jsonl")]
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImportResponse {
+    pub pool: String,
+    /// Addresses added to the queue
+    pub imported: usize,
+    /// Addresses skipped because their public key was already enqueued once
+    pub duplicates: usize,
+}
+
+/// Keypairs found by a remote grind worker
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WorkerSubmitRequest {
+    /// Pattern pool name, or `tenant:{name}` (default: `default`)
+    #[schema(example = "default")]
+    pub pool: Option<String>,
+    /// Worker ID from `POST /workers/register`, so the keypairs count
+    /// towards it in `/admin/workers`; any other value is only logged
+    #[schema(example = "9f2c4e1ab07d3356")]
+    pub worker: Option<String>,
+    pub keypairs: Vec<SubmittedKeypair>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct SubmittedKeypair {
+    /// Address (base58 public key)
+    #[schema(example = "AGm9DpEaQYHxLKy98WGGoqErJEML9Pf5HySA1o4skPet")]
+    pub public_key: String,
+    /// 64-byte secret key in base58, as dispensed by this server
+    #[schema(value_type = String)]
+    pub private_key: SecretKey,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WorkerSubmitResponse {
+    pub pool: String,
+    /// Keypairs added to the queue
+    pub accepted: usize,
+    /// Keypairs whose public key was already enqueued once
+    pub duplicates: usize,
+    /// Keypairs refused, with the reason; the worker may keep or resubmit them
+    pub rejected: Vec<RejectedKeypair>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RejectedKeypair {
+    pub public_key: String,
+    #[schema(example = "does not match [a-z]Pet")]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WorkerRegisterRequest {
+    /// Shown in logs and `/admin/workers`
+    #[schema(example = "spot-eu-1")]
+    pub name: String,
+    /// Grinder threads the worker runs
+    #[schema(example = 16)]
+    pub threads: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct WorkerHeartbeatRequest {
+    /// Current throughput
+    #[schema(example = 250000.0)]
+    pub keys_per_second: Option<f64>,
+    /// Keys tried since the worker started
+    pub keys_tried: Option<u64>,
+}
+
+/// What a worker should do until its next heartbeat
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WorkerInstructions {
+    pub worker_id: String,
+    /// Send `POST /workers/{id}/heartbeat` this often
+    pub heartbeat_interval_seconds: u64,
+    /// Pool to grind for; absent when every pool is full
+    pub assignment: Option<WorkerAssignmentResponse>,
+    pub throttle: ThrottleHint,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WorkerAssignmentResponse {
+    /// Submit found keypairs with this pool name
+    pub pool: String,
+    #[schema(example = "[a-z]Pet")]
+    pub pattern: String,
+    /// The same pattern as a regex
+    #[schema(example = "[a-z]Pet$")]
+    pub regex: String,
+    /// Addresses the pool is short of its target
+    pub needed: usize,
+}
+
+impl From<Assignment> for WorkerAssignmentResponse {
+    fn from(assignment: Assignment) -> Self {
+        Self {
+            pool: assignment.pool,
+            pattern: assignment.pattern,
+            regex: assignment.regex,
+            needed: assignment.needed,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ThrottleHint {
+    /// Stop grinding until a later heartbeat says otherwise: every pool is
+    /// full or an operator paused generation
+    pub pause: bool,
+    /// Keys/sec the worker should stay under; absent = no limit
+    pub max_keys_per_second: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WorkerListResponse {
+    /// Workers heard from within `[workers].timeout_seconds`
+    pub alive: usize,
+    /// Sum of the throughput reported by live workers
+    pub total_keys_per_second: f64,
+    pub workers: Vec<WorkerStatusResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WorkerStatusResponse {
+    pub id: String,
+    pub name: String,
+    pub threads: Option<usize>,
+    pub alive: bool,
+    pub registered_at: String,
+    /// Last heartbeat or submission
+    pub last_seen: String,
+    pub keys_per_second: f64,
+    pub keys_tried: u64,
+    /// Keypairs queued from this worker
+    pub accepted: u64,
+    pub duplicates: u64,
+    pub rejected: u64,
+    /// Pool the worker grinds for
+    pub pool: Option<String>,
+}
+
+impl WorkerStatusResponse {
+    pub fn new(worker: Worker, alive: bool) -> Self {
+        Self {
+            id: worker.id,
+            name: worker.name,
+            threads: worker.threads,
+            alive,
+            registered_at: worker.registered_at.to_rfc3339(),
+            last_seen: worker.last_seen.to_rfc3339(),
+            keys_per_second: worker.keys_per_second,
+            keys_tried: worker.keys_tried,
+            accepted: worker.accepted,
+            duplicates: worker.duplicates,
+            rejected: worker.rejected,
+            pool: worker.assignment.map(|assignment| assignment.pool),
+        }
+    }
+}
+
+/// Address ground by a replica, stored in the primary's pool
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReplicaStoreRequest {
+    pub public_key: String,
+    #[schema(value_type = String)]
+    pub private_key: SecretKey,
+    pub address: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReplicaStoreResponse {
+    /// Queue ID on the primary
+    pub id: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReplicaPopRequest {
+    /// Addresses to pop, 1..=1000; fewer are returned if the pool runs short
+    pub count: usize,
+}
+
+/// A popped address with its private key, as the replica hands it out
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReplicaAddress {
+    pub id: u64,
+    pub public_key: String,
+    #[schema(value_type = String)]
+    pub private_key: SecretKey,
+    pub address: String,
+    pub created_at: String,
+}
+
+impl From<PetAddressInfo> for ReplicaAddress {
+    fn from(info: PetAddressInfo) -> Self {
+        Self {
+            id: info.id,
+            public_key: info.address.public_key,
+            private_key: info.address.private_key,
+            address: info.address.address,
+            created_at: info.created_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReplicaPoolStatus {
+    /// Addresses queued on the primary
+    pub count: usize,
+    /// `max_queue_size` of the primary; absent when unlimited
+    pub capacity: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct KeyFileQuery {
+    /// `json` (default, Solana CLI keyfile), `base58` or `hex`
+    #[schema(example = "json")]
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct KeyCheckQuery {
+    /// Pattern pool name, or `tenant:{name}` (default: `default`)
+    #[schema(example = "default")]
+    pub pool: Option<String>,
+}
+
+/// Result of checking every stored key of a pool
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct KeyCheckResponse {
+    pub pool: String,
+    /// Stored records examined
+    pub checked: usize,
+    /// Keys of records that cannot be decoded or decrypted
+    pub corrupt: Vec<String>,
+    /// Keys of records whose private key does not derive their public key or address
+    pub mismatched: Vec<String>,
+    /// Addresses that do not match the pool's pattern
+    pub off_pattern: Vec<String>,
+    /// True when every record holds a usable key for the pool's pattern
+    pub clean: bool,
+}
+
+impl KeyCheckResponse {
+    pub fn new(pool: String, report: VerifyReport) -> Self {
+        Self {
+            pool,
+            checked: report.checked,
+            clean: report.unusable() == 0 && report.off_pattern.is_empty(),
+            corrupt: report.corrupt,
+            mismatched: report.mismatched,
+            off_pattern: report.off_pattern,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::PetAddress;
+
+    fn test_info() -> PetAddressInfo {
+        PetAddressInfo {
+            id: 7,
+            address: PetAddress {
+                public_key: "TestAddressaPet".to_string(),
+                private_key: "secret".to_string().into(),
+                address: "TestAddressaPet".to_string(),
+            },
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_private_key_present_by_default() {
+        let query = PetAddressQuery::default();
+        let response = GetPetAddressResponse::from_info(test_info(), query.include_private_key());
+        let json = serde_json::to_value(&response).unwrap();
+
+        assert_eq!(json["private_key"], "secret");
+        assert_eq!(json["address"], "TestAddressaPet");
+    }
+
+    #[test]
+    fn test_address_stream_message_is_tagged() {
+        let message = AddressStreamMessage::AddressFound {
+            pool: "default".to_string(),
+            id: 3,
+            public_key: "TestAddressaPet".to_string(),
+            queue_depth: 9,
+            target: 10,
+        };
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["type"], "address_found");
+        assert_eq!(json["queue_depth"], 9);
+        assert!(json.get("private_key").is_none());
+
+        let lagged = serde_json::to_string(&AddressStreamMessage::Lagged { skipped: 4 }).unwrap();
+        assert_eq!(lagged, r#"{"type":"lagged","skipped":4}"#);
+    }
+
+    #[test]
+    fn test_letter_query_validation() {
+        let letter = |s: &str| LetterQuery { letter: s.to_string() }.letter();
+        assert_eq!(letter("k"), Some('k'));
+        assert_eq!(letter("l"), None); // not base58
+        assert_eq!(letter("K"), None);
+        assert_eq!(letter("kk"), None);
+        assert_eq!(letter(""), None);
+    }
+
+    #[test]
+    fn test_private_key_absent_when_excluded() {
+        let query = PetAddressQuery { include_private_key: Some(false) };
+        let response = GetPetAddressResponse::from_info(test_info(), query.include_private_key());
+        let json = serde_json::to_value(&response).unwrap();
+
+        assert!(json.get("private_key").is_none());
+        assert_eq!(json["public_key"], "TestAddressaPet");
+        assert_eq!(json["id"], 7);
+    }
+}
@@ -25,15 +25,6 @@ impl<T> ApiResponse<T> {
             timestamp: chrono::Utc::now().timestamp(),
         }
     }
-
-    pub fn error(code: i32, message: impl Into<String>) -> ApiResponse<()> {
-        ApiResponse {
-            code,
-            message: message.into(),
-            data: None,
-            timestamp: chrono::Utc::now().timestamp(),
-        }
-    }
 }
 
 #[derive(Serialize, ToSchema)]
@@ -47,4 +38,23 @@ pub struct HealthResponse {
     /// Service uptime
     #[schema(example = "2024-01-15T10:30:00Z")]
     pub uptime: String,
-}
\ No newline at end of file
+}
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    /// Whether the replica should receive traffic
+    #[schema(example = true)]
+    pub ready: bool,
+    /// Addresses queued in the default pool; absent if storage did not answer
+    #[schema(example = 42)]
+    pub queue_depth: Option<usize>,
+    /// Queue depth required to be ready
+    #[schema(example = 1)]
+    pub min_queue_depth: usize,
+    /// Why the replica is not ready
+    #[schema(example = "Queue depth 0 is below 1")]
+    pub reason: Option<String>,
+    /// Sled keeps failing: queued addresses still pop, but new ones may not
+    /// be durable (or are refused with `[circuit_breaker].refuse_stores`)
+    #[schema(example = false)]
+    pub degraded: bool,
+}
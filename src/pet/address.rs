@@ -1,10 +1,347 @@
+use anyhow::{anyhow, Result};
+use rand::RngCore;
+use regex::Regex;
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+use regex_syntax::ParserBuilder;
 use serde::{Deserialize, Serialize};
 use solana_sdk::signature::{Keypair, Signer};
+use std::borrow::Borrow;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use zeroize::Zeroizing;
+
+use super::secret::SecretKey;
+use super::sharding::ShardLetters;
+use crate::config::PetGeneratorConfig;
+use crate::utils::{is_feasible_base58_char, validate_target_suffix, BASE58_ALPHABET};
+
+/// Suffix used when none is configured
+pub const DEFAULT_TARGET_SUFFIX: &str = "Pet";
+
+/// Start of every proof-of-possession message, so a proof signature can never
+/// double as a transaction signature
+pub const PROOF_MESSAGE_PREFIX: &str = "pinpet-proof";
+
+/// Limit attempts per address to avoid infinite loops.
+/// Statistically need ~435,000 attempts on average for [a-z]Pet suffix
+pub(crate) const MAX_ATTEMPTS: usize = 10_000_000;
+
+/// Longest address tail `base58_tail` can compute: 58^9 < 2^56, so shifting the
+/// running remainder left by a byte never overflows a u64
+const MAX_TAIL_DIGITS: usize = 9;
+
+/// Last `len` base58 characters of a 32-byte public key without encoding the
+/// whole key. The tail of a base58 string is the key modulo 58^len, so this
+/// costs 32 u64 divisions instead of a full big-number conversion.
+fn base58_tail<'a>(bytes: &[u8; 32], len: usize, buf: &'a mut [u8; MAX_TAIL_DIGITS]) -> &'a str {
+    debug_assert!(len <= MAX_TAIL_DIGITS);
+    let modulus = 58u64.pow(len as u32);
+
+    let mut rem = 0u64;
+    for byte in bytes {
+        rem = ((rem << 8) | u64::from(*byte)) % modulus;
+    }
+
+    let alphabet = BASE58_ALPHABET.as_bytes();
+    for slot in buf[..len].iter_mut().rev() {
+        *slot = alphabet[(rem % 58) as usize];
+        rem /= 58;
+    }
+
+    // Alphabet is ASCII
+    std::str::from_utf8(&buf[..len]).unwrap_or_default()
+}
+
+/// Literal vanity constraints on both ends of the address, e.g. starts with "Pin" and ends with "Pet"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatternSpec {
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub suffix: String,
+    #[serde(default = "default_case_sensitive")]
+    pub case_sensitive: bool,
+}
+
+fn default_case_sensitive() -> bool {
+    true
+}
+
+impl PatternSpec {
+    pub fn new(prefix: impl Into<String>, suffix: impl Into<String>, case_sensitive: bool) -> Self {
+        Self {
+            prefix: prefix.into(),
+            suffix: suffix.into(),
+            case_sensitive,
+        }
+    }
+
+    /// Reject empty specs and literals that can never appear in a base58 address
+    pub fn validate(&self) -> Result<()> {
+        if self.prefix.is_empty() && self.suffix.is_empty() {
+            return Err(anyhow!("Pattern spec needs a prefix, a suffix or both"));
+        }
+
+        for (name, literal) in [("prefix", &self.prefix), ("suffix", &self.suffix)] {
+            let impossible = literal
+                .chars()
+                .find(|c| !is_feasible_base58_char(*c, self.case_sensitive));
+            if let Some(c) = impossible {
+                return Err(anyhow!(
+                    "Pattern {} '{}' contains '{}' which never appears in base58 addresses",
+                    name, literal, c
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_match(&self, address: &str) -> bool {
+        if address.len() < self.prefix.len() + self.suffix.len() {
+            return false;
+        }
+
+        let head = &address.as_bytes()[..self.prefix.len()];
+        let tail = &address.as_bytes()[address.len() - self.suffix.len()..];
+
+        if self.case_sensitive {
+            head == self.prefix.as_bytes() && tail == self.suffix.as_bytes()
+        } else {
+            head.eq_ignore_ascii_case(self.prefix.as_bytes())
+                && tail.eq_ignore_ascii_case(self.suffix.as_bytes())
+        }
+    }
+}
+
+/// Decides whether a candidate address is a hit. Built once and shared by the grind loop.
+#[derive(Debug, Clone)]
+pub enum AddressMatcher {
+    /// Lowercase letter followed by a fixed suffix (the default `[a-z]Pet` rule).
+    /// Case-insensitively "aPet", "apet" and "aPET" all match.
+    Suffix { suffix: String, case_sensitive: bool },
+    /// Literal prefix and/or suffix
+    Spec(PatternSpec),
+    /// Arbitrary regex, e.g. `^Pin`, `[a-z]Pet$` or `Cafe`
+    Regex(Regex),
+    /// A suffix matcher limited to the letters of this node's shard, e.g. `[a-m]Pet`
+    Sharded { inner: Box<AddressMatcher>, letters: ShardLetters },
+}
+
+impl AddressMatcher {
+    /// Suffix matcher; fails if the suffix can never appear in a base58 address
+    pub fn suffix(suffix: &str, case_sensitive: bool) -> Result<Self> {
+        validate_target_suffix(suffix, case_sensitive).map_err(|e| anyhow!(e))?;
+        Ok(Self::Suffix {
+            suffix: suffix.to_string(),
+            case_sensitive,
+        })
+    }
+
+    /// Compile a regex matcher and reject patterns that can never match a base58 address
+    pub fn regex(pattern: &str, case_sensitive: bool) -> Result<Self> {
+        // Spell case-insensitivity into the source so `as_str()` (logs, difficulty estimates) reflects it
+        let source = if case_sensitive {
+            pattern.to_string()
+        } else {
+            format!("(?i){}", pattern)
+        };
+        let regex = Regex::new(&source)
+            .map_err(|e| anyhow!("Invalid address pattern '{}': {}", pattern, e))?;
+        Self::check_regex_feasible(pattern, case_sensitive)?;
+        Ok(Self::Regex(regex))
+    }
+
+    /// Validated prefix/suffix matcher
+    pub fn spec(spec: PatternSpec) -> Result<Self> {
+        spec.validate()?;
+        Ok(Self::Spec(spec))
+    }
+
+    /// Regex `pattern` when configured, then the `vanity` spec, otherwise `[a-z]` + `target_suffix`.
+    /// `case_sensitive` applies to the regex and suffix; the vanity spec carries its own flag.
+    pub fn from_config(config: &PetGeneratorConfig) -> Result<Self> {
+        if let Some(pattern) = config.pattern.as_deref().filter(|p| !p.is_empty()) {
+            return Self::regex(pattern, config.case_sensitive);
+        }
+
+        match &config.vanity {
+            Some(spec) => Self::spec(spec.clone()),
+            None => Self::suffix(&config.target_suffix, config.case_sensitive),
+        }
+    }
+
+    /// Limit a suffix matcher to the letters `letters` before the suffix.
+    /// Other matchers have no guaranteed letter there and are returned as they are.
+    pub fn in_shard(self, letters: &ShardLetters) -> Self {
+        match self {
+            Self::Suffix { .. } => Self::Sharded {
+                inner: Box::new(self),
+                letters: letters.clone(),
+            },
+            Self::Sharded { inner, .. } => inner.in_shard(letters),
+            other => other,
+        }
+    }
+
+    pub fn is_match(&self, address: &str) -> bool {
+        match self {
+            Self::Suffix { suffix, case_sensitive } => {
+                PetAddress::matches_target_suffix(address, suffix, *case_sensitive)
+            }
+            Self::Spec(spec) => spec.is_match(address),
+            Self::Regex(regex) => regex.is_match(address),
+            Self::Sharded { inner, letters } => {
+                inner.is_match(address) && self.ending_letter(address).is_some_and(|letter| letters.contains(letter))
+            }
+        }
+    }
+
+    /// True when addresses end with a literal suffix, so `ending_letter` is defined
+    pub fn has_fixed_suffix(&self) -> bool {
+        match self {
+            Self::Suffix { .. } => true,
+            Self::Spec(spec) => !spec.suffix.is_empty(),
+            Self::Regex(_) => false,
+            Self::Sharded { inner, .. } => inner.has_fixed_suffix(),
+        }
+    }
+
+    /// The literal suffix every address ends with, e.g. "Pet"
+    pub fn fixed_suffix(&self) -> Option<&str> {
+        match self {
+            Self::Suffix { suffix, .. } => Some(suffix),
+            Self::Spec(spec) if !spec.suffix.is_empty() => Some(&spec.suffix),
+            Self::Sharded { inner, .. } => inner.fixed_suffix(),
+            _ => None,
+        }
+    }
+
+    /// Letter immediately before the literal suffix (the 'k' in "…kPet"), for
+    /// matchers that have a suffix
+    pub fn ending_letter(&self, address: &str) -> Option<char> {
+        let suffix = self.fixed_suffix()?;
+        let head = address.get(..address.len().checked_sub(suffix.len())?)?;
+        head.chars().next_back()
+    }
+
+    /// Number of trailing characters `tail_may_match` needs, when this matcher
+    /// can reject candidates from the address tail alone
+    fn tail_len(&self) -> Option<usize> {
+        let len = match self {
+            Self::Suffix { suffix, .. } => suffix.len() + 1,
+            Self::Spec(spec) if !spec.suffix.is_empty() => spec.suffix.len(),
+            Self::Sharded { inner, .. } => return inner.tail_len(),
+            _ => return None,
+        };
+        (len <= MAX_TAIL_DIGITS).then_some(len)
+    }
+
+    /// Cheap pre-filter on the last `tail_len()` characters; false means the
+    /// full address can never match
+    fn tail_may_match(&self, tail: &str) -> bool {
+        match self {
+            Self::Suffix { suffix, case_sensitive } => {
+                PetAddress::matches_target_suffix(tail, suffix, *case_sensitive)
+            }
+            Self::Spec(spec) if spec.case_sensitive => tail == spec.suffix,
+            Self::Spec(spec) => tail.eq_ignore_ascii_case(&spec.suffix),
+            Self::Regex(_) => true,
+            // The tail of a suffix matcher starts with the letter
+            Self::Sharded { inner, letters } => {
+                inner.tail_may_match(tail) && tail.chars().next().is_some_and(|letter| letters.contains(letter))
+            }
+        }
+    }
+
+    /// Human-readable description for logs
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Suffix { suffix, case_sensitive: true } => format!("[a-z]{}", suffix),
+            Self::Suffix { suffix, case_sensitive: false } => {
+                format!("[a-z]{} (case-insensitive)", suffix)
+            }
+            Self::Spec(spec) => format!(
+                "{}*{}{}",
+                spec.prefix,
+                spec.suffix,
+                if spec.case_sensitive { "" } else { " (case-insensitive)" }
+            ),
+            Self::Regex(regex) => format!("/{}/", regex.as_str()),
+            Self::Sharded { inner, letters } => match inner.as_ref() {
+                Self::Suffix { suffix, case_sensitive: true } => format!("[{}]{}", letters, suffix),
+                Self::Suffix { suffix, case_sensitive: false } => format!("[{}]{} (case-insensitive)", letters, suffix),
+                other => other.describe(),
+            },
+        }
+    }
+
+    /// Equivalent regex source, for matching outside this process (remote workers)
+    pub fn to_regex(&self) -> String {
+        match self {
+            Self::Suffix { suffix, case_sensitive: true } => format!("[a-z]{}$", regex::escape(suffix)),
+            Self::Suffix { suffix, case_sensitive: false } => format!("[a-z](?i:{})$", regex::escape(suffix)),
+            Self::Spec(spec) => format!(
+                "{}^{}.*{}$",
+                if spec.case_sensitive { "" } else { "(?i)" },
+                regex::escape(&spec.prefix),
+                regex::escape(&spec.suffix)
+            ),
+            Self::Regex(regex) => regex.as_str().to_string(),
+            Self::Sharded { inner, letters } => match inner.as_ref() {
+                Self::Suffix { suffix, case_sensitive: true } => format!("[{}]{}$", letters, regex::escape(suffix)),
+                Self::Suffix { suffix, case_sensitive: false } => format!("[{}](?i:{})$", letters, regex::escape(suffix)),
+                other => other.to_regex(),
+            },
+        }
+    }
+
+    /// Every match must start with one of the extracted prefix literals and end with
+    /// one of the suffix literals; if all of them contain non-base58 characters the
+    /// pattern can never match a Solana address
+    fn check_regex_feasible(pattern: &str, case_sensitive: bool) -> Result<()> {
+        let hir = ParserBuilder::new()
+            .case_insensitive(!case_sensitive)
+            .build()
+            .parse(pattern)
+            .map_err(|e| anyhow!("Invalid address pattern '{}': {}", pattern, e))?;
+
+        for kind in [ExtractKind::Prefix, ExtractKind::Suffix] {
+            let seq = Extractor::new().kind(kind).extract(&hir);
+            if let Some(literals) = seq.literals() {
+                let all_impossible = !literals.is_empty()
+                    && literals.iter().all(|literal| {
+                        literal
+                            .as_bytes()
+                            .iter()
+                            .any(|b| !BASE58_ALPHABET.as_bytes().contains(b))
+                    });
+                if all_impossible {
+                    return Err(anyhow!(
+                        "Address pattern '{}' requires characters that never appear in base58 addresses",
+                        pattern
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for AddressMatcher {
+    fn default() -> Self {
+        Self::Suffix {
+            suffix: DEFAULT_TARGET_SUFFIX.to_string(),
+            case_sensitive: true,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PetAddress {
     pub public_key: String,
-    pub private_key: String,
+    /// Wiped from memory when dropped
+    pub private_key: SecretKey,
     pub address: String,
 }
 
@@ -16,64 +353,164 @@ pub struct PetAddressInfo {
 }
 
 impl PetAddress {
-    pub fn generate() -> Option<Self> {
-        const MAX_ATTEMPTS: usize = 10_000_000; // Limit attempts to avoid infinite loops
-                                                 // Statistically need ~7,804 attempts on average for [a-z]Pet suffix
-
-        for attempt in 1..=MAX_ATTEMPTS {
-            let keypair = Keypair::new();
-            let pubkey = keypair.pubkey();
-            let address_str = pubkey.to_string();
-
-            // Check if address ends with lowercase letter + "Pet" (e.g., aPet, bPet, zPet)
-            if Self::is_valid_pet_suffix(&address_str) {
-                return Some(Self {
-                    public_key: pubkey.to_string(),
-                    private_key: bs58::encode(&keypair.to_bytes()).into_string(),
-                    address: address_str,
-                });
+    /// Grind keypairs until an address satisfies `matcher`
+    pub fn generate(matcher: &AddressMatcher) -> Option<Self> {
+        Self::generate_any(std::slice::from_ref(matcher)).map(|(_, address)| address)
+    }
+
+    /// Grind keypairs testing each candidate against every matcher, so one loop
+    /// serves several patterns. Returns the index of the first matcher that hit.
+    pub fn generate_any<M: Borrow<AddressMatcher>>(matchers: &[M]) -> Option<(usize, Self)> {
+        let result = Self::search_any(matchers, MAX_ATTEMPTS, &AtomicUsize::new(0), &AtomicBool::new(false), &mut |_| {});
+
+        if result.is_none() {
+            let patterns: Vec<String> = matchers.iter().map(|m| m.borrow().describe()).collect();
+            tracing::warn!("Failed to generate {} address after {} attempts", patterns.join(" | "), MAX_ATTEMPTS);
+        }
+        result
+    }
+
+    /// Grind loop shared by worker threads: attempts are claimed in chunks from
+    /// `attempts` until `max_attempts` is spent or `cancel` is set. The first
+    /// worker to find a match sets `cancel` so its siblings stop. `on_chunk` is
+    /// called with the number of keys generated after every chunk, so callers
+    /// can sample throughput or pace the worker while a search is running.
+    pub(crate) fn search_any<M: Borrow<AddressMatcher>>(
+        matchers: &[M],
+        max_attempts: usize,
+        attempts: &AtomicUsize,
+        cancel: &AtomicBool,
+        on_chunk: &mut dyn FnMut(u64),
+    ) -> Option<(usize, Self)> {
+        const CHUNK: usize = 1024;
+        // Seeds drawn from the RNG per call; 2 KiB, so the batch stays on the stack
+        const SEED_BATCH: usize = 64;
+
+        let tail_lens: Vec<Option<usize>> = matchers.iter().map(|m| m.borrow().tail_len()).collect();
+        let mut tail_buf = [0u8; MAX_TAIL_DIGITS];
+        // One reseeding ChaCha stream per worker instead of a getrandom syscall per key
+        let mut rng = rand::thread_rng();
+        let mut seeds = Zeroizing::new([[0u8; 32]; SEED_BATCH]);
+
+        while !cancel.load(Ordering::Relaxed) {
+            let claimed = attempts.fetch_add(CHUNK, Ordering::Relaxed);
+            if claimed >= max_attempts {
+                return None;
+            }
+
+            let chunk = CHUNK.min(max_attempts - claimed);
+            for batch_start in (0..chunk).step_by(SEED_BATCH) {
+                let batch = SEED_BATCH.min(chunk - batch_start);
+                rng.fill_bytes(seeds[..batch].as_flattened_mut());
+
+                for (offset, seed) in seeds[..batch].iter().enumerate() {
+                    let keypair = Keypair::new_from_array(*seed);
+                    let bytes = keypair.pubkey().to_bytes();
+
+                    // Only candidates whose tail passes the cheap filter get fully encoded
+                    let mut encoded: Option<String> = None;
+                    let hit = matchers.iter().zip(&tail_lens).position(|(matcher, tail_len)| {
+                        let matcher = matcher.borrow();
+                        if let Some(len) = *tail_len {
+                            if !matcher.tail_may_match(base58_tail(&bytes, len, &mut tail_buf)) {
+                                return false;
+                            }
+                        }
+                        // Check if address matches (e.g. ends with aPet, bPet, zPet)
+                        matcher.is_match(encoded.get_or_insert_with(|| bs58::encode(bytes).into_string()))
+                    });
+
+                    if let Some(index) = hit {
+                        cancel.store(true, Ordering::Relaxed);
+                        on_chunk((batch_start + offset + 1) as u64);
+                        let address_str = encoded.unwrap_or_else(|| bs58::encode(bytes).into_string());
+                        return Some((index, Self {
+                            public_key: address_str.clone(),
+                            private_key: bs58::encode(&keypair.to_bytes()).into_string().into(),
+                            address: address_str,
+                        }));
+                    }
+                }
             }
+            on_chunk(chunk as u64);
 
-            // Log progress every 1M attempts
-            if attempt % 1_000_000 == 0 {
-                tracing::debug!("Pet address generation attempt {}/{}", attempt, MAX_ATTEMPTS);
+            // Log progress roughly every 1M attempts
+            if (claimed / CHUNK + 1).is_multiple_of(1_000) {
+                tracing::debug!("Pet address generation attempt {}/{}", claimed + CHUNK, max_attempts);
             }
         }
 
-        tracing::warn!("Failed to generate Pet address after {} attempts", MAX_ATTEMPTS);
         None
     }
 
     /// Validates that the address ends with a lowercase letter followed by "Pet"
     /// Valid examples: aPet, bPet, cPet, ..., zPet
     /// Invalid examples: APet, BPet, Pet, 1Pet
-    fn is_valid_pet_suffix(address: &str) -> bool {
-        if address.len() < 4 {
+    pub fn is_valid_pet_suffix(address: &str) -> bool {
+        Self::has_target_suffix(address, DEFAULT_TARGET_SUFFIX)
+    }
+
+    /// Validates that the address ends with a lowercase letter followed by `suffix`
+    /// e.g. with suffix "Cat": aCat, zCat are valid; ACat, Cat, 1Cat are not
+    pub fn has_target_suffix(address: &str, suffix: &str) -> bool {
+        Self::matches_target_suffix(address, suffix, true)
+    }
+
+    /// Like `has_target_suffix`, optionally ignoring the case of the suffix
+    /// (the preceding letter must still be lowercase)
+    pub fn matches_target_suffix(address: &str, suffix: &str, case_sensitive: bool) -> bool {
+        if suffix.is_empty() || address.len() <= suffix.len() {
             return false;
         }
 
-        let suffix = &address[address.len() - 4..];
-        if !suffix.ends_with("Pet") {
+        let split = address.len() - suffix.len();
+        if !address.is_char_boundary(split) {
             return false;
         }
+        let (head, tail) = address.split_at(split);
+
+        let suffix_matches = if case_sensitive {
+            tail == suffix
+        } else {
+            tail.eq_ignore_ascii_case(suffix)
+        };
 
-        let first_char = suffix.chars().next().unwrap();
-        first_char.is_ascii_lowercase()
+        suffix_matches && head.chars().next_back().is_some_and(|c| c.is_ascii_lowercase())
     }
     
     pub fn from_keypair(keypair: &Keypair) -> Self {
         let pubkey = keypair.pubkey();
         Self {
             public_key: pubkey.to_string(),
-            private_key: bs58::encode(&keypair.to_bytes()).into_string(),
+            private_key: bs58::encode(&keypair.to_bytes()).into_string().into(),
             address: pubkey.to_string(),
         }
     }
     
     pub fn to_keypair(&self) -> Result<Keypair, Box<dyn std::error::Error>> {
-        let private_key_bytes = bs58::decode(&self.private_key).into_vec()?;
+        let private_key_bytes = zeroize::Zeroizing::new(bs58::decode(self.private_key.expose()).into_vec()?);
         Ok(Keypair::try_from(&private_key_bytes[..])?)
     }
+
+    /// The private key must decode and derive both `public_key` and `address`
+    pub fn check_keypair(&self) -> Result<()> {
+        let keypair = self.to_keypair().map_err(|e| anyhow!("invalid private key: {}", e))?;
+        if keypair.pubkey().to_string() != self.public_key || self.address != self.public_key {
+            return Err(anyhow!("private key does not belong to {}", self.public_key));
+        }
+        Ok(())
+    }
+
+    /// Message signed to prove possession of this address's key: `pinpet-proof:{address}:{nonce}`
+    pub fn proof_message(&self, nonce: &str) -> String {
+        format!("{}:{}:{}", PROOF_MESSAGE_PREFIX, self.address, nonce)
+    }
+
+    /// Ed25519 signature (base58) of `proof_message(nonce)`, verifiable with the address as public key
+    pub fn prove_possession(&self, nonce: &str) -> Result<String> {
+        let keypair = self.to_keypair().map_err(|e| anyhow!("Unreadable private key of {}: {}", self.address, e))?;
+        Ok(keypair.sign_message(self.proof_message(nonce).as_bytes()).to_string())
+    }
 }
 
 #[cfg(test)]
@@ -117,4 +554,219 @@ mod tests {
         assert!(!PetAddress::is_valid_pet_suffix("abc"));
         assert!(!PetAddress::is_valid_pet_suffix(""));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_regex_matcher() {
+        let suffix = AddressMatcher::regex("[a-z]Pet$", true).unwrap();
+        assert!(suffix.is_match("SomeRandomAddressnPet"));
+        assert!(!suffix.is_match("SomeRandomAddressNPet"));
+        assert!(!suffix.is_match("SomeRandomAddressnPetx"));
+
+        let prefix = AddressMatcher::regex("^Pin", true).unwrap();
+        assert!(prefix.is_match("PinSomeRandomAddress"));
+        assert!(!prefix.is_match("SomePinRandomAddress"));
+
+        let contains = AddressMatcher::regex("Cafe", true).unwrap();
+        assert!(contains.is_match("SomeCafeAddress"));
+        assert!(!contains.is_match("SomeAddress"));
+    }
+
+    #[test]
+    fn test_regex_matcher_feasibility() {
+        // Invalid syntax
+        assert!(AddressMatcher::regex("[a-z", true).is_err());
+
+        // Characters outside the base58 alphabet can never match
+        assert!(AddressMatcher::regex("P0t$", true).is_err());
+        assert!(AddressMatcher::regex("^lol", true).is_err());
+        assert!(AddressMatcher::regex("^PIN", true).is_err());
+        assert!(AddressMatcher::regex("^(O|I)x", true).is_err());
+
+        // At least one feasible alternative is enough
+        assert!(AddressMatcher::regex("(P0t|Pet)$", true).is_ok());
+        assert!(AddressMatcher::regex(".*", true).is_ok());
+    }
+
+    #[test]
+    fn test_pattern_spec_prefix_and_suffix() {
+        let spec = PatternSpec::new("Pin", "Pet", true);
+        assert!(spec.is_match("PinSomeRandomAddressPet"));
+        assert!(!spec.is_match("PinSomeRandomAddressCat"));
+        assert!(!spec.is_match("SomeRandomAddressPet"));
+        assert!(!spec.is_match("pinSomeRandomAddresspet"));
+        assert!(!spec.is_match("PinPe"));
+
+        let prefix_only = PatternSpec::new("Pin", "", true);
+        assert!(prefix_only.is_match("PinAnything"));
+
+        let insensitive = PatternSpec::new("Pin", "Pet", false);
+        assert!(insensitive.is_match("pinSomeRandomAddressPET"));
+        assert!(insensitive.is_match("PiNSomeRandomAddresspet"));
+    }
+
+    #[test]
+    fn test_pattern_spec_validation() {
+        assert!(PatternSpec::new("Pin", "Pet", true).validate().is_ok());
+        assert!(PatternSpec::new("", "", true).validate().is_err());
+        // 'I' is not in the base58 alphabet ...
+        assert!(PatternSpec::new("PIN", "Pet", true).validate().is_err());
+        // ... but 'i' is, so case-insensitive matching makes it feasible
+        assert!(PatternSpec::new("PIN", "Pet", false).validate().is_ok());
+        assert!(PatternSpec::new("", "P0t", true).validate().is_err());
+
+        let matcher = AddressMatcher::spec(PatternSpec::new("Pin", "Pet", true)).unwrap();
+        assert!(matcher.is_match("PinSomeRandomAddressPet"));
+    }
+
+    #[test]
+    fn test_suffix_matcher_matches_validator() {
+        let matcher = AddressMatcher::default();
+        assert!(matcher.is_match("AGm9DpEaQYHxLKy98WGGoqErJEML9Pf5HySA1o4skPet"));
+        assert!(!matcher.is_match("AGm9DpEaQYHxLKy98WGGoqErJEML9Pf5HySA1o4sKPet"));
+
+        assert!(AddressMatcher::suffix("Cat", true).is_ok());
+        assert!(AddressMatcher::suffix("C0t", true).is_err());
+    }
+
+    #[test]
+    fn test_case_insensitive_suffix() {
+        assert!(PetAddress::matches_target_suffix("SomeRandomAddressapet", "Pet", false));
+        assert!(PetAddress::matches_target_suffix("SomeRandomAddressaPET", "Pet", false));
+        assert!(PetAddress::matches_target_suffix("SomeRandomAddressaPet", "Pet", false));
+        assert!(!PetAddress::matches_target_suffix("SomeRandomAddressapet", "Pet", true));
+        assert!(!PetAddress::matches_target_suffix("SomeRandomAddressaPET", "Pet", true));
+
+        // The preceding letter rule is unchanged
+        assert!(!PetAddress::matches_target_suffix("SomeRandomAddressApet", "Pet", false));
+        assert!(!PetAddress::matches_target_suffix("SomeRandomAddress1PET", "Pet", false));
+
+        let matcher = AddressMatcher::suffix("Pet", false).unwrap();
+        assert!(matcher.is_match("SomeRandomAddresskpEt"));
+
+        // "PIN" is impossible case-sensitively but feasible ignoring case
+        assert!(AddressMatcher::suffix("PIN", true).is_err());
+        assert!(AddressMatcher::suffix("PIN", false).is_ok());
+    }
+
+    #[test]
+    fn test_regex_form_matches_like_the_matcher() {
+        let matchers = [
+            AddressMatcher::default(),
+            AddressMatcher::suffix("Pet", false).unwrap(),
+            AddressMatcher::spec(PatternSpec::new("Pin", "Pet", false)).unwrap(),
+            AddressMatcher::regex("^Pin", true).unwrap(),
+            AddressMatcher::suffix("Pet", false).unwrap().in_shard(&ShardLetters::parse("a-m").unwrap()),
+        ];
+        for matcher in matchers {
+            let regex = Regex::new(&matcher.to_regex()).unwrap();
+            for address in ["AGm9DpEaQYHxLKy98WGGoqErJEML9Pf5HySA1o4skPet", "pinRandomAddressKpET", "PinRandomAddress1Pet", "SomeRandomAddressxpet"] {
+                assert_eq!(regex.is_match(address), matcher.is_match(address), "{} on {}", matcher.describe(), address);
+            }
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_regex() {
+        let matcher = AddressMatcher::regex("[a-z]pet$", false).unwrap();
+        assert!(matcher.is_match("SomeRandomAddressxPET"));
+        assert!(matcher.is_match("SomeRandomAddressXPet"));
+
+        assert!(AddressMatcher::regex("^PIN", true).is_err());
+        assert!(AddressMatcher::regex("^PIN", false).is_ok());
+    }
+
+    #[test]
+    fn test_generate_any_reports_matching_pattern() {
+        // Regexes that accept everything / nothing make the outcome deterministic
+        let never = AddressMatcher::regex("^$", true).unwrap();
+        let always = AddressMatcher::regex(".", true).unwrap();
+
+        let (index, address) = PetAddress::generate_any(&[&never, &always]).unwrap();
+        assert_eq!(index, 1);
+        assert!(always.is_match(&address.address));
+
+        // The private key is the keypair the seed was expanded into
+        let bytes = bs58::decode(address.private_key.expose()).into_vec().unwrap();
+        let keypair = Keypair::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(keypair.pubkey().to_string(), address.address);
+    }
+
+    #[test]
+    fn test_base58_tail_matches_full_encoding() {
+        let mut buf = [0u8; MAX_TAIL_DIGITS];
+
+        let zero = [0u8; 32];
+        assert_eq!(base58_tail(&zero, 4, &mut buf), "1111");
+
+        for _ in 0..200 {
+            let pubkey = Keypair::new().pubkey();
+            let encoded = pubkey.to_string();
+            for len in 1..=MAX_TAIL_DIGITS {
+                assert_eq!(
+                    base58_tail(&pubkey.to_bytes(), len, &mut buf),
+                    &encoded[encoded.len() - len..]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_tail_filter_never_rejects_a_match() {
+        let matchers = [
+            AddressMatcher::suffix("t", true).unwrap(),
+            AddressMatcher::suffix("T", false).unwrap(),
+            AddressMatcher::spec(PatternSpec::new("", "x", false)).unwrap(),
+            AddressMatcher::suffix("t", true).unwrap().in_shard(&ShardLetters::parse("a-m").unwrap()),
+        ];
+        let mut buf = [0u8; MAX_TAIL_DIGITS];
+
+        for _ in 0..2_000 {
+            let pubkey = Keypair::new().pubkey();
+            let encoded = pubkey.to_string();
+            for matcher in &matchers {
+                let len = matcher.tail_len().unwrap();
+                let tail = base58_tail(&pubkey.to_bytes(), len, &mut buf);
+                assert_eq!(matcher.tail_may_match(tail), matcher.is_match(&encoded));
+            }
+        }
+    }
+
+    #[test]
+    fn test_ending_letter() {
+        let matcher = AddressMatcher::default();
+        assert_eq!(matcher.ending_letter("SomeRandomAddresskPet"), Some('k'));
+
+        let spec = AddressMatcher::spec(PatternSpec::new("Pin", "Cat", true)).unwrap();
+        assert_eq!(spec.ending_letter("PinAddressqCat"), Some('q'));
+
+        let regex = AddressMatcher::regex("[a-z]Pet$", true).unwrap();
+        assert_eq!(regex.ending_letter("SomeRandomAddresskPet"), None);
+        assert!(matcher.has_fixed_suffix() && spec.has_fixed_suffix() && !regex.has_fixed_suffix());
+    }
+
+    #[test]
+    fn test_custom_target_suffix() {
+        assert!(PetAddress::has_target_suffix("SomeRandomAddressaCat", "Cat"));
+        assert!(PetAddress::has_target_suffix("xDog", "Dog"));
+        assert!(PetAddress::has_target_suffix("SomeRandomAddressqPinPet", "PinPet"));
+
+        assert!(!PetAddress::has_target_suffix("SomeRandomAddressaPet", "Cat"));
+        assert!(!PetAddress::has_target_suffix("SomeRandomAddressACat", "Cat"));
+        assert!(!PetAddress::has_target_suffix("Cat", "Cat"));
+        assert!(!PetAddress::has_target_suffix("1Cat", "Cat"));
+        assert!(!PetAddress::has_target_suffix("aCat", ""));
+    }
+
+    #[test]
+    fn test_proof_of_possession_verifies_against_the_address() {
+        let address = PetAddress::from_keypair(&Keypair::new());
+        let signature: solana_sdk::signature::Signature = address.prove_possession("nonce-1").unwrap().parse().unwrap();
+        let pubkey = bs58::decode(&address.address).into_vec().unwrap();
+
+        let message = address.proof_message("nonce-1");
+        assert!(message.starts_with("pinpet-proof:") && message.ends_with(":nonce-1"));
+        assert!(signature.verify(&pubkey, message.as_bytes()));
+        assert!(!signature.verify(&pubkey, address.proof_message("nonce-2").as_bytes()));
+        assert!(!signature.verify(&pubkey, b"nonce-1"));
+    }
+}
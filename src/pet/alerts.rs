@@ -0,0 +1,229 @@
+//! Low-watermark alerting: a background watcher polls every pool's queue depth
+//! and POSTs a JSON payload to a webhook when a pool runs low and again once
+//! it has recovered.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::backend::Storage;
+use super::http::{self, Url};
+use crate::config::AlertConfig;
+
+/// Webhook payload; never carries key material
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WatermarkAlert {
+    /// `queue_low` or `queue_recovered`
+    pub event: &'static str,
+    pub pool: String,
+    pub depth: usize,
+    pub low_watermark: usize,
+    pub recovery_watermark: usize,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Whether one pool is currently below its watermark. A pool goes low below
+/// `low` and only counts as recovered at `recovery`, so a depth hovering
+/// around the threshold does not page on every poll.
+#[derive(Debug, Clone, Copy)]
+pub struct Watermark {
+    low: usize,
+    recovery: usize,
+    is_low: bool,
+}
+
+impl Watermark {
+    pub fn new(low: usize, recovery: usize) -> Self {
+        Self {
+            low,
+            recovery: recovery.max(low),
+            is_low: false,
+        }
+    }
+
+    /// The event `depth` would trigger, if any. State only changes through
+    /// `confirm`, so an alert that failed to send is retried on the next poll.
+    pub fn check(&self, depth: usize) -> Option<&'static str> {
+        if !self.is_low && depth < self.low {
+            Some("queue_low")
+        } else if self.is_low && depth >= self.recovery {
+            Some("queue_recovered")
+        } else {
+            None
+        }
+    }
+
+    /// Record that the event returned by `check` was delivered
+    pub fn confirm(&mut self, event: &str) {
+        self.is_low = event == "queue_low";
+    }
+}
+
+/// Blocking JSON POSTer for one webhook URL
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    url: Url,
+    timeout: Duration,
+}
+
+impl Webhook {
+    pub fn new(url: &str, timeout: Duration) -> Result<Self> {
+        Ok(Self {
+            url: Url::parse(url).context("Invalid webhook URL")?,
+            timeout,
+        })
+    }
+
+    pub fn host(&self) -> &str {
+        &self.url.host
+    }
+
+    /// POST `payload` as JSON; fails unless the response status is 2xx
+    pub fn post<T: Serialize>(&self, payload: &T) -> Result<()> {
+        self.post_json(&serde_json::to_vec(payload)?, &[])
+    }
+
+    /// POST an encoded JSON `body` with `extra_headers`; fails unless the response status is 2xx
+    pub fn post_json(&self, body: &[u8], extra_headers: &[(&str, String)]) -> Result<()> {
+        let mut headers = vec![
+            ("Content-Type", "application/json".to_string()),
+            ("User-Agent", "pinpet-suffix-generator".to_string()),
+        ];
+        headers.extend_from_slice(extra_headers);
+        let response = http::send(&self.url, "POST", &self.url.path, &headers, body, self.timeout)?;
+        if !response.is_success() {
+            bail!("Webhook responded with {}", response.describe());
+        }
+        Ok(())
+    }
+}
+
+/// Poll `pools` every `check_interval_seconds` and post watermark crossings to the webhook
+pub fn start_watermark_watcher(pools: Vec<(String, Arc<dyn Storage>)>, config: &AlertConfig) -> Result<()> {
+    let webhook = Webhook::new(&config.webhook_url, Duration::from_millis(config.timeout_ms))?;
+    let low = config.low_watermark;
+    let recovery = config.recovery_watermark.unwrap_or(low).max(low);
+    let interval = Duration::from_secs(config.check_interval_seconds.max(1));
+    let mut watermarks = vec![Watermark::new(low, recovery); pools.len()];
+
+    tokio::spawn(async move {
+        loop {
+            for ((name, storage), watermark) in pools.iter().zip(watermarks.iter_mut()) {
+                let depth = match storage.count_addresses() {
+                    Ok(depth) => depth,
+                    Err(e) => {
+                        tracing::warn!("Watermark check for pool '{}' failed: {}", name, e);
+                        continue;
+                    }
+                };
+                let Some(event) = watermark.check(depth) else {
+                    continue;
+                };
+
+                let alert = WatermarkAlert {
+                    event,
+                    pool: name.clone(),
+                    depth,
+                    low_watermark: low,
+                    recovery_watermark: recovery,
+                    timestamp: Utc::now(),
+                };
+                let webhook = webhook.clone();
+                match tokio::task::spawn_blocking(move || webhook.post(&alert)).await {
+                    Ok(Ok(())) => {
+                        tracing::info!("Sent {} alert for pool '{}' (depth {})", event, name, depth);
+                        watermark.confirm(event);
+                    }
+                    Ok(Err(e)) => tracing::warn!("Failed to send {} alert for pool '{}': {}", event, name, e),
+                    Err(e) => tracing::warn!("Webhook task failed: {}", e),
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_watermark_fires_once_per_crossing() {
+        let mut watermark = Watermark::new(10, 20);
+        assert_eq!(watermark.check(15), None);
+        assert_eq!(watermark.check(9), Some("queue_low"));
+
+        // Not delivered yet: keeps asking
+        assert_eq!(watermark.check(8), Some("queue_low"));
+        watermark.confirm("queue_low");
+        assert_eq!(watermark.check(5), None);
+
+        // Back above the low watermark but not yet recovered
+        assert_eq!(watermark.check(15), None);
+        assert_eq!(watermark.check(20), Some("queue_recovered"));
+        watermark.confirm("queue_recovered");
+        assert_eq!(watermark.check(25), None);
+    }
+
+    #[test]
+    fn test_webhook_posts_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = Vec::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    content_length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push(line);
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            (&stream).write_all(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+            (head, body)
+        });
+
+        let webhook = Webhook::new(&format!("http://127.0.0.1:{}/alerts", port), Duration::from_secs(5)).unwrap();
+        webhook.post(&serde_json::json!({ "event": "queue_low" })).unwrap();
+
+        let (head, body) = server.join().unwrap();
+        assert_eq!(head[0], "POST /alerts HTTP/1.1\r\n");
+        assert!(head.contains(&format!("Host: 127.0.0.1:{}\r\n", port)));
+        assert_eq!(body, br#"{"event":"queue_low"}"#);
+    }
+
+    #[test]
+    fn test_webhook_rejects_error_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Read the whole request so the client never writes into a closed socket
+            let mut request = Vec::new();
+            let mut chunk = [0; 1024];
+            while !request.ends_with(b"{}") {
+                let read = stream.read(&mut chunk).unwrap();
+                request.extend_from_slice(&chunk[..read]);
+            }
+            stream.write_all(b"HTTP/1.1 500 Internal Server Error\r\n\r\n").unwrap();
+        });
+
+        let webhook = Webhook::new(&format!("http://127.0.0.1:{}/", port), Duration::from_secs(5)).unwrap();
+        let error = webhook.post(&serde_json::json!({})).unwrap_err();
+        assert!(error.to_string().contains("500"));
+    }
+}
@@ -0,0 +1,64 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::address::PetAddressInfo;
+
+/// Who asked for an address, as far as the server can tell
+#[derive(Debug, Clone, Default)]
+pub struct Requester {
+    /// Client IP of the connection
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// Audit entry kept after an address leaves the queue. The private key is
+/// never archived: once dispensed it belongs to the requester.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DispensedRecord {
+    pub id: u64,
+    pub pool: String,
+    pub public_key: String,
+    pub address: String,
+    pub created_at: DateTime<Utc>,
+    pub dispensed_at: DateTime<Utc>,
+    pub requester: Option<String>,
+    pub user_agent: Option<String>,
+    /// Set when the address was checked out under a lease
+    pub lease_expires_at: Option<DateTime<Utc>>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+}
+
+impl DispensedRecord {
+    pub fn new(pool: &str, address_info: &PetAddressInfo, requester: &Requester) -> Self {
+        Self {
+            id: address_info.id,
+            pool: pool.to_string(),
+            public_key: address_info.address.public_key.clone(),
+            address: address_info.address.address.clone(),
+            created_at: address_info.created_at,
+            dispensed_at: Utc::now(),
+            requester: requester.ip.clone(),
+            user_agent: requester.user_agent.clone(),
+            lease_expires_at: None,
+            acknowledged_at: None,
+        }
+    }
+}
+
+/// Archive query: records matching every set field, newest ID first
+#[derive(Debug, Clone)]
+pub struct DispensedFilter {
+    pub requester: Option<String>,
+    /// Only records dispensed at or after this time
+    pub since: Option<DateTime<Utc>>,
+    pub limit: usize,
+}
+
+impl DispensedFilter {
+    pub fn matches(&self, record: &DispensedRecord) -> bool {
+        self.requester
+            .as_ref()
+            .is_none_or(|requester| record.requester.as_ref() == Some(requester))
+            && self.since.is_none_or(|since| record.dispensed_at >= since)
+    }
+}
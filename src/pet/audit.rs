@@ -0,0 +1,464 @@
+//! Append-only audit log. Every entry carries the SHA-256 of its predecessor,
+//! so editing, reordering or deleting an entry in the middle of the log
+//! breaks the chain from there on. Dropping entries from the end cannot be
+//! detected from the log alone: keep a copy of the head hash elsewhere.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sled::Tree;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use super::address::{PetAddress, PetAddressInfo};
+use super::archive::DispensedRecord;
+use super::backend::Storage;
+use super::storage::PetStorage;
+
+/// Name of the sled tree holding the log, keyed by big-endian sequence number
+pub const AUDIT_TREE: &str = "audit";
+
+/// `prev_hash` of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Actor of operations outside a request, e.g. webhook deliveries
+const SYSTEM_ACTOR: &str = "system";
+
+tokio::task_local! {
+    /// Who the current request or task acts for; see `with_actor`
+    static ACTOR: String;
+}
+
+/// Run `future` with `actor` recorded as the actor of everything it audits
+pub async fn with_actor<F: Future>(actor: String, future: F) -> F::Output {
+    ACTOR.scope(actor, future).await
+}
+
+/// Run `f` with `actor` recorded as the actor of everything it audits
+pub fn with_actor_sync<R>(actor: String, f: impl FnOnce() -> R) -> R {
+    ACTOR.sync_scope(actor, f)
+}
+
+/// Actor of the running request or task; `system` outside `with_actor`
+pub fn current_actor() -> String {
+    ACTOR.try_with(Clone::clone).unwrap_or_else(|_| SYSTEM_ACTOR.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// An address was queued (ground, submitted by a worker or replica, or imported)
+    Store,
+    /// An address was dispensed
+    Pop,
+    /// A leased address was acknowledged
+    Ack,
+    /// Private keys left the server through `/admin/export`
+    Export,
+    /// Any other admin request
+    Admin,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Store => "store",
+            Self::Pop => "pop",
+            Self::Ack => "ack",
+            Self::Export => "export",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+/// What happened, before it is chained
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEvent {
+    pub action: AuditAction,
+    pub pool: Option<String>,
+    /// Address, address ID or request path the action applied to
+    pub subject: Option<String>,
+    pub detail: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction) -> Self {
+        Self {
+            action,
+            pool: None,
+            subject: None,
+            detail: None,
+        }
+    }
+
+    pub fn pool(mut self, pool: &str) -> Self {
+        self.pool = Some(pool.to_string());
+        self
+    }
+
+    pub fn subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// A chained log entry. `hash` covers every other field, `prev_hash` included.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    pub action: AuditAction,
+    pub actor: String,
+    pub pool: Option<String>,
+    pub subject: Option<String>,
+    pub detail: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn compute_hash(&self) -> Result<String> {
+        let unhashed = Self {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let encoded = serde_json::to_vec(&unhashed)?;
+        Ok(hex(&openssl::sha::sha256(&encoded)))
+    }
+}
+
+/// Outcome of walking the chain from the first entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditVerifyReport {
+    /// Entries checked before the first problem (all of them when valid)
+    pub verified: u64,
+    /// Hash of the last entry; compare with a copy kept elsewhere to detect truncation
+    pub head_hash: Option<String>,
+    /// Sequence number of the first entry that does not fit the chain
+    pub broken_at: Option<u64>,
+    pub problem: Option<String>,
+}
+
+impl AuditVerifyReport {
+    pub fn is_valid(&self) -> bool {
+        self.broken_at.is_none()
+    }
+}
+
+/// Sequence number and hash of the newest entry
+struct Head {
+    seq: u64,
+    hash: String,
+}
+
+/// The hash-chained log, in a sled tree or (with other backends) in memory
+#[derive(Clone)]
+pub struct AuditLog {
+    tree: Option<Tree>,
+    memory: Arc<Mutex<Vec<AuditEntry>>>,
+    /// Held while an entry is chained and written so sequence numbers and
+    /// hashes are assigned in write order
+    head: Arc<Mutex<Head>>,
+}
+
+impl AuditLog {
+    pub fn new(tree: Option<Tree>) -> Result<Self> {
+        let head = match tree.as_ref().map(Tree::last).transpose()?.flatten() {
+            Some((_, value)) => {
+                let last: AuditEntry = serde_json::from_slice(&value).context("Failed to deserialize audit entry")?;
+                Head {
+                    seq: last.seq,
+                    hash: last.hash,
+                }
+            }
+            None => Head {
+                seq: 0,
+                hash: GENESIS_HASH.to_string(),
+            },
+        };
+        Ok(Self {
+            tree,
+            memory: Arc::new(Mutex::new(Vec::new())),
+            head: Arc::new(Mutex::new(head)),
+        })
+    }
+
+    /// Chain `event` to the log as done by the current actor
+    pub fn record(&self, event: AuditEvent) {
+        if let Err(e) = self.append(event, current_actor()) {
+            tracing::error!("Failed to write an audit entry: {:#}", e);
+        }
+    }
+
+    fn append(&self, event: AuditEvent, actor: String) -> Result<AuditEntry> {
+        let mut head = self.head.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entry = AuditEntry {
+            seq: head.seq + 1,
+            at: Utc::now(),
+            action: event.action,
+            actor,
+            pool: event.pool,
+            subject: event.subject,
+            detail: event.detail,
+            prev_hash: head.hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash()?;
+
+        match &self.tree {
+            Some(tree) => {
+                tree.insert(entry.seq.to_be_bytes(), serde_json::to_vec(&entry)?)?;
+            }
+            None => self.memory.lock().unwrap_or_else(|e| e.into_inner()).push(entry.clone()),
+        }
+        head.seq = entry.seq;
+        head.hash = entry.hash.clone();
+        Ok(entry)
+    }
+
+    /// Up to `limit` entries, newest first, optionally only those before sequence number `before`
+    pub fn entries(&self, before: Option<u64>, limit: usize) -> Result<Vec<AuditEntry>> {
+        let before = before.unwrap_or(u64::MAX);
+        let Some(tree) = &self.tree else {
+            let memory = self.memory.lock().unwrap_or_else(|e| e.into_inner());
+            return Ok(memory.iter().rev().filter(|entry| entry.seq < before).take(limit).cloned().collect());
+        };
+        tree.range(..before.to_be_bytes())
+            .rev()
+            .take(limit)
+            .map(|entry| {
+                let (_, value) = entry?;
+                serde_json::from_slice(&value).context("Failed to deserialize audit entry")
+            })
+            .collect()
+    }
+
+    /// Walk the whole chain, stopping at the first entry that does not fit
+    pub fn verify(&self) -> Result<AuditVerifyReport> {
+        let mut report = AuditVerifyReport {
+            verified: 0,
+            head_hash: None,
+            broken_at: None,
+            problem: None,
+        };
+        let mut expected_seq = 1;
+        let mut prev_hash = GENESIS_HASH.to_string();
+
+        let mut check = |entry: AuditEntry| -> Result<bool> {
+            let problem = if entry.seq != expected_seq {
+                Some(format!("expected entry {} but found {}", expected_seq, entry.seq))
+            } else if entry.prev_hash != prev_hash {
+                Some("prev_hash does not match the previous entry".to_string())
+            } else if entry.compute_hash()? != entry.hash {
+                Some("contents do not match the entry's hash".to_string())
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                report.broken_at = Some(expected_seq);
+                report.problem = Some(problem);
+                return Ok(false);
+            }
+            report.verified += 1;
+            report.head_hash = Some(entry.hash.clone());
+            expected_seq += 1;
+            prev_hash = entry.hash;
+            Ok(true)
+        };
+
+        match &self.tree {
+            Some(tree) => {
+                for item in tree.iter() {
+                    let (key, value) = item?;
+                    let entry = match serde_json::from_slice::<AuditEntry>(&value) {
+                        Ok(entry) => entry,
+                        Err(e) => {
+                            let seq = key.as_ref().try_into().map(u64::from_be_bytes).unwrap_or(expected_seq);
+                            report.broken_at = Some(seq);
+                            report.problem = Some(format!("unreadable entry: {}", e));
+                            break;
+                        }
+                    };
+                    if !check(entry)? {
+                        break;
+                    }
+                }
+            }
+            None => {
+                let entries = self.memory.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                for entry in entries {
+                    if !check(entry)? {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Storage that writes an audit entry for every address stored, dispensed
+/// (as archived through `record_dispensed`) and acknowledged
+pub struct AuditedStorage {
+    inner: Arc<dyn Storage>,
+    pool: String,
+    log: AuditLog,
+}
+
+impl AuditedStorage {
+    pub fn new(inner: Arc<dyn Storage>, pool: &str, log: AuditLog) -> Self {
+        Self {
+            inner,
+            pool: pool.to_string(),
+            log,
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for AuditedStorage {
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+
+    fn store_address(&self, address: PetAddress) -> Result<u64> {
+        let public_key = address.public_key.clone();
+        let id = self.inner.store_address(address)?;
+        self.log.record(
+            AuditEvent::new(AuditAction::Store)
+                .pool(&self.pool)
+                .subject(public_key)
+                .detail(format!("id {}", id)),
+        );
+        Ok(id)
+    }
+
+    fn get_next_address(&self) -> Result<Option<PetAddressInfo>> {
+        self.inner.get_next_address()
+    }
+
+    fn get_next_addresses(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        self.inner.get_next_addresses(count)
+    }
+
+    fn count_addresses(&self) -> Result<usize> {
+        self.inner.count_addresses()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+
+    fn clear_all_addresses(&self) -> Result<()> {
+        self.inner.clear_all_addresses()
+    }
+
+    fn created_range(&self) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+        self.inner.created_range()
+    }
+
+    async fn restore(&self) -> Result<usize> {
+        self.inner.restore().await
+    }
+
+    async fn open_pool(&self, name: &str) -> Result<Arc<dyn Storage>> {
+        let pool = self.inner.open_pool(name).await?;
+        Ok(Arc::new(Self::new(pool, name, self.log.clone())))
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.inner.flush().await
+    }
+
+    async fn wait_for_demand(&self) {
+        self.inner.wait_for_demand().await
+    }
+
+    fn lease_expires_at(&self, id: u64) -> Option<DateTime<Utc>> {
+        self.inner.lease_expires_at(id)
+    }
+
+    fn acknowledge(&self, id: u64) -> bool {
+        let acknowledged = self.inner.acknowledge(id);
+        if acknowledged {
+            self.log.record(AuditEvent::new(AuditAction::Ack).pool(&self.pool).subject(format!("id {}", id)));
+        }
+        acknowledged
+    }
+
+    fn record_dispensed(&self, records: Vec<DispensedRecord>) {
+        for record in &records {
+            let mut event = AuditEvent::new(AuditAction::Pop)
+                .pool(&record.pool)
+                .subject(record.public_key.clone())
+                .detail(format!("id {}", record.id));
+            if let Some(lease_expires_at) = record.lease_expires_at {
+                event = event.detail(format!("id {}, leased until {}", record.id, lease_expires_at.to_rfc3339()));
+            }
+            self.log.record(event);
+        }
+        self.inner.record_dispensed(records)
+    }
+
+    fn sled(&self) -> Option<&PetStorage> {
+        self.inner.sled()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_chain_verifies_and_detects_tampering() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let log = AuditLog::new(Some(db.open_tree(AUDIT_TREE).unwrap())).unwrap();
+
+        log.record(AuditEvent::new(AuditAction::Store).pool("default").subject("TestAddressaPet"));
+        with_actor("client@203.0.113.7".to_string(), async {
+            log.record(AuditEvent::new(AuditAction::Pop).pool("default").subject("TestAddressaPet"));
+        })
+        .await;
+        log.record(AuditEvent::new(AuditAction::Ack).pool("default").subject("id 1"));
+
+        let entries = log.entries(None, 10).unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.seq).collect::<Vec<_>>(), vec![3, 2, 1]);
+        assert_eq!(entries[1].actor, "client@203.0.113.7");
+        assert_eq!(entries[2].actor, "system");
+        assert_eq!(log.entries(Some(3), 1).unwrap()[0].seq, 2);
+        let report = log.verify().unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.verified, 3);
+        assert_eq!(report.head_hash.as_deref(), Some(entries[0].hash.as_str()));
+
+        // A reopened log continues the chain
+        let reopened = AuditLog::new(Some(db.open_tree(AUDIT_TREE).unwrap())).unwrap();
+        reopened.record(AuditEvent::new(AuditAction::Admin).subject("GET /admin/audit/verify"));
+        assert_eq!(reopened.verify().unwrap().verified, 4);
+
+        // Rewriting an entry, even with a recomputed hash, breaks the chain after it
+        let tree = db.open_tree(AUDIT_TREE).unwrap();
+        let mut forged = entries[1].clone();
+        forged.actor = "someone-else".to_string();
+        forged.hash = forged.compute_hash().unwrap();
+        tree.insert(2u64.to_be_bytes(), serde_json::to_vec(&forged).unwrap()).unwrap();
+        let report = reopened.verify().unwrap();
+        assert_eq!((report.verified, report.broken_at), (2, Some(3)));
+
+        // So does deleting one
+        tree.insert(2u64.to_be_bytes(), serde_json::to_vec(&entries[1]).unwrap()).unwrap();
+        assert!(reopened.verify().unwrap().is_valid());
+        tree.remove(2u64.to_be_bytes()).unwrap();
+        assert_eq!(reopened.verify().unwrap().broken_at, Some(2));
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::address::{PetAddress, PetAddressInfo};
+use super::archive::DispensedRecord;
+use super::storage::PetStorage;
+
+/// Why a store (or, for `Busy`, a pop) was refused. Wrapped in
+/// `anyhow::Error`; callers that care downcast.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreError {
+    /// The public key was enqueued before (possibly already dispensed)
+    Duplicate { public_key: String },
+    /// The queue already holds its configured maximum
+    QueueFull { capacity: usize },
+    /// The background writer is too far behind; nothing was changed, retry shortly
+    Busy { pending: usize },
+    /// Sled keeps failing and degraded mode refuses new addresses
+    Degraded { failures: u32 },
+}
+
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreError::Duplicate { public_key } => write!(f, "Address {} was already enqueued", public_key),
+            StoreError::QueueFull { capacity } => write!(f, "Queue is full ({} addresses)", capacity),
+            StoreError::Busy { pending } => write!(f, "Storage is busy ({} writes waiting for disk); retry shortly", pending),
+            StoreError::Degraded { failures } => {
+                write!(f, "Storage is degraded ({} consecutive write failures); new addresses are refused", failures)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+/// How often backends without a demand signal let the generator re-check pool sizes
+const DEMAND_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Address queue the generator fills and the API pops from. `PetStorage`
+/// (sled) is the default implementor; other backends only need the core
+/// queue operations. Sled-specific extras (peek, filtered pops, diagnostics,
+/// reconciliation) are reached through `sled()` and unavailable elsewhere.
+///
+/// Queue operations are synchronous because they sit on the API hot path;
+/// network backends should keep them short (single round trips).
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Short name used in logs, e.g. "sled"
+    fn backend_name(&self) -> &'static str;
+
+    /// Append an address to the queue and return its ID. Fails with
+    /// `StoreError::Duplicate` when the backend has seen the public key before.
+    fn store_address(&self, address: PetAddress) -> Result<u64>;
+
+    /// Pop the oldest address
+    fn get_next_address(&self) -> Result<Option<PetAddressInfo>>;
+
+    /// Pop up to `count` addresses in FIFO order
+    fn get_next_addresses(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        let mut popped = Vec::new();
+        while popped.len() < count {
+            match self.get_next_address()? {
+                Some(address_info) => popped.push(address_info),
+                None => break,
+            }
+        }
+        Ok(popped)
+    }
+
+    /// Addresses currently queued
+    fn count_addresses(&self) -> Result<usize>;
+
+    /// Maximum queued addresses; None when unlimited
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// Drop every queued address
+    fn clear_all_addresses(&self) -> Result<()>;
+
+    /// When the oldest and the newest queued addresses were created; None when
+    /// the queue is empty or the backend cannot tell cheaply
+    fn created_range(&self) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+        Ok(None)
+    }
+
+    /// Reload the queue from durable storage; returns how many addresses are queued
+    async fn restore(&self) -> Result<usize>;
+
+    /// Open a separately queued pool `name` on the same backend
+    async fn open_pool(&self, name: &str) -> Result<Arc<dyn Storage>>;
+
+    /// Wait until everything written so far is durable. Backends that write
+    /// synchronously have nothing to do.
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Wait until addresses are consumed. Backends without a signal return
+    /// after a short poll interval so the generator re-checks pool sizes.
+    async fn wait_for_demand(&self) {
+        tokio::time::sleep(DEMAND_POLL_INTERVAL).await;
+    }
+
+    /// Expiry of the live lease on `id`, when pops check addresses out
+    fn lease_expires_at(&self, _id: u64) -> Option<chrono::DateTime<chrono::Utc>> {
+        None
+    }
+
+    /// Confirm a leased address; false when there is no live lease
+    fn acknowledge(&self, _id: u64) -> bool {
+        false
+    }
+
+    /// Archive audit entries for popped addresses. Backends without an
+    /// archive drop them.
+    fn record_dispensed(&self, _records: Vec<DispensedRecord>) {}
+
+    /// The sled storage behind this backend, for sled-only features
+    fn sled(&self) -> Option<&PetStorage> {
+        None
+    }
+}
+
+#[async_trait]
+impl Storage for PetStorage {
+    fn backend_name(&self) -> &'static str {
+        "sled"
+    }
+
+    fn store_address(&self, address: PetAddress) -> Result<u64> {
+        PetStorage::store_address(self, address)
+    }
+
+    fn get_next_address(&self) -> Result<Option<PetAddressInfo>> {
+        PetStorage::get_next_address(self)
+    }
+
+    fn get_next_addresses(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        PetStorage::get_next_addresses(self, count)
+    }
+
+    fn count_addresses(&self) -> Result<usize> {
+        PetStorage::count_addresses(self)
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        PetStorage::capacity(self)
+    }
+
+    fn clear_all_addresses(&self) -> Result<()> {
+        PetStorage::clear_all_addresses(self)
+    }
+
+    fn created_range(&self) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+        Ok(PetStorage::created_range(self))
+    }
+
+    async fn restore(&self) -> Result<usize> {
+        PetStorage::restore(self).await
+    }
+
+    async fn open_pool(&self, name: &str) -> Result<Arc<dyn Storage>> {
+        Ok(Arc::new(PetStorage::open_pool(self, name).await?))
+    }
+
+    async fn flush(&self) -> Result<()> {
+        PetStorage::flush(self).await
+    }
+
+    async fn wait_for_demand(&self) {
+        PetStorage::wait_for_demand(self).await
+    }
+
+    fn lease_expires_at(&self, id: u64) -> Option<chrono::DateTime<chrono::Utc>> {
+        PetStorage::lease_expires_at(self, id)
+    }
+
+    fn acknowledge(&self, id: u64) -> bool {
+        PetStorage::acknowledge(self, id)
+    }
+
+    fn record_dispensed(&self, records: Vec<DispensedRecord>) {
+        PetStorage::record_dispensed(self, records)
+    }
+
+    fn sled(&self) -> Option<&PetStorage> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::test_support::test_address;
+
+    #[tokio::test]
+    async fn test_sled_through_trait_object() {
+        let storage: Arc<dyn Storage> = Arc::new(PetStorage::temporary().unwrap());
+        assert_eq!(storage.backend_name(), "sled");
+        assert!(storage.sled().is_some());
+
+        storage.store_address(test_address("aPet")).unwrap();
+        storage.store_address(test_address("bPet")).unwrap();
+        storage.store_address(test_address("cPet")).unwrap();
+        assert_eq!(storage.count_addresses().unwrap(), 3);
+
+        assert_eq!(storage.get_next_address().unwrap().unwrap().address.address, "TestAddressaPet");
+        assert_eq!(storage.get_next_addresses(5).unwrap().len(), 2);
+        assert!(storage.get_next_address().unwrap().is_none());
+
+        let pool = storage.open_pool("Cat").await.unwrap();
+        pool.store_address(test_address("aCat")).unwrap();
+        assert_eq!(pool.count_addresses().unwrap(), 1);
+        assert_eq!(storage.count_addresses().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_restore_requeues_leased_addresses() {
+        let storage: Arc<dyn Storage> =
+            Arc::new(PetStorage::temporary().unwrap().with_lease_ttl(Duration::from_secs(60)));
+        let id = storage.store_address(test_address("aPet")).unwrap();
+        storage.store_address(test_address("bPet")).unwrap();
+        // Let the background writes land
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // A leased address keeps its sled record, so a reload queues it again
+        assert_eq!(storage.get_next_address().unwrap().unwrap().id, id);
+        assert!(storage.lease_expires_at(id).is_some());
+        assert_eq!(storage.count_addresses().unwrap(), 1);
+
+        assert_eq!(storage.restore().await.unwrap(), 2);
+        assert_eq!(storage.count_addresses().unwrap(), 2);
+        assert!(!storage.acknowledge(id));
+    }
+}
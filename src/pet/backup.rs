@@ -0,0 +1,300 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::{Keypair, Signer};
+
+use super::address::{AddressMatcher, PetAddress, PetAddressInfo};
+use super::secret::SecretKey;
+
+/// Line-oriented dump formats for moving addresses between hosts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// One JSON object per line
+    #[default]
+    JsonLines,
+    /// `public_key,private_key,address,created_at` with a header row. Base58
+    /// and RFC 3339 never contain commas or quotes, so no escaping is needed.
+    Csv,
+    /// The 64-byte secret key as a JSON integer array per line, the keyfile
+    /// format of `solana-keygen`. A one-key dump is a keyfile as it stands.
+    /// Keyfiles carry no timestamp, so imported keys count as new.
+    Keypair,
+}
+
+impl DumpFormat {
+    /// `jsonl`, `csv` or `keypair`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "jsonl" => Some(Self::JsonLines),
+            "csv" => Some(Self::Csv),
+            "keypair" => Some(Self::Keypair),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::JsonLines | Self::Keypair => "application/x-ndjson",
+            Self::Csv => "text/csv",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::JsonLines => "jsonl",
+            Self::Csv => "csv",
+            Self::Keypair => "json",
+        }
+    }
+
+    /// First line of a dump, if the format has one
+    pub fn header(self) -> Option<&'static str> {
+        match self {
+            Self::JsonLines | Self::Keypair => None,
+            Self::Csv => Some("public_key,private_key,address,created_at"),
+        }
+    }
+}
+
+/// Encodings of a single secret key, for retrieving one key file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyFileFormat {
+    /// The 64-byte secret key as a JSON integer array, the `solana-keygen` keyfile
+    #[default]
+    Json,
+    /// The 64-byte secret key in base58, as stored and dispensed
+    Base58,
+    /// The 64-byte secret key in lowercase hex
+    Hex,
+}
+
+impl KeyFileFormat {
+    /// `json`, `base58` or `hex`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Self::Json),
+            "base58" => Some(Self::Base58),
+            "hex" => Some(Self::Hex),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Base58 | Self::Hex => "text/plain; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Base58 | Self::Hex => "txt",
+        }
+    }
+
+    /// The secret key of `address`, newline included
+    pub fn encode(self, address: &PetAddress) -> Result<String> {
+        let secret_key = bs58::decode(address.private_key.expose())
+            .into_vec()
+            .map(zeroize::Zeroizing::new)
+            .map_err(|e| anyhow!("invalid private key of {}: {}", address.address, e))?;
+        Ok(match self {
+            Self::Json => format!("{}\n", serde_json::to_string(&*secret_key)?),
+            Self::Base58 => format!("{}\n", address.private_key.expose()),
+            Self::Hex => format!("{}\n", secret_key.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+        })
+    }
+}
+
+/// One exported address. IDs are not kept: the importing pool assigns new
+/// ones, but `created_at` survives so expiry still sees the real key age.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DumpRecord {
+    pub public_key: String,
+    pub private_key: SecretKey,
+    pub address: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DumpRecord {
+    pub fn from_info(address_info: &PetAddressInfo) -> Self {
+        Self {
+            public_key: address_info.address.public_key.clone(),
+            private_key: address_info.address.private_key.clone(),
+            address: address_info.address.address.clone(),
+            created_at: address_info.created_at,
+        }
+    }
+
+    /// The record as one line of `format`, newline included
+    pub fn encode(&self, format: DumpFormat) -> Result<String> {
+        Ok(match format {
+            DumpFormat::JsonLines => format!("{}\n", serde_json::to_string(self)?),
+            DumpFormat::Csv => format!(
+                "{},{},{},{}\n",
+                self.public_key,
+                self.private_key.expose(),
+                self.address,
+                self.created_at.to_rfc3339()
+            ),
+            DumpFormat::Keypair => KeyFileFormat::Json.encode(&self.to_address())?,
+        })
+    }
+
+    pub fn decode(line: &str, format: DumpFormat) -> Result<Self> {
+        match format {
+            DumpFormat::JsonLines => Ok(serde_json::from_str(line)?),
+            DumpFormat::Csv => {
+                let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+                let [public_key, private_key, address, created_at] = fields[..] else {
+                    bail!("expected 4 fields, found {}", fields.len());
+                };
+                Ok(Self {
+                    public_key: public_key.to_string(),
+                    private_key: private_key.into(),
+                    address: address.to_string(),
+                    created_at: DateTime::parse_from_rfc3339(created_at)
+                        .map_err(|e| anyhow!("invalid created_at: {}", e))?
+                        .with_timezone(&Utc),
+                })
+            }
+            DumpFormat::Keypair => {
+                let secret_key = zeroize::Zeroizing::new(serde_json::from_str::<Vec<u8>>(line)?);
+                let keypair = Keypair::try_from(&secret_key[..]).map_err(|e| anyhow!("invalid keypair: {}", e))?;
+                let public_key = keypair.pubkey().to_string();
+                Ok(Self {
+                    address: public_key.clone(),
+                    public_key,
+                    private_key: bs58::encode(&*secret_key).into_string().into(),
+                    created_at: Utc::now(),
+                })
+            }
+        }
+    }
+
+    /// The private key must derive the public key, and the address must
+    /// satisfy the pool's pattern
+    pub fn validate(&self, matcher: &AddressMatcher) -> Result<()> {
+        self.to_address().check_keypair()?;
+        if !matcher.is_match(&self.address) {
+            bail!("{} does not match {}", self.address, matcher.describe());
+        }
+        Ok(())
+    }
+
+    pub fn to_address(&self) -> PetAddress {
+        PetAddress {
+            public_key: self.public_key.clone(),
+            private_key: self.private_key.clone(),
+            address: self.address.clone(),
+        }
+    }
+}
+
+/// Parse and validate a whole dump. Blank lines and the CSV header are
+/// skipped; every other line must hold a valid record, otherwise the errors
+/// (1-based line numbers) are returned and nothing should be imported.
+pub fn parse_dump(body: &str, format: DumpFormat, matcher: &AddressMatcher) -> Result<Vec<DumpRecord>, Vec<String>> {
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+
+    for (index, line) in body.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || Some(line) == format.header() {
+            continue;
+        }
+        match DumpRecord::decode(line, format).and_then(|record| record.validate(matcher).map(|_| record)) {
+            Ok(record) => records.push(record),
+            Err(e) => errors.push(format!("line {}: {}", index + 1, e)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(records)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(matcher: &AddressMatcher) -> DumpRecord {
+        let address = PetAddress::generate(matcher).unwrap();
+        DumpRecord {
+            public_key: address.public_key,
+            private_key: address.private_key,
+            address: address.address,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_records_round_trip_in_both_formats() {
+        let matcher = AddressMatcher::regex(".", true).unwrap();
+        let record = record(&matcher);
+
+        for format in [DumpFormat::JsonLines, DumpFormat::Csv] {
+            let mut dump = format.header().map(|header| format!("{}\n", header)).unwrap_or_default();
+            dump.push_str(&record.encode(format).unwrap());
+            dump.push('\n');
+
+            assert_eq!(parse_dump(&dump, format, &matcher).unwrap(), vec![record.clone()]);
+        }
+    }
+
+    #[test]
+    fn test_keypair_lines_are_solana_keyfiles() {
+        let matcher = AddressMatcher::regex(".", true).unwrap();
+        let record = record(&matcher);
+
+        let line = record.encode(DumpFormat::Keypair).unwrap();
+        let secret_key: Vec<u8> = serde_json::from_str(&line).unwrap();
+        assert_eq!(secret_key.len(), 64);
+        assert_eq!(Keypair::try_from(&secret_key[..]).unwrap().pubkey().to_string(), record.address);
+
+        let imported = parse_dump(&line, DumpFormat::Keypair, &matcher).unwrap();
+        assert_eq!((&imported[0].address, &imported[0].private_key), (&record.address, &record.private_key));
+        assert!(parse_dump("[1, 2, 3]", DumpFormat::Keypair, &matcher).is_err());
+    }
+
+    #[test]
+    fn test_key_file_formats_encode_the_same_secret_key() {
+        let address = PetAddress::from_keypair(&Keypair::new());
+        let secret_key = bs58::decode(address.private_key.expose()).into_vec().unwrap();
+
+        let json = KeyFileFormat::Json.encode(&address).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<u8>>(&json).unwrap(), secret_key);
+        assert_eq!(KeyFileFormat::Base58.encode(&address).unwrap().trim_end(), address.private_key.expose());
+        let hex = KeyFileFormat::Hex.encode(&address).unwrap();
+        assert_eq!(hex.trim_end().len(), 128);
+        assert!(hex.starts_with(&format!("{:02x}", secret_key[0])));
+    }
+
+    #[test]
+    fn test_invalid_records_are_reported_by_line() {
+        let matcher = AddressMatcher::regex(".", true).unwrap();
+        let valid = record(&matcher);
+        let mut stolen = record(&matcher);
+        stolen.private_key = valid.private_key.clone();
+
+        let dump = [
+            valid.encode(DumpFormat::Csv).unwrap(),
+            stolen.encode(DumpFormat::Csv).unwrap(),
+            "not,a,record\n".to_string(),
+        ]
+        .concat();
+        let errors = parse_dump(&dump, DumpFormat::Csv, &matcher).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("line 2: private key does not belong"));
+        assert!(errors[1].starts_with("line 3: expected 4 fields"));
+
+        // Valid keys outside the pool's pattern are refused too
+        let strict = AddressMatcher::regex("^zzzzzzzz", true).unwrap();
+        let dump = valid.encode(DumpFormat::JsonLines).unwrap();
+        let errors = parse_dump(&dump, DumpFormat::JsonLines, &strict).unwrap_err();
+        assert!(errors[0].contains("does not match"), "{:?}", errors);
+    }
+}
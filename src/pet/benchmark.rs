@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::address::{AddressMatcher, PetAddress};
+
+/// Measured grinder throughput, persisted so estimates have real numbers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub threads: usize,
+    pub elapsed_seconds: f64,
+    /// Keys ground by each worker thread
+    pub keys_per_thread: Vec<u64>,
+    pub measured_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl BenchmarkReport {
+    pub fn total_keys(&self) -> u64 {
+        self.keys_per_thread.iter().sum()
+    }
+
+    /// Combined throughput of all threads
+    pub fn keys_per_second(&self) -> f64 {
+        if self.elapsed_seconds > 0.0 {
+            self.total_keys() as f64 / self.elapsed_seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Throughput of each thread
+    pub fn per_thread_keys_per_second(&self) -> Vec<f64> {
+        self.keys_per_thread
+            .iter()
+            .map(|keys| {
+                if self.elapsed_seconds > 0.0 {
+                    *keys as f64 / self.elapsed_seconds
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+}
+
+/// Run the real grind loop on `threads` workers for `duration` against a
+/// suffix that can never match, so every worker grinds for the whole run
+pub fn benchmark(threads: usize, duration: Duration) -> BenchmarkReport {
+    let threads = threads.max(1);
+    // '0' is not base58, so the tail filter rejects every candidate just like a normal miss
+    let never = AddressMatcher::Suffix {
+        suffix: "0".to_string(),
+        case_sensitive: true,
+    };
+    let matchers = [&never];
+    let attempts = AtomicUsize::new(0);
+    let cancel = AtomicBool::new(false);
+    let counters: Vec<AtomicU64> = (0..threads).map(|_| AtomicU64::new(0)).collect();
+
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for counter in &counters {
+            let (matchers, attempts, cancel) = (&matchers, &attempts, &cancel);
+            scope.spawn(move || {
+                PetAddress::search_any(matchers, usize::MAX, attempts, cancel, &mut |keys| {
+                    counter.fetch_add(keys, Ordering::Relaxed);
+                })
+            });
+        }
+
+        thread::sleep(duration);
+        cancel.store(true, Ordering::Relaxed);
+    });
+
+    BenchmarkReport {
+        threads,
+        elapsed_seconds: start.elapsed().as_secs_f64(),
+        keys_per_thread: counters.iter().map(|c| c.load(Ordering::Relaxed)).collect(),
+        measured_at: chrono::Utc::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benchmark_reports_every_thread() {
+        let report = benchmark(2, Duration::from_millis(200));
+
+        assert_eq!(report.threads, 2);
+        assert_eq!(report.keys_per_thread.len(), 2);
+        assert!(report.keys_per_thread.iter().all(|keys| *keys > 0));
+        assert!(report.elapsed_seconds >= 0.2);
+        assert!(report.keys_per_second() > 0.0);
+
+        let per_thread: f64 = report.per_thread_keys_per_second().iter().sum();
+        assert!((per_thread - report.keys_per_second()).abs() < 1e-6);
+    }
+}
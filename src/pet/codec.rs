@@ -0,0 +1,74 @@
+//! Byte encoding of stored address records. Records are bincode behind a
+//! leading version byte; records written before that are JSON objects,
+//! recognised by their leading `{`, and still decode.
+
+use anyhow::{bail, Context, Result};
+
+use super::address::PetAddressInfo;
+
+/// Version byte of the current binary layout. Never `b'{'`, so it cannot be
+/// mistaken for a legacy JSON record.
+const RECORD_V1: u8 = 1;
+
+pub fn encode(address_info: &PetAddressInfo) -> Result<Vec<u8>> {
+    let mut bytes = vec![RECORD_V1];
+    bincode::serialize_into(&mut bytes, address_info).context("Failed to serialize address info")?;
+    Ok(bytes)
+}
+
+/// Decode a record in the current layout or legacy JSON
+pub fn decode(bytes: &[u8]) -> Result<PetAddressInfo> {
+    match bytes.first() {
+        Some(&RECORD_V1) => bincode::deserialize(&bytes[1..]).context("Failed to deserialize address info"),
+        Some(b'{') => serde_json::from_slice(bytes).context("Failed to deserialize address info"),
+        Some(version) => bail!("Unknown address record version {}", version),
+        None => bail!("Empty address record"),
+    }
+}
+
+/// Whether `bytes` is in the current layout; older records are rewritten on load
+pub fn is_current(bytes: &[u8]) -> bool {
+    bytes.first() == Some(&RECORD_V1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::PetAddress;
+
+    fn record() -> PetAddressInfo {
+        PetAddressInfo {
+            id: 42,
+            address: PetAddress {
+                public_key: "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin".to_string(),
+                private_key: "4wBqpZM9xaSheZzJSMawUKKwhdpChKbZ5eu5ky4Vigw6Vz1b5yNmFzEdsTDDrMh9t5wbxWiH3JVdnP9EM6BdkU5m"
+                    .into(),
+                address: "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin".to_string(),
+            },
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_binary_records_round_trip_and_are_smaller() {
+        let record = record();
+        let bytes = encode(&record).unwrap();
+        assert!(is_current(&bytes));
+        assert!(bytes.len() < serde_json::to_vec(&record).unwrap().len());
+
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!((decoded.id, decoded.created_at), (record.id, record.created_at));
+        assert_eq!(decoded.address.private_key, record.address.private_key);
+    }
+
+    #[test]
+    fn test_legacy_json_records_still_decode() {
+        let record = record();
+        let json = serde_json::to_vec(&record).unwrap();
+        assert!(!is_current(&json));
+        assert_eq!(decode(&json).unwrap().address.public_key, record.address.public_key);
+
+        assert!(decode(&[]).is_err());
+        assert!(decode(&[9, 1, 2]).unwrap_err().to_string().contains("version 9"));
+    }
+}
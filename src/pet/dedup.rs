@@ -0,0 +1,158 @@
+use anyhow::Result;
+use sled::Db;
+use std::hash::BuildHasher;
+use std::sync::Mutex;
+
+/// Sled key prefix of the membership set, shared by every pool
+const SEEN_PREFIX: &str = "seen:";
+/// Expected distinct public keys; beyond this the false-positive rate rises,
+/// which only costs extra sled lookups
+const EXPECTED_KEYS: usize = 1_000_000;
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// Fixed-size Bloom filter over strings: "definitely new" or "maybe seen"
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+    hasher: std::collections::hash_map::RandomState,
+}
+
+impl BloomFilter {
+    pub fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        let n = expected_items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(n * false_positive_rate.ln()) / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let num_hashes = ((num_bits as f64 / n) * ln2).round().max(1.0) as u32;
+
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+            hasher: Default::default(),
+        }
+    }
+
+    pub fn insert(&mut self, item: &str) {
+        let positions: Vec<u64> = self.positions(item).collect();
+        for bit in positions {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        self.positions(item)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Double hashing: bit i is h1 + i * h2
+    fn positions(&self, item: &str) -> impl Iterator<Item = u64> + '_ {
+        let h1 = self.hasher.hash_one(item);
+        let h2 = h1.rotate_left(32) | 1;
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits)
+    }
+}
+
+/// Every public key ever enqueued in this database. The sled set under
+/// `seen:` is authoritative; the Bloom filter in front of it answers the
+/// common "never seen" case without a lookup.
+pub struct SeenKeys {
+    db: Db,
+    bloom: Mutex<BloomFilter>,
+}
+
+impl SeenKeys {
+    /// Load the existing membership set into the filter
+    pub fn load(db: Db) -> Result<Self> {
+        let mut bloom = BloomFilter::new(EXPECTED_KEYS, FALSE_POSITIVE_RATE);
+        for result in db.scan_prefix(SEEN_PREFIX.as_bytes()) {
+            let (key, _) = result?;
+            if let Some(public_key) = key.strip_prefix(SEEN_PREFIX.as_bytes()) {
+                bloom.insert(&String::from_utf8_lossy(public_key));
+            }
+        }
+
+        Ok(Self {
+            db,
+            bloom: Mutex::new(bloom),
+        })
+    }
+
+    /// Record `public_key`; false when it was already recorded.
+    /// Check and insert happen under one lock, so concurrent callers with
+    /// the same key cannot both succeed.
+    pub fn insert(&self, public_key: &str) -> Result<bool> {
+        let mut bloom = self.bloom.lock().unwrap_or_else(|e| e.into_inner());
+        let key = format!("{}{}", SEEN_PREFIX, public_key);
+
+        if bloom.contains(public_key) && self.db.contains_key(key.as_bytes())? {
+            return Ok(false);
+        }
+
+        // Written directly (not via the background writer) so the set is
+        // exact even for keys stored a moment ago
+        self.db.insert(key.as_bytes(), &[])?;
+        bloom.insert(public_key);
+        Ok(true)
+    }
+
+    /// Forget `public_key` after a store that recorded it failed. The Bloom
+    /// filter keeps the key, which only costs a lookup if it comes back.
+    pub fn remove(&self, public_key: &str) -> Result<()> {
+        let _bloom = self.bloom.lock().unwrap_or_else(|e| e.into_inner());
+        self.db.remove(format!("{}{}", SEEN_PREFIX, public_key).as_bytes())?;
+        Ok(())
+    }
+
+    /// Every recorded public key
+    pub fn public_keys(&self) -> Result<Vec<String>> {
+        self.db
+            .scan_prefix(SEEN_PREFIX.as_bytes())
+            .map(|result| {
+                let (key, _) = result?;
+                Ok(String::from_utf8_lossy(&key[SEEN_PREFIX.len()..]).into_owned())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter_has_no_false_negatives() {
+        let mut bloom = BloomFilter::new(1000, 0.01);
+        for i in 0..1000 {
+            bloom.insert(&format!("key-{}", i));
+        }
+        assert!((0..1000).all(|i| bloom.contains(&format!("key-{}", i))));
+
+        let false_positives = (0..10_000).filter(|i| bloom.contains(&format!("other-{}", i))).count();
+        assert!(false_positives < 500, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn test_seen_keys_survive_reload() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let seen = SeenKeys::load(db.clone()).unwrap();
+        assert!(seen.insert("aPet").unwrap());
+        assert!(!seen.insert("aPet").unwrap());
+        assert!(seen.insert("bPet").unwrap());
+
+        let reloaded = SeenKeys::load(db).unwrap();
+        assert!(!reloaded.insert("aPet").unwrap());
+        assert!(reloaded.insert("cPet").unwrap());
+        assert_eq!(reloaded.public_keys().unwrap(), ["aPet", "bPet", "cPet"]);
+    }
+
+    #[test]
+    fn test_removed_key_can_be_stored_again() {
+        let seen = SeenKeys::load(sled::Config::new().temporary(true).open().unwrap()).unwrap();
+        assert!(seen.insert("aPet").unwrap());
+        seen.remove("aPet").unwrap();
+        // Still in the Bloom filter, but sled is the authority
+        assert!(seen.insert("aPet").unwrap());
+        assert!(!seen.insert("aPet").unwrap());
+    }
+}
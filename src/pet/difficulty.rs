@@ -0,0 +1,269 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use regex_syntax::hir::{Class, Hir, HirKind, Look};
+use regex_syntax::ParserBuilder;
+
+use super::address::{AddressMatcher, PatternSpec};
+use crate::utils::BASE58_ALPHABET;
+
+/// Typical length of a base58-encoded Solana public key (43 or 44 characters)
+const ADDRESS_LEN: usize = 44;
+
+/// Expected cost of grinding one address for a pattern
+#[derive(Debug, Clone)]
+pub struct DifficultyEstimate {
+    /// Chance that a single random key matches
+    pub probability: f64,
+    /// Keys to try on average per hit (1 / probability)
+    pub expected_attempts: f64,
+    /// Throughput used for the time estimate, if known
+    pub keys_per_second: Option<f64>,
+    /// Expected wall-clock seconds per address at `keys_per_second`
+    pub estimated_seconds: Option<f64>,
+}
+
+/// Estimate difficulty of `matcher`, treating address characters as uniform
+/// over the base58 alphabet. Regexes are supported when built from literals,
+/// classes, fixed repetitions, alternations and `.*`-style gaps.
+pub fn estimate(matcher: &AddressMatcher, keys_per_second: Option<f64>) -> Result<DifficultyEstimate> {
+    let probability = match_probability(matcher)?;
+    let expected_attempts = if probability > 0.0 { 1.0 / probability } else { f64::INFINITY };
+    let keys_per_second = keys_per_second.filter(|rate| *rate > 0.0);
+
+    Ok(DifficultyEstimate {
+        probability,
+        expected_attempts,
+        keys_per_second,
+        estimated_seconds: keys_per_second.map(|rate| expected_attempts / rate),
+    })
+}
+
+/// Probability that one random address matches `matcher`
+pub fn match_probability(matcher: &AddressMatcher) -> Result<f64> {
+    match matcher {
+        AddressMatcher::Suffix { suffix, case_sensitive } => {
+            // The character before the suffix must be a lowercase letter
+            let lowercase = BASE58_ALPHABET.bytes().filter(u8::is_ascii_lowercase).count() as f64 / 58.0;
+            Ok(lowercase * literal_probability(suffix, *case_sensitive))
+        }
+        AddressMatcher::Spec(spec) => Ok(spec_probability(spec)),
+        AddressMatcher::Regex(regex) => regex_probability(regex),
+        AddressMatcher::Sharded { inner, letters } => {
+            // Only `letters` of the lowercase letters before the suffix count
+            let lowercase = BASE58_ALPHABET.bytes().filter(u8::is_ascii_lowercase).count() as f64;
+            Ok(match_probability(inner)? * letters.len() as f64 / lowercase)
+        }
+    }
+}
+
+fn spec_probability(spec: &PatternSpec) -> f64 {
+    literal_probability(&spec.prefix, spec.case_sensitive) * literal_probability(&spec.suffix, spec.case_sensitive)
+}
+
+/// Chance that a fixed position holds `literal`
+fn literal_probability(literal: &str, case_sensitive: bool) -> f64 {
+    literal
+        .chars()
+        .map(|c| {
+            let variants = if case_sensitive {
+                vec![c]
+            } else {
+                let mut variants = vec![c.to_ascii_lowercase(), c.to_ascii_uppercase()];
+                variants.dedup();
+                variants
+            };
+            variants.iter().filter(|v| BASE58_ALPHABET.contains(**v)).count() as f64 / 58.0
+        })
+        .product()
+}
+
+fn regex_probability(regex: &Regex) -> Result<f64> {
+    // Case-insensitive matchers carry a leading (?i), which turns literals into classes
+    let hir = ParserBuilder::new()
+        .build()
+        .parse(regex.as_str())
+        .map_err(|e| anyhow!("Invalid address pattern '{}': {}", regex.as_str(), e))?;
+
+    let branches = match hir.kind() {
+        HirKind::Alternation(branches) => branches.iter().collect(),
+        _ => vec![&hir],
+    };
+
+    let total: f64 = branches
+        .into_iter()
+        .map(branch_probability)
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(|| anyhow!("Cannot estimate difficulty of pattern '{}'", regex.as_str()))?
+        .into_iter()
+        .sum();
+
+    Ok(total.min(1.0))
+}
+
+/// One top-level branch: anchors, fixed-width segments and wildcard gaps
+fn branch_probability(hir: &Hir) -> Option<f64> {
+    let items: Vec<&Hir> = match hir.kind() {
+        HirKind::Concat(items) => items.iter().collect(),
+        HirKind::Capture(capture) => return branch_probability(&capture.sub),
+        _ => vec![hir],
+    };
+
+    let anchored_start = items.first().is_some_and(|item| is_look(item, Look::Start));
+    let anchored_end = items.last().is_some_and(|item| is_look(item, Look::End));
+    let body = &items[usize::from(anchored_start)..items.len() - usize::from(anchored_end)];
+
+    // Split the body into fixed-width segments separated by `.*`-style gaps
+    let mut segments: Vec<(usize, f64)> = vec![(0, 1.0)];
+    for item in body {
+        if is_wildcard_gap(item) {
+            segments.push((0, 1.0));
+            continue;
+        }
+        let (width, probability) = fixed_probability(item)?;
+        let current = segments.last_mut()?;
+        current.0 += width;
+        current.1 *= probability;
+    }
+
+    let last = segments.len() - 1;
+    let probability = segments
+        .iter()
+        .enumerate()
+        .map(|(index, (width, probability))| {
+            let pinned = (index == 0 && anchored_start) || (index == last && anchored_end);
+            if pinned || *width == 0 {
+                *probability
+            } else {
+                // Unanchored segment may start at any position
+                probability * ADDRESS_LEN.saturating_sub(*width).saturating_add(1) as f64
+            }
+        })
+        .product::<f64>();
+
+    Some(probability.min(1.0))
+}
+
+/// Width and probability of a sub-pattern that always matches the same number of characters
+fn fixed_probability(hir: &Hir) -> Option<(usize, f64)> {
+    match hir.kind() {
+        HirKind::Empty => Some((0, 1.0)),
+        HirKind::Literal(literal) => {
+            let probability = literal
+                .0
+                .iter()
+                .map(|b| if BASE58_ALPHABET.as_bytes().contains(b) { 1.0 / 58.0 } else { 0.0 })
+                .product();
+            Some((literal.0.len(), probability))
+        }
+        HirKind::Class(class) => Some((1, class_probability(class))),
+        HirKind::Capture(capture) => fixed_probability(&capture.sub),
+        HirKind::Concat(items) => items.iter().try_fold((0, 1.0), |(width, probability), item| {
+            let (w, p) = fixed_probability(item)?;
+            Some((width + w, probability * p))
+        }),
+        HirKind::Alternation(branches) => {
+            let parts = branches.iter().map(fixed_probability).collect::<Option<Vec<_>>>()?;
+            let width = parts.first()?.0;
+            if parts.iter().any(|(w, _)| *w != width) {
+                return None;
+            }
+            Some((width, parts.iter().map(|(_, p)| p).sum::<f64>().min(1.0)))
+        }
+        HirKind::Repetition(repetition) if repetition.max == Some(repetition.min) => {
+            let (width, probability) = fixed_probability(&repetition.sub)?;
+            Some((width * repetition.min as usize, probability.powi(repetition.min as i32)))
+        }
+        _ => None,
+    }
+}
+
+/// Share of the base58 alphabet accepted by a character class
+fn class_probability(class: &Class) -> f64 {
+    let accepted = BASE58_ALPHABET
+        .chars()
+        .filter(|c| match class {
+            Class::Unicode(unicode) => unicode.iter().any(|range| range.start() <= *c && *c <= range.end()),
+            Class::Bytes(bytes) => {
+                let b = *c as u8;
+                bytes.iter().any(|range| range.start() <= b && b <= range.end())
+            }
+        })
+        .count();
+    accepted as f64 / 58.0
+}
+
+fn is_look(hir: &Hir, look: Look) -> bool {
+    matches!(hir.kind(), HirKind::Look(l) if *l == look)
+}
+
+/// Unbounded repetition of something every base58 character satisfies, e.g. `.*` or `\w+`
+fn is_wildcard_gap(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Repetition(repetition) if repetition.max.is_none() => {
+            matches!(fixed_probability(&repetition.sub), Some((1, p)) if p >= 1.0)
+        }
+        HirKind::Capture(capture) => is_wildcard_gap(&capture.sub),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= expected * 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_default_suffix_probability() {
+        // 25 lowercase base58 letters (no 'l'), then three fixed characters
+        let probability = match_probability(&AddressMatcher::default()).unwrap();
+        assert_close(probability, 25.0 / 58f64.powi(4));
+
+        let timed = estimate(&AddressMatcher::default(), Some(100_000.0)).unwrap();
+        assert_close(timed.expected_attempts, 58f64.powi(4) / 25.0);
+        assert_close(timed.estimated_seconds.unwrap(), timed.expected_attempts / 100_000.0);
+        assert!(estimate(&AddressMatcher::default(), None).unwrap().estimated_seconds.is_none());
+    }
+
+    #[test]
+    fn test_regex_matches_equivalent_suffix() {
+        let regex = AddressMatcher::regex("[a-z]Pet$", true).unwrap();
+        assert_close(
+            match_probability(&regex).unwrap(),
+            match_probability(&AddressMatcher::default()).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive_is_easier() {
+        let sensitive = match_probability(&AddressMatcher::suffix("Pet", true).unwrap()).unwrap();
+        let insensitive = match_probability(&AddressMatcher::suffix("Pet", false).unwrap()).unwrap();
+        assert!(insensitive > sensitive);
+
+        let regex = match_probability(&AddressMatcher::regex("(?i)^pin", false).unwrap()).unwrap();
+        // 'i' has no uppercase variant in base58
+        assert_close(regex, 2.0 * 1.0 * 2.0 / 58f64.powi(3));
+    }
+
+    #[test]
+    fn test_prefix_gap_suffix_and_contains() {
+        let both = match_probability(&AddressMatcher::regex("^Pin.*Pet$", true).unwrap()).unwrap();
+        assert_close(both, 1.0 / 58f64.powi(6));
+
+        let contains = match_probability(&AddressMatcher::regex("Cafe", true).unwrap()).unwrap();
+        assert_close(contains, 41.0 / 58f64.powi(4));
+    }
+
+    #[test]
+    fn test_unsupported_regex_is_an_error() {
+        let matcher = AddressMatcher::regex("^a+b", true).unwrap();
+        assert!(match_probability(&matcher).is_err());
+    }
+}
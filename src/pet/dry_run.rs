@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+use super::address::{AddressMatcher, PetAddress};
+use super::grinder::Grinder;
+
+/// Result of a dry run: addresses generated in memory and how long it took
+#[derive(Debug)]
+pub struct DryRunReport {
+    pub addresses: Vec<PetAddress>,
+    pub elapsed: Duration,
+}
+
+impl DryRunReport {
+    /// Addresses found per second
+    pub fn rate(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.addresses.len() as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Generate `count` addresses matching `matcher` in memory without storage or
+/// server, to check feasibility and speed of the pattern on this machine
+pub fn dry_run(grinder: &Grinder, matcher: &AddressMatcher, count: usize) -> DryRunReport {
+    let start = Instant::now();
+    let mut addresses = Vec::with_capacity(count);
+
+    while addresses.len() < count {
+        match grinder.generate(matcher) {
+            Some(address) => addresses.push(address),
+            None => tracing::warn!("Dry run attempt exhausted without a match, retrying"),
+        }
+    }
+
+    DryRunReport {
+        addresses,
+        elapsed: start.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dry_run_produces_requested_count() {
+        let report = dry_run(&Grinder::new(0), &AddressMatcher::default(), 1);
+
+        assert_eq!(report.addresses.len(), 1);
+        for address in &report.addresses {
+            assert!(PetAddress::is_valid_pet_suffix(&address.address));
+            assert_eq!(address.address, address.public_key);
+        }
+        assert!(report.rate() > 0.0);
+    }
+
+    #[test]
+    fn test_dry_run_zero_count() {
+        let report = dry_run(&Grinder::new(0), &AddressMatcher::default(), 0);
+        assert!(report.addresses.is_empty());
+    }
+}
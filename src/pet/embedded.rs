@@ -0,0 +1,208 @@
+//! Grinding as a library: `Generator::builder().suffix("Pet").threads(4).build()?`
+//! yields matching keypairs through an iterator or a `Stream`, without the
+//! HTTP server, storage or a tokio runtime.
+
+use anyhow::{anyhow, Result};
+use futures_util::Stream;
+use solana_sdk::signature::Keypair;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+
+use super::address::{AddressMatcher, PetAddress, MAX_ATTEMPTS, DEFAULT_TARGET_SUFFIX};
+use super::grinder::Grinder;
+use super::stats::GeneratorStats;
+
+/// Configures a [`Generator`]. Without a pattern the server's default
+/// `[a-z]Pet` rule is used.
+#[derive(Debug, Clone)]
+pub struct GeneratorBuilder {
+    pattern: Option<String>,
+    suffix: Option<String>,
+    matcher: Option<AddressMatcher>,
+    case_sensitive: bool,
+    threads: usize,
+    max_attempts: usize,
+}
+
+impl Default for GeneratorBuilder {
+    fn default() -> Self {
+        Self {
+            pattern: None,
+            suffix: None,
+            matcher: None,
+            case_sensitive: true,
+            threads: 0,
+            max_attempts: MAX_ATTEMPTS,
+        }
+    }
+}
+
+impl GeneratorBuilder {
+    /// Regex the address must match, e.g. `^Pin` or `[a-z]Pet$`
+    pub fn pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.pattern = Some(pattern.into());
+        self
+    }
+
+    /// Lowercase letter followed by `suffix`, like the server's `target_suffix`
+    pub fn suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = Some(suffix.into());
+        self
+    }
+
+    /// A ready matcher; takes precedence over `pattern` and `suffix`
+    pub fn matcher(mut self, matcher: AddressMatcher) -> Self {
+        self.matcher = Some(matcher);
+        self
+    }
+
+    /// Applies to `pattern` and `suffix` (default: true)
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// Worker threads per keypair; 0 (the default) uses every available core
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Attempts shared by all workers before `Generator::generate` gives up
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Fails if the pattern is invalid or can never match a base58 address
+    pub fn build(self) -> Result<Generator> {
+        if self.pattern.is_some() && self.suffix.is_some() && self.matcher.is_none() {
+            return Err(anyhow!("set either a pattern or a suffix, not both"));
+        }
+        let matcher = match (self.matcher, self.pattern, self.suffix) {
+            (Some(matcher), _, _) => matcher,
+            (None, Some(pattern), _) => AddressMatcher::regex(&pattern, self.case_sensitive)?,
+            (None, None, Some(suffix)) => AddressMatcher::suffix(&suffix, self.case_sensitive)?,
+            (None, None, None) => AddressMatcher::suffix(DEFAULT_TARGET_SUFFIX, self.case_sensitive)?,
+        };
+        Ok(Generator {
+            grinder: Grinder::new(self.threads).with_max_attempts(self.max_attempts),
+            matcher,
+        })
+    }
+}
+
+/// Grinds keypairs whose address matches a pattern. Clones share the
+/// worker configuration and statistics.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    grinder: Grinder,
+    matcher: AddressMatcher,
+}
+
+impl Generator {
+    pub fn builder() -> GeneratorBuilder {
+        GeneratorBuilder::default()
+    }
+
+    pub fn matcher(&self) -> &AddressMatcher {
+        &self.matcher
+    }
+
+    /// Keys tried, keys/sec and time-to-find so far
+    pub fn stats(&self) -> GeneratorStats {
+        self.grinder.stats()
+    }
+
+    /// Grind one matching keypair on the calling thread (and its workers);
+    /// `None` once `max_attempts` is used up
+    pub fn generate(&self) -> Option<Keypair> {
+        self.grinder.generate(&self.matcher).and_then(into_keypair)
+    }
+
+    /// Endless blocking iterator of matching keypairs; use `take(n)` to bound it
+    pub fn keypairs(&self) -> Keypairs<'_> {
+        Keypairs { generator: self }
+    }
+
+    /// Grind on a background thread and yield keypairs as they are found.
+    /// Up to `buffer` keypairs are found ahead of the consumer. Works on any
+    /// executor; the thread stops after its current search once the stream
+    /// is dropped.
+    pub fn into_stream(self, buffer: usize) -> KeypairStream {
+        let (sender, receiver) = mpsc::channel(buffer.max(1));
+        std::thread::Builder::new()
+            .name("pinpet-generator".to_string())
+            .spawn(move || {
+                for keypair in self.keypairs() {
+                    if sender.blocking_send(keypair).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn generator thread");
+        KeypairStream { receiver }
+    }
+}
+
+fn into_keypair(address: PetAddress) -> Option<Keypair> {
+    address
+        .to_keypair()
+        .inspect_err(|e| tracing::error!("Ground an unreadable keypair for {}: {}", address.address, e))
+        .ok()
+}
+
+/// Iterator returned by [`Generator::keypairs`]
+pub struct Keypairs<'a> {
+    generator: &'a Generator,
+}
+
+impl Iterator for Keypairs<'_> {
+    type Item = Keypair;
+
+    fn next(&mut self) -> Option<Keypair> {
+        loop {
+            if let Some(keypair) = self.generator.generate() {
+                return Some(keypair);
+            }
+        }
+    }
+}
+
+/// Stream returned by [`Generator::into_stream`]
+pub struct KeypairStream {
+    receiver: mpsc::Receiver<Keypair>,
+}
+
+impl Stream for KeypairStream {
+    type Item = Keypair;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Keypair>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use solana_sdk::signature::Signer;
+
+    #[tokio::test]
+    async fn test_builder_yields_matching_keypairs() {
+        let generator = Generator::builder().pattern("^[1-9]").threads(2).build().unwrap();
+        let keypairs: Vec<Keypair> = generator.keypairs().take(3).collect();
+        assert_eq!(keypairs.len(), 3);
+        for keypair in &keypairs {
+            assert!(generator.matcher().is_match(&keypair.pubkey().to_string()));
+        }
+        assert_eq!(generator.stats().addresses_found, 3);
+
+        let streamed: Vec<Keypair> = generator.into_stream(2).take(2).collect().await;
+        assert_eq!(streamed.len(), 2);
+
+        assert!(Generator::builder().pattern("0").build().is_err());
+        assert!(Generator::builder().pattern("a").suffix("Pet").build().is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::{anyhow, bail, Context, Result};
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use rand::RngCore;
+use std::collections::HashMap;
+
+use super::address::{PetAddress, PetAddressInfo};
+use super::secret::SecretKey;
+use crate::config::Secrets;
+
+/// Secret holding the encryption keys as `id:hex,id:hex` (each key is
+/// 32 bytes / 64 hex characters): the variable, `{NAME}_FILE` or Vault
+pub const ENCRYPTION_KEYS_ENV: &str = "PRIVATE_KEY_ENCRYPTION_KEYS";
+
+/// Marker of a sealed value: `enc:v1:{key_id}:{nonce}:{ciphertext}:{tag}` (hex fields)
+const SEALED_PREFIX: &str = "enc:v1:";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// AES-256-GCM keys by ID. New values are sealed with the active key; any
+/// key still in the ring can open values it sealed, so keys can be rotated
+/// by adding a new active key and keeping the old one until records are rewritten.
+pub struct KeyRing {
+    active_key_id: String,
+    keys: HashMap<String, [u8; KEY_LEN]>,
+}
+
+impl KeyRing {
+    pub fn new(active_key_id: &str, keys: HashMap<String, [u8; KEY_LEN]>) -> Result<Self> {
+        if let Some(id) = keys.keys().find(|id| id.is_empty() || id.contains(':')) {
+            bail!("Invalid encryption key ID '{}': must be non-empty and contain no ':'", id);
+        }
+        if !keys.contains_key(active_key_id) {
+            bail!("Active encryption key '{}' is not among the configured keys", active_key_id);
+        }
+
+        Ok(Self {
+            active_key_id: active_key_id.to_string(),
+            keys,
+        })
+    }
+
+    /// Load keys from the `PRIVATE_KEY_ENCRYPTION_KEYS` secret
+    pub fn from_secrets(secrets: &Secrets, active_key_id: &str) -> Result<Self> {
+        let spec = secrets
+            .get(ENCRYPTION_KEYS_ENV)?
+            .with_context(|| format!("Encryption is enabled but {} is not set", ENCRYPTION_KEYS_ENV))?;
+        Self::parse(active_key_id, &spec)
+    }
+
+    /// Parse `id:hex,id:hex`
+    pub fn parse(active_key_id: &str, spec: &str) -> Result<Self> {
+        let mut keys = HashMap::new();
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (id, hex) = entry
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Encryption key entry must be 'id:hex'"))?;
+            let key: [u8; KEY_LEN] = decode_hex(hex)
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| anyhow!("Encryption key '{}' must be {} hex characters", id, KEY_LEN * 2))?;
+            keys.insert(id.to_string(), key);
+        }
+
+        Self::new(active_key_id, keys)
+    }
+
+    pub fn active_key_id(&self) -> &str {
+        &self.active_key_id
+    }
+
+    /// Seal `plaintext` with the active key. `aad` is authenticated but not
+    /// stored, so the value only opens alongside the same `aad`.
+    pub fn encrypt(&self, plaintext: &str, aad: &[u8]) -> Result<String> {
+        let key = &self.keys[&self.active_key_id];
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let mut tag = [0u8; TAG_LEN];
+        let ciphertext = encrypt_aead(Cipher::aes_256_gcm(), key, Some(&nonce), aad, plaintext.as_bytes(), &mut tag)
+            .context("Failed to encrypt private key")?;
+
+        Ok(format!(
+            "{}{}:{}:{}:{}",
+            SEALED_PREFIX,
+            self.active_key_id,
+            encode_hex(&nonce),
+            encode_hex(&ciphertext),
+            encode_hex(&tag)
+        ))
+    }
+
+    /// Open a value produced by `encrypt` with any key in the ring
+    pub fn decrypt(&self, sealed: &str, aad: &[u8]) -> Result<SecretKey> {
+        let body = sealed
+            .strip_prefix(SEALED_PREFIX)
+            .ok_or_else(|| anyhow!("Value is not encrypted"))?;
+        let fields: Vec<&str> = body.split(':').collect();
+        let [key_id, nonce, ciphertext, tag] = fields[..] else {
+            bail!("Malformed encrypted value");
+        };
+
+        let key = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| anyhow!("Unknown encryption key '{}'", key_id))?;
+        let (Some(nonce), Some(ciphertext), Some(tag)) = (decode_hex(nonce), decode_hex(ciphertext), decode_hex(tag)) else {
+            bail!("Malformed encrypted value");
+        };
+
+        let plaintext = decrypt_aead(Cipher::aes_256_gcm(), key, Some(&nonce), aad, &ciphertext, &tag)
+            .map_err(|_| anyhow!("Failed to decrypt private key with key '{}'", key_id))?;
+        // Wipe the plaintext bytes too if they are not valid UTF-8
+        let plaintext = zeroize::Zeroizing::new(plaintext);
+        let key = std::str::from_utf8(&plaintext).context("Decrypted private key is not UTF-8")?;
+        Ok(SecretKey::from(key))
+    }
+
+    /// Copy of `address_info` with its private key sealed. The public address
+    /// is bound as associated data so sealed keys cannot be swapped between records.
+    pub fn seal_record(&self, address_info: &PetAddressInfo) -> Result<PetAddressInfo> {
+        let address = &address_info.address;
+        Ok(PetAddressInfo {
+            id: address_info.id,
+            address: PetAddress {
+                public_key: address.public_key.clone(),
+                private_key: self.encrypt(&address.private_key, address.address.as_bytes())?.into(),
+                address: address.address.clone(),
+            },
+            created_at: address_info.created_at,
+        })
+    }
+
+    /// Undo `seal_record`; records that were never sealed pass through unchanged
+    pub fn open_record(&self, mut address_info: PetAddressInfo) -> Result<PetAddressInfo> {
+        if is_sealed(&address_info.address.private_key) {
+            address_info.address.private_key = self
+                .decrypt(&address_info.address.private_key, address_info.address.address.as_bytes())
+                .with_context(|| format!("Failed to decrypt address {}", address_info.id))?;
+        }
+        Ok(address_info)
+    }
+}
+
+/// Whether `value` was produced by `KeyRing::encrypt`
+pub fn is_sealed(value: &str) -> bool {
+    value.starts_with(SEALED_PREFIX)
+}
+
+/// ID of the key that sealed `value`
+pub fn sealed_key_id(value: &str) -> Option<&str> {
+    value.strip_prefix(SEALED_PREFIX)?.split(':').next()
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD_KEY: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+    const NEW_KEY: &str = "00000000000000000000000000000000000000000000000000000000000000ff";
+
+    #[test]
+    fn test_roundtrip_and_rotation() {
+        let old = KeyRing::parse("old", &format!("old:{}", OLD_KEY)).unwrap();
+        let sealed = old.encrypt("secret", b"address").unwrap();
+        assert!(is_sealed(&sealed));
+        assert_eq!(sealed_key_id(&sealed), Some("old"));
+        assert!(!sealed.contains("secret"));
+        assert_eq!(old.decrypt(&sealed, b"address").unwrap(), "secret");
+
+        // After rotation the old key still opens old values
+        let rotated = KeyRing::parse("new", &format!("old:{},new:{}", OLD_KEY, NEW_KEY)).unwrap();
+        assert_eq!(rotated.decrypt(&sealed, b"address").unwrap(), "secret");
+        assert_eq!(sealed_key_id(&rotated.encrypt("secret", b"address").unwrap()), Some("new"));
+
+        let without_old = KeyRing::parse("new", &format!("new:{}", NEW_KEY)).unwrap();
+        assert!(without_old.decrypt(&sealed, b"address").is_err());
+    }
+
+    #[test]
+    fn test_tampering_is_rejected() {
+        let ring = KeyRing::parse("k", &format!("k:{}", OLD_KEY)).unwrap();
+        let sealed = ring.encrypt("secret", b"address").unwrap();
+
+        // Bound to the address it was sealed for
+        assert!(ring.decrypt(&sealed, b"other").is_err());
+
+        let mut flipped = sealed.clone();
+        let last = flipped.pop().unwrap();
+        flipped.push(if last == '0' { '1' } else { '0' });
+        assert!(ring.decrypt(&flipped, b"address").is_err());
+    }
+
+    #[test]
+    fn test_invalid_key_specs() {
+        assert!(KeyRing::parse("k", "k:abcd").is_err());
+        assert!(KeyRing::parse("missing", &format!("k:{}", OLD_KEY)).is_err());
+        assert!(KeyRing::parse("k", OLD_KEY).is_err());
+    }
+}
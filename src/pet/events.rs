@@ -0,0 +1,91 @@
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Events slow subscribers may fall behind by before they start missing some
+const EVENT_BUFFER: usize = 256;
+
+/// Generator activity streamed to dashboards. Never carries key material.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GeneratorEvent {
+    /// Periodic throughput sample while grinding
+    Progress { keys_per_second: f64, total_keys: u64 },
+    /// A matching address was ground and stored
+    AddressFound { pool: String, id: u64, address: String },
+    /// A pool's queue depth changed (address stored or dispensed)
+    QueueDepth { pool: String, depth: usize, target: usize },
+    /// Grinding started (pools below target) or paused (all pools full)
+    GenerationActive { active: bool },
+}
+
+impl GeneratorEvent {
+    /// Name used as the SSE `event:` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Progress { .. } => "progress",
+            Self::AddressFound { .. } => "address_found",
+            Self::QueueDepth { .. } => "queue_depth",
+            Self::GenerationActive { .. } => "generation_active",
+        }
+    }
+}
+
+/// Fan-out channel for `GeneratorEvent`s; publishing with no subscribers is a no-op
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<GeneratorEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: GeneratorEvent) {
+        // Err only means nobody is listening
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<GeneratorEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribers_receive_published_events() {
+        let bus = EventBus::new();
+        bus.publish(GeneratorEvent::GenerationActive { active: true }); // nobody listening yet
+
+        let mut receiver = bus.subscribe();
+        assert_eq!(bus.subscriber_count(), 1);
+
+        let event = GeneratorEvent::QueueDepth {
+            pool: "default".to_string(),
+            depth: 3,
+            target: 10,
+        };
+        bus.publish(event.clone());
+
+        assert_eq!(receiver.recv().await.unwrap(), event);
+        assert_eq!(event.name(), "queue_depth");
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "queue_depth");
+        assert_eq!(json["depth"], 3);
+    }
+}
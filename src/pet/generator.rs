@@ -1,26 +1,242 @@
-use anyhow::Result;
-use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex};
-use tokio::time::{sleep, Duration};
-use tracing::{info, warn, error};
+use anyhow::{bail, Result};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::{mpsc, Mutex, Notify};
+use tokio::time::{timeout, Duration};
+use tracing::{info, warn, error, Instrument};
 
 use crate::config::PetGeneratorConfig;
-use super::address::PetAddress;
-use super::storage::PetStorage;
+use super::address::AddressMatcher;
+use super::audit;
+use super::sharding::ShardLetters;
+use super::events::{EventBus, GeneratorEvent};
+use super::grinder::Grinder;
+use super::backend::{Storage, StoreError};
+use super::metrics;
+use crate::telemetry::TRACE_TARGET;
 
+/// Name of the pool fed by the primary configured pattern
+pub const DEFAULT_POOL: &str = "default";
+
+/// Pool name prefix of tenant pools, which are only served under `/tenants/{name}`
+pub const TENANT_POOL_PREFIX: &str = "tenant:";
+
+/// Audit actor of the addresses the generator stores
+const GENERATOR_ACTOR: &str = "generator";
+
+/// How often a throughput sample is published while grinding
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the generator idles at target before re-checking pool sizes
+/// when no pop has woken it
+const IDLE_RECHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// How long a found address waits before re-checking a busy disk writer
+const BUSY_BACKOFF: Duration = Duration::from_millis(50);
+
+/// One pattern and the queue its hits are stored in
+#[derive(Clone)]
+pub struct PatternPool {
+    pub name: String,
+    pub matcher: Arc<AddressMatcher>,
+    pub storage: Arc<dyn Storage>,
+}
+
+/// Background subsystem that keeps every pool topped up to `pool_size`:
+/// grinds while any pool is below target, idles once all are full and
+/// resumes as soon as addresses are popped
 pub struct PetGenerator {
-    storage: Arc<PetStorage>,
+    // pools[0] is the default pool; extra suffix pools can be added and
+    // removed at runtime
+    pools: Arc<RwLock<Vec<PatternPool>>>,
+    pool_size: Arc<AtomicUsize>,
+    // Wakes the idle generator after a runtime change
+    reconfigured: Arc<Notify>,
     config: PetGeneratorConfig,
+    grinder: Grinder,
     is_running: Arc<Mutex<bool>>,
+    // True while a batch is being ground
+    is_generating: Arc<AtomicBool>,
+    events: EventBus,
+    // Letters before the suffix this node grinds, when sharded
+    shard: Option<ShardLetters>,
 }
 
 impl PetGenerator {
-    pub fn new(storage: Arc<PetStorage>, config: PetGeneratorConfig) -> Self {
-        Self {
-            storage,
+    /// Compiles every configured pattern once and opens a pool for each extra suffix;
+    /// fails if a pattern is invalid or can never match
+    pub async fn new(storage: Arc<dyn Storage>, config: PetGeneratorConfig) -> Result<Self> {
+        let mut pools = vec![PatternPool {
+            name: DEFAULT_POOL.to_string(),
+            matcher: Arc::new(AddressMatcher::from_config(&config)?),
+            storage: Arc::clone(&storage),
+        }];
+
+        for suffix in &config.extra_suffixes {
+            if pools.iter().any(|pool| pool.name == *suffix) {
+                bail!("Duplicate pattern pool '{}'", suffix);
+            }
+
+            pools.push(PatternPool {
+                name: suffix.clone(),
+                matcher: Arc::new(AddressMatcher::suffix(suffix, config.case_sensitive)?),
+                storage: storage.open_pool(suffix).await?,
+            });
+        }
+
+        let grinder = Grinder::new(config.threads);
+
+        Ok(Self {
+            pools: Arc::new(RwLock::new(pools)),
+            pool_size: Arc::new(AtomicUsize::new(config.pool_size)),
+            reconfigured: Arc::new(Notify::new()),
             config,
+            grinder,
             is_running: Arc::new(Mutex::new(false)),
+            is_generating: Arc::new(AtomicBool::new(false)),
+            events: EventBus::new(),
+            shard: None,
+        })
+    }
+
+    /// Grind only addresses with one of `letters` before the suffix, in every
+    /// suffix pool (regex and prefix pools are not sharded). Must be called
+    /// before `start`.
+    pub fn set_shard(&mut self, letters: ShardLetters) {
+        let mut pools = self.pools.write().unwrap_or_else(|e| e.into_inner());
+        for pool in pools.iter_mut() {
+            pool.matcher = Arc::new((*pool.matcher).clone().in_shard(&letters));
+            if !matches!(*pool.matcher, AddressMatcher::Sharded { .. }) {
+                info!("Pool '{}' ({}) is not a suffix pool and is not sharded", pool.name, pool.matcher.describe());
+            }
+        }
+        drop(pools);
+        self.shard = Some(letters);
+    }
+
+    /// Target queue depth of each pool
+    pub fn target_pool_size(&self) -> usize {
+        self.pool_size.load(Ordering::Relaxed)
+    }
+
+    /// Change the target queue depth; the generator picks it up on its next
+    /// check. A lower target does not drop queued addresses.
+    pub fn set_target_pool_size(&self, pool_size: usize) {
+        self.pool_size.store(pool_size, Ordering::Relaxed);
+        // Wake an idle generator so a raised target is filled right away
+        self.reconfigured.notify_one();
+    }
+
+    /// Whether a batch is currently being ground (false while idle at target or paused)
+    pub fn is_generating(&self) -> bool {
+        self.is_generating.load(Ordering::Relaxed) && !self.grinder.limits().is_paused()
+    }
+
+    /// Stream of generator activity (progress, hits, queue depth changes)
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    pub fn grinder(&self) -> &Grinder {
+        &self.grinder
+    }
+
+    /// Current pools, the default pool first
+    pub fn pools(&self) -> Vec<PatternPool> {
+        self.pools.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Pattern pool `name`; tenant pools are not reachable this way
+    pub fn pool(&self, name: &str) -> Option<PatternPool> {
+        if name.starts_with(TENANT_POOL_PREFIX) {
+            return None;
+        }
+        self.any_pool(name)
+    }
+
+    /// Pool of tenant `name`
+    pub fn tenant(&self, name: &str) -> Option<PatternPool> {
+        self.any_pool(&format!("{}{}", TENANT_POOL_PREFIX, name))
+    }
+
+    /// Pattern or tenant pool by its full name (e.g. `tenant:staging`)
+    pub fn any_pool(&self, name: &str) -> Option<PatternPool> {
+        let pools = self.pools.read().unwrap_or_else(|e| e.into_inner());
+        pools.iter().find(|pool| pool.name == name).cloned()
+    }
+
+    /// Suffixes of the extra pattern pools (neither default nor tenant pools)
+    pub fn extra_suffixes(&self) -> Vec<String> {
+        self.pools()
+            .into_iter()
+            .skip(1)
+            .filter(|pool| !pool.name.starts_with(TENANT_POOL_PREFIX))
+            .map(|pool| pool.name)
+            .collect()
+    }
+
+    /// Reject suffixes `add_suffix` would refuse as a pattern
+    pub fn check_suffix(&self, suffix: &str) -> Result<()> {
+        AddressMatcher::suffix(suffix, self.config.case_sensitive).map(|_| ())
+    }
+
+    /// Open a pool for the extra suffix `suffix` while running; its stored
+    /// addresses (from before a removal) are served again
+    pub async fn add_suffix(&self, suffix: &str) -> Result<PatternPool> {
+        if self.any_pool(suffix).is_some() {
+            bail!("Duplicate pattern pool '{}'", suffix);
+        }
+        let mut matcher = AddressMatcher::suffix(suffix, self.config.case_sensitive)?;
+        if let Some(letters) = &self.shard {
+            matcher = matcher.in_shard(letters);
+        }
+        let pool = PatternPool {
+            matcher: Arc::new(matcher),
+            name: suffix.to_string(),
+            storage: self.pools()[0].storage.open_pool(suffix).await?,
+        };
+
+        let mut pools = self.pools.write().unwrap_or_else(|e| e.into_inner());
+        // Checked again: another request may have added it meanwhile
+        if pools.iter().any(|existing| existing.name == suffix) {
+            bail!("Duplicate pattern pool '{}'", suffix);
+        }
+        pools.push(pool.clone());
+        drop(pools);
+        self.reconfigured.notify_one();
+        Ok(pool)
+    }
+
+    /// Stop serving and generating for the extra suffix pool `suffix`. Its
+    /// queued addresses stay stored; false if there is no such pool.
+    pub fn remove_suffix(&self, suffix: &str) -> bool {
+        if suffix == DEFAULT_POOL || suffix.starts_with(TENANT_POOL_PREFIX) {
+            return false;
+        }
+        let mut pools = self.pools.write().unwrap_or_else(|e| e.into_inner());
+        let before = pools.len();
+        pools.retain(|pool| pool.name != suffix);
+        pools.len() < before
+    }
+
+    /// Open a separately queued pool for tenant `name`, fed by the default pattern.
+    /// Must be called before `start`.
+    pub async fn add_tenant(&mut self, name: &str) -> Result<()> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            bail!("Invalid tenant name '{}': use letters, digits, '-' or '_'", name);
+        }
+        let pool_name = format!("{}{}", TENANT_POOL_PREFIX, name);
+        if self.any_pool(&pool_name).is_some() {
+            bail!("Duplicate tenant '{}'", name);
         }
+
+        let default = self.pools()[0].clone();
+        let pool = PatternPool {
+            storage: default.storage.open_pool(&pool_name).await?,
+            matcher: Arc::clone(&default.matcher),
+            name: pool_name,
+        };
+        self.pools.write().unwrap_or_else(|e| e.into_inner()).push(pool);
+        Ok(())
     }
     
     pub async fn start(&self) -> Result<()> {
@@ -32,11 +248,23 @@ impl PetGenerator {
             *running = true;
         }
         
-        info!("Starting Pet address generator");
+        let patterns: Vec<String> = self.pools().iter().map(|pool| pool.matcher.describe()).collect();
+        info!(
+            "Starting Pet address generator for {} on {} threads",
+            patterns.join(", "),
+            self.grinder.threads()
+        );
         
-        let storage = Arc::clone(&self.storage);
+        let pools = Arc::clone(&self.pools);
+        let pool_size = Arc::clone(&self.pool_size);
+        let reconfigured = Arc::clone(&self.reconfigured);
         let config = self.config.clone();
+        let grinder = self.grinder.clone();
         let is_running = Arc::clone(&self.is_running);
+        let is_generating = Arc::clone(&self.is_generating);
+        let events = self.events.clone();
+
+        self.start_progress_reporting();
         
         tokio::spawn(async move {
             loop {
@@ -47,24 +275,63 @@ impl PetGenerator {
                     }
                 }
                 
-                match storage.count_addresses() {
-                    Ok(count) => {
-                        if count < config.pool_size {
-                            let need_to_generate = config.pool_size - count;
-                            let batch_size = std::cmp::min(need_to_generate, config.batch_size);
-                            
-                            info!("Current address count: {}, generating {} more addresses", count, batch_size);
-                            
-                            Self::generate_batch(&storage, batch_size).await;
+                // Paused by an operator: release the CPU until resumed
+                if grinder.limits().is_paused() {
+                    if is_generating.swap(false, Ordering::Relaxed) {
+                        events.publish(GeneratorEvent::GenerationActive { active: false });
+                    }
+                    let _ = timeout(IDLE_RECHECK_INTERVAL, grinder.limits().wait_for_resume()).await;
+                    continue;
+                }
+
+                // Only grind for pools that are below target; both can change at runtime
+                let pools = pools.read().unwrap_or_else(|e| e.into_inner()).clone();
+                let pool_size = pool_size.load(Ordering::Relaxed);
+                let mut active = Vec::new();
+                let mut need_to_generate = 0;
+                for pool in &pools {
+                    match pool.storage.count_addresses() {
+                        Ok(count) => {
+                            if count < pool_size {
+                                need_to_generate += pool_size - count;
+                                active.push(pool.clone());
+                                info!("Pool '{}' address count: {}", pool.name, count);
+                            }
+                        }
+                        Err(e) => {
+                            error!("Failed to check address count for pool '{}': {}", pool.name, e);
                         }
                     }
-                    Err(e) => {
-                        error!("Failed to check address count: {}", e);
+                }
+
+                if active.is_empty() {
+                    // At target: pause until something is popped (or re-check periodically)
+                    if is_generating.swap(false, Ordering::Relaxed) {
+                        events.publish(GeneratorEvent::GenerationActive { active: false });
                     }
+                    let woken = async {
+                        tokio::select! {
+                            _ = pools[0].storage.wait_for_demand() => {}
+                            _ = reconfigured.notified() => {}
+                        }
+                    };
+                    let _ = timeout(IDLE_RECHECK_INTERVAL, woken).await;
+                    continue;
                 }
+
+                let batch_size = std::cmp::min(need_to_generate, config.batch_size.max(1));
+                
+                info!("Generating {} more addresses across {} pools", batch_size, active.len());
                 
-                sleep(Duration::from_secs(5)).await;
+                if !is_generating.swap(true, Ordering::Relaxed) {
+                    events.publish(GeneratorEvent::GenerationActive { active: true });
+                }
+                let span = tracing::info_span!(target: TRACE_TARGET, "generator.batch", batch_size, pools = active.len());
+                let batch = Self::generate_batch(grinder.clone(), &active, batch_size, pool_size, &events).instrument(span);
+                audit::with_actor(GENERATOR_ACTOR.to_string(), batch).await;
             }
+
+            is_generating.store(false, Ordering::Relaxed);
             
             info!("Pet address generator stopped");
         });
@@ -72,28 +339,68 @@ impl PetGenerator {
         Ok(())
     }
     
+    /// Publish keys/sec samples while grinding, until the generator is stopped
+    fn start_progress_reporting(&self) {
+        let grinder = self.grinder.clone();
+        let events = self.events.clone();
+        let is_running = Arc::clone(&self.is_running);
+        let is_generating = Arc::clone(&self.is_generating);
+
+        tokio::spawn(async move {
+            let mut last_keys = grinder.total_keys();
+            let mut last_sample = std::time::Instant::now();
+
+            loop {
+                tokio::time::sleep(PROGRESS_INTERVAL).await;
+                if !*is_running.lock().await {
+                    break;
+                }
+
+                let total_keys = grinder.total_keys();
+                let elapsed = last_sample.elapsed().as_secs_f64();
+                // Nothing ground since the last sample (idle): stay quiet
+                if is_generating.load(Ordering::Relaxed) && total_keys > last_keys && elapsed > 0.0 {
+                    events.publish(GeneratorEvent::Progress {
+                        keys_per_second: (total_keys - last_keys) as f64 / elapsed,
+                        total_keys,
+                    });
+                    last_keys = total_keys;
+                    last_sample = std::time::Instant::now();
+                }
+            }
+        });
+    }
+
     pub async fn stop(&self) {
         let mut running = self.is_running.lock().await;
         *running = false;
         info!("Stopping Pet address generator");
     }
     
-    async fn generate_batch(storage: &PetStorage, count: usize) {
+    async fn generate_batch(
+        grinder: Grinder,
+        pools: &[PatternPool],
+        count: usize,
+        target: usize,
+        events: &EventBus,
+    ) {
         let (tx, mut rx) = mpsc::channel(count);
+        let matchers: Arc<Vec<Arc<AddressMatcher>>> =
+            Arc::new(pools.iter().map(|pool| Arc::clone(&pool.matcher)).collect());
         
-        // Spawn generation tasks
-        for i in 0..count {
-            let tx = tx.clone();
-            tokio::spawn(async move {
+        // Grind on a blocking thread so the async runtime is never stalled; each address
+        // already uses every grinder thread, so addresses are found one after another
+        tokio::task::spawn_blocking(move || {
+            for i in 0..count {
                 info!("Starting generation task {}", i + 1);
                 
                 // Retry up to 3 times if generation fails
                 for retry in 1..=3 {
-                    match PetAddress::generate() {
-                        Some(address) => {
+                    match grinder.generate_any(&matchers) {
+                        Some((index, address)) => {
                             info!("Generated Pet address ending with: {}", 
                                   &address.address[address.address.len().saturating_sub(10)..]);
-                            if tx.send(address).await.is_err() {
+                            if tx.blocking_send((index, address)).is_err() {
                                 warn!("Failed to send generated address to channel");
                             }
                             break; // Success, exit retry loop
@@ -102,31 +409,47 @@ impl PetGenerator {
                             warn!("Failed to generate Pet address in task {} (attempt {}/3)", i + 1, retry);
                             if retry < 3 {
                                 // Wait a bit before retrying
-                                tokio::time::sleep(Duration::from_millis(100)).await;
+                                std::thread::sleep(Duration::from_millis(100));
                             }
                         }
                     }
                 }
-            });
-        }
-        
-        drop(tx); // Close the sender
+            }
+        });
         
-        // Collect generated addresses with timeout
+        // Collect every generated address; slow batches are only reported, never discarded
         let mut generated_count = 0;
-        let timeout_duration = Duration::from_secs(30); // 30 second timeout per batch
+        let slow_batch_threshold = Duration::from_secs(30);
         let start_time = std::time::Instant::now();
         
-        while let Some(address) = rx.recv().await {
-            if start_time.elapsed() > timeout_duration {
-                warn!("Batch generation timed out after 30 seconds");
-                break;
+        while let Some((index, address)) = rx.recv().await {
+            let pool = Self::least_filled(pools, index);
+            // Hold a found address while the disk writer catches up (or degraded
+            // mode refuses stores) instead of losing it to `Busy` or `Degraded`
+            while pool.storage.sled().is_some_and(|storage| storage.is_busy() || storage.refuses_stores()) {
+                tokio::time::sleep(BUSY_BACKOFF).await;
             }
-            
-            match storage.store_address(address) {
+            let address_str = address.address.clone();
+            match pool.storage.store_address(address) {
                 Ok(id) => {
                     generated_count += 1;
-                    info!("Stored Pet address with ID: {}", id);
+                    metrics::record_stored(&pool.name);
+                    info!("Stored address with ID {} in pool '{}'", id, pool.name);
+                    events.publish(GeneratorEvent::AddressFound {
+                        pool: pool.name.clone(),
+                        id,
+                        address: address_str,
+                    });
+                    if let Ok(depth) = pool.storage.count_addresses() {
+                        events.publish(GeneratorEvent::QueueDepth {
+                            pool: pool.name.clone(),
+                            depth,
+                            target,
+                        });
+                    }
+                }
+                Err(e) if matches!(e.downcast_ref(), Some(StoreError::QueueFull { .. } | StoreError::Busy { .. })) => {
+                    warn!("Dropped address for pool '{}': {}", pool.name, e);
                 }
                 Err(e) => {
                     error!("Failed to store Pet address: {}", e);
@@ -134,10 +457,182 @@ impl PetGenerator {
             }
         }
         
+        if start_time.elapsed() > slow_batch_threshold {
+            warn!("Batch generation took {:.1}s", start_time.elapsed().as_secs_f64());
+        }
         info!("Generated and stored {} Pet addresses in batch", generated_count);
     }
     
+    /// Tenant pools share the default pool's matcher, so a hit for one of them
+    /// goes to whichever pool with that matcher holds the fewest addresses
+    fn least_filled(pools: &[PatternPool], index: usize) -> &PatternPool {
+        pools
+            .iter()
+            .filter(|pool| Arc::ptr_eq(&pool.matcher, &pools[index].matcher))
+            .min_by_key(|pool| pool.storage.count_addresses().unwrap_or(usize::MAX))
+            .unwrap_or(&pools[index])
+    }
+
     pub async fn get_current_count(&self) -> Result<usize> {
-        self.storage.count_addresses()
+        self.pools()[0].storage.count_addresses()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::PetStorage;
+
+    fn test_config(pool_size: usize) -> PetGeneratorConfig {
+        PetGeneratorConfig {
+            pool_size,
+            batch_size: 2,
+            db_path: String::new(),
+            target_suffix: "Pet".to_string(),
+            case_sensitive: true,
+            // Matches every address so grinding is instant
+            pattern: Some(".".to_string()),
+            vanity: None,
+            extra_suffixes: Vec::new(),
+            threads: 2,
+            max_queue_size: None,
+            max_in_memory: None,
+        }
+    }
+
+    async fn wait_for_count(storage: &PetStorage, expected: usize) {
+        for _ in 0..100 {
+            if storage.count_addresses().unwrap() == expected {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!(
+            "pool never reached {} addresses (has {})",
+            expected,
+            storage.count_addresses().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keeps_pool_at_target_and_refills_after_pop() {
+        let storage = Arc::new(PetStorage::temporary().unwrap());
+        let generator = PetGenerator::new(storage.clone(), test_config(3)).await.unwrap();
+        generator.start().await.unwrap();
+
+        wait_for_count(&storage, 3).await;
+
+        // Pausing at target: no overshoot
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(storage.count_addresses().unwrap(), 3);
+
+        // A pop wakes the generator well before the idle re-check interval
+        storage.get_next_address().unwrap().unwrap();
+        wait_for_count(&storage, 3).await;
+
+        generator.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_raised_target_is_filled_right_away() {
+        let storage = Arc::new(PetStorage::temporary().unwrap());
+        let generator = PetGenerator::new(storage.clone(), test_config(2)).await.unwrap();
+        generator.start().await.unwrap();
+        wait_for_count(&storage, 2).await;
+
+        // Wakes the idle generator well before the idle re-check interval
+        generator.set_target_pool_size(4);
+        wait_for_count(&storage, 4).await;
+
+        generator.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_suffix_pools_added_and_removed_at_runtime() {
+        let storage = Arc::new(PetStorage::temporary().unwrap());
+        let mut generator = PetGenerator::new(storage, test_config(2)).await.unwrap();
+        generator.add_tenant("dev").await.unwrap();
+
+        assert!(generator.check_suffix("Cat").is_ok());
+        assert!(generator.check_suffix("C0l").is_err());
+        generator.add_suffix("Cat").await.unwrap();
+        assert!(generator.add_suffix("Cat").await.is_err());
+        assert_eq!(generator.extra_suffixes(), ["Cat"]);
+        assert!(generator.pool("Cat").is_some());
+
+        // Only extra suffix pools can be removed
+        assert!(!generator.remove_suffix(DEFAULT_POOL));
+        assert!(!generator.remove_suffix("tenant:dev"));
+        assert!(generator.remove_suffix("Cat"));
+        assert!(!generator.remove_suffix("Cat"));
+        assert!(generator.extra_suffixes().is_empty());
+        assert_eq!(generator.pools().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pause_stops_refill_until_resumed() {
+        let storage = Arc::new(PetStorage::temporary().unwrap());
+        let generator = PetGenerator::new(storage.clone(), test_config(2)).await.unwrap();
+        generator.grinder().limits().pause();
+        generator.start().await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(storage.count_addresses().unwrap(), 0);
+        assert!(!generator.is_generating());
+
+        generator.grinder().limits().resume();
+        wait_for_count(&storage, 2).await;
+
+        generator.stop().await;
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_publishes_found_and_depth_events() {
+        let storage = Arc::new(PetStorage::temporary().unwrap());
+        let generator = PetGenerator::new(storage.clone(), test_config(1)).await.unwrap();
+        let mut events = generator.events().subscribe();
+        generator.start().await.unwrap();
+
+        let mut seen = Vec::new();
+        while seen.len() < 4 {
+            let event = timeout(Duration::from_secs(2), events.recv()).await.unwrap().unwrap();
+            seen.push(event.name());
+            if event == (GeneratorEvent::QueueDepth { pool: DEFAULT_POOL.to_string(), depth: 1, target: 1 }) {
+                break;
+            }
+        }
+        generator.stop().await;
+
+        assert_eq!(seen, ["generation_active", "address_found", "queue_depth"]);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_pools_are_filled_separately() {
+        let storage = Arc::new(PetStorage::temporary().unwrap());
+        let mut generator = PetGenerator::new(storage.clone(), test_config(2)).await.unwrap();
+        generator.add_tenant("staging").await.unwrap();
+        assert!(generator.add_tenant("staging").await.is_err());
+        assert!(generator.add_tenant("no/slashes").await.is_err());
+
+        // Only reachable through the tenant lookup
+        assert!(generator.pool("tenant:staging").is_none());
+        let tenant = generator.tenant("staging").unwrap().storage.clone();
+
+        generator.start().await.unwrap();
+        wait_for_count(&storage, 2).await;
+        for _ in 0..100 {
+            if tenant.count_addresses().unwrap() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        // Hits are split between the pools instead of overfilling the first
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        generator.stop().await;
+
+        assert_eq!(tenant.count_addresses().unwrap(), 2);
+        let popped = tenant.get_next_address().unwrap().unwrap();
+        assert_eq!(popped.id, 0, "each tenant has its own ID counter");
+        assert_eq!(storage.count_addresses().unwrap(), 2);
+    }
+}
@@ -0,0 +1,350 @@
+use std::borrow::Borrow;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+use super::address::{AddressMatcher, PetAddress, MAX_ATTEMPTS};
+use super::metrics;
+use super::stats::{FindHistory, GeneratorStats, WorkerStats};
+
+/// How often a paused worker checks whether it may continue
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Keys tried and wall-clock time spent grinding, accumulated across calls
+#[derive(Debug, Default)]
+struct GrindStats {
+    keys: AtomicU64,
+    busy_nanos: AtomicU64,
+    // Time workers spent paused; excluded from the measured rate
+    paused_nanos: AtomicU64,
+    found: AtomicU64,
+    // Keys tried by searches that found an address
+    found_keys: AtomicU64,
+    workers: Mutex<Vec<WorkerStats>>,
+    recent: Mutex<FindHistory>,
+}
+
+impl GrindStats {
+    fn record_worker(&self, index: usize, keys: u64, busy: Duration) {
+        let mut workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+        if workers.len() <= index {
+            workers.resize(index + 1, WorkerStats::default());
+        }
+        workers[index].keys += keys;
+        workers[index].busy += busy;
+    }
+
+    fn record_found(&self, keys: u64, elapsed: Duration) {
+        self.found.fetch_add(1, Ordering::Relaxed);
+        self.found_keys.fetch_add(keys, Ordering::Relaxed);
+        self.recent.lock().unwrap_or_else(|e| e.into_inner()).record(elapsed);
+        metrics::record_found(elapsed);
+    }
+}
+
+/// Runtime limits adjustable through the admin API, applied to in-flight
+/// searches at the next chunk boundary
+#[derive(Debug, Default)]
+pub struct GrindLimits {
+    paused: AtomicBool,
+    resumed: Notify,
+    // 0 means unlimited
+    max_threads: AtomicUsize,
+    max_keys_per_second: AtomicU64,
+}
+
+impl GrindLimits {
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+        self.resumed.notify_one();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Wait until `resume` is called
+    pub async fn wait_for_resume(&self) {
+        self.resumed.notified().await;
+    }
+
+    /// Cap on worker threads per address (applies from the next address); `None` removes the cap
+    pub fn set_max_threads(&self, max_threads: Option<usize>) {
+        self.max_threads.store(max_threads.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn max_threads(&self) -> Option<usize> {
+        Some(self.max_threads.load(Ordering::Relaxed)).filter(|n| *n > 0)
+    }
+
+    /// Cap on combined keys/sec across workers; `None` removes the cap
+    pub fn set_max_keys_per_second(&self, max_keys_per_second: Option<u64>) {
+        self.max_keys_per_second
+            .store(max_keys_per_second.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn max_keys_per_second(&self) -> Option<u64> {
+        Some(self.max_keys_per_second.load(Ordering::Relaxed)).filter(|n| *n > 0)
+    }
+}
+
+/// Multi-threaded keypair grinder: every worker searches independently, the
+/// first one to hit a pattern wins and the others are cancelled
+#[derive(Debug, Clone)]
+pub struct Grinder {
+    // Shared by clones so the thread count can be changed at runtime
+    threads: Arc<AtomicUsize>,
+    max_attempts: usize,
+    // Shared by clones so the generator and API see the same numbers
+    stats: Arc<GrindStats>,
+    limits: Arc<GrindLimits>,
+}
+
+impl Grinder {
+    /// `threads == 0` uses every available core
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: Arc::new(AtomicUsize::new(resolve_threads(threads))),
+            max_attempts: MAX_ATTEMPTS,
+            stats: Arc::new(GrindStats::default()),
+            limits: Arc::new(GrindLimits::default()),
+        }
+    }
+
+    /// Cap on attempts shared by all workers for a single address
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Configured worker threads per address
+    pub fn threads(&self) -> usize {
+        self.threads.load(Ordering::Relaxed)
+    }
+
+    /// Change the worker threads per address (applies from the next address);
+    /// 0 uses every available core
+    pub fn set_threads(&self, threads: usize) {
+        self.threads.store(resolve_threads(threads), Ordering::Relaxed);
+    }
+
+    /// Worker threads the next address will use, after any admin cap
+    pub fn effective_threads(&self) -> usize {
+        match self.limits.max_threads() {
+            Some(max) => self.threads().min(max),
+            None => self.threads(),
+        }
+    }
+
+    /// Pause / throttle controls shared by every clone of this grinder
+    pub fn limits(&self) -> &GrindLimits {
+        &self.limits
+    }
+
+    /// Keys tried since the grinder was created
+    pub fn total_keys(&self) -> u64 {
+        self.stats.keys.load(Ordering::Relaxed)
+    }
+
+    /// Measured throughput across all threads, once anything has been ground
+    pub fn keys_per_second(&self) -> Option<f64> {
+        let keys = self.stats.keys.load(Ordering::Relaxed);
+        let busy = self.stats.busy_nanos.load(Ordering::Relaxed);
+        let paused = self.stats.paused_nanos.load(Ordering::Relaxed);
+        let secs = busy.saturating_sub(paused) as f64 / 1e9;
+        (keys > 0 && secs > 0.0).then(|| keys as f64 / secs)
+    }
+
+    /// Found addresses, attempts per find, per-thread rates and recent time-to-find
+    pub fn stats(&self) -> GeneratorStats {
+        let found = self.stats.found.load(Ordering::Relaxed);
+        let found_keys = self.stats.found_keys.load(Ordering::Relaxed);
+        GeneratorStats {
+            addresses_found: found,
+            keys_tried: self.total_keys(),
+            attempts_per_address: (found > 0).then(|| found_keys as f64 / found as f64),
+            keys_per_second: self.keys_per_second(),
+            workers: self.stats.workers.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            time_to_find: self.stats.recent.lock().unwrap_or_else(|e| e.into_inner()).summary(),
+        }
+    }
+
+    /// Grind one address matching `matcher`
+    pub fn generate(&self, matcher: &AddressMatcher) -> Option<PetAddress> {
+        self.generate_any(std::slice::from_ref(matcher)).map(|(_, address)| address)
+    }
+
+    /// Grind one address matching any of `matchers`; returns the index of the matcher that hit
+    pub fn generate_any<M: Borrow<AddressMatcher> + Sync>(&self, matchers: &[M]) -> Option<(usize, PetAddress)> {
+        let attempts = AtomicUsize::new(0);
+        let cancel = AtomicBool::new(false);
+        let threads = self.effective_threads();
+        let start = Instant::now();
+
+        let keys = AtomicU64::new(0);
+        let search = |index: usize| {
+            let worker_keys = AtomicU64::new(0);
+            let worker_start = Instant::now();
+            let result = PetAddress::search_any(matchers, self.max_attempts, &attempts, &cancel, &mut self.pacer(threads, &worker_keys));
+            let worker_keys = worker_keys.into_inner();
+            keys.fetch_add(worker_keys, Ordering::Relaxed);
+            self.stats.record_worker(index, worker_keys, worker_start.elapsed());
+            result
+        };
+
+        let result = if threads <= 1 {
+            search(0)
+        } else {
+            let search = &search;
+            thread::scope(|scope| {
+                let workers: Vec<_> = (0..threads).map(|index| scope.spawn(move || search(index))).collect();
+
+                // Two workers can hit in the same instant; keep the first
+                workers
+                    .into_iter()
+                    .filter_map(|worker| worker.join().ok().flatten())
+                    .next()
+            })
+        };
+
+        self.stats
+            .busy_nanos
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+
+        if result.is_some() {
+            self.stats.record_found(keys.into_inner(), start.elapsed());
+        } else {
+            let patterns: Vec<String> = matchers.iter().map(|m| m.borrow().describe()).collect();
+            tracing::warn!(
+                "Failed to generate {} address after {} attempts on {} threads",
+                patterns.join(" | "),
+                self.max_attempts,
+                threads
+            );
+        }
+        result
+    }
+
+    /// Per-worker chunk hook: records keys, blocks while paused and sleeps
+    /// as needed to keep this worker's share under the keys/sec cap
+    fn pacer<'a>(&'a self, workers: usize, worker_keys: &'a AtomicU64) -> impl FnMut(u64) + 'a {
+        let mut chunk_start = Instant::now();
+
+        move |keys| {
+            self.stats.keys.fetch_add(keys, Ordering::Relaxed);
+            worker_keys.fetch_add(keys, Ordering::Relaxed);
+
+            if let Some(max) = self.limits.max_keys_per_second() {
+                let share = max as f64 / workers as f64;
+                let min_duration = Duration::from_secs_f64(keys as f64 / share);
+                let elapsed = chunk_start.elapsed();
+                if elapsed < min_duration {
+                    thread::sleep(min_duration - elapsed);
+                }
+            }
+
+            if self.limits.is_paused() {
+                let paused_at = Instant::now();
+                while self.limits.is_paused() {
+                    thread::sleep(PAUSE_POLL_INTERVAL);
+                }
+                self.stats
+                    .paused_nanos
+                    .fetch_add(paused_at.elapsed().as_nanos() as u64, Ordering::Relaxed);
+            }
+
+            chunk_start = Instant::now();
+        }
+    }
+}
+
+fn resolve_threads(threads: usize) -> usize {
+    if threads == 0 {
+        thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    } else {
+        threads
+    }
+}
+
+impl Default for Grinder {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        let grinder = Grinder::new(4);
+        let clone = grinder.clone();
+
+        clone.limits().set_max_threads(Some(2));
+        assert_eq!(grinder.effective_threads(), 2);
+        clone.limits().set_max_threads(Some(8));
+        assert_eq!(grinder.effective_threads(), 4);
+        clone.limits().set_max_threads(None);
+        assert_eq!(grinder.effective_threads(), 4);
+        clone.set_threads(6);
+        assert_eq!(grinder.effective_threads(), 6);
+
+        clone.limits().pause();
+        assert!(grinder.limits().is_paused());
+        clone.limits().resume();
+        assert!(!grinder.limits().is_paused());
+    }
+
+    #[test]
+    fn test_keys_per_second_cap() {
+        let never = AddressMatcher::regex("^$", true).unwrap();
+        let grinder = Grinder::new(1).with_max_attempts(2_048);
+        grinder.limits().set_max_keys_per_second(Some(10_000));
+
+        let start = Instant::now();
+        assert!(grinder.generate(&never).is_none());
+        // Two 1024-key chunks at 10k keys/sec take at least ~0.2s
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_zero_threads_uses_available_cores() {
+        assert!(Grinder::new(0).threads() >= 1);
+        assert_eq!(Grinder::new(3).threads(), 3);
+    }
+
+    #[test]
+    fn test_first_worker_wins() {
+        let never = AddressMatcher::regex("^$", true).unwrap();
+        let always = AddressMatcher::regex(".", true).unwrap();
+
+        let grinder = Grinder::new(4);
+        let (index, address) = grinder.generate_any(&[&never, &always]).unwrap();
+        assert_eq!(index, 1);
+        assert!(always.is_match(&address.address));
+
+        let stats = grinder.stats();
+        assert_eq!(stats.addresses_found, 1);
+        assert_eq!(stats.time_to_find.samples, 1);
+        assert_eq!(stats.workers.len(), 4);
+        assert!(stats.attempts_per_address.unwrap() >= 1.0);
+    }
+
+    #[test]
+    fn test_attempt_budget_is_shared_across_workers() {
+        let never = AddressMatcher::regex("^$", true).unwrap();
+        let grinder = Grinder::new(4).with_max_attempts(4_096);
+
+        assert!(grinder.keys_per_second().is_none());
+        assert!(grinder.generate(&never).is_none());
+        assert_eq!(grinder.stats.keys.load(Ordering::Relaxed), 4_096);
+        assert!(grinder.keys_per_second().unwrap() > 0.0);
+    }
+}
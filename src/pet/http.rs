@@ -0,0 +1,317 @@
+//! Minimal blocking HTTP/1.1 client (plain TCP, TLS through openssl) for the
+//! few outbound calls the server makes: alert webhooks and snapshot uploads.
+//! One request per connection; no redirects, proxies or keep-alive.
+
+use anyhow::{anyhow, bail, Context, Result};
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Parsed `http://` or `https://host[:port][/path]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Url {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+    /// Path and query, always starting with `/`
+    pub path: String,
+}
+
+impl Url {
+    pub fn parse(url: &str) -> Result<Self> {
+        let (tls, rest) = if let Some(rest) = url.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            bail!("URL must start with http:// or https://");
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().with_context(|| format!("Invalid port '{}'", port))?),
+            None => (authority, if tls { 443 } else { 80 }),
+        };
+        if host.is_empty() {
+            bail!("URL has no host");
+        }
+
+        Ok(Self {
+            tls,
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// Value of the Host header; the port is omitted when it is the scheme default
+    pub fn host_header(&self) -> String {
+        match (self.tls, self.port) {
+            (true, 443) | (false, 80) => self.host.clone(),
+            _ => format!("{}:{}", self.host, self.port),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Status plus the start of the body, for error messages
+    pub fn describe(&self) -> String {
+        let body = String::from_utf8_lossy(&self.body);
+        let body = body.trim();
+        match body.char_indices().nth(200) {
+            Some((end, _)) => format!("HTTP {}: {}…", self.status, &body[..end]),
+            None if body.is_empty() => format!("HTTP {}", self.status),
+            None => format!("HTTP {}: {}", self.status, body),
+        }
+    }
+}
+
+/// TLS settings for servers that want a client certificate (mutual TLS)
+/// or use a private CA
+#[derive(Clone)]
+pub struct ClientTls {
+    connector: SslConnector,
+}
+
+impl ClientTls {
+    /// `identity` is a PEM certificate chain and its key; `ca_path` replaces
+    /// the system roots the server certificate is checked against
+    pub fn load(identity: Option<(&str, &str)>, ca_path: Option<&str>) -> Result<Self> {
+        let mut builder = SslConnector::builder(SslMethod::tls())?;
+        if let Some((cert_path, key_path)) = identity {
+            builder
+                .set_certificate_chain_file(cert_path)
+                .with_context(|| format!("Failed to load client certificate '{}'", cert_path))?;
+            builder
+                .set_private_key_file(key_path, SslFiletype::PEM)
+                .with_context(|| format!("Failed to load client key '{}'", key_path))?;
+            builder.check_private_key().context("The client key does not match its certificate")?;
+        }
+        if let Some(ca_path) = ca_path {
+            builder.set_ca_file(ca_path).with_context(|| format!("Failed to load CA bundle '{}'", ca_path))?;
+        }
+        Ok(Self { connector: builder.build() })
+    }
+}
+
+/// Send `method` to `url.host` for `path` (which may differ from `url.path`)
+/// and read the whole response. Host, Content-Length and Connection are set
+/// here; `headers` carries the rest.
+pub fn send(
+    url: &Url,
+    method: &str,
+    path: &str,
+    headers: &[(&str, String)],
+    body: &[u8],
+    timeout: Duration,
+) -> Result<Response> {
+    send_with(None, url, method, path, headers, body, timeout)
+}
+
+/// `send` with the client certificate and CA of `tls` on `https://` URLs
+pub fn send_with(
+    tls: Option<&ClientTls>,
+    url: &Url,
+    method: &str,
+    path: &str,
+    headers: &[(&str, String)],
+    body: &[u8],
+    timeout: Duration,
+) -> Result<Response> {
+    let address = (url.host.as_str(), url.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("Cannot resolve host '{}'", url.host))?;
+    let stream = TcpStream::connect_timeout(&address, timeout)
+        .with_context(|| format!("Failed to connect to {}", address))?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", method, path, url.host_header());
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()));
+
+    if url.tls {
+        let connector = match tls {
+            Some(tls) => tls.connector.clone(),
+            None => SslConnector::builder(SslMethod::tls())?.build(),
+        };
+        let stream = connector
+            .connect(&url.host, stream)
+            .map_err(|e| anyhow!("TLS handshake with {} failed: {}", url.host, e))?;
+        exchange(stream, head.as_bytes(), body, method == "HEAD")
+    } else {
+        exchange(stream, head.as_bytes(), body, method == "HEAD")
+    }
+}
+
+fn exchange<S: Read + Write>(mut stream: S, head: &[u8], body: &[u8], head_only: bool) -> Result<Response> {
+    stream.write_all(head)?;
+    stream.write_all(body)?;
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let status: u16 = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| anyhow!("Malformed HTTP response: {:?}", status_line.trim_end()))?;
+
+    let mut content_length = None;
+    let mut content_type = None;
+    let mut chunked = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse::<usize>().ok();
+            } else if name.eq_ignore_ascii_case("content-type") {
+                content_type = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            }
+        }
+    }
+
+    let mut body = Vec::new();
+    if head_only || status == 204 || status == 304 {
+        // No body
+    } else if chunked {
+        read_chunked(&mut reader, &mut body)?;
+    } else if let Some(length) = content_length {
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else {
+        reader.read_to_end(&mut body)?;
+    }
+
+    Ok(Response { status, content_type, body })
+}
+
+fn read_chunked<R: BufRead>(reader: &mut R, body: &mut Vec<u8>) -> Result<()> {
+    loop {
+        let mut size_line = String::new();
+        reader.read_line(&mut size_line)?;
+        let size = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| anyhow!("Malformed chunk size {:?}", size))?;
+        if size == 0 {
+            return Ok(());
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        // CRLF after each chunk
+        let mut crlf = String::new();
+        reader.read_line(&mut crlf)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// Request head lines and body captured by `serve_once`
+    type Captured = (Vec<String>, Vec<u8>);
+
+    /// Serve one connection: capture the request head and body, reply with `response`
+    fn serve_once(response: &'static [u8]) -> (u16, std::thread::JoinHandle<Captured>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = Vec::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    content_length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push(line);
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            (&stream).write_all(response).unwrap();
+            (head, body)
+        });
+        (port, server)
+    }
+
+    #[test]
+    fn test_url_parsing() {
+        let url = Url::parse("https://hooks.example.com/services/abc?x=1").unwrap();
+        assert_eq!(
+            url,
+            Url {
+                tls: true,
+                host: "hooks.example.com".to_string(),
+                port: 443,
+                path: "/services/abc?x=1".to_string(),
+            }
+        );
+        assert_eq!(url.host_header(), "hooks.example.com");
+
+        let url = Url::parse("http://127.0.0.1:8080").unwrap();
+        assert_eq!((url.port, url.path.as_str()), (8080, "/"));
+        assert_eq!(url.host_header(), "127.0.0.1:8080");
+
+        assert!(Url::parse("ftp://example.com").is_err());
+        assert!(Url::parse("http://:80/").is_err());
+    }
+
+    #[test]
+    fn test_request_and_content_length_response() {
+        let (port, server) = serve_once(b"HTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\nok");
+        let url = Url::parse(&format!("http://127.0.0.1:{}", port)).unwrap();
+        let headers = [("Content-Type", "application/json".to_string())];
+
+        let response = send(&url, "PUT", "/a/b", &headers, b"{}", Duration::from_secs(5)).unwrap();
+        assert_eq!((response.status, response.body.as_slice()), (201, &b"ok"[..]));
+
+        let (head, body) = server.join().unwrap();
+        assert_eq!(head[0], "PUT /a/b HTTP/1.1\r\n");
+        assert!(head.contains(&format!("Host: 127.0.0.1:{}\r\n", port)));
+        assert!(head.contains(&"Content-Type: application/json\r\n".to_string()));
+        assert_eq!(body, b"{}");
+    }
+
+    #[test]
+    fn test_chunked_response() {
+        let (port, server) =
+            serve_once(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6;x=y\r\n world\r\n0\r\n\r\n");
+        let url = Url::parse(&format!("http://127.0.0.1:{}", port)).unwrap();
+
+        let response = send(&url, "GET", "/", &[], b"", Duration::from_secs(5)).unwrap();
+        assert!(response.is_success());
+        assert_eq!(response.body, b"hello world");
+        server.join().unwrap();
+    }
+}
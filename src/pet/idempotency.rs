@@ -0,0 +1,270 @@
+use anyhow::Result;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+use super::address::PetAddressInfo;
+
+struct CachedPop {
+    addresses: Vec<PetAddressInfo>,
+    // How the first request had the private keys delivered
+    delivery: String,
+    inserted_at: Instant,
+}
+
+/// A key was replayed with other delivery parameters than on its first use,
+/// e.g. asking in plain text for keys first delivered sealed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayMismatch;
+
+impl fmt::Display for ReplayMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Idempotency-Key was first used with a different private key delivery (include_private_key or X-Recipient-Key)")
+    }
+}
+
+impl std::error::Error for ReplayMismatch {}
+
+/// Bounded TTL cache mapping client `Idempotency-Key` values to the addresses
+/// dispensed for them, so a retried pop returns the same addresses instead
+/// of consuming others
+#[derive(Clone)]
+pub struct IdempotencyCache {
+    entries: Arc<DashMap<String, CachedPop>>,
+    ttl: Duration,
+    max_entries: usize,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl_seconds: u64, max_entries: usize) -> Self {
+        let cache = Self {
+            entries: Arc::new(DashMap::new()),
+            ttl: Duration::from_secs(ttl_seconds),
+            max_entries: max_entries.max(1),
+        };
+
+        // Start cleanup task
+        let cache_clone = cache.clone();
+        tokio::spawn(async move {
+            cache_clone.cleanup_task().await;
+        });
+
+        cache
+    }
+
+    /// Return the address cached for `key`, or call `fetch` and remember its result.
+    /// The entry lock is held while fetching so concurrent retries with the same
+    /// key cannot both consume an address. A replay whose `delivery` differs
+    /// from the first request's fails with `ReplayMismatch`.
+    pub fn get_or_insert_with<F>(&self, key: &str, delivery: &str, fetch: F) -> Result<Option<PetAddressInfo>>
+    where
+        F: FnOnce() -> Result<Option<PetAddressInfo>>,
+    {
+        let addresses = self.get_or_insert_batch(key, delivery, || Ok(fetch()?.into_iter().collect()))?;
+        Ok(addresses.into_iter().next())
+    }
+
+    /// Batch form of `get_or_insert_with`: the whole popped batch is cached
+    /// under `key`. An empty batch is not cached, so a retry pops again.
+    pub fn get_or_insert_batch<F>(&self, key: &str, delivery: &str, fetch: F) -> Result<Vec<PetAddressInfo>>
+    where
+        F: FnOnce() -> Result<Vec<PetAddressInfo>>,
+    {
+        if !self.entries.contains_key(key) {
+            self.make_room();
+        }
+
+        match self.entries.entry(key.to_string()) {
+            Entry::Occupied(mut entry) => {
+                if entry.get().inserted_at.elapsed() < self.ttl {
+                    if entry.get().delivery != delivery {
+                        return Err(ReplayMismatch.into());
+                    }
+                    return Ok(entry.get().addresses.clone());
+                }
+
+                // Expired - treat as a fresh request
+                let addresses = fetch()?;
+                if addresses.is_empty() {
+                    entry.remove();
+                } else {
+                    entry.insert(CachedPop {
+                        addresses: addresses.clone(),
+                        delivery: delivery.to_string(),
+                        inserted_at: Instant::now(),
+                    });
+                }
+                Ok(addresses)
+            }
+            Entry::Vacant(entry) => {
+                let addresses = fetch()?;
+                if !addresses.is_empty() {
+                    entry.insert(CachedPop {
+                        addresses: addresses.clone(),
+                        delivery: delivery.to_string(),
+                        inserted_at: Instant::now(),
+                    });
+                }
+                Ok(addresses)
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Evict expired entries, then the oldest entry if the cache is still full
+    fn make_room(&self) {
+        if self.entries.len() < self.max_entries {
+            return;
+        }
+
+        self.purge_expired();
+
+        while self.entries.len() >= self.max_entries {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|entry| entry.value().inserted_at)
+                .map(|entry| entry.key().clone());
+
+            match oldest {
+                Some(key) => {
+                    self.entries.remove(&key);
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn purge_expired(&self) {
+        let ttl = self.ttl;
+        self.entries.retain(|_key, cached| cached.inserted_at.elapsed() < ttl);
+    }
+
+    async fn cleanup_task(&self) {
+        loop {
+            sleep(Duration::from_secs(60)).await; // Cleanup every minute
+            self.purge_expired();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::test_support::test_address;
+    use crate::pet::PetStorage;
+
+    #[tokio::test]
+    async fn test_same_key_returns_same_address() {
+        let storage = PetStorage::temporary().unwrap();
+        storage.store_address(test_address("aPet")).unwrap();
+        storage.store_address(test_address("bPet")).unwrap();
+
+        let cache = IdempotencyCache::new(300, 100);
+
+        let first = cache
+            .get_or_insert_with("retry-key", "plain", || storage.get_next_address())
+            .unwrap()
+            .unwrap();
+        let second = cache
+            .get_or_insert_with("retry-key", "plain", || storage.get_next_address())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(first.id, second.id);
+        assert_eq!(first.address.address, second.address.address);
+        assert_eq!(storage.count_addresses().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_distinct_keys_consume_distinct_addresses() {
+        let storage = PetStorage::temporary().unwrap();
+        storage.store_address(test_address("aPet")).unwrap();
+        storage.store_address(test_address("bPet")).unwrap();
+
+        let cache = IdempotencyCache::new(300, 100);
+
+        let first = cache
+            .get_or_insert_with("key-1", "plain", || storage.get_next_address())
+            .unwrap()
+            .unwrap();
+        let second = cache
+            .get_or_insert_with("key-2", "plain", || storage.get_next_address())
+            .unwrap()
+            .unwrap();
+
+        assert_ne!(first.id, second.id);
+        assert_eq!(storage.count_addresses().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_expired_and_bounded() {
+        let storage = PetStorage::temporary().unwrap();
+        for suffix in ["aPet", "bPet", "cPet"] {
+            storage.store_address(test_address(suffix)).unwrap();
+        }
+
+        // Zero TTL: every lookup is a fresh fetch
+        let cache = IdempotencyCache::new(0, 2);
+        let first = cache
+            .get_or_insert_with("key", "plain", || storage.get_next_address())
+            .unwrap()
+            .unwrap();
+        let second = cache
+            .get_or_insert_with("key", "plain", || storage.get_next_address())
+            .unwrap()
+            .unwrap();
+        assert_ne!(first.id, second.id);
+
+        cache
+            .get_or_insert_with("other-1", "plain", || storage.get_next_address())
+            .unwrap();
+        cache.get_or_insert_with("other-2", "plain", || Ok(None)).unwrap();
+        assert!(cache.len() <= 2);
+    }
+
+    #[tokio::test]
+    async fn test_batch_is_replayed_whole() {
+        let storage = PetStorage::temporary().unwrap();
+        for suffix in ["aPet", "bPet", "cPet"] {
+            storage.store_address(test_address(suffix)).unwrap();
+        }
+
+        let cache = IdempotencyCache::new(300, 100);
+        let first = cache.get_or_insert_batch("batch", "plain", || storage.get_next_addresses(2)).unwrap();
+        let second = cache.get_or_insert_batch("batch", "plain", || storage.get_next_addresses(2)).unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(
+            first.iter().map(|info| info.id).collect::<Vec<_>>(),
+            second.iter().map(|info| info.id).collect::<Vec<_>>()
+        );
+        assert_eq!(storage.count_addresses().unwrap(), 1);
+
+        // Nothing popped, nothing cached
+        assert!(cache.get_or_insert_batch("empty", "plain", || Ok(Vec::new())).unwrap().is_empty());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_replay_with_another_delivery_is_refused() {
+        let storage = PetStorage::temporary().unwrap();
+        storage.store_address(test_address("aPet")).unwrap();
+        storage.store_address(test_address("bPet")).unwrap();
+
+        let cache = IdempotencyCache::new(300, 100);
+        cache.get_or_insert_with("key", "sealed:AAAA", || storage.get_next_address()).unwrap();
+        let error = cache.get_or_insert_with("key", "plain:true", || storage.get_next_address()).unwrap_err();
+        assert!(error.downcast_ref::<ReplayMismatch>().is_some());
+        assert_eq!(storage.count_addresses().unwrap(), 1);
+    }
+}
@@ -0,0 +1,237 @@
+use anyhow::{bail, Result};
+use dashmap::DashMap;
+use rand::RngCore;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use super::address::{AddressMatcher, PetAddress};
+use super::difficulty::estimate;
+use super::grinder::Grinder;
+use crate::config::JobsConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    /// Waiting for a free job slot
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed)
+    }
+}
+
+/// One ad-hoc vanity request ground outside the pre-warmed pools
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: String,
+    pub pattern: String,
+    pub status: JobStatus,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub result: Option<PetAddress>,
+    pub error: Option<String>,
+    /// Identity of the caller that submitted the job; only it may read the result
+    pub owner: String,
+}
+
+/// Runs custom-pattern jobs in the background on the shared grinder.
+/// Finished jobs are kept for `retention_seconds` so clients can collect them.
+#[derive(Clone)]
+pub struct JobManager {
+    jobs: Arc<DashMap<String, Job>>,
+    slots: Arc<Semaphore>,
+    grinder: Grinder,
+    config: JobsConfig,
+}
+
+impl JobManager {
+    pub fn new(grinder: Grinder, config: JobsConfig) -> Self {
+        let manager = Self {
+            jobs: Arc::new(DashMap::new()),
+            slots: Arc::new(Semaphore::new(config.max_concurrent.max(1))),
+            grinder,
+            config,
+        };
+
+        // Start cleanup task
+        let manager_clone = manager.clone();
+        tokio::spawn(async move {
+            manager_clone.cleanup_task().await;
+        });
+
+        manager
+    }
+
+    /// Validate `matcher` and queue a job for it on behalf of `owner`; returns the job ID.
+    /// Patterns expected to need more than `max_expected_attempts` keys are rejected.
+    pub fn submit(&self, matcher: AddressMatcher, owner: String) -> Result<String> {
+        if let Ok(difficulty) = estimate(&matcher, None) {
+            if difficulty.expected_attempts > self.config.max_expected_attempts {
+                bail!(
+                    "Pattern {} needs ~{:.0} attempts per address, above the limit of {:.0}",
+                    matcher.describe(),
+                    difficulty.expected_attempts,
+                    self.config.max_expected_attempts
+                );
+            }
+        }
+
+        let unfinished = self.jobs.iter().filter(|job| !job.status.is_finished()).count();
+        if unfinished >= self.config.max_pending {
+            bail!("Too many pending jobs ({})", unfinished);
+        }
+
+        let id = new_job_id();
+        self.jobs.insert(
+            id.clone(),
+            Job {
+                id: id.clone(),
+                pattern: matcher.describe(),
+                status: JobStatus::Queued,
+                created_at: chrono::Utc::now(),
+                finished_at: None,
+                result: None,
+                error: None,
+                owner,
+            },
+        );
+
+        let manager = self.clone();
+        let job_id = id.clone();
+        tokio::spawn(async move {
+            manager.run(job_id, matcher).await;
+        });
+
+        Ok(id)
+    }
+
+    /// The job `id`, if `owner` submitted it
+    pub fn get(&self, id: &str, owner: &str) -> Option<Job> {
+        self.jobs.get(id).filter(|job| job.owner == owner).map(|job| job.clone())
+    }
+
+    async fn run(&self, id: String, matcher: AddressMatcher) {
+        let Ok(_slot) = self.slots.acquire().await else {
+            return;
+        };
+        self.update(&id, |job| job.status = JobStatus::Running);
+        tracing::info!("Job {} started for {}", id, matcher.describe());
+
+        let grinder = self.grinder.clone();
+        let outcome = tokio::task::spawn_blocking(move || {
+            // The estimate check bounds the expected cost, so keep going until a hit
+            loop {
+                if let Some(address) = grinder.generate(&matcher) {
+                    return address;
+                }
+            }
+        })
+        .await;
+
+        self.update(&id, |job| {
+            job.finished_at = Some(chrono::Utc::now());
+            match outcome {
+                Ok(address) => {
+                    job.status = JobStatus::Completed;
+                    job.result = Some(address);
+                }
+                Err(e) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(format!("Grinding task failed: {}", e));
+                }
+            }
+        });
+        tracing::info!("Job {} finished", id);
+    }
+
+    fn update(&self, id: &str, change: impl FnOnce(&mut Job)) {
+        if let Some(mut job) = self.jobs.get_mut(id) {
+            change(&mut job);
+        }
+    }
+
+    fn purge_finished(&self) {
+        let retention = chrono::Duration::seconds(self.config.retention_seconds as i64);
+        let now = chrono::Utc::now();
+        self.jobs.retain(|_id, job| match job.finished_at {
+            Some(finished_at) => now - finished_at < retention,
+            None => true,
+        });
+    }
+
+    async fn cleanup_task(&self) {
+        loop {
+            tokio::time::sleep(Duration::from_secs(60)).await; // Cleanup every minute
+            self.purge_finished();
+        }
+    }
+}
+
+/// Random 128-bit hex ID; doubles as the capability to read the job's private key
+fn new_job_id() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(config: JobsConfig) -> JobManager {
+        JobManager::new(Grinder::new(1), config)
+    }
+
+    async fn wait_finished(manager: &JobManager, id: &str) -> Job {
+        for _ in 0..100 {
+            let job = manager.get(id, "ip=10.0.0.1").unwrap();
+            if job.status.is_finished() {
+                return job;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("job {} never finished", id);
+    }
+
+    #[tokio::test]
+    async fn test_job_completes_with_matching_address() {
+        let manager = manager(JobsConfig::default());
+        let matcher = AddressMatcher::regex("^[A-Z]", true).unwrap();
+
+        let id = manager.submit(matcher.clone(), "ip=10.0.0.1".to_string()).unwrap();
+        assert_eq!(id.len(), 32);
+
+        let job = wait_finished(&manager, &id).await;
+        assert_eq!(job.status, JobStatus::Completed);
+        assert!(job.finished_at.is_some());
+        assert!(matcher.is_match(&job.result.unwrap().address));
+        assert!(manager.get("unknown", "ip=10.0.0.1").is_none());
+        // Another caller cannot read the result, not even its status
+        assert!(manager.get(&id, "ip=10.0.0.2").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_patterns_above_difficulty_limit() {
+        let manager = manager(JobsConfig {
+            max_expected_attempts: 1_000.0,
+            ..JobsConfig::default()
+        });
+
+        assert!(manager.submit(AddressMatcher::default(), "anonymous".to_string()).is_err());
+        assert!(manager.submit(AddressMatcher::regex(".", true).unwrap(), "anonymous".to_string()).is_ok());
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+use super::address::{PetAddress, PetAddressInfo};
+use super::backend::{Storage, StoreError};
+
+/// `Storage` that never touches disk: each pool is a FIFO behind a mutex.
+/// Everything is lost on restart, which suits tests and stateless
+/// deployments that only need a warm pool while they run.
+pub struct MemoryStorage {
+    queue: Arc<Mutex<VecDeque<PetAddressInfo>>>,
+    counter: Arc<AtomicU64>,
+    // Public keys ever enqueued, shared by all pools
+    seen: Arc<Mutex<HashSet<String>>>,
+    // Signalled whenever addresses are consumed. Shared by all pools.
+    demand: Arc<Notify>,
+    capacity: Option<usize>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self {
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+            counter: Arc::new(AtomicU64::new(0)),
+            seen: Arc::new(Mutex::new(HashSet::new())),
+            demand: Arc::new(Notify::new()),
+            capacity: None,
+        }
+    }
+
+    /// Refuse stores once `capacity` addresses are queued. Pools opened
+    /// afterwards inherit the limit.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    fn queue(&self) -> std::sync::MutexGuard<'_, VecDeque<PetAddressInfo>> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    fn backend_name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn store_address(&self, address: PetAddress) -> Result<u64> {
+        let mut queue = self.queue();
+        if let Some(capacity) = self.capacity {
+            if queue.len() >= capacity {
+                return Err(StoreError::QueueFull { capacity }.into());
+            }
+        }
+        if !self.seen.lock().unwrap_or_else(|e| e.into_inner()).insert(address.public_key.clone()) {
+            return Err(StoreError::Duplicate {
+                public_key: address.public_key,
+            }
+            .into());
+        }
+
+        let id = self.counter.fetch_add(1, Ordering::Relaxed);
+        queue.push_back(PetAddressInfo {
+            id,
+            address,
+            created_at: chrono::Utc::now(),
+        });
+        Ok(id)
+    }
+
+    fn get_next_address(&self) -> Result<Option<PetAddressInfo>> {
+        Ok(self.get_next_addresses(1)?.pop())
+    }
+
+    fn get_next_addresses(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        let popped: Vec<PetAddressInfo> = {
+            let mut queue = self.queue();
+            let count = count.min(queue.len());
+            queue.drain(..count).collect()
+        };
+        if !popped.is_empty() {
+            self.demand.notify_one();
+        }
+        Ok(popped)
+    }
+
+    fn count_addresses(&self) -> Result<usize> {
+        Ok(self.queue().len())
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    fn clear_all_addresses(&self) -> Result<()> {
+        self.queue().clear();
+        self.demand.notify_one();
+        Ok(())
+    }
+
+    fn created_range(&self) -> Result<Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)>> {
+        let queue = self.queue();
+        Ok(queue.front().zip(queue.back()).map(|(oldest, newest)| (oldest.created_at, newest.created_at)))
+    }
+
+    /// Nothing survives a restart, so there is nothing to reload
+    async fn restore(&self) -> Result<usize> {
+        self.count_addresses()
+    }
+
+    async fn open_pool(&self, _name: &str) -> Result<Arc<dyn Storage>> {
+        Ok(Arc::new(Self {
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+            counter: Arc::new(AtomicU64::new(0)),
+            seen: Arc::clone(&self.seen),
+            demand: Arc::clone(&self.demand),
+            capacity: self.capacity,
+        }))
+    }
+
+    async fn wait_for_demand(&self) {
+        self.demand.notified().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PetGeneratorConfig;
+    use crate::pet::test_support::test_address;
+    use crate::pet::PetGenerator;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_memory_queue_is_fifo_per_pool() {
+        let storage = MemoryStorage::new().with_capacity(2);
+        storage.store_address(test_address("aPet")).unwrap();
+        storage.store_address(test_address("bPet")).unwrap();
+        let error = storage.store_address(test_address("cPet")).unwrap_err();
+        assert_eq!(error.downcast_ref::<StoreError>(), Some(&StoreError::QueueFull { capacity: 2 }));
+
+        assert_eq!(storage.get_next_address().unwrap().unwrap().address.address, "TestAddressaPet");
+        assert_eq!(storage.get_next_addresses(5).unwrap().len(), 1);
+        assert!(storage.get_next_address().unwrap().is_none());
+
+        // Keys stay known after they were popped, in every pool
+        let pool = storage.open_pool("Cat").await.unwrap();
+        assert!(pool.store_address(test_address("aPet")).is_err());
+        pool.store_address(test_address("aCat")).unwrap();
+        assert_eq!((pool.count_addresses().unwrap(), storage.count_addresses().unwrap()), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_generator_fills_memory_pool() {
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let config: PetGeneratorConfig = serde_json::from_value(serde_json::json!({
+            "pool_size": 2,
+            "batch_size": 100,
+            "db_path": "",
+            "pattern": ".",
+            "threads": 2
+        }))
+        .unwrap();
+        let generator = PetGenerator::new(Arc::clone(&storage), config).await.unwrap();
+        generator.start().await.unwrap();
+
+        for _ in 0..100 {
+            if storage.count_addresses().unwrap() >= 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        generator.stop().await;
+        assert!(storage.count_addresses().unwrap() >= 2);
+    }
+}
@@ -0,0 +1,232 @@
+//! Prometheus metrics. Counters and histograms are recorded where things
+//! happen through the `metrics` macros; gauges mirroring current state (queue
+//! depth, throughput, persistence backlog, database size) are sampled when
+//! `/metrics` is scraped.
+
+use anyhow::Result;
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use super::generator::{PatternPool, PetGenerator};
+use super::stats::TIME_TO_FIND_BUCKETS;
+use crate::models::WorkerSubmitResponse;
+
+pub const HTTP_REQUESTS: &str = "http_requests_total";
+pub const HTTP_DURATION: &str = "http_request_duration_seconds";
+const STORED: &str = "pet_addresses_stored_total";
+const DISPENSED: &str = "pet_addresses_dispensed_total";
+const QUEUE_DEPTH: &str = "pet_queue_depth";
+const QUEUE_CAPACITY: &str = "pet_queue_capacity";
+const QUEUE_OLDEST_AGE: &str = "pet_queue_oldest_age_seconds";
+const QUEUE_NEWEST_AGE: &str = "pet_queue_newest_age_seconds";
+const GENERATOR_ACTIVE: &str = "pet_generator_active";
+const KEYS_PER_SECOND: &str = "pet_generator_keys_per_second";
+const KEYS_TOTAL: &str = "pet_generator_keys_total";
+const FOUND: &str = "pet_generator_addresses_found_total";
+const TIME_TO_FIND: &str = "pet_generator_time_to_find_seconds";
+const ATTEMPTS_PER_ADDRESS: &str = "pet_generator_attempts_per_address";
+const THREAD_KEYS_PER_SECOND: &str = "pet_generator_thread_keys_per_second";
+const PERSIST_PENDING: &str = "pet_persist_pending_writes";
+const PERSIST_LAG: &str = "pet_persist_lag_seconds";
+const PERSIST_FAILED: &str = "pet_persist_failed_writes_total";
+const PERSIST_RETRYING: &str = "pet_persist_retrying_writes";
+const PERSIST_RETRIES: &str = "pet_persist_retries_total";
+const PERSIST_DEAD_LETTERS: &str = "pet_persist_dead_letters";
+const PERSIST_REFUSED: &str = "pet_persist_refused_total";
+const STORAGE_DEGRADED: &str = "pet_storage_degraded";
+const BREAKER_TRIPS: &str = "pet_storage_breaker_trips_total";
+const DB_SIZE: &str = "pet_db_size_bytes";
+const WORKER_KEYPAIRS: &str = "pet_worker_keypairs_total";
+
+/// Upper bounds of the HTTP latency buckets, in seconds
+const LATENCY_BUCKETS: &[f64] = &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+/// How often histogram samples are folded into their buckets between scrapes
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Install the process-wide Prometheus recorder (once; later calls return the
+/// same handle). Metrics recorded before this are dropped.
+pub fn install_recorder() -> Result<PrometheusHandle> {
+    if let Some(handle) = HANDLE.get() {
+        return Ok(handle.clone());
+    }
+
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(HTTP_DURATION.to_string()), LATENCY_BUCKETS)?
+        .set_buckets_for_metric(Matcher::Full(TIME_TO_FIND.to_string()), TIME_TO_FIND_BUCKETS)?
+        .install_recorder()?;
+    describe();
+
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(UPKEEP_INTERVAL).await;
+            upkeep.run_upkeep();
+        }
+    });
+
+    Ok(HANDLE.get_or_init(|| handle).clone())
+}
+
+fn describe() {
+    describe_counter!(HTTP_REQUESTS, "HTTP requests by method, route and status");
+    describe_histogram!(HTTP_DURATION, Unit::Seconds, "HTTP request latency by method, route and status");
+    describe_counter!(STORED, "Addresses stored by the generator, per pool");
+    describe_counter!(DISPENSED, "Addresses handed out to clients, per pool");
+    describe_gauge!(QUEUE_DEPTH, "Addresses queued, per pool");
+    describe_gauge!(QUEUE_CAPACITY, "Hard cap on queued addresses, per pool (only when configured)");
+    describe_gauge!(QUEUE_OLDEST_AGE, Unit::Seconds, "Age of the oldest queued address, per pool (0 when empty)");
+    describe_gauge!(QUEUE_NEWEST_AGE, Unit::Seconds, "Age of the newest queued address, per pool (0 when empty)");
+    describe_gauge!(GENERATOR_ACTIVE, "1 while the generator is filling pools");
+    describe_gauge!(KEYS_PER_SECOND, "Measured grinder throughput across all threads");
+    describe_counter!(KEYS_TOTAL, "Keys tried by the grinder since startup");
+    describe_counter!(FOUND, "Addresses found by the grinder since startup");
+    describe_histogram!(TIME_TO_FIND, Unit::Seconds, "Wall-clock time to find one address");
+    describe_gauge!(ATTEMPTS_PER_ADDRESS, "Mean keys tried per address found");
+    describe_gauge!(THREAD_KEYS_PER_SECOND, "Measured throughput of each grinder thread");
+    describe_gauge!(PERSIST_PENDING, "Sled writes queued for the background writer");
+    describe_gauge!(PERSIST_LAG, Unit::Seconds, "How long the last applied write batch waited");
+    describe_counter!(PERSIST_FAILED, "Sled writes that failed every retry since startup");
+    describe_gauge!(PERSIST_RETRYING, "Sled writes in the batch waiting to be retried");
+    describe_counter!(PERSIST_RETRIES, "Retries of failed sled write batches since startup");
+    describe_gauge!(PERSIST_DEAD_LETTERS, "Failed sled writes kept in the dead-letter tree");
+    describe_counter!(PERSIST_REFUSED, "Stores and pops refused because the background writer was too far behind or storage was degraded");
+    describe_gauge!(STORAGE_DEGRADED, "1 while sled keeps failing and the circuit breaker holds the server in degraded mode");
+    describe_counter!(BREAKER_TRIPS, "Times the sled circuit breaker opened since startup");
+    describe_gauge!(DB_SIZE, Unit::Bytes, "Sled database size on disk");
+    describe_counter!(WORKER_KEYPAIRS, "Keypairs submitted by remote workers, per pool and result");
+}
+
+pub fn record_stored(pool: &str) {
+    counter!(STORED, "pool" => pool.to_string()).increment(1);
+}
+
+pub fn record_dispensed(pool: &str, count: usize) {
+    counter!(DISPENSED, "pool" => pool.to_string()).increment(count as u64);
+}
+
+pub fn record_found(elapsed: Duration) {
+    counter!(FOUND).increment(1);
+    histogram!(TIME_TO_FIND).record(elapsed.as_secs_f64());
+}
+
+/// Outcome of one `POST /workers/submit`
+pub fn record_worker_keypairs(pool: &str, response: &WorkerSubmitResponse) {
+    for (result, count) in [
+        ("accepted", response.accepted),
+        ("duplicate", response.duplicates),
+        ("rejected", response.rejected.len()),
+    ] {
+        counter!(WORKER_KEYPAIRS, "pool" => pool.to_string(), "result" => result).increment(count as u64);
+    }
+}
+
+/// How long ago the oldest and newest queued addresses of `pool` were created
+pub fn queue_ages(pool: &PatternPool) -> Option<(Duration, Duration)> {
+    let (oldest, newest) = match pool.storage.created_range() {
+        Ok(range) => range?,
+        Err(e) => {
+            tracing::warn!("Failed to read the queue age of pool '{}': {}", pool.name, e);
+            return None;
+        }
+    };
+    let now = chrono::Utc::now();
+    let age = |created_at: chrono::DateTime<chrono::Utc>| (now - created_at).to_std().unwrap_or_default();
+    Some((age(oldest), age(newest)))
+}
+
+/// Sample the state gauges and render everything in the Prometheus text format
+pub async fn render(handle: &PrometheusHandle, generator: &PetGenerator) -> String {
+    for pool in generator.pools() {
+        if let Ok(depth) = pool.storage.count_addresses() {
+            gauge!(QUEUE_DEPTH, "pool" => pool.name.clone()).set(depth as f64);
+        }
+        if let Some(capacity) = pool.storage.capacity() {
+            gauge!(QUEUE_CAPACITY, "pool" => pool.name.clone()).set(capacity as f64);
+        }
+        let (oldest, newest) = queue_ages(&pool).unzip();
+        gauge!(QUEUE_OLDEST_AGE, "pool" => pool.name.clone()).set(oldest.unwrap_or_default().as_secs_f64());
+        gauge!(QUEUE_NEWEST_AGE, "pool" => pool.name.clone()).set(newest.unwrap_or_default().as_secs_f64());
+    }
+
+    let grinder = generator.grinder();
+    gauge!(GENERATOR_ACTIVE).set(if generator.is_generating() { 1.0 } else { 0.0 });
+    gauge!(KEYS_PER_SECOND).set(grinder.keys_per_second().unwrap_or(0.0));
+    counter!(KEYS_TOTAL).absolute(grinder.total_keys());
+    let stats = grinder.stats();
+    if let Some(attempts) = stats.attempts_per_address {
+        gauge!(ATTEMPTS_PER_ADDRESS).set(attempts);
+    }
+    for (thread, worker) in stats.workers.iter().enumerate() {
+        gauge!(THREAD_KEYS_PER_SECOND, "thread" => thread.to_string()).set(worker.keys_per_second().unwrap_or(0.0));
+    }
+
+    // The writer and the database are shared by every sled pool
+    if let Some(storage) = generator.pools().iter().find_map(|pool| pool.storage.sled()) {
+        if let Some(persister) = storage.persister() {
+            gauge!(PERSIST_PENDING).set(persister.pending() as f64);
+            gauge!(PERSIST_LAG).set(persister.lag_ms() as f64 / 1000.0);
+            counter!(PERSIST_FAILED).absolute(persister.failed());
+            gauge!(PERSIST_RETRYING).set(persister.retrying() as f64);
+            counter!(PERSIST_RETRIES).absolute(persister.retries());
+            gauge!(PERSIST_DEAD_LETTERS).set(persister.dead_letters() as f64);
+            counter!(PERSIST_REFUSED).absolute(persister.refused());
+            gauge!(STORAGE_DEGRADED).set(if persister.is_degraded() { 1.0 } else { 0.0 });
+            counter!(BREAKER_TRIPS).absolute(persister.breaker_trips());
+        }
+        match storage.size_on_disk() {
+            Ok(Some(bytes)) => gauge!(DB_SIZE).set(bytes as f64),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to read the database size: {}", e),
+        }
+    }
+
+    handle.render()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PetGeneratorConfig;
+    use crate::pet::{MemoryStorage, Storage};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_render_samples_pool_gauges() {
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new().with_capacity(50));
+        let config: PetGeneratorConfig = serde_json::from_value(serde_json::json!({
+            "pool_size": 2,
+            "batch_size": 1,
+            "db_path": "",
+            "pattern": "."
+        }))
+        .unwrap();
+        let generator = PetGenerator::new(storage, config).await.unwrap();
+
+        let handle = install_recorder().unwrap();
+        record_dispensed("metrics-test", 2);
+        let body = render(&handle, &generator).await;
+
+        assert!(body.contains("# TYPE pet_addresses_dispensed_total counter"));
+        assert!(body.contains("pet_addresses_dispensed_total{pool=\"metrics-test\"} 2"));
+        assert!(body.contains("pet_queue_depth{pool=\"default\"} 0"));
+        assert!(body.contains("pet_queue_capacity{pool=\"default\"} 50"));
+        assert!(body.contains("pet_generator_active 0"));
+        assert!(body.contains("pet_queue_oldest_age_seconds{pool=\"default\"} 0"));
+
+        let pool = generator.pool("default").unwrap();
+        assert!(queue_ages(&pool).is_none());
+        pool.storage
+            .store_address(crate::pet::PetAddress {
+                public_key: "key".to_string(),
+                private_key: "secret".to_string().into(),
+                address: "aPet".to_string(),
+            })
+            .unwrap();
+        let (oldest, newest) = queue_ages(&pool).unwrap();
+        assert!(oldest >= newest && oldest < Duration::from_secs(5));
+    }
+}
@@ -1,7 +1,80 @@
 pub mod generator;
 pub mod storage;
 pub mod address;
+pub mod alerts;
+pub mod archive;
+pub mod audit;
+pub mod backend;
+pub mod backup;
+pub mod benchmark;
+pub mod codec;
+pub mod dedup;
+pub mod difficulty;
+pub mod encryption;
+pub mod dry_run;
+pub mod embedded;
+pub mod events;
+pub mod grinder;
+pub mod http;
+pub mod idempotency;
+pub mod jobs;
+pub mod memory;
+pub mod metrics;
+pub mod overrides;
+pub mod persistence;
+pub mod primary;
+pub mod recipients;
+#[cfg(feature = "redb")]
+pub mod redb;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod s3;
+pub mod secret;
+pub mod sharding;
+pub mod snapshot;
+pub mod stats;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod traced;
+pub mod usage;
+pub mod vault;
+pub mod webhooks;
+pub mod workers;
 
-pub use generator::PetGenerator;
-pub use storage::PetStorage;
-pub use address::{PetAddress, PetAddressInfo};
\ No newline at end of file
+pub use generator::{PatternPool, PetGenerator, DEFAULT_POOL};
+pub use storage::{Lease, PetStorage, PoolMaintenance, ReconcileReport, StorageDiagnostics, VerifyReport};
+pub use backend::{Storage, StoreError};
+pub use memory::MemoryStorage;
+pub use primary::PrimaryStorage;
+pub use traced::TracedStorage;
+pub use archive::{DispensedFilter, DispensedRecord, Requester};
+pub use audit::{AuditAction, AuditEntry, AuditEvent, AuditLog, AuditVerifyReport, AuditedStorage};
+pub use backup::{parse_dump, DumpFormat, DumpRecord, KeyFileFormat};
+pub use address::{AddressMatcher, PatternSpec, PetAddress, PetAddressInfo, DEFAULT_TARGET_SUFFIX};
+pub use idempotency::{IdempotencyCache, ReplayMismatch};
+pub use jobs::{Job, JobManager, JobStatus};
+pub use benchmark::{benchmark, BenchmarkReport};
+pub use difficulty::{estimate, match_probability, DifficultyEstimate};
+pub use encryption::KeyRing;
+pub use dry_run::{dry_run, DryRunReport};
+pub use embedded::{Generator, GeneratorBuilder, KeypairStream, Keypairs};
+pub use events::{EventBus, GeneratorEvent};
+pub use alerts::{start_watermark_watcher, WatermarkAlert};
+pub use grinder::Grinder;
+pub use snapshot::{start_snapshots, RestoreReport, SnapshotStore};
+pub use secret::SecretKey;
+pub use sharding::{ShardLetters, ShardPeer, ShardRouter, SHARD_FORWARDED_HEADER};
+pub use usage::{Quota, UsageError, UsageStore};
+pub use overrides::{OverrideStore, RuntimeOverrides};
+pub use recipients::{RecipientKey, RecipientRegistry};
+pub use vault::{HeldKey, KeyVault};
+pub use webhooks::{SubscribeError, Subscription, SubscriptionStatus, SubscriptionStore, WebhookDispatcher};
+pub use workers::{Assignment, Worker, WorkerRegistry};
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStorage;
+#[cfg(feature = "redis")]
+pub use redis::RedisStorage;
+#[cfg(feature = "redb")]
+pub use self::redb::RedbStorage;
\ No newline at end of file
@@ -0,0 +1,146 @@
+//! Settings changed at runtime through `PATCH /admin/config`. They are kept
+//! in sled so they survive a restart, where they are applied over the config
+//! file and environment.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use std::sync::{Arc, Mutex};
+
+use crate::config::{AppConfig, RateLimitConfig};
+
+/// Sled key of the persisted overrides, outside every pool's key space
+const OVERRIDES_KEY: &[u8] = b"overrides";
+
+/// Settings overridden at runtime; unset fields follow the config file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_size: Option<usize>,
+    /// The complete set of extra suffix pools
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_suffixes: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+impl RuntimeOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Names of the overridden settings
+    pub fn overridden(&self) -> Vec<String> {
+        [
+            ("pool_size", self.pool_size.is_some()),
+            ("extra_suffixes", self.extra_suffixes.is_some()),
+            ("threads", self.threads.is_some()),
+            ("rate_limit", self.rate_limit.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, set)| *set)
+        .map(|(name, _)| name.to_string())
+        .collect()
+    }
+
+    /// Layer `newer` on top; its set fields win
+    pub fn merge(&mut self, newer: RuntimeOverrides) {
+        self.pool_size = newer.pool_size.or(self.pool_size);
+        self.extra_suffixes = newer.extra_suffixes.or(self.extra_suffixes.take());
+        self.threads = newer.threads.or(self.threads);
+        self.rate_limit = newer.rate_limit.or(self.rate_limit.take());
+    }
+
+    /// Write the overridden settings into `config`
+    pub fn apply(&self, config: &mut AppConfig) {
+        if let Some(pool_size) = self.pool_size {
+            config.pet_generator.pool_size = pool_size;
+        }
+        if let Some(extra_suffixes) = &self.extra_suffixes {
+            config.pet_generator.extra_suffixes = extra_suffixes.clone();
+        }
+        if let Some(threads) = self.threads {
+            config.pet_generator.threads = threads;
+        }
+        if let Some(rate_limit) = &self.rate_limit {
+            config.rate_limit = rate_limit.clone();
+        }
+    }
+}
+
+/// Where the overrides are persisted: sled, or memory for other backends
+#[derive(Clone)]
+pub struct OverrideStore {
+    db: Option<Db>,
+    memory: Arc<Mutex<RuntimeOverrides>>,
+}
+
+impl OverrideStore {
+    pub fn new(db: Option<Db>) -> Self {
+        Self {
+            db,
+            memory: Arc::new(Mutex::new(RuntimeOverrides::default())),
+        }
+    }
+
+    pub fn load(&self) -> Result<RuntimeOverrides> {
+        match &self.db {
+            Some(db) => match db.get(OVERRIDES_KEY)? {
+                Some(value) => Ok(serde_json::from_slice(&value)?),
+                None => Ok(RuntimeOverrides::default()),
+            },
+            None => Ok(self.memory.lock().unwrap_or_else(|e| e.into_inner()).clone()),
+        }
+    }
+
+    pub fn save(&self, overrides: &RuntimeOverrides) -> Result<()> {
+        match &self.db {
+            Some(db) => {
+                db.insert(OVERRIDES_KEY, serde_json::to_vec(overrides)?)?;
+                db.flush()?;
+            }
+            None => *self.memory.lock().unwrap_or_else(|e| e.into_inner()) = overrides.clone(),
+        }
+        Ok(())
+    }
+
+    /// Forget every override; the config file applies again from the next restart
+    pub fn clear(&self) -> Result<()> {
+        self.save(&RuntimeOverrides::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_persist_and_merge() {
+        let store = OverrideStore::new(Some(sled::Config::new().temporary(true).open().unwrap()));
+        assert!(store.load().unwrap().is_empty());
+
+        let mut overrides = RuntimeOverrides {
+            pool_size: Some(500),
+            extra_suffixes: Some(vec!["Cat".to_string()]),
+            ..Default::default()
+        };
+        overrides.merge(RuntimeOverrides {
+            pool_size: Some(800),
+            threads: Some(2),
+            ..Default::default()
+        });
+        store.save(&overrides).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.pool_size, Some(800));
+        assert_eq!(loaded.extra_suffixes, Some(vec!["Cat".to_string()]));
+        assert_eq!(loaded.threads, Some(2));
+        assert_eq!(loaded.rate_limit, None);
+        assert_eq!(loaded.overridden(), ["pool_size", "extra_suffixes", "threads"]);
+
+        store.clear().unwrap();
+        assert!(store.load().unwrap().is_empty());
+    }
+}
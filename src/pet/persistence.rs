@@ -0,0 +1,853 @@
+use anyhow::{anyhow, Context, Result};
+use sled::{Batch, Db};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use zeroize::Zeroizing;
+
+use super::backend::StoreError;
+
+/// Writes that can be queued before callers block on the writer
+const CHANNEL_CAPACITY: usize = 10_000;
+/// Pending writes at which stores and pops are refused with `StoreError::Busy`,
+/// kept below the channel capacity so admitted writes rarely block
+const BUSY_THRESHOLD: usize = CHANNEL_CAPACITY * 9 / 10;
+/// Most writes applied in one sled batch; a group is never split, so a batch
+/// can run over by the size of its last group
+const MAX_BATCH: usize = 512;
+/// How often applied writes are flushed to disk
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// Tree holding writes that failed every retry, for inspection or replay
+pub(crate) const DEAD_LETTER_TREE: &str = "persist_dead_letters";
+/// Key the writer rewrites while degraded, to find out whether sled recovered
+const PROBE_KEY: &str = "persist_probe";
+/// Consecutive failed batches or flushes that open the circuit breaker
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// How a failed batch is retried: `base`, doubled after each failure up to
+/// `max_delay`, `max_retries` times before its writes are dead-lettered
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+    base: Duration,
+    max_delay: Duration,
+    max_retries: u32,
+}
+
+impl RetryPolicy {
+    /// About 12 seconds of retries in total
+    const DEFAULT: Self = Self {
+        base: Duration::from_millis(100),
+        max_delay: Duration::from_secs(5),
+        max_retries: 6,
+    };
+
+    fn delay(&self, retry: u32) -> Duration {
+        self.base.saturating_mul(1 << retry.min(16)).min(self.max_delay)
+    }
+}
+
+/// A sled mutation, applied in the order it was queued
+#[derive(Debug, Clone)]
+pub(crate) enum WriteOp {
+    /// Encoded records can hold plaintext private keys, so the value is
+    /// wiped once it has been written (or given up on)
+    Insert { key: String, value: Zeroizing<Vec<u8>> },
+    Remove { key: String },
+    /// Remove every key under the prefix
+    ClearPrefix { prefix: String },
+    /// Store `value` as a big-endian u64 unless the stored value is already
+    /// larger, so counter writes racing through the queue never go backwards
+    RaiseCounter { key: String, value: u64 },
+}
+
+enum Message {
+    Write(WriteOp, Instant),
+    /// Writes that must land in the same sled batch, e.g. a record and the
+    /// counter that covers its ID
+    Group(Vec<WriteOp>, Instant),
+    /// Reply once everything queued before it is applied and flushed, with an
+    /// error if any of it was dead-lettered
+    Flush(oneshot::Sender<Result<()>>),
+}
+
+#[derive(Debug, Default)]
+struct PersistStats {
+    pending: AtomicUsize,
+    last_lag_ms: AtomicU64,
+    failed: AtomicU64,
+    // Writes of the batch being retried; later writes wait behind it
+    retrying: AtomicUsize,
+    retries: AtomicU64,
+    dead_letters: AtomicU64,
+    refused: AtomicU64,
+    breaker: Breaker,
+}
+
+/// Circuit breaker over sled writes: `failure_threshold` failed batches or
+/// flushes in a row put the database in degraded mode, and the next success
+/// takes it out. A threshold of 0 never opens it.
+#[derive(Debug)]
+struct Breaker {
+    failure_threshold: AtomicU32,
+    refuse_stores: AtomicBool,
+    consecutive_failures: AtomicU32,
+    degraded: AtomicBool,
+    trips: AtomicU64,
+}
+
+impl Default for Breaker {
+    fn default() -> Self {
+        Self {
+            failure_threshold: AtomicU32::new(DEFAULT_FAILURE_THRESHOLD),
+            refuse_stores: AtomicBool::new(false),
+            consecutive_failures: AtomicU32::new(0),
+            degraded: AtomicBool::new(false),
+            trips: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Breaker {
+    fn record_failure(&self, error: &dyn std::fmt::Display) {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed).saturating_add(1);
+        let threshold = self.failure_threshold.load(Ordering::Relaxed);
+        if threshold > 0 && failures >= threshold && !self.degraded.swap(true, Ordering::Relaxed) {
+            self.trips.fetch_add(1, Ordering::Relaxed);
+            tracing::error!(
+                "🔌 Sled failed {} times in a row; degraded mode, addresses are no longer durable: {}",
+                failures,
+                error
+            );
+        }
+    }
+
+    fn record_success(&self) {
+        let failures = self.consecutive_failures.swap(0, Ordering::Relaxed);
+        if self.degraded.swap(false, Ordering::Relaxed) {
+            tracing::info!("🔌 Sled writes succeed again after {} failures; leaving degraded mode", failures);
+        }
+    }
+}
+
+/// Single background writer shared by every pool of one database. Writes go
+/// through a bounded channel, so they apply in order and a burst blocks the
+/// producers instead of piling up unbounded tasks. The writer batches whatever
+/// is queued into one sled batch and flushes to disk on an interval.
+///
+/// Each batch is applied atomically, counter raises included, so a crash
+/// leaves the database as of the end of some batch and never with a record
+/// whose ID the stored counter does not cover.
+///
+/// The writer holds its own `Db` handle; sled synchronises internally, so
+/// readers never wait for a batch to apply.
+///
+/// Once the writer falls `BUSY_THRESHOLD` writes behind, `admit` refuses new
+/// stores and pops so the API answers 503 instead of tying up request threads;
+/// writes that cannot be refused (acks, sweeps) still block for room.
+///
+/// A batch that fails is retried with exponential backoff before anything
+/// queued after it, keeping the order. When every retry fails, its writes are
+/// applied one by one and those that still fail go to the dead-letter tree.
+///
+/// Repeated failures open a circuit breaker (`is_degraded`). The in-memory
+/// queue keeps serving; with `refuse_stores` set, `admit_store` also turns
+/// new addresses away so none are handed out that were never on disk.
+#[derive(Clone)]
+pub(crate) struct Persister {
+    tx: SyncSender<Message>,
+    stats: Arc<PersistStats>,
+}
+
+impl Persister {
+    /// Start the writer thread; it exits once every handle is dropped
+    pub fn start(db: Db) -> Result<Self> {
+        let (tx, rx) = sync_channel(CHANNEL_CAPACITY);
+        let stats = Arc::new(PersistStats::default());
+
+        let worker_stats = Arc::clone(&stats);
+        std::thread::Builder::new()
+            .name("sled-persist".to_string())
+            .spawn(move || run(db, rx, worker_stats))
+            .context("Failed to start persistence writer")?;
+
+        Ok(Self { tx, stats })
+    }
+
+    /// Queue a write. Blocks only when the channel is full.
+    pub fn write(&self, op: WriteOp) {
+        self.send(Message::Write(op, Instant::now()), 1);
+    }
+
+    /// Queue writes that are applied together or not at all
+    pub fn write_all(&self, ops: Vec<WriteOp>) {
+        let count = ops.len();
+        if count > 0 {
+            self.send(Message::Group(ops, Instant::now()), count);
+        }
+    }
+
+    fn send(&self, message: Message, count: usize) {
+        self.stats.pending.fetch_add(count, Ordering::Relaxed);
+
+        let sent = match self.tx.try_send(message) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(message)) => {
+                tracing::debug!("Persistence queue full, waiting for the writer");
+                self.tx.send(message).map_err(|_| ())
+            }
+            Err(TrySendError::Disconnected(_)) => Err(()),
+        };
+
+        if sent.is_err() {
+            self.stats.pending.fetch_sub(count, Ordering::Relaxed);
+            self.stats.failed.fetch_add(count as u64, Ordering::Relaxed);
+            tracing::error!("Persistence writer stopped; {} writes dropped", count);
+        }
+    }
+
+    /// Refuse an operation that would queue `writes` more writes while the
+    /// writer is this far behind. Checked before anything changes in memory.
+    pub fn admit(&self, writes: usize) -> Result<(), StoreError> {
+        let pending = self.pending();
+        if pending + writes > BUSY_THRESHOLD {
+            self.stats.refused.fetch_add(1, Ordering::Relaxed);
+            return Err(StoreError::Busy { pending });
+        }
+        Ok(())
+    }
+
+    /// True while stores and pops are being refused
+    pub fn is_busy(&self) -> bool {
+        self.pending() >= BUSY_THRESHOLD
+    }
+
+    /// Like `admit`, and also refuses a store while the breaker is open and
+    /// stores are refused in degraded mode
+    pub fn admit_store(&self, writes: usize) -> Result<(), StoreError> {
+        self.admit(writes)?;
+        if self.refuses_stores() {
+            self.stats.refused.fetch_add(1, Ordering::Relaxed);
+            return Err(StoreError::Degraded {
+                failures: self.consecutive_failures(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Open the breaker after `failure_threshold` consecutive failures (0
+    /// never opens it), refusing stores while open if `refuse_stores`. Shared
+    /// by every pool on this writer.
+    pub fn set_breaker(&self, failure_threshold: u32, refuse_stores: bool) {
+        let breaker = &self.stats.breaker;
+        breaker.failure_threshold.store(failure_threshold, Ordering::Relaxed);
+        breaker.refuse_stores.store(refuse_stores, Ordering::Relaxed);
+    }
+
+    /// True while sled keeps failing and writes may be lost
+    pub fn is_degraded(&self) -> bool {
+        self.stats.breaker.degraded.load(Ordering::Relaxed)
+    }
+
+    /// True while degraded mode turns new addresses away
+    pub fn refuses_stores(&self) -> bool {
+        self.is_degraded() && self.stats.breaker.refuse_stores.load(Ordering::Relaxed)
+    }
+
+    /// Failed batches and flushes since the last success
+    pub fn consecutive_failures(&self) -> u32 {
+        self.stats.breaker.consecutive_failures.load(Ordering::Relaxed)
+    }
+
+    /// Times the breaker opened since startup
+    pub fn breaker_trips(&self) -> u64 {
+        self.stats.breaker.trips.load(Ordering::Relaxed)
+    }
+
+    /// Wait until every write queued so far is applied and flushed to disk.
+    /// Fails if the flush failed, or if writes queued since the previous
+    /// flush were dead-lettered.
+    pub async fn flush(&self) -> Result<()> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let tx = self.tx.clone();
+        // A full channel blocks the send; keep that off the async runtime
+        tokio::task::spawn_blocking(move || tx.send(Message::Flush(reply_tx)))
+            .await
+            .context("Persistence flush task failed")?
+            .map_err(|_| anyhow!("Persistence writer stopped"))?;
+
+        reply_rx.await.map_err(|_| anyhow!("Persistence writer stopped"))?
+    }
+
+    /// Writes queued but not yet applied
+    pub fn pending(&self) -> usize {
+        self.stats.pending.load(Ordering::Relaxed)
+    }
+
+    /// How long the oldest write of the last applied batch waited in the queue
+    pub fn lag_ms(&self) -> u64 {
+        self.stats.last_lag_ms.load(Ordering::Relaxed)
+    }
+
+    /// Writes that could not be applied since startup, after every retry
+    pub fn failed(&self) -> u64 {
+        self.stats.failed.load(Ordering::Relaxed)
+    }
+
+    /// Writes in the batch currently waiting to be retried
+    pub fn retrying(&self) -> usize {
+        self.stats.retrying.load(Ordering::Relaxed)
+    }
+
+    /// Batch retries since startup
+    pub fn retries(&self) -> u64 {
+        self.stats.retries.load(Ordering::Relaxed)
+    }
+
+    /// Writes kept in the dead-letter tree, including ones from earlier runs
+    pub fn dead_letters(&self) -> u64 {
+        self.stats.dead_letters.load(Ordering::Relaxed)
+    }
+
+    /// Stores and pops refused by `admit` and `admit_store` since startup
+    pub fn refused(&self) -> u64 {
+        self.stats.refused.load(Ordering::Relaxed)
+    }
+}
+
+fn run(db: Db, rx: Receiver<Message>, stats: Arc<PersistStats>) {
+    let dead_letters = match db.open_tree(DEAD_LETTER_TREE) {
+        Ok(tree) => {
+            if !tree.is_empty() {
+                tracing::warn!("{} failed writes are kept in the '{}' tree", tree.len(), DEAD_LETTER_TREE);
+            }
+            stats.dead_letters.store(tree.len() as u64, Ordering::Relaxed);
+            Some(tree)
+        }
+        Err(e) => {
+            tracing::error!("Failed to open the '{}' tree; failed writes will be dropped: {}", DEAD_LETTER_TREE, e);
+            None
+        }
+    };
+    let mut dirty = false;
+    let mut last_flush = Instant::now();
+    // Writes dead-lettered after the last answered flush, owed to the next one
+    let mut unreported = 0;
+
+    loop {
+        let first = match rx.recv_timeout(FLUSH_INTERVAL) {
+            Ok(message) => Some(message),
+            Err(RecvTimeoutError::Timeout) => {
+                // Nothing else will show that sled recovered while the API is idle
+                if stats.breaker.degraded.load(Ordering::Relaxed) {
+                    match probe(&db) {
+                        Ok(()) => stats.breaker.record_success(),
+                        Err(e) => stats.breaker.record_failure(&e),
+                    }
+                }
+                None
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                if dirty {
+                    if let Err(e) = db.flush() {
+                        tracing::warn!("Final persistence flush failed: {}", e);
+                    }
+                }
+                return;
+            }
+        };
+
+        let mut writes = Vec::new();
+        // Positions in `writes` of each message's writes, which stand or fall together
+        let mut groups = Vec::new();
+        let mut waiters = Vec::new();
+        let mut next = first;
+        while let Some(message) = next {
+            let start = writes.len();
+            match message {
+                Message::Write(op, queued_at) => writes.push((op, queued_at)),
+                Message::Group(ops, queued_at) => writes.extend(ops.into_iter().map(|op| (op, queued_at))),
+                Message::Flush(reply) => waiters.push((reply, start)),
+            }
+            if writes.len() > start {
+                groups.push(start..writes.len());
+            }
+            if writes.len() >= MAX_BATCH {
+                break;
+            }
+            next = rx.try_recv().ok();
+        }
+
+        let failed = match writes.is_empty() {
+            true => Vec::new(),
+            false => {
+                dirty = true;
+                persist(writes, &groups, &stats, dead_letters.as_ref(), RetryPolicy::DEFAULT, |writes| apply(&db, writes))
+            }
+        };
+
+        let mut flush_error = None;
+        if dirty && (!waiters.is_empty() || last_flush.elapsed() >= FLUSH_INTERVAL) {
+            match db.flush() {
+                Ok(_) => stats.breaker.record_success(),
+                Err(e) => {
+                    tracing::warn!("Persistence flush failed: {}", e);
+                    stats.breaker.record_failure(&e);
+                    flush_error = Some(e.to_string());
+                }
+            }
+            dirty = false;
+            last_flush = Instant::now();
+        }
+
+        unreported = answer(waiters, &failed, flush_error.as_deref(), unreported);
+    }
+}
+
+/// Reply to the flush `waiters` of a batch, each paired with how many of its
+/// writes were queued before it. `failed` are the batch's dead-lettered groups
+/// and `unreported` the writes dead-lettered earlier that no flush has
+/// reported yet. Returns the failures left for the next flush.
+fn answer(
+    waiters: Vec<(oneshot::Sender<Result<()>>, usize)>,
+    failed: &[Range<usize>],
+    flush_error: Option<&str>,
+    unreported: usize,
+) -> usize {
+    let lost_before = |queued: usize| -> usize { failed.iter().filter(|group| group.start < queued).map(|group| group.len()).sum() };
+    let Some(last) = waiters.last().map(|(_, queued)| *queued) else {
+        return unreported + lost_before(usize::MAX);
+    };
+    for (reply, queued) in waiters {
+        let result = match (flush_error, unreported + lost_before(queued)) {
+            (Some(e), _) => Err(anyhow!("Persistence flush failed: {}", e)),
+            (None, 0) => Ok(()),
+            (None, lost) => Err(anyhow!("{} writes queued before the flush were dead-lettered", lost)),
+        };
+        let _ = reply.send(result);
+    }
+    // Failures after the last waiter go to the next flush
+    lost_before(usize::MAX) - lost_before(last)
+}
+
+/// Apply `writes` with `apply`, retrying the whole batch per `policy`. Blocks
+/// the writer between retries, so later writes queue up behind the batch.
+/// A failed batch left nothing behind, so a retry starts from clean state.
+/// `groups` splits `writes` into the units that are applied or given up on
+/// together; returns the groups that were dead-lettered.
+fn persist(
+    writes: Vec<(WriteOp, Instant)>,
+    groups: &[Range<usize>],
+    stats: &PersistStats,
+    dead_letters: Option<&sled::Tree>,
+    policy: RetryPolicy,
+    mut apply: impl FnMut(&[(WriteOp, Instant)]) -> sled::Result<()>,
+) -> Vec<Range<usize>> {
+    let count = writes.len();
+    let oldest = writes.first().map(|(_, queued_at)| *queued_at);
+    let mut failed = Vec::new();
+
+    let mut retry = 0;
+    loop {
+        let Err(e) = apply(&writes) else {
+            stats.breaker.record_success();
+            break;
+        };
+        stats.breaker.record_failure(&e);
+        if retry == policy.max_retries {
+            tracing::error!("Background persistence of {} writes failed {} times: {}", count, retry + 1, e);
+            // One group at a time, so only the ones that still fail are set aside
+            // and a group (a record and its counter, an ack and its archive
+            // entry) is never applied in part
+            for range in groups {
+                let group = &writes[range.clone()];
+                if let Err(e) = apply(group) {
+                    stats.failed.fetch_add(group.len() as u64, Ordering::Relaxed);
+                    for (op, _) in group {
+                        dead_letter(dead_letters, op, &e, stats);
+                    }
+                    failed.push(range.clone());
+                }
+            }
+            break;
+        }
+        let delay = policy.delay(retry);
+        tracing::warn!("Background persistence of {} writes failed, retrying in {:?}: {}", count, delay, e);
+        stats.retrying.store(count, Ordering::Relaxed);
+        stats.retries.fetch_add(1, Ordering::Relaxed);
+        std::thread::sleep(delay);
+        retry += 1;
+    }
+    if retry > 0 {
+        stats.retrying.store(0, Ordering::Relaxed);
+    }
+
+    stats.pending.fetch_sub(count, Ordering::Relaxed);
+    if let Some(oldest) = oldest {
+        stats.last_lag_ms.store(oldest.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+    failed
+}
+
+/// Write and flush the probe key; succeeds once sled takes writes again
+fn probe(db: &Db) -> sled::Result<()> {
+    db.insert(PROBE_KEY, &chrono::Utc::now().timestamp().to_be_bytes())?;
+    db.flush().map(|_| ())
+}
+
+/// Keep a write that failed every retry, with the error, under a new ID
+fn dead_letter(tree: Option<&sled::Tree>, op: &WriteOp, error: &sled::Error, stats: &PersistStats) {
+    // Only the writer thread adds entries, so the next ID cannot race
+    let stored = tree.map(|tree| {
+        let next_id = tree.last()?.and_then(|(id, _)| id.as_ref().try_into().ok()).map_or(0, |id| u64::from_be_bytes(id) + 1);
+        // The record holds the value, which can be a plaintext private key, so
+        // it is serialized straight into the tree and never formatted elsewhere
+        tree.insert(next_id.to_be_bytes(), dead_letter_record(op, error).to_string().into_bytes())
+    });
+    match stored {
+        Some(Ok(_)) => {
+            stats.dead_letters.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(Err(e)) => tracing::error!("Failed to dead-letter {} ({}): {}", describe(op), error, e),
+        None => tracing::error!("Dropped failed {}: {}", describe(op), error),
+    }
+}
+
+/// Op kind and key of a write, without its value
+fn describe(op: &WriteOp) -> String {
+    match op {
+        WriteOp::Insert { key, .. } => format!("insert of {}", key),
+        WriteOp::Remove { key } => format!("remove of {}", key),
+        WriteOp::ClearPrefix { prefix } => format!("clear of prefix {}", prefix),
+        WriteOp::RaiseCounter { key, .. } => format!("counter raise of {}", key),
+    }
+}
+
+/// JSON description of a failed write; binary values are base64
+fn dead_letter_record(op: &WriteOp, error: &sled::Error) -> serde_json::Value {
+    let (kind, key, value) = match op {
+        WriteOp::Insert { key, value } => ("insert", key, Some(openssl::base64::encode_block(value))),
+        WriteOp::Remove { key } => ("remove", key, None),
+        WriteOp::ClearPrefix { prefix } => ("clear_prefix", prefix, None),
+        WriteOp::RaiseCounter { key, value } => ("raise_counter", key, Some(value.to_string())),
+    };
+    serde_json::json!({
+        "op": kind,
+        "key": key,
+        "value": value,
+        "error": error.to_string(),
+        "failed_at": chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+/// Apply `writes` in order as one atomic sled batch. A later write to a key
+/// replaces an earlier one in the batch, so the outcome matches applying them
+/// one by one.
+fn apply(db: &Db, writes: &[(WriteOp, Instant)]) -> sled::Result<()> {
+    let mut batch = Batch::default();
+    // Keys inserted earlier in this batch, which a later prefix clear must also remove
+    let mut inserted: BTreeSet<&str> = BTreeSet::new();
+    let mut counters: HashMap<&str, u64> = HashMap::new();
+
+    for (op, _) in writes {
+        match op {
+            WriteOp::Insert { key, value } => {
+                batch.insert(key.as_bytes(), value.as_slice());
+                inserted.insert(key);
+            }
+            WriteOp::Remove { key } => {
+                batch.remove(key.as_bytes());
+                inserted.remove(key.as_str());
+            }
+            WriteOp::ClearPrefix { prefix } => {
+                // Only this thread writes these keys, so the scan sees everything
+                // that earlier batches left under the prefix
+                for result in db.scan_prefix(prefix.as_bytes()) {
+                    batch.remove(result?.0);
+                }
+                let cleared: Vec<&str> = inserted.range(prefix.as_str()..).take_while(|key| key.starts_with(prefix.as_str())).copied().collect();
+                for key in cleared {
+                    batch.remove(key.as_bytes());
+                    inserted.remove(key);
+                }
+            }
+            WriteOp::RaiseCounter { key, value } => {
+                let highest = counters.entry(key).or_default();
+                *highest = (*highest).max(*value);
+            }
+        }
+    }
+    for (key, value) in counters {
+        if stored_counter(db, key)? < value {
+            batch.insert(key.as_bytes(), &value.to_be_bytes());
+        }
+    }
+    db.apply_batch(batch)
+}
+
+fn stored_counter(db: &Db, key: &str) -> sled::Result<u64> {
+    Ok(db
+        .get(key.as_bytes())?
+        .and_then(|bytes| bytes.as_ref().try_into().ok())
+        .map(u64::from_be_bytes)
+        .unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `groups` of a batch holding a single write
+    const ONE_GROUP: &[Range<usize>] = std::slice::from_ref(&(0..1));
+
+    #[tokio::test]
+    async fn test_writes_apply_in_order() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let persister = Persister::start(db.clone()).unwrap();
+
+        for i in 0..1000 {
+            persister.write(WriteOp::Insert {
+                key: format!("k:{:04}", i),
+                value: vec![1].into(),
+            });
+        }
+        persister.write(WriteOp::Remove { key: "k:0000".to_string() });
+        persister.write(WriteOp::ClearPrefix { prefix: "k:09".to_string() });
+        persister.write(WriteOp::Insert {
+            key: "k:0999".to_string(),
+            value: vec![2].into(),
+        });
+        persister.flush().await.unwrap();
+
+        assert_eq!(persister.pending(), 0);
+        assert_eq!(persister.failed(), 0);
+        assert!(db.get("k:0000").unwrap().is_none());
+        assert_eq!(db.get("k:0999").unwrap().unwrap().as_ref(), &[2]);
+        // 1000 inserted, one removed, 900..=999 cleared, one re-inserted
+        assert_eq!(db.scan_prefix("k:").count(), 1000 - 1 - 100 + 1);
+    }
+
+    #[test]
+    fn test_a_batch_clears_its_own_earlier_inserts() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        db.insert("k:old", vec![0]).unwrap();
+        let now = Instant::now();
+        let insert = |key: &str| (WriteOp::Insert { key: key.to_string(), value: vec![1].into() }, now);
+
+        // One batch: the clear removes the stored key and the one inserted just before it
+        let writes = vec![
+            insert("k:a"),
+            insert("other"),
+            (WriteOp::ClearPrefix { prefix: "k:".to_string() }, now),
+            insert("k:b"),
+            (WriteOp::RaiseCounter { key: "counter".to_string(), value: 2 }, now),
+        ];
+        apply(&db, &writes).unwrap();
+
+        let keys: Vec<_> = db.iter().keys().map(|key| String::from_utf8(key.unwrap().to_vec()).unwrap()).collect();
+        assert_eq!(keys, ["counter", "k:b", "other"]);
+        assert_eq!(stored_counter(&db, "counter").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_failed_batches_are_retried_then_dead_lettered() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree(DEAD_LETTER_TREE).unwrap();
+        let stats = PersistStats::default();
+        let policy = RetryPolicy {
+            base: Duration::from_millis(1),
+            max_delay: Duration::from_millis(4),
+            max_retries: 3,
+        };
+        let write = |key: &str| (WriteOp::Insert { key: key.to_string(), value: vec![1].into() }, Instant::now());
+        let failure = || sled::Error::Unsupported("disk unavailable".to_string());
+
+        // A transient failure succeeds on the second retry
+        let mut calls = 0;
+        stats.pending.store(2, Ordering::Relaxed);
+        persist(vec![write("a"), write("b")], &[0..1, 1..2], &stats, Some(&tree), policy, |_| {
+            calls += 1;
+            if calls < 3 { Err(failure()) } else { Ok(()) }
+        });
+        assert_eq!((stats.retries.load(Ordering::Relaxed), stats.failed.load(Ordering::Relaxed)), (2, 0));
+        assert_eq!(stats.pending.load(Ordering::Relaxed), 0);
+
+        // A write that keeps failing is set aside with its group once the retries
+        // run out; other groups apply
+        let mut applied = Vec::new();
+        stats.pending.store(4, Ordering::Relaxed);
+        persist(vec![write("good"), write("bad"), write("grouped"), write("also-good")], &[0..1, 1..3, 3..4], &stats, Some(&tree), policy, |writes| {
+            match writes.iter().any(|(op, _)| matches!(op, WriteOp::Insert { key, .. } if key == "bad")) {
+                true => Err(failure()),
+                false => {
+                    applied.extend(writes.iter().map(|(op, _)| format!("{:?}", op)));
+                    Ok(())
+                }
+            }
+        });
+        assert_eq!(applied.len(), 2);
+        assert!(!applied.iter().any(|op| op.contains("grouped")));
+        assert_eq!(stats.retries.load(Ordering::Relaxed), 2 + 3);
+        assert_eq!(stats.failed.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.retrying.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.dead_letters.load(Ordering::Relaxed), 2);
+
+        let (id, record) = tree.first().unwrap().unwrap();
+        let record: serde_json::Value = serde_json::from_slice(&record).unwrap();
+        assert_eq!(id.as_ref(), 0u64.to_be_bytes());
+        assert_eq!((record["op"].as_str(), record["key"].as_str(), record["value"].as_str()), (Some("insert"), Some("bad"), Some("AQ==")));
+        assert!(record["error"].as_str().unwrap().contains("disk unavailable"));
+    }
+
+    #[test]
+    fn test_flush_fails_when_earlier_writes_were_dead_lettered() {
+        let waiter = || oneshot::channel::<Result<()>>();
+        let result = |mut rx: oneshot::Receiver<Result<()>>| rx.try_recv().unwrap().map_err(|e| e.to_string());
+
+        // A waiter hears of the failed groups queued before it only; the one
+        // queued after the last waiter is owed to the next flush
+        let ((early, early_rx), (late, late_rx)) = (waiter(), waiter());
+        assert_eq!(answer(vec![(early, 0), (late, 3)], &[1..3, 4..5], None, 0), 1);
+        assert_eq!(result(early_rx), Ok(()));
+        assert_eq!(result(late_rx), Err("2 writes queued before the flush were dead-lettered".to_string()));
+
+        // Without a waiter the debt grows, and the next flush pays it off
+        assert_eq!(answer(Vec::new(), ONE_GROUP, None, 1), 2);
+        let (next, next_rx) = waiter();
+        assert_eq!(answer(vec![(next, 0)], &[], None, 2), 0);
+        assert!(result(next_rx).unwrap_err().starts_with("2 writes"));
+
+        let (failed_flush, failed_flush_rx) = waiter();
+        answer(vec![(failed_flush, 0)], &[], Some("io error"), 0);
+        assert!(result(failed_flush_rx).unwrap_err().contains("io error"));
+    }
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_dead_lettered_values_stay_out_of_the_logs() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt().with_ansi(false).with_writer(move || writer.clone()).finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let secret = b"plaintext-private-key".to_vec();
+        let encoded = openssl::base64::encode_block(&secret);
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree(DEAD_LETTER_TREE).unwrap();
+        let policy = RetryPolicy {
+            base: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+            max_retries: 1,
+        };
+        let failure = || sled::Error::Unsupported("disk unavailable".to_string());
+        let write = || vec![(WriteOp::Insert { key: "pool:default:address:7".to_string(), value: secret.clone().into() }, Instant::now())];
+
+        // Dead-lettered to the tree, and dropped when there is no tree
+        let stats = PersistStats::default();
+        stats.pending.store(2, Ordering::Relaxed);
+        persist(write(), ONE_GROUP, &stats, Some(&tree), policy, |_| Err(failure()));
+        persist(write(), ONE_GROUP, &stats, None, policy, |_| Err(failure()));
+        assert_eq!(stats.dead_letters.load(Ordering::Relaxed), 1);
+        // The tree keeps the value so the write can be replayed
+        let (_, record) = tree.first().unwrap().unwrap();
+        assert!(String::from_utf8_lossy(&record).contains(&encoded));
+
+        let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("Dropped failed insert of pool:default:address:7"), "{}", logs);
+        assert!(!logs.contains(&encoded) && !logs.contains("plaintext-private-key"), "{}", logs);
+    }
+
+    #[test]
+    fn test_operations_are_refused_while_the_writer_is_behind() {
+        let (tx, _rx) = sync_channel(1);
+        let persister = Persister { tx, stats: Arc::default() };
+        assert_eq!(persister.admit(2), Ok(()));
+
+        persister.stats.pending.store(BUSY_THRESHOLD - 1, Ordering::Relaxed);
+        assert_eq!(persister.admit(1), Ok(()));
+        assert_eq!(persister.admit(2), Err(StoreError::Busy { pending: BUSY_THRESHOLD - 1 }));
+        assert!(!persister.is_busy());
+        assert_eq!(persister.refused(), 1);
+
+        // Room again once the writer catches up
+        persister.stats.pending.store(0, Ordering::Relaxed);
+        assert_eq!(persister.admit(2), Ok(()));
+    }
+
+    #[test]
+    fn test_breaker_opens_on_repeated_failures_and_closes_on_success() {
+        let (tx, _rx) = sync_channel(1);
+        let persister = Persister { tx, stats: Arc::default() };
+        persister.set_breaker(3, true);
+        let policy = RetryPolicy {
+            base: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+            max_retries: 2,
+        };
+        // Each batch holds one queued write
+        let write = || {
+            persister.stats.pending.fetch_add(1, Ordering::Relaxed);
+            vec![(WriteOp::Remove { key: "k".to_string() }, Instant::now())]
+        };
+        let failure = || sled::Error::Unsupported("disk full".to_string());
+
+        // The first attempt and two retries reach the threshold
+        persist(write(), ONE_GROUP, &persister.stats, None, policy, |_| Err(failure()));
+        assert_eq!(persister.consecutive_failures(), 3);
+        assert!(persister.is_degraded() && persister.refuses_stores());
+        assert_eq!(persister.admit_store(2), Err(StoreError::Degraded { failures: 3 }));
+        // Pops are still admitted
+        assert_eq!(persister.admit(2), Ok(()));
+        assert_eq!(persister.breaker_trips(), 1);
+
+        persist(write(), ONE_GROUP, &persister.stats, None, policy, |_| Ok(()));
+        assert!(!persister.is_degraded());
+        assert_eq!((persister.consecutive_failures(), persister.admit_store(2)), (0, Ok(())));
+
+        // Degraded mode can be reported without refusing stores
+        persister.set_breaker(1, false);
+        persist(write(), ONE_GROUP, &persister.stats, None, policy, |_| Err(failure()));
+        assert!(persister.is_degraded() && !persister.refuses_stores());
+        assert_eq!(persister.admit_store(2), Ok(()));
+        assert_eq!(persister.breaker_trips(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_counter_never_goes_backwards() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let persister = Persister::start(db.clone()).unwrap();
+
+        for value in [3, 7, 5] {
+            persister.write(WriteOp::RaiseCounter {
+                key: "counter".to_string(),
+                value,
+            });
+        }
+        persister.flush().await.unwrap();
+        assert_eq!(stored_counter(&db, "counter").unwrap(), 7);
+
+        // A stale value queued later (e.g. by a racing store) is ignored
+        persister.write(WriteOp::RaiseCounter {
+            key: "counter".to_string(),
+            value: 6,
+        });
+        persister.flush().await.unwrap();
+        assert_eq!(stored_counter(&db, "counter").unwrap(), 7);
+    }
+}
@@ -0,0 +1,116 @@
+//! Versioned schema of the PostgreSQL backend. Each migration runs once, in
+//! its own transaction, and is recorded in `pinpet_schema_migrations`.
+
+use anyhow::{bail, Context, Result};
+
+use super::wire::{literal, Connection, ConnectionPool};
+
+/// Advisory lock held while migrating, so replicas starting together apply each migration once
+const MIGRATION_LOCK: i64 = 0x70_69_6e_70_65_74;
+
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every migration in order. Append new ones; never edit one that has shipped.
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    name: "queue and address history",
+    sql: r#"
+CREATE TABLE pinpet_pools (
+    pool TEXT PRIMARY KEY,
+    next_id BIGINT NOT NULL
+);
+
+-- Every address ever enqueued; rows stay after dispensing, without the private key
+CREATE TABLE pinpet_addresses (
+    public_key TEXT PRIMARY KEY,
+    address TEXT NOT NULL,
+    pool TEXT NOT NULL,
+    id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    dispensed_at TIMESTAMPTZ,
+    requester TEXT,
+    user_agent TEXT,
+    UNIQUE (pool, id)
+);
+CREATE INDEX pinpet_addresses_dispensed_at ON pinpet_addresses (dispensed_at);
+
+-- Addresses waiting to be dispensed, with the (possibly sealed) private key
+CREATE TABLE pinpet_queue (
+    pool TEXT NOT NULL,
+    id BIGINT NOT NULL,
+    public_key TEXT NOT NULL REFERENCES pinpet_addresses (public_key),
+    record JSONB NOT NULL,
+    PRIMARY KEY (pool, id)
+);
+"#,
+}];
+
+/// Apply every migration the database has not seen yet; returns how many ran
+pub fn run(pool: &ConnectionPool) -> Result<usize> {
+    pool.with_connection(|connection| {
+        connection.query(&format!(
+            "CREATE TABLE IF NOT EXISTS pinpet_schema_migrations (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            );
+            SELECT pg_advisory_lock({})",
+            MIGRATION_LOCK
+        ))?;
+        let result = apply_pending(connection);
+        // A failed migration leaves its transaction open; end it so the unlock runs
+        connection.query(&format!("ROLLBACK; SELECT pg_advisory_unlock({})", MIGRATION_LOCK))?;
+        result
+    })
+}
+
+fn apply_pending(connection: &mut Connection) -> Result<usize> {
+    let applied: Vec<i32> = connection
+        .query("SELECT version FROM pinpet_schema_migrations ORDER BY version")?
+        .into_iter()
+        .filter_map(|row| row.into_iter().next().flatten()?.parse().ok())
+        .collect();
+    let latest = MIGRATIONS.last().map_or(0, |m| m.version);
+    if let Some(newer) = applied.iter().find(|version| **version > latest) {
+        bail!(
+            "PostgreSQL schema is at version {}, newer than this build knows ({}); upgrade the server",
+            newer,
+            latest
+        );
+    }
+
+    let mut ran = 0;
+    for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+        connection
+            .query(&format!(
+                "BEGIN;
+                {}
+                INSERT INTO pinpet_schema_migrations (version, name) VALUES ({}, {});
+                COMMIT",
+                migration.sql,
+                migration.version,
+                literal(migration.name)?
+            ))
+            .with_context(|| format!("PostgreSQL migration {} ({}) failed", migration.version, migration.name))?;
+        tracing::info!("Applied PostgreSQL migration {}: {}", migration.version, migration.name);
+        ran += 1;
+    }
+    Ok(ran)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_numbered_in_order() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, index as i32 + 1, "{}", migration.name);
+            assert!(!migration.sql.contains("COMMIT"), "{} manages its own transaction", migration.name);
+        }
+    }
+}
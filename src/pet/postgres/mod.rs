@@ -0,0 +1,323 @@
+//! PostgreSQL storage backend (`--features postgres`): queued addresses live
+//! in `pinpet_queue` and every address ever enqueued stays in
+//! `pinpet_addresses`, so the history of generated and dispensed addresses can
+//! be queried with SQL. Speaks the wire protocol directly over TCP, so the
+//! feature adds no dependencies.
+
+pub mod migrations;
+mod wire;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::address::{PetAddress, PetAddressInfo};
+use super::archive::DispensedRecord;
+use super::backend::{Storage, StoreError};
+use super::encryption::{self, KeyRing};
+use super::generator::DEFAULT_POOL;
+use wire::{literal, ConnectionInfo, ConnectionPool, ServerError};
+
+/// SQLSTATE of a unique constraint violation
+const UNIQUE_VIOLATION: &str = "23505";
+
+/// `Storage` on PostgreSQL. Each store and pop is a single statement, so it
+/// commits atomically; pops lock rows with `SKIP LOCKED` so concurrent
+/// replicas never hand out the same address.
+pub struct PostgresStorage {
+    pool: Arc<ConnectionPool>,
+    /// Pool name as an SQL literal
+    name: String,
+    key_ring: Option<Arc<KeyRing>>,
+}
+
+impl PostgresStorage {
+    /// Connect to `url` and bring the schema up to date
+    pub fn connect(url: &str, pool_size: usize, timeout: Duration) -> Result<Self> {
+        let pool = ConnectionPool::new(ConnectionInfo::parse(url)?, pool_size, timeout);
+        let applied = migrations::run(&pool)?;
+
+        tracing::info!(
+            "Connected to PostgreSQL at {}:{}/{} ({} migrations applied)",
+            pool.info.host,
+            pool.info.port,
+            pool.info.database,
+            applied
+        );
+        Ok(Self {
+            pool: Arc::new(pool),
+            name: literal(DEFAULT_POOL)?,
+            key_ring: None,
+        })
+    }
+
+    /// Encrypt private keys before they are written to PostgreSQL
+    pub fn with_key_ring(mut self, key_ring: Arc<KeyRing>) -> Self {
+        self.key_ring = Some(key_ring);
+        self
+    }
+
+    fn decode_record(&self, json: &str) -> Result<PetAddressInfo> {
+        let address_info: PetAddressInfo = serde_json::from_str(json).context("Failed to deserialize address info")?;
+        match &self.key_ring {
+            Some(key_ring) => key_ring.open_record(address_info),
+            None if encryption::is_sealed(&address_info.address.private_key) => anyhow::bail!(
+                "Address {} has an encrypted private key but encryption is not configured",
+                address_info.id
+            ),
+            None => Ok(address_info),
+        }
+    }
+
+    fn pop(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        let rows = self.pool.query(&format!(
+            "WITH popped AS (
+                DELETE FROM pinpet_queue WHERE pool = {name} AND id IN (
+                    SELECT id FROM pinpet_queue WHERE pool = {name}
+                    ORDER BY id LIMIT {count} FOR UPDATE SKIP LOCKED
+                ) RETURNING id, public_key, record
+            ), dispensed AS (
+                UPDATE pinpet_addresses SET dispensed_at = now()
+                FROM popped WHERE pinpet_addresses.public_key = popped.public_key
+            )
+            SELECT record::text FROM popped ORDER BY id",
+            name = self.name,
+            count = count,
+        ))?;
+
+        rows.into_iter()
+            .map(|row| {
+                let record = row.into_iter().next().flatten().ok_or_else(|| anyhow!("Missing queue record"))?;
+                self.decode_record(&record)
+            })
+            .collect()
+    }
+}
+
+fn first_value(rows: Vec<wire::Row>) -> Option<String> {
+    rows.into_iter().next()?.into_iter().next().flatten()
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    fn backend_name(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn store_address(&self, address: PetAddress) -> Result<u64> {
+        let public_key = address.public_key.clone();
+        // The ID is assigned by the statement and patched into the record
+        let address_info = PetAddressInfo {
+            id: 0,
+            address,
+            created_at: Utc::now(),
+        };
+        let record = match &self.key_ring {
+            Some(key_ring) => serde_json::to_string(&key_ring.seal_record(&address_info)?),
+            None => serde_json::to_string(&address_info),
+        }
+        .context("Failed to serialize address info")?;
+
+        let result = self.pool.query(&format!(
+            "WITH fresh AS (
+                SELECT 1 WHERE NOT EXISTS (SELECT 1 FROM pinpet_addresses WHERE public_key = {public_key})
+            ), counter AS (
+                INSERT INTO pinpet_pools (pool, next_id) SELECT {name}, 1 FROM fresh
+                ON CONFLICT (pool) DO UPDATE SET next_id = pinpet_pools.next_id + 1
+                RETURNING next_id - 1 AS id
+            ), history AS (
+                INSERT INTO pinpet_addresses (public_key, address, pool, id, created_at)
+                SELECT {public_key}, {address}, {name}, id, {created_at} FROM counter
+            )
+            INSERT INTO pinpet_queue (pool, id, public_key, record)
+            SELECT {name}, id, {public_key}, jsonb_set({record}::jsonb, '{{id}}', to_jsonb(id)) FROM counter
+            RETURNING id",
+            public_key = literal(&public_key)?,
+            address = literal(&address_info.address.address)?,
+            name = self.name,
+            created_at = literal(&address_info.created_at.to_rfc3339())?,
+            record = literal(&record)?,
+        ));
+
+        let duplicate = || StoreError::Duplicate {
+            public_key: public_key.clone(),
+        };
+        match result {
+            Ok(rows) => first_value(rows)
+                .ok_or_else(|| duplicate().into())
+                .and_then(|id| id.parse().context("Invalid address ID")),
+            // Another replica stored the same key concurrently
+            Err(e) if e.downcast_ref::<ServerError>().is_some_and(|e| e.code == UNIQUE_VIOLATION) => {
+                Err(duplicate().into())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn get_next_address(&self) -> Result<Option<PetAddressInfo>> {
+        Ok(self.pop(1)?.pop())
+    }
+
+    fn get_next_addresses(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        self.pop(count)
+    }
+
+    fn count_addresses(&self) -> Result<usize> {
+        let rows = self
+            .pool
+            .query(&format!("SELECT count(*) FROM pinpet_queue WHERE pool = {}", self.name))?;
+        first_value(rows)
+            .ok_or_else(|| anyhow!("Missing count"))?
+            .parse()
+            .context("Invalid count")
+    }
+
+    fn clear_all_addresses(&self) -> Result<()> {
+        self.pool
+            .query(&format!("DELETE FROM pinpet_queue WHERE pool = {}", self.name))?;
+        Ok(())
+    }
+
+    fn created_range(&self) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+        let rows = self.pool.query(&format!(
+            "SELECT
+                (SELECT record->>'created_at' FROM pinpet_queue WHERE pool = {name} ORDER BY id LIMIT 1),
+                (SELECT record->>'created_at' FROM pinpet_queue WHERE pool = {name} ORDER BY id DESC LIMIT 1)",
+            name = self.name,
+        ))?;
+        let parse = |value: &Option<String>| -> Result<Option<DateTime<Utc>>> {
+            value
+                .as_deref()
+                .map(|v| Ok(DateTime::parse_from_rfc3339(v)?.with_timezone(&Utc)))
+                .transpose()
+        };
+        match rows.first().map(|row| row.as_slice()) {
+            Some([oldest, newest]) => Ok(parse(oldest)?.zip(parse(newest)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// The queue table is the durable state, so there is nothing to rebuild
+    async fn restore(&self) -> Result<usize> {
+        let queued = self.count_addresses()?;
+        tracing::info!("Found {} queued addresses in PostgreSQL", queued);
+        Ok(queued)
+    }
+
+    async fn open_pool(&self, name: &str) -> Result<Arc<dyn Storage>> {
+        Ok(Arc::new(Self {
+            pool: Arc::clone(&self.pool),
+            name: literal(name)?,
+            key_ring: self.key_ring.clone(),
+        }))
+    }
+
+    /// Note who received each address in the history table
+    fn record_dispensed(&self, records: Vec<DispensedRecord>) {
+        let rows = records
+            .iter()
+            .map(|record| {
+                let optional = |value: &Option<String>| value.as_deref().map_or(Ok("NULL".to_string()), literal);
+                Ok(format!(
+                    "({}, {}::timestamptz, {}, {})",
+                    literal(&record.public_key)?,
+                    literal(&record.dispensed_at.to_rfc3339())?,
+                    optional(&record.requester)?,
+                    optional(&record.user_agent)?
+                ))
+            })
+            .collect::<Result<Vec<_>>>();
+        let result = rows.and_then(|rows| {
+            if rows.is_empty() {
+                return Ok(());
+            }
+            self.pool.query(&format!(
+                "UPDATE pinpet_addresses SET dispensed_at = v.dispensed_at, requester = v.requester, user_agent = v.user_agent
+                FROM (VALUES {}) AS v (public_key, dispensed_at, requester, user_agent)
+                WHERE pinpet_addresses.public_key = v.public_key",
+                rows.join(", ")
+            ))?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            tracing::warn!("Failed to record {} dispensed addresses in PostgreSQL: {}", records.len(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::test_support::test_address;
+
+    #[test]
+    fn test_connect_failure_is_reported() {
+        // Nothing listens on port 1
+        let result = PostgresStorage::connect("postgres://127.0.0.1:1", 1, Duration::from_millis(200));
+        assert!(result.is_err());
+    }
+
+    /// Runs against a real server, e.g.
+    /// `PINPET_TEST_POSTGRES_URL=postgres://postgres@127.0.0.1/pinpet_test cargo test --features postgres -- --ignored postgres`.
+    /// The database is wiped.
+    #[tokio::test]
+    #[ignore]
+    async fn test_postgres_queue_roundtrip() {
+        let url = std::env::var("PINPET_TEST_POSTGRES_URL").expect("PINPET_TEST_POSTGRES_URL");
+        let storage = PostgresStorage::connect(&url, 2, Duration::from_secs(2)).unwrap();
+        storage
+            .pool
+            .query("TRUNCATE pinpet_queue, pinpet_addresses, pinpet_pools")
+            .unwrap();
+        // A second connect finds the schema current
+        assert_eq!(migrations::run(&storage.pool).unwrap(), 0);
+
+        let first = test_address("first");
+        let second = test_address("second");
+        assert_eq!(storage.store_address(first.clone()).unwrap(), 0);
+        assert_eq!(storage.store_address(second.clone()).unwrap(), 1);
+        assert!(matches!(
+            storage.store_address(first.clone()).unwrap_err().downcast::<StoreError>().unwrap(),
+            StoreError::Duplicate { .. }
+        ));
+        assert_eq!(storage.count_addresses().unwrap(), 2);
+        assert!(storage.created_range().unwrap().is_some());
+
+        // Pools queue separately but share the duplicate guard
+        let cats = storage.open_pool("cat's").await.unwrap();
+        assert!(cats.store_address(second.clone()).is_err());
+        assert_eq!(cats.store_address(test_address("cat")).unwrap(), 0);
+
+        let popped = storage.get_next_address().unwrap().unwrap();
+        assert_eq!((popped.id, popped.address.public_key.as_str()), (0, first.public_key.as_str()));
+        assert_eq!(popped.address.private_key, first.private_key);
+        storage.record_dispensed(vec![DispensedRecord {
+            id: 0,
+            pool: DEFAULT_POOL.to_string(),
+            public_key: first.public_key.clone(),
+            address: first.address.clone(),
+            created_at: popped.created_at,
+            dispensed_at: Utc::now(),
+            requester: Some("10.0.0.1".to_string()),
+            user_agent: None,
+            lease_expires_at: None,
+            acknowledged_at: None,
+        }]);
+        let history = storage
+            .pool
+            .query(&format!(
+                "SELECT requester, dispensed_at IS NOT NULL FROM pinpet_addresses WHERE public_key = {}",
+                literal(&first.public_key).unwrap()
+            ))
+            .unwrap();
+        assert_eq!(history, vec![vec![Some("10.0.0.1".to_string()), Some("t".to_string())]]);
+
+        assert_eq!(storage.get_next_addresses(5).unwrap().len(), 1);
+        assert!(storage.get_next_address().unwrap().is_none());
+        assert_eq!(cats.count_addresses().unwrap(), 1);
+        cats.clear_all_addresses().unwrap();
+        assert_eq!(cats.restore().await.unwrap(), 0);
+    }
+}
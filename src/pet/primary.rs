@@ -0,0 +1,249 @@
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use super::address::{PetAddress, PetAddressInfo};
+use super::backend::{Storage, StoreError};
+use super::generator::DEFAULT_POOL;
+use super::http::{self, ClientTls, Url};
+use crate::models::{ReplicaAddress, ReplicaPoolStatus, ReplicaPopRequest, ReplicaStoreRequest, ReplicaStoreResponse};
+
+/// `ApiResponse` as read back by the replica
+#[derive(Deserialize)]
+struct Envelope<T> {
+    data: Option<T>,
+}
+
+/// Connection settings shared by every pool of one replica
+struct PrimaryClient {
+    url: Url,
+    api_key: String,
+    timeout: Duration,
+    tls: Option<ClientTls>,
+}
+
+/// `Storage` on a replica: each pool lives on the primary server and every
+/// operation is an HTTP call to its `/replicas/pools/{pool}/…` routes. The
+/// replica keeps no queue, so any number of replicas behind a load balancer
+/// hand out from, and grind into, the same pools.
+pub struct PrimaryStorage {
+    client: Arc<PrimaryClient>,
+    pool: String,
+    // max_queue_size of the primary, read with the first status
+    capacity: Arc<OnceLock<Option<usize>>>,
+}
+
+impl PrimaryStorage {
+    /// Check that the primary at `url` answers with `api_key` before serving.
+    /// `tls` carries the client certificate for a primary requiring one.
+    pub fn connect(url: &str, api_key: &str, timeout: Duration, tls: Option<ClientTls>) -> Result<Self> {
+        let url = Url::parse(url).with_context(|| format!("Invalid primary URL '{}'", url))?;
+        let storage = Self {
+            client: Arc::new(PrimaryClient {
+                url,
+                api_key: api_key.to_string(),
+                timeout,
+                tls,
+            }),
+            pool: DEFAULT_POOL.to_string(),
+            capacity: Arc::new(OnceLock::new()),
+        };
+        let status = storage.status().context("The primary did not answer")?;
+        tracing::info!(
+            "Connected to primary {}; its default pool holds {} addresses",
+            storage.client.url.host_header(),
+            status.count
+        );
+        Ok(storage)
+    }
+
+    fn status(&self) -> Result<ReplicaPoolStatus> {
+        let status: ReplicaPoolStatus = self.call("GET", "status", &[])?;
+        let _ = self.capacity.set(status.capacity);
+        Ok(status)
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, action: &str, body: &[u8]) -> Result<T> {
+        let response = self.send(method, action, body)?;
+        if !response.is_success() {
+            bail!("Primary refused {} for pool '{}': {}", action, self.pool, response.describe());
+        }
+        let envelope: Envelope<T> =
+            serde_json::from_slice(&response.body).context("Malformed response from the primary")?;
+        envelope.data.ok_or_else(|| anyhow!("The primary sent no data for {}", action))
+    }
+
+    fn send(&self, method: &str, action: &str, body: &[u8]) -> Result<http::Response> {
+        let client = &self.client;
+        let path = format!("{}/replicas/pools/{}/{}", client.url.path.trim_end_matches('/'), self.pool, action);
+        let headers = [
+            ("Authorization", format!("Bearer {}", client.api_key)),
+            ("Content-Type", "application/json".to_string()),
+            ("Accept", "application/json".to_string()),
+        ];
+        http::send_with(client.tls.as_ref(), &client.url, method, &path, &headers, body, client.timeout)
+    }
+}
+
+#[async_trait]
+impl Storage for PrimaryStorage {
+    fn backend_name(&self) -> &'static str {
+        "primary"
+    }
+
+    fn store_address(&self, address: PetAddress) -> Result<u64> {
+        let public_key = address.public_key.clone();
+        let body = serde_json::to_vec(&ReplicaStoreRequest {
+            public_key: address.public_key,
+            private_key: address.private_key,
+            address: address.address,
+        })?;
+        let response = self.send("POST", "addresses", &body)?;
+        match response.status {
+            409 => return Err(StoreError::Duplicate { public_key }.into()),
+            503 => {
+                let capacity = self.capacity().unwrap_or_default();
+                return Err(StoreError::QueueFull { capacity }.into());
+            }
+            _ if !response.is_success() => bail!("Primary refused an address for pool '{}': {}", self.pool, response.describe()),
+            _ => {}
+        }
+        let envelope: Envelope<ReplicaStoreResponse> =
+            serde_json::from_slice(&response.body).context("Malformed response from the primary")?;
+        Ok(envelope.data.ok_or_else(|| anyhow!("The primary sent no ID for the address"))?.id)
+    }
+
+    fn get_next_address(&self) -> Result<Option<PetAddressInfo>> {
+        Ok(self.get_next_addresses(1)?.pop())
+    }
+
+    fn get_next_addresses(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        let body = serde_json::to_vec(&ReplicaPopRequest { count })?;
+        let popped: Vec<ReplicaAddress> = self.call("POST", "pop", &body)?;
+        popped
+            .into_iter()
+            .map(|popped| {
+                let created_at = chrono::DateTime::parse_from_rfc3339(&popped.created_at)
+                    .with_context(|| format!("Bad created_at from the primary: {}", popped.created_at))?;
+                Ok(PetAddressInfo {
+                    id: popped.id,
+                    address: PetAddress {
+                        public_key: popped.public_key,
+                        private_key: popped.private_key,
+                        address: popped.address,
+                    },
+                    created_at: created_at.with_timezone(&chrono::Utc),
+                })
+            })
+            .collect()
+    }
+
+    fn count_addresses(&self) -> Result<usize> {
+        Ok(self.status()?.count)
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        match self.capacity.get() {
+            Some(capacity) => *capacity,
+            None => self.status().ok().and_then(|status| status.capacity),
+        }
+    }
+
+    fn clear_all_addresses(&self) -> Result<()> {
+        bail!("Clear the pool on the primary; replicas cannot")
+    }
+
+    /// The queue lives on the primary, so there is nothing to reload
+    async fn restore(&self) -> Result<usize> {
+        self.count_addresses()
+    }
+
+    async fn open_pool(&self, name: &str) -> Result<Arc<dyn Storage>> {
+        Ok(Arc::new(Self {
+            client: Arc::clone(&self.client),
+            pool: name.to_string(),
+            capacity: Arc::new(OnceLock::new()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Answer each connection with the next canned (status, body) and
+    /// return the request lines seen
+    fn fake_primary(replies: Vec<(u16, &'static str)>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (status, body) in replies {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut authorized = false;
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    authorized |= line.trim_end() == "Authorization: Bearer r3plica";
+                    if let Some(length) = line.strip_prefix("Content-Length: ") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                }
+                std::io::Read::read_exact(&mut reader, &mut vec![0; content_length]).unwrap();
+                assert!(authorized);
+                requests.push(request_line.trim_end().to_string());
+                let response = format!("HTTP/1.1 {} X\r\nContent-Length: {}\r\n\r\n{}", status, body.len(), body);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_pools_are_served_by_the_primary() {
+        let (url, primary) = fake_primary(vec![
+            (200, r#"{"code":200,"data":{"count":3,"capacity":10}}"#),
+            (200, r#"{"code":200,"data":{"count":2,"capacity":10}}"#),
+            (409, r#"{"status":409}"#),
+            (200, r#"{"code":200,"data":[{"id":7,"public_key":"kPet","private_key":"s","address":"kPet","created_at":"2026-01-02T03:04:05+00:00"}]}"#),
+        ]);
+        let storage = PrimaryStorage::connect(&url, "r3plica", Duration::from_secs(2), None).unwrap();
+        let pool = storage.open_pool("tenant:staging").await.unwrap();
+        assert_eq!(pool.count_addresses().unwrap(), 2);
+        assert_eq!(pool.capacity(), Some(10));
+
+        let address = PetAddress {
+            public_key: "kPet".to_string(),
+            private_key: "s".to_string().into(),
+            address: "kPet".to_string(),
+        };
+        let error = pool.store_address(address).unwrap_err();
+        assert!(matches!(error.downcast_ref::<StoreError>(), Some(StoreError::Duplicate { .. })));
+
+        let popped = pool.get_next_address().unwrap().unwrap();
+        assert_eq!((popped.id, popped.address.public_key.as_str()), (7, "kPet"));
+
+        assert_eq!(
+            primary.join().unwrap(),
+            vec![
+                "GET /replicas/pools/default/status HTTP/1.1",
+                "GET /replicas/pools/tenant:staging/status HTTP/1.1",
+                "POST /replicas/pools/tenant:staging/addresses HTTP/1.1",
+                "POST /replicas/pools/tenant:staging/pop HTTP/1.1",
+            ]
+        );
+    }
+}
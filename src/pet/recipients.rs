@@ -0,0 +1,264 @@
+//! Client public keys that private keys are encrypted to before they leave
+//! the server, so no plaintext secret crosses the network or a proxy. Keys
+//! are sent per request in `X-Recipient-Key` or registered once per tenant.
+//!
+//! - RSA (2048 bits or more): RSA-OAEP with SHA-256
+//! - X25519 (ECIES): an ephemeral X25519 key agreement, HKDF-SHA256 over the
+//!   shared secret (salt: ephemeral public key || recipient public key, info:
+//!   `pinpet-ecies-v1`) and AES-256-GCM. The sealed value is
+//!   `ephemeral public key (32) || nonce (12) || ciphertext || tag (16)`.
+//!
+//! Sealed values are base64 encoded.
+
+use anyhow::{bail, Context, Result};
+use dashmap::DashMap;
+use openssl::base64;
+use openssl::derive::Deriver;
+use openssl::encrypt::Encrypter;
+use openssl::hash::MessageDigest;
+use openssl::md::Md;
+use openssl::pkey::{Id, PKey, Public};
+use openssl::pkey_ctx::PkeyCtx;
+use openssl::rsa::Padding;
+use openssl::symm::{encrypt_aead, Cipher};
+use rand::RngCore;
+use sled::Db;
+use std::sync::Arc;
+
+/// Smallest RSA modulus accepted
+const MIN_RSA_BITS: u32 = 2048;
+const X25519_KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+/// HKDF info string; bump with any change to the construction
+const ECIES_INFO: &[u8] = b"pinpet-ecies-v1";
+/// Sled key prefix of registered keys: `recipient:{tenant}`
+const RECIPIENT_PREFIX: &str = "recipient:";
+
+/// Public key a client wants its private keys encrypted to
+pub enum RecipientKey {
+    Rsa(PKey<Public>),
+    X25519(PKey<Public>),
+}
+
+impl RecipientKey {
+    /// Parse base64 of either a DER SubjectPublicKeyInfo (`openssl pkey
+    /// -pubout -outform DER | base64 -w0`) or a raw 32-byte X25519 key
+    pub fn parse(encoded: &str) -> Result<Self> {
+        let bytes = base64::decode_block(encoded.trim()).context("Recipient key is not valid base64")?;
+        let key = match bytes.len() {
+            X25519_KEY_LEN => PKey::public_key_from_raw_bytes(&bytes, Id::X25519)?,
+            _ => PKey::public_key_from_der(&bytes).context("Recipient key is not a DER public key")?,
+        };
+
+        match key.id() {
+            Id::X25519 => Ok(Self::X25519(key)),
+            Id::RSA => {
+                if key.bits() < MIN_RSA_BITS {
+                    bail!("RSA recipient keys must have at least {} bits", MIN_RSA_BITS);
+                }
+                Ok(Self::Rsa(key))
+            }
+            _ => bail!("Recipient key must be an X25519 or RSA key"),
+        }
+    }
+
+    /// Name of the sealing scheme, for clients to pick the matching decryption
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            Self::Rsa(_) => "rsa-oaep-sha256",
+            Self::X25519(_) => "x25519-hkdf-sha256-aes-256-gcm",
+        }
+    }
+
+    /// The key in the form `parse` accepts: raw bytes for X25519, DER for RSA
+    pub fn to_base64(&self) -> Result<String> {
+        Ok(base64::encode_block(&match self {
+            Self::Rsa(key) => key.public_key_to_der()?,
+            Self::X25519(key) => key.raw_public_key()?,
+        }))
+    }
+
+    /// Encrypt `plaintext` to this key, base64 encoded
+    pub fn seal(&self, plaintext: &str) -> Result<String> {
+        let sealed = match self {
+            Self::Rsa(key) => seal_rsa(key, plaintext.as_bytes()),
+            Self::X25519(key) => seal_x25519(key, plaintext.as_bytes()),
+        }
+        .context("Failed to encrypt private key for the recipient")?;
+        Ok(base64::encode_block(&sealed))
+    }
+}
+
+fn seal_rsa(key: &PKey<Public>, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut encrypter = Encrypter::new(key)?;
+    encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+    encrypter.set_rsa_oaep_md(MessageDigest::sha256())?;
+    encrypter.set_rsa_mgf1_md(MessageDigest::sha256())?;
+
+    let mut ciphertext = vec![0; encrypter.encrypt_len(plaintext)?];
+    let len = encrypter.encrypt(plaintext, &mut ciphertext)?;
+    ciphertext.truncate(len);
+    Ok(ciphertext)
+}
+
+fn seal_x25519(recipient: &PKey<Public>, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let ephemeral = PKey::generate_x25519()?;
+    let ephemeral_public = ephemeral.raw_public_key()?;
+    let mut deriver = Deriver::new(&ephemeral)?;
+    deriver.set_peer(recipient)?;
+    let shared = deriver.derive_to_vec()?;
+
+    let mut salt = ephemeral_public.clone();
+    salt.extend(recipient.raw_public_key()?);
+    let key = hkdf_sha256(&shared, &salt, ECIES_INFO)?;
+
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let mut tag = [0u8; TAG_LEN];
+    let ciphertext = encrypt_aead(Cipher::aes_256_gcm(), &key, Some(&nonce), &[], plaintext, &mut tag)?;
+
+    let mut sealed = ephemeral_public;
+    sealed.extend_from_slice(&nonce);
+    sealed.extend(ciphertext);
+    sealed.extend_from_slice(&tag);
+    Ok(sealed)
+}
+
+fn hkdf_sha256(ikm: &[u8], salt: &[u8], info: &[u8]) -> Result<[u8; 32]> {
+    let mut ctx = PkeyCtx::new_id(Id::HKDF)?;
+    ctx.derive_init()?;
+    ctx.set_hkdf_md(Md::sha256())?;
+    ctx.set_hkdf_key(ikm)?;
+    ctx.set_hkdf_salt(salt)?;
+    ctx.add_hkdf_info(info)?;
+    let mut okm = [0u8; 32];
+    ctx.derive(Some(&mut okm))?;
+    Ok(okm)
+}
+
+/// Recipient key registered per tenant. Once a tenant has one, none of its
+/// private keys leave the server unencrypted. Kept in sled next to the
+/// pools; in memory with other backends.
+#[derive(Clone)]
+pub struct RecipientRegistry {
+    db: Option<Db>,
+    memory: Arc<DashMap<String, String>>,
+}
+
+impl RecipientRegistry {
+    pub fn new(db: Option<Db>) -> Self {
+        Self {
+            db,
+            memory: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Register (or replace) the key of `tenant`
+    pub fn register(&self, tenant: &str, key: &RecipientKey) -> Result<()> {
+        let encoded = key.to_base64()?;
+        match &self.db {
+            Some(db) => {
+                db.insert(registry_key(tenant).as_bytes(), encoded.as_bytes())?;
+            }
+            None => {
+                self.memory.insert(tenant.to_string(), encoded);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, tenant: &str) -> Result<Option<RecipientKey>> {
+        let encoded = match &self.db {
+            Some(db) => db
+                .get(registry_key(tenant).as_bytes())?
+                .map(|value| String::from_utf8_lossy(&value).into_owned()),
+            None => self.memory.get(tenant).map(|entry| entry.clone()),
+        };
+        encoded.map(|encoded| RecipientKey::parse(&encoded)).transpose()
+    }
+
+    /// Unregister the key of `tenant`; false if it had none
+    pub fn remove(&self, tenant: &str) -> Result<bool> {
+        match &self.db {
+            Some(db) => Ok(db.remove(registry_key(tenant).as_bytes())?.is_some()),
+            None => Ok(self.memory.remove(tenant).is_some()),
+        }
+    }
+}
+
+fn registry_key(tenant: &str) -> String {
+    format!("{}{}", RECIPIENT_PREFIX, tenant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::encrypt::Decrypter;
+    use openssl::rsa::Rsa;
+    use openssl::symm::decrypt_aead;
+
+    #[test]
+    fn test_x25519_sealing_roundtrip() {
+        let private = PKey::generate_x25519().unwrap();
+        let raw = base64::encode_block(&private.raw_public_key().unwrap());
+        let recipient = RecipientKey::parse(&raw).unwrap();
+        assert_eq!(recipient.algorithm(), "x25519-hkdf-sha256-aes-256-gcm");
+        // The DER form is the same key
+        let der = base64::encode_block(&private.public_key_to_der().unwrap());
+        assert_eq!(RecipientKey::parse(&der).unwrap().to_base64().unwrap(), raw);
+
+        let sealed = base64::decode_block(&recipient.seal("secret").unwrap()).unwrap();
+        let (ephemeral, rest) = sealed.split_at(X25519_KEY_LEN);
+        let (nonce, rest) = rest.split_at(NONCE_LEN);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+        let ephemeral = PKey::public_key_from_raw_bytes(ephemeral, Id::X25519).unwrap();
+        let mut deriver = Deriver::new(&private).unwrap();
+        deriver.set_peer(&ephemeral).unwrap();
+        let shared = deriver.derive_to_vec().unwrap();
+        let mut salt = ephemeral.raw_public_key().unwrap();
+        salt.extend(private.raw_public_key().unwrap());
+        let key = hkdf_sha256(&shared, &salt, ECIES_INFO).unwrap();
+
+        let plaintext = decrypt_aead(Cipher::aes_256_gcm(), &key, Some(nonce), &[], ciphertext, tag).unwrap();
+        assert_eq!(plaintext, b"secret");
+    }
+
+    #[test]
+    fn test_rsa_sealing_roundtrip() {
+        let private = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let recipient = RecipientKey::parse(&base64::encode_block(&private.public_key_to_der().unwrap())).unwrap();
+
+        let sealed = base64::decode_block(&recipient.seal("secret").unwrap()).unwrap();
+        let mut decrypter = Decrypter::new(&private).unwrap();
+        decrypter.set_rsa_padding(Padding::PKCS1_OAEP).unwrap();
+        decrypter.set_rsa_oaep_md(MessageDigest::sha256()).unwrap();
+        decrypter.set_rsa_mgf1_md(MessageDigest::sha256()).unwrap();
+        let mut plaintext = vec![0; decrypter.decrypt_len(&sealed).unwrap()];
+        let len = decrypter.decrypt(&sealed, &mut plaintext).unwrap();
+        assert_eq!(&plaintext[..len], b"secret");
+
+        let weak = PKey::from_rsa(Rsa::generate(1024).unwrap()).unwrap();
+        assert!(RecipientKey::parse(&base64::encode_block(&weak.public_key_to_der().unwrap())).is_err());
+        let ed25519 = PKey::generate_ed25519().unwrap();
+        assert!(RecipientKey::parse(&base64::encode_block(&ed25519.public_key_to_der().unwrap())).is_err());
+        assert!(RecipientKey::parse("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_registry_persists_per_tenant() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let key = PKey::generate_x25519().unwrap();
+        let recipient = RecipientKey::parse(&base64::encode_block(&key.raw_public_key().unwrap())).unwrap();
+
+        let registry = RecipientRegistry::new(Some(db.clone()));
+        registry.register("staging", &recipient).unwrap();
+        let reopened = RecipientRegistry::new(Some(db));
+        assert!(reopened.get("staging").unwrap().is_some());
+        assert!(reopened.get("dev").unwrap().is_none());
+        assert!(reopened.remove("staging").unwrap());
+        assert!(!reopened.remove("staging").unwrap());
+        assert!(registry.get("staging").unwrap().is_none());
+    }
+}
@@ -0,0 +1,348 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition, TableError};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+use super::address::{PetAddress, PetAddressInfo};
+use super::backend::{Storage, StoreError};
+use super::codec;
+use super::encryption::{self, KeyRing};
+
+/// Per-pool ID counters, keyed like the sled counters (`counter`, `pool:{name}:counter`)
+const COUNTERS: TableDefinition<&str, u64> = TableDefinition::new("counters");
+/// Every public key ever enqueued, shared by all pools
+const SEEN: TableDefinition<&str, ()> = TableDefinition::new("seen");
+
+/// redb table names of one pool, mirroring the sled key spaces
+struct RedbTables {
+    addresses: String,
+    counter_key: String,
+}
+
+impl RedbTables {
+    fn new(pool: Option<&str>) -> Self {
+        match pool {
+            None => Self {
+                addresses: "addresses".to_string(),
+                counter_key: "counter".to_string(),
+            },
+            Some(name) => Self {
+                addresses: format!("pool:{}:addresses", name),
+                counter_key: format!("pool:{}:counter", name),
+            },
+        }
+    }
+
+    fn addresses(&self) -> TableDefinition<'_, u64, &'static [u8]> {
+        TableDefinition::new(&self.addresses)
+    }
+}
+
+/// `Storage` on an embedded redb file: one table of records per pool keyed
+/// by ID, so the first entry is the oldest address. Every store and pop is
+/// its own durable write transaction; redb serializes writers, so concurrent
+/// pops never hand out the same address and there is no separate in-memory queue.
+pub struct RedbStorage {
+    db: Arc<Database>,
+    tables: RedbTables,
+    // Signalled whenever addresses are consumed. Shared by all pools.
+    demand: Arc<Notify>,
+    capacity: Option<usize>,
+    key_ring: Option<Arc<KeyRing>>,
+}
+
+impl RedbStorage {
+    /// Open (or create) the database file at `path`
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let db = Database::create(path).with_context(|| format!("Failed to open redb database {}", path.display()))?;
+        Self::from_db(db)
+    }
+
+    /// Open a throwaway in-memory database (used by tests)
+    #[cfg(test)]
+    pub(crate) fn temporary() -> Result<Self> {
+        Self::from_db(Database::builder().create_with_backend(redb::backends::InMemoryBackend::new())?)
+    }
+
+    fn from_db(db: Database) -> Result<Self> {
+        // Create the shared tables up front so read transactions never miss them
+        let txn = db.begin_write()?;
+        txn.open_table(COUNTERS)?;
+        txn.open_table(SEEN)?;
+        txn.commit()?;
+
+        Ok(Self {
+            db: Arc::new(db),
+            tables: RedbTables::new(None),
+            demand: Arc::new(Notify::new()),
+            capacity: None,
+            key_ring: None,
+        })
+    }
+
+    /// Encrypt private keys before they are written
+    pub fn with_key_ring(mut self, key_ring: Arc<KeyRing>) -> Self {
+        self.key_ring = Some(key_ring);
+        self
+    }
+
+    /// Refuse stores once `capacity` addresses are queued. Pools opened
+    /// afterwards inherit the limit.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Pool `name` in the same database
+    pub fn named_pool(&self, name: &str) -> Self {
+        Self {
+            db: Arc::clone(&self.db),
+            tables: RedbTables::new(Some(name)),
+            demand: Arc::clone(&self.demand),
+            capacity: self.capacity,
+            key_ring: self.key_ring.clone(),
+        }
+    }
+
+    /// Whether no address was ever stored in this database
+    pub fn is_empty(&self) -> Result<bool> {
+        let txn = self.db.begin_read()?;
+        Ok(txn.open_table(SEEN)?.is_empty()?)
+    }
+
+    /// Write `records` with their original IDs and creation times and raise
+    /// the pool's counter past them (used by the sled migration). IDs that
+    /// already exist are overwritten.
+    pub fn import(&self, records: &[PetAddressInfo]) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        {
+            let mut addresses = txn.open_table(self.tables.addresses())?;
+            let mut seen = txn.open_table(SEEN)?;
+            for address_info in records {
+                addresses.insert(address_info.id, self.encode_record(address_info)?.as_slice())?;
+                seen.insert(address_info.address.public_key.as_str(), ())?;
+            }
+
+            let mut counters = txn.open_table(COUNTERS)?;
+            let next = records.iter().map(|address_info| address_info.id + 1).max().unwrap_or(0);
+            let current = counters.get(self.tables.counter_key.as_str())?.map(|value| value.value()).unwrap_or(0);
+            counters.insert(self.tables.counter_key.as_str(), current.max(next))?;
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Record public keys as seen without queueing them (dispensed keys, when migrating)
+    pub fn mark_seen<'a>(&self, public_keys: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        {
+            let mut seen = txn.open_table(SEEN)?;
+            for public_key in public_keys {
+                seen.insert(public_key, ())?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn encode_record(&self, address_info: &PetAddressInfo) -> Result<Vec<u8>> {
+        match &self.key_ring {
+            Some(key_ring) => codec::encode(&key_ring.seal_record(address_info)?),
+            None => codec::encode(address_info),
+        }
+    }
+
+    fn decode_record(&self, bytes: &[u8]) -> Result<PetAddressInfo> {
+        let address_info = codec::decode(bytes)?;
+        match &self.key_ring {
+            Some(key_ring) => key_ring.open_record(address_info),
+            None if encryption::is_sealed(&address_info.address.private_key) => bail!(
+                "Address {} has an encrypted private key but encryption is not configured",
+                address_info.id
+            ),
+            None => Ok(address_info),
+        }
+    }
+
+    fn pop(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        let txn = self.db.begin_write()?;
+        let mut values = Vec::new();
+        {
+            let mut addresses = txn.open_table(self.tables.addresses())?;
+            while values.len() < count {
+                match addresses.pop_first()? {
+                    Some((_, value)) => values.push(value.value().to_vec()),
+                    None => break,
+                }
+            }
+        }
+        if values.is_empty() {
+            txn.abort()?;
+            return Ok(Vec::new());
+        }
+        txn.commit()?;
+        self.demand.notify_one();
+
+        values.iter().map(|value| self.decode_record(value)).collect()
+    }
+}
+
+#[async_trait]
+impl Storage for RedbStorage {
+    fn backend_name(&self) -> &'static str {
+        "redb"
+    }
+
+    fn store_address(&self, address: PetAddress) -> Result<u64> {
+        let txn = self.db.begin_write()?;
+        let id = {
+            let mut addresses = txn.open_table(self.tables.addresses())?;
+            if let Some(capacity) = self.capacity {
+                if addresses.len()? as usize >= capacity {
+                    return Err(StoreError::QueueFull { capacity }.into());
+                }
+            }
+
+            let mut seen = txn.open_table(SEEN)?;
+            if seen.insert(address.public_key.as_str(), ())?.is_some() {
+                return Err(StoreError::Duplicate {
+                    public_key: address.public_key,
+                }
+                .into());
+            }
+
+            let mut counters = txn.open_table(COUNTERS)?;
+            let id = counters.get(self.tables.counter_key.as_str())?.map(|value| value.value()).unwrap_or(0);
+            counters.insert(self.tables.counter_key.as_str(), id + 1)?;
+
+            let address_info = PetAddressInfo {
+                id,
+                address,
+                created_at: chrono::Utc::now(),
+            };
+            addresses.insert(id, self.encode_record(&address_info)?.as_slice())?;
+            id
+        };
+        // Dropping an uncommitted transaction (the early returns above) rolls it back
+        txn.commit()?;
+
+        Ok(id)
+    }
+
+    fn get_next_address(&self) -> Result<Option<PetAddressInfo>> {
+        Ok(self.pop(1)?.pop())
+    }
+
+    fn get_next_addresses(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        self.pop(count)
+    }
+
+    fn count_addresses(&self) -> Result<usize> {
+        let txn = self.db.begin_read()?;
+        match txn.open_table(self.tables.addresses()) {
+            Ok(addresses) => Ok(addresses.len()? as usize),
+            Err(TableError::TableDoesNotExist(_)) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    fn created_range(&self) -> Result<Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)>> {
+        let txn = self.db.begin_read()?;
+        let addresses = match txn.open_table(self.tables.addresses()) {
+            Ok(addresses) => addresses,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let (Some((_, oldest)), Some((_, newest))) = (addresses.first()?, addresses.last()?) else {
+            return Ok(None);
+        };
+        // Only the timestamps are needed; sealed private keys stay sealed
+        let oldest = codec::decode(oldest.value())?.created_at;
+        let newest = codec::decode(newest.value())?.created_at;
+        Ok(Some((oldest, newest)))
+    }
+
+    fn clear_all_addresses(&self) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        txn.delete_table(self.tables.addresses())?;
+        txn.commit()?;
+        self.demand.notify_one();
+        Ok(())
+    }
+
+    /// The table is the queue, so there is nothing to rebuild
+    async fn restore(&self) -> Result<usize> {
+        let count = self.count_addresses()?;
+        tracing::info!("{} addresses queued in redb", count);
+        Ok(count)
+    }
+
+    async fn open_pool(&self, name: &str) -> Result<Arc<dyn Storage>> {
+        Ok(Arc::new(self.named_pool(name)))
+    }
+
+    async fn wait_for_demand(&self) {
+        self.demand.notified().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::test_support::test_address;
+
+    #[tokio::test]
+    async fn test_redb_queue_is_fifo_per_pool() {
+        let storage = RedbStorage::temporary().unwrap().with_capacity(3);
+        assert_eq!(storage.count_addresses().unwrap(), 0);
+        assert!(storage.get_next_address().unwrap().is_none());
+
+        for suffix in ["aPet", "bPet", "cPet"] {
+            storage.store_address(test_address(suffix)).unwrap();
+        }
+        let error = storage.store_address(test_address("dPet")).unwrap_err();
+        assert_eq!(error.downcast_ref::<StoreError>(), Some(&StoreError::QueueFull { capacity: 3 }));
+
+        assert_eq!(storage.get_next_address().unwrap().unwrap().address.address, "TestAddressaPet");
+        let popped = storage.get_next_addresses(5).unwrap();
+        assert_eq!(popped.iter().map(|a| a.id).collect::<Vec<_>>(), [1, 2]);
+
+        // Keys are remembered across pools even after they were popped
+        let pool = storage.open_pool("Cat").await.unwrap();
+        let error = pool.store_address(test_address("aPet")).unwrap_err();
+        assert!(matches!(error.downcast_ref::<StoreError>(), Some(StoreError::Duplicate { .. })));
+        assert_eq!(pool.store_address(test_address("aCat")).unwrap(), 0);
+        assert_eq!(pool.count_addresses().unwrap(), 1);
+        assert_eq!(storage.count_addresses().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_import_keeps_ids_and_raises_counter() {
+        let storage = RedbStorage::temporary().unwrap();
+        assert!(storage.is_empty().unwrap());
+        let created_at = chrono::Utc::now() - chrono::Duration::days(3);
+        let records: Vec<PetAddressInfo> = [(4, "aPet"), (9, "bPet")]
+            .into_iter()
+            .map(|(id, suffix)| PetAddressInfo {
+                id,
+                address: test_address(suffix),
+                created_at,
+            })
+            .collect();
+        storage.import(&records).unwrap();
+        storage.mark_seen(["TestAddresszPet"]).unwrap();
+
+        assert!(storage.store_address(test_address("zPet")).is_err());
+        assert_eq!(storage.store_address(test_address("cPet")).unwrap(), 10);
+
+        let first = storage.get_next_address().unwrap().unwrap();
+        assert_eq!((first.id, first.created_at), (4, created_at));
+    }
+}
@@ -0,0 +1,570 @@
+//! Redis storage backend (`--features redis`): the queue is a Redis list of
+//! IDs and the records live in a hash, so several server replicas can share
+//! one pool. Speaks RESP directly over TCP, so the feature adds no dependencies.
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::address::{PetAddress, PetAddressInfo};
+use super::backend::{Storage, StoreError};
+use super::encryption::{self, KeyRing};
+
+/// Pops up to ARGV[1] records atomically; IDs without a record are skipped
+const POP_SCRIPT: &str = r#"
+local popped = {}
+while #popped < tonumber(ARGV[1]) do
+    local id = redis.call('LPOP', KEYS[1])
+    if not id then break end
+    local record = redis.call('HGET', KEYS[2], id)
+    if record then
+        redis.call('HDEL', KEYS[2], id)
+        table.insert(popped, record)
+    end
+end
+return popped
+"#;
+
+/// Parsed RESP reply
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Nil,
+    Status(String),
+    Error(String),
+    Int(i64),
+    Bulk(Vec<u8>),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn into_int(self) -> Result<i64> {
+        match self {
+            Value::Int(n) => Ok(n),
+            other => bail!("Expected an integer reply from Redis, got {:?}", other),
+        }
+    }
+
+    fn into_bulks(self) -> Result<Vec<Vec<u8>>> {
+        match self {
+            Value::Nil => Ok(Vec::new()),
+            Value::Array(items) => items
+                .into_iter()
+                .map(|item| match item {
+                    Value::Bulk(bytes) => Ok(bytes),
+                    other => Err(anyhow!("Expected a bulk reply from Redis, got {:?}", other)),
+                })
+                .collect(),
+            other => bail!("Expected an array reply from Redis, got {:?}", other),
+        }
+    }
+}
+
+/// Encode one command as a RESP array of bulk strings
+fn encode_command(args: &[&[u8]], out: &mut Vec<u8>) {
+    out.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+    for arg in args {
+        out.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        out.extend_from_slice(arg);
+        out.extend_from_slice(b"\r\n");
+    }
+}
+
+fn read_value<R: BufRead>(reader: &mut R) -> io::Result<Value> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Redis closed the connection"));
+    }
+    let line = line.trim_end_matches("\r\n");
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("Invalid RESP line: {:?}", line));
+    let (kind, rest) = line.split_at_checked(1).ok_or_else(invalid)?;
+
+    match kind {
+        "+" => Ok(Value::Status(rest.to_string())),
+        "-" => Ok(Value::Error(rest.to_string())),
+        ":" => rest.parse().map(Value::Int).map_err(|_| invalid()),
+        "$" => {
+            let len: i64 = rest.parse().map_err(|_| invalid())?;
+            if len < 0 {
+                return Ok(Value::Nil);
+            }
+            let mut bytes = vec![0u8; len as usize + 2];
+            reader.read_exact(&mut bytes)?;
+            bytes.truncate(len as usize);
+            Ok(Value::Bulk(bytes))
+        }
+        "*" => {
+            let len: i64 = rest.parse().map_err(|_| invalid())?;
+            if len < 0 {
+                return Ok(Value::Nil);
+            }
+            (0..len).map(|_| read_value(reader)).collect::<io::Result<_>>().map(Value::Array)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Why a pipeline failed: a stale connection fails before the server could
+/// have run anything, so the request can safely be replayed on a new one
+enum Failure {
+    Stale(io::Error),
+    Broken(io::Error),
+}
+
+fn is_disconnect(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::BrokenPipe
+    )
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    fn pipeline(&mut self, commands: &[u8], replies: usize) -> Result<Vec<Value>, Failure> {
+        self.writer
+            .write_all(commands)
+            .and_then(|_| self.writer.flush())
+            .map_err(Failure::Stale)?;
+
+        let mut values = Vec::with_capacity(replies);
+        for _ in 0..replies {
+            match read_value(&mut self.reader) {
+                Ok(value) => values.push(value),
+                // The server dropped an idle connection before answering
+                Err(e) if values.is_empty() && is_disconnect(&e) => {
+                    return Err(Failure::Stale(e))
+                }
+                Err(e) => return Err(Failure::Broken(e)),
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// Where and how to connect, parsed from `redis://[[user]:password@]host[:port][/db]`
+#[derive(Debug, Clone, PartialEq)]
+struct ConnectionInfo {
+    host: String,
+    port: u16,
+    username: Option<String>,
+    password: Option<String>,
+    db: i64,
+}
+
+impl ConnectionInfo {
+    fn parse(url: &str) -> Result<Self> {
+        let rest = url
+            .strip_prefix("redis://")
+            .ok_or_else(|| anyhow!("Redis URL must start with redis://"))?;
+        let (authority, db) = match rest.split_once('/') {
+            Some((authority, db)) if !db.is_empty() => {
+                (authority, db.parse().with_context(|| format!("Invalid Redis database '{}'", db))?)
+            }
+            Some((authority, _)) => (authority, 0),
+            None => (rest, 0),
+        };
+
+        let (credentials, host_port) = match authority.rsplit_once('@') {
+            Some((credentials, host_port)) => (Some(credentials), host_port),
+            None => (None, authority),
+        };
+        let (username, password) = match credentials.map(|c| c.split_once(':').unwrap_or(("", c))) {
+            Some((user, password)) => (
+                Some(user.to_string()).filter(|u| !u.is_empty()),
+                Some(password.to_string()).filter(|p| !p.is_empty()),
+            ),
+            None => (None, None),
+        };
+
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().with_context(|| format!("Invalid Redis port '{}'", port))?),
+            None => (host_port, 6379),
+        };
+        if host.is_empty() {
+            bail!("Redis URL has no host");
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            username,
+            password,
+            db,
+        })
+    }
+}
+
+/// Small blocking connection pool. Idle connections are reused; one the
+/// server has dropped is replaced and the request replayed once.
+struct ConnectionPool {
+    info: ConnectionInfo,
+    idle: Mutex<Vec<Connection>>,
+    max_idle: usize,
+    timeout: Duration,
+}
+
+impl ConnectionPool {
+    fn new(info: ConnectionInfo, max_idle: usize, timeout: Duration) -> Self {
+        Self {
+            info,
+            idle: Mutex::new(Vec::new()),
+            max_idle: max_idle.max(1),
+            timeout,
+        }
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        let address = (self.info.host.as_str(), self.info.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("Cannot resolve Redis host '{}'", self.info.host))?;
+        let stream = TcpStream::connect_timeout(&address, self.timeout)
+            .with_context(|| format!("Failed to connect to Redis at {}", address))?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.set_nodelay(true)?;
+
+        let mut connection = Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        };
+
+        let mut setup = Vec::new();
+        let mut replies = 0;
+        if let Some(password) = &self.info.password {
+            match &self.info.username {
+                Some(user) => encode_command(&[b"AUTH", user.as_bytes(), password.as_bytes()], &mut setup),
+                None => encode_command(&[b"AUTH", password.as_bytes()], &mut setup),
+            }
+            replies += 1;
+        }
+        if self.info.db != 0 {
+            encode_command(&[b"SELECT", self.info.db.to_string().as_bytes()], &mut setup);
+            replies += 1;
+        }
+        if replies > 0 {
+            let values = connection
+                .pipeline(&setup, replies)
+                .map_err(|(Failure::Stale(e) | Failure::Broken(e))| e)?;
+            if let Some(Value::Error(e)) = values.into_iter().find(|v| matches!(v, Value::Error(_))) {
+                bail!("Redis connection setup failed: {}", e);
+            }
+        }
+
+        tracing::debug!("Opened Redis connection to {}:{}", self.info.host, self.info.port);
+        Ok(connection)
+    }
+
+    /// Send `commands` and read one reply per command. Server error replies fail the call.
+    fn run(&self, commands: &[Vec<&[u8]>]) -> Result<Vec<Value>> {
+        let mut request = Vec::new();
+        for command in commands {
+            encode_command(command, &mut request);
+        }
+
+        let reused = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let (mut connection, mut retry) = match reused {
+            Some(connection) => (connection, true),
+            None => (self.connect()?, false),
+        };
+
+        let values = loop {
+            match connection.pipeline(&request, commands.len()) {
+                Ok(values) => break values,
+                Err(Failure::Stale(e)) if retry => {
+                    tracing::debug!("Reconnecting to Redis after stale connection: {}", e);
+                    connection = self.connect()?;
+                    retry = false;
+                }
+                Err(Failure::Stale(e) | Failure::Broken(e)) => {
+                    return Err(anyhow!(e).context("Redis request failed"));
+                }
+            }
+        };
+
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        if idle.len() < self.max_idle {
+            idle.push(connection);
+        }
+        drop(idle);
+
+        if let Some(Value::Error(e)) = values.iter().find(|v| matches!(v, Value::Error(_))) {
+            bail!("Redis error: {}", e);
+        }
+        Ok(values)
+    }
+
+    fn command(&self, args: &[&[u8]]) -> Result<Value> {
+        let mut values = self.run(&[args.to_vec()])?;
+        values.pop().ok_or_else(|| anyhow!("Missing Redis reply"))
+    }
+}
+
+/// Redis key layout of one pool, mirroring the sled key spaces
+struct RedisKeys {
+    queue: String,
+    records: String,
+    counter: String,
+    /// Set of every public key ever enqueued, shared by all pools
+    seen: String,
+}
+
+impl RedisKeys {
+    fn new(prefix: &str, pool: Option<&str>) -> Self {
+        let base = match pool {
+            None => prefix.to_string(),
+            Some(name) => format!("{}:pool:{}", prefix, name),
+        };
+        Self {
+            queue: format!("{}:queue", base),
+            records: format!("{}:addresses", base),
+            counter: format!("{}:counter", base),
+            seen: format!("{}:seen", prefix),
+        }
+    }
+}
+
+/// `Storage` on Redis: RPUSH/LPOP on a list of IDs plus a hash of records.
+/// Pops run as one Lua script so concurrent replicas never hand out the same address.
+pub struct RedisStorage {
+    pool: Arc<ConnectionPool>,
+    prefix: String,
+    keys: RedisKeys,
+    key_ring: Option<Arc<KeyRing>>,
+}
+
+impl RedisStorage {
+    /// Connect to `url` and verify the server answers. Keys are namespaced under `prefix`.
+    pub fn connect(url: &str, prefix: &str, pool_size: usize, timeout: Duration) -> Result<Self> {
+        let pool = ConnectionPool::new(ConnectionInfo::parse(url)?, pool_size, timeout);
+        match pool.command(&[b"PING"])? {
+            Value::Status(_) => {}
+            other => bail!("Unexpected PING reply from Redis: {:?}", other),
+        }
+
+        tracing::info!("Connected to Redis at {}:{}", pool.info.host, pool.info.port);
+        Ok(Self {
+            pool: Arc::new(pool),
+            prefix: prefix.to_string(),
+            keys: RedisKeys::new(prefix, None),
+            key_ring: None,
+        })
+    }
+
+    /// Encrypt private keys before they are written to Redis
+    pub fn with_key_ring(mut self, key_ring: Arc<KeyRing>) -> Self {
+        self.key_ring = Some(key_ring);
+        self
+    }
+
+    fn decode_record(&self, bytes: &[u8]) -> Result<PetAddressInfo> {
+        let address_info: PetAddressInfo =
+            serde_json::from_slice(bytes).context("Failed to deserialize address info")?;
+        match &self.key_ring {
+            Some(key_ring) => key_ring.open_record(address_info),
+            None if encryption::is_sealed(&address_info.address.private_key) => bail!(
+                "Address {} has an encrypted private key but encryption is not configured",
+                address_info.id
+            ),
+            None => Ok(address_info),
+        }
+    }
+
+    fn pop(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        let count = count.to_string();
+        let reply = self.pool.command(&[
+            b"EVAL",
+            POP_SCRIPT.as_bytes(),
+            b"2",
+            self.keys.queue.as_bytes(),
+            self.keys.records.as_bytes(),
+            count.as_bytes(),
+        ])?;
+
+        reply
+            .into_bulks()?
+            .iter()
+            .map(|record| self.decode_record(record))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Storage for RedisStorage {
+    fn backend_name(&self) -> &'static str {
+        "redis"
+    }
+
+    fn store_address(&self, address: PetAddress) -> Result<u64> {
+        let added = self
+            .pool
+            .command(&[b"SADD", self.keys.seen.as_bytes(), address.public_key.as_bytes()])?
+            .into_int()?;
+        if added == 0 {
+            return Err(StoreError::Duplicate {
+                public_key: address.public_key,
+            }
+            .into());
+        }
+
+        // INCR starts at 1; IDs start at 0 like the sled counter
+        let id = self.pool.command(&[b"INCR", self.keys.counter.as_bytes()])?.into_int()? as u64 - 1;
+        let address_info = PetAddressInfo {
+            id,
+            address,
+            created_at: chrono::Utc::now(),
+        };
+        let value = match &self.key_ring {
+            Some(key_ring) => serde_json::to_vec(&key_ring.seal_record(&address_info)?),
+            None => serde_json::to_vec(&address_info),
+        }
+        .context("Failed to serialize address info")?;
+
+        let id_arg = id.to_string();
+        self.pool.run(&[
+            vec![b"MULTI".as_slice()],
+            vec![b"HSET", self.keys.records.as_bytes(), id_arg.as_bytes(), &value],
+            vec![b"RPUSH", self.keys.queue.as_bytes(), id_arg.as_bytes()],
+            vec![b"EXEC"],
+        ])?;
+
+        Ok(id)
+    }
+
+    fn get_next_address(&self) -> Result<Option<PetAddressInfo>> {
+        Ok(self.pop(1)?.pop())
+    }
+
+    fn get_next_addresses(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        self.pop(count)
+    }
+
+    fn count_addresses(&self) -> Result<usize> {
+        Ok(self.pool.command(&[b"LLEN", self.keys.queue.as_bytes()])?.into_int()? as usize)
+    }
+
+    fn clear_all_addresses(&self) -> Result<()> {
+        self.pool
+            .command(&[b"DEL", self.keys.queue.as_bytes(), self.keys.records.as_bytes()])?;
+        Ok(())
+    }
+
+    /// Rebuild the ID list from the record hash (repairs IDs lost from the list)
+    async fn restore(&self) -> Result<usize> {
+        let mut ids: Vec<u64> = self
+            .pool
+            .command(&[b"HKEYS", self.keys.records.as_bytes()])?
+            .into_bulks()?
+            .iter()
+            .filter_map(|id| std::str::from_utf8(id).ok()?.parse().ok())
+            .collect();
+        ids.sort_unstable();
+
+        let ids: Vec<String> = ids.iter().map(u64::to_string).collect();
+        let mut push: Vec<&[u8]> = vec![b"RPUSH", self.keys.queue.as_bytes()];
+        push.extend(ids.iter().map(|id| id.as_bytes()));
+
+        let mut commands = vec![vec![b"MULTI".as_slice()], vec![b"DEL", self.keys.queue.as_bytes()]];
+        if !ids.is_empty() {
+            commands.push(push);
+        }
+        commands.push(vec![b"EXEC"]);
+        self.pool.run(&commands)?;
+
+        tracing::info!("Restored {} addresses from Redis to queue", ids.len());
+        Ok(ids.len())
+    }
+
+    async fn open_pool(&self, name: &str) -> Result<Arc<dyn Storage>> {
+        Ok(Arc::new(Self {
+            pool: Arc::clone(&self.pool),
+            prefix: self.prefix.clone(),
+            keys: RedisKeys::new(&self.prefix, Some(name)),
+            key_ring: self.key_ring.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_resp_roundtrip() {
+        let mut encoded = Vec::new();
+        encode_command(&[b"HSET", b"key", b"a\r\nb"], &mut encoded);
+        assert_eq!(encoded, b"*3\r\n$4\r\nHSET\r\n$3\r\nkey\r\n$4\r\na\r\nb\r\n");
+
+        let mut reply = Cursor::new(b"+OK\r\n:42\r\n$-1\r\n*2\r\n$3\r\nfoo\r\n$0\r\n\r\n-ERR boom\r\n".to_vec());
+        assert_eq!(read_value(&mut reply).unwrap(), Value::Status("OK".to_string()));
+        assert_eq!(read_value(&mut reply).unwrap().into_int().unwrap(), 42);
+        assert_eq!(read_value(&mut reply).unwrap(), Value::Nil);
+        assert_eq!(read_value(&mut reply).unwrap().into_bulks().unwrap(), vec![b"foo".to_vec(), Vec::new()]);
+        assert_eq!(read_value(&mut reply).unwrap(), Value::Error("ERR boom".to_string()));
+        assert_eq!(read_value(&mut reply).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_connection_info() {
+        let info = ConnectionInfo::parse("redis://:secret@cache.internal:6380/2").unwrap();
+        assert_eq!(info.host, "cache.internal");
+        assert_eq!(info.port, 6380);
+        assert_eq!(info.username, None);
+        assert_eq!(info.password.as_deref(), Some("secret"));
+        assert_eq!(info.db, 2);
+
+        let info = ConnectionInfo::parse("redis://app:pw@localhost").unwrap();
+        assert_eq!((info.username.as_deref(), info.port, info.db), (Some("app"), 6379, 0));
+
+        assert!(ConnectionInfo::parse("http://localhost").is_err());
+        assert!(ConnectionInfo::parse("redis://localhost:port").is_err());
+    }
+
+    #[test]
+    fn test_pool_key_layout() {
+        let keys = RedisKeys::new("pinpet", None);
+        assert_eq!((keys.queue.as_str(), keys.records.as_str()), ("pinpet:queue", "pinpet:addresses"));
+        assert_eq!(RedisKeys::new("pinpet", Some("Cat")).counter, "pinpet:pool:Cat:counter");
+        // Duplicate guard spans every pool
+        assert_eq!(RedisKeys::new("pinpet", Some("Cat")).seen, "pinpet:seen");
+    }
+
+    #[test]
+    fn test_reconnects_after_server_drops_idle_connection() {
+        // Fake server that answers one command per connection, then hangs up
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            for (served, stream) in listener.incoming().take(3).enumerate() {
+                let mut stream = stream.unwrap();
+                read_value(&mut BufReader::new(stream.try_clone().unwrap())).unwrap();
+                stream.write_all(format!(":{}\r\n", served).as_bytes()).unwrap();
+            }
+        });
+
+        let pool = ConnectionPool::new(
+            ConnectionInfo::parse(&format!("redis://127.0.0.1:{}", port)).unwrap(),
+            1,
+            Duration::from_secs(2),
+        );
+        for expected in 0..3 {
+            let reply = pool.command(&[b"LLEN", b"queue"]).unwrap();
+            assert_eq!(reply.into_int().unwrap(), expected);
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_connect_failure_is_reported() {
+        // Nothing listens on port 1
+        let result = RedisStorage::connect("redis://127.0.0.1:1", "pinpet", 1, Duration::from_millis(200));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//! Private keys in memory. A key is copied a few times on its way from the
+//! grinder to a client (queue, persistence, responses); each copy is wiped
+//! when dropped instead of being left behind in freed heap memory.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Deref;
+use zeroize::Zeroize;
+
+/// A base58 private key (or its sealed form). The buffer is overwritten with
+/// zeros on drop, and `Debug` never prints it.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretKey(String);
+
+impl SecretKey {
+    pub fn new(key: String) -> Self {
+        Self(key)
+    }
+
+    /// The key itself; keep the borrow short and avoid copying it into a plain `String`
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Deref for SecretKey {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SecretKey {
+    fn from(key: String) -> Self {
+        Self(key)
+    }
+}
+
+impl From<&str> for SecretKey {
+    fn from(key: &str) -> Self {
+        Self(key.to_string())
+    }
+}
+
+impl PartialEq<str> for SecretKey {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for SecretKey {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKey(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_key_is_never_printed() {
+        let key = SecretKey::from("5Kd3NBUAdUnhyzenEwVLy9pBKxSwXvE9FMPyR4UKZvpe");
+        assert_eq!(format!("{:?}", key), "SecretKey(..)");
+        assert_eq!(key, "5Kd3NBUAdUnhyzenEwVLy9pBKxSwXvE9FMPyR4UKZvpe");
+        assert_eq!(serde_json::to_string(&key).unwrap(), "\"5Kd3NBUAdUnhyzenEwVLy9pBKxSwXvE9FMPyR4UKZvpe\"");
+        let decoded: SecretKey = serde_json::from_str("\"abc\"").unwrap();
+        assert_eq!(decoded.expose(), "abc");
+    }
+}
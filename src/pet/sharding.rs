@@ -0,0 +1,223 @@
+//! Suffix-letter sharding: each node grinds and serves the addresses whose
+//! letter before the suffix (the `k` in `…kPet`) is in its share, and
+//! forwards letter-specific pops for other letters to the node owning them.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::time::Duration;
+
+use super::http::{self, Url};
+use crate::config::ShardingConfig;
+use crate::models::SuffixStatsResponse;
+
+/// `ApiResponse` as read back from a peer
+#[derive(Deserialize)]
+struct Envelope<T> {
+    data: Option<T>,
+}
+
+/// Marks a request one node forwarded to another; it is answered locally
+/// and never forwarded again
+pub const SHARD_FORWARDED_HEADER: &str = "x-shard-forwarded";
+
+/// Lowercase base58 letters (`a`–`z` without `l`) a node is responsible for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardLetters(BTreeSet<char>);
+
+impl ShardLetters {
+    /// Every lowercase base58 letter
+    pub fn all() -> Self {
+        Self(('a'..='z').filter(|letter| *letter != 'l').collect())
+    }
+
+    /// Letters and ranges, e.g. `a-m`, `n-z` or `a-c,x,y`. `l` is skipped
+    /// inside ranges since base58 lacks it.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut letters = BTreeSet::new();
+        for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let (first, last) = match part.split_once('-') {
+                Some((first, last)) => (single_letter(first)?, single_letter(last)?),
+                None => {
+                    let letter = single_letter(part)?;
+                    (letter, letter)
+                }
+            };
+            if first > last {
+                bail!("Letter range '{}' runs backwards", part);
+            }
+            if first == last && first == 'l' {
+                bail!("'l' never appears in base58 addresses");
+            }
+            letters.extend((first..=last).filter(|letter| *letter != 'l'));
+        }
+        if letters.is_empty() {
+            bail!("No letters in '{}'", spec);
+        }
+        Ok(Self(letters))
+    }
+
+    pub fn contains(&self, letter: char) -> bool {
+        self.0.contains(&letter)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = char> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+fn single_letter(text: &str) -> Result<char> {
+    let mut chars = text.trim().chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) if letter.is_ascii_lowercase() => Ok(letter),
+        _ => Err(anyhow!("'{}' is not a lowercase letter", text.trim())),
+    }
+}
+
+/// Regex character-class form, e.g. `a-km` for `a-m`
+impl fmt::Display for ShardLetters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letters: Vec<char> = self.iter().collect();
+        let mut start = 0;
+        while start < letters.len() {
+            let mut end = start;
+            while end + 1 < letters.len() && letters[end + 1] as u8 == letters[end] as u8 + 1 {
+                end += 1;
+            }
+            match end - start {
+                0 => write!(f, "{}", letters[start])?,
+                1 => write!(f, "{}{}", letters[start], letters[end])?,
+                _ => write!(f, "{}-{}", letters[start], letters[end])?,
+            }
+            start = end + 1;
+        }
+        Ok(())
+    }
+}
+
+/// Another node and the letters it serves
+#[derive(Debug, Clone)]
+pub struct ShardPeer {
+    pub url: Url,
+    pub letters: ShardLetters,
+}
+
+/// This node's share of the letters and where the rest live
+#[derive(Debug, Clone)]
+pub struct ShardRouter {
+    letters: ShardLetters,
+    peers: Vec<ShardPeer>,
+    timeout: Duration,
+}
+
+impl ShardRouter {
+    /// Fails on unparsable letters or a letter claimed by two nodes
+    pub fn new(config: &ShardingConfig) -> Result<Self> {
+        let letters = ShardLetters::parse(&config.letters).context("sharding.letters")?;
+        let mut claimed = letters.clone();
+        let mut peers = Vec::new();
+        for peer in &config.peers {
+            let url = Url::parse(&peer.url).with_context(|| format!("Invalid shard peer URL '{}'", peer.url))?;
+            let peer_letters = ShardLetters::parse(&peer.letters).with_context(|| format!("Letters of shard peer {}", peer.url))?;
+            if let Some(letter) = peer_letters.iter().find(|letter| claimed.contains(*letter)) {
+                bail!("Letter '{}' is served by more than one shard (again by {})", letter, peer.url);
+            }
+            claimed.0.extend(peer_letters.iter());
+            peers.push(ShardPeer { url, letters: peer_letters });
+        }
+
+        let unserved: Vec<String> = ShardLetters::all()
+            .iter()
+            .filter(|letter| !claimed.contains(*letter))
+            .map(String::from)
+            .collect();
+        if !unserved.is_empty() {
+            tracing::warn!("No shard serves the letters {}; addresses ending in them are never ground", unserved.join(", "));
+        }
+
+        Ok(Self {
+            letters,
+            peers,
+            timeout: Duration::from_millis(config.timeout_ms),
+        })
+    }
+
+    /// Letters this node grinds and serves
+    pub fn letters(&self) -> &ShardLetters {
+        &self.letters
+    }
+
+    pub fn peers(&self) -> &[ShardPeer] {
+        &self.peers
+    }
+
+    /// The peer serving `letter`; None when it is this node's (or nobody's)
+    pub fn owner(&self, letter: char) -> Option<&ShardPeer> {
+        if self.letters.contains(letter) {
+            return None;
+        }
+        self.peers.iter().find(|peer| peer.letters.contains(letter))
+    }
+
+    /// Send a request to `peer`, marked as forwarded. Blocking.
+    pub fn forward(&self, peer: &ShardPeer, method: &str, path: &str, headers: &[(&str, String)]) -> Result<http::Response> {
+        let mut headers = headers.to_vec();
+        headers.push((SHARD_FORWARDED_HEADER, "1".to_string()));
+        headers.push(("Accept", "application/json".to_string()));
+        let path = format!("{}{}", peer.url.path.trim_end_matches('/'), path);
+        http::send(&peer.url, method, &path, &headers, &[], self.timeout)
+    }
+
+    /// The ending-letter counts `peer` holds itself. Blocking.
+    pub fn suffix_stats(&self, peer: &ShardPeer) -> Result<SuffixStatsResponse> {
+        let response = self.forward(peer, "GET", "/stats/suffixes", &[])?;
+        if !response.is_success() {
+            bail!("Suffix stats refused: {}", response.describe());
+        }
+        let envelope: Envelope<SuffixStatsResponse> =
+            serde_json::from_slice(&response.body).context("Malformed suffix stats")?;
+        envelope.data.ok_or_else(|| anyhow!("No suffix stats in the response"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ShardPeerConfig;
+
+    #[test]
+    fn test_letters_and_owners() {
+        let letters = ShardLetters::parse("a-m").unwrap();
+        assert_eq!(letters.len(), 12);
+        assert!(!letters.contains('l'));
+        assert_eq!(letters.to_string(), "a-km");
+        assert_eq!(ShardLetters::parse("x, a-c ,y").unwrap().to_string(), "a-cxy");
+        assert_eq!(ShardLetters::all().to_string(), "a-km-z");
+        for bad in ["", "A-M", "m-a", "l", "ab"] {
+            assert!(ShardLetters::parse(bad).is_err(), "{}", bad);
+        }
+
+        let config = |peer_letters: &str| ShardingConfig {
+            enabled: true,
+            letters: "a-m".to_string(),
+            timeout_ms: 1000,
+            peers: vec![ShardPeerConfig {
+                url: "http://node-b:5057".to_string(),
+                letters: peer_letters.to_string(),
+            }],
+        };
+        let router = ShardRouter::new(&config("n-z")).unwrap();
+        assert!(router.owner('k').is_none());
+        assert_eq!(router.owner('p').unwrap().url.host, "node-b");
+        assert!(ShardRouter::new(&config("m-z")).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! Scheduled snapshots: a logical JSONL dump of every sled pool is uploaded to
+//! S3-compatible object storage, older snapshots are pruned down to a
+//! retention count, and a snapshot can be restored into the pools at startup.
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::backend::StoreError;
+use super::backup::{DumpFormat, DumpRecord};
+use super::generator::PatternPool;
+use super::s3::{Credentials, S3Client};
+use crate::config::{BackupConfig, Secrets};
+
+/// One line of a snapshot: a dump record tagged with the pool it came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotRecord {
+    pub pool: String,
+    #[serde(flatten)]
+    pub record: DumpRecord,
+}
+
+/// Outcome of restoring a snapshot
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RestoreReport {
+    pub restored: usize,
+    /// Already queued in the target pool
+    pub duplicates: usize,
+    /// Unknown pool, invalid key or full queue
+    pub skipped: usize,
+}
+
+/// Uploads, lists, prunes and fetches snapshots under one bucket prefix
+#[derive(Clone)]
+pub struct SnapshotStore {
+    client: S3Client,
+    prefix: String,
+    retain: usize,
+}
+
+impl SnapshotStore {
+    pub fn from_config(config: &BackupConfig, secrets: &Secrets) -> Result<Self> {
+        let client = S3Client::new(
+            &config.endpoint,
+            &config.bucket,
+            &config.region,
+            config.path_style,
+            Credentials::from_secrets(secrets)?,
+            Duration::from_millis(config.timeout_ms),
+        )?;
+        Ok(Self::new(client, &config.prefix, config.retain))
+    }
+
+    pub fn new(client: S3Client, prefix: &str, retain: usize) -> Self {
+        Self {
+            client,
+            prefix: prefix.to_string(),
+            retain: retain.max(1),
+        }
+    }
+
+    /// Dump every sled pool, upload the dump and prune old snapshots.
+    /// Returns the object key and the number of records written.
+    pub fn take(&self, pools: &[PatternPool]) -> Result<(String, usize)> {
+        let mut body = String::new();
+        let mut count = 0;
+        for pool in pools {
+            let Some(storage) = pool.storage.sled() else {
+                continue;
+            };
+            for address_info in storage.records() {
+                let line = SnapshotRecord {
+                    pool: pool.name.clone(),
+                    record: DumpRecord::from_info(&address_info),
+                };
+                body.push_str(&serde_json::to_string(&line)?);
+                body.push('\n');
+                count += 1;
+            }
+        }
+
+        let key = format!(
+            "{}snapshot-{}.{}",
+            self.prefix,
+            Utc::now().format("%Y%m%dT%H%M%SZ"),
+            DumpFormat::JsonLines.extension()
+        );
+        self.client.put_object(&key, body.as_bytes())?;
+
+        if let Err(e) = self.prune() {
+            tracing::warn!("Failed to prune old snapshots: {}", e);
+        }
+        Ok((key, count))
+    }
+
+    /// Snapshot keys, oldest first (timestamps sort lexicographically)
+    pub fn list(&self) -> Result<Vec<String>> {
+        let mut keys: Vec<String> = self
+            .client
+            .list_objects(&self.prefix)?
+            .into_iter()
+            .filter(|key| key[self.prefix.len()..].starts_with("snapshot-"))
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Delete all but the newest `retain` snapshots
+    pub fn prune(&self) -> Result<usize> {
+        let keys = self.list()?;
+        let stale = keys.len().saturating_sub(self.retain);
+        for key in &keys[..stale] {
+            self.client.delete_object(key)?;
+        }
+        Ok(stale)
+    }
+
+    /// Resolve `latest` to the newest snapshot key; anything else is a key
+    /// (with or without the prefix)
+    pub fn resolve(&self, name: &str) -> Result<String> {
+        if name == "latest" {
+            return self
+                .list()?
+                .pop()
+                .ok_or_else(|| anyhow!("No snapshots found under '{}'", self.prefix));
+        }
+        Ok(match name.starts_with(&self.prefix) {
+            true => name.to_string(),
+            false => format!("{}{}", self.prefix, name),
+        })
+    }
+
+    /// Load snapshot `name` into the matching sled pools
+    pub fn restore(&self, name: &str, pools: &[PatternPool]) -> Result<(String, RestoreReport)> {
+        let key = self.resolve(name)?;
+        let body = self.client.get_object(&key)?;
+        let body = String::from_utf8(body).map_err(|_| anyhow!("Snapshot '{}' is not UTF-8", key))?;
+        Ok((key, restore_records(&body, pools)))
+    }
+}
+
+/// Store every valid line of a snapshot into its pool, keeping `created_at`.
+/// Keys already queued in the pool are counted as duplicates, so restoring
+/// onto a partially filled database is safe.
+pub fn restore_records(body: &str, pools: &[PatternPool]) -> RestoreReport {
+    let mut report = RestoreReport::default();
+    let mut queued: Vec<HashSet<String>> = pools
+        .iter()
+        .map(|pool| match pool.storage.sled() {
+            Some(storage) => storage
+                .records()
+                .into_iter()
+                .map(|address_info| address_info.address.public_key)
+                .collect(),
+            None => HashSet::new(),
+        })
+        .collect();
+
+    for (index, line) in body.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let line: SnapshotRecord = match serde_json::from_str(line) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!("Snapshot line {}: {}", index + 1, e);
+                report.skipped += 1;
+                continue;
+            }
+        };
+        let Some(position) = pools.iter().position(|pool| pool.name == line.pool) else {
+            tracing::warn!("Snapshot line {}: unknown pool '{}'", index + 1, line.pool);
+            report.skipped += 1;
+            continue;
+        };
+        let (pool, record) = (&pools[position], line.record);
+        let Some(storage) = pool.storage.sled() else {
+            report.skipped += 1;
+            continue;
+        };
+        if let Err(e) = record.validate(&pool.matcher) {
+            tracing::warn!("Snapshot line {}: {}", index + 1, e);
+            report.skipped += 1;
+            continue;
+        }
+        if !queued[position].insert(record.public_key.clone()) {
+            report.duplicates += 1;
+            continue;
+        }
+
+        storage.wait_for_writer();
+        match storage.store_address_at(record.to_address(), record.created_at) {
+            Ok(_) => report.restored += 1,
+            Err(e) => match e.downcast_ref::<StoreError>() {
+                Some(StoreError::Duplicate { .. }) => report.duplicates += 1,
+                _ => {
+                    tracing::warn!("Snapshot line {}: {}", index + 1, e);
+                    report.skipped += 1;
+                }
+            },
+        }
+    }
+
+    report
+}
+
+/// Take a snapshot every `interval_seconds`
+pub fn start_snapshots(store: SnapshotStore, pools: Vec<PatternPool>, interval_seconds: u64) {
+    let interval = Duration::from_secs(interval_seconds.max(1));
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            let (store, pools) = (store.clone(), pools.clone());
+            match tokio::task::spawn_blocking(move || store.take(&pools)).await {
+                Ok(Ok((key, count))) => tracing::info!("Uploaded snapshot '{}' ({} addresses)", key, count),
+                Ok(Err(e)) => tracing::warn!("Snapshot failed: {}", e),
+                Err(e) => tracing::warn!("Snapshot task failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::{AddressMatcher, PetAddress, PetStorage, Storage};
+    use std::sync::Arc;
+
+    fn pool(name: &str, matcher: &Arc<AddressMatcher>) -> PatternPool {
+        let storage: Arc<dyn Storage> = Arc::new(PetStorage::temporary().unwrap());
+        PatternPool {
+            name: name.to_string(),
+            matcher: Arc::clone(matcher),
+            storage,
+        }
+    }
+
+    #[test]
+    fn test_restore_records_into_matching_pools() {
+        let matcher = Arc::new(AddressMatcher::regex(".", true).unwrap());
+        let source = [pool("default", &matcher), pool("tenant:dev", &matcher)];
+        for pool in &source {
+            pool.storage.sled().unwrap().store_address(PetAddress::generate(&matcher).unwrap()).unwrap();
+        }
+        let body: String = source
+            .iter()
+            .flat_map(|pool| {
+                pool.storage.sled().unwrap().records().into_iter().map(|address_info| {
+                    let line = SnapshotRecord {
+                        pool: pool.name.clone(),
+                        record: DumpRecord::from_info(&address_info),
+                    };
+                    format!("{}\n", serde_json::to_string(&line).unwrap())
+                })
+            })
+            .chain(["{\"pool\":\"gone\"}\n".to_string()])
+            .collect();
+
+        let target = [pool("default", &matcher), pool("tenant:dev", &matcher)];
+        let report = restore_records(&body, &target);
+        assert_eq!(
+            report,
+            RestoreReport {
+                restored: 2,
+                duplicates: 0,
+                skipped: 1
+            }
+        );
+        for (source, target) in source.iter().zip(&target) {
+            let expected = source.storage.sled().unwrap().records();
+            let restored = target.storage.sled().unwrap().records();
+            assert_eq!(restored[0].address.private_key, expected[0].address.private_key);
+            assert_eq!(restored[0].created_at, expected[0].created_at);
+        }
+
+        // Restoring twice does not queue the same keys again
+        let report = restore_records(&body, &target);
+        assert_eq!((report.restored, report.duplicates), (0, 2));
+    }
+}
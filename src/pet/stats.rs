@@ -0,0 +1,109 @@
+//! Generator statistics beyond raw throughput: attempts per found address,
+//! per-thread rates, and how long recent addresses took to find. Meant for
+//! capacity planning, e.g. whether more cores would shorten refills.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Upper bounds of the time-to-find buckets, in seconds
+pub const TIME_TO_FIND_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 600.0];
+
+/// Finds kept for the rolling histogram
+const WINDOW: usize = 1000;
+
+/// Time to find each of the most recent addresses
+#[derive(Debug, Default)]
+pub struct FindHistory {
+    recent: VecDeque<Duration>,
+}
+
+impl FindHistory {
+    pub fn record(&mut self, elapsed: Duration) {
+        if self.recent.len() == WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(elapsed);
+    }
+
+    pub fn summary(&self) -> TimeToFind {
+        let mut seconds: Vec<f64> = self.recent.iter().map(Duration::as_secs_f64).collect();
+        seconds.sort_by(f64::total_cmp);
+        let percentile = |p: f64| -> Option<f64> {
+            let rank = ((p * seconds.len() as f64).ceil() as usize).max(1);
+            seconds.get(rank - 1).copied()
+        };
+        TimeToFind {
+            samples: seconds.len(),
+            mean_seconds: (!seconds.is_empty()).then(|| seconds.iter().sum::<f64>() / seconds.len() as f64),
+            p50_seconds: percentile(0.5),
+            p90_seconds: percentile(0.9),
+            p99_seconds: percentile(0.99),
+            // Cumulative, like a Prometheus histogram
+            buckets: TIME_TO_FIND_BUCKETS
+                .iter()
+                .map(|&le| (le, seconds.partition_point(|s| *s <= le)))
+                .collect(),
+        }
+    }
+}
+
+/// Summary of the recent time-to-find window
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeToFind {
+    pub samples: usize,
+    pub mean_seconds: Option<f64>,
+    pub p50_seconds: Option<f64>,
+    pub p90_seconds: Option<f64>,
+    pub p99_seconds: Option<f64>,
+    /// (upper bound in seconds, finds at or under it)
+    pub buckets: Vec<(f64, usize)>,
+}
+
+/// Keys one worker slot has tried and the time it spent searching
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WorkerStats {
+    pub keys: u64,
+    pub busy: Duration,
+}
+
+impl WorkerStats {
+    pub fn keys_per_second(&self) -> Option<f64> {
+        let secs = self.busy.as_secs_f64();
+        (self.keys > 0 && secs > 0.0).then(|| self.keys as f64 / secs)
+    }
+}
+
+/// Point-in-time generator statistics
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorStats {
+    pub addresses_found: u64,
+    pub keys_tried: u64,
+    /// Mean keys tried per address found, across all workers
+    pub attempts_per_address: Option<f64>,
+    pub keys_per_second: Option<f64>,
+    /// Indexed by worker slot; slot 0 is also used by single-threaded searches
+    pub workers: Vec<WorkerStats>,
+    pub time_to_find: TimeToFind,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_keeps_a_rolling_window() {
+        let mut history = FindHistory::default();
+        assert_eq!(history.summary().p50_seconds, None);
+
+        for millis in 1..=WINDOW as u64 + 100 {
+            history.record(Duration::from_millis(millis));
+        }
+        let summary = history.summary();
+        assert_eq!(summary.samples, WINDOW);
+        // The first 100 finds fell out of the window
+        assert!((summary.p50_seconds.unwrap() - 0.6).abs() < 1e-9);
+        assert!((summary.p99_seconds.unwrap() - 1.09).abs() < 1e-9);
+        assert_eq!(summary.buckets[3], (0.5, 400));
+        assert_eq!(summary.buckets.last(), Some(&(600.0, WINDOW)));
+    }
+}
@@ -1,37 +1,460 @@
 use anyhow::{Result, Context};
 use crossbeam_queue::SegQueue;
+use dashmap::DashMap;
 use sled::Db;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
 
-use super::address::{PetAddress, PetAddressInfo};
+use crate::config::{ExpiryConfig, LeaseConfig, ReconciliationConfig};
+
+use super::address::{AddressMatcher, PetAddress, PetAddressInfo};
+use super::archive::{DispensedFilter, DispensedRecord};
+use super::audit::{AuditLog, AUDIT_TREE};
+use super::backend::StoreError;
+use super::benchmark::BenchmarkReport;
+use super::codec;
+use super::dedup::SeenKeys;
+use super::encryption::{self, KeyRing};
+use super::persistence::{Persister, WriteOp};
+use super::usage::{Quota, UsageStore};
+use super::overrides::OverrideStore;
+use super::recipients::RecipientRegistry;
+use super::vault::KeyVault;
+use super::webhooks::SubscriptionStore;
+
+/// Sled key of the latest grinder benchmark, outside every pool's key space
+const BENCHMARK_KEY: &[u8] = b"benchmark";
+/// Prefix corrupt address records are moved under by `verify`, out of every pool
+const QUARANTINE_PREFIX: &str = "quarantine:";
+/// DB writes one pop can cause: removing the record and archiving it
+const POP_WRITES: usize = 2;
+/// Scratch key of the startup write-read check, outside every pool's key space
+const SMOKE_TEST_KEY: &[u8] = b"preflight";
+/// How often a waiting bulk load checks whether the writer has caught up
+const BUSY_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Background upkeep every sled pool gets, including pools opened at runtime
+#[derive(Debug, Clone, Default)]
+pub struct PoolMaintenance {
+    pub reconciliation: ReconciliationConfig,
+    pub leases: LeaseConfig,
+    pub expiry: ExpiryConfig,
+}
+
+impl PoolMaintenance {
+    /// Start the enabled reconciliation, lease and expiry tasks of `storage`,
+    /// and its pager when the pool is memory-limited
+    pub fn start(&self, storage: &PetStorage) {
+        // Optionally repair queue/DB drift in the background
+        if self.reconciliation.enabled {
+            storage.start_reconciliation(self.reconciliation.interval_seconds);
+        }
+
+        // Requeue checked-out addresses that were never acknowledged
+        if self.leases.enabled {
+            storage.start_lease_sweeper(self.leases.sweep_interval_seconds);
+        }
+
+        // Prune addresses derived too long ago
+        if self.expiry.enabled {
+            let max_age = Duration::from_secs(self.expiry.max_age_days.saturating_mul(86_400));
+            storage.start_expiry_sweeper(max_age, self.expiry.sweep_interval_seconds);
+        }
+
+        // Read addresses held only in sled back as the queue drains
+        storage.start_pager();
+    }
+}
+
+/// Snapshot of queue/DB accounting used to spot drift between the atomic
+/// size counter, the real queue contents and the persisted records
+#[derive(Debug, Clone)]
+pub struct StorageDiagnostics {
+    pub reported_size: usize,
+    pub actual_queue_len: usize,
+    /// Queued addresses held only in sled because of the memory limit
+    pub spilled: usize,
+    pub db_record_count: usize,
+    /// Checked-out addresses awaiting acknowledgement (still persisted)
+    pub leased: usize,
+    pub counter: u64,
+    /// Writes queued for sled but not yet applied
+    pub pending_writes: usize,
+    /// How long the last applied write batch waited in the queue
+    pub persist_lag_ms: u64,
+    /// Writes the background writer failed to apply since startup, after every retry
+    pub failed_writes: u64,
+    /// Writes of the batch waiting to be retried
+    pub retrying_writes: usize,
+    /// Writes kept in the dead-letter tree
+    pub dead_letters: u64,
+    /// Stores and pops refused since startup because the writer was behind
+    /// or degraded mode refused stores
+    pub refused_operations: u64,
+    /// Sled keeps failing and the circuit breaker is open
+    pub degraded: bool,
+    /// Failed write batches and flushes since the last success
+    pub consecutive_write_failures: u32,
+    /// Addresses pruned for exceeding the maximum age since startup
+    pub expired: u64,
+}
+
+impl StorageDiagnostics {
+    /// Atomic size minus real queue length
+    pub fn size_delta(&self) -> i64 {
+        self.reported_size as i64 - self.actual_queue_len as i64
+    }
+
+    /// Persisted records minus queued and leased addresses (pending writes can make this briefly non-zero)
+    pub fn db_delta(&self) -> i64 {
+        self.db_record_count as i64 - self.actual_queue_len as i64 - self.leased as i64
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.size_delta() == 0 && self.db_delta() == 0
+    }
+}
+
+/// Log an unusable record and, when `quarantine` is set, move it under
+/// `quarantine:`. Returns the record's key for the report.
+fn set_aside(
+    db: &Db,
+    key: &[u8],
+    value: &[u8],
+    error: &anyhow::Error,
+    quarantine: bool,
+    quarantined: &mut usize,
+) -> Result<String> {
+    let name = String::from_utf8_lossy(key).into_owned();
+    tracing::error!("Corrupt record {}: {:#}", name, error);
+    if quarantine {
+        db.insert(format!("{}{}", QUARANTINE_PREFIX, name).as_bytes(), value)?;
+        db.remove(key)?;
+        *quarantined += 1;
+    }
+    Ok(name)
+}
+
+/// Result of one reconciliation pass between the queue and sled
+#[derive(Debug, Clone, Default)]
+pub struct ReconcileReport {
+    /// Queued addresses that had no DB record and were written again
+    pub repersisted: usize,
+    /// DB records for addresses no longer queued that were deleted
+    pub orphans_removed: usize,
+}
+
+/// Outcome of `PetStorage::verify`
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    /// Sled records examined
+    pub checked: usize,
+    /// Records that were neither queued nor leased, deleted
+    pub orphans_removed: usize,
+    /// Queued addresses that had no record, written again
+    pub repersisted: usize,
+    /// Later copies of a public key already queued (here or in an earlier
+    /// pool), removed from the queue and sled
+    pub duplicates_removed: usize,
+    /// Keys of records that cannot be decoded or decrypted
+    pub corrupt: Vec<String>,
+    /// Keys of readable records whose private key does not derive their
+    /// public key or address
+    pub mismatched: Vec<String>,
+    /// Addresses that do not match the pool's pattern. Only reported: the
+    /// pattern may have changed since they were generated.
+    pub off_pattern: Vec<String>,
+    /// Corrupt and mismatched records moved under `quarantine:`
+    pub quarantined: usize,
+}
+
+impl VerifyReport {
+    pub fn is_clean(&self) -> bool {
+        self.orphans_removed == 0
+            && self.repersisted == 0
+            && self.duplicates_removed == 0
+            && self.corrupt.is_empty()
+            && self.mismatched.is_empty()
+    }
+
+    /// Records that cannot be trusted to hold a usable key
+    pub fn unusable(&self) -> usize {
+        self.corrupt.len() + self.mismatched.len()
+    }
+}
+
+/// A checked-out address that returns to the queue unless acknowledged before `expires_at`
+#[derive(Debug, Clone)]
+pub struct Lease {
+    pub address_info: PetAddressInfo,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    /// Archive entry of this checkout, completed on acknowledgement
+    pub dispensed: Option<DispensedRecord>,
+}
 
 /// High-performance storage with zero-copy lock-free queue for API hot path
 /// Architecture:
-/// - Hot path (API): Lock-free SegQueue for O(1) pop operations
+/// - Hot path (API): Lock-free SegQueue of IDs for O(1) FIFO pops, records in a sharded map
 /// - Cold path (backup): Sled DB for persistence and recovery
-/// - Background: Async batch flush to avoid blocking
+/// - Background: One writer thread applies sled writes in order, in batches
 #[derive(Clone)]
 pub struct PetStorage {
-    // Hot path: Lock-free queue for instant API access
-    address_queue: Arc<SegQueue<PetAddressInfo>>,
+    // Hot path: Lock-free FIFO of address IDs for instant API access
+    address_queue: Arc<SegQueue<u64>>,
+    // Queued records by ID - the source of truth for what is still available.
+    // IDs left in `address_queue` without a record here are skipped on pop.
+    addresses: Arc<DashMap<u64, PetAddressInfo>>,
 
     // Metrics: Lock-free atomic counters
     queue_size: Arc<AtomicUsize>,
     counter: Arc<AtomicU64>,
+    expired: Arc<AtomicU64>,
+
+    // Cold path: Persistence (optional, for backup only). sled synchronises
+    // internally, so readers and the writer share plain handles.
+    db: Option<Db>,
+    // Ordered background writer for `db`, shared by all pools
+    persister: Option<Persister>,
+    // Public keys ever enqueued in `db`, shared by all pools
+    seen: Option<Arc<SeenKeys>>,
+    // Sled key layout for this pool
+    keys: Arc<KeySpace>,
+
+    // Signalled whenever addresses are consumed so the generator can refill.
+    // Shared by all pools opened from the same storage.
+    demand: Arc<Notify>,
+
+    // When set, pops check addresses out instead of deleting them; the sled
+    // record is only removed on `acknowledge`
+    lease_ttl: Option<Duration>,
+    leases: Arc<DashMap<u64, Lease>>,
+
+    // When set, stores fail with `StoreError::QueueFull` once this many addresses are queued
+    capacity: Option<usize>,
+
+    // When set, private keys are sealed with AES-256-GCM before they reach sled
+    key_ring: Option<Arc<KeyRing>>,
+
+    // Time `new` spent opening the database and loading the default pool
+    opened_in: Option<Duration>,
+
+    // When set, only the front of the queue is kept in memory; later
+    // addresses stay in sled until the pager reads them back
+    spill: Option<Arc<Spill>>,
+}
+
+/// In-memory window of a memory-limited pool
+#[derive(Debug)]
+struct Spill {
+    limit: usize,
+    state: Mutex<SpillState>,
+    // Signalled when the window runs low
+    wake: Notify,
+}
+
+#[derive(Debug, Default)]
+struct SpillState {
+    // Lowest ID that is queued only in sled. Every queued ID from here up
+    // is on disk only; stores go to disk while it is set, to keep FIFO order.
+    from: Option<u64>,
+    // Queued addresses held only in sled
+    spilled: usize,
+    // Bumped whenever the window is rebuilt, so a page read before that is dropped
+    generation: u64,
+    // Spilled IDs popped straight from sled by a filtered pop. Their record
+    // stays until the remove (or the lease's ack) lands, so readers of the
+    // spilled range skip them.
+    taken: HashSet<u64>,
+}
+
+impl Spill {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            state: Mutex::new(SpillState::default()),
+            wake: Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SpillState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Sled key layout of one address pool. The default pool keeps the original
+/// `address:` / `dispensed:` / `counter` keys; named pools are namespaced under `pool:{name}:`
+#[derive(Debug)]
+struct KeySpace {
+    address_prefix: String,
+    dispensed_prefix: String,
+    counter_key: String,
+}
+
+impl KeySpace {
+    fn default_pool() -> Self {
+        Self {
+            address_prefix: "address:".to_string(),
+            dispensed_prefix: "dispensed:".to_string(),
+            counter_key: "counter".to_string(),
+        }
+    }
+
+    fn named_pool(name: &str) -> Self {
+        Self {
+            address_prefix: format!("pool:{}:address:", name),
+            dispensed_prefix: format!("pool:{}:dispensed:", name),
+            counter_key: format!("pool:{}:counter", name),
+        }
+    }
+
+    /// IDs are zero-padded to the full width of u64 so key order is ID order.
+    /// Older databases used 10 digits; those keys are rewritten on load.
+    fn address_key(&self, id: u64) -> String {
+        format!("{}{:020}", self.address_prefix, id)
+    }
 
-    // Cold path: Persistence (optional, for backup only)
-    db: Option<Arc<RwLock<Db>>>,
+    fn dispensed_key(&self, id: u64) -> String {
+        format!("{}{:020}", self.dispensed_prefix, id)
+    }
+
+    fn parse_address_key(&self, key: &[u8]) -> Option<u64> {
+        let id = key.strip_prefix(self.address_prefix.as_bytes())?;
+        std::str::from_utf8(id).ok()?.parse().ok()
+    }
 }
 
 impl PetStorage {
     pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let started = Instant::now();
+        let db = sled::open(db_path)?;
+        let storage = Self::from_db(db, None, KeySpace::default_pool(), Arc::new(Notify::new()), None)?;
+        Ok(storage.opened_after(started))
+    }
+
+    /// Like `new`, but private keys are encrypted at rest with `key_ring`.
+    /// Plaintext records and records sealed with an older key are rewritten
+    /// with the active key on startup.
+    pub fn new_encrypted<P: AsRef<Path>>(db_path: P, key_ring: Arc<KeyRing>) -> Result<Self> {
+        let started = Instant::now();
         let db = sled::open(db_path)?;
+        let storage = Self::from_db(db, None, KeySpace::default_pool(), Arc::new(Notify::new()), Some(key_ring))?;
+        Ok(storage.opened_after(started))
+    }
+
+    fn opened_after(mut self, started: Instant) -> Self {
+        self.opened_in = Some(started.elapsed());
+        self
+    }
+
+    /// How long opening the database took, sled recovery and loading the
+    /// default pool included; None for pools opened from a sibling
+    pub fn opened_in(&self) -> Option<Duration> {
+        self.opened_in
+    }
+
+    /// Open a throwaway database that is deleted on drop (used by tests)
+    #[cfg(test)]
+    pub(crate) fn temporary() -> Result<Self> {
+        let db = sled::Config::new().temporary(true).open()?;
+        Self::from_db(db, None, KeySpace::default_pool(), Arc::new(Notify::new()), None)
+    }
+
+    /// Open a separately queued and persisted pool `name` in the same database
+    pub async fn open_pool(&self, name: &str) -> Result<Self> {
+        let (Some(db), Some(persister), Some(seen)) = (&self.db, &self.persister, &self.seen) else {
+            anyhow::bail!("Named pools require a persistent database");
+        };
+
+        let pool = Self::from_db(
+            db.clone(),
+            Some((persister.clone(), Arc::clone(seen))),
+            KeySpace::named_pool(name),
+            Arc::clone(&self.demand),
+            self.key_ring.clone(),
+        )?;
+        let pool = match self.lease_ttl {
+            Some(ttl) => pool.with_lease_ttl(ttl),
+            None => pool,
+        };
+        let pool = match &self.spill {
+            Some(spill) => pool.with_memory_limit(spill.limit),
+            None => pool,
+        };
+        Ok(match self.capacity {
+            Some(capacity) => pool.with_capacity(capacity),
+            None => pool,
+        })
+    }
+
+    /// Refuse stores once `capacity` addresses are queued (leased addresses
+    /// do not count). Pools opened afterwards inherit the limit.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Maximum queued addresses, if limited
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Keep at most `limit` queued addresses in memory. Later ones stay only
+    /// in sled and are read back by `start_pager` as the front drains. Pools
+    /// opened afterwards inherit the limit; without a database it does nothing.
+    pub fn with_memory_limit(mut self, limit: usize) -> Self {
+        if self.db.is_none() {
+            return self;
+        }
+        let spill = Spill::new(limit.max(1));
+
+        // Spill what the initial load put past the window
+        let mut ids: Vec<u64> = self.addresses.iter().map(|entry| *entry.key()).collect();
+        if ids.len() > spill.limit {
+            ids.sort_unstable();
+            let spilled = ids.split_off(spill.limit);
+            for id in &spilled {
+                self.addresses.remove(id);
+            }
+            while self.address_queue.pop().is_some() {}
+            for id in ids {
+                self.address_queue.push(id);
+            }
+            let mut state = spill.lock();
+            state.from = spilled.first().copied();
+            state.spilled = spilled.len();
+        }
+
+        self.spill = Some(Arc::new(spill));
+        self
+    }
+
+    /// Queued addresses kept in memory at most, if limited
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.spill.as_ref().map(|spill| spill.limit)
+    }
+
+    /// Check addresses out for `ttl` on pop instead of deleting them.
+    /// Pools opened afterwards inherit the setting.
+    pub fn with_lease_ttl(mut self, ttl: Duration) -> Self {
+        self.lease_ttl = Some(ttl);
+        self
+    }
 
+    /// `shared` is the writer and membership set of a sibling pool on the
+    /// same database; without it they are created.
+    fn from_db(
+        db: Db,
+        shared: Option<(Persister, Arc<SeenKeys>)>,
+        keys: KeySpace,
+        demand: Arc<Notify>,
+        key_ring: Option<Arc<KeyRing>>,
+    ) -> Result<Self> {
         // Load existing counter from DB
-        let counter = db.get(b"counter")?
+        let counter = db.get(keys.counter_key.as_bytes())?
             .map(|bytes| {
                 let mut array = [0u8; 8];
                 array.copy_from_slice(&bytes);
@@ -39,166 +462,2072 @@ impl PetStorage {
             })
             .unwrap_or(0);
 
+        let (persister, seen) = match shared {
+            Some(shared) => shared,
+            None => (Persister::start(db.clone())?, Arc::new(SeenKeys::load(db.clone())?)),
+        };
+
+        let storage = Self {
+            address_queue: Arc::new(SegQueue::new()),
+            addresses: Arc::new(DashMap::new()),
+            queue_size: Arc::new(AtomicUsize::new(0)),
+            counter: Arc::new(AtomicU64::new(counter)),
+            expired: Arc::new(AtomicU64::new(0)),
+            db: Some(db.clone()),
+            persister: Some(persister),
+            seen: Some(seen),
+            keys: Arc::new(keys),
+            demand,
+            lease_ttl: None,
+            leases: Arc::new(DashMap::new()),
+            capacity: None,
+            key_ring,
+            opened_in: None,
+            spill: None,
+        };
+
         // Restore addresses from DB to queue (during initialization, synchronous is fine)
-        let address_queue = Arc::new(SegQueue::new());
-        let mut count = 0;
+        storage.load_records(&db)?;
 
-        for result in db.scan_prefix(b"address:") {
-            let (_key, value) = result?;
-            let address_info: PetAddressInfo = serde_json::from_slice(&value)
-                .context("Failed to deserialize address info")?;
+        Ok(storage)
+    }
+
+    /// Rebuild the in-memory queue from this pool's sled records, replacing
+    /// whatever is queued or leased. Meant for startup or after the database
+    /// was changed externally: a pop racing the reload may be handed out again.
+    pub async fn restore(&self) -> Result<usize> {
+        let Some(db) = &self.db else {
+            return Ok(self.addresses.len());
+        };
+
+        // Load what every write queued so far left behind
+        self.flush().await?;
+        self.load_records(db)
+    }
+
+    fn load_records(&self, db: &Db) -> Result<usize> {
+        // Oldest first regardless of the order sled yields the keys in. With a
+        // memory limit only the oldest `limit` are kept; the rest stay in sled.
+        let limit = self.spill.as_ref().map_or(usize::MAX, |spill| spill.limit);
+        let mut records = BTreeMap::new();
+        let mut spilled = 0;
+        let mut spill_from: Option<u64> = None;
+        let mut max_id = None;
+        let mut resealed = 0;
+        let mut rekeyed = 0;
+        let mut converted = 0;
+
+        // Collect first: records moved to a new key must not show up again in the scan
+        let entries = db
+            .scan_prefix(self.keys.address_prefix.as_bytes())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (mut key, value) in entries {
+            // Unreadable records stay where they are; `verify` reports them
+            let mut address_info = match codec::decode(&value) {
+                Ok(address_info) => address_info,
+                Err(e) => {
+                    tracing::error!("Skipping unreadable record {}: {:#}", String::from_utf8_lossy(&key), e);
+                    continue;
+                }
+            };
+            // Legacy JSON records are rewritten in the binary layout
+            let mut rewrite = !codec::is_current(&value);
+            if rewrite {
+                converted += 1;
+            }
+
+            // Move records stored under the old narrow key format
+            let current_key = self.keys.address_key(address_info.id);
+            if key != current_key.as_bytes() {
+                db.insert(current_key.as_bytes(), value)?;
+                db.remove(&key)?;
+                key = current_key.into_bytes().into();
+                rekeyed += 1;
+            }
+
+            let private_key = &address_info.address.private_key;
+            match self.key_ring.as_deref() {
+                Some(key_ring) => {
+                    let stale = encryption::sealed_key_id(private_key) != Some(key_ring.active_key_id());
+                    address_info = match key_ring.open_record(address_info) {
+                        Ok(address_info) => address_info,
+                        Err(e) => {
+                            tracing::error!("Skipping unreadable record {}: {:#}", String::from_utf8_lossy(&key), e);
+                            continue;
+                        }
+                    };
+                    // Migrate plaintext and rotated-out records to the active key
+                    if stale {
+                        rewrite = true;
+                        resealed += 1;
+                    }
+                }
+                None if encryption::is_sealed(private_key) => {
+                    anyhow::bail!(
+                        "Address {} has an encrypted private key but encryption is not configured",
+                        address_info.id
+                    );
+                }
+                None => {}
+            }
+            if rewrite {
+                db.insert(key, Self::encode_record(self.key_ring.as_deref(), &address_info)?)?;
+            }
+
+            // Databases from before the duplicate guard have records outside the set
+            if let Some(seen) = &self.seen {
+                seen.insert(&address_info.address.public_key)?;
+            }
 
-            address_queue.push(address_info);
-            count += 1;
+            max_id = max_id.max(Some(address_info.id));
+            records.insert(address_info.id, address_info);
+            if records.len() > limit {
+                if let Some((id, _)) = records.pop_last() {
+                    spill_from = Some(spill_from.map_or(id, |from| from.min(id)));
+                    spilled += 1;
+                }
+            }
         }
 
-        tracing::info!("Restored {} addresses from database to queue", count);
+        while self.address_queue.pop().is_some() {}
+        self.addresses.clear();
+        self.leases.clear();
 
-        let storage = Self {
-            address_queue,
-            queue_size: Arc::new(AtomicUsize::new(count)),
-            counter: Arc::new(AtomicU64::new(counter)),
-            db: Some(Arc::new(RwLock::new(db))),
-        };
+        // Never reuse a persisted ID, even if the stored counter lags behind
+        if let Some(max_id) = max_id {
+            self.counter.fetch_max(max_id + 1, Ordering::Relaxed);
+        }
 
-        Ok(storage)
+        let count = records.len() + spilled;
+        for (id, address_info) in records {
+            self.address_queue.push(id);
+            self.addresses.insert(id, address_info);
+        }
+        self.queue_size.store(count, Ordering::Relaxed);
+        if let Some(spill) = &self.spill {
+            let mut state = spill.lock();
+            state.from = spill_from;
+            state.spilled = spilled;
+            state.generation += 1;
+            state.taken.clear();
+        }
+
+        tracing::info!("Restored {} addresses from database to queue", count);
+        if spilled > 0 {
+            tracing::info!("Left {} of them in sled beyond the in-memory limit of {}", spilled, limit);
+        }
+        if rekeyed > 0 {
+            tracing::info!("Migrated {} address records to the widened key format", rekeyed);
+        }
+        if resealed > 0 {
+            tracing::info!("Re-encrypted {} stored private keys with the active key", resealed);
+        }
+        if converted > 0 {
+            tracing::info!("Converted {} JSON address records to the binary format", converted);
+        }
+
+        Ok(count)
     }
 
-    /// Store address - uses lock-free queue, no blocking
+    /// Store address - uses lock-free queue, no blocking.
+    /// Fails with `StoreError::QueueFull` when the queue is at capacity,
+    /// `StoreError::Duplicate` if the public key was ever enqueued before,
+    /// `StoreError::Busy` while the background writer is saturated and
+    /// `StoreError::Degraded` while degraded mode refuses stores.
     pub fn store_address(&self, address: PetAddress) -> Result<u64> {
+        self.store_address_at(address, chrono::Utc::now())
+    }
+
+    /// Like `store_address`, keeping the original creation time (used by imports)
+    pub fn store_address_at(&self, address: PetAddress, created_at: chrono::DateTime<chrono::Utc>) -> Result<u64> {
+        // The record and the counter; refused before anything changes
+        if let Some(persister) = &self.persister {
+            persister.admit_store(2)?;
+        }
+
+        // Reserve a slot first so concurrent stores cannot overshoot the capacity,
+        // and a refused store never marks its key as seen
+        if !self.reserve_slot() {
+            return Err(StoreError::QueueFull {
+                capacity: self.capacity.unwrap_or_default(),
+            }
+            .into());
+        }
+
+        if let Some(seen) = &self.seen {
+            match seen.insert(&address.public_key) {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.queue_size.fetch_sub(1, Ordering::Relaxed);
+                    return Err(StoreError::Duplicate {
+                        public_key: address.public_key,
+                    }
+                    .into());
+                }
+                Err(e) => {
+                    self.queue_size.fetch_sub(1, Ordering::Relaxed);
+                    return Err(e);
+                }
+            }
+        }
+
+        // With a memory limit, IDs are handed out and their writes queued under
+        // the window lock, so the pager never reads past an unqueued write
+        let mut window = self.spill.as_ref().map(|spill| (spill.limit, spill.lock()));
         let id = self.next_id();
         let address_info = PetAddressInfo {
             id,
             address,
-            created_at: chrono::Utc::now(),
+            created_at,
         };
 
-        // Push to lock-free queue - O(1), non-blocking
-        self.address_queue.push(address_info.clone());
-        self.queue_size.fetch_add(1, Ordering::Relaxed);
-
-        // Async persist to DB (fire-and-forget, no blocking)
-        if let Some(db) = &self.db {
-            let db = Arc::clone(db);
-            let info = address_info.clone();
-            tokio::spawn(async move {
-                if let Err(e) = Self::persist_address_async(db, info).await {
-                    tracing::warn!("Background persistence failed: {}", e);
+        // Encode before the record moves into the map, so the private key
+        // is not copied just to be persisted
+        let value = match &self.persister {
+            Some(_) => match Self::encode_record(self.key_ring.as_deref(), &address_info) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    self.queue_size.fetch_sub(1, Ordering::Relaxed);
+                    // Nothing was stored, so the key may be stored again
+                    if let Some(seen) = &self.seen {
+                        seen.remove(&address_info.address.public_key)?;
+                    }
+                    return Err(e);
                 }
-            });
+            },
+            None => None,
+        };
+
+        // Past a full window, or behind addresses already on disk, the record
+        // only goes to sled
+        let spilled = match &mut window {
+            Some((limit, state)) if state.from.is_some() || self.addresses.len() >= *limit => {
+                state.from.get_or_insert(id);
+                state.spilled += 1;
+                true
+            }
+            _ => false,
+        };
+        if !spilled {
+            // Record first so the ID is never popped before its data is visible
+            self.addresses.insert(id, address_info);
+            // Push to lock-free queue - O(1), non-blocking
+            self.address_queue.push(id);
+        }
+
+        // Queue the DB write for the background writer. The counter rides in
+        // the same batch so a crash never hands out an ID that is already persisted.
+        if let (Some(persister), Some(value)) = (&self.persister, value) {
+            let key = self.keys.address_key(id);
+            persister.write_all(vec![
+                WriteOp::Insert { key, value: value.into() },
+                WriteOp::RaiseCounter {
+                    key: self.keys.counter_key.clone(),
+                    value: id + 1,
+                },
+            ]);
+        }
+        drop(window);
+        if spilled {
+            self.wake_pager();
         }
 
         Ok(id)
     }
 
+    /// Count one more queued address, unless that would exceed the capacity
+    fn reserve_slot(&self) -> bool {
+        match self.capacity {
+            Some(capacity) => self
+                .queue_size
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |size| (size < capacity).then_some(size + 1))
+                .is_ok(),
+            None => {
+                self.queue_size.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+        }
+    }
+
     /// Get next address - lock-free pop, zero blocking, O(1)
     pub fn get_next_address(&self) -> Result<Option<PetAddressInfo>> {
-        match self.address_queue.pop() {
-            Some(address_info) => {
-                self.queue_size.fetch_sub(1, Ordering::Relaxed);
+        self.admit_writes(POP_WRITES)?;
+        Ok(self.pop_queued().map(|address_info| self.finish_pop(address_info)))
+    }
 
-                // Async remove from DB (fire-and-forget)
-                if let Some(db) = &self.db {
-                    let db = Arc::clone(db);
-                    let id = address_info.id;
-                    tokio::spawn(async move {
-                        if let Err(e) = Self::remove_address_async(db, id).await {
-                            tracing::warn!("Background removal failed: {}", e);
-                        }
-                    });
-                }
+    /// The next `count` addresses without consuming them, oldest first.
+    /// Records are ordered by ID, which matches pop order except for stores
+    /// that race each other. Only the in-memory window is looked at.
+    pub fn peek_addresses(&self, count: usize) -> Vec<PetAddressInfo> {
+        let mut ids: Vec<u64> = self.addresses.iter().map(|entry| *entry.key()).collect();
+        ids.sort_unstable();
 
-                Ok(Some(address_info))
-            }
-            None => Ok(None),
+        ids.into_iter()
+            // A concurrent pop may remove a record after the ID scan
+            .filter_map(|id| self.addresses.get(&id).map(|entry| entry.value().clone()))
+            .take(count)
+            .collect()
+    }
+
+    /// Creation times of the oldest and newest queued addresses. Of the
+    /// addresses held only in sled, just the newest is read.
+    pub fn created_range(&self) -> Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)> {
+        let newest_spilled = self
+            .spilled_range()
+            .and_then(|mut range| range.next_back())
+            .and_then(|entry| entry.ok())
+            .and_then(|(_, value)| self.read_record(&value).ok());
+        let resident = self.addresses.iter().map(|entry| entry.value().created_at);
+        resident.chain(newest_spilled.map(|address_info| address_info.created_at)).fold(None, |range, created_at| {
+            Some(match range {
+                Some((oldest, newest)) => (created_at.min(oldest), created_at.max(newest)),
+                None => (created_at, created_at),
+            })
+        })
+    }
+
+    /// Queued addresses by the letter before `matcher`'s suffix. Addresses
+    /// spilled to sled are read back to be counted once their write is applied.
+    pub fn ending_letter_counts(&self, matcher: &AddressMatcher) -> BTreeMap<char, usize> {
+        let mut counts = BTreeMap::new();
+        let resident = self.addresses.iter().map(|entry| matcher.ending_letter(&entry.value().address.address));
+        let spilled = self.spilled_records().map(|address_info| matcher.ending_letter(&address_info.address.address));
+        for letter in resident.chain(spilled).flatten() {
+            *counts.entry(letter).or_default() += 1;
         }
+        counts
     }
 
-    /// Count addresses - O(1) atomic read, zero blocking
-    pub fn count_addresses(&self) -> Result<usize> {
-        Ok(self.queue_size.load(Ordering::Relaxed))
+    /// The queued (not yet dispensed) address with this ID
+    pub fn queued_address(&self, id: u64) -> Option<PetAddressInfo> {
+        if let Some(entry) = self.addresses.get(&id) {
+            return Some(entry.value().clone());
+        }
+        let db = self.db.as_ref()?;
+        let spill = self.spill.as_ref()?.lock();
+        if spill.from.is_none_or(|from| id < from) || spill.taken.contains(&id) {
+            return None;
+        }
+        drop(spill);
+        let value = db.get(self.keys.address_key(id).as_bytes()).ok()??;
+        self.read_record(&value).ok()
     }
 
-    /// Clear all addresses - fast queue drain
-    pub fn clear_all_addresses(&self) -> Result<()> {
-        while self.address_queue.pop().is_some() {
-            self.queue_size.fetch_sub(1, Ordering::Relaxed);
+    /// The stored address, queued or leased, with this public key. Scans the
+    /// pool, so it is meant for one-off lookups.
+    pub fn find_by_public_key(&self, public_key: &str) -> Option<PetAddressInfo> {
+        let queued = self
+            .addresses
+            .iter()
+            .find(|entry| entry.value().address.public_key == public_key)
+            .map(|entry| entry.value().clone());
+        queued
+            .or_else(|| {
+                self.leases
+                    .iter()
+                    .find(|entry| entry.value().address_info.address.public_key == public_key)
+                    .map(|entry| entry.value().address_info.clone())
+            })
+            .or_else(|| self.spilled_records().find(|address_info| address_info.address.public_key == public_key))
+    }
+
+    /// Every stored address, queued or leased, in ID order (used by exports).
+    /// Addresses held only in sled are included once their write is applied.
+    pub fn records(&self) -> Vec<PetAddressInfo> {
+        let mut records: Vec<PetAddressInfo> = self
+            .addresses
+            .iter()
+            .map(|entry| entry.value().clone())
+            .chain(self.leases.iter().map(|entry| entry.value().address_info.clone()))
+            .chain(self.spilled_records())
+            .collect();
+        records.sort_unstable_by_key(|address_info| address_info.id);
+        records
+    }
+
+    /// Lowest ID queued only in sled, when the memory limit spilled addresses
+    fn spill_from(&self) -> Option<u64> {
+        self.spill.as_ref().and_then(|spill| spill.lock().from)
+    }
+
+    /// Sled records of the addresses queued only on disk, oldest first
+    fn spilled_range(&self) -> Option<sled::Iter> {
+        let from = self.spill_from()?;
+        let db = self.db.as_ref()?;
+        Some(db.range(self.keys.address_key(from)..=self.keys.address_key(u64::MAX)))
+    }
+
+    /// Addresses queued only in sled, oldest first, without those a filtered pop took
+    fn spilled_records(&self) -> impl Iterator<Item = PetAddressInfo> + '_ {
+        let taken = self.spill.as_ref().map(|spill| spill.lock().taken.clone()).unwrap_or_default();
+        self.spilled_range()
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|(_, value)| self.read_record(&value).ok())
+            .filter(move |address_info| !taken.contains(&address_info.id))
+    }
+
+    /// Ask the pager for more records once the in-memory window is half empty
+    fn wake_pager(&self) {
+        if let Some(spill) = &self.spill {
+            if self.addresses.len() <= spill.limit / 2 {
+                spill.wake.notify_one();
+            }
         }
+    }
 
-        // Clear DB in background
-        if let Some(db) = &self.db {
-            let db = Arc::clone(db);
-            tokio::spawn(async move {
-                if let Err(e) = Self::clear_db_async(db).await {
-                    tracing::warn!("Background clear failed: {}", e);
+    /// Move the oldest addresses held only in sled into the in-memory window,
+    /// up to the memory limit. Returns how many were read back.
+    pub async fn page_in(&self) -> Result<usize> {
+        let (Some(spill), Some(db)) = (&self.spill, &self.db) else {
+            return Ok(0);
+        };
+        let want = spill.limit.saturating_sub(self.addresses.len());
+        let (from, upto, pending, generation, taken) = {
+            let state = spill.lock();
+            match state.from {
+                Some(from) if want > 0 => (from, self.counter.load(Ordering::Relaxed), state.spilled, state.generation, state.taken.clone()),
+                _ => return Ok(0),
+            }
+        };
+
+        // Every ID below `upto` has its write queued; wait until sled has it
+        self.flush().await?;
+        let mut loaded = Vec::new();
+        let mut unreadable = 0;
+        let mut full = false;
+        for entry in db.range(self.keys.address_key(from)..self.keys.address_key(upto)) {
+            if loaded.len() == want {
+                full = true;
+                break;
+            }
+            let (key, value) = entry?;
+            match self.read_record(&value) {
+                // Already popped, and no longer counted as spilled
+                Ok(address_info) if taken.contains(&address_info.id) => {}
+                Ok(address_info) => loaded.push(address_info),
+                Err(e) => {
+                    // Left in place for `verify`
+                    tracing::error!("Skipping unreadable record {}: {:#}", String::from_utf8_lossy(&key), e);
+                    unreadable += 1;
                 }
-            });
+            }
         }
 
-        Ok(())
+        let mut state = spill.lock();
+        // A clear or restore rebuilt the window while sled was read
+        if state.generation != generation {
+            return Ok(0);
+        }
+        state.generation += 1;
+
+        // Past the last read record, unless the whole range was read. Every
+        // address spilled before the read was in the range; any not found
+        // there never reached sled.
+        let (next, consumed) = match (full, loaded.last()) {
+            (true, Some(last)) => (last.id + 1, loaded.len() + unreadable),
+            _ => (upto, pending),
+        };
+        let lost = consumed.saturating_sub(loaded.len());
+        if lost > 0 {
+            self.queue_size.fetch_sub(lost, Ordering::Relaxed);
+            tracing::warn!("{} spilled addresses could not be read back from sled", lost);
+        }
+        state.spilled = state.spilled.saturating_sub(consumed);
+        state.from = (state.spilled > 0).then_some(next);
+        let from = state.from;
+        state.taken.retain(|id| from.is_some_and(|from| *id >= from));
+
+        let count = loaded.len();
+        for address_info in loaded {
+            self.address_queue.push(address_info.id);
+            self.addresses.insert(address_info.id, address_info);
+        }
+        Ok(count)
     }
 
-    /// Get next ID - lock-free atomic increment
-    fn next_id(&self) -> u64 {
-        self.counter.fetch_add(1, Ordering::Relaxed)
+    /// Read spilled addresses back from sled whenever the window runs low
+    pub fn start_pager(&self) {
+        let Some(spill) = self.spill.clone() else {
+            return;
+        };
+        let storage = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                // Fill the window, then wait for pops to drain it again
+                loop {
+                    match storage.page_in().await {
+                        Ok(0) => break,
+                        Ok(_) => {}
+                        Err(e) => {
+                            tracing::warn!("Paging addresses in from sled failed: {}", e);
+                            break;
+                        }
+                    }
+                }
+                spill.wake.notified().await;
+            }
+        });
     }
 
-    /// Async persist to DB (non-blocking background operation)
-    async fn persist_address_async(db: Arc<RwLock<Db>>, address_info: PetAddressInfo) -> Result<()> {
-        let key = format!("address:{:010}", address_info.id);
-        let value = serde_json::to_vec(&address_info)
-            .context("Failed to serialize address info")?;
+    /// Pop up to `count` addresses in FIFO order. Each address goes to exactly
+    /// one caller even when batches run concurrently.
+    pub fn get_next_addresses(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        self.admit_writes(POP_WRITES * count.min(self.addresses.len()))?;
+        let mut popped = Vec::with_capacity(count.min(self.addresses.len()));
+        while popped.len() < count {
+            match self.pop_queued() {
+                Some(address_info) => popped.push(address_info),
+                None => break,
+            }
+        }
 
-        let db = db.write().await;
-        db.insert(key.as_bytes(), value)?;
-        // Note: Removed flush() - sled auto-flushes periodically, no blocking needed
+        if popped.is_empty() {
+            return Ok(popped);
+        }
 
-        Ok(())
-    }
+        self.queue_size.fetch_sub(popped.len(), Ordering::Relaxed);
+        self.demand.notify_one();
+        self.wake_pager();
 
-    /// Async remove from DB (non-blocking background operation)
-    async fn remove_address_async(db: Arc<RwLock<Db>>, id: u64) -> Result<()> {
-        let key = format!("address:{:010}", id);
-        let db = db.write().await;
-        db.remove(key.as_bytes())?;
+        if self.lease_ttl.is_some() {
+            for address_info in &popped {
+                self.lease(address_info);
+            }
+            return Ok(popped);
+        }
 
-        Ok(())
+        for address_info in &popped {
+            self.remove_persisted(address_info.id);
+        }
+
+        Ok(popped)
     }
 
-    /// Async clear DB (non-blocking background operation)
-    async fn clear_db_async(db: Arc<RwLock<Db>>) -> Result<()> {
-        let db = db.write().await;
-        let keys: Vec<_> = db.scan_prefix(b"address:")
-            .map(|result| result.unwrap().0)
-            .collect();
+    /// Pop the oldest address satisfying `predicate`, leaving the rest queued.
+    /// O(n) scan over queued records; the popped ID is skipped later by `pop_queued`.
+    /// Addresses spilled to sled are searched when none in memory match.
+    pub fn get_next_address_where<F>(&self, predicate: F) -> Result<Option<PetAddressInfo>>
+    where
+        F: Fn(&PetAddress) -> bool,
+    {
+        self.admit_writes(POP_WRITES)?;
+        loop {
+            let oldest = self
+                .addresses
+                .iter()
+                .filter(|entry| predicate(&entry.value().address))
+                .map(|entry| *entry.key())
+                .min();
+
+            let Some(id) = oldest else {
+                return Ok(self.pop_spilled_where(&predicate));
+            };
 
-        for key in keys {
-            db.remove(&key)?;
+            // Another request may have taken it between the scan and the removal
+            if let Some((_, address_info)) = self.addresses.remove(&id) {
+                return Ok(Some(self.finish_pop(address_info)));
+            }
         }
+    }
 
-        Ok(())
+    /// Pop the oldest address held only in sled that satisfies `predicate`.
+    /// Runs under the window lock and starts a new generation, so a page read
+    /// meanwhile is discarded instead of handing the address out twice.
+    fn pop_spilled_where<F>(&self, predicate: F) -> Option<PetAddressInfo>
+    where
+        F: Fn(&PetAddress) -> bool,
+    {
+        let (Some(spill), Some(db)) = (&self.spill, &self.db) else {
+            return None;
+        };
+        let mut state = spill.lock();
+        let from = state.from?;
+        let address_info = db
+            .range(self.keys.address_key(from)..=self.keys.address_key(u64::MAX))
+            .filter_map(|entry| entry.ok())
+            .filter_map(|(_, value)| self.read_record(&value).ok())
+            .find(|address_info| !state.taken.contains(&address_info.id) && predicate(&address_info.address))?;
+
+        state.taken.insert(address_info.id);
+        state.generation += 1;
+        state.spilled = state.spilled.saturating_sub(1);
+        if state.spilled == 0 {
+            state.from = None;
+            state.taken.clear();
+        }
+        // Leased or removed before the lock is released, so no reader misses both
+        Some(self.finish_pop(address_info))
     }
 
-    /// Start background task to periodically persist counter (every 10 seconds)
-    pub fn start_counter_persistence(&self) {
-        if let Some(db) = &self.db {
-            let db = Arc::clone(db);
-            let counter = Arc::clone(&self.counter);
+    /// Bookkeeping after a record was taken out of the map
+    fn finish_pop(&self, address_info: PetAddressInfo) -> PetAddressInfo {
+        self.queue_size.fetch_sub(1, Ordering::Relaxed);
+        self.demand.notify_one();
+        self.wake_pager();
 
-            tokio::spawn(async move {
-                loop {
-                    tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+        if self.lease_ttl.is_some() {
+            self.lease(&address_info);
+        } else {
+            self.remove_persisted(address_info.id);
+        }
 
-                    let current_counter = counter.load(Ordering::Relaxed);
-                    let db = db.write().await;
+        address_info
+    }
 
-                    if let Err(e) = db.insert(b"counter", &current_counter.to_be_bytes()) {
-                        tracing::warn!("Failed to persist counter: {}", e);
-                    } else {
-                        tracing::debug!("Counter persisted: {}", current_counter);
-                    }
-                }
+    /// Queue removal of the DB record
+    fn remove_persisted(&self, id: u64) {
+        if let Some(persister) = &self.persister {
+            persister.write(WriteOp::Remove {
+                key: self.keys.address_key(id),
             });
         }
     }
-}
\ No newline at end of file
+
+    fn lease(&self, address_info: &PetAddressInfo) {
+        let Some(ttl) = self.lease_ttl else {
+            return;
+        };
+        let expires_at = chrono::Duration::from_std(ttl)
+            .ok()
+            .and_then(|ttl| chrono::Utc::now().checked_add_signed(ttl))
+            .unwrap_or(chrono::DateTime::<chrono::Utc>::MAX_UTC);
+        self.leases.insert(
+            address_info.id,
+            Lease {
+                address_info: address_info.clone(),
+                expires_at,
+                dispensed: None,
+            },
+        );
+    }
+
+    /// Expiry of the live lease on `id`, if it is checked out
+    pub fn lease_expires_at(&self, id: u64) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.leases.get(&id).map(|lease| lease.expires_at)
+    }
+
+    /// Confirm a checked-out address was received and delete it for good.
+    /// Returns false when there is no live lease (never leased, already
+    /// acknowledged, or expired and requeued).
+    pub fn acknowledge(&self, id: u64) -> bool {
+        match self.leases.remove(&id) {
+            Some((_, lease)) => {
+                let Some(persister) = &self.persister else {
+                    return true;
+                };
+                // The removal and the archive update land together or not at all
+                let mut ops = vec![WriteOp::Remove { key: self.keys.address_key(id) }];
+                if let Some(mut record) = lease.dispensed {
+                    record.acknowledged_at = Some(chrono::Utc::now());
+                    ops.extend(self.archive_op(&record));
+                }
+                persister.write_all(ops);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Keep an audit entry for each popped address in this pool's
+    /// `dispensed:` key space. Entries of leased addresses pick up the lease
+    /// expiry and are updated again on acknowledgement.
+    pub fn record_dispensed(&self, records: Vec<DispensedRecord>) {
+        for mut record in records {
+            if let Some(mut lease) = self.leases.get_mut(&record.id) {
+                record.lease_expires_at = Some(lease.expires_at);
+                lease.dispensed = Some(record.clone());
+            }
+            self.archive(&record);
+        }
+    }
+
+    fn archive(&self, record: &DispensedRecord) {
+        if let (Some(persister), Some(op)) = (&self.persister, self.archive_op(record)) {
+            persister.write(op);
+        }
+    }
+
+    fn archive_op(&self, record: &DispensedRecord) -> Option<WriteOp> {
+        match serde_json::to_vec(record) {
+            Ok(value) => Some(WriteOp::Insert {
+                key: self.keys.dispensed_key(record.id),
+                value: value.into(),
+            }),
+            Err(e) => {
+                tracing::warn!("Failed to serialize archive entry for address {}: {}", record.id, e);
+                None
+            }
+        }
+    }
+
+    /// Archived dispense records matching `filter`, newest ID first. A record
+    /// reflects the latest checkout of its address.
+    pub fn dispensed(&self, filter: &DispensedFilter) -> Result<Vec<DispensedRecord>> {
+        let Some(db) = &self.db else {
+            return Ok(Vec::new());
+        };
+
+        let mut records = Vec::new();
+        for result in db.scan_prefix(self.keys.dispensed_prefix.as_bytes()).rev() {
+            if records.len() >= filter.limit {
+                break;
+            }
+            let (_key, value) = result?;
+            let record: DispensedRecord = serde_json::from_slice(&value)
+                .context("Failed to deserialize archive entry")?;
+            if filter.matches(&record) {
+                records.push(record);
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Return every expired lease to the back of the queue; returns how many were requeued
+    pub fn requeue_expired_leases(&self) -> usize {
+        let now = chrono::Utc::now();
+        let expired: Vec<u64> = self
+            .leases
+            .iter()
+            .filter(|entry| entry.value().expires_at <= now)
+            .map(|entry| *entry.key())
+            .collect();
+
+        let mut requeued = 0;
+        for id in expired {
+            // An acknowledge may win the race after the scan
+            if let Some((_, lease)) = self.leases.remove_if(&id, |_, lease| lease.expires_at <= now) {
+                self.addresses.insert(id, lease.address_info);
+                self.address_queue.push(id);
+                self.queue_size.fetch_add(1, Ordering::Relaxed);
+                requeued += 1;
+            }
+        }
+
+        if requeued > 0 {
+            tracing::info!("Requeued {} unacknowledged leased addresses", requeued);
+        }
+        requeued
+    }
+
+    /// Periodically requeue leases that were never acknowledged
+    pub fn start_lease_sweeper(&self, interval_seconds: u64) {
+        let storage = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(tokio::time::Duration::from_secs(interval_seconds.max(1))).await;
+                storage.requeue_expired_leases();
+            }
+        });
+    }
+
+    /// Remove queued addresses created before `now - max_age` from the queue and
+    /// sled, including those spilled to sled only. Leased addresses are left
+    /// alone; one that is requeued while stale is pruned on the next pass.
+    /// Their public keys stay marked as seen.
+    pub fn expire_older_than(&self, max_age: Duration) -> usize {
+        let Some(cutoff) = chrono::Duration::from_std(max_age)
+            .ok()
+            .and_then(|max_age| chrono::Utc::now().checked_sub_signed(max_age))
+        else {
+            return 0;
+        };
+        let stale: Vec<u64> = self
+            .addresses
+            .iter()
+            .filter(|entry| entry.value().created_at < cutoff)
+            .map(|entry| *entry.key())
+            .collect();
+
+        let mut expired = 0;
+        for id in stale {
+            // The ID stays in the FIFO and is skipped once popped
+            if self.addresses.remove(&id).is_some() {
+                self.queue_size.fetch_sub(1, Ordering::Relaxed);
+                self.remove_persisted(id);
+                expired += 1;
+            }
+        }
+        expired += self.expire_spilled(cutoff);
+
+        if expired > 0 {
+            let total = self.expired.fetch_add(expired as u64, Ordering::Relaxed) + expired as u64;
+            tracing::info!(
+                "Expired {} addresses older than {:?} ({} since startup)",
+                expired,
+                max_age,
+                total
+            );
+            // Refill what was pruned
+            self.demand.notify_one();
+            self.wake_pager();
+        }
+        expired
+    }
+
+    /// Drop records held only in sled that were created before `cutoff`.
+    /// Runs under the window lock and starts a new generation, so a page read
+    /// meanwhile is discarded instead of bringing them back.
+    fn expire_spilled(&self, cutoff: chrono::DateTime<chrono::Utc>) -> usize {
+        let (Some(spill), Some(db)) = (&self.spill, &self.db) else {
+            return 0;
+        };
+        let mut state = spill.lock();
+        let Some(from) = state.from else {
+            return 0;
+        };
+        let stale: Vec<u64> = db
+            .range(self.keys.address_key(from)..=self.keys.address_key(u64::MAX))
+            .filter_map(|entry| entry.ok())
+            .filter_map(|(_, value)| self.read_record(&value).ok())
+            .filter(|address_info| address_info.created_at < cutoff && !state.taken.contains(&address_info.id))
+            .map(|address_info| address_info.id)
+            .collect();
+        if stale.is_empty() {
+            return 0;
+        }
+
+        for id in &stale {
+            self.remove_persisted(*id);
+        }
+        state.generation += 1;
+        state.spilled = state.spilled.saturating_sub(stale.len());
+        if state.spilled == 0 {
+            state.from = None;
+            state.taken.clear();
+        }
+        self.queue_size.fetch_sub(stale.len(), Ordering::Relaxed);
+        stale.len()
+    }
+
+    /// Periodically prune addresses older than `max_age`
+    pub fn start_expiry_sweeper(&self, max_age: Duration, interval_seconds: u64) {
+        let storage = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                storage.expire_older_than(max_age);
+                tokio::time::sleep(tokio::time::Duration::from_secs(interval_seconds.max(1))).await;
+            }
+        });
+    }
+
+    /// Pop IDs until one still has a record (IDs whose record was already taken are skipped)
+    fn pop_queued(&self) -> Option<PetAddressInfo> {
+        while let Some(id) = self.address_queue.pop() {
+            if let Some((_, address_info)) = self.addresses.remove(&id) {
+                return Some(address_info);
+            }
+        }
+        None
+    }
+
+    /// Wait until addresses are consumed from this or any sibling pool.
+    /// A pop that happened while nobody was waiting completes the next wait immediately.
+    pub async fn wait_for_demand(&self) {
+        self.demand.notified().await;
+    }
+
+    /// Count addresses - O(1) atomic read, zero blocking
+    pub fn count_addresses(&self) -> Result<usize> {
+        Ok(self.queue_size.load(Ordering::Relaxed))
+    }
+
+    /// Clear all addresses - fast queue drain
+    pub fn clear_all_addresses(&self) -> Result<()> {
+        self.admit_writes(1)?;
+        while self.pop_queued().is_some() {
+            self.queue_size.fetch_sub(1, Ordering::Relaxed);
+        }
+        self.leases.clear();
+        self.demand.notify_one();
+
+        // Addresses held only in sled go with the prefix. Holding the window
+        // lock keeps stores that spill meanwhile behind the clear.
+        let mut window = self.spill.as_ref().map(|spill| spill.lock());
+        if let Some(state) = &mut window {
+            self.queue_size.fetch_sub(state.spilled, Ordering::Relaxed);
+            state.from = None;
+            state.spilled = 0;
+            state.generation += 1;
+            state.taken.clear();
+        }
+
+        // Clear DB in background, after any writes already queued
+        if let Some(persister) = &self.persister {
+            persister.write(WriteOp::ClearPrefix {
+                prefix: self.keys.address_prefix.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Wait until every queued DB write (from any pool) is applied and flushed to disk
+    pub async fn flush(&self) -> Result<()> {
+        match &self.persister {
+            Some(persister) => persister.flush().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Refuse an operation queuing `writes` DB writes while the background
+    /// writer is saturated, with `StoreError::Busy`
+    fn admit_writes(&self, writes: usize) -> Result<()> {
+        match &self.persister {
+            Some(persister) => Ok(persister.admit(writes)?),
+            None => Ok(()),
+        }
+    }
+
+    /// True while the background writer is too far behind to take stores and pops
+    pub fn is_busy(&self) -> bool {
+        self.persister.as_ref().is_some_and(Persister::is_busy)
+    }
+
+    /// True while sled keeps failing (the circuit breaker is open)
+    pub fn is_degraded(&self) -> bool {
+        self.persister.as_ref().is_some_and(Persister::is_degraded)
+    }
+
+    /// True while degraded mode turns new addresses away
+    pub fn refuses_stores(&self) -> bool {
+        self.persister.as_ref().is_some_and(Persister::refuses_stores)
+    }
+
+    /// Open the circuit breaker after `failure_threshold` consecutive sled
+    /// failures, optionally refusing stores while it is open. Applies to every
+    /// pool of this database.
+    pub fn with_circuit_breaker(self, failure_threshold: u32, refuse_stores: bool) -> Self {
+        if let Some(persister) = &self.persister {
+            persister.set_breaker(failure_threshold, refuse_stores);
+        }
+        self
+    }
+
+    /// Block until the background writer takes stores again. For bulk loads
+    /// off the async runtime, which should wait for the disk rather than fail.
+    pub fn wait_for_writer(&self) {
+        while self.is_busy() {
+            std::thread::sleep(BUSY_POLL_INTERVAL);
+        }
+    }
+
+    /// Background writer of this database, shared by all pools
+    pub(crate) fn persister(&self) -> Option<&Persister> {
+        self.persister.as_ref()
+    }
+
+    /// Size of the database files in bytes, if there is a database
+    pub fn size_on_disk(&self) -> Result<Option<u64>> {
+        match &self.db {
+            Some(db) => Ok(Some(db.size_on_disk()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Write a random value to a scratch key, flush it, read it back and
+    /// remove it again, so a read-only or failing disk shows up before
+    /// anything is served. Returns how long the round trip took.
+    pub async fn smoke_test(&self) -> Result<Option<Duration>> {
+        let Some(db) = &self.db else {
+            return Ok(None);
+        };
+
+        let started = Instant::now();
+        let value: [u8; 16] = rand::random();
+        db.insert(SMOKE_TEST_KEY, &value).context("Write failed")?;
+        db.flush_async().await.context("Flush failed")?;
+        let read = db.get(SMOKE_TEST_KEY).context("Read failed")?;
+        if read.as_deref() != Some(&value[..]) {
+            anyhow::bail!("Read back a different value than was written");
+        }
+        db.remove(SMOKE_TEST_KEY).context("Remove failed")?;
+        db.flush_async().await.context("Flush failed")?;
+        Ok(Some(started.elapsed()))
+    }
+
+    /// Persist the latest grinder benchmark (shared by all pools)
+    pub async fn store_benchmark(&self, report: &BenchmarkReport) -> Result<()> {
+        if let Some(db) = &self.db {
+            let value = serde_json::to_vec(report).context("Failed to serialize benchmark report")?;
+            db.insert(BENCHMARK_KEY, value)?;
+            db.flush_async().await?;
+        }
+        Ok(())
+    }
+
+    /// Latest stored grinder benchmark, if one was ever run
+    pub fn load_benchmark(&self) -> Result<Option<BenchmarkReport>> {
+        let Some(db) = &self.db else {
+            return Ok(None);
+        };
+
+        match db.get(BENCHMARK_KEY)? {
+            Some(value) => Ok(Some(
+                serde_json::from_slice(&value).context("Failed to deserialize benchmark report")?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Names of the named pools that ever stored an address in this database.
+    /// The default pool is not listed.
+    pub fn pool_names(&self) -> Result<Vec<String>> {
+        let Some(db) = &self.db else {
+            return Ok(Vec::new());
+        };
+
+        let mut names = Vec::new();
+        for result in db.scan_prefix(b"pool:") {
+            let (key, _) = result?;
+            // Every pool's counter is written along with its first address
+            if let Some(name) = key.strip_prefix(b"pool:").and_then(|rest| rest.strip_suffix(b":counter")) {
+                names.push(String::from_utf8_lossy(name).into_owned());
+            }
+        }
+        Ok(names)
+    }
+
+    /// Per-tenant usage counters kept in this database
+    pub fn usage_store(&self, quotas: HashMap<String, Quota>) -> UsageStore {
+        UsageStore::new(self.db.clone(), quotas)
+    }
+
+    /// Vault for the private keys of redacted pops, kept in this database and
+    /// sealed with the same key ring as the addresses
+    pub fn key_vault(&self) -> KeyVault {
+        KeyVault::new(self.db.clone(), self.key_ring.clone())
+    }
+
+    /// Recipient keys registered per tenant, kept in this database
+    pub fn recipient_registry(&self) -> RecipientRegistry {
+        RecipientRegistry::new(self.db.clone())
+    }
+
+    /// Runtime setting overrides, kept in this database
+    pub fn override_store(&self) -> OverrideStore {
+        OverrideStore::new(self.db.clone())
+    }
+
+    /// Webhook subscriptions, kept in this database
+    pub fn subscription_store(&self) -> SubscriptionStore {
+        SubscriptionStore::new(self.db.clone())
+    }
+
+    /// Audit log, in its own tree of this database
+    pub fn audit_log(&self) -> Result<AuditLog> {
+        let tree = match &self.db {
+            Some(db) => Some(db.open_tree(AUDIT_TREE)?),
+            None => None,
+        };
+        AuditLog::new(tree)
+    }
+
+    /// Every public key ever enqueued in this database, dispensed ones included
+    pub fn seen_keys(&self) -> Result<Vec<String>> {
+        match &self.seen {
+            Some(seen) => seen.public_keys(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Compare the atomic size, the real queue length and the sled record count.
+    /// Logs a warning with the delta when they disagree.
+    pub async fn diagnostics(&self) -> Result<StorageDiagnostics> {
+        let db_record_count = match &self.db {
+            Some(db) => db.scan_prefix(self.keys.address_prefix.as_bytes()).count(),
+            None => 0,
+        };
+
+        let spilled = self.spill.as_ref().map_or(0, |spill| spill.lock().spilled);
+        let diagnostics = StorageDiagnostics {
+            reported_size: self.queue_size.load(Ordering::Relaxed),
+            actual_queue_len: self.addresses.len() + spilled,
+            spilled,
+            db_record_count,
+            leased: self.leases.len(),
+            counter: self.counter.load(Ordering::Relaxed),
+            pending_writes: self.persister.as_ref().map_or(0, Persister::pending),
+            persist_lag_ms: self.persister.as_ref().map_or(0, Persister::lag_ms),
+            failed_writes: self.persister.as_ref().map_or(0, Persister::failed),
+            retrying_writes: self.persister.as_ref().map_or(0, Persister::retrying),
+            dead_letters: self.persister.as_ref().map_or(0, Persister::dead_letters),
+            refused_operations: self.persister.as_ref().map_or(0, Persister::refused),
+            degraded: self.is_degraded(),
+            consecutive_write_failures: self.persister.as_ref().map_or(0, Persister::consecutive_failures),
+            expired: self.expired.load(Ordering::Relaxed),
+        };
+
+        if diagnostics.size_delta() != 0 {
+            tracing::warn!(
+                "Queue size counter diverged: reported {}, actual {} (delta {})",
+                diagnostics.reported_size,
+                diagnostics.actual_queue_len,
+                diagnostics.size_delta()
+            );
+        }
+        if diagnostics.db_delta() != 0 {
+            tracing::warn!(
+                "Database record count diverged: db {}, queue {}, leased {} (delta {})",
+                diagnostics.db_record_count,
+                diagnostics.actual_queue_len,
+                diagnostics.leased,
+                diagnostics.db_delta()
+            );
+        }
+
+        Ok(diagnostics)
+    }
+
+    /// Compare queued IDs with this pool's address records in sled and repair drift:
+    /// re-persist queued addresses missing from the DB and delete records for
+    /// addresses that are neither queued nor leased
+    pub async fn reconcile(&self) -> Result<ReconcileReport> {
+        let mut report = ReconcileReport::default();
+        let (Some(db), Some(persister)) = (&self.db, &self.persister) else {
+            return Ok(report);
+        };
+
+        // Compare against a database holding every write queued so far. Repairs
+        // go through the writer, so they apply in order with pops and stores.
+        // Only addresses queued before the flush can be missing; later stores
+        // have their own insert still in flight.
+        let queued: Vec<u64> = self.addresses.iter().map(|entry| *entry.key()).collect();
+        persister.flush().await?;
+        let mut persisted_ids = HashSet::new();
+        // Records from here up are queued on disk only
+        let spill_from = self.spill_from().unwrap_or(u64::MAX);
+
+        for result in db.scan_prefix(self.keys.address_prefix.as_bytes()) {
+            let (key, _value) = result?;
+            let id = match self.keys.parse_address_key(&key) {
+                Some(id) => id,
+                None => {
+                    tracing::warn!("Skipping malformed address key: {:?}", key);
+                    continue;
+                }
+            };
+
+            if id >= spill_from || self.addresses.contains_key(&id) || self.leases.contains_key(&id) {
+                persisted_ids.insert(id);
+            } else {
+                persister.write(WriteOp::Remove { key: self.keys.address_key(id) });
+                report.orphans_removed += 1;
+            }
+        }
+
+        for id in queued.into_iter().filter(|id| !persisted_ids.contains(id)) {
+            // Holding the entry keeps a pop from removing it until the insert is
+            // queued, so the pop's remove always lands after it. Popped ones are skipped.
+            let Some(entry) = self.addresses.get(&id) else {
+                continue;
+            };
+            let value = Self::encode_record(self.key_ring.as_deref(), entry.value())?;
+            persister.write(WriteOp::Insert {
+                key: self.keys.address_key(id),
+                value: value.into(),
+            });
+            drop(entry);
+            report.repersisted += 1;
+        }
+        persister.flush().await?;
+
+        if report.repersisted > 0 || report.orphans_removed > 0 {
+            tracing::warn!(
+                "Reconciliation repaired drift: {} re-persisted, {} orphaned records removed",
+                report.repersisted,
+                report.orphans_removed
+            );
+        } else {
+            tracing::debug!("Reconciliation found no drift");
+        }
+
+        Ok(report)
+    }
+
+    /// Compare the queue against every sled record of this pool and repair
+    /// what can be repaired: orphaned records are deleted, missing ones are
+    /// written again and duplicate public keys are dropped (the oldest copy
+    /// wins; `seen` carries keys across pools). Records that cannot be read,
+    /// or whose private key does not derive their address, are only reported,
+    /// or moved under `quarantine:` when `quarantine` is set. Addresses
+    /// outside `matcher` are reported.
+    pub async fn verify(
+        &self,
+        seen: &mut HashSet<String>,
+        matcher: Option<&AddressMatcher>,
+        quarantine: bool,
+    ) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+        let Some(db) = &self.db else {
+            return Ok(report);
+        };
+
+        // Runs before the pool serves; start from every write queued so far
+        self.flush().await?;
+        let mut persisted_ids = HashSet::new();
+        let spill_from = self.spill_from().unwrap_or(u64::MAX);
+
+        let entries = db
+            .scan_prefix(self.keys.address_prefix.as_bytes())
+            .collect::<Result<Vec<_>, _>>()?;
+        for (key, value) in entries {
+            report.checked += 1;
+            let record = self.read_record(&value).and_then(|address_info| {
+                match self.keys.parse_address_key(&key) {
+                    Some(id) if id == address_info.id => Ok(address_info),
+                    _ => anyhow::bail!("key does not match record ID {}", address_info.id),
+                }
+            });
+            let address_info = match record {
+                Ok(address_info) => address_info,
+                Err(e) => {
+                    report.corrupt.push(set_aside(db, &key, &value, &e, quarantine, &mut report.quarantined)?);
+                    continue;
+                }
+            };
+            if let Err(e) = address_info.address.check_keypair() {
+                report.mismatched.push(set_aside(db, &key, &value, &e, quarantine, &mut report.quarantined)?);
+                continue;
+            }
+            if matcher.is_some_and(|matcher| !matcher.is_match(&address_info.address.address)) {
+                report.off_pattern.push(address_info.address.address.clone());
+            }
+
+            let id = address_info.id;
+            if id < spill_from && !self.addresses.contains_key(&id) && !self.leases.contains_key(&id) {
+                db.remove(&key)?;
+                report.orphans_removed += 1;
+            } else if !seen.insert(address_info.address.public_key) {
+                // The ID left in the queue is skipped on pop once its record is gone
+                db.remove(&key)?;
+                if self.addresses.remove(&id).is_some() {
+                    self.queue_size.fetch_sub(1, Ordering::Relaxed);
+                } else if let Some(spill) = self.spill.as_ref().filter(|_| id >= spill_from) {
+                    let mut state = spill.lock();
+                    state.spilled = state.spilled.saturating_sub(1);
+                    self.queue_size.fetch_sub(1, Ordering::Relaxed);
+                }
+                self.leases.remove(&id);
+                report.duplicates_removed += 1;
+            } else {
+                persisted_ids.insert(id);
+            }
+        }
+
+        let missing: Vec<PetAddressInfo> = self
+            .addresses
+            .iter()
+            .filter(|entry| !persisted_ids.contains(entry.key()))
+            .map(|entry| entry.value().clone())
+            .collect();
+        for address_info in missing {
+            let value = Self::encode_record(self.key_ring.as_deref(), &address_info)?;
+            db.insert(self.keys.address_key(address_info.id).as_bytes(), value)?;
+            report.repersisted += 1;
+        }
+        db.flush_async().await?;
+
+        Ok(report)
+    }
+
+    /// Check every sled record of this pool without repairing anything: it
+    /// must decode, its private key must derive its address, and the address
+    /// should match `matcher`. Safe while the pool is serving.
+    pub async fn check_keys(&self, matcher: &AddressMatcher) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+        let Some(db) = &self.db else {
+            return Ok(report);
+        };
+
+        for entry in db.scan_prefix(self.keys.address_prefix.as_bytes()) {
+            let (key, value) = entry?;
+            report.checked += 1;
+            let name = String::from_utf8_lossy(&key).into_owned();
+            match self.read_record(&value) {
+                Ok(address_info) => match address_info.address.check_keypair() {
+                    Ok(()) if matcher.is_match(&address_info.address.address) => {}
+                    Ok(()) => report.off_pattern.push(address_info.address.address),
+                    Err(e) => {
+                        tracing::error!("Corrupt record {}: {:#}", name, e);
+                        report.mismatched.push(name);
+                    }
+                },
+                Err(e) => {
+                    tracing::error!("Corrupt record {}: {:#}", name, e);
+                    report.corrupt.push(name);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Decode a stored record and decrypt its private key
+    fn read_record(&self, value: &[u8]) -> Result<PetAddressInfo> {
+        let address_info = codec::decode(value)?;
+        match self.key_ring.as_deref() {
+            Some(key_ring) => key_ring.open_record(address_info),
+            None if encryption::is_sealed(&address_info.address.private_key) => {
+                anyhow::bail!("encrypted private key but encryption is not configured")
+            }
+            None => Ok(address_info),
+        }
+    }
+
+    /// Start background task that periodically reconciles the queue with sled
+    pub fn start_reconciliation(&self, interval_seconds: u64) {
+        let storage = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(tokio::time::Duration::from_secs(interval_seconds.max(1))).await;
+
+                if let Err(e) = storage.reconcile().await {
+                    tracing::warn!("Reconciliation failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Get next ID - lock-free atomic increment
+    fn next_id(&self) -> u64 {
+        self.counter.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Serialize a record for sled, sealing the private key when a key ring is set
+    fn encode_record(key_ring: Option<&KeyRing>, address_info: &PetAddressInfo) -> Result<Vec<u8>> {
+        match key_ring {
+            Some(key_ring) => codec::encode(&key_ring.seal_record(address_info)?),
+            None => codec::encode(address_info),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::test_support::test_address;
+    use crate::pet::Requester;
+
+    #[tokio::test]
+    async fn test_diagnostics_consistent() {
+        let storage = PetStorage::temporary().unwrap();
+        storage.store_address(test_address("aPet")).unwrap();
+        storage.store_address(test_address("bPet")).unwrap();
+        storage.flush().await.unwrap();
+
+        let diagnostics = storage.diagnostics().await.unwrap();
+        assert_eq!(diagnostics.reported_size, 2);
+        assert_eq!(diagnostics.actual_queue_len, 2);
+        assert_eq!(diagnostics.db_record_count, 2);
+        assert_eq!(diagnostics.counter, 2);
+        assert!(diagnostics.is_consistent());
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_removes_orphaned_record() {
+        let storage = PetStorage::temporary().unwrap();
+        storage.store_address(test_address("aPet")).unwrap();
+        storage.flush().await.unwrap();
+
+        // Seed a DB record for an address that is not queued
+        let orphan = PetAddressInfo {
+            id: 99,
+            address: test_address("zPet"),
+            created_at: chrono::Utc::now(),
+        };
+        let db = storage.db.as_ref().unwrap();
+        db.insert(
+            storage.keys.address_key(orphan.id).as_bytes(),
+            serde_json::to_vec(&orphan).unwrap(),
+        )
+        .unwrap();
+
+        let report = storage.reconcile().await.unwrap();
+        assert_eq!(report.orphans_removed, 1);
+        assert_eq!(report.repersisted, 0);
+
+        assert!(db.get(storage.keys.address_key(99).as_bytes()).unwrap().is_none());
+        assert_eq!(db.scan_prefix(b"address:").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_repersists_missing_record() {
+        let storage = PetStorage::temporary().unwrap();
+        let id = storage.store_address(test_address("aPet")).unwrap();
+        storage.flush().await.unwrap();
+
+        // Simulate a failed background insert
+        let db = storage.db.as_ref().unwrap();
+        db.remove(storage.keys.address_key(id).as_bytes()).unwrap();
+
+        let report = storage.reconcile().await.unwrap();
+        assert_eq!(report.repersisted, 1);
+        assert_eq!(report.orphans_removed, 0);
+
+        let diagnostics = storage.diagnostics().await.unwrap();
+        assert!(diagnostics.is_consistent());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_reconcile_does_not_resurrect_concurrent_pops() {
+        const COUNT: u64 = 2000;
+        let storage = PetStorage::temporary().unwrap();
+        for i in 0..COUNT {
+            storage.store_address(test_address(&format!("{}Pet", i))).unwrap();
+        }
+        storage.flush().await.unwrap();
+
+        // Every record is missing, so reconcile re-persists while the pops run
+        let db = storage.db.clone().unwrap();
+        for id in 0..COUNT {
+            db.remove(storage.keys.address_key(id).as_bytes()).unwrap();
+        }
+        let popper = {
+            let storage = storage.clone();
+            std::thread::spawn(move || {
+                let mut popped = Vec::new();
+                loop {
+                    match storage.get_next_address() {
+                        Ok(Some(address_info)) => popped.push(address_info.id),
+                        Ok(None) => return popped,
+                        // The writer is behind; let it catch up
+                        Err(_) => {}
+                    }
+                    std::thread::sleep(Duration::from_micros(20));
+                }
+            })
+        };
+        storage.reconcile().await.unwrap();
+        let popped = popper.join().unwrap();
+        storage.flush().await.unwrap();
+
+        assert_eq!(popped.len(), COUNT as usize);
+        let resurrected: Vec<u64> = popped
+            .into_iter()
+            .filter(|id| db.get(storage.keys.address_key(*id).as_bytes()).unwrap().is_some())
+            .collect();
+        assert!(resurrected.is_empty(), "popped but still in sled: {:?}", resurrected);
+    }
+
+    #[tokio::test]
+    async fn test_named_pools_are_isolated() {
+        let storage = PetStorage::temporary().unwrap();
+        let cats = storage.open_pool("Cat").await.unwrap();
+
+        storage.store_address(test_address("aPet")).unwrap();
+        cats.store_address(test_address("aCat")).unwrap();
+        cats.store_address(test_address("bCat")).unwrap();
+        storage.flush().await.unwrap();
+
+        assert_eq!(storage.count_addresses().unwrap(), 1);
+        assert_eq!(cats.count_addresses().unwrap(), 2);
+        assert!(storage.diagnostics().await.unwrap().is_consistent());
+        assert!(cats.diagnostics().await.unwrap().is_consistent());
+
+        // Reopening the pool restores only its own records
+        let reopened = storage.open_pool("Cat").await.unwrap();
+        assert_eq!(reopened.count_addresses().unwrap(), 2);
+        let popped = reopened.get_next_address().unwrap().unwrap();
+        assert!(popped.address.address.ends_with("Cat"));
+
+        // Reconciling the default pool must not treat pool records as orphans
+        let report = storage.reconcile().await.unwrap();
+        assert_eq!(report.orphans_removed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_public_keys_are_rejected() {
+        let storage = PetStorage::temporary().unwrap();
+        let id = storage.store_address(test_address("aPet")).unwrap();
+
+        let error = storage.store_address(test_address("aPet")).unwrap_err();
+        assert!(matches!(error.downcast_ref::<StoreError>(), Some(StoreError::Duplicate { .. })));
+        assert_eq!(storage.count_addresses().unwrap(), 1);
+
+        // Still refused after it was dispensed, and in sibling pools
+        assert_eq!(storage.get_next_address().unwrap().unwrap().id, id);
+        assert!(storage.store_address(test_address("aPet")).is_err());
+        let pool = storage.open_pool("Cat").await.unwrap();
+        assert!(pool.store_address(test_address("aPet")).is_err());
+        assert!(pool.store_address(test_address("aCat")).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stale_addresses_are_expired() {
+        let storage = PetStorage::temporary().unwrap();
+        storage.store_address(test_address("aPet")).unwrap();
+        storage.store_address(test_address("bPet")).unwrap();
+
+        assert_eq!(storage.expire_older_than(Duration::from_secs(3600)), 0);
+        assert_eq!(storage.expire_older_than(Duration::ZERO), 2);
+        assert_eq!(storage.count_addresses().unwrap(), 0);
+        assert!(storage.get_next_address().unwrap().is_none());
+
+        storage.flush().await.unwrap();
+        let diagnostics = storage.diagnostics().await.unwrap();
+        assert_eq!(diagnostics.db_record_count, 0);
+        assert_eq!(diagnostics.expired, 2);
+        assert!(diagnostics.is_consistent());
+    }
+
+    #[tokio::test]
+    async fn test_spilled_addresses_are_expired() {
+        let storage = PetStorage::temporary().unwrap().with_memory_limit(1);
+        let last_year = chrono::Utc::now() - chrono::Duration::days(365);
+        storage.store_address(test_address("aPet")).unwrap();
+        storage.store_address_at(test_address("bPet"), last_year).unwrap();
+        let fresh = storage.store_address(test_address("cPet")).unwrap();
+        storage.flush().await.unwrap();
+        assert_eq!(storage.diagnostics().await.unwrap().spilled, 2);
+
+        // Only the old record on disk goes; the fresh one is still paged in
+        assert_eq!(storage.expire_older_than(Duration::from_secs(86_400)), 1);
+        assert_eq!(storage.count_addresses().unwrap(), 2);
+        storage.flush().await.unwrap();
+        assert_eq!(storage.diagnostics().await.unwrap().spilled, 1);
+        assert_eq!(storage.get_next_address().unwrap().unwrap().address.address, "TestAddressaPet");
+        assert_eq!(storage.page_in().await.unwrap(), 1);
+        assert_eq!(storage.get_next_address().unwrap().unwrap().id, fresh);
+        assert_eq!(storage.spill_from(), None);
+
+        storage.flush().await.unwrap();
+        let diagnostics = storage.diagnostics().await.unwrap();
+        assert_eq!(diagnostics.expired, 1);
+        assert!(diagnostics.is_consistent());
+    }
+
+    #[tokio::test]
+    async fn test_imported_addresses_keep_their_age() {
+        let storage = PetStorage::temporary().unwrap().with_lease_ttl(Duration::from_secs(60));
+        let last_year = chrono::Utc::now() - chrono::Duration::days(365);
+        let old = storage.store_address_at(test_address("aPet"), last_year).unwrap();
+        let queued = storage.store_address(test_address("bPet")).unwrap();
+        let fresh = storage.store_address(test_address("cPet")).unwrap();
+        assert_eq!(storage.get_next_address().unwrap().unwrap().id, old);
+
+        // Exports see queued and leased addresses alike
+        let ids: Vec<u64> = storage.records().iter().map(|info| info.id).collect();
+        assert_eq!(ids, [old, queued, fresh]);
+        assert_eq!(storage.records()[0].created_at, last_year);
+
+        // Only the leased one is old; it is left alone until it returns
+        assert_eq!(storage.expire_older_than(Duration::from_secs(86_400)), 0);
+    }
+
+    #[tokio::test]
+    async fn test_queue_capacity_rejects_when_full() {
+        let storage = PetStorage::temporary().unwrap().with_capacity(2);
+        storage.store_address(test_address("aPet")).unwrap();
+        storage.store_address(test_address("bPet")).unwrap();
+
+        let error = storage.store_address(test_address("cPet")).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<StoreError>(),
+            Some(StoreError::QueueFull { capacity: 2 })
+        ));
+        assert_eq!(storage.count_addresses().unwrap(), 2);
+
+        // A refused key was never marked as seen, so it fits once a slot frees up
+        storage.get_next_address().unwrap().unwrap();
+        storage.store_address(test_address("cPet")).unwrap();
+        assert_eq!(storage.count_addresses().unwrap(), 2);
+
+        // Duplicates do not leak reserved slots
+        assert!(storage.store_address(test_address("cPet")).is_err());
+        assert_eq!(storage.count_addresses().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_filtered_pop_leaves_other_addresses_queued() {
+        let storage = PetStorage::temporary().unwrap();
+        for suffix in ["aPet", "1kPet", "bPet", "2kPet"] {
+            storage.store_address(test_address(suffix)).unwrap();
+        }
+
+        let ends_with_k = |address: &PetAddress| address.address.ends_with("kPet");
+        let first = storage.get_next_address_where(ends_with_k).unwrap().unwrap();
+        let second = storage.get_next_address_where(ends_with_k).unwrap().unwrap();
+        assert!(first.id < second.id);
+        assert!(storage.get_next_address_where(ends_with_k).unwrap().is_none());
+        assert_eq!(storage.count_addresses().unwrap(), 2);
+
+        // Plain pops skip the taken IDs and keep FIFO order
+        assert!(storage.get_next_address().unwrap().unwrap().address.address.ends_with("aPet"));
+        assert!(storage.get_next_address().unwrap().unwrap().address.address.ends_with("bPet"));
+        assert!(storage.get_next_address().unwrap().is_none());
+        assert_eq!(storage.count_addresses().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_ending_letter_counts() {
+        let storage = PetStorage::temporary().unwrap();
+        for suffix in ["1aPet", "2kPet", "3kPet", "4ZPet"] {
+            storage.store_address(test_address(suffix)).unwrap();
+        }
+        storage.get_next_address().unwrap();
+
+        let matcher = AddressMatcher::suffix("Pet", true).unwrap();
+        let counts = storage.ending_letter_counts(&matcher);
+        assert_eq!(counts, BTreeMap::from([('k', 2), ('Z', 1)]));
+
+        let stats = crate::models::PoolSuffixStats::new("default", "Pet", counts);
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.letters.len(), 26);
+        assert_eq!(stats.letters[0].variant, "ZPet");
+        assert!(!stats.scarcest.contains(&"k".to_string()) && stats.scarcest.contains(&"a".to_string()));
+
+        // Addresses past the memory limit are counted from sled
+        let limited = PetStorage::temporary().unwrap().with_memory_limit(1);
+        for suffix in ["1aPet", "2kPet", "3kPet"] {
+            limited.store_address(test_address(suffix)).unwrap();
+        }
+        limited.flush().await.unwrap();
+        assert_eq!(limited.ending_letter_counts(&matcher), BTreeMap::from([('a', 1), ('k', 2)]));
+        limited.get_next_address_where(|address| address.address.ends_with("3kPet")).unwrap();
+        assert_eq!(limited.ending_letter_counts(&matcher), BTreeMap::from([('a', 1), ('k', 1)]));
+    }
+
+    #[tokio::test]
+    async fn test_batch_pop() {
+        let storage = PetStorage::temporary().unwrap();
+        for suffix in ["aPet", "bPet", "cPet"] {
+            storage.store_address(test_address(suffix)).unwrap();
+        }
+        storage.flush().await.unwrap();
+
+        let batch = storage.get_next_addresses(2).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert!(batch[0].id < batch[1].id);
+        assert!(batch[0].address.address.ends_with("aPet"));
+        assert_eq!(storage.count_addresses().unwrap(), 1);
+
+        // Asking for more than is queued returns what is left
+        assert_eq!(storage.get_next_addresses(5).unwrap().len(), 1);
+        assert!(storage.get_next_addresses(5).unwrap().is_empty());
+        assert_eq!(storage.count_addresses().unwrap(), 0);
+
+        storage.flush().await.unwrap();
+        let diagnostics = storage.diagnostics().await.unwrap();
+        assert!(diagnostics.is_consistent());
+    }
+
+    #[tokio::test]
+    async fn test_peek_does_not_consume() {
+        let storage = PetStorage::temporary().unwrap();
+        for suffix in ["aPet", "bPet", "cPet"] {
+            storage.store_address(test_address(suffix)).unwrap();
+        }
+
+        let peeked = storage.peek_addresses(2);
+        assert_eq!(peeked.len(), 2);
+        assert_eq!(storage.count_addresses().unwrap(), 3);
+        assert_eq!(storage.peek_addresses(10).len(), 3);
+
+        // Peek shows exactly what the next pops return
+        for expected in peeked {
+            assert_eq!(storage.get_next_address().unwrap().unwrap().id, expected.id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_roundtrip() {
+        let storage = PetStorage::temporary().unwrap();
+        assert!(storage.load_benchmark().unwrap().is_none());
+
+        let report = BenchmarkReport {
+            threads: 2,
+            elapsed_seconds: 1.5,
+            keys_per_thread: vec![100, 200],
+            measured_at: chrono::Utc::now(),
+        };
+        storage.store_benchmark(&report).await.unwrap();
+
+        // Visible from sibling pools, which share the database
+        let pool = storage.open_pool("Cat").await.unwrap();
+        let loaded = pool.load_benchmark().unwrap().unwrap();
+        assert_eq!(loaded.keys_per_thread, vec![100, 200]);
+        assert_eq!(loaded.keys_per_second(), 200.0);
+    }
+
+    #[tokio::test]
+    async fn test_leased_address_is_kept_until_acknowledged() {
+        let storage = PetStorage::temporary().unwrap().with_lease_ttl(Duration::from_secs(60));
+        let id = storage.store_address(test_address("aPet")).unwrap();
+        storage.flush().await.unwrap();
+
+        let leased = storage.get_next_address().unwrap().unwrap();
+        assert_eq!(leased.id, id);
+        assert!(storage.lease_expires_at(id).is_some());
+        assert_eq!(storage.count_addresses().unwrap(), 0);
+        storage.flush().await.unwrap();
+
+        // Still persisted while checked out, and not counted as drift
+        let diagnostics = storage.diagnostics().await.unwrap();
+        assert_eq!(diagnostics.db_record_count, 1);
+        assert_eq!(diagnostics.leased, 1);
+        assert!(diagnostics.is_consistent());
+        assert_eq!(storage.reconcile().await.unwrap().orphans_removed, 0);
+
+        assert!(storage.acknowledge(id));
+        assert!(!storage.acknowledge(id));
+        storage.flush().await.unwrap();
+        assert_eq!(storage.diagnostics().await.unwrap().db_record_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_expired_lease_is_requeued() {
+        let storage = PetStorage::temporary().unwrap().with_lease_ttl(Duration::ZERO);
+        let id = storage.store_address(test_address("aPet")).unwrap();
+
+        assert_eq!(storage.get_next_addresses(5).unwrap().len(), 1);
+        assert_eq!(storage.count_addresses().unwrap(), 0);
+
+        assert_eq!(storage.requeue_expired_leases(), 1);
+        assert_eq!(storage.count_addresses().unwrap(), 1);
+        assert!(!storage.acknowledge(id));
+
+        let again = storage.get_next_address().unwrap().unwrap();
+        assert_eq!(again.id, id);
+    }
+
+    fn key_ring(active: &str) -> Arc<KeyRing> {
+        let spec = "old:0000000000000000000000000000000000000000000000000000000000000001,\
+                    new:00000000000000000000000000000000000000000000000000000000000000ff";
+        Arc::new(KeyRing::parse(active, spec).unwrap())
+    }
+
+    fn reopen(db: &Db, key_ring: Option<Arc<KeyRing>>) -> Result<PetStorage> {
+        PetStorage::from_db(db.clone(), None, KeySpace::default_pool(), Arc::new(Notify::new()), key_ring)
+    }
+
+    fn stored_private_key(db: &Db, id: u64) -> String {
+        let value = db.get(KeySpace::default_pool().address_key(id)).unwrap().unwrap();
+        codec::decode(&value).unwrap().address.private_key.expose().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_private_keys_are_encrypted_at_rest() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let storage = reopen(&db, Some(key_ring("old"))).unwrap();
+        let id = storage.store_address(test_address("aPet")).unwrap();
+        storage.flush().await.unwrap();
+
+        let stored = stored_private_key(&db, id);
+        assert!(encryption::is_sealed(&stored));
+        assert_eq!(encryption::sealed_key_id(&stored), Some("old"));
+
+        // Restored in plaintext, and rewritten with the new key after rotation
+        let rotated = reopen(&db, Some(key_ring("new"))).unwrap();
+        assert_eq!(encryption::sealed_key_id(&stored_private_key(&db, id)), Some("new"));
+
+        // Sealed records cannot be loaded without the keys
+        assert!(reopen(&db, None).is_err());
+
+        assert_eq!(rotated.get_next_address().unwrap().unwrap().address.private_key, "secret-aPet");
+    }
+
+    #[tokio::test]
+    async fn test_ids_are_not_reused_after_restart() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let storage = reopen(&db, None).unwrap();
+        for suffix in ["aPet", "bPet", "cPet"] {
+            storage.store_address(test_address(suffix)).unwrap();
+        }
+        storage.flush().await.unwrap();
+        let restarted = reopen(&db, None).unwrap();
+        assert_eq!(restarted.store_address(test_address("dPet")).unwrap(), 3);
+        restarted.flush().await.unwrap();
+
+        // A counter that lags the records (e.g. written by an older version) is raised past them
+        db.remove(KeySpace::default_pool().counter_key).unwrap();
+        assert_eq!(reopen(&db, None).unwrap().store_address(test_address("ePet")).unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_dispensed_addresses_are_archived() {
+        let storage = PetStorage::temporary().unwrap().with_lease_ttl(Duration::from_secs(60));
+        for suffix in ["aPet", "bPet", "cPet"] {
+            storage.store_address(test_address(suffix)).unwrap();
+        }
+
+        let alice = Requester {
+            ip: Some("10.0.0.1".to_string()),
+            user_agent: Some("curl".to_string()),
+        };
+        let bob = Requester {
+            ip: Some("10.0.0.2".to_string()),
+            user_agent: None,
+        };
+        let first = storage.get_next_address().unwrap().unwrap();
+        storage.record_dispensed(vec![DispensedRecord::new("default", &first, &alice)]);
+        let rest = storage.get_next_addresses(2).unwrap();
+        storage.record_dispensed(rest.iter().map(|info| DispensedRecord::new("default", info, &bob)).collect());
+        assert!(storage.acknowledge(first.id));
+        storage.flush().await.unwrap();
+
+        let all = DispensedFilter {
+            requester: None,
+            since: None,
+            limit: 10,
+        };
+        let records = storage.dispensed(&all).unwrap();
+        assert_eq!(records.iter().map(|record| record.id).collect::<Vec<_>>(), [2, 1, 0]);
+        assert!(records.iter().all(|record| record.lease_expires_at.is_some()));
+
+        // Acknowledgement completes the entry; private keys are never archived
+        let acknowledged = &records[2];
+        assert_eq!(acknowledged.requester.as_deref(), Some("10.0.0.1"));
+        assert!(acknowledged.acknowledged_at.is_some());
+        assert!(records[0].acknowledged_at.is_none());
+        let raw = storage.db.as_ref().unwrap().get(storage.keys.dispensed_key(first.id)).unwrap();
+        assert!(!String::from_utf8_lossy(&raw.unwrap()).contains("secret-"));
+
+        let by_bob = DispensedFilter {
+            requester: Some("10.0.0.2".to_string()),
+            since: None,
+            limit: 1,
+        };
+        assert_eq!(storage.dispensed(&by_bob).unwrap().len(), 1);
+        let future = DispensedFilter {
+            since: Some(chrono::Utc::now() + chrono::Duration::hours(1)),
+            ..all
+        };
+        assert!(storage.dispensed(&future).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fifo_order_survives_restart() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let storage = reopen(&db, None).unwrap();
+        for suffix in ["aPet", "bPet", "cPet", "dPet"] {
+            storage.store_address(test_address(suffix)).unwrap();
+        }
+        storage.get_next_address().unwrap();
+        storage.flush().await.unwrap();
+
+        let restarted = reopen(&db, None).unwrap();
+        let order: Vec<String> = restarted
+            .get_next_addresses(10)
+            .unwrap()
+            .into_iter()
+            .map(|info| info.address.private_key.expose().to_string())
+            .collect();
+        assert_eq!(order, ["secret-bPet", "secret-cPet", "secret-dPet"]);
+    }
+
+    #[tokio::test]
+    async fn test_memory_limit_spills_and_pages_in_fifo_order() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let storage = reopen(&db, None).unwrap().with_memory_limit(2);
+        let secrets = |popped: Vec<PetAddressInfo>| -> Vec<String> {
+            popped.into_iter().map(|info| info.address.private_key.expose().to_string()).collect()
+        };
+        for suffix in ["aPet", "bPet", "cPet", "dPet", "ePet"] {
+            storage.store_address(test_address(suffix)).unwrap();
+        }
+        assert_eq!(storage.addresses.len(), 2);
+        assert_eq!(storage.count_addresses().unwrap(), 5);
+        storage.flush().await.unwrap();
+        assert_eq!(storage.queued_address(4).unwrap().address.address, "TestAddressePet");
+        assert_eq!(storage.records().len(), 5);
+
+        // Spilled records are queued, not orphans
+        assert_eq!(storage.reconcile().await.unwrap().orphans_removed, 0);
+        let diagnostics = storage.diagnostics().await.unwrap();
+        assert_eq!(diagnostics.spilled, 3);
+        assert!(diagnostics.is_consistent());
+
+        // Only the window is served until the pager reads more back
+        assert_eq!(secrets(storage.get_next_addresses(10).unwrap()), ["secret-aPet", "secret-bPet"]);
+        storage.store_address(test_address("fPet")).unwrap();
+        assert_eq!(storage.page_in().await.unwrap(), 2);
+        assert_eq!(storage.page_in().await.unwrap(), 0);
+        assert_eq!(secrets(storage.get_next_addresses(10).unwrap()), ["secret-cPet", "secret-dPet"]);
+        assert_eq!(storage.page_in().await.unwrap(), 2);
+        assert_eq!(storage.spill_from(), None);
+
+        // A restart loads only the window
+        storage.flush().await.unwrap();
+        let restarted = reopen(&db, None).unwrap().with_memory_limit(1);
+        assert_eq!(restarted.addresses.len(), 1);
+        assert_eq!(restarted.count_addresses().unwrap(), 2);
+        restarted.restore().await.unwrap();
+        assert_eq!(restarted.addresses.len(), 1);
+        assert_eq!(secrets(restarted.get_next_addresses(10).unwrap()), ["secret-ePet"]);
+        assert_eq!(restarted.page_in().await.unwrap(), 1);
+        assert_eq!(secrets(restarted.get_next_addresses(10).unwrap()), ["secret-fPet"]);
+        assert_eq!(restarted.count_addresses().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_filtered_pop_reaches_spilled_addresses() {
+        let storage = PetStorage::temporary().unwrap().with_lease_ttl(Duration::from_secs(60)).with_memory_limit(1);
+        for suffix in ["aPet", "bPet", "cPet", "dPet"] {
+            storage.store_address(test_address(suffix)).unwrap();
+        }
+        storage.flush().await.unwrap();
+        let is_c = |address: &PetAddress| address.address.ends_with("cPet");
+
+        // cPet is on disk only; leased, its record stays in sled until acknowledged
+        let popped = storage.get_next_address_where(is_c).unwrap().unwrap();
+        assert_eq!(popped.address.address, "TestAddresscPet");
+        assert!(storage.get_next_address_where(is_c).unwrap().is_none());
+        assert!(storage.queued_address(popped.id).is_none());
+        assert_eq!(storage.count_addresses().unwrap(), 3);
+        assert_eq!(storage.records().len(), 4);
+        assert_eq!(storage.diagnostics().await.unwrap().spilled, 2);
+
+        // The pager skips it and the FIFO order of the rest is kept
+        let mut order = Vec::new();
+        while let Some(address_info) = storage.get_next_address().unwrap() {
+            order.push(address_info.address.address);
+            storage.page_in().await.unwrap();
+        }
+        assert_eq!(order, ["TestAddressaPet", "TestAddressbPet", "TestAddressdPet"]);
+        assert_eq!(storage.spill_from(), None);
+
+        // Only the three still leased are left in sled
+        assert!(storage.acknowledge(popped.id));
+        storage.flush().await.unwrap();
+        let diagnostics = storage.diagnostics().await.unwrap();
+        assert_eq!(diagnostics.db_record_count, 3);
+        assert!(diagnostics.is_consistent());
+    }
+
+    #[tokio::test]
+    async fn test_narrow_keys_are_migrated_in_id_order() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        // Old 10-digit keys: an 11-digit ID sorts before a smaller one
+        for (id, suffix) in [(10_000_000_000u64, "bPet"), (9_999_999_999, "aPet")] {
+            let info = PetAddressInfo {
+                id,
+                address: test_address(suffix),
+                created_at: chrono::Utc::now(),
+            };
+            db.insert(format!("address:{:010}", id), serde_json::to_vec(&info).unwrap()).unwrap();
+        }
+
+        let storage = reopen(&db, None).unwrap();
+        let keys: Vec<_> = db.scan_prefix("address:").map(|result| result.unwrap().0).collect();
+        assert_eq!(keys.len(), 2);
+        assert!(keys.iter().all(|key| key.len() == "address:".len() + 20));
+
+        assert_eq!(storage.get_next_address().unwrap().unwrap().id, 9_999_999_999);
+        assert_eq!(storage.next_id(), 10_000_000_001);
+    }
+
+    #[tokio::test]
+    async fn test_verify_repairs_and_reports_corruption() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        // verify checks that keys derive their address, so use real ones
+        let real_address = || PetAddress::from_keypair(&solana_sdk::signature::Keypair::new());
+        let first = real_address();
+        let storage = reopen(&db, None).unwrap();
+        let kept = storage.store_address(first.clone()).unwrap();
+        storage.store_address(real_address()).unwrap();
+        storage.flush().await.unwrap();
+
+        // A second copy of the first address (written before the duplicate
+        // guard existed), an unreadable record and a missing record
+        let keys = KeySpace::default_pool();
+        let copy = PetAddressInfo {
+            id: 5,
+            address: first.clone(),
+            created_at: chrono::Utc::now(),
+        };
+        db.insert(keys.address_key(5).as_bytes(), codec::encode(&copy).unwrap()).unwrap();
+        db.insert(keys.address_key(6).as_bytes(), &b"\x01garbage"[..]).unwrap();
+        let storage = reopen(&db, None).unwrap();
+        assert_eq!(storage.count_addresses().unwrap(), 3);
+        db.remove(keys.address_key(1).as_bytes()).unwrap();
+
+        let report = storage.verify(&mut HashSet::new(), None, false).await.unwrap();
+        assert_eq!(report.checked, 3);
+        assert_eq!((report.duplicates_removed, report.repersisted, report.orphans_removed), (1, 1, 0));
+        assert_eq!(report.corrupt, [keys.address_key(6)]);
+        assert!(db.contains_key(keys.address_key(6).as_bytes()).unwrap());
+
+        assert_eq!(storage.count_addresses().unwrap(), 2);
+        assert_eq!(storage.get_next_address().unwrap().unwrap().id, kept);
+
+        // Quarantining moves the corrupt record out of the pool
+        let report = storage.verify(&mut HashSet::new(), None, true).await.unwrap();
+        assert_eq!((report.quarantined, report.duplicates_removed), (1, 0));
+        assert!(!db.contains_key(keys.address_key(6).as_bytes()).unwrap());
+        assert!(db.contains_key(format!("quarantine:{}", keys.address_key(6)).as_bytes()).unwrap());
+        assert!(storage.verify(&mut HashSet::new(), None, false).await.unwrap().is_clean());
+
+        // A readable record whose private key belongs to another address,
+        // and addresses outside the pool's pattern
+        let mut forged = real_address();
+        forged.private_key = first.private_key.clone();
+        let forged = PetAddressInfo {
+            id: 8,
+            address: forged,
+            created_at: chrono::Utc::now(),
+        };
+        db.insert(keys.address_key(8).as_bytes(), codec::encode(&forged).unwrap()).unwrap();
+        let strict = AddressMatcher::regex("^zzzzzzzz", true).unwrap();
+        let report = storage.check_keys(&strict).await.unwrap();
+        assert_eq!((report.checked, report.off_pattern.len()), (2, 1));
+        assert_eq!(report.mismatched, [keys.address_key(8)]);
+        assert!(db.contains_key(keys.address_key(8).as_bytes()).unwrap());
+
+        let report = storage.verify(&mut HashSet::new(), Some(&strict), true).await.unwrap();
+        assert_eq!((report.mismatched.len(), report.quarantined, report.off_pattern.len()), (1, 1, 1));
+        assert!(!db.contains_key(keys.address_key(8).as_bytes()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_json_records_are_converted_to_binary() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let info = PetAddressInfo {
+            id: 7,
+            address: test_address("aPet"),
+            created_at: chrono::Utc::now(),
+        };
+        let key = KeySpace::default_pool().address_key(info.id);
+        db.insert(key.as_bytes(), serde_json::to_vec(&info).unwrap()).unwrap();
+
+        let storage = reopen(&db, None).unwrap();
+        assert!(codec::is_current(&db.get(key.as_bytes()).unwrap().unwrap()));
+        let restored = storage.get_next_address().unwrap().unwrap();
+        assert_eq!((restored.id, restored.created_at), (info.id, info.created_at));
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_records_are_migrated() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let plain = reopen(&db, None).unwrap();
+        let id = plain.store_address(test_address("aPet")).unwrap();
+        plain.flush().await.unwrap();
+        assert_eq!(stored_private_key(&db, id), "secret-aPet");
+
+        let storage = reopen(&db, Some(key_ring("new"))).unwrap();
+        assert_eq!(storage.count_addresses().unwrap(), 1);
+        assert!(encryption::is_sealed(&stored_private_key(&db, id)));
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_reports_desync() {
+        let storage = PetStorage::temporary().unwrap();
+        storage.store_address(test_address("aPet")).unwrap();
+        storage.flush().await.unwrap();
+
+        // Deliberately desync the atomic size counter
+        storage.queue_size.fetch_add(3, Ordering::Relaxed);
+
+        let diagnostics = storage.diagnostics().await.unwrap();
+        assert_eq!(diagnostics.reported_size, 4);
+        assert_eq!(diagnostics.actual_queue_len, 1);
+        assert_eq!(diagnostics.size_delta(), 3);
+        assert!(!diagnostics.is_consistent());
+    }
+}
@@ -0,0 +1,14 @@
+//! Fixtures shared by the storage tests
+
+use super::address::PetAddress;
+
+/// Address whose public key and address are `TestAddress{suffix}` and whose
+/// private key is `secret-{suffix}`
+pub(crate) fn test_address(suffix: &str) -> PetAddress {
+    let address = format!("TestAddress{}", suffix);
+    PetAddress {
+        public_key: address.clone(),
+        private_key: format!("secret-{}", suffix).into(),
+        address,
+    }
+}
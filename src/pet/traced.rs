@@ -0,0 +1,112 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tracing::{Instrument, Span};
+
+use super::address::{PetAddress, PetAddressInfo};
+use super::archive::DispensedRecord;
+use super::backend::Storage;
+use super::storage::PetStorage;
+use crate::telemetry::TRACE_TARGET;
+
+/// Storage that records a `storage.{operation}` span around each queue
+/// operation for trace export. Operations outside a request or generator
+/// batch are not traced, so idle polling does not start traces of its own.
+pub struct TracedStorage {
+    inner: Arc<dyn Storage>,
+}
+
+impl TracedStorage {
+    pub fn new(inner: Arc<dyn Storage>) -> Self {
+        Self { inner }
+    }
+
+    fn span(&self, operation: &'static str) -> Span {
+        if Span::current().is_none() {
+            return Span::none();
+        }
+        tracing::info_span!(
+            target: TRACE_TARGET,
+            "storage",
+            otel.name = %format_args!("storage.{}", operation),
+            db.system = self.inner.backend_name(),
+            db.operation.name = operation,
+        )
+    }
+
+    /// Run `operation`, marking its span failed when it errors
+    fn traced<T>(&self, operation: &'static str, run: impl FnOnce(&dyn Storage) -> Result<T>) -> Result<T> {
+        let span = self.span(operation);
+        let _entered = span.enter();
+        run(self.inner.as_ref()).inspect_err(|e| tracing::error!(target: TRACE_TARGET, "{:#}", e))
+    }
+}
+
+#[async_trait]
+impl Storage for TracedStorage {
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+
+    fn store_address(&self, address: PetAddress) -> Result<u64> {
+        self.traced("store", |inner| inner.store_address(address))
+    }
+
+    fn get_next_address(&self) -> Result<Option<PetAddressInfo>> {
+        self.traced("pop", |inner| inner.get_next_address())
+    }
+
+    fn get_next_addresses(&self, count: usize) -> Result<Vec<PetAddressInfo>> {
+        self.traced("pop_batch", |inner| inner.get_next_addresses(count))
+    }
+
+    fn count_addresses(&self) -> Result<usize> {
+        self.traced("count", |inner| inner.count_addresses())
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+
+    fn clear_all_addresses(&self) -> Result<()> {
+        self.traced("clear", |inner| inner.clear_all_addresses())
+    }
+
+    fn created_range(&self) -> Result<Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)>> {
+        self.inner.created_range()
+    }
+
+    async fn restore(&self) -> Result<usize> {
+        self.inner.restore().instrument(self.span("restore")).await
+    }
+
+    async fn open_pool(&self, name: &str) -> Result<Arc<dyn Storage>> {
+        let pool = self.inner.open_pool(name).instrument(self.span("open_pool")).await?;
+        Ok(Arc::new(Self::new(pool)))
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.inner.flush().instrument(self.span("flush")).await
+    }
+
+    async fn wait_for_demand(&self) {
+        self.inner.wait_for_demand().await
+    }
+
+    fn lease_expires_at(&self, id: u64) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.inner.lease_expires_at(id)
+    }
+
+    fn acknowledge(&self, id: u64) -> bool {
+        let _entered = self.span("acknowledge").entered();
+        self.inner.acknowledge(id)
+    }
+
+    fn record_dispensed(&self, records: Vec<DispensedRecord>) {
+        self.inner.record_dispensed(records)
+    }
+
+    fn sled(&self) -> Option<&PetStorage> {
+        self.inner.sled()
+    }
+}
@@ -0,0 +1,235 @@
+//! Dispense accounting per tenant: daily and monthly counters in sled,
+//! checked against the tenant's quotas before an address leaves its pool.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sled::transaction::{abort, ConflictableTransactionError, TransactionError, TransactionalTree};
+use sled::Db;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Sled key prefix of the counters: `usage:{tenant}:day:{YYYY-MM-DD}` and
+/// `usage:{tenant}:month:{YYYY-MM}`, UTC
+const USAGE_PREFIX: &str = "usage:";
+
+/// Most addresses a tenant may receive per UTC day and month; unset is unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quota {
+    pub daily: Option<u64>,
+    pub monthly: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsageError {
+    /// The tenant already received `limit` addresses in `period`
+    QuotaExceeded { tenant: String, period: String, limit: u64 },
+}
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UsageError::QuotaExceeded { tenant, period, limit } => {
+                write!(f, "Tenant '{}' used its quota of {} addresses for {}", tenant, limit, period)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// Per-tenant counters of dispensed addresses. Without a sled database
+/// nothing is counted and quotas are not enforced.
+#[derive(Clone)]
+pub struct UsageStore {
+    db: Option<Db>,
+    quotas: HashMap<String, Quota>,
+}
+
+impl UsageStore {
+    pub fn new(db: Option<Db>, quotas: HashMap<String, Quota>) -> Self {
+        Self { db, quotas }
+    }
+
+    /// Whether usage is recorded at all
+    pub fn is_persistent(&self) -> bool {
+        self.db.is_some()
+    }
+
+    pub fn quota(&self, tenant: &str) -> Quota {
+        self.quotas.get(tenant).copied().unwrap_or_default()
+    }
+
+    /// Configured tenants, sorted by name
+    pub fn tenants(&self) -> Vec<String> {
+        let mut tenants: Vec<String> = self.quotas.keys().cloned().collect();
+        tenants.sort();
+        tenants
+    }
+
+    /// Count one address dispensed to `tenant` at `now`. Fails with
+    /// `UsageError::QuotaExceeded` (and counts nothing) once a quota is used up.
+    pub fn charge(&self, tenant: &str, now: DateTime<Utc>) -> Result<()> {
+        let Some(db) = &self.db else {
+            return Ok(());
+        };
+        let quota = self.quota(tenant);
+        let (day_key, month_key) = (day_key(tenant, now), month_key(tenant, now));
+
+        let result = db.transaction(|tx| {
+            let day = read_counter(tx, &day_key)?;
+            let month = read_counter(tx, &month_key)?;
+            for (used, limit, period) in [(day, quota.daily, "%Y-%m-%d"), (month, quota.monthly, "%Y-%m")] {
+                if let Some(limit) = limit.filter(|&limit| used >= limit) {
+                    return abort(UsageError::QuotaExceeded {
+                        tenant: tenant.to_string(),
+                        period: now.format(period).to_string(),
+                        limit,
+                    });
+                }
+            }
+            tx.insert(day_key.as_bytes(), &(day + 1).to_be_bytes())?;
+            tx.insert(month_key.as_bytes(), &(month + 1).to_be_bytes())?;
+            Ok(())
+        });
+        match result {
+            Ok(()) => Ok(()),
+            Err(TransactionError::Abort(e)) => Err(e.into()),
+            Err(TransactionError::Storage(e)) => Err(e.into()),
+        }
+    }
+
+    /// Undo a `charge` made at `now` whose address was never handed out
+    pub fn refund(&self, tenant: &str, now: DateTime<Utc>) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        let (day_key, month_key) = (day_key(tenant, now), month_key(tenant, now));
+
+        let result: Result<(), TransactionError<()>> = db.transaction(|tx| {
+            for key in [&day_key, &month_key] {
+                let used = read_counter(tx, key)?;
+                tx.insert(key.as_bytes(), &used.saturating_sub(1).to_be_bytes())?;
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            tracing::warn!("Failed to refund usage of tenant '{}': {:?}", tenant, e);
+        }
+    }
+
+    /// Addresses dispensed to `tenant` in `month` (`YYYY-MM`)
+    pub fn monthly(&self, tenant: &str, month: &str) -> Result<u64> {
+        self.get(&format!("{}{}:month:{}", USAGE_PREFIX, tenant, month))
+    }
+
+    /// Addresses dispensed to `tenant` on `date` (`YYYY-MM-DD`)
+    pub fn on_day(&self, tenant: &str, date: &str) -> Result<u64> {
+        self.get(&format!("{}{}:day:{}", USAGE_PREFIX, tenant, date))
+    }
+
+    /// `(date, count)` for every day of `month` (`YYYY-MM`) with usage, oldest first
+    pub fn daily(&self, tenant: &str, month: &str) -> Result<Vec<(String, u64)>> {
+        let Some(db) = &self.db else {
+            return Ok(Vec::new());
+        };
+        let prefix = format!("{}{}:day:", USAGE_PREFIX, tenant);
+
+        let mut days = Vec::new();
+        for result in db.scan_prefix(format!("{}{}-", prefix, month).as_bytes()) {
+            let (key, value) = result?;
+            let date = String::from_utf8_lossy(&key[prefix.len()..]).into_owned();
+            days.push((date, decode_counter(&value)));
+        }
+        Ok(days)
+    }
+
+    fn get(&self, key: &str) -> Result<u64> {
+        let Some(db) = &self.db else {
+            return Ok(0);
+        };
+        Ok(db.get(key.as_bytes())?.map(|value| decode_counter(&value)).unwrap_or(0))
+    }
+}
+
+fn day_key(tenant: &str, now: DateTime<Utc>) -> String {
+    format!("{}{}:day:{}", USAGE_PREFIX, tenant, now.format("%Y-%m-%d"))
+}
+
+fn month_key(tenant: &str, now: DateTime<Utc>) -> String {
+    format!("{}{}:month:{}", USAGE_PREFIX, tenant, now.format("%Y-%m"))
+}
+
+fn read_counter<E>(tx: &TransactionalTree, key: &str) -> Result<u64, ConflictableTransactionError<E>> {
+    Ok(tx.get(key.as_bytes())?.map(|value| decode_counter(&value)).unwrap_or(0))
+}
+
+fn decode_counter(value: &[u8]) -> u64 {
+    value.try_into().map(u64::from_be_bytes).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn store(quota: Quota) -> UsageStore {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        UsageStore::new(Some(db), HashMap::from([("staging".to_string(), quota), ("dev".to_string(), Quota::default())]))
+    }
+
+    #[test]
+    fn test_quotas_are_enforced_per_day_and_month() {
+        let store = store(Quota {
+            daily: Some(2),
+            monthly: Some(3),
+        });
+        let first = Utc.with_ymd_and_hms(2026, 10, 14, 23, 0, 0).unwrap();
+        let second = Utc.with_ymd_and_hms(2026, 10, 15, 9, 0, 0).unwrap();
+
+        store.charge("staging", first).unwrap();
+        store.charge("staging", first).unwrap();
+        let error = store.charge("staging", first).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<UsageError>(),
+            Some(&UsageError::QuotaExceeded {
+                tenant: "staging".to_string(),
+                period: "2026-10-14".to_string(),
+                limit: 2
+            })
+        );
+
+        // A new day, but only one address left this month
+        store.charge("staging", second).unwrap();
+        let error = store.charge("staging", second).unwrap_err();
+        assert!(error.to_string().contains("for 2026-10"));
+
+        assert_eq!(store.monthly("staging", "2026-10").unwrap(), 3);
+        assert_eq!(
+            store.daily("staging", "2026-10").unwrap(),
+            [("2026-10-14".to_string(), 2), ("2026-10-15".to_string(), 1)]
+        );
+        // Tenants without a quota are only counted
+        for _ in 0..5 {
+            store.charge("dev", second).unwrap();
+        }
+        assert_eq!(store.on_day("dev", "2026-10-15").unwrap(), 5);
+    }
+
+    #[test]
+    fn test_refund_frees_quota() {
+        let store = store(Quota {
+            daily: Some(1),
+            monthly: None,
+        });
+        let now = Utc::now();
+        store.charge("staging", now).unwrap();
+        store.refund("staging", now);
+        store.charge("staging", now).unwrap();
+        assert!(store.charge("staging", now).is_err());
+        assert_eq!(store.monthly("staging", &now.format("%Y-%m").to_string()).unwrap(), 1);
+
+        let untracked = UsageStore::new(None, HashMap::new());
+        untracked.charge("staging", now).unwrap();
+        assert_eq!(untracked.monthly("staging", "2026-10").unwrap(), 0);
+    }
+}
@@ -0,0 +1,259 @@
+//! Redacted dispensing. Pops hand out only the public fields and the private
+//! key waits here until a client holding the reveal key claims it (unless it
+//! is encrypted to the client instead, see `recipients`). Either way a
+//! logged pop response no longer leaks anything.
+//!
+//! With `[retention]` on, keys nobody revealed are destroyed after a while:
+//! the entry is replaced by a tombstone holding only the address and times.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::address::PetAddressInfo;
+use super::encryption::KeyRing;
+use super::secret::SecretKey;
+
+/// Environment variable holding the key that reveals held private keys
+pub const REVEAL_KEY_ENV: &str = "PRIVATE_KEY_REVEAL_API_KEY";
+
+/// Sled key prefix of held keys: `vault:{pool}:{id}`
+const VAULT_PREFIX: &str = "vault:";
+
+/// A private key waiting to be revealed, or the tombstone left once the
+/// retention policy destroyed it (empty `private_key`, `destroyed_at` set)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeldKey {
+    pub address: String,
+    pub private_key: SecretKey,
+    /// When the address was dispensed; missing on keys held before retention existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub held_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destroyed_at: Option<DateTime<Utc>>,
+}
+
+impl HeldKey {
+    pub fn is_destroyed(&self) -> bool {
+        self.destroyed_at.is_some()
+    }
+
+    fn tombstone(&self, now: DateTime<Utc>) -> Self {
+        Self {
+            address: self.address.clone(),
+            private_key: SecretKey::default(),
+            held_at: self.held_at,
+            destroyed_at: Some(now),
+        }
+    }
+}
+
+/// Private keys of addresses dispensed in redacted form, each revealed at
+/// most once. Kept in sled (sealed when encryption at rest is on) so a
+/// restart does not lose them; in memory with other backends.
+#[derive(Clone)]
+pub struct KeyVault {
+    db: Option<Db>,
+    memory: Arc<DashMap<String, HeldKey>>,
+    key_ring: Option<Arc<KeyRing>>,
+}
+
+impl KeyVault {
+    pub fn new(db: Option<Db>, key_ring: Option<Arc<KeyRing>>) -> Self {
+        Self {
+            db,
+            memory: Arc::new(DashMap::new()),
+            key_ring,
+        }
+    }
+
+    /// Whether held keys survive a restart
+    pub fn is_persistent(&self) -> bool {
+        self.db.is_some()
+    }
+
+    /// Keep the private key of `address_info`, dispensed from `pool`
+    pub fn hold(&self, pool: &str, address_info: &PetAddressInfo) -> Result<()> {
+        let key = vault_key(pool, address_info.id);
+        let mut held = HeldKey {
+            address: address_info.address.address.clone(),
+            private_key: address_info.address.private_key.clone(),
+            held_at: Some(Utc::now()),
+            destroyed_at: None,
+        };
+
+        let Some(db) = &self.db else {
+            self.memory.insert(key, held);
+            return Ok(());
+        };
+        if let Some(key_ring) = &self.key_ring {
+            held.private_key = key_ring.encrypt(&held.private_key, held.address.as_bytes())?.into();
+        }
+        db.insert(key.as_bytes(), serde_json::to_vec(&held)?)?;
+        Ok(())
+    }
+
+    /// Remove and return the key held for address `id` of `pool`; None if
+    /// it was never held or was already revealed. A destroyed key comes back
+    /// as its tombstone, which stays in place.
+    pub fn reveal(&self, pool: &str, id: u64) -> Result<Option<HeldKey>> {
+        let key = vault_key(pool, id);
+
+        let Some(db) = &self.db else {
+            let Some((key, held)) = self.memory.remove(&key) else {
+                return Ok(None);
+            };
+            if held.is_destroyed() {
+                self.memory.insert(key, held.clone());
+            }
+            return Ok(Some(held));
+        };
+        let Some(value) = db.remove(key.as_bytes())? else {
+            return Ok(None);
+        };
+        let mut held: HeldKey = serde_json::from_slice(&value).context("Failed to deserialize held key")?;
+        if held.is_destroyed() {
+            db.insert(key.as_bytes(), value)?;
+            return Ok(Some(held));
+        }
+        if let Some(key_ring) = &self.key_ring {
+            held.private_key = key_ring.decrypt(&held.private_key, held.address.as_bytes())?;
+        }
+        Ok(Some(held))
+    }
+
+    /// Replace every key held since before `cutoff` with a tombstone;
+    /// returns how many were destroyed. Keys without `held_at` are stamped
+    /// now so they expire one retention period later.
+    pub fn destroy_held_before(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        let now = Utc::now();
+        let mut destroyed = 0;
+
+        let Some(db) = &self.db else {
+            for mut entry in self.memory.iter_mut() {
+                let held = entry.value_mut();
+                match held.held_at {
+                    _ if held.is_destroyed() => {}
+                    None => held.held_at = Some(now),
+                    Some(held_at) if held_at < cutoff => {
+                        *held = held.tombstone(now);
+                        destroyed += 1;
+                    }
+                    Some(_) => {}
+                }
+            }
+            return Ok(destroyed);
+        };
+        for entry in db.scan_prefix(VAULT_PREFIX) {
+            let (key, value) = entry?;
+            let mut held: HeldKey = serde_json::from_slice(&value).context("Failed to deserialize held key")?;
+            let replacement = match held.held_at {
+                _ if held.is_destroyed() => continue,
+                None => {
+                    held.held_at = Some(now);
+                    held
+                }
+                Some(held_at) if held_at < cutoff => held.tombstone(now),
+                Some(_) => continue,
+            };
+            // Skipped if the key was revealed in the meantime
+            let swapped = db.compare_and_swap(&key, Some(value), Some(serde_json::to_vec(&replacement)?))?;
+            if swapped.is_ok() && replacement.is_destroyed() {
+                destroyed += 1;
+            }
+        }
+        if destroyed > 0 {
+            db.flush()?;
+        }
+        Ok(destroyed)
+    }
+
+    /// Destroy keys held longer than `retention`, checking every `interval`
+    pub fn start_retention_sweeper(&self, retention: Duration, interval: Duration) {
+        let vault = self.clone();
+        let Ok(retention) = chrono::Duration::from_std(retention) else {
+            return;
+        };
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval.max(Duration::from_secs(1))).await;
+                let Some(cutoff) = Utc::now().checked_sub_signed(retention) else {
+                    continue;
+                };
+                match vault.destroy_held_before(cutoff) {
+                    Ok(0) => {}
+                    Ok(destroyed) => tracing::info!("🔥 Destroyed {} held private keys past their retention period", destroyed),
+                    Err(e) => tracing::error!("Failed to destroy expired held keys: {}", e),
+                }
+            }
+        });
+    }
+}
+
+fn vault_key(pool: &str, id: u64) -> String {
+    format!("{}{}:{:020}", VAULT_PREFIX, pool, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::PetAddress;
+
+    fn address_info(id: u64) -> PetAddressInfo {
+        PetAddressInfo {
+            id,
+            address: PetAddress {
+                public_key: "TestAddressaPet".to_string(),
+                private_key: "secret".to_string().into(),
+                address: "TestAddressaPet".to_string(),
+            },
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_held_keys_are_revealed_once_and_sealed_at_rest() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let key_ring = KeyRing::parse("k", &format!("k:{}", "11".repeat(32))).unwrap();
+        let vault = KeyVault::new(Some(db.clone()), Some(Arc::new(key_ring)));
+
+        vault.hold("default", &address_info(7)).unwrap();
+        let (_, stored) = db.scan_prefix(VAULT_PREFIX).next().unwrap().unwrap();
+        assert!(!String::from_utf8_lossy(&stored).contains("secret"));
+
+        assert_eq!(vault.reveal("Cat", 7).unwrap(), None);
+        let held = vault.reveal("default", 7).unwrap().unwrap();
+        assert_eq!(held.private_key, "secret");
+        assert_eq!(vault.reveal("default", 7).unwrap(), None);
+
+        let in_memory = KeyVault::new(None, None);
+        in_memory.hold("default", &address_info(1)).unwrap();
+        assert_eq!(in_memory.reveal("default", 1).unwrap().unwrap().private_key, "secret");
+        assert!(in_memory.reveal("default", 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_keys_past_retention_leave_a_tombstone() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let key_ring = KeyRing::parse("k", &format!("k:{}", "11".repeat(32))).unwrap();
+        for vault in [KeyVault::new(Some(db), Some(Arc::new(key_ring))), KeyVault::new(None, None)] {
+            vault.hold("default", &address_info(1)).unwrap();
+            assert_eq!(vault.destroy_held_before(Utc::now() - chrono::Duration::hours(1)).unwrap(), 0);
+            vault.hold("default", &address_info(2)).unwrap();
+            assert_eq!(vault.destroy_held_before(Utc::now() + chrono::Duration::seconds(1)).unwrap(), 2);
+            assert_eq!(vault.destroy_held_before(Utc::now() + chrono::Duration::seconds(1)).unwrap(), 0);
+
+            for _ in 0..2 {
+                let tombstone = vault.reveal("default", 1).unwrap().unwrap();
+                assert!(tombstone.is_destroyed());
+                assert_eq!(tombstone.private_key, "");
+                assert_eq!(tombstone.address, "TestAddressaPet");
+            }
+        }
+    }
+}
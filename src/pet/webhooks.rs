@@ -0,0 +1,620 @@
+//! Push delivery: a consumer registers a callback URL and how many addresses
+//! it wants, and the server POSTs them in batches as they become available
+//! instead of the consumer polling. Every POST is signed with the
+//! subscription's secret (`X-Signature: sha256=<hex HMAC-SHA256 of the body>`)
+//! and retried with exponential backoff.
+//!
+//! A batch that still fails after the last attempt is not put back: the
+//! consumer may have received it, and a private key is never handed out twice.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::address::PetAddressInfo;
+use super::alerts::Webhook;
+use super::archive::{DispensedRecord, Requester};
+use super::generator::{PatternPool, PetGenerator, TENANT_POOL_PREFIX};
+use super::metrics;
+use super::recipients::{RecipientKey, RecipientRegistry};
+use super::secret::SecretKey;
+use super::usage::UsageStore;
+use super::vault::KeyVault;
+use crate::config::WebhookConfig;
+use crate::middleware::ResponseSigner;
+
+/// Sled key prefix of subscriptions: `webhook:{id}`
+const SUBSCRIPTION_PREFIX: &str = "webhook:";
+/// Header carrying `{subscription id}.{batch number}`, the same on every retry
+pub const DELIVERY_ID_HEADER: &str = "X-Delivery-Id";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubscriptionStatus {
+    Active,
+    /// Every requested address was delivered
+    Completed,
+    /// A batch could not be delivered
+    Failed,
+    Cancelled,
+}
+
+impl SubscriptionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubscriptionStatus::Active => "active",
+            SubscriptionStatus::Completed => "completed",
+            SubscriptionStatus::Failed => "failed",
+            SubscriptionStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// A consumer's request to have `count` addresses of `pool` pushed to `callback_url`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: String,
+    pub pool: String,
+    pub callback_url: String,
+    pub count: usize,
+    pub delivered: usize,
+    /// Batches delivered so far
+    pub batches: u64,
+    pub status: SubscriptionStatus,
+    /// HMAC key of `X-Signature`
+    pub secret: String,
+    /// Public key private keys are encrypted to (base64)
+    pub recipient_key: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+}
+
+/// Why a subscription was refused. Wrapped in `anyhow::Error`; callers that care downcast.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscribeError {
+    Invalid(String),
+    TooMany { max_active: usize },
+}
+
+impl std::fmt::Display for SubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubscribeError::Invalid(reason) => write!(f, "{}", reason),
+            SubscribeError::TooMany { max_active } => write!(f, "Too many active subscriptions ({})", max_active),
+        }
+    }
+}
+
+impl std::error::Error for SubscribeError {}
+
+/// Body POSTed to the callback
+#[derive(Debug, Serialize)]
+pub struct DeliveryPayload {
+    pub subscription_id: String,
+    pub delivery_id: String,
+    pub pool: String,
+    pub addresses: Vec<PushedAddress>,
+    /// Addresses delivered including this batch
+    pub delivered: usize,
+    pub remaining: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PushedAddress {
+    pub id: u64,
+    pub public_key: String,
+    pub address: String,
+    /// Omitted when redaction is on or the key is sent encrypted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<SecretKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypted_private_key: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Where subscriptions are persisted: sled, or memory for other backends
+#[derive(Clone)]
+pub struct SubscriptionStore {
+    db: Option<Db>,
+}
+
+impl SubscriptionStore {
+    pub fn new(db: Option<Db>) -> Self {
+        Self { db }
+    }
+
+    fn save(&self, subscription: &Subscription) -> Result<()> {
+        if let Some(db) = &self.db {
+            db.insert(store_key(&subscription.id).as_bytes(), serde_json::to_vec(subscription)?)?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> Result<()> {
+        if let Some(db) = &self.db {
+            db.remove(store_key(id).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn load_all(&self) -> Result<Vec<Subscription>> {
+        let Some(db) = &self.db else {
+            return Ok(Vec::new());
+        };
+        db.scan_prefix(SUBSCRIPTION_PREFIX.as_bytes())
+            .values()
+            .map(|value| Ok(serde_json::from_slice(&value?)?))
+            .collect()
+    }
+}
+
+fn store_key(id: &str) -> String {
+    format!("{}{}", SUBSCRIPTION_PREFIX, id)
+}
+
+/// Runs one delivery task per active subscription
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    subscriptions: Arc<DashMap<String, Subscription>>,
+    store: SubscriptionStore,
+    generator: Arc<PetGenerator>,
+    usage: UsageStore,
+    /// Set when pops are redacted
+    vault: Option<KeyVault>,
+    recipients: RecipientRegistry,
+    config: WebhookConfig,
+}
+
+impl WebhookDispatcher {
+    pub fn new(
+        store: SubscriptionStore,
+        generator: Arc<PetGenerator>,
+        usage: UsageStore,
+        vault: Option<KeyVault>,
+        recipients: RecipientRegistry,
+        config: WebhookConfig,
+    ) -> Self {
+        Self {
+            subscriptions: Arc::new(DashMap::new()),
+            store,
+            generator,
+            usage,
+            vault,
+            recipients,
+            config,
+        }
+    }
+
+    /// Resume the active subscriptions of the store; finished ones are
+    /// dropped. Returns how many were resumed.
+    pub fn resume(&self) -> Result<usize> {
+        let mut resumed = 0;
+        for subscription in self.store.load_all()? {
+            if subscription.status != SubscriptionStatus::Active {
+                self.store.remove(&subscription.id)?;
+                continue;
+            }
+            let id = subscription.id.clone();
+            self.subscriptions.insert(id.clone(), subscription);
+            self.spawn(id);
+            resumed += 1;
+        }
+        Ok(resumed)
+    }
+
+    /// Register a subscription and start delivering to it
+    pub fn subscribe(
+        &self,
+        pool: &str,
+        callback_url: &str,
+        count: usize,
+        recipient_key: Option<String>,
+    ) -> Result<Subscription> {
+        let invalid = |reason: String| anyhow::Error::new(SubscribeError::Invalid(reason));
+        if count == 0 || count > self.config.max_count {
+            return Err(invalid(format!("count must be between 1 and {}", self.config.max_count)));
+        }
+        let webhook = Webhook::new(callback_url, self.timeout()).map_err(|e| invalid(format!("{:#}", e)))?;
+        let allowed_hosts = &self.config.allowed_hosts;
+        if !allowed_hosts.is_empty() && !allowed_hosts.iter().any(|host| host.eq_ignore_ascii_case(webhook.host())) {
+            return Err(invalid(format!("Callback host '{}' is not allowed", webhook.host())));
+        }
+        if let Some(recipient_key) = &recipient_key {
+            RecipientKey::parse(recipient_key).map_err(|e| invalid(format!("{:#}", e)))?;
+        }
+        let active = self
+            .subscriptions
+            .iter()
+            .filter(|subscription| subscription.status == SubscriptionStatus::Active)
+            .count();
+        if active >= self.config.max_active {
+            return Err(SubscribeError::TooMany { max_active: self.config.max_active }.into());
+        }
+
+        let now = Utc::now();
+        let subscription = Subscription {
+            id: random_hex(16),
+            pool: pool.to_string(),
+            callback_url: callback_url.to_string(),
+            count,
+            delivered: 0,
+            batches: 0,
+            status: SubscriptionStatus::Active,
+            secret: random_hex(32),
+            recipient_key,
+            created_at: now,
+            updated_at: now,
+            last_error: None,
+        };
+        self.store.save(&subscription)?;
+        self.subscriptions.insert(subscription.id.clone(), subscription.clone());
+        self.spawn(subscription.id.clone());
+        tracing::info!("Subscription {} registered for {} addresses of pool '{}'", subscription.id, count, pool);
+        Ok(subscription)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Subscription> {
+        self.subscriptions.get(id).map(|subscription| subscription.clone())
+    }
+
+    /// Stop an active subscription; a batch being retried is given up
+    pub fn cancel(&self, id: &str) -> Option<Subscription> {
+        self.finish(id, SubscriptionStatus::Cancelled, None);
+        self.get(id)
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.config.timeout_ms)
+    }
+
+    fn spawn(&self, id: String) {
+        let dispatcher = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = dispatcher.run(&id).await {
+                // Cancelled while a batch was retried: nothing failed
+                if dispatcher.is_active(&id) {
+                    tracing::warn!("Subscription {} failed: {:#}", id, e);
+                    dispatcher.finish(&id, SubscriptionStatus::Failed, Some(format!("{:#}", e)));
+                }
+            }
+        });
+    }
+
+    fn is_active(&self, id: &str) -> bool {
+        self.subscriptions
+            .get(id)
+            .is_some_and(|subscription| subscription.status == SubscriptionStatus::Active)
+    }
+
+    /// Apply `change` to subscription `id` and persist it
+    fn update(&self, id: &str, change: impl FnOnce(&mut Subscription)) {
+        let Some(mut subscription) = self.subscriptions.get_mut(id) else {
+            return;
+        };
+        change(&mut subscription);
+        subscription.updated_at = Utc::now();
+        if let Err(e) = self.store.save(&subscription) {
+            tracing::warn!("Failed to persist subscription {}: {}", id, e);
+        }
+    }
+
+    /// Move an active subscription to `status`; finished ones stay as they are
+    fn finish(&self, id: &str, status: SubscriptionStatus, error: Option<String>) {
+        if !self.is_active(id) {
+            return;
+        }
+        self.update(id, |subscription| {
+            subscription.status = status;
+            subscription.last_error = error;
+        });
+        tracing::info!("Subscription {} {}", id, status.as_str());
+    }
+
+    /// Deliver until the subscription is complete, cancelled or a batch fails
+    async fn run(&self, id: &str) -> Result<()> {
+        let Some(subscription) = self.get(id) else {
+            return Ok(());
+        };
+        let webhook = Webhook::new(&subscription.callback_url, self.timeout())?;
+        let signer = ResponseSigner::new(subscription.secret.as_bytes())?;
+        let requester = Requester {
+            ip: Some(webhook.host().to_string()),
+            user_agent: Some(format!("webhook:{}", id)),
+        };
+
+        loop {
+            let Some(subscription) = self.get(id).filter(|subscription| subscription.status == SubscriptionStatus::Active) else {
+                return Ok(());
+            };
+            let remaining = subscription.count - subscription.delivered;
+            if remaining == 0 {
+                self.finish(id, SubscriptionStatus::Completed, None);
+                return Ok(());
+            }
+            let pool = self
+                .generator
+                .any_pool(&subscription.pool)
+                .ok_or_else(|| anyhow!("Pool '{}' no longer exists", subscription.pool))?;
+
+            let batch = self.pop(&pool, remaining.min(self.config.batch_size.max(1))).unwrap_or_else(|e| {
+                tracing::warn!("Subscription {} could not pop from pool '{}': {}", id, pool.name, e);
+                Vec::new()
+            });
+            if batch.is_empty() {
+                tokio::time::sleep(Duration::from_millis(self.config.poll_interval_ms.max(1))).await;
+                continue;
+            }
+            metrics::record_dispensed(&pool.name, batch.len());
+            pool.storage.record_dispensed(
+                batch
+                    .iter()
+                    .map(|address_info| DispensedRecord::new(&pool.name, address_info, &requester))
+                    .collect(),
+            );
+
+            let delivered = subscription.delivered + batch.len();
+            let payload = DeliveryPayload {
+                subscription_id: id.to_string(),
+                delivery_id: format!("{}.{}", id, subscription.batches + 1),
+                pool: pool.name.clone(),
+                addresses: self.pushed(&subscription, &pool.name, &batch)?,
+                delivered,
+                remaining: subscription.count - delivered,
+            };
+            let result = self.post(&webhook, &signer, &payload).await;
+
+            // Delivered or given up: either way the batch does not go back to the queue
+            for address_info in &batch {
+                pool.storage.acknowledge(address_info.id);
+            }
+            result.with_context(|| format!("Batch {} was not delivered", payload.delivery_id))?;
+            self.update(id, |subscription| {
+                subscription.delivered = delivered;
+                subscription.batches += 1;
+            });
+            tracing::debug!("Subscription {} delivered {}/{}", id, delivered, subscription.count);
+        }
+    }
+
+    /// Pop up to `count` addresses, charging tenants' quotas like a fetch
+    fn pop(&self, pool: &PatternPool, count: usize) -> Result<Vec<PetAddressInfo>> {
+        let Some(tenant) = pool.name.strip_prefix(TENANT_POOL_PREFIX) else {
+            return pool.storage.get_next_addresses(count);
+        };
+
+        let now = Utc::now();
+        let mut popped = Vec::new();
+        while popped.len() < count {
+            // Quota used up: wait for the next day or month like an empty pool
+            if self.usage.charge(tenant, now).is_err() {
+                break;
+            }
+            match pool.storage.get_next_address() {
+                Ok(Some(address_info)) => popped.push(address_info),
+                Ok(None) => {
+                    self.usage.refund(tenant, now);
+                    break;
+                }
+                Err(e) => {
+                    self.usage.refund(tenant, now);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(popped)
+    }
+
+    /// The batch as sent: private keys encrypted to the subscription's (or
+    /// tenant's) recipient key, held in the vault when redacted, else plain
+    fn pushed(&self, subscription: &Subscription, pool: &str, batch: &[PetAddressInfo]) -> Result<Vec<PushedAddress>> {
+        let recipient = match &subscription.recipient_key {
+            Some(encoded) => Some(RecipientKey::parse(encoded)?),
+            None => match pool.strip_prefix(TENANT_POOL_PREFIX) {
+                Some(tenant) => self.recipients.get(tenant)?,
+                None => None,
+            },
+        };
+
+        batch
+            .iter()
+            .map(|address_info| {
+                let private_key = &address_info.address.private_key;
+                let (private_key, encrypted_private_key) = match (&recipient, &self.vault) {
+                    (Some(recipient), _) => (None, Some(recipient.seal(private_key)?)),
+                    (None, Some(vault)) => {
+                        vault.hold(pool, address_info)?;
+                        (None, None)
+                    }
+                    (None, None) => (Some(private_key.clone()), None),
+                };
+                Ok(PushedAddress {
+                    id: address_info.id,
+                    public_key: address_info.address.public_key.clone(),
+                    address: address_info.address.address.clone(),
+                    private_key,
+                    encrypted_private_key,
+                    created_at: address_info.created_at,
+                })
+            })
+            .collect()
+    }
+
+    /// POST `payload`, retrying with exponential backoff while the subscription is active
+    async fn post(&self, webhook: &Webhook, signer: &ResponseSigner, payload: &DeliveryPayload) -> Result<()> {
+        let body = serde_json::to_vec(payload)?;
+        let signature = signer.sign(&body)?;
+        let max_attempts = self.config.max_attempts;
+        let mut backoff = Duration::from_millis(self.config.retry_backoff_ms);
+
+        let mut attempt = 1;
+        loop {
+            let headers = vec![
+                ("X-Signature", signature.clone()),
+                (DELIVERY_ID_HEADER, payload.delivery_id.clone()),
+                ("X-Delivery-Attempt", attempt.to_string()),
+            ];
+            let (webhook, body) = (webhook.clone(), body.clone());
+            let error = match tokio::task::spawn_blocking(move || webhook.post_json(&body, &headers)).await {
+                Ok(Ok(())) => return Ok(()),
+                Ok(Err(e)) => e,
+                Err(e) => e.into(),
+            };
+            if attempt >= max_attempts {
+                return Err(error);
+            }
+            tracing::info!(
+                "Delivery {} attempt {}/{} failed, retrying in {:?}: {}",
+                payload.delivery_id,
+                attempt,
+                max_attempts,
+                backoff,
+                error
+            );
+            tokio::time::sleep(backoff).await;
+            if !self.is_active(&payload.subscription_id) {
+                return Err(anyhow!("Subscription is no longer active"));
+            }
+            backoff = backoff.saturating_mul(2);
+            attempt += 1;
+        }
+    }
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PetGeneratorConfig;
+    use crate::pet::{MemoryStorage, PetAddress, Storage};
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Request head lines and body captured by `callback`
+    type Captured = (Vec<String>, Vec<u8>);
+
+    /// Answer `responses.len()` requests with those statuses; returns the
+    /// callback URL and the captured requests
+    fn callback(responses: &'static [&'static str]) -> (String, std::thread::JoinHandle<Vec<Captured>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://127.0.0.1:{}/hook", listener.local_addr().unwrap().port());
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for status in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let (mut head, mut content_length) = (Vec::new(), 0);
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("Content-Length: ") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                    head.push(line.trim_end().to_string());
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                (&stream).write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).as_bytes()).unwrap();
+                requests.push((head, body));
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    async fn dispatcher(addresses: usize, config: WebhookConfig) -> WebhookDispatcher {
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        for n in 0..addresses {
+            let key = format!("Address{}", n);
+            storage
+                .store_address(PetAddress {
+                    public_key: key.clone(),
+                    private_key: format!("secret-{}", n).into(),
+                    address: key,
+                })
+                .unwrap();
+        }
+        let generator_config: PetGeneratorConfig =
+            serde_json::from_value(serde_json::json!({ "pool_size": 10, "batch_size": 1, "db_path": "" })).unwrap();
+        let generator = PetGenerator::new(storage, generator_config).await.unwrap();
+        WebhookDispatcher::new(
+            SubscriptionStore::new(None),
+            Arc::new(generator),
+            UsageStore::new(None, HashMap::new()),
+            None,
+            RecipientRegistry::new(None),
+            config,
+        )
+    }
+
+    async fn wait_for_status(dispatcher: &WebhookDispatcher, id: &str, status: SubscriptionStatus) -> Subscription {
+        for _ in 0..200 {
+            let subscription = dispatcher.get(id).unwrap();
+            if subscription.status == status {
+                return subscription;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("subscription never became {}", status.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_addresses_are_pushed_in_signed_batches() {
+        let config = WebhookConfig { enabled: true, batch_size: 2, ..Default::default() };
+        let dispatcher = dispatcher(5, config).await;
+        let (url, server) = callback(&["200 OK", "204 No Content"]);
+
+        let subscription = dispatcher.subscribe("default", &url, 3, None).unwrap();
+        let finished = wait_for_status(&dispatcher, &subscription.id, SubscriptionStatus::Completed).await;
+        assert_eq!((finished.delivered, finished.batches), (3, 2));
+
+        let signer = ResponseSigner::new(subscription.secret.as_bytes()).unwrap();
+        let requests = server.join().unwrap();
+        for (n, (head, body)) in requests.iter().enumerate() {
+            assert!(head.contains(&format!("X-Signature: {}", signer.sign(body).unwrap())));
+            assert!(head.contains(&format!("X-Delivery-Id: {}.{}", subscription.id, n + 1)));
+        }
+        let last: serde_json::Value = serde_json::from_slice(&requests[1].1).unwrap();
+        assert_eq!(last["addresses"].as_array().unwrap().len(), 1);
+        assert_eq!(last["addresses"][0]["private_key"], "secret-2");
+        assert_eq!((last["delivered"].as_u64(), last["remaining"].as_u64()), (Some(3), Some(0)));
+
+        // Only what was asked for left the pool
+        assert_eq!(dispatcher.generator.pools()[0].storage.count_addresses().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_batch_is_retried_then_gives_up() {
+        let config = WebhookConfig {
+            enabled: true,
+            max_attempts: 2,
+            retry_backoff_ms: 10,
+            ..Default::default()
+        };
+        let dispatcher = dispatcher(1, config).await;
+        let (url, server) = callback(&["500 Internal Server Error", "503 Service Unavailable"]);
+
+        let subscription = dispatcher.subscribe("default", &url, 1, None).unwrap();
+        let failed = wait_for_status(&dispatcher, &subscription.id, SubscriptionStatus::Failed).await;
+        assert!(failed.last_error.unwrap().contains("503"));
+        let requests = server.join().unwrap();
+        assert!(requests[1].0.contains(&"X-Delivery-Attempt: 2".to_string()));
+
+        let invalid = dispatcher.subscribe("default", "ftp://example.com", 1, None).unwrap_err();
+        assert!(matches!(invalid.downcast_ref(), Some(SubscribeError::Invalid(_))));
+    }
+}
@@ -0,0 +1,241 @@
+use dashmap::DashMap;
+use rand::RngCore;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::generator::PetGenerator;
+use crate::config::WorkersConfig;
+
+/// Workers silent for this many `timeout_seconds` are forgotten
+const FORGET_AFTER_TIMEOUTS: u32 = 10;
+
+/// A remote grind worker known to the server
+#[derive(Debug, Clone)]
+pub struct Worker {
+    pub id: String,
+    pub name: String,
+    /// Grinder threads the worker runs, as it reported
+    pub threads: Option<usize>,
+    pub registered_at: chrono::DateTime<chrono::Utc>,
+    /// Last heartbeat or submission
+    pub last_seen: chrono::DateTime<chrono::Utc>,
+    /// Throughput from the last heartbeat
+    pub keys_per_second: f64,
+    /// Keys tried since the worker started, from the last heartbeat
+    pub keys_tried: u64,
+    pub accepted: u64,
+    pub duplicates: u64,
+    pub rejected: u64,
+    /// Pool the worker was last told to grind for
+    pub assignment: Option<Assignment>,
+}
+
+/// What a worker should grind next
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub pool: String,
+    /// Pattern of the pool, e.g. `[a-z]Pet`
+    pub pattern: String,
+    /// Regex form of the pattern, for workers that match with a regex engine
+    pub regex: String,
+    /// Addresses the pool is short of its target
+    pub needed: usize,
+}
+
+/// Tracks remote workers: registration, liveness, throughput and the pool
+/// each one grinds for. Kept in memory; workers register again after a restart.
+#[derive(Clone)]
+pub struct WorkerRegistry {
+    workers: Arc<DashMap<String, Worker>>,
+    config: WorkersConfig,
+}
+
+impl WorkerRegistry {
+    pub fn new(config: WorkersConfig) -> Self {
+        let registry = Self {
+            workers: Arc::new(DashMap::new()),
+            config,
+        };
+
+        let registry_clone = registry.clone();
+        tokio::spawn(async move {
+            registry_clone.cleanup_task().await;
+        });
+
+        registry
+    }
+
+    pub fn config(&self) -> &WorkersConfig {
+        &self.config
+    }
+
+    /// Add a worker and return it with its new ID
+    pub fn register(&self, name: &str, threads: Option<usize>) -> Worker {
+        let now = chrono::Utc::now();
+        let worker = Worker {
+            id: new_worker_id(),
+            name: name.to_string(),
+            threads,
+            registered_at: now,
+            last_seen: now,
+            keys_per_second: 0.0,
+            keys_tried: 0,
+            accepted: 0,
+            duplicates: 0,
+            rejected: 0,
+            assignment: None,
+        };
+        self.workers.insert(worker.id.clone(), worker.clone());
+        worker
+    }
+
+    /// Record a heartbeat; None if the worker is unknown or was forgotten
+    pub fn heartbeat(&self, id: &str, keys_per_second: Option<f64>, keys_tried: Option<u64>) -> Option<Worker> {
+        let mut worker = self.workers.get_mut(id)?;
+        worker.last_seen = chrono::Utc::now();
+        if let Some(keys_per_second) = keys_per_second {
+            worker.keys_per_second = keys_per_second.max(0.0);
+        }
+        if let Some(keys_tried) = keys_tried {
+            worker.keys_tried = keys_tried;
+        }
+        Some(worker.clone())
+    }
+
+    /// Count a submission against a registered worker; unknown IDs are ignored
+    pub fn record_submission(&self, id: &str, accepted: usize, duplicates: usize, rejected: usize) {
+        if let Some(mut worker) = self.workers.get_mut(id) {
+            worker.last_seen = chrono::Utc::now();
+            worker.accepted += accepted as u64;
+            worker.duplicates += duplicates as u64;
+            worker.rejected += rejected as u64;
+        }
+    }
+
+    /// Pick the pool for `id` and remember it. Pools below target are shared
+    /// out by how many addresses they need per live worker already on them;
+    /// None when every pool is full.
+    pub fn assign(&self, id: &str, generator: &PetGenerator) -> Option<Assignment> {
+        let target = generator.target_pool_size();
+        let now = chrono::Utc::now();
+        let mut best: Option<(f64, Assignment)> = None;
+        for pool in generator.pools() {
+            let count = match pool.storage.count_addresses() {
+                Ok(count) => count,
+                Err(e) => {
+                    tracing::error!("Failed to check address count for pool '{}': {}", pool.name, e);
+                    continue;
+                }
+            };
+            if count >= target {
+                continue;
+            }
+            let needed = target - count;
+            let assigned = self
+                .workers
+                .iter()
+                .filter(|worker| worker.id != id && self.is_alive(worker, now))
+                .filter(|worker| worker.assignment.as_ref().is_some_and(|a| a.pool == pool.name))
+                .count();
+            let share = needed as f64 / (assigned + 1) as f64;
+            if best.as_ref().is_none_or(|(best_share, _)| share > *best_share) {
+                let assignment = Assignment {
+                    pool: pool.name.clone(),
+                    pattern: pool.matcher.describe(),
+                    regex: pool.matcher.to_regex(),
+                    needed,
+                };
+                best = Some((share, assignment));
+            }
+        }
+
+        let assignment = best.map(|(_, assignment)| assignment);
+        if let Some(mut worker) = self.workers.get_mut(id) {
+            if worker.assignment.as_ref().map(|a| &a.pool) != assignment.as_ref().map(|a| &a.pool) {
+                match &assignment {
+                    Some(assignment) => tracing::info!("Worker '{}' ({}) now grinds for pool '{}'", worker.name, id, assignment.pool),
+                    None => tracing::info!("Worker '{}' ({}) has nothing to grind, every pool is full", worker.name, id),
+                }
+            }
+            worker.assignment = assignment.clone();
+        }
+        assignment
+    }
+
+    /// Workers by registration time, oldest first
+    pub fn list(&self) -> Vec<Worker> {
+        let mut workers: Vec<Worker> = self.workers.iter().map(|worker| worker.clone()).collect();
+        workers.sort_by_key(|worker| worker.registered_at);
+        workers
+    }
+
+    /// Heard from within `timeout_seconds`
+    pub fn is_alive(&self, worker: &Worker, now: chrono::DateTime<chrono::Utc>) -> bool {
+        now - worker.last_seen < chrono::Duration::seconds(self.config.timeout_seconds as i64)
+    }
+
+    fn forget_silent(&self) {
+        let now = chrono::Utc::now();
+        let forget_after = chrono::Duration::seconds(self.config.timeout_seconds as i64 * FORGET_AFTER_TIMEOUTS as i64);
+        self.workers.retain(|id, worker| {
+            let keep = now - worker.last_seen < forget_after;
+            if !keep {
+                tracing::info!("Forgetting worker '{}' ({}), silent since {}", worker.name, id, worker.last_seen.to_rfc3339());
+            }
+            keep
+        });
+    }
+
+    async fn cleanup_task(&self) {
+        loop {
+            tokio::time::sleep(Duration::from_secs(60)).await; // Cleanup every minute
+            self.forget_silent();
+        }
+    }
+}
+
+/// Random 64-bit hex ID
+fn new_worker_id() -> String {
+    let mut bytes = [0u8; 8];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PetGeneratorConfig;
+    use crate::pet::{MemoryStorage, PetAddress};
+
+    #[tokio::test]
+    async fn test_workers_are_spread_over_pools_below_target() {
+        let storage = Arc::new(MemoryStorage::new());
+        let config: PetGeneratorConfig = serde_json::from_value(serde_json::json!({
+            "pool_size": 2,
+            "batch_size": 1,
+            "db_path": "",
+            "target_suffix": "Pet",
+            "extra_suffixes": ["Pin"]
+        }))
+        .unwrap();
+        let generator = PetGenerator::new(storage, config).await.unwrap();
+        let default_pool = generator.pool("default").unwrap();
+        default_pool.storage.store_address(PetAddress::generate(&default_pool.matcher).unwrap()).unwrap();
+
+        let registry = WorkerRegistry::new(WorkersConfig::default());
+        let first = registry.register("spot-1", Some(8));
+        let second = registry.register("spot-2", None);
+        // Pin needs two addresses, default one
+        assert_eq!(registry.assign(&first.id, &generator).unwrap().pool, "Pin");
+        let assignment = registry.assign(&second.id, &generator).unwrap();
+        assert_eq!(assignment.pool, "default");
+        assert_eq!(assignment.needed, 1);
+
+        registry.record_submission(&first.id, 2, 1, 0);
+        let worker = registry.heartbeat(&first.id, Some(12000.0), Some(50000)).unwrap();
+        assert_eq!((worker.accepted, worker.duplicates, worker.keys_tried), (2, 1, 50000));
+        assert!(registry.is_alive(&worker, chrono::Utc::now()));
+        assert!(!registry.is_alive(&worker, chrono::Utc::now() + chrono::Duration::seconds(3600)));
+        assert!(registry.heartbeat("unknown", None, None).is_none());
+    }
+}
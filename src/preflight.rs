@@ -0,0 +1,191 @@
+//! Startup checks run before the port is bound. A full disk, a database that
+//! cannot be written, a reset clock or a pattern that can never match stops
+//! the server with one clear message instead of failing later at runtime.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::pet::address::MAX_ATTEMPTS;
+use crate::pet::{match_probability, PatternPool, PetStorage};
+
+/// 2025-01-01T00:00:00Z. An earlier wall clock is a reset RTC or a missing
+/// NTP sync, which breaks token expiry, TLS and address ages.
+const CLOCK_FLOOR_SECONDS: i64 = 1_735_689_600;
+/// Records stored further in the future than this mean the clock went back
+const CLOCK_SKEW_TOLERANCE: chrono::Duration = chrono::Duration::minutes(5);
+/// Opening the database slower than this is worth a warning; probes may
+/// restart a pod that takes as long again on every start
+const SLOW_OPEN: Duration = Duration::from_secs(10);
+const MB: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Warn,
+    /// Stops the server
+    Fail,
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+/// Results of the startup checks, logged and judged by `finish`
+#[derive(Debug, Default)]
+pub struct Preflight {
+    checks: Vec<Check>,
+}
+
+impl Preflight {
+    pub fn checks(&self) -> &[Check] {
+        &self.checks
+    }
+
+    fn record(&mut self, name: &'static str, outcome: Outcome, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name,
+            outcome,
+            detail: detail.into(),
+        });
+    }
+
+    /// Free space on the volume holding `path` (or its nearest existing
+    /// ancestor, before the database is created)
+    pub fn check_disk(&mut self, path: &Path, min_free_bytes: u64) {
+        let Some(existing) = path.ancestors().find(|ancestor| ancestor.exists()) else {
+            self.record("disk", Outcome::Fail, format!("No part of {} exists", path.display()));
+            return;
+        };
+        match fs2::available_space(existing) {
+            Ok(free) if free < min_free_bytes => self.record(
+                "disk",
+                Outcome::Fail,
+                format!("{} MB free at {}, below the {} MB minimum", free / MB, existing.display(), min_free_bytes / MB),
+            ),
+            Ok(free) => self.record("disk", Outcome::Pass, format!("{} MB free at {}", free / MB, existing.display())),
+            Err(e) => self.record("disk", Outcome::Warn, format!("Free space of {} unknown: {}", existing.display(), e)),
+        }
+    }
+
+    /// The wall clock is past a sane floor, and no stored record is dated
+    /// after it
+    pub fn check_clock(&mut self, now: DateTime<Utc>, newest_record: Option<DateTime<Utc>>) {
+        let floor = DateTime::from_timestamp(CLOCK_FLOOR_SECONDS, 0).unwrap_or_default();
+        if now < floor {
+            self.record("clock", Outcome::Fail, format!("System time {} is before {}; check NTP", now.to_rfc3339(), floor.date_naive()));
+            return;
+        }
+        match newest_record {
+            Some(newest) if newest > now + CLOCK_SKEW_TOLERANCE => self.record(
+                "clock",
+                Outcome::Warn,
+                format!("Newest stored address was created at {}, after the current time {}; did the clock jump back?", newest.to_rfc3339(), now.to_rfc3339()),
+            ),
+            _ => self.record("clock", Outcome::Pass, now.to_rfc3339()),
+        }
+    }
+
+    /// How long the database took to open and load, then a write-read round trip
+    pub async fn check_database(&mut self, storage: &PetStorage) {
+        if let Some(opened_in) = storage.opened_in() {
+            let outcome = if opened_in > SLOW_OPEN { Outcome::Warn } else { Outcome::Pass };
+            self.record("database open", outcome, format!("Opened and recovered in {:.2?}", opened_in));
+        }
+        match storage.smoke_test().await {
+            Ok(Some(elapsed)) => self.record("database write", Outcome::Pass, format!("Write, flush and read back in {:.2?}", elapsed)),
+            Ok(None) => {}
+            Err(e) => self.record("database write", Outcome::Fail, format!("{:#}", e)),
+        }
+    }
+
+    /// Every pool's pattern can match, and usually within one search
+    pub fn check_patterns(&mut self, pools: &[PatternPool]) {
+        for pool in pools {
+            match match_probability(&pool.matcher) {
+                Ok(probability) if probability <= 0.0 => self.record(
+                    "pattern",
+                    Outcome::Fail,
+                    format!("Pool '{}' can never match {}", pool.name, pool.matcher.describe()),
+                ),
+                Ok(probability) if 1.0 / probability > MAX_ATTEMPTS as f64 => self.record(
+                    "pattern",
+                    Outcome::Warn,
+                    format!(
+                        "Pool '{}' needs ~{:.0} keys per address; searches give up after {}, so most fail",
+                        pool.name,
+                        1.0 / probability,
+                        MAX_ATTEMPTS
+                    ),
+                ),
+                Ok(probability) => self.record("pattern", Outcome::Pass, format!("Pool '{}': ~{:.0} keys per address", pool.name, 1.0 / probability)),
+                Err(e) => self.record("pattern", Outcome::Warn, format!("Pool '{}': difficulty unknown: {}", pool.name, e)),
+            }
+        }
+    }
+
+    /// Log every check and fail with all fatal ones at once
+    pub fn finish(self) -> Result<()> {
+        let mut failures = Vec::new();
+        for check in self.checks {
+            match check.outcome {
+                Outcome::Pass => tracing::info!("✈️  Preflight {}: {}", check.name, check.detail),
+                Outcome::Warn => tracing::warn!("✈️  Preflight {}: {}", check.name, check.detail),
+                Outcome::Fail => {
+                    tracing::error!("✈️  Preflight {} failed: {}", check.name, check.detail);
+                    failures.push(format!("{}: {}", check.name, check.detail));
+                }
+            }
+        }
+        match failures.is_empty() {
+            true => Ok(()),
+            false => anyhow::bail!("Preflight checks failed ({}); set [preflight].enabled = false to skip them", failures.join("; ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::{AddressMatcher, MemoryStorage};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_fatal_checks_are_reported_together() {
+        let pool = |name: &str, suffix: &str| PatternPool {
+            name: name.to_string(),
+            matcher: Arc::new(AddressMatcher::Suffix {
+                suffix: suffix.to_string(),
+                case_sensitive: true,
+            }),
+            storage: Arc::new(MemoryStorage::new()),
+        };
+        let now = Utc::now();
+
+        let mut preflight = Preflight::default();
+        preflight.check_disk(&std::env::temp_dir().join("pinpet-preflight/pet.db"), 0);
+        preflight.check_clock(now, Some(now - chrono::Duration::hours(1)));
+        preflight.check_patterns(&[pool("default", "Pet"), pool("Hard", "Pinpet")]);
+        let outcomes: Vec<_> = preflight.checks().iter().map(|check| check.outcome).collect();
+        assert_eq!(outcomes, [Outcome::Pass, Outcome::Pass, Outcome::Pass, Outcome::Warn]);
+        assert!(preflight.finish().is_ok());
+
+        let mut preflight = Preflight::default();
+        preflight.check_disk(&std::env::temp_dir(), u64::MAX);
+        preflight.check_clock(DateTime::from_timestamp(0, 0).unwrap(), None);
+        preflight.check_clock(now, Some(now + chrono::Duration::hours(1)));
+        // '0' is not base58
+        preflight.check_patterns(&[pool("zero", "0")]);
+        let outcomes: Vec<_> = preflight.checks().iter().map(|check| check.outcome).collect();
+        assert_eq!(outcomes, [Outcome::Fail, Outcome::Fail, Outcome::Warn, Outcome::Fail]);
+
+        let error = preflight.finish().unwrap_err().to_string();
+        for expected in ["disk:", "clock: System time 1970", "pattern: Pool 'zero' can never match"] {
+            assert!(error.contains(expected), "{} missing from {}", expected, error);
+        }
+    }
+}
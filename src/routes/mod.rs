@@ -1,7 +1,10 @@
-use axum::{routing::get, Router};
+use axum::{extract::DefaultBodyLimit, http::header, middleware::from_fn_with_state, routing::{get, post}, Router};
 use std::sync::Arc;
-use crate::handlers::{health_check, detailed_health_check, get_server_time, get_multi_timezone, get_pet_address, get_pet_status, PetAppState};
+use crate::handlers::{health_check, detailed_health_check, healthz, livez, readyz, get_server_time, get_multi_timezone, get_pet_address, get_pet_status, get_pattern_address, get_pattern_status, get_pet_address_by_letter, get_pattern_address_by_letter, pop_pet_addresses, pop_pattern_addresses, acknowledge_pet_address, acknowledge_pattern_address, reveal_pet_private_key, reveal_pattern_private_key, reveal_tenant_private_key, prove_pet_address, prove_pattern_address, prove_tenant_address, register_recipient_key, get_recipient_key, delete_recipient_key, peek_pet_addresses, peek_pattern_addresses, get_difficulty_estimate, validate_address, get_queue_diagnostics, get_dispensed_addresses, get_audit_log, verify_audit_log, export_addresses, import_addresses, verify_keys, get_address_keyfile, stream_generator_events, create_job, get_job, get_tenant_address, get_tenant_status, get_usage, get_metrics, get_generator_stats, get_suffix_stats, subscribe_addresses, get_generator_control, pause_generator, resume_generator, throttle_generator, get_runtime_config, update_runtime_config, reset_runtime_config, reload_runtime_config, create_subscription, get_subscription, cancel_subscription, submit_keypairs, register_worker, worker_heartbeat, list_workers,
+    replica_store_address, replica_pop_addresses, replica_pool_status, PetAppState};
 use crate::config::AppConfig;
+use crate::middleware::unversioned_path_middleware;
+use crate::models::MAX_IMPORT_BYTES;
 
 pub fn health_routes() -> Router {
     Router::new()
@@ -9,6 +12,14 @@ pub fn health_routes() -> Router {
         .route("/health/detailed", get(detailed_health_check))
 }
 
+/// Kubernetes-style probes, outside the versioned API prefix
+pub fn probe_routes() -> Router<Arc<PetAppState>> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz))
+}
+
 pub fn time_routes() -> Router {
     Router::new()
         .route("/time", get(get_server_time))
@@ -18,19 +29,119 @@ pub fn time_routes() -> Router {
 pub fn pet_routes() -> Router<Arc<PetAppState>> {
     Router::new()
         .route("/pet/address", get(get_pet_address))
+        .route("/pet/{pattern}/address", get(get_pattern_address))
+        .route("/pet/address/next", get(get_pet_address_by_letter))
+        .route("/pet/{pattern}/address/next", get(get_pattern_address_by_letter))
+        .route("/pet/addresses/pop", post(pop_pet_addresses))
+        .route("/pet/{pattern}/addresses/pop", post(pop_pattern_addresses))
+        .route("/pet/address/{id}/ack", post(acknowledge_pet_address))
+        .route("/pet/{pattern}/address/{id}/ack", post(acknowledge_pattern_address))
+        .route("/pet/address/{id}/reveal", post(reveal_pet_private_key))
+        .route("/pet/{pattern}/address/{id}/reveal", post(reveal_pattern_private_key))
+        .route("/tenants/{tenant}/address/next", get(get_tenant_address))
+        .route("/tenants/{tenant}/address/{id}/reveal", post(reveal_tenant_private_key))
+        .route("/subscriptions", post(create_subscription))
+        .route("/jobs", post(create_job))
+        .route("/jobs/{id}", get(get_job))
 }
 
 pub fn pet_status_routes() -> Router<Arc<PetAppState>> {
     Router::new()
         .route("/pet/status", get(get_pet_status))
+        .route("/pet/{pattern}/status", get(get_pattern_status))
+        .route("/pet/address/peek", get(peek_pet_addresses))
+        .route("/pet/{pattern}/address/peek", get(peek_pattern_addresses))
+        .route("/tenants/{tenant}/status", get(get_tenant_status))
+        .route("/pet/address/{id}/prove", post(prove_pet_address))
+        .route("/pet/{pattern}/address/{id}/prove", post(prove_pattern_address))
+        .route("/tenants/{tenant}/address/{id}/prove", post(prove_tenant_address))
+        .route(
+            "/tenants/{tenant}/recipient-key",
+            get(get_recipient_key).put(register_recipient_key).delete(delete_recipient_key),
+        )
+        .route("/estimate", get(get_difficulty_estimate))
+        .route("/validate", post(validate_address))
+        .route("/events", get(stream_generator_events))
+        .route("/subscriptions/{id}", get(get_subscription).delete(cancel_subscription))
+}
+
+pub fn admin_routes() -> Router<Arc<PetAppState>> {
+    Router::new()
+        .route("/admin/diagnostics", get(get_queue_diagnostics))
+        .route("/admin/dispensed", get(get_dispensed_addresses))
+        .route("/admin/audit", get(get_audit_log))
+        .route("/admin/audit/verify", get(verify_audit_log))
+        .route("/admin/export", get(export_addresses))
+        .route("/admin/import", post(import_addresses).layer(DefaultBodyLimit::max(MAX_IMPORT_BYTES)))
+        .route("/admin/verify", get(verify_keys))
+        .route("/admin/address/{pubkey}/keyfile", get(get_address_keyfile))
+        .route("/admin/workers", get(list_workers))
+        .route("/admin/generator", get(get_generator_control))
+        .route("/admin/generator/pause", post(pause_generator))
+        .route("/admin/generator/resume", post(resume_generator))
+        .route("/admin/generator/throttle", post(throttle_generator))
+        .route("/admin/config", get(get_runtime_config).patch(update_runtime_config).delete(reset_runtime_config))
+        .route("/admin/reload", post(reload_runtime_config))
+        .route("/usage", get(get_usage))
+}
+
+/// Submissions from remote grind workers, outside the versioned API prefix
+pub fn worker_routes() -> Router<Arc<PetAppState>> {
+    Router::new()
+        .route("/workers/register", post(register_worker))
+        .route("/workers/{id}/heartbeat", post(worker_heartbeat))
+        .route("/workers/submit", post(submit_keypairs))
+}
+
+/// Pools served to replicas using the `primary` storage backend
+pub fn replica_routes() -> Router<Arc<PetAppState>> {
+    Router::new()
+        .route("/replicas/pools/{pool}/addresses", post(replica_store_address))
+        .route("/replicas/pools/{pool}/pop", post(replica_pop_addresses))
+        .route("/replicas/pools/{pool}/status", get(replica_pool_status))
+}
+
+/// Where the OpenAPI document is served
+pub const OPENAPI_PATH: &str = "/openapi.json";
+
+/// Serve the pre-rendered OpenAPI document `spec`
+pub fn openapi_routes(spec: String) -> Router {
+    Router::new().route(
+        OPENAPI_PATH,
+        get(move || {
+            let spec = spec.clone();
+            async move { ([(header::CONTENT_TYPE, "application/json")], spec) }
+        }),
+    )
+}
+
+/// WebSocket feed of new addresses, outside the versioned API prefix
+pub fn websocket_routes() -> Router<Arc<PetAppState>> {
+    Router::new().route("/ws", get(subscribe_addresses))
+}
+
+pub fn metrics_routes() -> Router<Arc<PetAppState>> {
+    Router::new()
+        .route("/metrics", get(get_metrics))
+        .route("/stats/generator", get(get_generator_stats))
+        .route("/stats/suffixes", get(get_suffix_stats))
 }
 
 pub fn api_routes(config: &AppConfig) -> (Router, Router<Arc<PetAppState>>, Router<Arc<PetAppState>>) {
     let api_prefix = &config.api_base_url();
     
-    let time_api = Router::new().nest(api_prefix, time_routes());
-    let pet_api = Router::new().nest(api_prefix, pet_routes());
-    let pet_status_api = Router::new().nest(api_prefix, pet_status_routes());
+    let mut time_api = Router::new().nest(api_prefix, time_routes());
+    let mut pet_api = Router::new().nest(api_prefix, pet_routes());
+    let mut pet_status_api = Router::new().nest(api_prefix, pet_status_routes());
+
+    // Compatibility layer: the same handlers without the version segment
+    if let Some(unversioned_prefix) = config.unversioned_base_url() {
+        let versioned_prefix: Arc<str> = Arc::from(api_prefix.as_str());
+        let deprecated = || from_fn_with_state(Arc::clone(&versioned_prefix), unversioned_path_middleware);
+        time_api = time_api.nest(unversioned_prefix, time_routes().layer(deprecated()));
+        pet_api = pet_api.nest(unversioned_prefix, pet_routes().layer(deprecated()));
+        pet_status_api = pet_status_api.nest(unversioned_prefix, pet_status_routes().layer(deprecated()));
+    }
     
     (time_api, pet_api, pet_status_api)
 }
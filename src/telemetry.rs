@@ -0,0 +1,712 @@
+//! OpenTelemetry trace export: `tracing` spans sent to an OTLP/HTTP collector
+//! (Jaeger, Tempo, the OpenTelemetry Collector) as JSON, configured by the
+//! standard `OTEL_*` variables. Exported are the request span of every HTTP
+//! request, plus storage operations and generator batches, which are spans
+//! under the `otel` target that the log output never shows.
+//!
+//! Written against the OTLP specification instead of the `opentelemetry`
+//! crates, like the other clients in this crate: spans are batched on a
+//! background thread and posted with the small HTTP client in `pet::http`.
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context as LayerContext;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::pet::http::{self, Url};
+
+/// Target of spans meant only for trace export, e.g.
+/// `tracing::info_span!(target: TRACE_TARGET, "storage.store")`
+pub const TRACE_TARGET: &str = "otel";
+
+/// Name of the span `logging_layer` opens for every HTTP request
+const REQUEST_SPAN: &str = "request";
+
+const DEFAULT_ENDPOINT: &str = "http://localhost:4318";
+const DEFAULT_SERVICE_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// Events kept per span; later ones are counted as dropped
+const MAX_EVENTS_PER_SPAN: usize = 64;
+
+/// Feeds the exporter thread once tracing export is on
+static EXPORTER: OnceLock<SyncSender<Message>> = OnceLock::new();
+
+/// Whether spans are being exported
+pub fn is_enabled() -> bool {
+    EXPORTER.get().is_some()
+}
+
+/// Export the spans still queued, waiting at most `timeout`
+pub fn flush(timeout: Duration) {
+    let Some(exporter) = EXPORTER.get() else {
+        return;
+    };
+    let (done, finished) = mpsc::sync_channel(1);
+    if exporter.send(Message::Flush(done)).is_ok() && finished.recv_timeout(timeout).is_err() {
+        tracing::warn!("Trace export did not finish within {:?}", timeout);
+    }
+}
+
+/// Export settings from the `OTEL_*` variables
+#[derive(Debug, Clone, PartialEq)]
+pub struct OtelSettings {
+    /// Full URL spans are posted to, e.g. `http://tempo:4318/v1/traces`
+    pub traces_url: String,
+    pub headers: Vec<(String, String)>,
+    pub resource: Vec<(String, String)>,
+    pub sampler: Sampler,
+    pub timeout: Duration,
+    pub schedule_delay: Duration,
+    pub max_queue_size: usize,
+    pub max_batch_size: usize,
+}
+
+impl OtelSettings {
+    /// `None` unless an OTLP endpoint is set or `OTEL_TRACES_EXPORTER=otlp`;
+    /// `OTEL_SDK_DISABLED=true` and `OTEL_TRACES_EXPORTER=none` turn export off
+    pub fn from_env() -> Result<Option<Self>> {
+        Self::from_lookup(|name| std::env::var(name).ok().filter(|value| !value.trim().is_empty()))
+    }
+
+    fn from_lookup(var: impl Fn(&str) -> Option<String>) -> Result<Option<Self>> {
+        if var("OTEL_SDK_DISABLED").is_some_and(|disabled| disabled.eq_ignore_ascii_case("true")) {
+            return Ok(None);
+        }
+        let exporter = var("OTEL_TRACES_EXPORTER").map(|exporter| exporter.to_ascii_lowercase());
+        let traces_endpoint = var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT");
+        let endpoint = var("OTEL_EXPORTER_OTLP_ENDPOINT");
+        match exporter.as_deref() {
+            Some("none") => return Ok(None),
+            Some("otlp") => {}
+            Some(other) => bail!("OTEL_TRACES_EXPORTER '{}' is not supported; use otlp or none", other),
+            None if traces_endpoint.is_none() && endpoint.is_none() => return Ok(None),
+            None => {}
+        }
+
+        let protocol = var("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL").or_else(|| var("OTEL_EXPORTER_OTLP_PROTOCOL"));
+        if let Some(protocol) = protocol.filter(|protocol| protocol != "http/json") {
+            bail!("OTEL_EXPORTER_OTLP_PROTOCOL '{}' is not supported; set it to http/json", protocol);
+        }
+
+        // A signal-specific endpoint is used as is; the generic one gets the signal path
+        let traces_url = match traces_endpoint {
+            Some(url) => url,
+            None => format!("{}/v1/traces", endpoint.as_deref().unwrap_or(DEFAULT_ENDPOINT).trim_end_matches('/')),
+        };
+        Url::parse(&traces_url).with_context(|| format!("Invalid OTLP traces endpoint '{}'", traces_url))?;
+
+        let headers = var("OTEL_EXPORTER_OTLP_TRACES_HEADERS").or_else(|| var("OTEL_EXPORTER_OTLP_HEADERS"));
+        let mut resource = parse_pairs(&var("OTEL_RESOURCE_ATTRIBUTES").unwrap_or_default())?;
+        let service_name = var("OTEL_SERVICE_NAME")
+            .or_else(|| resource.iter().find(|(key, _)| key == "service.name").map(|(_, value)| value.clone()))
+            .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+        resource.retain(|(key, _)| key != "service.name");
+        resource.insert(0, ("service.name".to_string(), service_name));
+
+        let millis = |name: &str, default: u64| -> Result<Duration> {
+            match var(name) {
+                Some(value) => Ok(Duration::from_millis(value.trim().parse().with_context(|| format!("{} must be milliseconds", name))?)),
+                None => Ok(Duration::from_millis(default)),
+            }
+        };
+        let count = |name: &str, default: usize| -> Result<usize> {
+            match var(name) {
+                Some(value) => value.trim().parse().with_context(|| format!("{} must be a positive integer", name)),
+                None => Ok(default),
+            }
+        };
+
+        Ok(Some(Self {
+            traces_url,
+            headers: parse_pairs(&headers.unwrap_or_default())?,
+            resource,
+            sampler: Sampler::parse(var("OTEL_TRACES_SAMPLER").as_deref(), var("OTEL_TRACES_SAMPLER_ARG").as_deref())?,
+            timeout: millis("OTEL_EXPORTER_OTLP_TIMEOUT", 10_000)?,
+            schedule_delay: millis("OTEL_BSP_SCHEDULE_DELAY", 5_000)?,
+            max_queue_size: count("OTEL_BSP_MAX_QUEUE_SIZE", 2048)?.max(1),
+            max_batch_size: count("OTEL_BSP_MAX_EXPORT_BATCH_SIZE", 512)?.max(1),
+        }))
+    }
+}
+
+/// `key=value,key=value` with percent-encoded values, as in `OTEL_EXPORTER_OTLP_HEADERS`
+fn parse_pairs(list: &str) -> Result<Vec<(String, String)>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), percent_decode(value.trim()))),
+            _ => bail!("Expected key=value, got '{}'", pair),
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Which traces are recorded, from `OTEL_TRACES_SAMPLER` and `OTEL_TRACES_SAMPLER_ARG`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sampler {
+    AlwaysOn,
+    AlwaysOff,
+    /// This fraction of traces, chosen by trace ID
+    Ratio(f64),
+    /// Follow the caller's `traceparent` decision; new traces use the inner sampler
+    ParentBased(Box<Sampler>),
+}
+
+impl Sampler {
+    fn parse(name: Option<&str>, arg: Option<&str>) -> Result<Self> {
+        let ratio = || -> Result<f64> {
+            let ratio = match arg {
+                Some(arg) => arg.trim().parse().context("OTEL_TRACES_SAMPLER_ARG must be a number")?,
+                None => 1.0,
+            };
+            match (0.0..=1.0).contains(&ratio) {
+                true => Ok(ratio),
+                false => bail!("OTEL_TRACES_SAMPLER_ARG must be between 0 and 1"),
+            }
+        };
+        Ok(match name.unwrap_or("parentbased_always_on") {
+            "always_on" => Self::AlwaysOn,
+            "always_off" => Self::AlwaysOff,
+            "traceidratio" => Self::Ratio(ratio()?),
+            "parentbased_always_on" => Self::ParentBased(Box::new(Self::AlwaysOn)),
+            "parentbased_always_off" => Self::ParentBased(Box::new(Self::AlwaysOff)),
+            "parentbased_traceidratio" => Self::ParentBased(Box::new(Self::Ratio(ratio()?))),
+            other => bail!("OTEL_TRACES_SAMPLER '{}' is not supported", other),
+        })
+    }
+
+    /// Whether to record a trace; `parent` is the caller's decision, if any
+    fn should_sample(&self, trace_id: &[u8; 16], parent: Option<bool>) -> bool {
+        match (self, parent) {
+            (Self::ParentBased(_), Some(sampled)) => sampled,
+            (Self::ParentBased(root), None) => root.should_sample(trace_id, None),
+            (Self::AlwaysOn, _) => true,
+            (Self::AlwaysOff, _) => false,
+            (Self::Ratio(ratio), _) => {
+                let low = u64::from_be_bytes(trace_id[8..].try_into().unwrap_or_default());
+                (low as f64) < ratio * u64::MAX as f64
+            }
+        }
+    }
+}
+
+/// A span being recorded, kept in the span's extensions
+struct SpanData {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    sampled: bool,
+    start: u64,
+    attributes: Vec<(&'static str, Value)>,
+    events: Vec<Value>,
+    dropped_events: u32,
+    error: Option<String>,
+}
+
+enum Message {
+    Span(Value),
+    Flush(SyncSender<()>),
+}
+
+/// `tracing` layer that records spans and hands finished, sampled ones to the exporter thread
+pub struct OtlpLayer {
+    traces_url: String,
+    sampler: Sampler,
+    sender: SyncSender<Message>,
+    dropped: AtomicU64,
+}
+
+impl OtlpLayer {
+    /// The layer configured by the `OTEL_*` variables, with its exporter
+    /// thread started; `None` when export is off
+    pub fn from_env() -> Result<Option<Self>> {
+        match OtelSettings::from_env()? {
+            Some(settings) => Self::start(settings).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn start(settings: OtelSettings) -> Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel(settings.max_queue_size);
+        if EXPORTER.set(sender.clone()).is_err() {
+            bail!("Trace export is already running");
+        }
+        let (traces_url, sampler) = (settings.traces_url.clone(), settings.sampler.clone());
+        std::thread::Builder::new()
+            .name("otlp-exporter".to_string())
+            .spawn(move || Exporter::new(settings).run(receiver))?;
+        Ok(Self {
+            traces_url,
+            sampler,
+            sender,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// Where spans are posted
+    pub fn traces_url(&self) -> &str {
+        &self.traces_url
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = Fields::default();
+        attrs.record(&mut visitor);
+
+        let parent = span.parent().and_then(|parent| {
+            let extensions = parent.extensions();
+            let data = extensions.get::<SpanData>()?;
+            Some((data.trace_id, data.span_id, data.sampled))
+        });
+        // A root span continues the caller's trace when it sent a `traceparent`
+        let parent = parent.or_else(|| visitor.traceparent.as_deref().and_then(parse_traceparent));
+        let (trace_id, parent_span_id, sampled) = match parent {
+            Some((trace_id, span_id, sampled)) => (trace_id, Some(span_id), self.sampler.should_sample(&trace_id, Some(sampled))),
+            None => {
+                let trace_id = rand::random();
+                (trace_id, None, self.sampler.should_sample(&trace_id, None))
+            }
+        };
+
+        span.extensions_mut().insert(SpanData {
+            trace_id,
+            span_id: rand::random(),
+            parent_span_id,
+            sampled,
+            start: unix_nanos(),
+            attributes: visitor.values,
+            events: Vec::new(),
+            dropped_events: 0,
+            error: None,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = Fields::default();
+        values.record(&mut visitor);
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanData>() {
+            for (name, value) in visitor.values {
+                data.attributes.retain(|(existing, _)| *existing != name);
+                data.attributes.push((name, value));
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.get_mut::<SpanData>().filter(|data| data.sampled) else {
+            return;
+        };
+
+        let mut visitor = Fields::default();
+        event.record(&mut visitor);
+        let message = visitor.message.unwrap_or_else(|| event.metadata().name().to_string());
+        if *event.metadata().level() == Level::ERROR {
+            data.error = Some(message.clone());
+        }
+        if data.events.len() >= MAX_EVENTS_PER_SPAN {
+            data.dropped_events += 1;
+            return;
+        }
+        let mut attributes = visitor.values;
+        attributes.push(("level", Value::from(event.metadata().level().as_str())));
+        data.events.push(json!({
+            "timeUnixNano": unix_nanos().to_string(),
+            "name": message,
+            "attributes": otlp_attributes(attributes),
+        }));
+    }
+
+    fn on_close(&self, id: Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(data) = span.extensions_mut().remove::<SpanData>().filter(|data| data.sampled) else {
+            return;
+        };
+        let encoded = encode_span(span.name(), data, unix_nanos());
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(Message::Span(encoded)) {
+            // Reported rarely so a stalled collector does not flood the log
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped.is_power_of_two() {
+                eprintln!("Trace export queue is full; {} spans dropped so far", dropped);
+            }
+        }
+    }
+}
+
+/// Span fields, with `message` and `traceparent` kept apart
+#[derive(Default)]
+struct Fields {
+    values: Vec<(&'static str, Value)>,
+    message: Option<String>,
+    traceparent: Option<String>,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = Some(value.to_string()),
+            "traceparent" => self.traceparent = Some(value.to_string()),
+            name => self.values.push((name, Value::from(value))),
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.values.push((field.name(), Value::from(value)));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.values.push((field.name(), Value::from(value)));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.values.push((field.name(), Value::from(value)));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.values.push((field.name(), Value::from(value)));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+/// `00-{trace id}-{parent id}-{flags}` → trace ID, parent span ID and whether the caller sampled
+fn parse_traceparent(header: &str) -> Option<([u8; 16], [u8; 8], bool)> {
+    let mut parts = header.trim().split('-');
+    let (version, trace_id, span_id, flags) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+    if version != "00" || parts.next().is_some() {
+        return None;
+    }
+    let trace_id: [u8; 16] = decode_hex(trace_id)?.try_into().ok()?;
+    let span_id: [u8; 8] = decode_hex(span_id)?.try_into().ok()?;
+    let flags = u8::from_str_radix(flags, 16).ok()?;
+    if trace_id == [0; 16] || span_id == [0; 8] {
+        return None;
+    }
+    Some((trace_id, span_id, flags & 1 == 1))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+/// One OTLP/JSON span. The request span is named and attributed after the
+/// HTTP server conventions (`GET /api/v1/pet/address`, `http.route`, ...).
+fn encode_span(name: &str, data: SpanData, end: u64) -> Value {
+    let request = name == REQUEST_SPAN;
+    let mut status = json!({});
+    let mut attributes = data.attributes;
+    if request {
+        let field = |name: &str| attributes.iter().find(|(key, _)| *key == name).map(|(_, value)| value.clone());
+        let server_error = field("status").and_then(|status| status.as_u64()).is_some_and(|status| status >= 500);
+        if server_error {
+            status = json!({ "code": 2 });
+        }
+        attributes = attributes
+            .into_iter()
+            .map(|(key, value)| match key {
+                "method" => ("http.request.method", value),
+                "route" => ("http.route", value),
+                "status" => ("http.response.status_code", value),
+                "uri" => ("url.path", Value::from(value.as_str().unwrap_or_default().split('?').next().unwrap_or_default())),
+                other => (other, value),
+            })
+            .collect();
+    }
+    if let Some(message) = data.error {
+        status = json!({ "code": 2, "message": message });
+    }
+
+    let span_name = match request {
+        true => {
+            let field = |name: &str| attributes.iter().find(|(key, _)| *key == name).and_then(|(_, value)| value.as_str().map(String::from));
+            match (field("http.request.method"), field("http.route")) {
+                (Some(method), Some(route)) => format!("{} {}", method, route),
+                (Some(method), None) => method,
+                _ => name.to_string(),
+            }
+        }
+        false => name.to_string(),
+    };
+    // `otel.name` renames a span whose name has to be a literal, e.g. `storage.pop`
+    let span_name = match attributes.iter().position(|(key, _)| *key == "otel.name") {
+        Some(index) => attributes.remove(index).1.as_str().map_or(span_name, String::from),
+        None => span_name,
+    };
+
+    let mut span = json!({
+        "traceId": encode_hex(&data.trace_id),
+        "spanId": encode_hex(&data.span_id),
+        "name": span_name,
+        // SPAN_KIND_SERVER for requests, SPAN_KIND_INTERNAL otherwise
+        "kind": if request { 2 } else { 1 },
+        "startTimeUnixNano": data.start.to_string(),
+        "endTimeUnixNano": end.to_string(),
+        "attributes": otlp_attributes(attributes),
+        "events": data.events,
+        "droppedEventsCount": data.dropped_events,
+        "status": status,
+    });
+    if let Some(parent) = data.parent_span_id {
+        span["parentSpanId"] = Value::from(encode_hex(&parent));
+    }
+    span
+}
+
+fn otlp_attributes<K: AsRef<str>>(attributes: Vec<(K, Value)>) -> Vec<Value> {
+    attributes
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::Bool(value) => json!({ "boolValue": value }),
+                Value::Number(number) if number.is_f64() => json!({ "doubleValue": number }),
+                // 64-bit integers are strings in OTLP/JSON
+                Value::Number(number) => json!({ "intValue": number.to_string() }),
+                Value::String(value) => json!({ "stringValue": value }),
+                other => json!({ "stringValue": other.to_string() }),
+            };
+            json!({ "key": key.as_ref(), "value": value })
+        })
+        .collect()
+}
+
+/// Batches finished spans and posts them to the collector
+struct Exporter {
+    url: Url,
+    headers: Vec<(&'static str, String)>,
+    extra_headers: Vec<(String, String)>,
+    resource: Value,
+    timeout: Duration,
+    schedule_delay: Duration,
+    max_batch_size: usize,
+    failing: bool,
+}
+
+impl Exporter {
+    fn new(settings: OtelSettings) -> Self {
+        Self {
+            // Checked when the settings were read
+            url: Url::parse(&settings.traces_url).expect("valid OTLP endpoint"),
+            headers: vec![("Content-Type", "application/json".to_string())],
+            extra_headers: settings.headers,
+            resource: json!({ "attributes": otlp_attributes(settings.resource.into_iter().map(|(key, value)| (key, Value::from(value))).collect()) }),
+            timeout: settings.timeout,
+            schedule_delay: settings.schedule_delay,
+            max_batch_size: settings.max_batch_size,
+            failing: false,
+        }
+    }
+
+    fn run(mut self, receiver: Receiver<Message>) {
+        let mut batch = Vec::new();
+        let mut deadline = Instant::now() + self.schedule_delay;
+        loop {
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(Message::Span(span)) => {
+                    batch.push(span);
+                    if batch.len() < self.max_batch_size {
+                        continue;
+                    }
+                }
+                Ok(Message::Flush(done)) => {
+                    self.export(std::mem::take(&mut batch));
+                    let _ = done.send(());
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.export(batch);
+                    return;
+                }
+            }
+            self.export(std::mem::take(&mut batch));
+            deadline = Instant::now() + self.schedule_delay;
+        }
+    }
+
+    fn export(&mut self, spans: Vec<Value>) {
+        if spans.is_empty() {
+            return;
+        }
+        let count = spans.len();
+        let body = json!({
+            "resourceSpans": [{
+                "resource": self.resource,
+                "scopeSpans": [{
+                    "scope": { "name": DEFAULT_SERVICE_NAME, "version": env!("CARGO_PKG_VERSION") },
+                    "spans": spans,
+                }],
+            }],
+        });
+        let headers: Vec<(&str, String)> = self
+            .headers
+            .iter()
+            .cloned()
+            .chain(self.extra_headers.iter().map(|(name, value)| (name.as_str(), value.clone())))
+            .collect();
+        let result = serde_json::to_vec(&body)
+            .map_err(anyhow::Error::from)
+            .and_then(|body| http::send(&self.url, "POST", &self.url.path, &headers, &body, self.timeout));
+
+        // Only changes are logged, so an unreachable collector logs once
+        match result {
+            Ok(response) if response.is_success() => {
+                if std::mem::replace(&mut self.failing, false) {
+                    tracing::info!("Trace export to {} recovered", self.url.host_header());
+                }
+            }
+            Ok(response) => self.report_failure(count, response.describe()),
+            Err(e) => self.report_failure(count, format!("{:#}", e)),
+        }
+    }
+
+    fn report_failure(&mut self, count: usize, reason: String) {
+        if !std::mem::replace(&mut self.failing, true) {
+            tracing::warn!("Failed to export {} spans to {}: {}", count, self.url.host_header(), reason);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_settings_follow_the_otel_variables() {
+        let settings = |vars: &[(&str, &str)]| {
+            let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            OtelSettings::from_lookup(|name| vars.get(name).cloned())
+        };
+
+        assert_eq!(settings(&[]).unwrap(), None);
+        assert_eq!(settings(&[("OTEL_EXPORTER_OTLP_ENDPOINT", "http://tempo:4318"), ("OTEL_SDK_DISABLED", "true")]).unwrap(), None);
+
+        let enabled = settings(&[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://tempo:4318/"),
+            ("OTEL_EXPORTER_OTLP_HEADERS", "authorization=Basic%20abc, x-scope-orgid=team-a"),
+            ("OTEL_RESOURCE_ATTRIBUTES", "service.name=from-resource,deployment.environment=prod"),
+            ("OTEL_SERVICE_NAME", "pinpet-eu"),
+            ("OTEL_TRACES_SAMPLER", "parentbased_traceidratio"),
+            ("OTEL_TRACES_SAMPLER_ARG", "0.25"),
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(enabled.traces_url, "http://tempo:4318/v1/traces");
+        assert_eq!(enabled.headers[0], ("authorization".to_string(), "Basic abc".to_string()));
+        assert_eq!(enabled.resource[0], ("service.name".to_string(), "pinpet-eu".to_string()));
+        assert_eq!(enabled.resource.len(), 2);
+        assert_eq!(enabled.sampler, Sampler::ParentBased(Box::new(Sampler::Ratio(0.25))));
+
+        let defaults = settings(&[("OTEL_TRACES_EXPORTER", "otlp")]).unwrap().unwrap();
+        assert_eq!(defaults.traces_url, "http://localhost:4318/v1/traces");
+        assert_eq!(defaults.resource[0].1, DEFAULT_SERVICE_NAME);
+
+        assert!(settings(&[("OTEL_TRACES_EXPORTER", "otlp"), ("OTEL_EXPORTER_OTLP_PROTOCOL", "grpc")]).is_err());
+        assert!(settings(&[("OTEL_TRACES_EXPORTER", "zipkin")]).is_err());
+        assert!(settings(&[("OTEL_TRACES_EXPORTER", "otlp"), ("OTEL_TRACES_SAMPLER", "traceidratio"), ("OTEL_TRACES_SAMPLER_ARG", "2")]).is_err());
+    }
+
+    #[test]
+    fn test_traceparent_and_sampling() {
+        let (trace_id, span_id, sampled) = parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        assert_eq!(encode_hex(&trace_id), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(encode_hex(&span_id), "00f067aa0ba902b7");
+        assert!(sampled);
+        assert!(parse_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none());
+        assert!(parse_traceparent("zz-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").is_none());
+
+        let parent_based = Sampler::ParentBased(Box::new(Sampler::AlwaysOff));
+        assert!(parent_based.should_sample(&trace_id, Some(true)));
+        assert!(!parent_based.should_sample(&trace_id, None));
+        assert!(!Sampler::Ratio(0.0).should_sample(&trace_id, Some(true)));
+        assert!(Sampler::Ratio(1.0).should_sample(&[0xff; 16], None) || Sampler::Ratio(1.0).should_sample(&trace_id, None));
+    }
+
+    #[test]
+    fn test_request_spans_follow_the_http_conventions() {
+        let data = SpanData {
+            trace_id: [1; 16],
+            span_id: [2; 8],
+            parent_span_id: Some([3; 8]),
+            sampled: true,
+            start: 10,
+            attributes: vec![
+                ("method", Value::from("GET")),
+                ("uri", Value::from("/api/v1/pet/address?count=2")),
+                ("route", Value::from("/api/v1/pet/address")),
+                ("status", Value::from(503u64)),
+            ],
+            events: Vec::new(),
+            dropped_events: 0,
+            error: None,
+        };
+        let span = encode_span(REQUEST_SPAN, data, 20);
+        assert_eq!(span["name"], "GET /api/v1/pet/address");
+        assert_eq!(span["kind"], 2);
+        assert_eq!(span["parentSpanId"], "0303030303030303");
+        assert_eq!(span["status"]["code"], 2);
+        assert_eq!(span["endTimeUnixNano"], "20");
+        let attributes = span["attributes"].as_array().unwrap();
+        assert!(attributes.contains(&json!({"key": "url.path", "value": {"stringValue": "/api/v1/pet/address"}})));
+        assert!(attributes.contains(&json!({"key": "http.response.status_code", "value": {"intValue": "503"}})));
+    }
+}
@@ -0,0 +1,190 @@
+//! HTTPS listener (rustls). The certificate and key are PEM files; with
+//! `reload_interval_seconds` set they are watched and swapped in without a
+//! restart, so certificate renewals (certbot, cert-manager) just work.
+//! With `client_ca_path` set, clients may also present a certificate from
+//! that CA (mutual TLS); requests of such connections carry it.
+
+use anyhow::{Context, Result};
+use axum::Router;
+use axum_server::accept::{Accept, DefaultAcceptor};
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use axum_server::Handle;
+use futures_util::future::BoxFuture;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::Notify;
+use tokio_rustls::server::TlsStream;
+use tower::Layer;
+use tower_http::add_extension::{AddExtension, AddExtensionLayer};
+
+use crate::config::TlsConfig;
+use crate::middleware::ClientCertificate;
+
+/// Bind `addr` and serve `app` over TLS. The returned future resolves once
+/// `stop_accepting` is notified and every connection has finished.
+pub async fn serve(
+    tls: &TlsConfig,
+    addr: &str,
+    app: Router,
+    stop_accepting: Arc<Notify>,
+) -> Result<impl Future<Output = std::io::Result<()>>> {
+    // Both ring and aws-lc-rs may be compiled in through other crates; pick one
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let rustls_config = match tls.client_ca_path.is_empty() {
+        true => RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path).await.map_err(anyhow::Error::from),
+        false => server_config(tls).map(|config| RustlsConfig::from_config(Arc::new(config))),
+    }
+    .with_context(|| format!("Failed to load TLS certificate '{}' / key '{}'", tls.cert_path, tls.key_path))?;
+    if tls.reload_interval_seconds > 0 {
+        watch_certificate(tls.clone(), rustls_config.clone());
+    }
+
+    let handle = Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            stop_accepting.notified().await;
+            handle.graceful_shutdown(None);
+        }
+    });
+
+    let addr: SocketAddr = addr.parse().with_context(|| format!("Invalid listen address '{}'", addr))?;
+    Ok(axum_server::bind(addr)
+        .acceptor(ClientCertAcceptor(RustlsAcceptor::new(rustls_config)))
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>()))
+}
+
+/// Certificate, key and a verifier for client certificates signed by
+/// `client_ca_path`. Clients without a certificate are still let in; the
+/// routes needing one check for it.
+fn server_config(tls: &TlsConfig) -> Result<ServerConfig> {
+    let chain = CertificateDer::pem_file_iter(&tls.cert_path)?.collect::<Result<Vec<_>, _>>()?;
+    let key = PrivateKeyDer::from_pem_file(&tls.key_path)?;
+
+    let mut roots = RootCertStore::empty();
+    for certificate in CertificateDer::pem_file_iter(&tls.client_ca_path)
+        .with_context(|| format!("Failed to read client CA '{}'", tls.client_ca_path))?
+    {
+        roots.add(certificate?)?;
+    }
+    let verifier = WebPkiClientVerifier::builder(Arc::new(roots)).allow_unauthenticated().build()?;
+
+    let mut config = ServerConfig::builder()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(chain, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// The rustls acceptor, plus an `Option<ClientCertificate>` extension on
+/// every request: Some when the connection presented a verified certificate
+#[derive(Clone)]
+struct ClientCertAcceptor(RustlsAcceptor<DefaultAcceptor>);
+
+impl<I, S> Accept<I, S> for ClientCertAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = AddExtension<S, Option<ClientCertificate>>;
+    type Future = BoxFuture<'static, std::io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let handshake = self.0.accept(stream, service);
+        Box::pin(async move {
+            let (stream, service) = handshake.await?;
+            let certificate = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .map(|leaf| ClientCertificate::from_der(leaf));
+            Ok((stream, AddExtensionLayer::new(certificate).layer(service)))
+        })
+    }
+}
+
+/// Modification time and length of a file; a renewal changes at least one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fingerprint {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl Fingerprint {
+    fn read(path: &Path) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(Self {
+            modified: metadata.modified()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+fn fingerprints(tls: &TlsConfig) -> std::io::Result<(Fingerprint, Fingerprint)> {
+    Ok((Fingerprint::read(Path::new(&tls.cert_path))?, Fingerprint::read(Path::new(&tls.key_path))?))
+}
+
+/// Reload the certificate whenever the files change. A half-written or
+/// mismatched pair is logged and retried on the next check; the previous
+/// certificate keeps serving meanwhile.
+fn watch_certificate(tls: TlsConfig, rustls_config: RustlsConfig) {
+    tokio::spawn(async move {
+        let interval = Duration::from_secs(tls.reload_interval_seconds);
+        let mut loaded = fingerprints(&tls).ok();
+        loop {
+            tokio::time::sleep(interval).await;
+            let current = match fingerprints(&tls) {
+                Ok(current) => current,
+                Err(e) => {
+                    tracing::warn!("Failed to check TLS certificate files: {}", e);
+                    continue;
+                }
+            };
+            if loaded == Some(current) {
+                continue;
+            }
+            let reloaded = match tls.client_ca_path.is_empty() {
+                true => rustls_config.reload_from_pem_file(&tls.cert_path, &tls.key_path).await.map_err(anyhow::Error::from),
+                false => server_config(&tls).map(|config| rustls_config.reload_from_config(Arc::new(config))),
+            };
+            match reloaded {
+                Ok(()) => {
+                    tracing::info!("🔐 Reloaded TLS certificate from {}", tls.cert_path);
+                    loaded = Some(current);
+                }
+                Err(e) => tracing::warn!("Failed to reload TLS certificate, keeping the current one: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_changes_when_file_is_rewritten() {
+        let path = std::env::temp_dir().join(format!("pinpet-tls-test-{}.pem", std::process::id()));
+        std::fs::write(&path, "-----BEGIN CERTIFICATE-----\n").unwrap();
+        let before = Fingerprint::read(&path).unwrap();
+        assert_eq!(Fingerprint::read(&path).unwrap(), before);
+
+        std::fs::write(&path, "-----BEGIN CERTIFICATE-----\nrenewed\n").unwrap();
+        assert_ne!(Fingerprint::read(&path).unwrap(), before);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(Fingerprint::read(&path).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! HTTP listener on a Unix domain socket, for sidecars on the same host that
+//! should reach the API (and its private keys) without any network exposure.
+
+use anyhow::{bail, Context, Result};
+use axum::extract::ConnectInfo;
+use axum::{Extension, Router};
+use std::fs::Permissions;
+use std::future::{Future, IntoFuture};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::net::UnixListener;
+use tokio::sync::Notify;
+
+/// Peer address handlers see for socket clients: they have no IP, but are on this host
+const LOCAL_PEER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+/// Bind a socket at `path` with permissions `mode` and serve `app` on it.
+/// The returned future resolves once `stop_accepting` is notified and every
+/// connection has finished; the socket file is removed then.
+pub fn serve(
+    path: &str,
+    mode: u32,
+    app: Router,
+    stop_accepting: Arc<Notify>,
+) -> Result<impl Future<Output = std::io::Result<()>>> {
+    remove_stale_socket(Path::new(path))?;
+    let listener = bind_private(Path::new(path), mode).with_context(|| format!("Failed to bind Unix socket '{}'", path))?;
+
+    // Handlers and middleware read the client address from ConnectInfo
+    let app = app.layer(Extension(ConnectInfo(LOCAL_PEER)));
+    let server = axum::serve(listener, app)
+        .with_graceful_shutdown(async move { stop_accepting.notified().await })
+        .into_future();
+
+    let path = path.to_string();
+    Ok(async move {
+        let result = server.await;
+        if let Err(e) = std::fs::remove_file(&path) {
+            tracing::warn!("Failed to remove Unix socket '{}': {}", path, e);
+        }
+        result
+    })
+}
+
+/// Bind the socket inside a fresh 0700 directory next to `path`, so nobody
+/// can connect while it still has the umask's permissions, give it `mode`
+/// and only then move it to `path`
+fn bind_private(path: &Path, mode: u32) -> Result<UnixListener> {
+    let name = path.file_name().context("Socket path has no file name")?.to_string_lossy();
+    let staging = path.with_file_name(format!(".{}.{}", name, std::process::id()));
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&staging)
+        .with_context(|| format!("Failed to create '{}'", staging.display()))?;
+
+    let staged: PathBuf = staging.join("socket");
+    let bound = UnixListener::bind(&staged).map_err(anyhow::Error::from).and_then(|listener| {
+        std::fs::set_permissions(&staged, Permissions::from_mode(mode))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    if let Err(e) = std::fs::remove_dir_all(&staging) {
+        tracing::warn!("Failed to remove '{}': {}", staging.display(), e);
+    }
+    bound
+}
+
+/// A socket file left behind by a crashed run would make `bind` fail. Remove
+/// it unless a server still answers on it; never remove anything but a socket.
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    if !metadata.file_type().is_socket() {
+        bail!("'{}' exists and is not a Unix socket", path.display());
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        bail!("Unix socket '{}' is in use by another process", path.display());
+    }
+
+    tracing::info!("Removing stale Unix socket '{}'", path.display());
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_serves_over_the_socket_as_a_local_peer() {
+        let dir = std::env::temp_dir().join(format!("pinpet-unix-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pinpet.sock");
+        let path = path.to_str().unwrap();
+
+        // A stale socket from an earlier run is replaced; other files are not
+        drop(std::os::unix::net::UnixListener::bind(path).unwrap());
+        let regular = dir.join("regular");
+        std::fs::write(&regular, "keep").unwrap();
+        let app = || Router::new().route("/", get(|ConnectInfo(peer): ConnectInfo<SocketAddr>| async move { peer.ip().to_string() }));
+        let stop = Arc::new(Notify::new());
+        assert!(serve(regular.to_str().unwrap(), 0o600, app(), Arc::clone(&stop)).is_err());
+        let server = tokio::spawn(serve(path, 0o600, app(), Arc::clone(&stop)).unwrap());
+
+        let mode = std::fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // The directory it was bound in is gone
+        let mut entries: Vec<String> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        entries.sort();
+        assert_eq!(entries, ["pinpet.sock", "regular"]);
+
+        let mut stream = tokio::net::UnixStream::connect(path).await.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.ends_with("127.0.0.1"), "{}", response);
+
+        stop.notify_one();
+        server.await.unwrap().unwrap();
+        assert!(!Path::new(path).exists());
+        assert_eq!(std::fs::read_to_string(regular).unwrap(), "keep");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -23,7 +23,7 @@ pub fn validate_timezone(tz: &str) -> bool {
     }
     
     if tz.len() > 3 {
-        if !tz.chars().nth(3).map_or(false, |c| c == ':') {
+        if tz.chars().nth(3) != Some(':') {
             return false;
         }
         
@@ -45,4 +45,84 @@ pub fn validate_timezone(tz: &str) -> bool {
     }
     
     true
-}
\ No newline at end of file
+}
+
+/// Characters allowed in base58-encoded Solana addresses (no 0, O, I, l)
+pub const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+pub fn is_base58(value: &str) -> bool {
+    value.chars().all(|c| BASE58_ALPHABET.contains(c))
+}
+
+/// Whether `c` can appear in an address; case-insensitively either case will do
+pub fn is_feasible_base58_char(c: char, case_sensitive: bool) -> bool {
+    if case_sensitive {
+        BASE58_ALPHABET.contains(c)
+    } else {
+        BASE58_ALPHABET.contains(c.to_ascii_lowercase())
+            || BASE58_ALPHABET.contains(c.to_ascii_uppercase())
+    }
+}
+
+/// Check that `address` is a Solana public key: base58 that decodes to 32 bytes
+pub fn validate_solana_address(address: &str) -> Result<(), String> {
+    if let Some(c) = address.chars().find(|c| !BASE58_ALPHABET.contains(*c)) {
+        return Err(format!("Address contains '{}' which is not a base58 character", c));
+    }
+
+    match bs58::decode(address).into_vec() {
+        Ok(bytes) if bytes.len() == 32 => Ok(()),
+        Ok(bytes) => Err(format!("Address decodes to {} bytes, expected 32", bytes.len())),
+        Err(e) => Err(format!("Address is not valid base58: {}", e)),
+    }
+}
+
+/// Validate that a target suffix can actually appear at the end of an address
+pub fn validate_target_suffix(suffix: &str, case_sensitive: bool) -> Result<(), String> {
+    if suffix.is_empty() {
+        return Err("Target suffix must not be empty".to_string());
+    }
+
+    if let Some(c) = suffix.chars().find(|c| !is_feasible_base58_char(*c, case_sensitive)) {
+        return Err(format!(
+            "Target suffix '{}' contains '{}' which never appears in base58 addresses",
+            suffix, c
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_target_suffix() {
+        assert!(validate_target_suffix("Pet", true).is_ok());
+        assert!(validate_target_suffix("Cat", true).is_ok());
+        assert!(validate_target_suffix("Dog", true).is_ok());
+
+        assert!(validate_target_suffix("", true).is_err());
+        assert!(validate_target_suffix("P0t", true).is_err());
+        assert!(validate_target_suffix("lol", true).is_err());
+        assert!(validate_target_suffix("OIL", true).is_err());
+
+        // Either case of each letter is acceptable when matching case-insensitively
+        assert!(validate_target_suffix("lol", false).is_ok());
+        assert!(validate_target_suffix("OIL", false).is_ok());
+        assert!(validate_target_suffix("P0t", false).is_err());
+    }
+
+    #[test]
+    fn test_validate_solana_address() {
+        assert!(validate_solana_address("11111111111111111111111111111111").is_ok());
+        assert!(validate_solana_address("So11111111111111111111111111111111111111112").is_ok());
+
+        let error = validate_solana_address("So1111111111111111111111111111111111111111O").unwrap_err();
+        assert!(error.contains("'O'"), "{}", error);
+        // Too short to be a 32-byte key
+        assert!(validate_solana_address("So111111111111111111111111111112").is_err());
+        assert!(validate_solana_address("").is_err());
+    }
+}